@@ -0,0 +1,87 @@
+// Copyright 2017 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Benchmarks every plugin found in a directory, in both sync and async
+//! modes, and prints a table of the results.
+//!
+//! ```text
+//! cargo run --example bench -- [plugin dir] [graphs per plugin]
+//! ```
+//!
+//! The plugin directory defaults to `target/debug/plugins`, and each
+//! plugin attempts 10 graphs per mode by default. Sync runs which reused
+//! the plugin's solver context between graphs are shown as `Sync+ctx`.
+
+extern crate cuckoo_miner as cuckoo;
+
+use std::env;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use cuckoo::{BenchmarkMode, CuckooMiner, CuckooMinerConfig, CuckooPluginManager};
+
+const HEADER_SEED: u64 = 0;
+
+fn millis(d: Duration) -> f64 {
+	d.as_secs() as f64 * 1000.0 + d.subsec_nanos() as f64 / 1_000_000.0
+}
+
+fn main() {
+	let mut args = env::args().skip(1);
+	let plugin_dir = match args.next() {
+		Some(d) => PathBuf::from(d),
+		None => Path::new(env!("CARGO_MANIFEST_DIR")).join("target/debug/plugins"),
+	};
+	let iterations = args.next().map_or(10, |n| n.parse().expect("graphs must be a number"));
+
+	let mut plugin_manager = CuckooPluginManager::new().unwrap();
+	plugin_manager
+		.load_plugin_dir(String::from(plugin_dir.to_str().unwrap()))
+		.expect("Unable to read the plugin directory");
+	let caps = plugin_manager.get_available_plugins("").unwrap();
+
+	println!(
+		"{:<32} {:<8} {:>6} {:>10} {:>10} {:>10} {:>10} {:>8} {:>5}",
+		"plugin", "mode", "graphs", "min ms", "median ms", "mean ms", "max ms", "g/s", "sols"
+	);
+	for c in caps {
+		let mut config = CuckooMinerConfig::new();
+		config.plugin_full_path = c.full_path.clone();
+		let miner = match CuckooMiner::new(vec![config]) {
+			Ok(m) => m,
+			Err(e) => {
+				println!("{:<32} unable to load: {:?}", c.name, e);
+				continue;
+			}
+		};
+		for mode in &[BenchmarkMode::Sync, BenchmarkMode::Async] {
+			match miner.benchmark(0, iterations, HEADER_SEED, *mode) {
+				Ok(r) => println!(
+					"{:<32} {:<8} {:>6} {:>10.1} {:>10.1} {:>10.1} {:>10.1} {:>8.3} {:>5}",
+					c.name,
+					// sync runs reusing the solver context are marked
+					format!("{:?}{}", r.mode, if r.persistent_context { "+ctx" } else { "" }),
+					r.graphs,
+					millis(r.min_graph_time),
+					millis(r.median_graph_time),
+					millis(r.mean_graph_time),
+					millis(r.max_graph_time),
+					r.graphs_per_second,
+					r.solutions_found
+				),
+				Err(e) => println!("{:<32} {:<8} failed: {:?}", c.name, format!("{:?}", mode), e),
+			}
+		}
+	}
+}
@@ -0,0 +1,347 @@
+// Copyright 2017 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A command line miner, showing how the pieces of the API fit together:
+//! finding plugins with the plugin manager, building a miner, then either
+//! benchmarking or self-testing the plugins, or mining a header until
+//! stopped, printing stats as it goes and any solutions found. Doubles as
+//! a quick smoke test for a new plugin.
+//!
+//! ```text
+//! cargo run --example cuckoo-miner -- [options] [plugin]
+//! ```
+//!
+//! Run with `--help` for the options. Ctrl-C shuts the miner down
+//! through its shutdown flag, printing the solutions found while
+//! stopping.
+
+extern crate cuckoo_miner as cuckoo;
+extern crate libc;
+
+use std::env;
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use cuckoo::{BenchmarkMode, CuckooMinerBuilder, CuckooMinerError, CuckooMinerSolution, CuckooPluginCapabilities,
+	CuckooPluginManager, Header, PluginLibrary, SelfTestReport, to_hex};
+
+// The header mined by default, the same as the tests use
+const SAMPLE_PRE_HEADER: &str = "00000000000000118e0fe6bcfaa76c6795592339f27b6d330d8f9c4ac8e86171a66357d1\
+    d0fce808000000005971f14f0000000000000000000000000000000000000000000000000000000000000000\
+    3e1fcdd453ce51ffbb16dd200aeb9ef7375aec196e97094868428a7325e4a19b00";
+const SAMPLE_POST_HEADER: &str = "010a020364";
+
+const USAGE: &str = "Usage: cuckoo-miner [options] [plugin]
+
+Mines with the plugins whose file names match `plugin`, a regular
+expression, or with those picked by --auto.
+
+Options:
+    --auto                 Mine with the CUDA plugins if any have a usable
+                           device, otherwise the CPU plugin searching the
+                           largest graphs
+    --plugin-dir DIR       Look for plugins in DIR, rather than the plugin
+                           search directories
+    -p NAME=VALUE          Set a plugin parameter, e.g. -p NUM_THREADS=8.
+                           May be given more than once.
+    --bench N              Benchmark N graphs per plugin instead of mining
+    --self-test            Check each plugin finds a valid solution to a
+                           known header instead of mining
+    --duration SECS        Stop mining after SECS seconds, rather than on
+                           Ctrl-C
+    --stats-interval SECS  Print stats every SECS seconds [default: 5]
+    --pre-header HEX       The header before the nonce [default: a test
+                           header]
+    --post-header HEX      The header after the nonce
+    --difficulty N         Only print solutions of at least this difficulty
+    -h, --help             Print this message";
+
+// How long plugins are given to stop on Ctrl-C
+const SHUTDOWN_DEADLINE: Duration = Duration::from_secs(5);
+
+// How long each plugin is given to solve its self-test header
+const SELF_TEST_TIMEOUT: Duration = Duration::from_secs(120);
+
+// Set by the Ctrl-C handler, which mustn't do more than store to an atomic
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn on_interrupt(_: libc::c_int) {
+	INTERRUPTED.store(true, Ordering::SeqCst);
+}
+
+struct Options {
+	plugin: Option<String>,
+	auto: bool,
+	plugin_dir: Option<String>,
+	parameters: Vec<(String, u32)>,
+	bench: Option<u32>,
+	self_test: bool,
+	duration: Option<Duration>,
+	stats_interval: Duration,
+	pre_header: String,
+	post_header: String,
+	difficulty: u64,
+}
+
+fn fail(message: &str) -> ! {
+	eprintln!("{}\n\n{}", message, USAGE);
+	process::exit(2);
+}
+
+fn parse_options() -> Options {
+	let mut options = Options {
+		plugin: None,
+		auto: false,
+		plugin_dir: None,
+		parameters: Vec::new(),
+		bench: None,
+		self_test: false,
+		duration: None,
+		stats_interval: Duration::from_secs(5),
+		pre_header: String::from(SAMPLE_PRE_HEADER),
+		post_header: String::from(SAMPLE_POST_HEADER),
+		difficulty: 0,
+	};
+	let mut args = env::args().skip(1);
+	while let Some(arg) = args.next() {
+		let mut value = |name: &str| args.next().unwrap_or_else(|| fail(&format!("{} needs a value", name)));
+		let number = |name: &str, v: String| v.parse::<u64>().unwrap_or_else(|_| fail(&format!("{} must be a number", name)));
+		match arg.as_str() {
+			"-h" | "--help" => {
+				println!("{}", USAGE);
+				process::exit(0);
+			}
+			"--auto" => options.auto = true,
+			"--plugin-dir" => options.plugin_dir = Some(value(&arg)),
+			"-p" => {
+				let p = value(&arg);
+				let parameter = p.find('=').and_then(|i| p[i + 1..].parse().ok().map(|v| (String::from(&p[..i]), v)));
+				match parameter {
+					Some(p) => options.parameters.push(p),
+					None => fail(&format!("-p {} isn't NAME=VALUE", p)),
+				}
+			}
+			"--bench" => options.bench = Some(number(&arg, value(&arg)) as u32),
+			"--self-test" => options.self_test = true,
+			"--duration" => options.duration = Some(Duration::from_secs(number(&arg, value(&arg)))),
+			"--stats-interval" => options.stats_interval = Duration::from_secs(number(&arg, value(&arg)).max(1)),
+			"--pre-header" => options.pre_header = value(&arg),
+			"--post-header" => options.post_header = value(&arg),
+			"--difficulty" => options.difficulty = number(&arg, value(&arg)),
+			a if a.starts_with('-') => fail(&format!("Unknown option {}", a)),
+			a if options.plugin.is_none() => options.plugin = Some(String::from(a)),
+			a => fail(&format!("Only one plugin filter can be given, not {}", a)),
+		}
+	}
+	if options.plugin.is_some() == options.auto {
+		fail("Give either a plugin or --auto");
+	}
+	if let Err(e) = Header::from_hex(&options.pre_header, &options.post_header) {
+		fail(&format!("Invalid header: {:?}", e));
+	}
+	options
+}
+
+// Picks the plugins to mine with for --auto: every CUDA plugin with a
+// usable device, or failing that the CPU plugin searching the largest
+// graphs
+fn auto_select(caps: Vec<CuckooPluginCapabilities>) -> Vec<CuckooPluginCapabilities> {
+	let (cuda, cpu): (Vec<_>, Vec<_>) = caps.into_iter().partition(|c| c.capabilities.is_cuda);
+	let cuda: Vec<_> = cuda.into_iter().filter(|c| c.devices.iter().any(|d| d.usable)).collect();
+	if !cuda.is_empty() {
+		return cuda;
+	}
+	cpu.into_iter()
+		.fold(None, |best: Option<CuckooPluginCapabilities>, c| match best {
+			Some(ref b) if b.capabilities.edge_bits >= c.capabilities.edge_bits => best.clone(),
+			_ => Some(c),
+		})
+		.into_iter()
+		.collect()
+}
+
+fn find_plugins(options: &Options) -> Result<Vec<CuckooPluginCapabilities>, CuckooMinerError> {
+	let mut manager = CuckooPluginManager::new()?;
+	match options.plugin_dir {
+		Some(ref d) => manager.load_plugin_dir(d.clone())?,
+		None => manager.load_search_dirs()?,
+	}
+	match options.plugin {
+		Some(ref filter) => manager.get_available_plugins(filter),
+		None => Ok(auto_select(manager.get_available_plugins("")?)),
+	}
+}
+
+fn millis(d: Duration) -> f64 {
+	d.as_secs() as f64 * 1000.0 + d.subsec_nanos() as f64 / 1_000_000.0
+}
+
+fn print_solution(s: &CuckooMinerSolution) {
+	let proof: Vec<String> = s.solution_nonces.iter().map(|n| format!("{:x}", n)).collect();
+	println!(
+		"Solution from {}: nonce {}, difficulty {}, hash {}",
+		s.plugin_name.as_ref().map(|n| n.as_str()).unwrap_or("?"),
+		s.nonce,
+		s.difficulty(),
+		to_hex(&s.hash())
+	);
+	println!("  proof {}", proof.join(" "));
+	if let Some(ref m) = s.metadata {
+		let known = |v: Option<String>| v.unwrap_or_else(|| String::from("?"));
+		println!(
+			"  found by device {} in {} ms, attempt {}",
+			known(m.device_id.clone()),
+			known(m.graph_time_ms.map(|t| t.to_string())),
+			known(m.attempt_index.map(|a| a.to_string()))
+		);
+	}
+}
+
+// Self-tests a plugin with the parameters given on the command line
+fn self_test(plugin: &CuckooPluginCapabilities, options: &Options) -> Result<SelfTestReport, CuckooMinerError> {
+	let library = PluginLibrary::new(&plugin.full_path)?;
+	for &(ref name, value) in &options.parameters {
+		library.set_parameter(name, value)?;
+	}
+	let report = library.self_test(SELF_TEST_TIMEOUT);
+	library.unload();
+	report
+}
+
+fn main() {
+	let options = parse_options();
+	let plugins = match find_plugins(&options) {
+		Ok(ref p) if p.is_empty() => fail("No plugins were found to mine with"),
+		Ok(p) => p,
+		Err(e) => fail(&format!("Unable to find plugins: {:?}", e)),
+	};
+
+	if options.self_test {
+		let mut failed = false;
+		for p in &plugins {
+			match self_test(p, &options) {
+				Ok(ref r) if r.passed => println!(
+					"{}: passed at cuckoo {} ({:?} mode) in {:.1} ms",
+					p.name,
+					r.cuckoo_size,
+					r.mode,
+					millis(r.graph_time)
+				),
+				Ok(r) => {
+					failed = true;
+					println!("{}: FAILED: {}", p.name, r.failure.unwrap_or_default());
+				}
+				Err(e) => {
+					failed = true;
+					println!("{}: unable to self-test: {:?}", p.name, e);
+				}
+			}
+		}
+		process::exit(if failed { 1 } else { 0 });
+	}
+
+	let mut builder = CuckooMinerBuilder::new().stats_interval(options.stats_interval);
+	for p in &plugins {
+		println!("Using {} ({})", p.name, p.full_path);
+		builder = builder.plugin(&p.full_path);
+		for &(ref name, value) in &options.parameters {
+			builder = builder.parameter(name, value);
+		}
+	}
+	let miner = match builder.build() {
+		Ok(m) => m,
+		Err(e) => fail(&format!("Unable to set up the plugins: {:?}", e)),
+	};
+
+	if let Some(graphs) = options.bench {
+		for (i, p) in plugins.iter().enumerate() {
+			match miner.benchmark(i, graphs, 0, BenchmarkMode::Sync) {
+				Ok(r) => println!(
+					"{}: {} graphs, median {:.1} ms, mean {:.1} ms, {:.3} graphs/s, {} solutions",
+					p.name,
+					r.graphs,
+					millis(r.median_graph_time),
+					millis(r.mean_graph_time),
+					r.graphs_per_second,
+					r.solutions_found
+				),
+				Err(e) => println!("{}: benchmark failed: {:?}", p.name, e),
+			}
+		}
+		return;
+	}
+
+	unsafe {
+		libc::signal(libc::SIGINT, on_interrupt as extern "C" fn(libc::c_int) as libc::sighandler_t);
+	}
+	let handle = match miner.notify(1, &options.pre_header, &options.post_header, options.difficulty, false) {
+		Ok(h) => h,
+		Err(e) => fail(&format!("Unable to start mining: {:?}", e)),
+	};
+	let start = Instant::now();
+	let mut next_stats = start + options.stats_interval;
+	loop {
+		if INTERRUPTED.load(Ordering::SeqCst) {
+			println!("Interrupted, shutting down");
+			break;
+		}
+		if options.duration.map_or(false, |d| start.elapsed() >= d) {
+			break;
+		}
+		for s in handle.get_solutions().unwrap_or_default() {
+			print_solution(&s);
+		}
+		if Instant::now() >= next_stats {
+			next_stats += options.stats_interval;
+			println!(
+				"[{}s] {:.3} graphs/s, {} solutions, {} cycles",
+				start.elapsed().as_secs(),
+				handle.graphs_per_second(),
+				handle.solutions_found(),
+				handle.cycles_found()
+			);
+			for (i, stats) in handle.get_all_stats().into_iter().enumerate() {
+				match stats {
+					Ok(devices) => for d in devices {
+						let state = match (d.has_errored, d.in_use) {
+							(true, _) => format!("errored: {}", d.error_reason),
+							(false, 0) => String::from("idle"),
+							_ => String::from("mining"),
+						};
+						println!(
+							"  {} device {} ({}): {} graphs, {}",
+							plugins[i].name,
+							d.device_id,
+							d.device_name,
+							d.iterations_completed,
+							state
+						);
+					},
+					Err(e) => println!("  {}: unable to read stats: {:?}", plugins[i].name, e),
+				}
+			}
+		}
+		thread::sleep(Duration::from_millis(100));
+	}
+	for s in handle.shutdown(SHUTDOWN_DEADLINE) {
+		print_solution(&s);
+	}
+	println!(
+		"Mined for {}s, {} solutions found",
+		start.elapsed().as_secs(),
+		handle.solutions_found()
+	);
+}
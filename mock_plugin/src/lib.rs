@@ -0,0 +1,1210 @@
+// Copyright 2017 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A mock cuckoo-miner plugin, implementing the whole plugin interface in
+//! Rust without searching any graphs. Headers with a known solution get
+//! that solution back, and every other header gets none, so tests can
+//! drive the miner deterministically and without the real plugins.
+//!
+//! Besides the standard parameters, the mock takes `MOCK_` parameters
+//! which make it slow, or make it misbehave:
+//!
+//! * `MOCK_DEVICES` the number of devices it reports
+//! * `MOCK_DELAY_MS` how long each graph takes, per device. Calls through
+//! `cuckoo_call_cancellable` give up within a millisecond of being
+//! cancelled
+//! * `MOCK_ERROR_AFTER` how many graphs a device searches before it
+//! reports an error and stops, per device, or 0 to never fail
+//! * `MOCK_IGNORE_STOP_MS` how long a request to stop processing is
+//! ignored for
+//! * `MOCK_START_ERROR` a code for `cuckoo_start_processing` to fail with
+//! * `MOCK_BAD_STATS` whether the stats are malformed JSON
+//! * `MOCK_SOLVE_ALL` whether every header gets a canned solution, which
+//! won't verify, rather than only the known ones
+//! * `MOCK_NAME_LENGTH` a length to pad device names to, so the stats and
+//! device list overflow small buffers
+//! * `MOCK_BAD_PROOF` makes solutions malformed: 1 for an edge nonce
+//! outside the graph, 2 for a repeated edge nonce
+//! * `MOCK_PROOF_SIZE` the number of nonces in each proof, as declared in
+//! the mock's capabilities. Only canned solutions of that length are
+//! given.
+//! * `MOCK_MEMORY` the device memory a device reports using, per device.
+//! This can exceed u32::MAX, so is set through the 64-bit calls.
+//! * `MOCK_HOST_MEMORY` the host memory the mock reports using, through
+//! the 64-bit calls as well
+//! * `MOCK_NONCE` a nonce to report every solution as being for, in
+//! place of the header's, or 0 to report the header's
+//! * `MOCK_ID_BY_NONCE` whether solutions are reported under the id of
+//! the last job pushed with their nonce, as a plugin keeping track of
+//! its work by nonce would, rather than the id of the job they were
+//! found for
+//! * `MOCK_NO_CONTEXT` whether `cuckoo_create_context` fails, returning
+//! null
+//! * `MOCK_NO_QUEUE_DEPTHS` whether `cuckoo_queue_depths` fails, as
+//! though the mock couldn't tell
+//!
+//! `MOCK_CONTEXTS` can be read but not set, and is the number of solver
+//! contexts created and not yet destroyed.
+//!
+//! `EDGE_BITS` can also be set, and only canned solutions for graphs of
+//! that size are given. Above 32 edge bits, the solutions' nonces don't
+//! fit in a u32, so they're only returned by `cuckoo_call_64` and
+//! `cuckoo_read_from_output_queue_64`, and the 32-bit calls find none.
+//!
+//! Once the host registers a logger through `cuckoo_set_logger`, the mock
+//! logs when processing starts, when a device finds a solution, and when
+//! a device fails, the last two from its worker threads.
+//!
+//! The plugin's state is global, so each copy of the library loaded, e.g.
+//! through `PluginLibrary::load_instance`, is a separate mock.
+
+#[macro_use]
+extern crate lazy_static;
+
+use std::collections::{HashMap, VecDeque};
+use std::os::raw::c_void;
+use std::sync::Mutex;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use std::{cmp, ptr, slice};
+
+/// The plugin interface version the mock implements
+pub const API_VERSION: u32 = (1 << 16) | 1;
+
+/// Device id addressing every device when setting a parameter
+pub const ALL_DEVICES: u32 = 0xffff_ffff;
+
+/// The most devices the mock can be asked to report
+pub const MAX_DEVICES: u64 = 16;
+
+/// The log2 of the edges in the graphs searched, until it's set
+pub const EDGE_BITS: u32 = 15;
+
+/// The longest parameter name accepted
+pub const MAX_NAME_LENGTH: usize = 64;
+
+/// The longest header accepted on the input queue
+pub const MAX_DATA_LENGTH: usize = 256;
+
+// How long an idle device waits before checking the queue again, and the
+// longest it sleeps while a graph is "searched" before checking whether
+// it's been told to stop
+const IDLE_WAIT: Duration = Duration::from_millis(1);
+
+/// Log levels passed to the host's logger, as in the log crate
+pub const LOG_ERROR: u32 = 1;
+pub const LOG_INFO: u32 = 3;
+pub const LOG_DEBUG: u32 = 4;
+
+/// The host's logger, called with the context it was registered with
+pub type Logger = extern "C" fn(*mut c_void, u32, *const u8, u32);
+
+/// A parameter the mock accepts
+
+pub struct Parameter {
+	/// The parameter's name
+	pub name: &'static str,
+	/// What it does
+	pub description: &'static str,
+	/// Its value until it's set
+	pub default_value: u64,
+	/// The smallest value accepted
+	pub min_value: u64,
+	/// The largest value accepted
+	pub max_value: u64,
+	/// Whether each device has its own value
+	pub per_device: bool,
+}
+
+/// Every parameter the mock accepts
+pub const PARAMETERS: &[Parameter] = &[
+	Parameter {
+		name: "NUM_THREADS",
+		description: "Threads per device, which are only recorded",
+		default_value: 1,
+		min_value: 1,
+		max_value: 32,
+		per_device: true,
+	},
+	Parameter {
+		name: "USE_DEVICE",
+		description: "Whether the device mines",
+		default_value: 1,
+		min_value: 0,
+		max_value: 1,
+		per_device: true,
+	},
+	Parameter {
+		name: "QUEUE_SIZE",
+		description: "Headers the input queue holds",
+		default_value: 100,
+		min_value: 1,
+		max_value: 10000,
+		per_device: false,
+	},
+	Parameter {
+		name: "OUTPUT_QUEUE_SIZE",
+		description: "Solutions the output queue holds",
+		default_value: 100,
+		min_value: 1,
+		max_value: 10000,
+		per_device: false,
+	},
+	Parameter {
+		name: "EDGE_BITS",
+		description: "Log2 of the edges in each graph",
+		default_value: EDGE_BITS as u64,
+		min_value: 1,
+		max_value: 63,
+		per_device: false,
+	},
+	Parameter {
+		name: "CPU_AFFINITY_MASK",
+		description: "Cores threads may run on, or 0 for any, which is only recorded",
+		default_value: 0,
+		min_value: 0,
+		max_value: 0xffff_ffff_ffff_ffff,
+		per_device: false,
+	},
+	Parameter {
+		name: "EXPECTED_MEMORY_MB",
+		description: "Host memory expected once processing starts, or 0 if unknown",
+		default_value: 0,
+		min_value: 0,
+		max_value: 0xffff_ffff,
+		per_device: false,
+	},
+	Parameter {
+		name: "THREAD_PRIORITY",
+		description: "Priority of threads, which is only recorded",
+		default_value: 2,
+		min_value: 0,
+		max_value: 3,
+		per_device: false,
+	},
+	Parameter {
+		name: "MOCK_DEVICES",
+		description: "Devices reported",
+		default_value: 1,
+		min_value: 1,
+		max_value: MAX_DEVICES,
+		per_device: false,
+	},
+	Parameter {
+		name: "MOCK_DELAY_MS",
+		description: "Time each graph takes",
+		default_value: 0,
+		min_value: 0,
+		max_value: 60_000,
+		per_device: true,
+	},
+	Parameter {
+		name: "MOCK_ERROR_AFTER",
+		description: "Graphs before the device fails",
+		default_value: 0,
+		min_value: 0,
+		max_value: 0xffff_ffff,
+		per_device: true,
+	},
+	Parameter {
+		name: "MOCK_IGNORE_STOP_MS",
+		description: "Time a stop is ignored for",
+		default_value: 0,
+		min_value: 0,
+		max_value: 3_600_000,
+		per_device: false,
+	},
+	Parameter {
+		name: "MOCK_START_ERROR",
+		description: "Code starting fails with",
+		default_value: 0,
+		min_value: 0,
+		max_value: 255,
+		per_device: false,
+	},
+	Parameter {
+		name: "MOCK_BAD_STATS",
+		description: "Whether stats are malformed",
+		default_value: 0,
+		min_value: 0,
+		max_value: 1,
+		per_device: false,
+	},
+	Parameter {
+		name: "MOCK_SOLVE_ALL",
+		description: "Whether every header is solved",
+		default_value: 0,
+		min_value: 0,
+		max_value: 1,
+		per_device: false,
+	},
+	Parameter {
+		name: "MOCK_BAD_PROOF",
+		description: "How solutions are malformed",
+		default_value: 0,
+		min_value: 0,
+		max_value: 2,
+		per_device: false,
+	},
+	Parameter {
+		name: "MOCK_PROOF_SIZE",
+		description: "Nonces in each proof",
+		default_value: 42,
+		min_value: 1,
+		max_value: 64,
+		per_device: false,
+	},
+	Parameter {
+		name: "MOCK_NAME_LENGTH",
+		description: "Length device names are padded to",
+		default_value: 0,
+		min_value: 0,
+		max_value: 4096,
+		per_device: false,
+	},
+	Parameter {
+		name: "MOCK_MEMORY",
+		description: "Memory used per device",
+		default_value: 0,
+		min_value: 0,
+		max_value: 1 << 40,
+		per_device: true,
+	},
+	Parameter {
+		name: "MOCK_HOST_MEMORY",
+		description: "Host memory used",
+		default_value: 0,
+		min_value: 0,
+		max_value: 1 << 40,
+		per_device: false,
+	},
+	Parameter {
+		name: "MOCK_NONCE",
+		description: "Nonce solutions are reported for",
+		default_value: 0,
+		min_value: 0,
+		max_value: 0xffff_ffff,
+		per_device: false,
+	},
+	Parameter {
+		name: "MOCK_ID_BY_NONCE",
+		description: "Whether solutions are reported under the last id pushed with their nonce",
+		default_value: 0,
+		min_value: 0,
+		max_value: 1,
+		per_device: false,
+	},
+	Parameter {
+		name: "MOCK_NO_CONTEXT",
+		description: "Whether creating a solver context fails",
+		default_value: 0,
+		min_value: 0,
+		max_value: 1,
+		per_device: false,
+	},
+	Parameter {
+		name: "MOCK_NO_QUEUE_DEPTHS",
+		description: "Whether reading the queue depths fails",
+		default_value: 0,
+		min_value: 0,
+		max_value: 1,
+		per_device: false,
+	},
+	Parameter {
+		name: "MOCK_PUSH_NOTHING",
+		description: "Whether batches are pushed without any headers being accepted",
+		default_value: 0,
+		min_value: 0,
+		max_value: 1,
+		per_device: false,
+	},
+];
+
+/// A header the mock knows the solution to
+
+pub struct CannedSolution {
+	/// The header, in hex
+	pub header: &'static str,
+	/// The cuckoo size the solution is for
+	pub cuckoo_size: u32,
+	/// The solution
+	pub nonces: &'static [u64],
+}
+
+/// The headers the mock solves, with real solutions at cuckoo 16 so they
+/// verify. The first of the graph and proof size asked for is also given
+/// for every header when `MOCK_SOLVE_ALL` is set.
+pub const CANNED_SOLUTIONS: &[CannedSolution] = &[
+	// a 32 byte hash
+	CannedSolution {
+		header: "c008b9ff7292fdacef0efbdff73d1db66674ff3b6dea6cca670c85b6a110f0b2",
+		cuckoo_size: 16,
+		nonces: &[
+			1614, 2703, 4264, 4807, 6003, 7120, 8031, 8332, 8369, 8572,
+			10617, 10777, 11836, 14187, 14468, 14944, 15488, 15855, 15862, 16398,
+			16488, 17873, 18201, 18731, 18756, 19711, 19984, 20071, 22332, 24056,
+			24090, 24754, 25484, 26009, 26887, 27085, 27627, 28594, 29488, 30213,
+			31641, 32738,
+		],
+	},
+	// the hash of a grin header, with nonce 56
+	CannedSolution {
+		header: "07af6396156b421dcf889a34a481fc6fd0641773150f5c8b513d2b2242825ee3",
+		cuckoo_size: 16,
+		nonces: &[
+			219, 521, 1028, 1059, 2358, 2674, 2789, 2896, 3854, 4796,
+			5455, 7940, 8068, 8109, 8151, 9872, 10165, 10353, 10660, 11280,
+			11594, 12008, 12103, 14227, 15011, 15404, 15446, 15449, 18315, 18356,
+			20541, 22292, 22652, 23965, 24148, 24526, 25081, 25467, 26310, 26502,
+			26612, 32263,
+		],
+	},
+	// two 32 byte headers sharing nonce 56, between the 8 byte prefixes
+	// and the zeroes after
+	CannedSolution {
+		header: "0000000000000045000000000000003800000000000000000000000000000000",
+		cuckoo_size: 16,
+		nonces: &[
+			2043, 2149, 2292, 3593, 4135, 4859, 5375, 9016, 9320, 9660,
+			9734, 10381, 11805, 12111, 13852, 13886, 14368, 14899, 15090, 15844,
+			15856, 16285, 16841, 17175, 19879, 20892, 22551, 23216, 23260, 23366,
+			26069, 27869, 27977, 27994, 28619, 30165, 30192, 30216, 30653, 30852,
+			31642, 32282,
+		],
+	},
+	CannedSolution {
+		header: "00000000000000a0000000000000003800000000000000000000000000000000",
+		cuckoo_size: 16,
+		nonces: &[
+			266, 1050, 1157, 1766, 2058, 2505, 2677, 3627, 4473, 5359,
+			6852, 6966, 8148, 9689, 11365, 12957, 13192, 14634, 14768, 16587,
+			16758, 17169, 17212, 17349, 17863, 20521, 20763, 20949, 21141, 21184,
+			21267, 21432, 22143, 22885, 23102, 24880, 26044, 26787, 29232, 29731,
+			31175, 31904,
+		],
+	},
+	// a 32 byte hash with a 12-cycle
+	CannedSolution {
+		header: "000e000000000000000000000000000000000000000000000000000000000012",
+		cuckoo_size: 16,
+		nonces: &[
+			739, 2105, 3492, 6431, 9143, 9732, 15584, 19988, 21316, 28431,
+			28997, 29639,
+		],
+	},
+	// a 32 byte hash, with nonces too big for a u32 at 40 edge bits. This
+	// isn't a real cycle, so won't verify
+	CannedSolution {
+		header: "0028000000000000000000000000000000000000000000000000000000000029",
+		cuckoo_size: 41,
+		nonces: &[
+			59602490257, 64261236989, 69277281641, 180341550461, 189255149691, 226092516247,
+			230140327644, 230486493922, 270719719242, 279660798720, 306438998373, 325671254997,
+			344258043877, 350489710499, 364924463807, 372616235987, 451390609670, 542069801734,
+			573092164861, 576223994975, 579101676094, 583590998906, 584238525883, 641919827914,
+			652379084937, 708505498145, 735652418662, 753822818061, 770125198288, 784345123092,
+			808810046096, 812724089554, 815597889918, 824117248069, 842364945140, 863910366229,
+			865271933087, 872636648149, 903836191478, 909857257787, 938281416271, 1077535352614,
+		],
+	},
+];
+
+struct Job {
+	id: u32,
+	data: Vec<u8>,
+	nonce: [u8; 8],
+}
+
+struct Solution {
+	id: u32,
+	nonce: [u8; 8],
+	cuckoo_size: u32,
+	nonces: Vec<u64>,
+}
+
+#[derive(Default)]
+struct Device {
+	last_start_time: u64,
+	last_end_time: u64,
+	last_solution_time: u64,
+	iterations_completed: u32,
+	error: Option<String>,
+}
+
+struct Mock {
+	values: HashMap<&'static str, Vec<u64>>,
+	input: VecDeque<Job>,
+	output: VecDeque<Solution>,
+	// the id each nonce was last pushed with
+	last_ids: HashMap<[u8; 8], u32>,
+	devices: Vec<Device>,
+	stop_requested: Option<Instant>,
+	workers: Vec<JoinHandle<()>>,
+	running: usize,
+	// solver contexts created and not yet destroyed
+	contexts: usize,
+	// the host's logger, and its context as an address so the mock can
+	// be shared between threads
+	logger: Option<(Logger, usize)>,
+}
+
+impl Mock {
+	fn new() -> Mock {
+		Mock {
+			values: PARAMETERS
+				.iter()
+				.map(|p| {
+					let n = if p.per_device { MAX_DEVICES as usize } else { 1 };
+					(p.name, vec![p.default_value; n])
+				})
+				.collect(),
+			input: VecDeque::new(),
+			output: VecDeque::new(),
+			last_ids: HashMap::new(),
+			devices: (0..MAX_DEVICES).map(|_| Device::default()).collect(),
+			stop_requested: None,
+			workers: Vec::new(),
+			running: 0,
+			contexts: 0,
+			logger: None,
+		}
+	}
+
+	// Sends a message to the host's logger, if it's registered one
+	fn log(&self, level: u32, message: &str) {
+		if let Some((logger, context)) = self.logger {
+			logger(context as *mut c_void, level, message.as_ptr(), message.len() as u32);
+		}
+	}
+
+	fn value(&self, name: &str, device: usize) -> u64 {
+		let values = &self.values[name];
+		values[cmp::min(device, values.len() - 1)]
+	}
+
+	fn device_count(&self) -> usize {
+		self.value("MOCK_DEVICES", 0) as usize
+	}
+
+	fn proof_size(&self) -> usize {
+		self.value("MOCK_PROOF_SIZE", 0) as usize
+	}
+
+	fn edge_bits(&self) -> u32 {
+		self.value("EDGE_BITS", 0) as u32
+	}
+
+	// Whether the workers should stop, once any time a stop is to be
+	// ignored for has passed
+	fn should_quit(&self) -> bool {
+		match self.stop_requested {
+			Some(t) => t.elapsed() >= Duration::from_millis(self.value("MOCK_IGNORE_STOP_MS", 0)),
+			None => false,
+		}
+	}
+
+	// Checks a parameter call, returning the parameter or the plugin's
+	// error code
+	fn parameter(&self, name: &[u8], device: u32, setting: bool) -> Result<&'static Parameter, u32> {
+		if name.len() > MAX_NAME_LENGTH {
+			return Err(4);
+		}
+		let p = match PARAMETERS.iter().find(|p| p.name.as_bytes() == name) {
+			Some(p) => p,
+			None => return Err(1),
+		};
+		if (device as usize) < self.device_count() || (setting && device == ALL_DEVICES) {
+			Ok(p)
+		} else {
+			Err(5)
+		}
+	}
+
+	fn get(&self, name: &[u8], device: u32) -> Result<u64, u32> {
+		if name == b"MOCK_CONTEXTS" {
+			return Ok(self.contexts as u64);
+		}
+		let p = self.parameter(name, device, false)?;
+		Ok(self.value(p.name, device as usize))
+	}
+
+	fn set(&mut self, name: &[u8], device: u32, value: u64) -> u32 {
+		let p = match self.parameter(name, device, true) {
+			Ok(p) => p,
+			Err(e) => return e,
+		};
+		if value < p.min_value || value > p.max_value {
+			return 2;
+		}
+		let values = self.values.get_mut(p.name).unwrap();
+		if device == ALL_DEVICES || !p.per_device {
+			for v in values.iter_mut() {
+				*v = value;
+			}
+		} else {
+			values[device as usize] = value;
+		}
+		0
+	}
+
+	fn device_name(&self, device: usize) -> String {
+		let mut name = format!("Mock Device {}", device);
+		while name.len() < self.value("MOCK_NAME_LENGTH", 0) as usize {
+			name.push('.');
+		}
+		name
+	}
+
+	fn stats_json(&self) -> String {
+		if self.value("MOCK_BAD_STATS", 0) == 1 {
+			return String::from("[{\"device_id\":");
+		}
+		let stats: Vec<String> = (0..self.device_count())
+			.map(|i| {
+				let d = &self.devices[i];
+				let mut memory = match self.value("MOCK_MEMORY", i) {
+					0 => String::new(),
+					m => format!(",\"device_memory_bytes\":{}", m),
+				};
+				let host = self.value("MOCK_HOST_MEMORY", 0);
+				if host > 0 {
+					memory.push_str(&format!(",\"host_memory_bytes\":{}", host));
+				}
+				format!(
+					"{{\"device_id\":\"{}\",\"cuckoo_size\":\"16\",\"device_name\":\"{}\",\"in_use\":{},\
+					 \"has_errored\":{},\"error_reason\":\"{}\",\"last_start_time\":{},\"last_end_time\":{},\
+					 \"last_solution_time\":{},\"iterations_completed\":{}{}}}",
+					i,
+					self.device_name(i),
+					self.value("USE_DEVICE", i),
+					d.error.is_some() as u32,
+					d.error.clone().unwrap_or_default(),
+					d.last_start_time,
+					d.last_end_time,
+					d.last_solution_time,
+					d.iterations_completed,
+					memory
+				)
+			})
+			.collect();
+		format!("[{}]", stats.join(","))
+	}
+
+	fn devices_json(&self) -> String {
+		let devices: Vec<String> = (0..self.device_count())
+			.map(|i| {
+				format!(
+					"{{\"device_id\":{},\"name\":\"{}\",\"total_memory\":{},\"usable\":true}}",
+					i,
+					self.device_name(i),
+					1u64 << 30
+				)
+			})
+			.collect();
+		format!("[{}]", devices.join(","))
+	}
+}
+
+lazy_static! {
+	static ref MOCK: Mutex<Mock> = Mutex::new(Mock::new());
+}
+
+fn now_ms() -> u64 {
+	let t = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or(Duration::new(0, 0));
+	t.as_secs() * 1000 + (t.subsec_nanos() / 1_000_000) as u64
+}
+
+fn from_hex(hex: &str) -> Vec<u8> {
+	(0..hex.len() / 2)
+		.map(|i| u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).unwrap())
+		.collect()
+}
+
+/// The canned solution of the given edge bits and proof size for a
+/// header, if there is one
+pub fn solve(
+	header: &[u8],
+	edge_bits: u32,
+	proof_size: usize,
+	solve_all: bool,
+) -> Option<&'static CannedSolution> {
+	let mut sized = CANNED_SOLUTIONS
+		.iter()
+		.filter(|s| s.cuckoo_size == edge_bits + 1 && s.nonces.len() == proof_size);
+	match sized.clone().find(|s| from_hex(s.header) == header) {
+		Some(s) => Some(s),
+		None if solve_all => sized.next(),
+		None => None,
+	}
+}
+
+// Applies MOCK_BAD_PROOF to a solution
+fn malformed(nonces: &[u64], edge_bits: u32, bad_proof: u64) -> Vec<u64> {
+	let mut nonces = nonces.to_vec();
+	let last = nonces.len() - 1;
+	match bad_proof {
+		1 => nonces[last] = 1 << edge_bits,
+		2 => nonces[1] = nonces[0],
+		_ => {}
+	}
+	nonces
+}
+
+// Writes a string and its terminating null to a buffer of `*len` bytes,
+// setting `*len` to the string's length, or returns 3 without writing
+// anything if it doesn't fit
+unsafe fn write_buffer(s: &str, buf: *mut u8, len: *mut u32) -> u32 {
+	let bytes = s.as_bytes();
+	if bytes.len() + 1 > *len as usize {
+		return 3;
+	}
+	ptr::copy_nonoverlapping(bytes.as_ptr(), buf, bytes.len());
+	*buf.offset(bytes.len() as isize) = 0;
+	*len = bytes.len() as u32;
+	0
+}
+
+// Waits out the device's delay, returning false if it's told to stop
+// first
+fn delay(device: usize) -> bool {
+	let delay = Duration::from_millis(MOCK.lock().unwrap().value("MOCK_DELAY_MS", device));
+	let start = Instant::now();
+	while start.elapsed() < delay {
+		if MOCK.lock().unwrap().should_quit() {
+			return false;
+		}
+		thread::sleep(cmp::min(IDLE_WAIT, delay.saturating_sub(start.elapsed())));
+	}
+	true
+}
+
+// Searches jobs from the input queue on one device until told to stop
+fn work(device: usize) {
+	loop {
+		let job = {
+			let mut m = MOCK.lock().unwrap();
+			if m.should_quit() {
+				break;
+			}
+			if m.devices[device].error.is_some() || m.value("USE_DEVICE", device) == 0 {
+				None
+			} else {
+				let job = m.input.pop_front();
+				if job.is_some() {
+					m.devices[device].last_start_time = now_ms();
+				}
+				job
+			}
+		};
+		let job = match job {
+			Some(j) => j,
+			None => {
+				thread::sleep(IDLE_WAIT);
+				continue;
+			}
+		};
+		let start = Instant::now();
+		if !delay(device) {
+			break;
+		}
+		let mut m = MOCK.lock().unwrap();
+		let solve_all = m.value("MOCK_SOLVE_ALL", 0) == 1;
+		if let Some(s) = solve(&job.data, m.edge_bits(), m.proof_size(), solve_all) {
+			if m.output.len() < m.value("OUTPUT_QUEUE_SIZE", 0) as usize {
+				let nonces = malformed(s.nonces, m.edge_bits(), m.value("MOCK_BAD_PROOF", 0));
+				let nonce = match m.value("MOCK_NONCE", 0) {
+					0 => job.nonce,
+					n => n.to_be_bytes(),
+				};
+				let id = match m.value("MOCK_ID_BY_NONCE", 0) {
+					1 => m.last_ids.get(&job.nonce).cloned().unwrap_or(job.id),
+					_ => job.id,
+				};
+				m.output.push_back(Solution {
+					id: id,
+					nonce: nonce,
+					cuckoo_size: s.cuckoo_size,
+					nonces: nonces,
+				});
+				m.log(LOG_DEBUG, &format!("Device {} found a solution for job {}", device, job.id));
+			}
+		}
+		let elapsed = start.elapsed();
+		let error_after = m.value("MOCK_ERROR_AFTER", device);
+		let d = &mut m.devices[device];
+		d.last_end_time = now_ms();
+		d.last_solution_time = elapsed.as_secs() * 1000 + (elapsed.subsec_nanos() / 1_000_000) as u64;
+		d.iterations_completed += 1;
+		if error_after > 0 && d.iterations_completed as u64 >= error_after {
+			let error = format!("Failed after {} graphs, as asked", d.iterations_completed);
+			d.error = Some(error.clone());
+			m.log(LOG_ERROR, &format!("Device {} {}", device, error));
+		}
+	}
+	MOCK.lock().unwrap().running -= 1;
+}
+
+#[no_mangle]
+pub extern "C" fn cuckoo_api_version() -> u32 {
+	API_VERSION
+}
+
+#[no_mangle]
+pub extern "C" fn cuckoo_init() {}
+
+#[no_mangle]
+pub unsafe extern "C" fn cuckoo_description(
+	name: *mut u8,
+	name_len: *mut u32,
+	description: *mut u8,
+	description_len: *mut u32,
+) -> u32 {
+	let mut name_needed = *name_len;
+	let mut description_needed = *description_len;
+	if write_buffer("mock", name, &mut name_needed) != 0 {
+		return 3;
+	}
+	if write_buffer("Returns canned solutions, for testing", description, &mut description_needed) != 0 {
+		return 3;
+	}
+	*name_len = name_needed;
+	*description_len = description_needed;
+	0
+}
+
+// Searches a graph for the cuckoo_call functions, returning the
+// solution's nonces and cuckoo size. Err if the cancel flag, if there is
+// one, is set while the graph is "searched".
+unsafe fn call(header: *const u8, header_len: u32, cancel: *const u8) -> Result<Option<(Vec<u64>, u32)>, ()> {
+	let header = slice::from_raw_parts(header, header_len as usize);
+	let (delay, edge_bits, proof_size, solve_all, bad_proof) = {
+		let m = MOCK.lock().unwrap();
+		(
+			m.value("MOCK_DELAY_MS", 0),
+			m.edge_bits(),
+			m.proof_size(),
+			m.value("MOCK_SOLVE_ALL", 0) == 1,
+			m.value("MOCK_BAD_PROOF", 0),
+		)
+	};
+	let delay = Duration::from_millis(delay);
+	let start = Instant::now();
+	while start.elapsed() < delay {
+		if !cancel.is_null() && ptr::read_volatile(cancel) != 0 {
+			return Err(());
+		}
+		thread::sleep(cmp::min(IDLE_WAIT, delay.saturating_sub(start.elapsed())));
+	}
+	Ok(solve(header, edge_bits, proof_size, solve_all)
+		.map(|s| (malformed(s.nonces, edge_bits, bad_proof), s.cuckoo_size)))
+}
+
+// The nonces as u32s, if they all fit
+fn to_u32s(nonces: &[u64]) -> Option<Vec<u32>> {
+	nonces.iter().map(|n| if *n > u32::max_value() as u64 { None } else { Some(*n as u32) }).collect()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn cuckoo_call(
+	header: *const u8,
+	header_len: u32,
+	cuckoo_size: *mut u32,
+	sol_nonces: *mut u32,
+) -> u32 {
+	match call(header, header_len, ptr::null()) {
+		Ok(Some((nonces, size))) => copy_u32s(&nonces, size, cuckoo_size, sol_nonces),
+		_ => 0,
+	}
+}
+
+// Copies a solution out for the 32-bit calls, if its nonces fit
+unsafe fn copy_u32s(nonces: &[u64], size: u32, cuckoo_size: *mut u32, sol_nonces: *mut u32) -> u32 {
+	match to_u32s(nonces) {
+		Some(nonces) => {
+			ptr::copy_nonoverlapping(nonces.as_ptr(), sol_nonces, nonces.len());
+			*cuckoo_size = size;
+			1
+		}
+		None => 0,
+	}
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn cuckoo_call_cancellable(
+	header: *const u8,
+	header_len: u32,
+	cuckoo_size: *mut u32,
+	sol_nonces: *mut u32,
+	cancel: *const u8,
+) -> u32 {
+	match call(header, header_len, cancel) {
+		Ok(Some((nonces, size))) => copy_u32s(&nonces, size, cuckoo_size, sol_nonces),
+		Ok(None) => 0,
+		Err(()) => 2,
+	}
+}
+
+// A solver context, whose buffer stands in for a real solver's, set up
+// once and reused for every graph
+struct Context {
+	edges: Vec<u64>,
+}
+
+#[no_mangle]
+pub extern "C" fn cuckoo_create_context() -> *mut c_void {
+	let mut m = MOCK.lock().unwrap();
+	if m.value("MOCK_NO_CONTEXT", 0) == 1 {
+		return ptr::null_mut();
+	}
+	m.contexts += 1;
+	let context = Box::new(Context {
+		edges: Vec::with_capacity(1 << cmp::min(m.edge_bits(), 16)),
+	});
+	Box::into_raw(context) as *mut c_void
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn cuckoo_call_ctx(
+	context: *mut c_void,
+	header: *const u8,
+	header_len: u32,
+	cuckoo_size: *mut u32,
+	sol_nonces: *mut u32,
+	cancel: *const u8,
+) -> u32 {
+	if context.is_null() {
+		return 3;
+	}
+	(*(context as *mut Context)).edges.clear();
+	match call(header, header_len, cancel) {
+		Ok(Some((nonces, size))) => copy_u32s(&nonces, size, cuckoo_size, sol_nonces),
+		Ok(None) => 0,
+		Err(()) => 2,
+	}
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn cuckoo_destroy_context(context: *mut c_void) {
+	if context.is_null() {
+		return;
+	}
+	drop(Box::from_raw(context as *mut Context));
+	MOCK.lock().unwrap().contexts -= 1;
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn cuckoo_call_64(
+	header: *const u8,
+	header_len: u32,
+	cuckoo_size: *mut u32,
+	sol_nonces: *mut u64,
+) -> u32 {
+	match call(header, header_len, ptr::null()) {
+		Ok(Some((nonces, size))) => {
+			ptr::copy_nonoverlapping(nonces.as_ptr(), sol_nonces, nonces.len());
+			*cuckoo_size = size;
+			1
+		}
+		_ => 0,
+	}
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn cuckoo_parameter_list(params_out_buf: *mut u8, params_len: *mut u32) -> u32 {
+	let params: Vec<String> = PARAMETERS
+		.iter()
+		.map(|p| {
+			format!(
+				"{{\"name\":\"{}\",\"description\":\"{}\",\"default_value\":{},\"min_value\":{},\"max_value\":{}}}",
+				p.name,
+				p.description,
+				p.default_value,
+				p.min_value,
+				p.max_value
+			)
+		})
+		.collect();
+	write_buffer(&format!("[{}]", params.join(",")), params_out_buf, params_len)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn cuckoo_get_parameter(
+	name: *const u8,
+	name_len: u32,
+	device_id: u32,
+	value: *mut u32,
+) -> u32 {
+	let name = slice::from_raw_parts(name, name_len as usize);
+	match MOCK.lock().unwrap().get(name, device_id) {
+		Ok(v) => {
+			*value = v as u32;
+			0
+		}
+		Err(e) => e,
+	}
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn cuckoo_set_parameter(name: *const u8, name_len: u32, device_id: u32, value: u32) -> u32 {
+	let name = slice::from_raw_parts(name, name_len as usize);
+	MOCK.lock().unwrap().set(name, device_id, value as u64)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn cuckoo_get_parameter_u64(
+	name: *const u8,
+	name_len: u32,
+	device_id: u32,
+	value: *mut u64,
+) -> u32 {
+	let name = slice::from_raw_parts(name, name_len as usize);
+	match MOCK.lock().unwrap().get(name, device_id) {
+		Ok(v) => {
+			*value = v;
+			0
+		}
+		Err(e) => e,
+	}
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn cuckoo_set_parameter_u64(name: *const u8, name_len: u32, device_id: u32, value: u64) -> u32 {
+	let name = slice::from_raw_parts(name, name_len as usize);
+	MOCK.lock().unwrap().set(name, device_id, value)
+}
+
+#[no_mangle]
+pub extern "C" fn cuckoo_is_queue_under_limit() -> u32 {
+	let m = MOCK.lock().unwrap();
+	(m.input.len() < m.value("QUEUE_SIZE", 0) as usize) as u32
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn cuckoo_queue_depths(input: *mut u32, output: *mut u32) -> u32 {
+	let m = MOCK.lock().unwrap();
+	if m.value("MOCK_NO_QUEUE_DEPTHS", 0) == 1 {
+		return 1;
+	}
+	*input = m.input.len() as u32;
+	*output = m.output.len() as u32;
+	0
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn cuckoo_push_to_input_queue(id: u32, data: *const u8, data_length: u32, nonce: *const u8) -> u32 {
+	let mut m = MOCK.lock().unwrap();
+	if m.stop_requested.is_some() {
+		return 4;
+	}
+	if data_length as usize > MAX_DATA_LENGTH {
+		return 2;
+	}
+	if m.input.len() >= m.value("QUEUE_SIZE", 0) as usize {
+		return 1;
+	}
+	let mut job = Job {
+		id: id,
+		data: slice::from_raw_parts(data, data_length as usize).to_vec(),
+		nonce: [0; 8],
+	};
+	job.nonce.copy_from_slice(slice::from_raw_parts(nonce, 8));
+	m.last_ids.insert(job.nonce, id);
+	m.input.push_back(job);
+	0
+}
+
+// The size of each record of a batch pushed through
+// cuckoo_push_batch_to_input_queue, a 32-byte header then its nonce
+const RECORD_SIZE: usize = 40;
+
+#[no_mangle]
+pub unsafe extern "C" fn cuckoo_push_batch_to_input_queue(
+	id: u32,
+	records: *const u8,
+	count: u32,
+	accepted: *mut u32,
+) -> u32 {
+	*accepted = 0;
+	let mut m = MOCK.lock().unwrap();
+	if m.stop_requested.is_some() {
+		return 4;
+	}
+	if m.value("MOCK_PUSH_NOTHING", 0) != 0 {
+		return 0;
+	}
+	let records = slice::from_raw_parts(records, count as usize * RECORD_SIZE);
+	for record in records.chunks(RECORD_SIZE) {
+		if m.input.len() >= m.value("QUEUE_SIZE", 0) as usize {
+			return 1;
+		}
+		let mut job = Job {
+			id: id,
+			data: record[..32].to_vec(),
+			nonce: [0; 8],
+		};
+		job.nonce.copy_from_slice(&record[32..]);
+		m.last_ids.insert(job.nonce, id);
+		m.input.push_back(job);
+		*accepted += 1;
+	}
+	0
+}
+
+#[no_mangle]
+pub extern "C" fn cuckoo_clear_queues() {
+	let mut m = MOCK.lock().unwrap();
+	m.input.clear();
+	m.output.clear();
+}
+
+#[no_mangle]
+pub extern "C" fn cuckoo_clear_input_queue() {
+	MOCK.lock().unwrap().input.clear();
+}
+
+#[no_mangle]
+pub extern "C" fn cuckoo_clear_output_queue() {
+	MOCK.lock().unwrap().output.clear();
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn cuckoo_read_from_output_queue(
+	id: *mut u32,
+	sol_nonces: *mut u32,
+	cuckoo_size: *mut u32,
+	nonce: *mut u8,
+) -> u32 {
+	// a solution whose nonces don't fit is dropped
+	match MOCK.lock().unwrap().output.pop_front() {
+		Some(s) => match to_u32s(&s.nonces) {
+			Some(nonces) => {
+				*id = s.id;
+				ptr::copy_nonoverlapping(nonces.as_ptr(), sol_nonces, nonces.len());
+				*cuckoo_size = s.cuckoo_size;
+				ptr::copy_nonoverlapping(s.nonce.as_ptr(), nonce, 8);
+				1
+			}
+			None => 0,
+		},
+		None => 0,
+	}
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn cuckoo_read_from_output_queue_64(
+	id: *mut u32,
+	sol_nonces: *mut u64,
+	cuckoo_size: *mut u32,
+	nonce: *mut u8,
+) -> u32 {
+	match MOCK.lock().unwrap().output.pop_front() {
+		Some(s) => {
+			*id = s.id;
+			ptr::copy_nonoverlapping(s.nonces.as_ptr(), sol_nonces, s.nonces.len());
+			*cuckoo_size = s.cuckoo_size;
+			ptr::copy_nonoverlapping(s.nonce.as_ptr(), nonce, 8);
+			1
+		}
+		None => 0,
+	}
+}
+
+#[no_mangle]
+pub extern "C" fn cuckoo_start_processing() -> u32 {
+	let mut m = MOCK.lock().unwrap();
+	let code = m.value("MOCK_START_ERROR", 0) as u32;
+	if code != 0 {
+		return code;
+	}
+	if m.running > 0 {
+		return 0;
+	}
+	m.stop_requested = None;
+	let devices = m.device_count();
+	m.running = devices;
+	m.workers = (0..devices).map(|i| thread::spawn(move || work(i))).collect();
+	m.log(LOG_INFO, &format!("Processing on {} devices", devices));
+	0
+}
+
+#[no_mangle]
+pub extern "C" fn cuckoo_stop_processing() -> u32 {
+	let mut m = MOCK.lock().unwrap();
+	if m.stop_requested.is_none() {
+		m.stop_requested = Some(Instant::now());
+	}
+	1
+}
+
+#[no_mangle]
+pub extern "C" fn cuckoo_reset_processing() -> u32 {
+	let mut m = MOCK.lock().unwrap();
+	if m.running == 0 {
+		m.stop_requested = None;
+	}
+	1
+}
+
+#[no_mangle]
+pub extern "C" fn cuckoo_has_processing_stopped() -> u32 {
+	let workers = {
+		let mut m = MOCK.lock().unwrap();
+		if m.running > 0 {
+			return 0;
+		}
+		m.workers.drain(..).collect::<Vec<_>>()
+	};
+	// the workers have finished, but are joined so none is still
+	// running when the library is unloaded
+	for w in workers {
+		let _ = w.join();
+	}
+	1
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn cuckoo_get_stats(prop_string: *mut u8, length: *mut u32) -> u32 {
+	let stats = MOCK.lock().unwrap().stats_json();
+	write_buffer(&stats, prop_string, length)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn cuckoo_list_devices(device_buf: *mut u8, length: *mut u32) -> u32 {
+	let devices = MOCK.lock().unwrap().devices_json();
+	write_buffer(&devices, device_buf, length)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn cuckoo_capabilities(caps_buf: *mut u8, length: *mut u32) -> u32 {
+	let m = MOCK.lock().unwrap();
+	let caps = format!(
+		"{{\"edge_bits\":{},\"proof_size\":{},\"is_cuda\":false,\"supports_async\":true,\
+		 \"max_queue_depth\":10000,\"graph_type\":\"cuckoo\"}}",
+		m.edge_bits(),
+		m.proof_size()
+	);
+	write_buffer(&caps, caps_buf, length)
+}
+
+#[no_mangle]
+pub extern "C" fn cuckoo_capability_flags() -> u32 {
+	// queue and stats, and the cuckoo size in bits 8 to 15
+	let size = MOCK.lock().unwrap().edge_bits() + 1;
+	0b11 | (size as u32 & 0xff) << 8
+}
+
+#[no_mangle]
+pub extern "C" fn cuckoo_set_logger(logger: Option<Logger>, context: *mut c_void) {
+	MOCK.lock().unwrap().logger = logger.map(|l| (l, context as usize));
+}
@@ -0,0 +1,98 @@
+// Copyright 2017 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A check that an ELF plugin file hasn't been cut short, made before
+//! it's loaded. The loader maps a library's segments straight from the
+//! file, so a truncated copy can crash the process with SIGBUS rather
+//! than fail to load. Other formats are left to the loader.
+
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+use byteorder::{ByteOrder, BigEndian, LittleEndian};
+
+/// The size of a 64-bit ELF header, the larger of the two
+const ELF_HEADER_SIZE: usize = 64;
+
+/// #Description
+///
+/// Checks the headers of an ELF file, and everything they say the loader
+/// will read, lie within the file.
+///
+/// #Arguments
+///
+/// * `path` (IN) The file
+///
+/// #Returns
+///
+/// What's missing from the file, if it's an ELF file which has been cut
+/// short. None for a complete ELF file, any other file, or one that
+/// can't be read, which the loader reports.
+///
+
+pub fn truncation(path: &Path) -> Option<String> {
+	let mut file = File::open(path).ok()?;
+	let len = file.metadata().ok()?.len();
+	let mut header = Vec::with_capacity(ELF_HEADER_SIZE);
+	(&mut file).take(ELF_HEADER_SIZE as u64).read_to_end(&mut header).ok()?;
+	if header.len() < 6 || &header[..4] != b"\x7fELF" {
+		return None;
+	}
+	match header[5] {
+		2 => check::<BigEndian>(&mut file, len, &header),
+		_ => check::<LittleEndian>(&mut file, len, &header),
+	}
+}
+
+// Checks the ELF header, the tables it points to and the segments the
+// program headers point to all fit in the file's length
+fn check<B: ByteOrder>(file: &mut File, len: u64, header: &[u8]) -> Option<String> {
+	let wide = header[4] == 2;
+	let header_size = if wide { 64 } else { 52 };
+	if header.len() < header_size {
+		return Some(String::from("the ELF header is cut short"));
+	}
+	let word = |b: &[u8], offset: usize| if wide {
+		B::read_u64(&b[offset..])
+	} else {
+		B::read_u32(&b[offset..]) as u64
+	};
+	let half = |offset: usize| B::read_u16(&header[offset..]) as u64;
+	let (ph_offset, sh_offset) = if wide { (0x20, 0x28) } else { (0x1c, 0x20) };
+	let ph_fields = if wide { 0x36 } else { 0x2a };
+	let (ph_start, ph_size, ph_count) = (word(header, ph_offset), half(ph_fields), half(ph_fields + 2));
+	let (sh_start, sh_size, sh_count) = (word(header, sh_offset), half(ph_fields + 4), half(ph_fields + 6));
+	let ph_end = ph_start.saturating_add(ph_size * ph_count);
+	if ph_end > len {
+		return Some(String::from("the program headers are cut short"));
+	}
+	if sh_start.saturating_add(sh_size * sh_count) > len {
+		return Some(String::from("the section headers are cut short"));
+	}
+	// each program header's segment, from its offset and size in the file
+	let (offset_at, size_at, entry_size) = if wide { (8, 32, 40) } else { (4, 16, 20) };
+	if ph_size < entry_size {
+		return None;
+	}
+	let mut table = vec![0u8; (ph_end - ph_start) as usize];
+	file.seek(SeekFrom::Start(ph_start)).ok()?;
+	file.read_exact(&mut table).ok()?;
+	for entry in table.chunks(ph_size as usize) {
+		if word(entry, offset_at).saturating_add(word(entry, size_at)) > len {
+			return Some(String::from("a segment is cut short"));
+		}
+	}
+	None
+}
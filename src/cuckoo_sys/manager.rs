@@ -20,57 +20,705 @@
 //!
 //! Note that plugins are shared libraries, not objects. You can have multiple
 //! instances of a PluginLibrary, but all of them will reference the same
-//! loaded code. Plugins aren't threadsafe, so only one thread should ever
-//! be calling a particular plugin at a time.
+//! loaded code, so share a single instance between threads (e.g. in an
+//! `Arc`) rather than loading the same plugin more than once. To run the
+//! same plugin more than once with independent state, use
+//! [load_instance](struct.PluginLibrary.html#method.load_instance). See
+//! [PluginLibrary](struct.PluginLibrary.html) for which calls may run
+//! concurrently.
 
-use std::sync::Mutex;
+use std::sync::{Mutex, RwLock};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::collections::HashSet;
+use std::{fs, io};
+use std::time::{Duration, Instant};
+use std::{cmp, env, mem, panic, process, ptr, slice, thread};
+use std::path::Path;
 
 use libloading;
 use libc::*;
+use log::LogLevel;
 
 use error::error::CuckooMinerError;
+use util::json::{self, FromJson, JsonValue};
+use CuckooMinerSolution;
+use SolutionMetadata;
+use NonceEncoding;
+use verify::verify::{PROOF_SIZE, MAX_32_BIT_EDGE_BITS};
+use super::state::{StateModel, PluginState, PluginOp};
+use super::path::resolve_plugin_path;
+use super::elf::truncation;
 
 // PRIVATE MEMBERS
 
 // Type definitions corresponding to each function that the plugin implements
 
 type CuckooInit = unsafe extern "C" fn();
-type CuckooCall = unsafe extern "C" fn(*const c_uchar, uint32_t, *mut uint32_t, *mut uint32_t) -> uint32_t;
-type CuckooParameterList = unsafe extern "C" fn(*mut c_uchar, *mut uint32_t) -> uint32_t;
-type CuckooSetParameter = unsafe extern "C" fn(*const c_uchar, uint32_t, uint32_t, uint32_t) -> uint32_t;
-type CuckooGetParameter = unsafe extern "C" fn(*const c_uchar, uint32_t, uint32_t, *mut uint32_t) -> uint32_t;
-type CuckooIsQueueUnderLimit = unsafe extern "C" fn() -> uint32_t;
-type CuckooPushToInputQueue = unsafe extern "C" fn(uint32_t, *const c_uchar, uint32_t, *const c_uchar)
-                                                   -> uint32_t;
-type CuckooReadFromOutputQueue = unsafe extern "C" fn(*mut uint32_t, *mut uint32_t, *mut uint32_t, *mut c_uchar) -> uint32_t;
+type CuckooCall = unsafe extern "C" fn(*const c_uchar, u32, *mut u32, *mut u32) -> u32;
+type CuckooCallWithKeys = unsafe extern "C" fn(*const u64, *mut u32, *mut u32) -> u32;
+type CuckooCall64 = unsafe extern "C" fn(*const c_uchar, u32, *mut u32, *mut u64) -> u32;
+type CuckooCallCancellable = unsafe extern "C" fn(*const c_uchar, u32, *mut u32, *mut u32, *const u8) -> u32;
+type CuckooCreateContext = unsafe extern "C" fn() -> *mut c_void;
+type CuckooCallCtx = unsafe extern "C" fn(*mut c_void, *const c_uchar, u32, *mut u32, *mut u32, *const u8) -> u32;
+type CuckooDestroyContext = unsafe extern "C" fn(*mut c_void);
+type CuckooParameterList = unsafe extern "C" fn(*mut c_uchar, *mut u32) -> u32;
+type CuckooSetParameter = unsafe extern "C" fn(*const c_uchar, u32, u32, u32) -> u32;
+type CuckooGetParameter = unsafe extern "C" fn(*const c_uchar, u32, u32, *mut u32) -> u32;
+type CuckooSetParameterU64 = unsafe extern "C" fn(*const c_uchar, u32, u32, u64) -> u32;
+type CuckooGetParameterU64 = unsafe extern "C" fn(*const c_uchar, u32, u32, *mut u64) -> u32;
+type CuckooIsQueueUnderLimit = unsafe extern "C" fn() -> u32;
+type CuckooQueueDepths = unsafe extern "C" fn(*mut u32, *mut u32) -> u32;
+type CuckooPushToInputQueue = unsafe extern "C" fn(u32, *const c_uchar, u32, *const c_uchar)
+                                                   -> u32;
+type CuckooPushBatchToInputQueue = unsafe extern "C" fn(u32, *const c_uchar, u32, *mut u32) -> u32;
+type CuckooReadFromOutputQueue = unsafe extern "C" fn(*mut u32, *mut u32, *mut u32, *mut c_uchar) -> u32;
+type CuckooReadFromOutputQueue64 = unsafe extern "C" fn(*mut u32, *mut u64, *mut u32, *mut c_uchar) -> u32;
 type CuckooClearQueues = unsafe extern "C" fn();
-type CuckooStartProcessing = unsafe extern "C" fn() -> uint32_t;
-type CuckooStopProcessing = unsafe extern "C" fn() -> uint32_t;
-type CuckooResetProcessing = unsafe extern "C" fn() -> uint32_t;
-type CuckooHasProcessingStopped = unsafe extern "C" fn() -> uint32_t;
-type CuckooGetStats = unsafe extern "C" fn(*mut c_uchar, *mut uint32_t) -> uint32_t;
+type CuckooStartProcessing = unsafe extern "C" fn() -> u32;
+type CuckooStopProcessing = unsafe extern "C" fn() -> u32;
+type CuckooResetProcessing = unsafe extern "C" fn() -> u32;
+type CuckooHasProcessingStopped = unsafe extern "C" fn() -> u32;
+type CuckooGetStats = unsafe extern "C" fn(*mut c_uchar, *mut u32) -> u32;
+type CuckooListDevices = unsafe extern "C" fn(*mut c_uchar, *mut u32) -> u32;
+type CuckooCapabilities = unsafe extern "C" fn(*mut c_uchar, *mut u32) -> u32;
+type CuckooLogger = extern "C" fn(*mut c_void, u32, *const c_uchar, u32);
+type CuckooSetLogger = unsafe extern "C" fn(Option<CuckooLogger>, *mut c_void);
+type CuckooApiVersion = unsafe extern "C" fn() -> u32;
+type CuckooCapabilityFlags = unsafe extern "C" fn() -> u32;
+type CuckooDescription = unsafe extern "C" fn(*mut c_uchar, *mut u32, *mut c_uchar, *mut u32)
+                                             -> u32;
 
-/// Struct to hold instances of loaded plugins
+/// Returned by raw calls that return a status code when the call isn't
+/// legal in the plugin's current [PluginState](enum.PluginState.html),
+/// in which case the plugin isn't called at all
+pub const CALL_REFUSED: u32 = 0xffff_ffff;
+
+/// Returned by [call_cuckoo_cancellable](struct.PluginLibrary.html#method.call_cuckoo_cancellable)
+/// when the call was cancelled before the graph was searched
+pub const CALL_CANCELLED: u32 = 2;
+
+/// How soon a plugin's cuckoo_call_cancellable must return once it's
+/// cancelled. Plugins check the cancel flag at least this often while
+/// searching a graph.
+pub const CANCEL_BOUND: Duration = Duration::from_millis(250);
+
+/// Device id addressing every device a plugin reports, for
+/// [set_parameter_for_device](struct.PluginLibrary.html#method.set_parameter_for_device)
+/// and per-device config
+pub const ALL_DEVICES: u32 = 0xffff_ffff;
+
+/// The version of the plugin interface this crate implements, as
+/// `(major << 16) | minor`. Plugins export theirs via `cuckoo_api_version`,
+/// and are rejected if the major version differs. Legacy plugins, which
+/// don't export it, are loaded with a warning unless they're disallowed. Plugins from version
+/// 1.1 must also export `cuckoo_capability_flags`.
+pub const PLUGIN_API_VERSION: u32 = (1 << 16) | 1;
+
+/// The standard parameter holding the number of headers a plugin's input
+/// queue accepts before it's full. Only takes effect when set before
+/// processing starts.
+pub const QUEUE_SIZE_PARAM: &str = "QUEUE_SIZE";
+
+/// The standard parameter holding the number of solutions a plugin's
+/// output queue holds
+pub const OUTPUT_QUEUE_SIZE_PARAM: &str = "OUTPUT_QUEUE_SIZE";
+
+/// The standard per-device parameter selecting whether a device mines,
+/// 1 if it does and 0 if not. Takes effect from the device's next graph
+/// attempt.
+pub const USE_DEVICE_PARAM: &str = "USE_DEVICE";
+
+/// The standard parameter holding the log2 of the number of edges in the
+/// graphs a plugin searches, i.e. its cuckoo size less one
+pub const EDGE_BITS_PARAM: &str = "EDGE_BITS";
+
+/// The standard read-only parameter holding the host memory a plugin
+/// expects to allocate once it starts processing, in MiB, at its current
+/// settings. 0 if it can't tell.
+pub const EXPECTED_MEMORY_PARAM: &str = "EXPECTED_MEMORY_MB";
+
+/// The size of the header in each record of a batch pushed with
+/// [push_jobs](struct.PluginLibrary.html#method.push_jobs)
+pub const JOB_HEADER_SIZE: usize = 32;
+
+/// The size of each record of a batch pushed with
+/// [push_jobs](struct.PluginLibrary.html#method.push_jobs). A batch is
+/// handed to the plugin's `cuckoo_push_batch_to_input_queue` as one packed
+/// buffer of these records, back to back with no padding, each the
+/// 32-byte header followed by its 8-byte nonce, as
+/// [pack_jobs](fn.pack_jobs.html) lays them out.
+pub const JOB_RECORD_SIZE: usize = JOB_HEADER_SIZE + 8;
+
+// How long unload will wait for processing to stop
+const UNLOAD_STOP_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Shortest and longest sleeps between polls of the output queue while
+// waiting for a solution
+const MIN_POLL_INTERVAL: Duration = Duration::from_millis(1);
+const MAX_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+// Starting and maximum sizes of the buffers handed to plugins for
+// variable-length output
+const INITIAL_BUFFER_SIZE: usize = 256;
+const MAX_BUFFER_SIZE: usize = 65536;
+
+/// The plugin functions which return a status code. The same code can
+/// mean different things depending on which function returned it, so
+/// codes are always translated in the context of the call that produced
+/// them.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PluginCall {
+	/// cuckoo_parameter_list
+	ParameterList,
+	/// cuckoo_get_parameter
+	GetParameter,
+	/// cuckoo_set_parameter
+	SetParameter,
+	/// cuckoo_push_to_input_queue
+	PushToInputQueue,
+	/// cuckoo_start_processing
+	StartProcessing,
+	/// cuckoo_get_stats
+	GetStats,
+	/// cuckoo_description
+	Description,
+	/// cuckoo_list_devices
+	ListDevices,
+	/// cuckoo_capabilities
+	Capabilities,
+}
+
+impl PluginCall {
+	/// Translates a return code from this call into a Result, using
+	/// `context` (e.g. the parameter name) to describe the failure.
+
+	pub fn check(&self, code: u32, context: &str) -> Result<(), CuckooMinerError> {
+		match (*self, code) {
+			(_, 0) => Ok(()),
+			(_, CALL_REFUSED) => Err(CuckooMinerError::PluginStateError(format!(
+				"{:?} not allowed in the plugin's current state: {}",
+				self,
+				context
+			))),
+			(PluginCall::GetParameter, 1) | (PluginCall::SetParameter, 1) => Err(
+				CuckooMinerError::ParameterNotFoundError(format!(
+					"Property doesn't exist for this plugin: {}",
+					context
+				)),
+			),
+			(PluginCall::SetParameter, 2) => Err(CuckooMinerError::ParameterRangeError(format!(
+				"Property outside allowed range: {}",
+				context
+			))),
+			(PluginCall::GetParameter, 4) | (PluginCall::SetParameter, 4) => Err(
+				CuckooMinerError::ParameterNameTooLongError(format!(
+					"Property name too long: {}",
+					context
+				)),
+			),
+			(PluginCall::GetParameter, 5) | (PluginCall::SetParameter, 5) => Err(
+				CuckooMinerError::DeviceNotFoundError(format!("Device doesn't exist: {}", context)),
+			),
+			(PluginCall::ParameterList, 3) |
+			(PluginCall::GetStats, 3) |
+			(PluginCall::Description, 3) |
+			(PluginCall::ListDevices, 3) |
+			(PluginCall::Capabilities, 3) => Err(
+				CuckooMinerError::BufferTooSmallError(format!(
+					"Buffer too small for plugin output: {}",
+					context
+				)),
+			),
+			(PluginCall::PushToInputQueue, 1) => Err(CuckooMinerError::QueueFull {
+				plugin: String::from(context),
+				depth: None,
+			}),
+			(PluginCall::PushToInputQueue, 2) => Err(CuckooMinerError::DataTooLongError(format!(
+				"Data is longer than the plugin allows: {}",
+				context
+			))),
+			(PluginCall::PushToInputQueue, 4) => Err(CuckooMinerError::PluginProcessingError(
+				format!("Plugin has been told to shut down: {}", context),
+			)),
+			(PluginCall::StartProcessing, c) => Err(CuckooMinerError::PluginProcessingError(
+				format!("Processing failed to start ({}): {}", c, context),
+			)),
+			(_, c) => Err(CuckooMinerError::UnexpectedResultError(c)),
+		}
+	}
+}
+
+/// A plugin's self-reported name and description
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PluginDescription {
+	/// The plugin's name
+	pub name: String,
+
+	/// A short description of the plugin
+	pub description: String,
+}
+
+/// Holds a plugin parameter description returned from a plugin
+/// as deserialised from json
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CuckooPluginParameter {
+	/// The name of the parameter
+	pub name: String,
+
+	/// Description of the parameter
+	pub description: String,
+
+	/// The default value of the parameter, used if none is provided
+	pub default_value: u64,
+
+	/// The minimum allowed value for the parameter
+	pub min_value: u64,
+
+	/// The maximum allowed value for the parameter. Parameters which can
+	/// exceed u32::MAX are read and set through the plugin's 64-bit calls.
+	pub max_value: u64,
+}
+
+impl CuckooPluginParameter {
+	/// Whether the parameter's values can exceed u32::MAX, so need the
+	/// plugin's 64-bit parameter calls
+	pub fn is_64_bit(&self) -> bool {
+		self.max_value > u32::max_value() as u64
+	}
+}
+
+impl FromJson for CuckooPluginParameter {
+	fn from_json(value: &JsonValue) -> Result<CuckooPluginParameter, String> {
+		Ok(CuckooPluginParameter {
+			name: json::string_field(value, "name")?,
+			description: json::string_field(value, "description")?,
+			default_value: json::u64_field(value, "default_value")?,
+			min_value: json::u64_field(value, "min_value")?,
+			max_value: json::u64_field(value, "max_value")?,
+		})
+	}
+}
+
+/// Holds deserialised performance metrics for a single device, as
+/// returned from a plugin
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PluginDeviceStats {
+	/// The plugin file name (optional so the plugins don't have to deal with it on de/ser)
+	pub plugin_name: Option<String>,
+
+	/// The internal device id
+	pub device_id: String,
+
+	/// Cuckoo size currently being used by the device
+	pub cuckoo_size: String,
+
+	/// The device name
+	pub device_name: String,
+
+	/// Whether the device is marked for use
+	pub in_use: u32,
+ 
+	/// Whether the device has thrown an error (and has stopped). Older
+	/// plugins report this as 0 or 1.
+	#[cfg_attr(feature = "serde", serde(deserialize_with = "bool_or_int::deserialize"))]
+	pub has_errored: bool,
+
+	/// Why the device errored, if the plugin says. Empty otherwise.
+	#[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "String::is_empty"))]
+	pub error_reason: String,
+
+	/// The time at which the device last began to search a hash (epoch in
+	/// mills)
+	pub last_start_time: u64,
+
+	/// The time at which the device last completed a solution search (epoch in
+	/// mills)
+	pub last_end_time: u64,
+
+	/// The amount of time the last solution search took (epoch in mills)
+	pub last_solution_time: u64,
+
+	/// The total number of searched performed since init
+	pub iterations_completed: u32,
+
+	/// The device temperature in degrees Celsius, if the plugin reports it
+	#[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Option::is_none"))]
+	pub temperature_c: Option<f32>,
+
+	/// The device's fan speed as a percentage of its maximum, if the
+	/// plugin reports it
+	#[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Option::is_none"))]
+	pub fan_percent: Option<u32>,
+
+	/// The device memory in use in bytes, if the plugin reports it.
+	/// Plugins report it as `device_memory_bytes`, or as
+	/// `memory_used_bytes` as earlier plugins did.
+	#[cfg_attr(feature = "serde", serde(default, alias = "device_memory_bytes", skip_serializing_if = "Option::is_none"))]
+	pub memory_used_bytes: Option<u64>,
+
+	/// The host memory the plugin has allocated in bytes, if the plugin
+	/// reports it. It's the plugin's as a whole rather than the device's,
+	/// so each of the plugin's devices reports the same figure.
+	#[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Option::is_none"))]
+	pub host_memory_bytes: Option<u64>,
+}
+
+impl FromJson for PluginDeviceStats {
+	fn from_json(value: &JsonValue) -> Result<PluginDeviceStats, String> {
+		// older plugins report the flag as 0 or 1
+		let has_errored = match value.get("has_errored") {
+			Some(&JsonValue::Bool(b)) => b,
+			Some(v) => match v.as_u64() {
+				Some(n) => n != 0,
+				None => return Err(String::from("`has_errored` should be a bool or 0/1")),
+			},
+			None => return Err(String::from("missing field `has_errored`")),
+		};
+		let memory_used_bytes = match json::optional_u64_field(value, "memory_used_bytes")? {
+			Some(m) => Some(m),
+			None => json::optional_u64_field(value, "device_memory_bytes")?,
+		};
+		Ok(PluginDeviceStats {
+			plugin_name: json::optional_string_field(value, "plugin_name")?,
+			device_id: json::string_field(value, "device_id")?,
+			cuckoo_size: json::string_field(value, "cuckoo_size")?,
+			device_name: json::string_field(value, "device_name")?,
+			in_use: json::u32_field(value, "in_use")?,
+			has_errored: has_errored,
+			error_reason: json::optional_string_field(value, "error_reason")?.unwrap_or_default(),
+			last_start_time: json::u64_field(value, "last_start_time")?,
+			last_end_time: json::u64_field(value, "last_end_time")?,
+			last_solution_time: json::u64_field(value, "last_solution_time")?,
+			iterations_completed: json::u32_field(value, "iterations_completed")?,
+			temperature_c: json::optional_f64_field(value, "temperature_c")?.map(|t| t as f32),
+			fan_percent: json::optional_u32_field(value, "fan_percent")?,
+			memory_used_bytes: memory_used_bytes,
+			host_memory_bytes: json::optional_u64_field(value, "host_memory_bytes")?,
+		})
+	}
+}
+
+impl PluginDeviceStats {
+	/// How long the device's last graph took, if it's finished one
+
+	pub fn last_solve_time(&self) -> Option<Duration> {
+		match self.last_solution_time {
+			0 => None,
+			ms => Some(Duration::from_millis(ms)),
+		}
+	}
+
+	/// The rate the device is searching graphs at, going by its last
+	/// graph, or 0 if it hasn't finished one
+
+	pub fn graphs_per_second(&self) -> f64 {
+		match self.last_solution_time {
+			0 => 0.0,
+			ms => 1000.0 / ms as f64,
+		}
+	}
+
+	/// Why the device has stopped, if it's thrown an error. The reason is
+	/// empty if the plugin doesn't give one.
+
+	pub fn error(&self) -> Option<&str> {
+		match self.has_errored {
+			true => Some(&self.error_reason),
+			false => None,
+		}
+	}
+}
+
+// Deserialises a flag reported either as a bool or, by older plugins, as
+// a number where anything but 0 is true
+
+#[cfg(feature = "serde")]
+mod bool_or_int {
+	use serde::{Deserializer, Deserialize};
+	use serde::de::Error;
+	use serde_json::Value;
+
+	pub fn deserialize<'de, D>(deserializer: D) -> Result<bool, D::Error>
+	where
+		D: Deserializer<'de>,
+	{
+		match Value::deserialize(deserializer)? {
+			Value::Bool(b) => Ok(b),
+			Value::Number(ref n) if n.as_u64().is_some() => Ok(n.as_u64() != Some(0)),
+			v => Err(D::Error::custom(format!("expected a bool or 0/1, got {}", v))),
+		}
+	}
+}
+
+/// Describes a device a plugin can mine on, as returned from
+/// [list_devices](struct.PluginLibrary.html#method.list_devices)
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DeviceInfo {
+	/// The id the plugin knows the device by, as used when setting
+	/// parameters
+	pub device_id: u32,
+
+	/// The device name
+	pub name: String,
+
+	/// The device's total memory in bytes, or 0 if unknown
+	pub total_memory: u64,
+
+	/// Whether the plugin considers the device usable, e.g. it has
+	/// enough memory for the plugin's cuckoo size
+	pub usable: bool,
+}
+
+impl FromJson for DeviceInfo {
+	fn from_json(value: &JsonValue) -> Result<DeviceInfo, String> {
+		Ok(DeviceInfo {
+			device_id: json::u32_field(value, "device_id")?,
+			name: json::string_field(value, "name")?,
+			total_memory: json::u64_field(value, "total_memory")?,
+			usable: json::bool_field(value, "usable")?,
+		})
+	}
+}
+
+/// The kind of graph a plugin searches for cycles
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum GraphType {
+	/// A cuckoo graph, whose proofs the miner can verify
+	Cuckoo,
+
+	/// A cuckatoo graph, whose proofs the miner passes unverified
+	Cuckatoo,
+}
+
+impl Default for GraphType {
+	fn default() -> GraphType {
+		GraphType::Cuckoo
+	}
+}
+
+impl GraphType {
+	/// The graph type as plugins name it in their capabilities
+
+	pub fn name(&self) -> &'static str {
+		match *self {
+			GraphType::Cuckoo => "cuckoo",
+			GraphType::Cuckatoo => "cuckatoo",
+		}
+	}
+}
+
+/// What a plugin can do, as returned from
+/// [capabilities](struct.PluginLibrary.html#method.capabilities). Plugins
+/// may leave out any field when reporting them, which then takes the
+/// value an older plugin is assumed to have.
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PluginCapabilities {
+	/// The log2 of the number of edges in the graphs the plugin searches,
+	/// which bounds the edge nonces in its proofs, if known
+	#[cfg_attr(feature = "serde", serde(default))]
+	pub edge_bits: Option<u32>,
+
+	/// The number of edge nonces in each proof
+	#[cfg_attr(feature = "serde", serde(default = "default_proof_size"))]
+	pub proof_size: u32,
+
+	/// Whether the plugin mines on CUDA devices
+	#[cfg_attr(feature = "serde", serde(default))]
+	pub is_cuda: bool,
+
+	/// Whether the plugin can be given headers through its input queue,
+	/// rather than only one at a time through its synchronous call
+	#[cfg_attr(feature = "serde", serde(default = "default_supports_async"))]
+	pub supports_async: bool,
+
+	/// Whether the plugin reports per-device stats
+	#[cfg_attr(feature = "serde", serde(default = "default_supports_stats"))]
+	pub supports_stats: bool,
+
+	/// The most headers the input queue can be set to hold, if known
+	#[cfg_attr(feature = "serde", serde(default))]
+	pub max_queue_depth: Option<u32>,
+
+	/// The kind of graph the plugin searches, which decides whether its
+	/// proofs can be verified
+	#[cfg_attr(feature = "serde", serde(default))]
+	pub graph_type: GraphType,
+}
+
+fn default_proof_size() -> u32 {
+	PROOF_SIZE as u32
+}
+
+fn default_supports_async() -> bool {
+	true
+}
+
+fn default_supports_stats() -> bool {
+	true
+}
+
+impl Default for PluginCapabilities {
+	fn default() -> PluginCapabilities {
+		PluginCapabilities {
+			edge_bits: None,
+			proof_size: default_proof_size(),
+			is_cuda: false,
+			supports_async: default_supports_async(),
+			supports_stats: default_supports_stats(),
+			max_queue_depth: None,
+			graph_type: GraphType::default(),
+		}
+	}
+}
+
+impl FromJson for PluginCapabilities {
+	fn from_json(value: &JsonValue) -> Result<PluginCapabilities, String> {
+		let defaults = PluginCapabilities::default();
+		Ok(PluginCapabilities {
+			edge_bits: json::optional_u32_field(value, "edge_bits")?,
+			proof_size: json::optional_u32_field(value, "proof_size")?.unwrap_or(defaults.proof_size),
+			is_cuda: json::optional_bool_field(value, "is_cuda")?.unwrap_or(defaults.is_cuda),
+			supports_async: json::optional_bool_field(value, "supports_async")?.unwrap_or(defaults.supports_async),
+			supports_stats: json::optional_bool_field(value, "supports_stats")?.unwrap_or(defaults.supports_stats),
+			max_queue_depth: json::optional_u32_field(value, "max_queue_depth")?,
+			graph_type: match json::optional_string_field(value, "graph_type")? {
+				None => defaults.graph_type,
+				Some(ref t) if t == GraphType::Cuckoo.name() => GraphType::Cuckoo,
+				Some(ref t) if t == GraphType::Cuckatoo.name() => GraphType::Cuckatoo,
+				Some(t) => return Err(format!("unknown graph type {}", t)),
+			},
+		})
+	}
+}
+
+impl PluginCapabilities {
+	// Takes what the plugin reports in its capability flags over anything
+	// else, as plugins from version 1.1 of the interface return them from
+	// cuckoo_capability_flags: the flags in the low bits, and the cuckoo
+	// size, if known, in bits 8 to 15
+
+	fn apply_flags(&mut self, flags: u32) {
+		self.supports_async = flags & 1 != 0;
+		self.supports_stats = flags & (1 << 1) != 0;
+		self.is_cuda = flags & (1 << 2) != 0;
+		match (flags >> 8) & 0xff {
+			0 => {}
+			size => self.edge_bits = Some(size - 1),
+		}
+	}
+}
+
+/// #Description
+///
+/// Struct to hold instances of loaded plugins. A PluginLibrary is `Send`
+/// and `Sync`, so a single instance can be shared between threads, e.g.
+/// one pushing jobs, another reading solutions and a third reading stats.
+///
+/// #Concurrency
+///
+/// * Calls to different plugin functions may run at the same time. In
+/// particular, pushing to the input queue, reading from the output queue
+/// and reading stats don't block each other.
+/// * Concurrent calls to the same plugin function are serialised, as
+/// plugins aren't required to make any single function reentrant.
+/// * Init and unload are exclusive. They wait for calls in progress on
+/// other threads to return, and hold off new calls until they're done.
+/// Calls made after unload are refused as usual.
+///
+/// #Lifetime
+///
+/// The plugin's unloaded when the PluginLibrary is dropped, if it hasn't
+/// been already, so its library is never left open. To share one plugin
+/// between owners, so it's unloaded once the last of them is done with
+/// it, put it in an `Arc`. Calling [unload](#method.unload) through any
+/// of them unloads it for all, after which their calls are refused.
 
 pub struct PluginLibrary {
 	///The full file path to the plugin loaded by this instance
 	pub lib_full_path: String,
 
-	loaded_library: Mutex<libloading::Library>,
+	// taken and closed on unload
+	loaded_library: Mutex<Option<libloading::Library>>,
 	cuckoo_init: Mutex<CuckooInit>,
 	cuckoo_call: Mutex<CuckooCall>,
 	cuckoo_parameter_list: Mutex<CuckooParameterList>,
 	cuckoo_get_parameter: Mutex<CuckooGetParameter>,
 	cuckoo_set_parameter: Mutex<CuckooSetParameter>,
 	cuckoo_is_queue_under_limit: Mutex<CuckooIsQueueUnderLimit>,
+	// optional, plugins without it have their input queue depth estimated
+	cuckoo_queue_depths: Option<Mutex<CuckooQueueDepths>>,
 	cuckoo_clear_queues: Mutex<CuckooClearQueues>,
 	cuckoo_push_to_input_queue: Mutex<CuckooPushToInputQueue>,
+	// optional, plugins without it are pushed one header at a time
+	cuckoo_push_batch_to_input_queue: Option<Mutex<CuckooPushBatchToInputQueue>>,
 	cuckoo_read_from_output_queue: Mutex<CuckooReadFromOutputQueue>,
 	cuckoo_start_processing: Mutex<CuckooStartProcessing>,
 	cuckoo_stop_processing: Mutex<CuckooStopProcessing>,
 	cuckoo_reset_processing: Mutex<CuckooResetProcessing>,
 	cuckoo_has_processing_stopped: Mutex<CuckooHasProcessingStopped>,
 	cuckoo_get_stats: Mutex<CuckooGetStats>,
+	// optional, not all plugins export it
+	cuckoo_description: Option<Mutex<CuckooDescription>>,
+	// optional, older plugins only take a header
+	cuckoo_call_with_keys: Option<Mutex<CuckooCallWithKeys>>,
+	// optional, older plugins can only clear both queues at once
+	cuckoo_clear_input_queue: Option<Mutex<CuckooClearQueues>>,
+	cuckoo_clear_output_queue: Option<Mutex<CuckooClearQueues>>,
+	// optional, needed by plugins whose edge indices don't fit in a u32
+	cuckoo_call_64: Option<Mutex<CuckooCall64>>,
+	cuckoo_read_from_output_queue_64: Option<Mutex<CuckooReadFromOutputQueue64>>,
+	// optional, calls to plugins without it can't be interrupted
+	cuckoo_call_cancellable: Option<Mutex<CuckooCallCancellable>>,
+	// optional, plugins without them set up their solver on every call
+	cuckoo_create_context: Option<CuckooCreateContext>,
+	cuckoo_call_ctx: Option<Mutex<CuckooCallCtx>>,
+	cuckoo_destroy_context: Option<CuckooDestroyContext>,
+	// optional before 1.1, older plugins only report capabilities as JSON
+	cuckoo_capability_flags: Option<Mutex<CuckooCapabilityFlags>>,
+	// the addresses of the contexts created and not yet destroyed, which
+	// are destroyed on unload if they're still around
+	contexts: Mutex<HashSet<usize>>,
+	// optional, plugins without it print their own output
+	cuckoo_set_logger: Option<CuckooSetLogger>,
+	// the target the plugin's messages are logged to, which the plugin
+	// holds a pointer to until it's unloaded
+	log_target: Mutex<Option<Box<String>>>,
+	// held shared by every call, and exclusively by init and unload
+	lifecycle: RwLock<()>,
+	// lifecycle state, which all calls are checked against
+	state: Mutex<StateModel>,
+	// ids of the devices disabled through set_device_enabled
+	disabled_devices: Mutex<HashSet<u32>>,
+	// whether every device the plugin reports is disabled
+	all_devices_disabled: Mutex<bool>,
+	// the plugin's reported interface version, 0 for legacy plugins
+	api_version: u32,
+	// the number of nonces in the plugin's proofs, from its capabilities
+	proof_size: AtomicUsize,
+	// whether its edge indices may not fit in a u32, from its capabilities
+	wide_edges: AtomicBool,
+	// the kind of graph it searches, from its capabilities
+	graph_type: Mutex<GraphType>,
+	// the headers pushed since the input queue was last cleared, and the
+	// graphs the plugin had started as of then, for estimating the queue's
+	// depth when the plugin can't report it
+	queue_pushed: AtomicUsize,
+	queue_graphs_started: AtomicUsize,
+	// how nonces are laid out in the bytes passed to and from the plugin
+	nonce_encoding: Mutex<NonceEncoding>,
 }
 
 impl PluginLibrary {
@@ -82,12 +730,19 @@ impl PluginLibrary {
 	/// via the exposed wrapper functions. A plugin can be
 	/// loaded into multiple PluginLibrary instances, however
 	/// they will all reference the same loaded library. One
-	/// should only exist per library in a given thread.
+	/// should only exist per library, shared between threads
+	/// if needed.
+	///
+	/// Legacy plugins, which don't export `cuckoo_api_version`, are
+	/// loaded with a warning, as their interface can't be checked. Use
+	/// [load](#method.load) to reject them instead.
 	///
 	/// #Arguments
 	///
-	/// * `lib_full_path` The full path to the library that is
-	/// to be loaded.
+	/// * `lib_full_path` The path to the library that is to be loaded,
+	/// or its name. If there's no file at the path, the plugin search
+	/// directories are checked as described in
+	/// [resolve_plugin_path](fn.resolve_plugin_path.html).
 	///
 	/// #Returns
 	///
@@ -111,191 +766,556 @@ impl PluginLibrary {
 	///
 
 	pub fn new(lib_full_path: &str) -> Result<PluginLibrary, CuckooMinerError> {
+		PluginLibrary::load(lib_full_path, true)
+	}
+
+	/// #Description
+	///
+	/// As [new](#method.new), but optionally rejects legacy plugins that
+	/// don't export `cuckoo_api_version`. Their interface can't be checked,
+	/// so a mismatched legacy plugin may misbehave rather than fail to
+	/// load, and they're only loaded, with a warning, when allowed.
+	///
+	/// #Arguments
+	///
+	/// * `lib_full_path` The path to the library that is to be loaded, or
+	/// its name
+	/// * `allow_legacy_plugins` Whether to load plugins without an
+	/// interface version, as [new](#method.new) does
+	///
+	/// #Returns
+	///
+	/// * `Ok()` is the library was successfully loaded.
+	/// * a PluginApiMismatch if the plugin's major interface version
+	/// differs, or it has none and `allow_legacy_plugins` isn't set
+	/// * another [CuckooMinerError](enum.CuckooMinerError.html)
+	/// with specific detail if an error was encountered.
+	///
+
+	pub fn load(lib_full_path: &str, allow_legacy_plugins: bool) -> Result<PluginLibrary, CuckooMinerError> {
+		let resolved_path = resolve_plugin_path(lib_full_path)?;
+		let lib_full_path = &*resolved_path.to_string_lossy();
 		debug!("Loading miner plugin: {}", &lib_full_path);
 
+		// dlopen doesn't report why it failed in a way we can match on, so
+		// check the file can be read first
+		if let Err(e) = fs::File::open(&resolved_path) {
+			return Err(match e.kind() {
+				io::ErrorKind::PermissionDenied => CuckooMinerError::PluginPermissionError(
+					format!("{} - {}", lib_full_path, e),
+				),
+				io::ErrorKind::NotFound => CuckooMinerError::PluginNotFoundError(
+					String::from(lib_full_path),
+				),
+				_ => CuckooMinerError::PluginIOError(format!("{} - {}", lib_full_path, e)),
+			});
+		}
+		// and that it's whole, as loading a truncated library can crash
+		if let Some(problem) = truncation(&resolved_path) {
+			return Err(CuckooMinerError::PluginIOError(
+				format!("{} is not a loadable plugin - {}", lib_full_path, problem),
+			));
+		}
+
 		let result = libloading::Library::new(lib_full_path);
 
 		if let Err(e) = result {
-			return Err(CuckooMinerError::PluginNotFoundError(
-				String::from(format!("{} - {:?}", lib_full_path, e)),
+			return Err(CuckooMinerError::PluginIOError(
+				String::from(format!("{} is not a loadable plugin - {:?}", lib_full_path, e)),
 			));
 		}
 
 		let loaded_library = result.unwrap();
-		PluginLibrary::load_symbols(loaded_library, lib_full_path)
+		let api_version = PluginLibrary::check_api_version(
+			&loaded_library,
+			lib_full_path,
+			allow_legacy_plugins,
+		)?;
+		PluginLibrary::load_symbols(loaded_library, lib_full_path, api_version)
+	}
+
+	/// #Description
+	///
+	/// Loads a separate instance of a plugin, with its own queues,
+	/// parameters and processing state, so the same plugin can be run
+	/// more than once side by side. The shared library can only be loaded
+	/// once per file, so each instance after the first is loaded from a
+	/// private copy in the temp directory, and is named after the plugin
+	/// and its instance number, e.g. `lean_cpu_16.1`.
+	///
+	/// #Arguments
+	///
+	/// * `lib_full_path` The path to the library that is to be loaded, or
+	/// its name
+	/// * `instance` Which instance to load. Instance 0 is the library
+	/// itself, as loaded by [load](#method.load).
+	/// * `allow_legacy_plugins` Whether to load plugins without an
+	/// interface version
+	///
+	/// #Returns
+	///
+	/// * `Ok()` is the library was successfully loaded.
+	/// * a PluginIOError if the copy couldn't be made
+	/// * another [CuckooMinerError](enum.CuckooMinerError.html)
+	/// as for [load](#method.load)
+	///
+
+	pub fn load_instance(
+		lib_full_path: &str,
+		instance: usize,
+		allow_legacy_plugins: bool,
+	) -> Result<PluginLibrary, CuckooMinerError> {
+		if instance == 0 {
+			return PluginLibrary::load(lib_full_path, allow_legacy_plugins);
+		}
+		let resolved_path = resolve_plugin_path(lib_full_path)?;
+		let (stem, extension) = match (resolved_path.file_stem(), resolved_path.extension()) {
+			(Some(s), Some(e)) => (s.to_string_lossy(), e.to_string_lossy()),
+			_ => {
+				return Err(CuckooMinerError::PluginIOError(
+					format!("{} isn't a plugin file name", lib_full_path),
+				))
+			}
+		};
+		let mut copy_path = env::temp_dir();
+		copy_path.push(format!("cuckoo-miner-{}", process::id()));
+		copy_path.push(format!("{}.{}.{}", stem, instance, extension));
+		let copied = copy_path
+			.parent()
+			.map_or(Ok(()), |d| fs::create_dir_all(d))
+			.and_then(|_| fs::copy(&resolved_path, &copy_path));
+		if let Err(e) = copied {
+			return Err(CuckooMinerError::PluginIOError(format!(
+				"Unable to copy {} to {} - {}",
+				lib_full_path,
+				copy_path.display(),
+				e
+			)));
+		}
+		PluginLibrary::load(&copy_path.to_string_lossy(), allow_legacy_plugins)
+	}
+
+	// Reads the plugin's interface version, before anything else in the
+	// plugin is touched, and checks it's compatible
+
+	fn check_api_version(
+		loaded_library: &libloading::Library,
+		path: &str,
+		allow_legacy_plugins: bool,
+	) -> Result<u32, CuckooMinerError> {
+		let found = unsafe {
+			match get_symbol::<CuckooApiVersion>(loaded_library, path, "cuckoo_api_version") {
+				Ok(f) => f(),
+				Err(_) => 0,
+			}
+		};
+		if found == 0 {
+			if allow_legacy_plugins {
+				warn!(
+					"Loading legacy plugin {} without an interface version. Rebuild it against \
+					 interface version {}.{} so it can be checked.",
+					path,
+					PLUGIN_API_VERSION >> 16,
+					PLUGIN_API_VERSION & 0xffff
+				);
+				return Ok(0);
+			}
+			error!(
+				"{} doesn't export cuckoo_api_version, and was probably built against an older \
+				 plugin interface. Rebuild it, or allow legacy plugins to load it anyway.",
+				path
+			);
+			return Err(CuckooMinerError::PluginApiMismatch {
+				expected: PLUGIN_API_VERSION >> 16,
+				found: 0,
+			});
+		}
+		if found >> 16 != PLUGIN_API_VERSION >> 16 {
+			return Err(CuckooMinerError::PluginApiMismatch {
+				expected: PLUGIN_API_VERSION >> 16,
+				found: found >> 16,
+			});
+		}
+		// from 1.1, plugins report their capability flags
+		if found & 0xffff >= 1 {
+			unsafe {
+				get_symbol::<CuckooCapabilityFlags>(loaded_library, path, "cuckoo_capability_flags")?;
+			}
+		}
+		Ok(found)
 	}
 
 	fn load_symbols(
 		loaded_library: libloading::Library,
-		path: &str
+		path: &str,
+		api_version: u32,
 	) -> Result<PluginLibrary, CuckooMinerError> {
 		unsafe {
 			let ret_val = PluginLibrary {
 				lib_full_path: String::from(path),
-				cuckoo_init: {
-					let cuckoo_init: libloading::Symbol<CuckooInit> =
-						loaded_library.get(b"cuckoo_init\0").unwrap();
-					Mutex::new(*cuckoo_init.into_raw())
-				},
+				cuckoo_init: Mutex::new(get_symbol(&loaded_library, path, "cuckoo_init")?),
+				cuckoo_call: Mutex::new(get_symbol(&loaded_library, path, "cuckoo_call")?),
+				cuckoo_parameter_list: Mutex::new(get_symbol(&loaded_library, path, "cuckoo_parameter_list")?),
+				cuckoo_get_parameter: Mutex::new(get_symbol(&loaded_library, path, "cuckoo_get_parameter")?),
+				cuckoo_set_parameter: Mutex::new(get_symbol(&loaded_library, path, "cuckoo_set_parameter")?),
+				cuckoo_is_queue_under_limit: Mutex::new(get_symbol(&loaded_library, path, "cuckoo_is_queue_under_limit")?),
+				cuckoo_queue_depths: get_symbol(&loaded_library, path, "cuckoo_queue_depths")
+					.ok()
+					.map(Mutex::new),
+				cuckoo_clear_queues: Mutex::new(get_symbol(&loaded_library, path, "cuckoo_clear_queues")?),
+				cuckoo_push_to_input_queue: Mutex::new(get_symbol(&loaded_library, path, "cuckoo_push_to_input_queue")?),
+				cuckoo_push_batch_to_input_queue: get_symbol(
+					&loaded_library,
+					path,
+					"cuckoo_push_batch_to_input_queue",
+				).ok()
+					.map(Mutex::new),
+				cuckoo_read_from_output_queue: Mutex::new(get_symbol(&loaded_library, path, "cuckoo_read_from_output_queue")?),
+				cuckoo_start_processing: Mutex::new(get_symbol(&loaded_library, path, "cuckoo_start_processing")?),
+				cuckoo_stop_processing: Mutex::new(get_symbol(&loaded_library, path, "cuckoo_stop_processing")?),
+				cuckoo_reset_processing: Mutex::new(get_symbol(&loaded_library, path, "cuckoo_reset_processing")?),
+				cuckoo_has_processing_stopped: Mutex::new(get_symbol(&loaded_library, path, "cuckoo_has_processing_stopped")?),
+				cuckoo_get_stats: Mutex::new(get_symbol(&loaded_library, path, "cuckoo_get_stats")?),
 
-				cuckoo_call: {
-					let cuckoo_call: libloading::Symbol<CuckooCall> =
-						loaded_library.get(b"cuckoo_call\0").unwrap();
-					Mutex::new(*cuckoo_call.into_raw())
+				cuckoo_description: {
+					let cuckoo_description: Result<libloading::Symbol<CuckooDescription>, _> =
+						loaded_library.get(b"cuckoo_description\0");
+					match cuckoo_description {
+						Ok(s) => Some(Mutex::new(*s.into_raw())),
+						Err(_) => None,
+					}
 				},
+				cuckoo_call_with_keys: get_symbol(&loaded_library, path, "cuckoo_call_with_keys")
+					.ok()
+					.map(Mutex::new),
+				cuckoo_clear_input_queue: get_symbol(&loaded_library, path, "cuckoo_clear_input_queue")
+					.ok()
+					.map(Mutex::new),
+				cuckoo_clear_output_queue: get_symbol(&loaded_library, path, "cuckoo_clear_output_queue")
+					.ok()
+					.map(Mutex::new),
+				cuckoo_call_64: get_symbol(&loaded_library, path, "cuckoo_call_64")
+					.ok()
+					.map(Mutex::new),
+				cuckoo_read_from_output_queue_64: get_symbol(
+					&loaded_library,
+					path,
+					"cuckoo_read_from_output_queue_64",
+				).ok()
+					.map(Mutex::new),
+				cuckoo_call_cancellable: get_symbol(&loaded_library, path, "cuckoo_call_cancellable")
+					.ok()
+					.map(Mutex::new),
+				cuckoo_create_context: get_symbol(&loaded_library, path, "cuckoo_create_context").ok(),
+				cuckoo_call_ctx: get_symbol(&loaded_library, path, "cuckoo_call_ctx")
+					.ok()
+					.map(Mutex::new),
+				cuckoo_destroy_context: get_symbol(&loaded_library, path, "cuckoo_destroy_context").ok(),
+				cuckoo_capability_flags: get_symbol(&loaded_library, path, "cuckoo_capability_flags")
+					.ok()
+					.map(Mutex::new),
+				contexts: Mutex::new(HashSet::new()),
+				cuckoo_set_logger: get_symbol(&loaded_library, path, "cuckoo_set_logger").ok(),
+				log_target: Mutex::new(None),
 
-				cuckoo_parameter_list: {
-					let cuckoo_parameter_list:libloading::Symbol<CuckooParameterList> =
-						loaded_library.get(b"cuckoo_parameter_list\0").unwrap();
-					Mutex::new(*cuckoo_parameter_list.into_raw())
-				},
+				loaded_library: Mutex::new(Some(loaded_library)),
+				lifecycle: RwLock::new(()),
+				state: Mutex::new(StateModel::new()),
+				disabled_devices: Mutex::new(HashSet::new()),
+				all_devices_disabled: Mutex::new(false),
+				api_version: api_version,
+				proof_size: AtomicUsize::new(PROOF_SIZE),
+				wide_edges: AtomicBool::new(false),
+				graph_type: Mutex::new(GraphType::default()),
+				queue_pushed: AtomicUsize::new(0),
+				queue_graphs_started: AtomicUsize::new(0),
+				nonce_encoding: Mutex::new(NonceEncoding::default()),
+			};
 
-				cuckoo_get_parameter: {
-					let cuckoo_get_parameter:libloading::Symbol<CuckooGetParameter> =
-						loaded_library.get(b"cuckoo_get_parameter\0").unwrap();
-					Mutex::new(*cuckoo_get_parameter.into_raw())
-				},
+			ret_val.register_logger();
+			ret_val.call_cuckoo_init();
+			ret_val.refresh_capabilities();
+			ret_val.check_edge_bits()?;
+			return Ok(ret_val);
+		}
+	}
 
-				cuckoo_set_parameter: {
-					let cuckoo_set_parameter:libloading::Symbol<CuckooSetParameter> =
-						loaded_library.get(b"cuckoo_set_parameter\0").unwrap();
-					Mutex::new(*cuckoo_set_parameter.into_raw())
-				},
+	// Routes the plugin's log output through the log crate, with the
+	// plugin's name as the target, before it's initialised
 
-				cuckoo_is_queue_under_limit: {
-					let cuckoo_is_queue_under_limit:libloading::Symbol<CuckooIsQueueUnderLimit> =
-						loaded_library.get(b"cuckoo_is_queue_under_limit\0").unwrap();
-					Mutex::new(*cuckoo_is_queue_under_limit.into_raw())
-				},
+	fn register_logger(&self) {
+		let set_logger = match self.cuckoo_set_logger {
+			Some(s) => s,
+			None => {
+				debug!("{} doesn't export cuckoo_set_logger, so prints its own output", self.lib_full_path);
+				return;
+			}
+		};
+		let target = Box::new(
+			Path::new(&self.lib_full_path)
+				.file_stem()
+				.map(|s| s.to_string_lossy().into_owned())
+				.unwrap_or_default(),
+		);
+		let context = &*target as *const String as *mut c_void;
+		*self.log_target.lock().unwrap() = Some(target);
+		unsafe { set_logger(Some(log_plugin_message), context) };
+	}
 
-				cuckoo_clear_queues: {
-					let cuckoo_clear_queues:libloading::Symbol<CuckooClearQueues> =
-						loaded_library.get(b"cuckoo_clear_queues\0").unwrap();
-					Mutex::new(*cuckoo_clear_queues.into_raw())
-				},
+	// Leaks the log target, for when the plugin can't be unloaded and may
+	// still log to it
 
-				cuckoo_push_to_input_queue: {
-					let cuckoo_push_to_input_queue:libloading::Symbol<CuckooPushToInputQueue> =
-						loaded_library.get(b"cuckoo_push_to_input_queue\0").unwrap();
-					Mutex::new(*cuckoo_push_to_input_queue.into_raw())
-				},
+	fn leak_log_target(&self) {
+		if let Some(t) = self.log_target.lock().unwrap().take() {
+			mem::forget(t);
+		}
+	}
 
-				cuckoo_read_from_output_queue: {
-					let cuckoo_read_from_output_queue:libloading::Symbol<CuckooReadFromOutputQueue> =
-						loaded_library.get(b"cuckoo_read_from_output_queue\0").unwrap();
-					Mutex::new(*cuckoo_read_from_output_queue.into_raw())
-				},
+	/// Checks `op` against the plugin's lifecycle state, applying it if
+	/// it's legal. Illegal calls are logged and must not be passed on to
+	/// the plugin.
 
-				cuckoo_start_processing: {
-					let cuckoo_start_processing:libloading::Symbol<CuckooStartProcessing> =
-						loaded_library.get(b"cuckoo_start_processing\0").unwrap();
-					Mutex::new(*cuckoo_start_processing.into_raw())
-				},
+	fn guard(&self, op: PluginOp) -> bool {
+		match self.state.lock().unwrap().apply(op) {
+			Ok(()) => true,
+			Err(e) => {
+				warn!("Refusing plugin call to {}: {:?}", self.lib_full_path, e);
+				false
+			}
+		}
+	}
 
-				cuckoo_stop_processing: {
-					let cuckoo_stop_processing:libloading::Symbol<CuckooStopProcessing> =
-						loaded_library.get(b"cuckoo_stop_processing\0").unwrap();
-					Mutex::new(*cuckoo_stop_processing.into_raw())
-				},
+	/// Looks up an optional plugin function when it's first needed,
+	/// returning None if the plugin doesn't export it or has been unloaded
 
-				cuckoo_reset_processing: {
-					let cuckoo_reset_processing:libloading::Symbol<CuckooResetProcessing> =
-						loaded_library.get(b"cuckoo_reset_processing\0").unwrap();
-					Mutex::new(*cuckoo_reset_processing.into_raw())
-				},
+	fn optional_symbol<T: Copy>(&self, symbol: &str) -> Option<T> {
+		match *self.loaded_library.lock().unwrap() {
+			Some(ref l) => unsafe { get_symbol(l, &self.lib_full_path, symbol).ok() },
+			None => None,
+		}
+	}
 
-				cuckoo_has_processing_stopped: {
-					let cuckoo_has_processing_stopped:libloading::Symbol<CuckooHasProcessingStopped> =
-						loaded_library.get(b"cuckoo_has_processing_stopped\0").unwrap();
-					Mutex::new(*cuckoo_has_processing_stopped.into_raw())
-				},
+	/// The number of nonces in the plugin's proofs, as declared in its
+	/// [capabilities](#method.capabilities) when it was loaded or a
+	/// parameter was last set. Buffers passed to its calls must hold at
+	/// least this many.
 
-				cuckoo_get_stats: {
-					let cuckoo_get_stats: libloading::Symbol<CuckooGetStats> =
-						loaded_library.get(b"cuckoo_get_stats\0").unwrap();
-					Mutex::new(*cuckoo_get_stats.into_raw())
-				},
+	pub fn proof_size(&self) -> usize {
+		self.proof_size.load(Ordering::SeqCst)
+	}
 
-				loaded_library: Mutex::new(loaded_library),
-			};
+	/// How nonces are laid out in the bytes passed to and from the plugin,
+	/// with headers and in solutions. Big-Endian unless set otherwise.
 
-			ret_val.call_cuckoo_init();
-			return Ok(ret_val);
-		}
+	pub fn nonce_encoding(&self) -> NonceEncoding {
+		*self.nonce_encoding.lock().unwrap()
 	}
 
-	/// #Description
-	///
-	/// Unloads the currently loaded plugin and all symbols.
-	///
-	/// #Arguments
-	///
-	/// None
-	///
-	/// #Returns
-	///
-	/// Nothing
-	///
+	/// Sets how nonces are laid out in the bytes passed to and from the
+	/// plugin. Solutions read from then on are decoded with it, and the
+	/// miner encodes the nonces it pushes with it.
 
-	pub fn unload(&self) {
-		let cuckoo_get_parameter_ref = self.cuckoo_get_parameter.lock().unwrap();
-		drop(cuckoo_get_parameter_ref);
+	pub fn set_nonce_encoding(&self, encoding: NonceEncoding) {
+		*self.nonce_encoding.lock().unwrap() = encoding;
+	}
 
-		let cuckoo_set_parameter_ref = self.cuckoo_set_parameter.lock().unwrap();
-		drop(cuckoo_set_parameter_ref);
+	/// Whether the plugin's edge indices may not fit in a u32, as declared
+	/// in its [capabilities](#method.capabilities) when it was loaded or a
+	/// parameter was last set. Its graphs must then be searched through
+	/// the 64-bit calls.
 
-		let cuckoo_parameter_list_ref = self.cuckoo_parameter_list.lock().unwrap();
-		drop(cuckoo_parameter_list_ref);
+	pub fn has_wide_edges(&self) -> bool {
+		self.wide_edges.load(Ordering::SeqCst)
+	}
 
-		let cuckoo_call_ref = self.cuckoo_call.lock().unwrap();
-		drop(cuckoo_call_ref);
+	/// The kind of graph the plugin searches, as declared in its
+	/// [capabilities](#method.capabilities) when it was loaded or a
+	/// parameter was last set. Only proofs in cuckoo graphs are verified.
 
-		let cuckoo_is_queue_under_limit_ref = self.cuckoo_is_queue_under_limit.lock().unwrap();
-		drop(cuckoo_is_queue_under_limit_ref);
+	pub fn graph_type(&self) -> GraphType {
+		*self.graph_type.lock().unwrap()
+	}
 
-		let cuckoo_clear_queues_ref = self.cuckoo_clear_queues.lock().unwrap();
-		drop(cuckoo_clear_queues_ref);
+	// Reads the proof size, edge bits and graph type from the plugin's
+	// capabilities, if it reports them. Older plugins' proofs are always
+	// the default size, and their edges fit in a u32.
 
-		let cuckoo_push_to_input_queue_ref = self.cuckoo_push_to_input_queue.lock().unwrap();
-		drop(cuckoo_push_to_input_queue_ref);
+	fn refresh_capabilities(&self) {
+		if self.optional_symbol::<CuckooCapabilities>("cuckoo_capabilities").is_none() {
+			*self.graph_type.lock().unwrap() = self.legacy_capabilities().graph_type;
+			return;
+		}
+		match self.capabilities() {
+			Ok(c) => {
+				*self.graph_type.lock().unwrap() = c.graph_type;
+				self.proof_size.store(c.proof_size as usize, Ordering::SeqCst);
+				let wide = c.edge_bits.map_or(false, |b| b > MAX_32_BIT_EDGE_BITS);
+				self.wide_edges.store(wide, Ordering::SeqCst);
+			}
+			Err(e) => warn!(
+				"Keeping {}-nonce proofs for {}: {:?}",
+				self.proof_size(),
+				self.lib_full_path,
+				e
+			),
+		}
+	}
 
-		let cuckoo_read_from_output_queue_ref = self.cuckoo_read_from_output_queue.lock().unwrap();
-		drop(cuckoo_read_from_output_queue_ref);
+	// Fails if the plugin's edge indices don't fit in a u32, but it can't
+	// return them any other way
 
-		let cuckoo_start_processing_ref = self.cuckoo_start_processing.lock().unwrap();
-		drop(cuckoo_start_processing_ref);
+	fn check_edge_bits(&self) -> Result<(), CuckooMinerError> {
+		let edge_bits = match self.edge_bits() {
+			Some(b) if b > MAX_32_BIT_EDGE_BITS => b,
+			_ => return Ok(()),
+		};
+		let missing = match (self.cuckoo_call_64.is_some(), self.cuckoo_read_from_output_queue_64.is_some()) {
+			(true, true) => return Ok(()),
+			(false, _) => "cuckoo_call_64",
+			(true, false) => "cuckoo_read_from_output_queue_64",
+		};
+		error!(
+			"{} searches graphs with {} edge bits, whose edge indices don't fit in the 32-bit \
+			 plugin calls, but doesn't export {}",
+			self.lib_full_path,
+			edge_bits,
+			missing
+		);
+		Err(CuckooMinerError::PluginSymbolMissing {
+			path: self.lib_full_path.clone(),
+			symbol: String::from(missing),
+		})
+	}
 
-		let cuckoo_stop_processing_ref = self.cuckoo_stop_processing.lock().unwrap();
-		drop(cuckoo_stop_processing_ref);
+	// Whether a buffer can hold the plugin's proofs, so the plugin can't
+	// write past its end
 
-		let cuckoo_reset_processing_ref = self.cuckoo_reset_processing.lock().unwrap();
-		drop(cuckoo_reset_processing_ref);
+	fn fits_proof<T>(&self, solutions: &[T]) -> bool {
+		if solutions.len() < self.proof_size() {
+			warn!(
+				"Refusing plugin call to {}: buffer for {} proof nonces, but it needs {}",
+				self.lib_full_path,
+				solutions.len(),
+				self.proof_size()
+			);
+			return false;
+		}
+		true
+	}
 
-		let cuckoo_has_processing_stopped_ref = self.cuckoo_has_processing_stopped.lock().unwrap();
-		drop(cuckoo_has_processing_stopped_ref);
+	/// The plugin's interface version as `(major << 16) | minor`, or 0 for
+	/// a legacy plugin loaded without one
 
-		let cuckoo_get_stats_ref = self.cuckoo_get_stats.lock().unwrap();
-		drop(cuckoo_get_stats_ref);
+	pub fn api_version(&self) -> u32 {
+		self.api_version
+	}
+
+	/// Returns the plugin's current lifecycle
+	/// [PluginState](enum.PluginState.html)
 
-		let loaded_library_ref = self.loaded_library.lock().unwrap();
-		drop(loaded_library_ref);
+	pub fn state(&self) -> PluginState {
+		self.state.lock().unwrap().state()
 	}
 
 	/// #Description
 	///
-	/// Initialises the cuckoo plugin, mostly allowing it to write a list of
-	/// its accepted parameters. This should be called just after the plugin
-	/// is loaded, and before anything else is called.
+	/// Unloads the currently loaded plugin and all symbols. If the plugin
+	/// is processing, it's stopped and reset first, waiting up to five
+	/// seconds for it to stop. If it doesn't stop in time, the plugin is
+	/// left loaded, as its threads may still be running. This is called
+	/// automatically when the PluginLibrary is dropped, and calling it
+	/// more than once is safe.
 	///
 	/// #Arguments
 	///
-	/// * None
+	/// None
 	///
 	/// #Returns
 	///
-	/// * Nothing
+	/// Nothing
 	///
-	/// #Example
+
+	pub fn unload(&self) {
+		let state = self.state();
+		if state == PluginState::Unloaded {
+			return;
+		}
+		// Processing threads inside the plugin must be gone before it's
+		// unloaded, so stop them first if the caller hasn't
+		if state == PluginState::Processing || state == PluginState::Stopping {
+			warn!("Unloading {} while processing, stopping first", self.lib_full_path);
+			if let Err(e) = self.stop_and_wait(UNLOAD_STOP_TIMEOUT) {
+				error!("Not unloading: {:?}", e);
+			}
+		} else if state == PluginState::Stopped {
+			self.call_cuckoo_reset_processing();
+		}
+		// waits for calls in progress on other threads, and holds off new
+		// ones until the library is gone
+		let _lifecycle = self.lifecycle.write().unwrap();
+		if !self.guard(PluginOp::Unload) {
+			return;
+		}
+		if let Some(set_logger) = self.cuckoo_set_logger {
+			unsafe { set_logger(None, ptr::null_mut()) };
+		}
+		// contexts still held are dead once the library is gone, so free
+		// their memory while it's still there
+		let contexts: Vec<usize> = self.contexts.lock().unwrap().drain().collect();
+		if let Some(destroy) = self.cuckoo_destroy_context {
+			for c in contexts {
+				unsafe { destroy(c as *mut c_void) };
+			}
+		}
+		self.log_target.lock().unwrap().take();
+		let loaded_library = self.loaded_library.lock().unwrap().take();
+		drop(loaded_library);
+	}
+
+	/// #Description
+	///
+	/// Gives up on a plugin whose processing won't stop, e.g. because an
+	/// internal thread has hung. All further calls are refused, as after
+	/// [unload](#method.unload), but the library itself is never unloaded
+	/// from the process, since its threads may still be running in it.
+	/// Only use this once a graceful stop has failed.
+	///
+	/// #Arguments
+	///
+	/// None
+	///
+	/// #Returns
+	///
+	/// Nothing
+	///
+
+	pub fn force_unload(&self) {
+		warn!("Force unloading {}, leaving its library loaded", self.lib_full_path);
+		self.state.lock().unwrap().force_unload();
+		// the plugin may be stuck in a call using one, so contexts are
+		// leaked with the library
+		self.contexts.lock().unwrap().clear();
+		if let Some(l) = self.loaded_library.lock().unwrap().take() {
+			mem::forget(l);
+		}
+		self.leak_log_target();
+	}
+
+	/// #Description
+	///
+	/// Initialises the cuckoo plugin, mostly allowing it to write a list of
+	/// its accepted parameters. This should be called just after the plugin
+	/// is loaded, and before anything else is called.
+	///
+	/// #Arguments
+	///
+	/// * None
+	///
+	/// #Returns
+	///
+	/// * Nothing
+	///
+	/// #Example
 	///
 	/// ```
 	///  # use cuckoo_miner::PluginLibrary;
@@ -311,6 +1331,10 @@ impl PluginLibrary {
 	///
 
 	pub fn call_cuckoo_init(&self) {
+		let _lifecycle = self.lifecycle.write().unwrap();
+		if !self.guard(PluginOp::Init) {
+			return;
+		}
 		let cuckoo_init_ref = self.cuckoo_init.lock().unwrap();
 		unsafe {
 			cuckoo_init_ref();
@@ -321,7 +1345,7 @@ impl PluginLibrary {
 	///
 	/// Call to the cuckoo_call function of the currently loaded plugin, which
 	/// will perform a Cuckoo Cycle on the given seed, returning the first 
-	/// solution (a length 42 cycle) that is found. The implementation details 
+	/// solution (a cycle of the plugin's proof size, usually 42) that is found. The implementation details 
 	/// are dependent on particular loaded plugin.
 	///
 	/// #Arguments
@@ -331,17 +1355,17 @@ impl PluginLibrary {
 	/// in the graph. In practice, this is a Grin blockheader, 
 	/// but from the plugin's perspective this can be anything.
 	///
-	/// * `solutions` (OUT) A caller-allocated array of 42 unsigned bytes. This
-	/// currently must be of size 42, corresponding to a conventional 
-	/// cuckoo-cycle solution length. If a solution is found, the solution 
-	/// nonces will be stored in this array, otherwise, they will be left 
-	/// untouched.
+	/// * `solutions` (OUT) A caller-allocated array of u32s, at least as
+	/// long as the plugin's [proof_size](#method.proof_size), usually 42.
+	/// If a solution is found, the solution nonces will be stored in this
+	/// array, otherwise, they will be left untouched.
 	///
 	/// #Returns
 	///
-	/// 1 if a solution is found, with the 42 solution nonces contained
+	/// 1 if a solution is found, with the solution nonces contained
 	/// within `sol_nonces`. 0 if no solution is found and `sol_nonces`
-	/// remains untouched.
+	/// remains untouched. CALL_REFUSED if `solutions` is too short for
+	/// the plugin's proofs.
 	///
 	/// #Example
 	///
@@ -367,34 +1391,1175 @@ impl PluginLibrary {
 	/// ```
 	///
 
-	pub fn call_cuckoo(&self, header: &[u8], cuckoo_size: &mut u32, solutions: &mut [u32; 42]) -> u32 {
+	pub fn call_cuckoo(&self, header: &[u8], cuckoo_size: &mut u32, solutions: &mut [u32]) -> u32 {
+		let _lifecycle = self.lifecycle.read().unwrap();
+		if !self.fits_proof(solutions) || !self.guard(PluginOp::Call) {
+			return CALL_REFUSED;
+		}
 		let cuckoo_call_ref = self.cuckoo_call.lock().unwrap();
 		unsafe { cuckoo_call_ref(header.as_ptr(), header.len() as u32, cuckoo_size, solutions.as_mut_ptr()) }
 	}
 
+	/// Whether the plugin exports `cuckoo_call_cancellable`, so it can be
+	/// called with [call_cuckoo_cancellable](#method.call_cuckoo_cancellable)
+
+	pub fn has_cancellable_call(&self) -> bool {
+		self.cuckoo_call_cancellable.is_some()
+	}
+
+	/// #Description
+	///
+	/// As [call_cuckoo](#method.call_cuckoo), but through the plugin's
+	/// optional cuckoo_call_cancellable function, which polls a flag while
+	/// it searches the graph and gives up once it's set. The plugin is
+	/// passed a pointer to the flag's byte, which it only reads, and only
+	/// until it returns. It returns within [CANCEL_BOUND](constant.CANCEL_BOUND.html)
+	/// of the flag being set.
+	///
+	/// #Arguments
+	///
+	/// * `header` (IN) As for [call_cuckoo](#method.call_cuckoo)
+	///
+	/// * `cuckoo_size` (OUT) The size of the graph searched
+	///
+	/// * `solutions` (OUT) As for [call_cuckoo](#method.call_cuckoo)
+	///
+	/// * `cancel` (IN) Cancels the call when set, e.g. from another thread
+	///
+	/// #Returns
+	///
+	/// As for [call_cuckoo](#method.call_cuckoo), or CALL_CANCELLED if
+	/// the call was cancelled, in which case `solutions` is untouched.
+	/// CALL_REFUSED if the plugin doesn't export the function.
+	///
+
+	pub fn call_cuckoo_cancellable(
+		&self,
+		header: &[u8],
+		cuckoo_size: &mut u32,
+		solutions: &mut [u32],
+		cancel: &AtomicBool,
+	) -> u32 {
+		let _lifecycle = self.lifecycle.read().unwrap();
+		let cancellable_ref = match self.cuckoo_call_cancellable {
+			Some(ref c) => c.lock().unwrap(),
+			None => {
+				warn!("{} doesn't export cuckoo_call_cancellable", self.lib_full_path);
+				return CALL_REFUSED;
+			}
+		};
+		if !self.fits_proof(solutions) || !self.guard(PluginOp::Call) {
+			return CALL_REFUSED;
+		}
+		// an AtomicBool is a single byte, as a bool is
+		let cancel = cancel as *const AtomicBool as *const u8;
+		unsafe {
+			cancellable_ref(
+				header.as_ptr(),
+				header.len() as u32,
+				cuckoo_size,
+				solutions.as_mut_ptr(),
+				cancel,
+			)
+		}
+	}
+
+	/// Whether the plugin exports `cuckoo_create_context`,
+	/// `cuckoo_call_ctx` and `cuckoo_destroy_context`, so a
+	/// [SolverContext](struct.SolverContext.html) can keep its solver's
+	/// buffers between calls
+
+	pub fn has_persistent_context(&self) -> bool {
+		self.cuckoo_create_context.is_some() && self.cuckoo_call_ctx.is_some() &&
+			self.cuckoo_destroy_context.is_some()
+	}
+
+	/// #Description
+	///
+	/// Creates a [SolverContext](struct.SolverContext.html) for searching
+	/// a run of graphs one after another, e.g. for successive nonces, so
+	/// the plugin sets up its solver's buffers once rather than on every
+	/// call. Plugins without the context calls, or which fail to create
+	/// one, get a context which makes plain
+	/// [call_cuckoo_cancellable](#method.call_cuckoo_cancellable) or
+	/// [call_cuckoo](#method.call_cuckoo) calls instead, so the context
+	/// can be used the same way for any plugin.
+	///
+	/// The context's memory is released when it's dropped, or when the
+	/// plugin is unloaded if that's sooner.
+	///
+	/// #Arguments
+	///
+	/// * None
+	///
+	/// #Returns
+	///
+	/// The context
+	///
+	/// #Example
+	///
+	/// ```
+	///  # use cuckoo_miner::PluginLibrary;
+	///  # use std::env;
+	///  # use std::path::PathBuf;
+	///  # static DLL_SUFFIX: &str = ".cuckooplugin";
+	///  # let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+	///  # d.push(format!("./target/debug/plugins/lean_cpu_16{}", DLL_SUFFIX).as_str());
+	///  # let plugin_path = d.to_str().unwrap();
+	///  let pl = PluginLibrary::new(plugin_path).unwrap();
+	///  let context = pl.solver_context();
+	///  let mut solution = [0u32; 42];
+	///  let mut cuckoo_size = 0;
+	///  for i in 0..10u8 {
+	///    let header = [i; 32];
+	///    if context.call_cuckoo(&header, &mut cuckoo_size, &mut solution, None) == 1 {
+	///      println!("Solution Found for header {}", i);
+	///    }
+	///  }
+	/// ```
+	///
+
+	pub fn solver_context<'a>(&'a self) -> SolverContext<'a> {
+		let handle = match (self.cuckoo_create_context, self.has_persistent_context()) {
+			(Some(create), true) => {
+				let _lifecycle = self.lifecycle.read().unwrap();
+				if !self.guard(PluginOp::Call) {
+					None
+				} else {
+					match unsafe { create() } {
+						c if c.is_null() => {
+							warn!(
+								"{} couldn't create a solver context, so sets up its solver on every call",
+								self.lib_full_path
+							);
+							None
+						}
+						c => {
+							self.contexts.lock().unwrap().insert(c as usize);
+							Some(c as usize)
+						}
+					}
+				}
+			}
+			_ => None,
+		};
+		SolverContext {
+			library: self,
+			handle: handle,
+		}
+	}
+
+	/// Whether the plugin exports `cuckoo_call_with_keys`, so it can be
+	/// called with [call_cuckoo_with_keys](#method.call_cuckoo_with_keys)
+
+	pub fn has_keyed_call(&self) -> bool {
+		self.cuckoo_call_with_keys.is_some()
+	}
+
+	/// Whether the plugin exports the 64-bit calls,
+	/// [call_cuckoo_64](#method.call_cuckoo_64) and
+	/// [call_cuckoo_read_from_output_queue_64](#method.call_cuckoo_read_from_output_queue_64),
+	/// which return edge indices as u64s. Plugins whose graphs have more
+	/// than 32 edge bits must export both.
+
+	pub fn has_64_bit_calls(&self) -> bool {
+		self.cuckoo_call_64.is_some() && self.cuckoo_read_from_output_queue_64.is_some()
+	}
+
+	/// #Description
+	///
+	/// As [call_cuckoo](#method.call_cuckoo), but through the plugin's
+	/// optional cuckoo_call_64 function, which returns the solution's
+	/// edge indices as u64s, for graphs too large for them to fit in a u32.
+	///
+	/// #Arguments
+	///
+	/// * `header` (IN) As for [call_cuckoo](#method.call_cuckoo)
+	///
+	/// * `cuckoo_size` (OUT) The size of the graph searched, as reported
+	/// by the plugin
+	///
+	/// * `solutions` (OUT) A caller-allocated array of u64s, at least as
+	/// long as the plugin's [proof_size](#method.proof_size)
+	///
+	/// #Returns
+	///
+	/// As for [call_cuckoo](#method.call_cuckoo). CALL_REFUSED if the
+	/// plugin doesn't export `cuckoo_call_64`, or `solutions` is too short.
+	///
+
+	pub fn call_cuckoo_64(&self, header: &[u8], cuckoo_size: &mut u32, solutions: &mut [u64]) -> u32 {
+		let _lifecycle = self.lifecycle.read().unwrap();
+		let cuckoo_call_64_ref = match self.cuckoo_call_64 {
+			Some(ref c) => c.lock().unwrap(),
+			None => {
+				warn!("{} doesn't export cuckoo_call_64", self.lib_full_path);
+				return CALL_REFUSED;
+			}
+		};
+		if !self.fits_proof(solutions) || !self.guard(PluginOp::Call) {
+			return CALL_REFUSED;
+		}
+		unsafe { cuckoo_call_64_ref(header.as_ptr(), header.len() as u32, cuckoo_size, solutions.as_mut_ptr()) }
+	}
+
+	/// #Description
+	///
+	/// As [call_cuckoo](#method.call_cuckoo), but passes the plugin the
+	/// SipHash keys to generate the graph from, rather than a header for
+	/// it to derive them from. The keys are usually derived with
+	/// [siphash_keys](fn.siphash_keys.html), but any other header hashing
+	/// scheme can be used.
+	///
+	/// #Arguments
+	///
+	/// * `keys` (IN) The four SipHash keys
+	///
+	/// * `cuckoo_size` (OUT) The size of the graph searched, as reported
+	/// by the plugin
+	///
+	/// * `solutions` (OUT) As for [call_cuckoo](#method.call_cuckoo)
+	///
+	/// #Returns
+	///
+	/// As for [call_cuckoo](#method.call_cuckoo). CALL_REFUSED if the
+	/// plugin doesn't export `cuckoo_call_with_keys`, which
+	/// [has_keyed_call](#method.has_keyed_call) reports, or `solutions` is
+	/// too short.
+	///
+	/// #Example
+	///
+	/// ```
+	///  # use cuckoo_miner::{PluginLibrary, siphash_keys};
+	///  # use std::env;
+	///  # use std::path::PathBuf;
+	///  # static DLL_SUFFIX: &str = ".cuckooplugin";
+	///  # let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+	///  # d.push(format!("./target/debug/plugins/lean_cpu_16{}", DLL_SUFFIX).as_str());
+	///  # let plugin_path = d.to_str().unwrap();
+	///  let pl = PluginLibrary::new(plugin_path).unwrap();
+	///  let header:[u8;32] = [0;32];
+	///  let mut solution:[u32; 42] = [0;42];
+	///  let mut cuckoo_size = 0;
+	///  let result = match pl.has_keyed_call() {
+	///    true => pl.call_cuckoo_with_keys(&siphash_keys(&header), &mut cuckoo_size, &mut solution),
+	///    false => pl.call_cuckoo(&header, &mut cuckoo_size, &mut solution),
+	///  };
+	/// ```
+	///
+
+	pub fn call_cuckoo_with_keys(&self, keys: &[u64; 4], cuckoo_size: &mut u32, solutions: &mut [u32]) -> u32 {
+		let _lifecycle = self.lifecycle.read().unwrap();
+		let cuckoo_call_with_keys_ref = match self.cuckoo_call_with_keys {
+			Some(ref c) => c.lock().unwrap(),
+			None => {
+				warn!("{} doesn't export cuckoo_call_with_keys", self.lib_full_path);
+				return CALL_REFUSED;
+			}
+		};
+		if !self.fits_proof(solutions) || !self.guard(PluginOp::Call) {
+			return CALL_REFUSED;
+		}
+		unsafe { cuckoo_call_with_keys_ref(keys.as_ptr(), cuckoo_size, solutions.as_mut_ptr()) }
+	}
+
 	/// #Description
 	///
 	/// Call to the cuckoo_call_parameter_list function of the currently loaded
 	/// plugin, which will provide an informative JSON array of the parameters that the
 	/// plugin supports, as well as their descriptions and range of values.
 	///
-	/// #Arguments
+	/// #Arguments
+	///
+	/// * `param_list_bytes` (OUT) A reference to a block of [u8] bytes to fill
+	/// with the JSON result array
+	///
+	/// * `param_list_len` (IN-OUT) When called, this should contain the
+	/// maximum number of bytes the plugin should write to `param_list_bytes`.
+	/// Upon return, this is filled with the number of bytes that were written to 
+	/// `param_list_bytes`.
+	///
+	/// #Returns
+	///
+	/// 0 if okay, with the result is stored in `param_list_bytes`
+	/// 3 if the provided array is too short
+	///
+	/// #Example
+	///
+	/// ```
+	///  # use cuckoo_miner::PluginLibrary;
+	///  # use std::env;
+	///  # use std::path::PathBuf;
+	///  # static DLL_SUFFIX: &str = ".cuckooplugin";
+	///  # let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+	///  # d.push(format!("./target/debug/plugins/lean_cpu_16{}", DLL_SUFFIX).as_str());
+	///  # let plugin_path = d.to_str().unwrap();
+	///  let pl=PluginLibrary::new(plugin_path).unwrap();
+	///  pl.call_cuckoo_init();
+	///  let mut param_list_bytes:[u8;1024]=[0;1024];
+	///  let mut param_list_len=param_list_bytes.len() as u32;
+	///  //get a list of json parameters
+	///  let parameter_list=pl.call_cuckoo_parameter_list(&mut param_list_bytes,
+	///    &mut param_list_len);
+	/// ```
+	///
+
+	pub fn call_cuckoo_parameter_list(
+		&self,
+		param_list_bytes: &mut [u8],
+		param_list_len: &mut u32,
+	) -> u32 {
+		let _lifecycle = self.lifecycle.read().unwrap();
+		if !self.guard(PluginOp::ParameterList) {
+			return CALL_REFUSED;
+		}
+		let cuckoo_parameter_list_ref = self.cuckoo_parameter_list.lock().unwrap();
+		unsafe { cuckoo_parameter_list_ref(param_list_bytes.as_mut_ptr(), param_list_len) }
+	}
+
+	/// #Description
+	///
+	/// Retrieves and parses the plugin's parameter list, growing the
+	/// buffer internally if the plugin reports it's too short.
+	///
+	/// #Returns
+	///
+	/// * `Ok(Vec<CuckooPluginParameter>)` describing each of the plugin's
+	/// parameters if successful
+	/// * a [CuckooMinerError](enum.CuckooMinerError.html) if the list can't
+	/// be retrieved, or a PluginProcessingError if the plugin returns
+	/// malformed JSON
+	///
+	/// #Example
+	///
+	/// ```
+	///  # use cuckoo_miner::PluginLibrary;
+	///  # use std::env;
+	///  # use std::path::PathBuf;
+	///  # static DLL_SUFFIX: &str = ".cuckooplugin";
+	///  # let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+	///  # d.push(format!("./target/debug/plugins/lean_cpu_16{}", DLL_SUFFIX).as_str());
+	///  # let plugin_path = d.to_str().unwrap();
+	///  let pl=PluginLibrary::new(plugin_path).unwrap();
+	///  for p in pl.parameters().unwrap() {
+	///    println!("{} ({}-{}): {}", p.name, p.min_value, p.max_value, p.description);
+	///  }
+	/// ```
+	///
+
+	pub fn parameters(&self) -> Result<Vec<CuckooPluginParameter>, CuckooMinerError> {
+		let mut size = INITIAL_BUFFER_SIZE;
+		loop {
+			let mut param_list_bytes = vec![0u8; size];
+			let mut param_list_len = size as u32;
+			let code = self.call_cuckoo_parameter_list(&mut param_list_bytes, &mut param_list_len);
+			if code == 3 && size < MAX_BUFFER_SIZE {
+				size *= 2;
+				continue;
+			}
+			PluginCall::ParameterList.check(code, &self.lib_full_path)?;
+			let param_list_json = buffer_to_string(param_list_bytes, param_list_len)?;
+			return json::from_str(&param_list_json).map_err(|e| {
+				CuckooMinerError::PluginProcessingError(format!(
+					"Error parsing parameter list from {}: {}",
+					self.lib_full_path,
+					e
+				))
+			});
+		}
+	}
+
+	/// #Description
+	///
+	/// Retrieves the value of a parameter from the currently loaded plugin
+	///
+	/// #Arguments
+	///
+	/// * `name_bytes` (IN) A reference to a block of [u8] bytes storing the
+	/// parameter name
+	///
+	/// * `device_id` (IN) The device ID to which the parameter applies (if applicable)
+	/// * `value` (OUT) A reference where the parameter value will be stored
+	///
+	/// #Returns
+	///
+	/// 0 if the parameter was retrived, and the result is stored in `value`
+	/// 1 if the parameter does not exist
+	/// 4 if the provided parameter name was too long
+	///
+	/// #Example
+	/// ```
+	///  # use cuckoo_miner::PluginLibrary;
+	///  # use std::env;
+	///  # use std::path::PathBuf;
+	///  # static DLL_SUFFIX: &str = ".cuckooplugin";
+	///  # let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+	///  # d.push(format!("./target/debug/plugins/lean_cpu_16{}", DLL_SUFFIX).as_str());
+	///  # let plugin_path = d.to_str().unwrap();
+	///  let pl=PluginLibrary::new(plugin_path).unwrap();
+	///  pl.call_cuckoo_init();
+	///  let name = "NUM_THREADS";
+	///  let mut num_threads:u32 = 0;
+	///  let ret_val = pl.call_cuckoo_get_parameter(name.as_bytes(), 0, &mut num_threads);
+	/// ```
+	///
+
+	pub fn call_cuckoo_get_parameter(&self, name_bytes: &[u8], device_id: u32, value: &mut u32) -> u32 {
+		let _lifecycle = self.lifecycle.read().unwrap();
+		if !self.guard(PluginOp::GetParameter) {
+			return CALL_REFUSED;
+		}
+		let cuckoo_get_parameter_ref = self.cuckoo_get_parameter.lock().unwrap();
+		unsafe { cuckoo_get_parameter_ref(name_bytes.as_ptr(), name_bytes.len() as u32, device_id, value) }
+	}
+
+	/// #Description
+	///
+	/// Retrieves the value of a parameter from the currently loaded plugin,
+	/// translating the plugin's return code into a Result.
+	///
+	/// #Arguments
+	///
+	/// * `name` (IN) The parameter name
+	///
+	/// #Returns
+	///
+	/// * `Ok(value)` if the parameter was retrieved
+	/// * a [CuckooMinerError](enum.CuckooMinerError.html) describing why
+	/// the parameter couldn't be read otherwise
+	///
+	/// #Example
+	/// ```
+	///  # use cuckoo_miner::PluginLibrary;
+	///  # use std::env;
+	///  # use std::path::PathBuf;
+	///  # static DLL_SUFFIX: &str = ".cuckooplugin";
+	///  # let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+	///  # d.push(format!("./target/debug/plugins/lean_cpu_16{}", DLL_SUFFIX).as_str());
+	///  # let plugin_path = d.to_str().unwrap();
+	///  let pl=PluginLibrary::new(plugin_path).unwrap();
+	///  let num_threads = pl.get_parameter("NUM_THREADS").unwrap();
+	/// ```
+	///
+
+	pub fn get_parameter(&self, name: &str) -> Result<u32, CuckooMinerError> {
+		let mut value = 0;
+		let code = self.call_cuckoo_get_parameter(name.as_bytes(), 0, &mut value);
+		PluginCall::GetParameter.check(code, name)?;
+		Ok(value)
+	}
+
+	/// #Description
+	///
+	/// As [call_cuckoo_get_parameter](#method.call_cuckoo_get_parameter),
+	/// for parameters whose values can exceed u32::MAX, such as memory
+	/// sizes in bytes. The plugin's `cuckoo_get_parameter_u64` is looked
+	/// up on first use, as older plugins don't export it.
+	///
+	/// #Returns
+	///
+	/// * `Ok(code)` with the plugin's return code, as for
+	/// [call_cuckoo_get_parameter](#method.call_cuckoo_get_parameter).
+	/// CALL_REFUSED if the call isn't legal in the plugin's state.
+	/// * a NotSupported error if the plugin doesn't export the function
+	///
+
+	pub fn call_cuckoo_get_parameter_u64(
+		&self,
+		name_bytes: &[u8],
+		device_id: u32,
+		value: &mut u64,
+	) -> Result<u32, CuckooMinerError> {
+		let _lifecycle = self.lifecycle.read().unwrap();
+		let f: CuckooGetParameterU64 = match self.optional_symbol("cuckoo_get_parameter_u64") {
+			Some(f) => f,
+			None => {
+				return Err(CuckooMinerError::NotSupported(format!(
+					"{} doesn't export cuckoo_get_parameter_u64",
+					self.lib_full_path
+				)))
+			}
+		};
+		if !self.guard(PluginOp::GetParameter) {
+			return Ok(CALL_REFUSED);
+		}
+		// serialised with the 32-bit call, as both touch the same values
+		let _get_parameter = self.cuckoo_get_parameter.lock().unwrap();
+		Ok(unsafe { f(name_bytes.as_ptr(), name_bytes.len() as u32, device_id, value) })
+	}
+
+	/// #Description
+	///
+	/// As [get_parameter](#method.get_parameter), through the plugin's
+	/// 64-bit call
+	///
+	/// #Returns
+	///
+	/// * `Ok(value)` if the parameter was retrieved
+	/// * a NotSupported error if the plugin has no 64-bit parameter calls
+	/// * another [CuckooMinerError](enum.CuckooMinerError.html) describing
+	/// why the parameter couldn't be read otherwise
+	///
+
+	pub fn get_parameter_u64(&self, name: &str) -> Result<u64, CuckooMinerError> {
+		let mut value = 0;
+		let code = self.call_cuckoo_get_parameter_u64(name.as_bytes(), 0, &mut value)?;
+		PluginCall::GetParameter.check(code, name)?;
+		Ok(value)
+	}
+
+	/// #Description
+	///
+	/// Reads a parameter the plugin declares in its parameter list, through
+	/// the 64-bit call if the parameter's declared maximum exceeds
+	/// u32::MAX, and the 32-bit call otherwise.
+	///
+	/// #Returns
+	///
+	/// * `Ok(value)` if the parameter was retrieved
+	/// * ParameterNotFoundError if the plugin doesn't declare the parameter
+	/// * another [CuckooMinerError](enum.CuckooMinerError.html) describing
+	/// why the parameter couldn't be read otherwise
+	///
+
+	pub fn get_parameter_checked(&self, name: &str) -> Result<u64, CuckooMinerError> {
+		match self.declared_parameter(name)?.is_64_bit() {
+			true => self.get_parameter_u64(name),
+			false => self.get_parameter(name).map(|v| v as u64),
+		}
+	}
+
+	/// Sets the value of a parameter in the currently loaded plugin
+	///
+	/// #Arguments
+	///
+	/// * `name_bytes` (IN) A reference to a block of [u8] bytes storing the
+	/// parameter name
+	///
+	/// * `device_id` (IN) The deviceID to which the parameter applies (if applicable)
+	/// * `value` (IN) The value to which to set the parameter
+	///
+	/// #Returns
+	///
+	/// 0 if the parameter was retrieved, and the result is stored in `value`
+	/// 1 if the parameter does not exist
+	/// 2 if the parameter exists, but the provided value is outside the 
+	/// allowed range determined by the plugin
+	/// 4 if the provided parameter name is too long
+	///
+	/// #Example
+	///
+	/// ```
+	///  # use cuckoo_miner::PluginLibrary;
+	///  # use std::env;
+	///  # use std::path::PathBuf;
+	///  # static DLL_SUFFIX: &str = ".cuckooplugin";
+	///  # let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+	///  # d.push(format!("./target/debug/plugins/lean_cpu_16{}", DLL_SUFFIX).as_str());
+	///  # let plugin_path = d.to_str().unwrap();
+	///  let pl=PluginLibrary::new(plugin_path).unwrap();
+	///  let name = "NUM_THREADS";
+	///  let return_code = pl.call_cuckoo_set_parameter(name.as_bytes(), 0, 8);
+	/// ```
+	///
+
+	pub fn call_cuckoo_set_parameter(&self, name_bytes: &[u8], device_id: u32, value: u32) -> u32 {
+		let code = {
+			let _lifecycle = self.lifecycle.read().unwrap();
+			if !self.guard(PluginOp::SetParameter) {
+				return CALL_REFUSED;
+			}
+			let cuckoo_set_parameter_ref = self.cuckoo_set_parameter.lock().unwrap();
+			unsafe { cuckoo_set_parameter_ref(name_bytes.as_ptr(), name_bytes.len() as u32, device_id, value) }
+		};
+		// a parameter may change the plugin's proof size or edge bits
+		if code == 0 {
+			self.refresh_capabilities();
+		}
+		code
+	}
+
+	/// #Description
+	///
+	/// Sets the value of a parameter in the currently loaded plugin,
+	/// translating the plugin's return code into a Result.
+	///
+	/// #Arguments
+	///
+	/// * `name` (IN) The parameter name
+	/// * `value` (IN) The value to which to set the parameter
+	///
+	/// #Returns
+	///
+	/// * `Ok(())` if the parameter was set
+	/// * a [CuckooMinerError](enum.CuckooMinerError.html) describing why
+	/// the parameter couldn't be set otherwise
+	///
+	/// #Example
+	/// ```
+	///  # use cuckoo_miner::PluginLibrary;
+	///  # use std::env;
+	///  # use std::path::PathBuf;
+	///  # static DLL_SUFFIX: &str = ".cuckooplugin";
+	///  # let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+	///  # d.push(format!("./target/debug/plugins/lean_cpu_16{}", DLL_SUFFIX).as_str());
+	///  # let plugin_path = d.to_str().unwrap();
+	///  let pl=PluginLibrary::new(plugin_path).unwrap();
+	///  pl.set_parameter("NUM_THREADS", 16).unwrap();
+	/// ```
+	///
+
+	pub fn set_parameter(&self, name: &str, value: u32) -> Result<(), CuckooMinerError> {
+		let code = self.call_cuckoo_set_parameter(name.as_bytes(), 0, value);
+		PluginCall::SetParameter.check(code, &format!("{} = {}", name, value))
+	}
+
+	/// #Description
+	///
+	/// As [call_cuckoo_set_parameter](#method.call_cuckoo_set_parameter),
+	/// for parameters whose values can exceed u32::MAX, such as memory
+	/// sizes in bytes. The plugin's `cuckoo_set_parameter_u64` is looked
+	/// up on first use, as older plugins don't export it.
+	///
+	/// #Returns
+	///
+	/// * `Ok(code)` with the plugin's return code, as for
+	/// [call_cuckoo_set_parameter](#method.call_cuckoo_set_parameter).
+	/// CALL_REFUSED if the call isn't legal in the plugin's state.
+	/// * a NotSupported error if the plugin doesn't export the function
+	///
+
+	pub fn call_cuckoo_set_parameter_u64(
+		&self,
+		name_bytes: &[u8],
+		device_id: u32,
+		value: u64,
+	) -> Result<u32, CuckooMinerError> {
+		let _lifecycle = self.lifecycle.read().unwrap();
+		let f: CuckooSetParameterU64 = match self.optional_symbol("cuckoo_set_parameter_u64") {
+			Some(f) => f,
+			None => {
+				return Err(CuckooMinerError::NotSupported(format!(
+					"{} doesn't export cuckoo_set_parameter_u64",
+					self.lib_full_path
+				)))
+			}
+		};
+		if !self.guard(PluginOp::SetParameter) {
+			return Ok(CALL_REFUSED);
+		}
+		// serialised with the 32-bit call, as both touch the same values
+		let _set_parameter = self.cuckoo_set_parameter.lock().unwrap();
+		Ok(unsafe { f(name_bytes.as_ptr(), name_bytes.len() as u32, device_id, value) })
+	}
+
+	/// #Description
+	///
+	/// As [set_parameter](#method.set_parameter), through the plugin's
+	/// 64-bit call
+	///
+	/// #Returns
+	///
+	/// * `Ok(())` if the parameter was set
+	/// * a NotSupported error if the plugin has no 64-bit parameter calls
+	/// * another [CuckooMinerError](enum.CuckooMinerError.html) describing
+	/// why the parameter couldn't be set otherwise
+	///
+	/// #Example
+	/// ```
+	///  # use cuckoo_miner::PluginLibrary;
+	///  # use std::env;
+	///  # use std::path::PathBuf;
+	///  # static DLL_SUFFIX: &str = ".cuckooplugin";
+	///  # let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+	///  # d.push(format!("./target/debug/plugins/lean_cuda_30{}", DLL_SUFFIX).as_str());
+	///  # let plugin_path = d.to_str().unwrap();
+	///  let pl=PluginLibrary::new(plugin_path).unwrap();
+	///  //6 GiB
+	///  pl.set_parameter_u64("MEMORY", 6 << 30).unwrap();
+	/// ```
+	///
+
+	pub fn set_parameter_u64(&self, name: &str, value: u64) -> Result<(), CuckooMinerError> {
+		let code = self.call_cuckoo_set_parameter_u64(name.as_bytes(), 0, value)?;
+		PluginCall::SetParameter.check(code, &format!("{} = {}", name, value))
+	}
+
+	// Finds a parameter in the plugin's declared parameter list
+
+	fn declared_parameter(&self, name: &str) -> Result<CuckooPluginParameter, CuckooMinerError> {
+		match self.parameters()?.into_iter().find(|p| p.name == name) {
+			Some(p) => Ok(p),
+			None => Err(CuckooMinerError::ParameterNotFoundError(format!(
+				"{} is not declared by {}",
+				name,
+				self.lib_full_path
+			))),
+		}
+	}
+
+	/// #Description
+	///
+	/// Sets the value of a parameter in the currently loaded plugin, after
+	/// checking the value against the range the plugin declares for it in
+	/// its parameter list. Some plugins clamp out of range values rather
+	/// than rejecting them, so this gives the same behaviour regardless of
+	/// the plugin implementation. Parameters whose declared maximum exceeds
+	/// u32::MAX are set through the plugin's 64-bit call.
+	///
+	/// #Arguments
+	///
+	/// * `name` (IN) The parameter name
+	/// * `value` (IN) The value to which to set the parameter
+	///
+	/// #Returns
+	///
+	/// * `Ok(())` if the parameter was set
+	/// * ParameterOutOfRange if the value is outside the declared range, in
+	/// which case the plugin isn't called
+	/// * ParameterNotFoundError if the plugin doesn't declare the parameter
+	/// * another [CuckooMinerError](enum.CuckooMinerError.html) describing
+	/// why the parameter couldn't be set otherwise
+	///
+	/// #Example
+	/// ```
+	///  # use cuckoo_miner::PluginLibrary;
+	///  # use std::env;
+	///  # use std::path::PathBuf;
+	///  # static DLL_SUFFIX: &str = ".cuckooplugin";
+	///  # let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+	///  # d.push(format!("./target/debug/plugins/lean_cpu_16{}", DLL_SUFFIX).as_str());
+	///  # let plugin_path = d.to_str().unwrap();
+	///  let pl=PluginLibrary::new(plugin_path).unwrap();
+	///  pl.set_parameter_checked("NUM_THREADS", 4).unwrap();
+	/// ```
+	///
+
+	pub fn set_parameter_checked(&self, name: &str, value: u64) -> Result<(), CuckooMinerError> {
+		let param = self.declared_parameter(name)?;
+		if value < param.min_value || value > param.max_value {
+			return Err(CuckooMinerError::ParameterOutOfRange {
+				name: String::from(name),
+				value: value,
+				min: param.min_value,
+				max: param.max_value,
+			});
+		}
+		match param.is_64_bit() {
+			true => self.set_parameter_u64(name, value),
+			false => self.set_parameter(name, value as u32),
+		}
+	}
+
+	// Checks the plugin reports a device with the given id, so a parameter
+	// meant for a missing device isn't quietly applied to device 0 by
+	// plugins which ignore the id
+
+	fn check_device(&self, device_id: u32) -> Result<(), CuckooMinerError> {
+		match self.list_devices()?.iter().any(|d| d.device_id == device_id) {
+			true => Ok(()),
+			false => Err(CuckooMinerError::DeviceNotFoundError(format!(
+				"{} has no device {}",
+				self.lib_full_path,
+				device_id
+			))),
+		}
+	}
+
+	/// #Description
+	///
+	/// Retrieves the value of a parameter for a single device, e.g. one GPU
+	/// of a multi-GPU rig. Device 0 gives the same value as
+	/// [get_parameter](#method.get_parameter).
+	///
+	/// #Arguments
+	///
+	/// * `name` (IN) The parameter name
+	/// * `device_id` (IN) The device, as reported by
+	/// [list_devices](#method.list_devices)
+	///
+	/// #Returns
+	///
+	/// * `Ok(value)` if the parameter was retrieved
+	/// * DeviceNotFoundError if the plugin has no such device, or
+	/// `device_id` is [ALL_DEVICES](constant.ALL_DEVICES.html)
+	/// * another [CuckooMinerError](enum.CuckooMinerError.html) describing
+	/// why the parameter couldn't be read otherwise
+	///
+
+	pub fn get_parameter_for_device(&self, name: &str, device_id: u32) -> Result<u32, CuckooMinerError> {
+		if device_id == ALL_DEVICES {
+			return Err(CuckooMinerError::DeviceNotFoundError(format!(
+				"{} can't be read from all devices at once",
+				name
+			)));
+		}
+		self.check_device(device_id)?;
+		let mut value = 0;
+		let code = self.call_cuckoo_get_parameter(name.as_bytes(), device_id, &mut value);
+		PluginCall::GetParameter.check(code, &format!("{} on device {}", name, device_id))?;
+		Ok(value)
+	}
+
+	/// #Description
+	///
+	/// Sets the value of a parameter for a single device, so devices of
+	/// different capability can be tuned independently, or for every
+	/// device the plugin reports via
+	/// [ALL_DEVICES](constant.ALL_DEVICES.html). Device 0 behaves as
+	/// [set_parameter](#method.set_parameter), apart from checking the
+	/// device exists.
+	///
+	/// #Arguments
+	///
+	/// * `name` (IN) The parameter name
+	/// * `device_id` (IN) The device, as reported by
+	/// [list_devices](#method.list_devices), or ALL_DEVICES
+	/// * `value` (IN) The value to which to set the parameter
+	///
+	/// #Returns
+	///
+	/// * `Ok(())` if the parameter was set
+	/// * DeviceNotFoundError if the plugin has no such device, in which
+	/// case the plugin isn't called
+	/// * another [CuckooMinerError](enum.CuckooMinerError.html) describing
+	/// why the parameter couldn't be set otherwise. With ALL_DEVICES, the
+	/// devices before the failing one keep their new value.
+	///
+	/// #Example
+	/// ```
+	///  # use cuckoo_miner::{PluginLibrary, ALL_DEVICES};
+	///  # use std::env;
+	///  # use std::path::PathBuf;
+	///  # static DLL_SUFFIX: &str = ".cuckooplugin";
+	///  # let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+	///  # d.push(format!("./target/debug/plugins/lean_cuda_30{}", DLL_SUFFIX).as_str());
+	///  # let plugin_path = d.to_str().unwrap();
+	///  let pl=PluginLibrary::new(plugin_path).unwrap();
+	///  pl.set_parameter_for_device("N_TRIMS", ALL_DEVICES, 176).unwrap();
+	///  pl.set_parameter_for_device("GENU_BLOCKS", 1, 64).unwrap();
+	/// ```
+	///
+
+	pub fn set_parameter_for_device(&self, name: &str, device_id: u32, value: u32) -> Result<(), CuckooMinerError> {
+		let device_ids = match device_id {
+			ALL_DEVICES => self.list_devices()?.iter().map(|d| d.device_id).collect(),
+			id => {
+				self.check_device(id)?;
+				vec![id]
+			}
+		};
+		for id in device_ids {
+			let code = self.call_cuckoo_set_parameter(name.as_bytes(), id, value);
+			PluginCall::SetParameter.check(code, &format!("{} = {} on device {}", name, value, id))?;
+		}
+		Ok(())
+	}
+
+	/// #Description
+	///
+	/// Enables or disables a single device, e.g. to exclude a flaky GPU,
+	/// through the plugin's [USE_DEVICE](constant.USE_DEVICE_PARAM.html)
+	/// parameter. This is safe while processing, and takes effect from the
+	/// device's next graph attempt. Plugins without USE_DEVICE, such as
+	/// the CPU plugins, have a single device, which is disabled by no
+	/// longer feeding the plugin new headers. Disabled devices are still
+	/// reported by [stats](#method.stats), marked as not in use.
+	///
+	/// #Arguments
+	///
+	/// * `device_id` (IN) The device, as reported by
+	/// [list_devices](#method.list_devices)
+	/// * `enabled` (IN) Whether the device should mine
+	///
+	/// #Returns
+	///
+	/// * `Ok(())` if the device was enabled or disabled
+	/// * DeviceNotFoundError if the plugin has no such device
+	/// * NotSupported if the plugin has several devices but no USE_DEVICE
+	/// parameter
+	/// * another [CuckooMinerError](enum.CuckooMinerError.html) if the
+	/// parameter couldn't be set
+	///
+
+	pub fn set_device_enabled(&self, device_id: u32, enabled: bool) -> Result<(), CuckooMinerError> {
+		let devices = self.list_devices()?;
+		if !devices.iter().any(|d| d.device_id == device_id) {
+			return Err(CuckooMinerError::DeviceNotFoundError(format!(
+				"{} has no device {}",
+				self.lib_full_path,
+				device_id
+			)));
+		}
+		match self.set_parameter_for_device(USE_DEVICE_PARAM, device_id, enabled as u32) {
+			Ok(()) => {}
+			Err(CuckooMinerError::ParameterNotFoundError(_)) if devices.len() == 1 => {}
+			Err(CuckooMinerError::ParameterNotFoundError(_)) => {
+				return Err(CuckooMinerError::NotSupported(format!(
+					"{} can't disable single devices, as it has no {} parameter",
+					self.lib_full_path,
+					USE_DEVICE_PARAM
+				)))
+			}
+			Err(e) => return Err(e),
+		}
+		let mut disabled = self.disabled_devices.lock().unwrap();
+		match enabled {
+			true => disabled.remove(&device_id),
+			false => disabled.insert(device_id),
+		};
+		*self.all_devices_disabled.lock().unwrap() =
+			devices.iter().all(|d| disabled.contains(&d.device_id));
+		Ok(())
+	}
+
+	/// Whether a device is enabled, i.e. hasn't been disabled through
+	/// [set_device_enabled](#method.set_device_enabled)
+
+	pub fn is_device_enabled(&self, device_id: u32) -> bool {
+		!self.disabled_devices.lock().unwrap().contains(&device_id)
+	}
+
+	/// The ids of the devices disabled through
+	/// [set_device_enabled](#method.set_device_enabled)
+
+	pub fn disabled_devices(&self) -> Vec<u32> {
+		self.disabled_devices.lock().unwrap().iter().cloned().collect()
+	}
+
+	/// Whether every device the plugin reports has been disabled, in which
+	/// case there's no point feeding it headers
+
+	pub fn all_devices_disabled(&self) -> bool {
+		*self.all_devices_disabled.lock().unwrap()
+	}
+
+	/// #Description
+	///
+	/// For Async/Queued mode, check whether the plugin is ready
+	/// to accept more headers.
+	///
+	/// #Arguments
+	///
+	/// * None
+	///
+	/// #Returns
+	///
+	/// * 1 if the queue can accept more hashes, 0 otherwise
+	///
+
+	pub fn call_cuckoo_is_queue_under_limit(&self) -> u32 {
+		let _lifecycle = self.lifecycle.read().unwrap();
+		if !self.guard(PluginOp::IsQueueUnderLimit) {
+			return 0;
+		}
+		let cuckoo_is_queue_under_limit_ref = self.cuckoo_is_queue_under_limit.lock().unwrap();
+		unsafe { cuckoo_is_queue_under_limit_ref() }
+	}
+
+	/// Whether the plugin exports `cuckoo_queue_depths`, so
+	/// [queue_depths](#method.queue_depths) are as the plugin reports them
+	/// rather than estimated
+
+	pub fn has_queue_depths(&self) -> bool {
+		self.cuckoo_queue_depths.is_some()
+	}
+
+	/// #Description
+	///
+	/// Reads how many headers are waiting in the plugin's input queue, and
+	/// how many solutions in its output queue, through its optional
+	/// `cuckoo_queue_depths` function. Cheap enough to call on every pass
+	/// of a mining loop.
+	///
+	/// #Arguments
+	///
+	/// * `input` (OUT) The headers waiting in the input queue, not
+	/// counting those the plugin has started on
+	///
+	/// * `output` (OUT) The solutions waiting in the output queue
+	///
+	/// #Returns
+	///
+	/// 0 if the depths were read
+	/// CALL_REFUSED if the plugin doesn't export the function, or can't be
+	/// called in its current state
+	/// Any other code if the plugin couldn't tell
+	///
+
+	pub fn call_cuckoo_queue_depths(&self, input: &mut u32, output: &mut u32) -> u32 {
+		let _lifecycle = self.lifecycle.read().unwrap();
+		let cuckoo_queue_depths_ref = match self.cuckoo_queue_depths {
+			Some(ref q) => q.lock().unwrap(),
+			None => return CALL_REFUSED,
+		};
+		if !self.guard(PluginOp::IsQueueUnderLimit) {
+			return CALL_REFUSED;
+		}
+		unsafe { cuckoo_queue_depths_ref(input, output) }
+	}
+
+	/// #Description
+	///
+	/// Returns how full the plugin's queues are, to tell a plugin starved
+	/// of headers from one whose solutions aren't being read. Plugins
+	/// which don't export `cuckoo_queue_depths`, or can't tell, have the
+	/// depth of their input queue estimated from the headers pushed since
+	/// it was last cleared and the graphs their stats show they've started
+	/// since, which is only as good as those stats. The estimate can't see
+	/// into the output queue, so reports it as empty.
+	///
+	/// #Returns
+	///
+	/// The headers waiting in the input queue, and the solutions waiting
+	/// in the output queue
+	///
+	/// #Example
+	///
+	/// ```
+	///  # use cuckoo_miner::PluginLibrary;
+	///  # use std::env;
+	///  # use std::path::PathBuf;
+	///  # static DLL_SUFFIX: &str = ".cuckooplugin";
+	///  # let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+	///  # d.push(format!("./target/debug/plugins/lean_cpu_16{}", DLL_SUFFIX).as_str());
+	///  # let plugin_path = d.to_str().unwrap();
+	///  let pl = PluginLibrary::new(plugin_path).unwrap();
+	///  let (input, output) = pl.queue_depths();
+	///  println!("{} headers waiting, {} solutions unread", input, output);
+	/// ```
+	///
+
+	pub fn queue_depths(&self) -> (u32, u32) {
+		let (mut input, mut output) = (0, 0);
+		if self.call_cuckoo_queue_depths(&mut input, &mut output) == 0 {
+			return (input, output);
+		}
+		let taken = self.graphs_started()
+			.map_or(0, |g| g.saturating_sub(self.queue_graphs_started.load(Ordering::SeqCst) as u64));
+		let mut input = (self.queue_pushed.load(Ordering::SeqCst) as u64).saturating_sub(taken);
+		if let Ok(capacity) = self.input_queue_capacity() {
+			input = cmp::min(input, capacity as u64);
+		}
+		(input as u32, 0)
+	}
+
+	// The graphs the plugin's stats show it has taken from its input
+	// queue, finished or not, or None if they can't be read
+
+	fn graphs_started(&self) -> Option<u64> {
+		let stats = self.stats().ok()?;
+		Some(
+			stats
+				.iter()
+				.map(|s| {
+					let busy = s.last_start_time > 0 && s.last_start_time >= s.last_end_time;
+					s.iterations_completed as u64 + busy as u64
+				})
+				.sum(),
+		)
+	}
+
+	// Starts the estimate of the input queue's depth over, once it's been
+	// emptied. Plugins which can report the depth don't need one.
+
+	fn reset_queue_estimate(&self) {
+		let (mut input, mut output) = (0, 0);
+		if self.call_cuckoo_queue_depths(&mut input, &mut output) == 0 {
+			return;
+		}
+		self.queue_pushed.store(0, Ordering::SeqCst);
+		let started = self.graphs_started().unwrap_or(0);
+		self.queue_graphs_started.store(started as usize, Ordering::SeqCst);
+	}
+
+	/// #Description
+	///
+	/// Returns the number of headers the input queue accepts before
+	/// pushes fail with QueueFull, as reported through the plugin's
+	/// [QUEUE_SIZE](constant.QUEUE_SIZE_PARAM.html) parameter.
+	///
+	/// #Returns
+	///
+	/// * The capacity, if the plugin reports it
+	/// * a ParameterNotFoundError if the plugin doesn't
+	///
+
+	pub fn input_queue_capacity(&self) -> Result<u32, CuckooMinerError> {
+		self.get_parameter(QUEUE_SIZE_PARAM)
+	}
+
+	/// #Description
+	///
+	/// Returns the log2 of the number of edges in the graphs the plugin
+	/// searches, which bounds the edge nonces in its proofs, as reported
+	/// in its [capabilities](#method.capabilities).
+	///
+	/// #Returns
+	///
+	/// * The edge bits, or None if they can't be found out
+	///
+
+	pub fn edge_bits(&self) -> Option<u32> {
+		self.capabilities().ok().and_then(|c| c.edge_bits)
+	}
+
+	/// #Description
+	///
+	/// Returns the number of solutions the output queue holds, as
+	/// reported through the plugin's
+	/// [OUTPUT_QUEUE_SIZE](constant.OUTPUT_QUEUE_SIZE_PARAM.html) parameter.
+	///
+	/// #Returns
+	///
+	/// * The capacity, if the plugin reports it
+	/// * a ParameterNotFoundError if the plugin doesn't
+	///
+
+	pub fn output_queue_capacity(&self) -> Result<u32, CuckooMinerError> {
+		self.get_parameter(OUTPUT_QUEUE_SIZE_PARAM)
+	}
+
+	/// #Description
+	///
+	/// Returns the host memory the plugin expects to allocate once it
+	/// starts processing, as reported through its
+	/// [EXPECTED_MEMORY_MB](constant.EXPECTED_MEMORY_PARAM.html)
+	/// parameter.
+	///
+	/// #Returns
+	///
+	/// * The memory in bytes, or None if the plugin doesn't declare the
+	/// parameter or can't tell
+	/// * another [CuckooMinerError](enum.CuckooMinerError.html) if the
+	/// parameter can't be read
+	///
+
+	pub fn expected_memory(&self) -> Result<Option<u64>, CuckooMinerError> {
+		match self.get_parameter(EXPECTED_MEMORY_PARAM) {
+			Ok(0) | Err(CuckooMinerError::ParameterNotFoundError(_)) => Ok(None),
+			Ok(mb) => Ok(Some(mb as u64 * 1024 * 1024)),
+			Err(e) => Err(e),
+		}
+	}
+
+	/// #Description
+	///
+	/// Sets the number of headers the input queue accepts before it's
+	/// full, via the plugin's [QUEUE_SIZE](constant.QUEUE_SIZE_PARAM.html)
+	/// parameter. Must be called before processing starts.
 	///
-	/// * `param_list_bytes` (OUT) A reference to a block of [u8] bytes to fill
-	/// with the JSON result array
+	/// #Arguments
 	///
-	/// * `param_list_len` (IN-OUT) When called, this should contain the
-	/// maximum number of bytes the plugin should write to `param_list_bytes`.
-	/// Upon return, this is filled with the number of bytes that were written to 
-	/// `param_list_bytes`.
+	/// * `capacity` (IN) The number of headers the queue should accept
 	///
 	/// #Returns
 	///
-	/// 0 if okay, with the result is stored in `param_list_bytes`
-	/// 3 if the provided array is too short
+	/// * `Ok(())` if the capacity was set
+	/// * a [CuckooMinerError](enum.CuckooMinerError.html) if the plugin
+	/// doesn't declare the parameter, or the capacity is outside its range
 	///
 	/// #Example
-	///
 	/// ```
 	///  # use cuckoo_miner::PluginLibrary;
 	///  # use std::env;
@@ -404,41 +2569,41 @@ impl PluginLibrary {
 	///  # d.push(format!("./target/debug/plugins/lean_cpu_16{}", DLL_SUFFIX).as_str());
 	///  # let plugin_path = d.to_str().unwrap();
 	///  let pl=PluginLibrary::new(plugin_path).unwrap();
-	///  pl.call_cuckoo_init();
-	///  let mut param_list_bytes:[u8;1024]=[0;1024];
-	///  let mut param_list_len=param_list_bytes.len() as u32;
-	///  //get a list of json parameters
-	///  let parameter_list=pl.call_cuckoo_parameter_list(&mut param_list_bytes,
-	///    &mut param_list_len);
+	///  pl.set_input_queue_capacity(16).unwrap();
+	///  assert_eq!(pl.input_queue_capacity().unwrap(), 16);
 	/// ```
 	///
 
-	pub fn call_cuckoo_parameter_list(
-		&self,
-		param_list_bytes: &mut [u8],
-		param_list_len: &mut u32,
-	) -> u32 {
-		let cuckoo_parameter_list_ref = self.cuckoo_parameter_list.lock().unwrap();
-		unsafe { cuckoo_parameter_list_ref(param_list_bytes.as_mut_ptr(), param_list_len) }
+	pub fn set_input_queue_capacity(&self, capacity: u32) -> Result<(), CuckooMinerError> {
+		self.set_parameter_checked(QUEUE_SIZE_PARAM, capacity as u64)
 	}
 
 	/// #Description
 	///
-	/// Retrieves the value of a parameter from the currently loaded plugin
+	/// Pushes header data to the loaded plugin for later processing in
+	/// asyncronous/queued mode.
 	///
 	/// #Arguments
 	///
-	/// * `name_bytes` (IN) A reference to a block of [u8] bytes storing the
-	/// parameter name
+	/// * `data` (IN) A block of bytes to use for the seed to the internal
+	/// SIPHASH function which generates edge locations in the graph. In 
+	/// practice, this is a Grin blockheader, but from the 
+	/// plugin's perspective this can be anything.
 	///
-	/// * `device_id` (IN) The device ID to which the parameter applies (if applicable)
-	/// * `value` (OUT) A reference where the parameter value will be stored
+	/// * `nonce` (IN) The nonce that was used to generate this data, for
+	/// identification purposes in the solution queue
 	///
 	/// #Returns
 	///
-	/// 0 if the parameter was retrived, and the result is stored in `value`
-	/// 1 if the parameter does not exist
-	/// 4 if the provided parameter name was too long
+	/// 0 if the hash was successfully added to the queue
+	/// 1 if the queue is full
+	/// 2 if the length of the data is greater than the plugin allows
+	/// 4 if the plugin has been told to shutdown
+	///
+	/// #Unsafe
+	///
+	/// Provided values are copied within the plugin, and will not be
+	/// modified
 	///
 	/// #Example
 	/// ```
@@ -450,104 +2615,140 @@ impl PluginLibrary {
 	///  # d.push(format!("./target/debug/plugins/lean_cpu_16{}", DLL_SUFFIX).as_str());
 	///  # let plugin_path = d.to_str().unwrap();
 	///  let pl=PluginLibrary::new(plugin_path).unwrap();
-	///  pl.call_cuckoo_init();
-	///  let name = "NUM_THREADS";
-	///  let mut num_threads:u32 = 0;
-	///  let ret_val = pl.call_cuckoo_get_parameter(name.as_bytes(), 0, &mut num_threads);
+	///  //Processing started after call to cuckoo_start_processing()
+	///  //a test hash of zeroes
+	///  let hash:[u8;32]=[0;32];
+	///  //test nonce (u64, basically) should be unique
+	///  let nonce:[u8;8]=[0;8];
+	///  let result=pl.call_cuckoo_push_to_input_queue(&hash, &nonce);
 	/// ```
 	///
 
-	pub fn call_cuckoo_get_parameter(&self, name_bytes: &[u8], device_id: u32, value: &mut u32) -> u32 {
-		let cuckoo_get_parameter_ref = self.cuckoo_get_parameter.lock().unwrap();
-		unsafe { cuckoo_get_parameter_ref(name_bytes.as_ptr(), name_bytes.len() as u32, device_id, value) }
+	pub fn call_cuckoo_push_to_input_queue(&self, id: u32, data: &[u8], nonce: &[u8;8]) -> u32 {
+		let _lifecycle = self.lifecycle.read().unwrap();
+		if !self.guard(PluginOp::PushToInputQueue) {
+			return CALL_REFUSED;
+		}
+		let cuckoo_push_to_input_queue_ref = self.cuckoo_push_to_input_queue.lock().unwrap();
+		let code = unsafe { cuckoo_push_to_input_queue_ref(id, data.as_ptr(), data.len() as u32, nonce.as_ptr()) };
+		if code == 0 {
+			self.queue_pushed.fetch_add(1, Ordering::SeqCst);
+		}
+		code
 	}
 
-	/// Sets the value of a parameter in the currently loaded plugin
-	///
-	/// #Arguments
-	///
-	/// * `name_bytes` (IN) A reference to a block of [u8] bytes storing the
-	/// parameter name
+	/// #Description
 	///
-	/// * `device_id` (IN) The deviceID to which the parameter applies (if applicable)
-	/// * `value` (IN) The value to which to set the parameter
+	/// As [`call_cuckoo_push_to_input_queue`](#method.call_cuckoo_push_to_input_queue),
+	/// but translates the plugin's return code into a Result.
 	///
 	/// #Returns
 	///
-	/// 0 if the parameter was retrieved, and the result is stored in `value`
-	/// 1 if the parameter does not exist
-	/// 2 if the parameter exists, but the provided value is outside the 
-	/// allowed range determined by the plugin
-	/// 4 if the provided parameter name is too long
-	///
-	/// #Example
-	///
-	/// ```
-	///  # use cuckoo_miner::PluginLibrary;
-	///  # use std::env;
-	///  # use std::path::PathBuf;
-	///  # static DLL_SUFFIX: &str = ".cuckooplugin";
-	///  # let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
-	///  # d.push(format!("./target/debug/plugins/lean_cpu_16{}", DLL_SUFFIX).as_str());
-	///  # let plugin_path = d.to_str().unwrap();
-	///  let pl=PluginLibrary::new(plugin_path).unwrap();
-	///  let name = "NUM_THREADS";
-	///  let return_code = pl.call_cuckoo_set_parameter(name.as_bytes(), 0, 8);
-	/// ```
+	/// * `Ok(())` if the data was added to the queue
+	/// * QueueFull if the queue is full, with its depth if the plugin
+	/// reports its capacity
+	/// * another [CuckooMinerError](enum.CuckooMinerError.html) if the
+	/// data is too long, or the plugin is shutting down
 	///
 
-	pub fn call_cuckoo_set_parameter(&self, name_bytes: &[u8], device_id: u32, value: u32) -> u32 {
-		let cuckoo_set_parameter_ref = self.cuckoo_set_parameter.lock().unwrap();
-		unsafe { cuckoo_set_parameter_ref(name_bytes.as_ptr(), name_bytes.len() as u32, device_id, value) }
+	pub fn push_to_input_queue(&self, id: u32, data: &[u8], nonce: &[u8; 8]) -> Result<(), CuckooMinerError> {
+		let code = self.call_cuckoo_push_to_input_queue(id, data, nonce);
+		match PluginCall::PushToInputQueue.check(code, &self.lib_full_path) {
+			Err(CuckooMinerError::QueueFull { plugin, .. }) => Err(CuckooMinerError::QueueFull {
+				plugin: plugin,
+				// a full queue holds as many headers as it accepts
+				depth: self.input_queue_capacity().ok(),
+			}),
+			r => r,
+		}
+	}
+
+	/// Whether the plugin exports `cuckoo_push_batch_to_input_queue`, so a
+	/// batch of headers can be pushed in one call with
+	/// [push_jobs](#method.push_jobs)
+
+	pub fn has_batch_push(&self) -> bool {
+		self.cuckoo_push_batch_to_input_queue.is_some()
 	}
 
 	/// #Description
 	///
-	/// For Async/Queued mode, check whether the plugin is ready
-	/// to accept more headers.
+	/// Pushes a batch of 32-byte headers to the plugin's input queue in one
+	/// call, through its optional `cuckoo_push_batch_to_input_queue`
+	/// function. The plugin takes records from the front of the batch
+	/// until its queue is full, so it may only accept some of them.
 	///
 	/// #Arguments
 	///
-	/// * None
+	/// * `id` (IN) The job id, for every header in the batch
+	///
+	/// * `records` (IN) The batch, packed as described for
+	/// [JOB_RECORD_SIZE](constant.JOB_RECORD_SIZE.html)
+	///
+	/// * `accepted` (OUT) The number of records, from the front of the
+	/// batch, the plugin added to its queue
 	///
 	/// #Returns
 	///
-	/// * 1 if the queue can accept more hashes, 0 otherwise
+	/// 0 if every record was added to the queue
+	/// 1 if the queue filled before every record was added
+	/// 4 if the plugin has been told to shutdown
+	/// CALL_REFUSED if the plugin doesn't export the function, or the
+	/// batch isn't a whole number of records
 	///
 
-	pub fn call_cuckoo_is_queue_under_limit(&self) -> u32 {
-		let cuckoo_is_queue_under_limit_ref = self.cuckoo_is_queue_under_limit.lock().unwrap();
-		unsafe { cuckoo_is_queue_under_limit_ref() }
+	pub fn call_cuckoo_push_batch_to_input_queue(&self, id: u32, records: &[u8], accepted: &mut u32) -> u32 {
+		*accepted = 0;
+		let _lifecycle = self.lifecycle.read().unwrap();
+		let push_batch_ref = match self.cuckoo_push_batch_to_input_queue {
+			Some(ref p) => p.lock().unwrap(),
+			None => {
+				warn!("{} doesn't export cuckoo_push_batch_to_input_queue", self.lib_full_path);
+				return CALL_REFUSED;
+			}
+		};
+		if records.len() % JOB_RECORD_SIZE != 0 {
+			warn!(
+				"Refusing plugin call to {}: a batch of {} bytes isn't a whole number of {}-byte records",
+				self.lib_full_path,
+				records.len(),
+				JOB_RECORD_SIZE
+			);
+			return CALL_REFUSED;
+		}
+		if !self.guard(PluginOp::PushToInputQueue) {
+			return CALL_REFUSED;
+		}
+		let count = (records.len() / JOB_RECORD_SIZE) as u32;
+		let code = unsafe { push_batch_ref(id, records.as_ptr(), count, accepted) };
+		self.queue_pushed.fetch_add(cmp::min(*accepted, count) as usize, Ordering::SeqCst);
+		code
 	}
 
 	/// #Description
 	///
-	/// Pushes header data to the loaded plugin for later processing in
-	/// asyncronous/queued mode.
+	/// Pushes a batch of headers and their nonces to the plugin's input
+	/// queue, in order, stopping once the queue is full. Plugins exporting
+	/// `cuckoo_push_batch_to_input_queue` are handed the whole batch in
+	/// one call, packed by [pack_jobs](fn.pack_jobs.html), which saves a
+	/// call per header for small graphs. Other plugins are pushed one
+	/// header at a time.
 	///
 	/// #Arguments
 	///
-	/// * `data` (IN) A block of bytes to use for the seed to the internal
-	/// SIPHASH function which generates edge locations in the graph. In 
-	/// practice, this is a Grin blockheader, but from the 
-	/// plugin's perspective this can be anything.
+	/// * `id` (IN) The job id, for every header in the batch
 	///
-	/// * `nonce` (IN) The nonce that was used to generate this data, for
-	/// identification purposes in the solution queue
+	/// * `jobs` (IN) Each header, as its 32-byte hash, with its nonce as
+	/// for [push_to_input_queue](#method.push_to_input_queue)
 	///
 	/// #Returns
 	///
-	/// 0 if the hash was successfully added to the queue
-	/// 1 if the queue is full
-	/// 2 if the length of the data is greater than the plugin allows
-	/// 4 if the plugin has been told to shutdown
-	///
-	/// #Unsafe
-	///
-	/// Provided values are copied within the plugin, and will not be
-	/// modified
+	/// The number of jobs, from the front of `jobs`, the plugin accepted.
+	/// Fewer than all of them are accepted if the queue fills, or the
+	/// push fails, in which case the failure is logged.
 	///
 	/// #Example
+	///
 	/// ```
 	///  # use cuckoo_miner::PluginLibrary;
 	///  # use std::env;
@@ -556,19 +2757,41 @@ impl PluginLibrary {
 	///  # let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
 	///  # d.push(format!("./target/debug/plugins/lean_cpu_16{}", DLL_SUFFIX).as_str());
 	///  # let plugin_path = d.to_str().unwrap();
-	///  let pl=PluginLibrary::new(plugin_path).unwrap();
-	///  //Processing started after call to cuckoo_start_processing()
-	///  //a test hash of zeroes
-	///  let hash:[u8;32]=[0;32];
-	///  //test nonce (u64, basically) should be unique
-	///  let nonce:[u8;8]=[0;8];
-	///  let result=pl.call_cuckoo_push_to_input_queue(&hash, &nonce);
+	///  let pl = PluginLibrary::new(plugin_path).unwrap();
+	///  let jobs: Vec<([u8; 32], [u8; 8])> = (0..16u8).map(|i| ([i; 32], [0, 0, 0, 0, 0, 0, 0, i])).collect();
+	///  let accepted = pl.push_jobs(0, &jobs);
+	///  println!("{} of {} headers queued", accepted, jobs.len());
 	/// ```
 	///
 
-	pub fn call_cuckoo_push_to_input_queue(&self, id: u32, data: &[u8], nonce: &[u8;8]) -> u32 {
-		let cuckoo_push_to_input_queue_ref = self.cuckoo_push_to_input_queue.lock().unwrap();
-		unsafe { cuckoo_push_to_input_queue_ref(id, data.as_ptr(), data.len() as u32, nonce.as_ptr()) }
+	pub fn push_jobs(&self, id: u32, jobs: &[([u8; JOB_HEADER_SIZE], [u8; 8])]) -> usize {
+		if jobs.is_empty() {
+			return 0;
+		}
+		if self.has_batch_push() {
+			let mut accepted = 0;
+			let code = self.call_cuckoo_push_batch_to_input_queue(id, &pack_jobs(jobs), &mut accepted);
+			if code != 1 {
+				if let Err(e) = PluginCall::PushToInputQueue.check(code, &self.lib_full_path) {
+					warn!("Pushing a batch of {} headers failed: {:?}", jobs.len(), e);
+				}
+			}
+			// a plugin can't accept more than it was given
+			return cmp::min(accepted as usize, jobs.len());
+		}
+		for (i, &(ref header, ref nonce)) in jobs.iter().enumerate() {
+			match self.call_cuckoo_push_to_input_queue(id, header, nonce) {
+				0 => {}
+				1 => return i,
+				code => {
+					if let Err(e) = PluginCall::PushToInputQueue.check(code, &self.lib_full_path) {
+						warn!("Pushing a batch of {} headers failed: {:?}", jobs.len(), e);
+					}
+					return i;
+				}
+			}
+		}
+		jobs.len()
 	}
 
 	/// #Description
@@ -605,8 +2828,93 @@ impl PluginLibrary {
 	///
 
 	pub fn call_cuckoo_clear_queues(&self) {
-		let cuckoo_clear_queues_ref = self.cuckoo_clear_queues.lock().unwrap();
-		unsafe { cuckoo_clear_queues_ref() }
+		{
+			let _lifecycle = self.lifecycle.read().unwrap();
+			if !self.guard(PluginOp::ClearQueues) {
+				return;
+			}
+			let cuckoo_clear_queues_ref = self.cuckoo_clear_queues.lock().unwrap();
+			unsafe { cuckoo_clear_queues_ref() }
+		}
+		self.reset_queue_estimate();
+	}
+
+	/// #Description
+	///
+	/// Clears headers waiting in the input queue, leaving the output
+	/// queue alone, so solutions already found can still be read. Safe to
+	/// call while processing; graphs the plugin has already taken from the
+	/// queue are still attempted, and their solutions still queued.
+	///
+	/// #Returns
+	///
+	/// * `Ok(())` if the queue was cleared
+	/// * a PluginSymbolMissing error if the plugin can only clear both
+	/// queues at once, in which case nothing is cleared
+	/// * a PluginStateError if the plugin has been unloaded
+	///
+	/// #Example
+	/// ```
+	///  # use cuckoo_miner::PluginLibrary;
+	///  # use std::env;
+	///  # use std::path::PathBuf;
+	///  # static DLL_SUFFIX: &str = ".cuckooplugin";
+	///  # let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+	///  # d.push(format!("./target/debug/plugins/lean_cpu_16{}", DLL_SUFFIX).as_str());
+	///  # let plugin_path = d.to_str().unwrap();
+	///  let pl=PluginLibrary::new(plugin_path).unwrap();
+	///  //A new block has arrived, so drop the work for the old one
+	///  pl.clear_input_queue().unwrap();
+	///  //but keep what's been found for it
+	///  let solutions = pl.drain_solutions(100);
+	/// ```
+	///
+
+	pub fn clear_input_queue(&self) -> Result<(), CuckooMinerError> {
+		{
+			let _lifecycle = self.lifecycle.read().unwrap();
+			self.state.lock().unwrap().apply(PluginOp::ClearInputQueue)?;
+			let cuckoo_clear_input_queue_ref = match self.cuckoo_clear_input_queue {
+				Some(ref c) => c.lock().unwrap(),
+				None => {
+					return Err(CuckooMinerError::PluginSymbolMissing {
+						path: self.lib_full_path.clone(),
+						symbol: String::from("cuckoo_clear_input_queue"),
+					})
+				}
+			};
+			unsafe { cuckoo_clear_input_queue_ref() };
+		}
+		self.reset_queue_estimate();
+		Ok(())
+	}
+
+	/// #Description
+	///
+	/// Discards any solutions waiting in the output queue, leaving the
+	/// input queue alone. Safe to call while processing, though solutions
+	/// for graphs in progress may be queued straight afterwards. Plugins
+	/// which can only clear both queues at once have their output queue
+	/// read until it's empty instead.
+	///
+	/// #Returns
+	///
+	/// * `Ok(())` if the queue was cleared
+	/// * a PluginStateError if the plugin has been unloaded
+	///
+
+	pub fn clear_output_queue(&self) -> Result<(), CuckooMinerError> {
+		{
+			let _lifecycle = self.lifecycle.read().unwrap();
+			self.state.lock().unwrap().apply(PluginOp::ClearOutputQueue)?;
+			if let Some(ref c) = self.cuckoo_clear_output_queue {
+				let cuckoo_clear_output_queue_ref = c.lock().unwrap();
+				unsafe { cuckoo_clear_output_queue_ref() };
+				return Ok(());
+			}
+		}
+		while self.read_solution().is_some() {}
+		Ok(())
 	}
 
 
@@ -620,8 +2928,9 @@ impl PluginLibrary {
 	///
 	/// #Arguments
 	///
-	/// * `sol_nonces` (OUT) A block of 42 u32s in which the solution nonces
-	/// will be stored, if any exist.
+	/// * `sol_nonces` (OUT) A block of u32s in which the solution nonces
+	/// will be stored, if any exist. This must be at least as long as the
+	/// plugin's [proof_size](#method.proof_size), usually 42.
 	///
 	/// * `nonce` (OUT) A block of 8 u8s representing a Big-Endian u64, used
 	/// for identification purposes so the caller can reconstruct the header 
@@ -630,7 +2939,7 @@ impl PluginLibrary {
 	/// #Returns
 	///
 	/// 1 if a solution was popped from the queue
-	/// 0 if a solution is not available
+	/// 0 if a solution is not available, or `sol_nonces` is too short
 	///
 	/// #Example
 	/// ```
@@ -657,16 +2966,208 @@ impl PluginLibrary {
 	/// ```
 	///
 
-	pub fn call_cuckoo_read_from_output_queue(
-		&self,
-		id: &mut u32,
-		solutions: &mut [u32; 42],
-		cuckoo_size: &mut u32,
-		nonce: &mut [u8; 8],
-	) -> u32 {
-		let cuckoo_read_from_output_queue_ref = self.cuckoo_read_from_output_queue.lock().unwrap();
-		let ret = unsafe { cuckoo_read_from_output_queue_ref(id, solutions.as_mut_ptr(), cuckoo_size, nonce.as_mut_ptr()) };
-		ret
+	pub fn call_cuckoo_read_from_output_queue(
+		&self,
+		id: &mut u32,
+		solutions: &mut [u32],
+		cuckoo_size: &mut u32,
+		nonce: &mut [u8; 8],
+	) -> u32 {
+		let _lifecycle = self.lifecycle.read().unwrap();
+		if !self.fits_proof(solutions) || !self.guard(PluginOp::ReadFromOutputQueue) {
+			return 0;
+		}
+		let cuckoo_read_from_output_queue_ref = self.cuckoo_read_from_output_queue.lock().unwrap();
+		let ret = unsafe { cuckoo_read_from_output_queue_ref(id, solutions.as_mut_ptr(), cuckoo_size, nonce.as_mut_ptr()) };
+		ret
+	}
+
+	/// #Description
+	///
+	/// As [call_cuckoo_read_from_output_queue](#method.call_cuckoo_read_from_output_queue),
+	/// but through the plugin's optional cuckoo_read_from_output_queue_64
+	/// function, which returns the solution's edge indices as u64s.
+	///
+	/// #Returns
+	///
+	/// 1 if a solution was popped from the queue
+	/// 0 if a solution is not available, `sol_nonces` is too short, or the
+	/// plugin doesn't export the function
+	///
+
+	pub fn call_cuckoo_read_from_output_queue_64(
+		&self,
+		id: &mut u32,
+		solutions: &mut [u64],
+		cuckoo_size: &mut u32,
+		nonce: &mut [u8; 8],
+	) -> u32 {
+		let _lifecycle = self.lifecycle.read().unwrap();
+		let read_64_ref = match self.cuckoo_read_from_output_queue_64 {
+			Some(ref r) => r.lock().unwrap(),
+			None => return 0,
+		};
+		if !self.fits_proof(solutions) || !self.guard(PluginOp::ReadFromOutputQueue) {
+			return 0;
+		}
+		unsafe { read_64_ref(id, solutions.as_mut_ptr(), cuckoo_size, nonce.as_mut_ptr()) }
+	}
+
+	// Pops a single solution from the output queue, if there is one,
+	// through the 64-bit call if the plugin has it
+	fn read_solution(&self) -> Option<CuckooMinerSolution> {
+		let mut result = CuckooMinerSolution::new();
+		result.solution_nonces = vec![0; self.proof_size()];
+		let mut nonce = [0; 8];
+		let found = if self.cuckoo_read_from_output_queue_64.is_some() {
+			self.call_cuckoo_read_from_output_queue_64(
+				&mut result.id,
+				&mut result.solution_nonces,
+				&mut result.cuckoo_size,
+				&mut nonce,
+			)
+		} else {
+			let mut nonces = vec![0u32; self.proof_size()];
+			let found = self.call_cuckoo_read_from_output_queue(
+				&mut result.id,
+				&mut nonces,
+				&mut result.cuckoo_size,
+				&mut nonce,
+			);
+			result.set_solution(&nonces);
+			found
+		};
+		match found {
+			1 => {
+				result.set_nonce_bytes_as(&nonce, self.nonce_encoding());
+				result.plugin_name = Path::new(&self.lib_full_path)
+					.file_stem()
+					.map(|s| s.to_string_lossy().into_owned());
+				result.metadata = Some(SolutionMetadata::new(result.plugin_name.clone()));
+				Some(result)
+			}
+			_ => None,
+		}
+	}
+
+	/// #Description
+	///
+	/// Reads solutions from the output queue until it's empty or `max`
+	/// have been read. This can be called while processing, or after
+	/// processing has stopped to pick up any solutions still queued
+	/// before calling
+	/// [call_cuckoo_clear_queues](#method.call_cuckoo_clear_queues).
+	///
+	/// #Arguments
+	///
+	/// * `max` (IN) The most solutions to read
+	///
+	/// #Returns
+	///
+	/// * The solutions read, in the order the plugin queued them. Empty if
+	/// there were none, or the plugin has been unloaded.
+	///
+	/// #Example
+	/// ```
+	///  # use cuckoo_miner::PluginLibrary;
+	///  # use std::env;
+	///  # use std::path::PathBuf;
+	///  # static DLL_SUFFIX: &str = ".cuckooplugin";
+	///  # let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+	///  # d.push(format!("./target/debug/plugins/lean_cpu_16{}", DLL_SUFFIX).as_str());
+	///  # let plugin_path = d.to_str().unwrap();
+	///  let pl=PluginLibrary::new(plugin_path).unwrap();
+	///  for s in pl.drain_solutions(100) {
+	///    println!("Solution for job {}", s.id);
+	///  }
+	///  pl.call_cuckoo_clear_queues();
+	/// ```
+	///
+
+	pub fn drain_solutions(&self, max: usize) -> Vec<CuckooMinerSolution> {
+		let mut solutions = Vec::new();
+		while solutions.len() < max {
+			match self.read_solution() {
+				Some(s) => solutions.push(s),
+				None => break,
+			}
+		}
+		solutions
+	}
+
+	/// #Description
+	///
+	/// Waits for a solution to appear in the output queue, polling with a
+	/// backoff between 1ms and 50ms rather than spinning. Returns early
+	/// with an error if processing has stopped (or was never started) and
+	/// the queue is empty, as no more solutions will arrive.
+	///
+	/// #Arguments
+	///
+	/// * `timeout` (IN) The longest time to wait for a solution
+	///
+	/// #Returns
+	///
+	/// * `Ok(Some(`[CuckooMinerSolution](struct.CuckooMinerSolution.html)`))` if a
+	/// solution was read
+	/// * `Ok(None)` if nothing arrived before the timeout
+	/// * a ProcessingStopped error if the plugin isn't processing and its
+	/// queue is empty
+	/// * a PluginStateError if the plugin has been unloaded
+	///
+	/// #Example
+	/// ```
+	///  # use cuckoo_miner::PluginLibrary;
+	///  # use std::env;
+	///  # use std::path::PathBuf;
+	///  # use std::time::Duration;
+	///  # static DLL_SUFFIX: &str = ".cuckooplugin";
+	///  # let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+	///  # d.push(format!("./target/debug/plugins/lean_cpu_16{}", DLL_SUFFIX).as_str());
+	///  # let plugin_path = d.to_str().unwrap();
+	///  let pl=PluginLibrary::new(plugin_path).unwrap();
+	///  pl.call_cuckoo_start_processing();
+	///  //push jobs to the input queue, then
+	///  match pl.wait_for_solution(Duration::from_secs(5)) {
+	///    Ok(Some(s)) => println!("Solution for job {}", s.id),
+	///    Ok(None) => println!("No solution yet"),
+	///    Err(e) => println!("Processing stopped: {:?}", e),
+	///  }
+	/// ```
+	///
+
+	pub fn wait_for_solution(&self, timeout: Duration) -> Result<Option<CuckooMinerSolution>, CuckooMinerError> {
+		let start = Instant::now();
+		let mut interval = MIN_POLL_INTERVAL;
+		loop {
+			// check before reading, so a solution queued just before
+			// processing stopped is still picked up
+			let stopped = match self.state() {
+				PluginState::Processing => false,
+				PluginState::Stopping => self.call_cuckoo_has_processing_stopped() == 1,
+				PluginState::Unloaded => {
+					return Err(CuckooMinerError::PluginStateError(format!(
+						"Waiting for a solution from unloaded plugin {}",
+						self.lib_full_path
+					)))
+				}
+				_ => true,
+			};
+			if let Some(s) = self.read_solution() {
+				return Ok(Some(s));
+			}
+			if stopped {
+				return Err(CuckooMinerError::ProcessingStopped(
+					self.lib_full_path.clone(),
+				));
+			}
+			let elapsed = start.elapsed();
+			if elapsed >= timeout {
+				return Ok(None);
+			}
+			thread::sleep(cmp::min(interval, timeout - elapsed));
+			interval = cmp::min(interval * 2, MAX_POLL_INTERVAL);
+		}
 	}
 
 	/// #Description
@@ -709,8 +3210,32 @@ impl PluginLibrary {
 	/// ```
 
 	pub fn call_cuckoo_start_processing(&self) -> u32 {
+		let _lifecycle = self.lifecycle.read().unwrap();
+		if !self.guard(PluginOp::StartProcessing) {
+			return CALL_REFUSED;
+		}
 		let cuckoo_start_processing_ref = self.cuckoo_start_processing.lock().unwrap();
-		unsafe { cuckoo_start_processing_ref() }
+		let ret = unsafe { cuckoo_start_processing_ref() };
+		if ret != 0 {
+			self.state.lock().unwrap().processing_failed_to_start();
+		}
+		ret
+	}
+
+	/// #Description
+	///
+	/// As [`call_cuckoo_start_processing`](#method.call_cuckoo_start_processing),
+	/// but translates the plugin's return code into a Result.
+	///
+	/// #Returns
+	///
+	/// * `Ok(())` if processing was started
+	/// * a [CuckooMinerError](enum.CuckooMinerError.html) otherwise
+	///
+
+	pub fn start_processing(&self) -> Result<(), CuckooMinerError> {
+		let code = self.call_cuckoo_start_processing();
+		PluginCall::StartProcessing.check(code, &self.lib_full_path)
 	}
 
 	/// #Description
@@ -749,6 +3274,10 @@ impl PluginLibrary {
 	/// ```
 
 	pub fn call_cuckoo_stop_processing(&self) -> u32 {
+		let _lifecycle = self.lifecycle.read().unwrap();
+		if !self.guard(PluginOp::StopProcessing) {
+			return 0;
+		}
 		let cuckoo_stop_processing_ref = self.cuckoo_stop_processing.lock().unwrap();
 		unsafe { cuckoo_stop_processing_ref() }
 	}
@@ -787,6 +3316,10 @@ impl PluginLibrary {
 	/// ```
 
 	pub fn call_cuckoo_reset_processing(&self) -> u32 {
+		let _lifecycle = self.lifecycle.read().unwrap();
+		if !self.guard(PluginOp::ResetProcessing) {
+			return 0;
+		}
 		let cuckoo_reset_processing_ref = self.cuckoo_reset_processing.lock().unwrap();
 		unsafe { cuckoo_reset_processing_ref() }
 	}
@@ -825,8 +3358,99 @@ impl PluginLibrary {
 	/// ```
 
 	pub fn call_cuckoo_has_processing_stopped(&self) -> u32 {
+		let _lifecycle = self.lifecycle.read().unwrap();
+		if !self.guard(PluginOp::HasProcessingStopped) {
+			return 1;
+		}
 		let cuckoo_has_processing_stopped_ref = self.cuckoo_has_processing_stopped.lock().unwrap();
-		unsafe { cuckoo_has_processing_stopped_ref() }
+		let ret = unsafe { cuckoo_has_processing_stopped_ref() };
+		self.state.lock().unwrap().processing_stopped(ret == 1);
+		ret
+	}
+
+	/// #Description
+	///
+	/// Waits for internal processing to stop after a call to
+	/// [`call_cuckoo_stop_processing`](#method.call_cuckoo_stop_processing),
+	/// polling [`call_cuckoo_has_processing_stopped`](#method.call_cuckoo_has_processing_stopped)
+	/// with a sleep between checks that starts short and backs off, rather
+	/// than spinning. The output queue is left as it is, so solutions
+	/// found while stopping can still be read.
+	///
+	/// #Arguments
+	///
+	/// * `timeout` (IN) The longest time to wait
+	///
+	/// #Returns
+	///
+	/// * `Ok(())` once processing has stopped
+	/// * a PluginShutdownTimeout error if it hasn't stopped within the
+	/// timeout
+	///
+
+	pub fn wait_for_processing_stopped(&self, timeout: Duration) -> Result<(), CuckooMinerError> {
+		let start = Instant::now();
+		let mut interval = MIN_POLL_INTERVAL;
+		loop {
+			if self.call_cuckoo_has_processing_stopped() == 1 {
+				return Ok(());
+			}
+			let elapsed = start.elapsed();
+			if elapsed >= timeout {
+				return Err(CuckooMinerError::PluginShutdownTimeout(format!(
+					"{} didn't stop processing within {:?}",
+					self.lib_full_path,
+					timeout
+				)));
+			}
+			thread::sleep(cmp::min(interval, timeout - elapsed));
+			interval = cmp::min(interval * 2, MAX_POLL_INTERVAL);
+		}
+	}
+
+	/// #Description
+	///
+	/// Stops asynchronous processing, waits for it to stop as
+	/// [`wait_for_processing_stopped`](#method.wait_for_processing_stopped)
+	/// does, then resets processing so it can be started again. Use this
+	/// instead of spinning on `call_cuckoo_has_processing_stopped`. It
+	/// doesn't matter if processing has already been told to stop, e.g.
+	/// with `call_cuckoo_stop_processing` from another thread.
+	///
+	/// #Arguments
+	///
+	/// * `timeout` (IN) The longest time to wait for processing to stop
+	///
+	/// #Returns
+	///
+	/// * `Ok(())` if processing stopped and was reset
+	/// * a PluginShutdownTimeout error if it hasn't stopped within the
+	/// timeout, in which case it isn't reset, and can be waited for again
+	///
+	/// #Example
+	/// ```
+	///  # use cuckoo_miner::PluginLibrary;
+	///  # use std::env;
+	///  # use std::path::PathBuf;
+	///  # use std::time::Duration;
+	///  # static DLL_SUFFIX: &str = ".cuckooplugin";
+	///  # let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+	///  # d.push(format!("./target/debug/plugins/lean_cpu_16{}", DLL_SUFFIX).as_str());
+	///  # let plugin_path = d.to_str().unwrap();
+	///  let pl=PluginLibrary::new(plugin_path).unwrap();
+	///  pl.call_cuckoo_start_processing();
+	///  //Send data into queue, read results, etc
+	///  if let Err(e) = pl.stop_and_wait(Duration::from_secs(5)) {
+	///    println!("Plugin is stuck: {:?}", e);
+	///  }
+	/// ```
+	///
+
+	pub fn stop_and_wait(&self, timeout: Duration) -> Result<(), CuckooMinerError> {
+		self.call_cuckoo_stop_processing();
+		self.wait_for_processing_stopped(timeout)?;
+		self.call_cuckoo_reset_processing();
+		Ok(())
 	}
 
 	/// #Description
@@ -888,7 +3512,599 @@ impl PluginLibrary {
 	///
 
 	pub fn call_cuckoo_get_stats(&self, stat_bytes: &mut [u8], stat_bytes_len: &mut u32) -> u32 {
+		let _lifecycle = self.lifecycle.read().unwrap();
+		if !self.guard(PluginOp::GetStats) {
+			return CALL_REFUSED;
+		}
 		let cuckoo_get_stats_ref = self.cuckoo_get_stats.lock().unwrap();
 		unsafe { cuckoo_get_stats_ref(stat_bytes.as_mut_ptr(), stat_bytes_len) }
 	}
+
+	/// #Description
+	///
+	/// Retrieves and parses the plugin's per-device stats, growing the
+	/// buffer internally if the plugin reports it's too short.
+	///
+	/// #Returns
+	///
+	/// * `Ok(Vec<`[PluginDeviceStats](struct.PluginDeviceStats.html)`>)`
+	/// with an entry per device. This is empty if the plugin has nothing
+	/// to report yet, e.g. before processing has started, apart from any
+	/// disabled devices, which are always included with `in_use` 0.
+	/// * a StatsError if the plugin returns malformed JSON, or another
+	/// [CuckooMinerError](enum.CuckooMinerError.html) if the stats can't
+	/// be retrieved
+	///
+	/// #Example
+	///
+	/// ```
+	///  # use cuckoo_miner::PluginLibrary;
+	///  # use std::env;
+	///  # use std::path::PathBuf;
+	///  # static DLL_SUFFIX: &str = ".cuckooplugin";
+	///  # let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+	///  # d.push(format!("./target/debug/plugins/lean_cpu_16{}", DLL_SUFFIX).as_str());
+	///  # let plugin_path = d.to_str().unwrap();
+	///  let pl=PluginLibrary::new(plugin_path).unwrap();
+	///  for s in pl.stats().unwrap() {
+	///    println!("{} {}: {} iterations", s.device_id, s.device_name, s.iterations_completed);
+	///  }
+	/// ```
+	///
+
+	pub fn stats(&self) -> Result<Vec<PluginDeviceStats>, CuckooMinerError> {
+		let mut size = INITIAL_BUFFER_SIZE;
+		loop {
+			let mut stat_bytes = vec![0u8; size];
+			let mut stat_len = size as u32;
+			let code = self.call_cuckoo_get_stats(&mut stat_bytes, &mut stat_len);
+			if code == 3 && size < MAX_BUFFER_SIZE {
+				size *= 2;
+				continue;
+			}
+			PluginCall::GetStats.check(code, &self.lib_full_path)?;
+			let stats_json = buffer_to_string(stat_bytes, stat_len)?;
+			let stats = match stats_json.trim().is_empty() {
+				true => Vec::new(),
+				false => json::from_str(&stats_json).map_err(|e| {
+					CuckooMinerError::StatsError(format!(
+						"Error parsing stats from {}: {}",
+						self.lib_full_path,
+						e
+					))
+				})?,
+			};
+			return self.mark_disabled_devices(stats);
+		}
+	}
+
+	// Marks the devices disabled through set_device_enabled as not in use,
+	// adding an entry for any the plugin leaves out, so a disabled device
+	// doesn't look like one which has died
+
+	fn mark_disabled_devices(
+		&self,
+		mut stats: Vec<PluginDeviceStats>,
+	) -> Result<Vec<PluginDeviceStats>, CuckooMinerError> {
+		let mut disabled = self.disabled_devices.lock().unwrap().clone();
+		if disabled.is_empty() {
+			return Ok(stats);
+		}
+		for s in stats.iter_mut() {
+			if let Ok(id) = s.device_id.parse::<u32>() {
+				if disabled.remove(&id) {
+					s.in_use = 0;
+				}
+			}
+		}
+		if disabled.is_empty() {
+			return Ok(stats);
+		}
+		for d in self.list_devices()? {
+			if disabled.contains(&d.device_id) {
+				stats.push(PluginDeviceStats {
+					plugin_name: None,
+					device_id: d.device_id.to_string(),
+					cuckoo_size: String::from(""),
+					device_name: d.name,
+					in_use: 0,
+					has_errored: false,
+					error_reason: String::from(""),
+					last_start_time: 0,
+					last_end_time: 0,
+					last_solution_time: 0,
+					iterations_completed: 0,
+					temperature_c: None,
+					fan_percent: None,
+					memory_used_bytes: None,
+					host_memory_bytes: None,
+				});
+			}
+		}
+		Ok(stats)
+	}
+
+	/// #Description
+	///
+	/// Retrieves the plugin's name and description via its optional
+	/// cuckoo_description function. Buffer sizing is handled internally,
+	/// retrying with a larger buffer if the plugin reports the provided
+	/// one is too small, and trailing nulls are stripped from the result.
+	///
+	/// #Returns
+	///
+	/// * `Ok(`[PluginDescription](struct.PluginDescription.html)`)` if successful
+	/// * a [CuckooMinerError](enum.CuckooMinerError.html) if the plugin
+	/// doesn't export cuckoo_description, its output doesn't fit in the
+	/// largest buffer tried, or it isn't valid UTF-8
+	///
+	/// #Example
+	///
+	/// ```
+	///  # use cuckoo_miner::PluginLibrary;
+	///  # use std::env;
+	///  # use std::path::PathBuf;
+	///  # static DLL_SUFFIX: &str = ".cuckooplugin";
+	///  # let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+	///  # d.push(format!("./target/debug/plugins/lean_cpu_16{}", DLL_SUFFIX).as_str());
+	///  # let plugin_path = d.to_str().unwrap();
+	///  let pl=PluginLibrary::new(plugin_path).unwrap();
+	///  let desc = pl.description().unwrap();
+	///  println!("{}: {}", desc.name, desc.description);
+	/// ```
+	///
+
+	pub fn description(&self) -> Result<PluginDescription, CuckooMinerError> {
+		let _lifecycle = self.lifecycle.read().unwrap();
+		self.state.lock().unwrap().apply(PluginOp::Description)?;
+		let cuckoo_description_ref = match self.cuckoo_description {
+			Some(ref d) => d.lock().unwrap(),
+			None => {
+				return Err(CuckooMinerError::PluginSymbolMissing {
+					path: self.lib_full_path.clone(),
+					symbol: String::from("cuckoo_description"),
+				})
+			}
+		};
+		let mut size = INITIAL_BUFFER_SIZE;
+		loop {
+			let mut name_bytes = vec![0u8; size];
+			let mut name_len = size as u32;
+			let mut desc_bytes = vec![0u8; size];
+			let mut desc_len = size as u32;
+			let code = unsafe {
+				cuckoo_description_ref(
+					name_bytes.as_mut_ptr(),
+					&mut name_len,
+					desc_bytes.as_mut_ptr(),
+					&mut desc_len,
+				)
+			};
+			if code == 3 && size < MAX_BUFFER_SIZE {
+				size *= 2;
+				continue;
+			}
+			PluginCall::Description.check(code, &self.lib_full_path)?;
+			return Ok(PluginDescription {
+				name: buffer_to_string(name_bytes, name_len)?,
+				description: buffer_to_string(desc_bytes, desc_len)?,
+			});
+		}
+	}
+
+	/// #Description
+	///
+	/// Lists the devices the plugin can mine on, via its optional
+	/// cuckoo_list_devices function, which writes them to the buffer as a
+	/// JSON array. Plugins which don't export it are CPU plugins, and get
+	/// a single synthetic "CPU" device. A plugin with no devices, e.g. a
+	/// CUDA plugin on a machine without a GPU, gives an empty list rather
+	/// than an error, so discovery can carry on.
+	///
+	/// #Returns
+	///
+	/// * `Ok(devices)` if successful
+	/// * a [CuckooMinerError](enum.CuckooMinerError.html) if the call isn't
+	/// legal in the plugin's state, or its output can't be parsed
+	///
+	/// #Example
+	///
+	/// ```
+	///  # use cuckoo_miner::PluginLibrary;
+	///  # use std::env;
+	///  # use std::path::PathBuf;
+	///  # static DLL_SUFFIX: &str = ".cuckooplugin";
+	///  # let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+	///  # d.push(format!("./target/debug/plugins/lean_cpu_16{}", DLL_SUFFIX).as_str());
+	///  # let plugin_path = d.to_str().unwrap();
+	///  let pl=PluginLibrary::new(plugin_path).unwrap();
+	///  for d in pl.list_devices().unwrap() {
+	///    println!("{}: {} ({} bytes)", d.device_id, d.name, d.total_memory);
+	///  }
+	/// ```
+	///
+
+	pub fn list_devices(&self) -> Result<Vec<DeviceInfo>, CuckooMinerError> {
+		let _lifecycle = self.lifecycle.read().unwrap();
+		self.state.lock().unwrap().apply(PluginOp::ListDevices)?;
+		let f: CuckooListDevices = match self.optional_symbol("cuckoo_list_devices") {
+			Some(f) => f,
+			None => {
+				return Ok(vec![
+					DeviceInfo {
+						device_id: 0,
+						name: String::from("CPU"),
+						total_memory: 0,
+						usable: true,
+					},
+				])
+			}
+		};
+		let mut size = INITIAL_BUFFER_SIZE;
+		loop {
+			let mut device_bytes = vec![0u8; size];
+			let mut device_len = size as u32;
+			let code = unsafe { f(device_bytes.as_mut_ptr(), &mut device_len) };
+			if code == 3 && size < MAX_BUFFER_SIZE {
+				size *= 2;
+				continue;
+			}
+			PluginCall::ListDevices.check(code, &self.lib_full_path)?;
+			let devices_json = buffer_to_string(device_bytes, device_len)?;
+			if devices_json.trim().is_empty() {
+				return Ok(Vec::new());
+			}
+			return json::from_str(&devices_json).map_err(|e| {
+				CuckooMinerError::PluginIOError(format!(
+					"Error parsing device list from {}: {}",
+					self.lib_full_path,
+					e
+				))
+			});
+		}
+	}
+
+	/// The devices [listed](#method.list_devices) which the plugin
+	/// considers usable, e.g. the GPUs with enough memory for a CUDA
+	/// plugin's cuckoo size
+
+	pub fn usable_devices(&self) -> Result<Vec<DeviceInfo>, CuckooMinerError> {
+		Ok(self.list_devices()?.into_iter().filter(|d| d.usable).collect())
+	}
+
+	/// #Description
+	///
+	/// Queries what the plugin can do via its optional cuckoo_capabilities
+	/// function, which writes them to the buffer as a JSON object. Older
+	/// plugins which don't export it have them worked out instead:
+	///
+	/// * `edge_bits` from the plugin's [EDGE_BITS](constant.EDGE_BITS_PARAM.html)
+	/// parameter if it has one, and otherwise from the cuckoo size at the
+	/// end of its name, e.g. 29 for `lean_cpu_30`
+	/// * `is_cuda` from whether its name contains `cuda`
+	/// * `graph_type` from whether its name contains `cuckatoo`
+	/// * `max_queue_depth` from the range of its
+	/// [QUEUE_SIZE](constant.QUEUE_SIZE_PARAM.html) parameter
+	///
+	/// and the defaults for the rest. Relying on the name is deprecated,
+	/// and will go once plugins report their capabilities.
+	///
+	/// Plugins from version 1.1 of the interface also report whether they
+	/// take headers through their input queue, report stats and mine on
+	/// GPUs, along with their cuckoo size, via cuckoo_capability_flags,
+	/// which are taken over the JSON.
+	///
+	/// #Returns
+	///
+	/// * `Ok(`[PluginCapabilities](struct.PluginCapabilities.html)`)` if successful
+	/// * a [CuckooMinerError](enum.CuckooMinerError.html) if the call isn't
+	/// legal in the plugin's state, or its output can't be parsed
+	///
+	/// #Example
+	///
+	/// ```
+	///  # use cuckoo_miner::PluginLibrary;
+	///  # use std::env;
+	///  # use std::path::PathBuf;
+	///  # static DLL_SUFFIX: &str = ".cuckooplugin";
+	///  # let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+	///  # d.push(format!("./target/debug/plugins/lean_cpu_16{}", DLL_SUFFIX).as_str());
+	///  # let plugin_path = d.to_str().unwrap();
+	///  let pl=PluginLibrary::new(plugin_path).unwrap();
+	///  assert_eq!(pl.capabilities().unwrap().edge_bits, Some(15));
+	/// ```
+	///
+
+	pub fn capabilities(&self) -> Result<PluginCapabilities, CuckooMinerError> {
+		let mut caps = self.reported_capabilities()?;
+		if let Some(ref f) = self.cuckoo_capability_flags {
+			let f = f.lock().unwrap();
+			let _lifecycle = self.lifecycle.read().unwrap();
+			self.state.lock().unwrap().apply(PluginOp::Capabilities)?;
+			caps.apply_flags(unsafe { f() });
+		}
+		Ok(caps)
+	}
+
+	// The capabilities as the plugin reports them in JSON, or as worked
+	// out for older plugins which can't
+
+	fn reported_capabilities(&self) -> Result<PluginCapabilities, CuckooMinerError> {
+		let f: CuckooCapabilities = match self.optional_symbol("cuckoo_capabilities") {
+			Some(f) => f,
+			None => {
+				self.state.lock().unwrap().apply(PluginOp::Capabilities)?;
+				return Ok(self.legacy_capabilities());
+			}
+		};
+		let _lifecycle = self.lifecycle.read().unwrap();
+		self.state.lock().unwrap().apply(PluginOp::Capabilities)?;
+		let mut size = INITIAL_BUFFER_SIZE;
+		loop {
+			let mut caps_bytes = vec![0u8; size];
+			let mut caps_len = size as u32;
+			let code = unsafe { f(caps_bytes.as_mut_ptr(), &mut caps_len) };
+			if code == 3 && size < MAX_BUFFER_SIZE {
+				size *= 2;
+				continue;
+			}
+			PluginCall::Capabilities.check(code, &self.lib_full_path)?;
+			let caps_json = buffer_to_string(caps_bytes, caps_len)?;
+			let caps: PluginCapabilities = json::from_str(&caps_json).map_err(|e| {
+				CuckooMinerError::PluginIOError(format!(
+					"Error parsing capabilities from {}: {}",
+					self.lib_full_path,
+					e
+				))
+			})?;
+			// a cycle in a bipartite graph has an even number of edges
+			if caps.proof_size == 0 || caps.proof_size % 2 != 0 {
+				return Err(CuckooMinerError::PluginIOError(format!(
+					"{} declares a proof size of {}, which can't be a cycle",
+					self.lib_full_path,
+					caps.proof_size
+				)));
+			}
+			return Ok(caps);
+		}
+	}
+
+	// Works out the capabilities of a plugin which can't report them,
+	// partly from its name
+
+	fn legacy_capabilities(&self) -> PluginCapabilities {
+		let mut caps = PluginCapabilities::default();
+		let stem = Path::new(&self.lib_full_path)
+			.file_stem()
+			.map(|s| s.to_string_lossy().into_owned())
+			.unwrap_or_default();
+		// instances are named e.g. lean_cpu_30.1
+		let name = stem.split('.').next().unwrap_or("");
+		caps.edge_bits = match self.get_parameter(EDGE_BITS_PARAM) {
+			Ok(b) => Some(b),
+			Err(_) => match name.rsplit('_').next().map(|s| s.parse::<u32>()) {
+				Some(Ok(size)) if size > 1 => Some(size - 1),
+				_ => None,
+			},
+		};
+		caps.is_cuda = name.contains("cuda");
+		if name.contains("cuckatoo") {
+			caps.graph_type = GraphType::Cuckatoo;
+		}
+		caps.max_queue_depth = self.parameters().ok().and_then(|params| {
+			params
+				.into_iter()
+				.find(|p| p.name == QUEUE_SIZE_PARAM)
+				.map(|p| cmp::min(p.max_value, u32::max_value() as u64) as u32)
+		});
+		caps
+	}
+}
+
+/// A plugin's solver, kept set up between calls for plugins which export
+/// the context calls. Created by
+/// [solver_context](struct.PluginLibrary.html#method.solver_context), and
+/// destroyed when dropped.
+
+pub struct SolverContext<'a> {
+	library: &'a PluginLibrary,
+	// the plugin's context, by address, or None to make plain calls
+	handle: Option<usize>,
+}
+
+impl<'a> SolverContext<'a> {
+	/// Whether calls go through the plugin's persistent context, rather
+	/// than the plain calls which set the solver up each time
+
+	pub fn is_persistent(&self) -> bool {
+		self.handle.is_some()
+	}
+
+	/// #Description
+	///
+	/// As [call_cuckoo](struct.PluginLibrary.html#method.call_cuckoo), but
+	/// reusing the context's solver. Contexts which aren't persistent make
+	/// a plain call, cancellable if the plugin allows it.
+	///
+	/// #Arguments
+	///
+	/// * `header` (IN) As for [call_cuckoo](struct.PluginLibrary.html#method.call_cuckoo)
+	///
+	/// * `cuckoo_size` (OUT) The size of the graph searched
+	///
+	/// * `solutions` (OUT) As for [call_cuckoo](struct.PluginLibrary.html#method.call_cuckoo)
+	///
+	/// * `cancel` (IN) Cancels the call when set, as for
+	/// [call_cuckoo_cancellable](struct.PluginLibrary.html#method.call_cuckoo_cancellable),
+	/// if the plugin can be cancelled
+	///
+	/// #Returns
+	///
+	/// As for [call_cuckoo_cancellable](struct.PluginLibrary.html#method.call_cuckoo_cancellable)
+	///
+
+	pub fn call_cuckoo(
+		&self,
+		header: &[u8],
+		cuckoo_size: &mut u32,
+		solutions: &mut [u32],
+		cancel: Option<&AtomicBool>,
+	) -> u32 {
+		let l = self.library;
+		let handle = match self.handle {
+			Some(h) => h,
+			None => {
+				return match cancel {
+					Some(c) if l.has_cancellable_call() => l.call_cuckoo_cancellable(header, cuckoo_size, solutions, c),
+					_ => l.call_cuckoo(header, cuckoo_size, solutions),
+				}
+			}
+		};
+		let _lifecycle = l.lifecycle.read().unwrap();
+		// the context went with the plugin if it's been unloaded
+		if !l.contexts.lock().unwrap().contains(&handle) {
+			warn!("Refusing plugin call to {}: its solver context was released on unload", l.lib_full_path);
+			return CALL_REFUSED;
+		}
+		let call_ctx_ref = match l.cuckoo_call_ctx {
+			Some(ref c) => c.lock().unwrap(),
+			None => return CALL_REFUSED,
+		};
+		if !l.fits_proof(solutions) || !l.guard(PluginOp::Call) {
+			return CALL_REFUSED;
+		}
+		let cancel = match cancel {
+			Some(c) => c as *const AtomicBool as *const u8,
+			None => ptr::null(),
+		};
+		unsafe {
+			call_ctx_ref(
+				handle as *mut c_void,
+				header.as_ptr(),
+				header.len() as u32,
+				cuckoo_size,
+				solutions.as_mut_ptr(),
+				cancel,
+			)
+		}
+	}
+}
+
+impl<'a> Drop for SolverContext<'a> {
+	fn drop(&mut self) {
+		let handle = match self.handle {
+			Some(h) => h,
+			None => return,
+		};
+		let l = self.library;
+		let _lifecycle = l.lifecycle.read().unwrap();
+		// unload destroys any contexts left, and takes them from the set
+		if !l.contexts.lock().unwrap().remove(&handle) {
+			return;
+		}
+		if let Some(destroy) = l.cuckoo_destroy_context {
+			unsafe { destroy(handle as *mut c_void) };
+		}
+	}
+}
+
+impl Drop for PluginLibrary {
+	fn drop(&mut self) {
+		self.unload();
+		if self.state() != PluginState::Unloaded {
+			// processing didn't stop in time, and the plugin's threads may
+			// still be running its code, so leak it rather than close it
+			// out from under them
+			error!("Leaking {} as it couldn't be stopped", self.lib_full_path);
+			if let Some(l) = self.loaded_library.lock().unwrap().take() {
+				mem::forget(l);
+			}
+			self.leak_log_target();
+		}
+	}
+}
+
+// The log crate's level for a plugin's log level, which counts up from
+// 1 for errors to 5 for trace, as LogLevel does. Anything past trace is
+// logged as trace.
+
+fn plugin_log_level(level: u32) -> LogLevel {
+	match level {
+		0 | 1 => LogLevel::Error,
+		2 => LogLevel::Warn,
+		3 => LogLevel::Info,
+		4 => LogLevel::Debug,
+		_ => LogLevel::Trace,
+	}
+}
+
+// Registered with plugins through cuckoo_set_logger, and called from
+// their threads with each message, which is copied before returning. A
+// panic, e.g. in the installed logger, mustn't unwind into the plugin, so
+// the message is dropped instead.
+
+extern "C" fn log_plugin_message(context: *mut c_void, level: u32, msg: *const c_uchar, msg_len: u32) {
+	let _ = panic::catch_unwind(|| {
+		if context.is_null() || msg.is_null() {
+			return;
+		}
+		let target = unsafe { &*(context as *const String) };
+		let message = String::from_utf8_lossy(unsafe { slice::from_raw_parts(msg, msg_len as usize) })
+			.into_owned();
+		log!(target: target, plugin_log_level(level), "{}", message.trim_end());
+	});
+}
+
+/// Looks up a required plugin function, reporting which one is missing
+/// if it can't be found
+
+unsafe fn get_symbol<T: Copy>(
+	library: &libloading::Library,
+	path: &str,
+	symbol: &str,
+) -> Result<T, CuckooMinerError> {
+	let symbol_bytes = format!("{}\0", symbol);
+	match library.get::<T>(symbol_bytes.as_bytes()) {
+		Ok(s) => Ok(*s.into_raw()),
+		Err(_) => Err(CuckooMinerError::PluginSymbolMissing {
+			path: String::from(path),
+			symbol: String::from(symbol),
+		}),
+	}
+}
+
+/// #Description
+///
+/// Packs headers and their nonces into the buffer handed to a plugin's
+/// `cuckoo_push_batch_to_input_queue`, one
+/// [JOB_RECORD_SIZE](constant.JOB_RECORD_SIZE.html)-byte record per job.
+///
+/// #Arguments
+///
+/// * `jobs` (IN) Each header, as its 32-byte hash, with its nonce
+///
+/// #Returns
+///
+/// The packed records, in the order given
+///
+
+pub fn pack_jobs(jobs: &[([u8; JOB_HEADER_SIZE], [u8; 8])]) -> Vec<u8> {
+	let mut records = Vec::with_capacity(jobs.len() * JOB_RECORD_SIZE);
+	for &(ref header, ref nonce) in jobs {
+		records.extend_from_slice(header);
+		records.extend_from_slice(nonce);
+	}
+	records
+}
+
+/// Converts the first `len` bytes of a buffer filled by a plugin into a
+/// String, stopping at the first null
+
+pub fn buffer_to_string(mut bytes: Vec<u8>, len: u32) -> Result<String, CuckooMinerError> {
+	let len = cmp::min(len as usize, bytes.len());
+	bytes.truncate(len);
+	if let Some(i) = bytes.iter().position(|b| *b == 0) {
+		bytes.truncate(i);
+	}
+	Ok(String::from_utf8(bytes)?)
 }
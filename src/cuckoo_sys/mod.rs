@@ -22,3 +22,7 @@
 //! crate.
 
 pub mod manager;
+pub mod path;
+pub mod state;
+
+mod elf;
@@ -0,0 +1,154 @@
+// Copyright 2017 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Resolves plugin names and paths to plugin files, so an installed miner
+//! doesn't need to know where its plugins were built. Locations are
+//! searched in the order:
+//!
+//! * the path given by the caller, as is
+//! * each directory in the `CUCKOO_PLUGIN_PATH` environment variable,
+//! separated as `PATH` is on the platform (`:`, or `;` on Windows)
+//! * a `plugins` directory next to the running executable, then the
+//! executable's own directory
+//! * the default install location
+
+use std::env;
+use std::path::{Path, PathBuf};
+
+use error::error::CuckooMinerError;
+
+/// Environment variable listing extra directories to search for plugins
+pub const PLUGIN_PATH_VAR: &str = "CUCKOO_PLUGIN_PATH";
+
+/// The directory plugins are built to in a debug build of this crate,
+/// relative to the crate's root
+pub const BUILD_PLUGIN_DIR: &str = "target/debug/plugins";
+
+/// File extensions recognised as plugins on this platform, in order of
+/// preference
+
+#[cfg(target_os = "windows")]
+pub const PLUGIN_SUFFIXES: &[&str] = &["cuckooplugin", "dll"];
+
+/// File extensions recognised as plugins on this platform, in order of
+/// preference
+
+#[cfg(target_os = "macos")]
+pub const PLUGIN_SUFFIXES: &[&str] = &["cuckooplugin", "dylib"];
+
+/// File extensions recognised as plugins on this platform, in order of
+/// preference
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+pub const PLUGIN_SUFFIXES: &[&str] = &["cuckooplugin"];
+
+#[cfg(target_os = "windows")]
+fn default_install_dir() -> PathBuf {
+	let program_files = env::var_os("ProgramFiles").unwrap_or("C:\\Program Files".into());
+	Path::new(&program_files).join("cuckoo-miner").join("plugins")
+}
+
+#[cfg(not(target_os = "windows"))]
+fn default_install_dir() -> PathBuf {
+	PathBuf::from("/usr/local/lib/cuckoo-miner/plugins")
+}
+
+/// #Description
+///
+/// Returns the directories searched for plugins, in order: those in
+/// `CUCKOO_PLUGIN_PATH`, a `plugins` directory next to the running
+/// executable, the executable's directory, then the default install
+/// location. Directories are returned whether or not they exist.
+
+pub fn plugin_search_dirs() -> Vec<PathBuf> {
+	let mut dirs = Vec::new();
+	if let Some(p) = env::var_os(PLUGIN_PATH_VAR) {
+		dirs.extend(env::split_paths(&p).filter(|d| !d.as_os_str().is_empty()));
+	}
+	if let Ok(exe) = env::current_exe() {
+		if let Some(exe_dir) = exe.parent() {
+			dirs.push(exe_dir.join("plugins"));
+			dirs.push(exe_dir.to_path_buf());
+		}
+	}
+	dirs.push(default_install_dir());
+	dirs
+}
+
+/// Whether the path has one of the recognised plugin extensions
+pub fn has_plugin_suffix(path: &Path) -> bool {
+	match path.extension() {
+		Some(e) => PLUGIN_SUFFIXES.iter().any(|s| e == *s),
+		None => false,
+	}
+}
+
+/// #Description
+///
+/// Resolves a plugin path or name to a plugin file. An existing file at
+/// `plugin` is used as is. Otherwise each search directory is checked for
+/// `plugin`, then `plugin` with each recognised extension, so
+/// `"lean_cpu_30"` finds `lean_cpu_30.cuckooplugin`. Only the file name
+/// of `plugin` is used when searching directories.
+///
+/// #Arguments
+///
+/// * `plugin` (IN) A path to a plugin, or a plugin's name
+///
+/// #Returns
+///
+/// * `Ok(PathBuf)` with the path of the plugin
+/// * a PluginNotFoundError listing every path tried otherwise
+///
+
+pub fn resolve_plugin_path(plugin: &str) -> Result<PathBuf, CuckooMinerError> {
+	let explicit = PathBuf::from(plugin);
+	if explicit.is_file() {
+		return Ok(explicit);
+	}
+	let mut searched = vec![explicit.clone()];
+	let file_name = match explicit.file_name() {
+		Some(f) => PathBuf::from(f),
+		None => {
+			return Err(CuckooMinerError::PluginNotFoundError(format!(
+				"{} is not a plugin file name",
+				plugin
+			)))
+		}
+	};
+	let mut candidates = vec![file_name.clone()];
+	if !has_plugin_suffix(&file_name) {
+		for s in PLUGIN_SUFFIXES {
+			let mut f = file_name.clone().into_os_string();
+			f.push(".");
+			f.push(s);
+			candidates.push(PathBuf::from(f));
+		}
+	}
+	for dir in plugin_search_dirs() {
+		for c in &candidates {
+			let p = dir.join(c);
+			if p.is_file() {
+				return Ok(p);
+			}
+			searched.push(p);
+		}
+	}
+	let searched: Vec<String> = searched.iter().map(|p| p.display().to_string()).collect();
+	Err(CuckooMinerError::PluginNotFoundError(format!(
+		"{} not found, searched: {}",
+		plugin,
+		searched.join(", ")
+	)))
+}
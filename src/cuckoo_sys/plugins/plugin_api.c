@@ -0,0 +1,51 @@
+// Copyright 2017 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// The plugin interface version and capability flags, built into every
+// plugin alongside its solver. Must match PLUGIN_API_VERSION and
+// PluginCapabilities::apply_flags in src/cuckoo_sys/manager.rs.
+
+#include <stdint.h>
+
+#ifdef _WIN32
+#define PLUGIN_EXPORT __declspec(dllexport)
+#else
+#define PLUGIN_EXPORT __attribute__((visibility("default")))
+#endif
+
+#ifndef EDGEBITS
+#error "EDGEBITS must be defined for the plugin being built"
+#endif
+
+// interface version 1.1, as (major << 16) | minor
+#define CUCKOO_API_VERSION ((1 << 16) | 1)
+
+#define CAPABILITY_ASYNC_QUEUE 1
+#define CAPABILITY_STATS 2
+#define CAPABILITY_GPU 4
+
+#ifdef PLUGIN_GPU
+#define CAPABILITY_FLAGS (CAPABILITY_ASYNC_QUEUE | CAPABILITY_STATS | CAPABILITY_GPU)
+#else
+#define CAPABILITY_FLAGS (CAPABILITY_ASYNC_QUEUE | CAPABILITY_STATS)
+#endif
+
+PLUGIN_EXPORT uint32_t cuckoo_api_version() {
+  return CUCKOO_API_VERSION;
+}
+
+// the flags, with the cuckoo size in bits 8 to 15
+PLUGIN_EXPORT uint32_t cuckoo_capability_flags() {
+  return CAPABILITY_FLAGS | (((EDGEBITS + 1) & 0xff) << 8);
+}
@@ -0,0 +1,215 @@
+// Copyright 2017 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Reference model of a plugin's lifecycle. This is the specification of
+//! which plugin calls are legal in which order, and [PluginLibrary]
+//! (struct.PluginLibrary.html) implements its guards against it, so the
+//! two can't drift apart.
+//!
+//! The legal orderings are:
+//!
+//! ```text
+//!             start                stop              has_stopped == 1
+//!   Idle  ------------> Processing ------> Stopping ------------------> Stopped
+//!    ^                                                                     |
+//!    +-------------------------------- reset ------------------------------+
+//! ```
+//!
+//! * Queue, parameter and stats calls are legal in every state but Unloaded.
+//! * A synchronous `cuckoo_call` and `cuckoo_init` are only legal when Idle.
+//! * `reset` is legal when Idle (where it does nothing) or Stopped, but not
+//!   before a stop has completed.
+//! * `stop` is legal while Processing, Stopping or Stopped.
+//! * `unload` is legal when Idle or Stopped, after which nothing else is.
+//!   A plugin which won't stop can be force unloaded from any state.
+
+use error::error::CuckooMinerError;
+
+/// The states a loaded plugin can be in
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PluginState {
+	/// Loaded and not processing
+	Idle,
+	/// Asynchronous processing has been started
+	Processing,
+	/// Processing has been told to stop, but hasn't stopped yet
+	Stopping,
+	/// Processing has stopped, and needs a reset before it's restarted
+	Stopped,
+	/// The plugin has been unloaded
+	Unloaded,
+}
+
+/// The operations that can be performed on a loaded plugin
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PluginOp {
+	/// cuckoo_init
+	Init,
+	/// cuckoo_call
+	Call,
+	/// cuckoo_parameter_list
+	ParameterList,
+	/// cuckoo_get_parameter
+	GetParameter,
+	/// cuckoo_set_parameter
+	SetParameter,
+	/// cuckoo_is_queue_under_limit, and cuckoo_queue_depths
+	IsQueueUnderLimit,
+	/// cuckoo_push_to_input_queue
+	PushToInputQueue,
+	/// cuckoo_read_from_output_queue
+	ReadFromOutputQueue,
+	/// cuckoo_clear_queues
+	ClearQueues,
+	/// cuckoo_clear_input_queue
+	ClearInputQueue,
+	/// cuckoo_clear_output_queue
+	ClearOutputQueue,
+	/// cuckoo_start_processing
+	StartProcessing,
+	/// cuckoo_stop_processing
+	StopProcessing,
+	/// cuckoo_has_processing_stopped
+	HasProcessingStopped,
+	/// cuckoo_reset_processing
+	ResetProcessing,
+	/// cuckoo_get_stats
+	GetStats,
+	/// cuckoo_description
+	Description,
+	/// cuckoo_list_devices
+	ListDevices,
+	/// cuckoo_capabilities
+	Capabilities,
+	/// Unloading the plugin
+	Unload,
+}
+
+/// All operations, mostly for generating call sequences in tests
+pub const ALL_PLUGIN_OPS: [PluginOp; 20] = [
+	PluginOp::Init,
+	PluginOp::Call,
+	PluginOp::ParameterList,
+	PluginOp::GetParameter,
+	PluginOp::SetParameter,
+	PluginOp::IsQueueUnderLimit,
+	PluginOp::PushToInputQueue,
+	PluginOp::ReadFromOutputQueue,
+	PluginOp::ClearQueues,
+	PluginOp::ClearInputQueue,
+	PluginOp::ClearOutputQueue,
+	PluginOp::StartProcessing,
+	PluginOp::StopProcessing,
+	PluginOp::HasProcessingStopped,
+	PluginOp::ResetProcessing,
+	PluginOp::GetStats,
+	PluginOp::Description,
+	PluginOp::ListDevices,
+	PluginOp::Capabilities,
+	PluginOp::Unload,
+];
+
+/// Tracks the lifecycle state of a plugin and decides which operations
+/// are legal from it.
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StateModel {
+	state: PluginState,
+}
+
+impl Default for StateModel {
+	fn default() -> StateModel {
+		StateModel {
+			state: PluginState::Idle,
+		}
+	}
+}
+
+impl StateModel {
+	/// Returns a new model, in the Idle state of a freshly loaded plugin
+
+	pub fn new() -> StateModel {
+		StateModel::default()
+	}
+
+	/// The current state
+	pub fn state(&self) -> PluginState {
+		self.state
+	}
+
+	/// Whether `op` is legal in the current state
+
+	pub fn allows(&self, op: PluginOp) -> bool {
+		match (self.state, op) {
+			(PluginState::Unloaded, _) => false,
+			(PluginState::Idle, PluginOp::StopProcessing) => false,
+			(PluginState::Idle, _) => true,
+			(_, PluginOp::Init) | (_, PluginOp::Call) | (_, PluginOp::StartProcessing) => false,
+			(PluginState::Processing, PluginOp::ResetProcessing) |
+			(PluginState::Stopping, PluginOp::ResetProcessing) => false,
+			(PluginState::Processing, PluginOp::Unload) |
+			(PluginState::Stopping, PluginOp::Unload) => false,
+			_ => true,
+		}
+	}
+
+	/// Applies `op`, moving to the resulting state. Returns a
+	/// PluginStateError and leaves the state unchanged if `op` isn't legal
+	/// in the current state.
+
+	pub fn apply(&mut self, op: PluginOp) -> Result<(), CuckooMinerError> {
+		if !self.allows(op) {
+			return Err(CuckooMinerError::PluginStateError(format!(
+				"{:?} is not allowed while {:?}",
+				op,
+				self.state
+			)));
+		}
+		self.state = match (self.state, op) {
+			(PluginState::Idle, PluginOp::StartProcessing) => PluginState::Processing,
+			(PluginState::Processing, PluginOp::StopProcessing) => PluginState::Stopping,
+			(PluginState::Stopped, PluginOp::ResetProcessing) => PluginState::Idle,
+			(_, PluginOp::Unload) => PluginState::Unloaded,
+			(s, _) => s,
+		};
+		Ok(())
+	}
+
+	/// Records that a legal start_processing call failed, so the plugin
+	/// is still Idle
+
+	pub fn processing_failed_to_start(&mut self) {
+		if self.state == PluginState::Processing {
+			self.state = PluginState::Idle;
+		}
+	}
+
+	/// Records the result of a legal has_processing_stopped call, moving
+	/// from Stopping to Stopped once the plugin reports it has stopped
+
+	pub fn processing_stopped(&mut self, has_stopped: bool) {
+		if has_stopped && self.state == PluginState::Stopping {
+			self.state = PluginState::Stopped;
+		}
+	}
+
+	/// Records that the plugin has been given up on and detached while
+	/// still processing, after which nothing else is legal
+
+	pub fn force_unload(&mut self) {
+		self.state = PluginState::Unloaded;
+	}
+}
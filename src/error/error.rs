@@ -32,9 +32,38 @@ pub enum CuckooMinerError {
 	/// Occurs when trying to load plugin function that doesn't exist
 	PluginSymbolNotFoundError(String),
 
+	/// A function a plugin is required to export is missing, usually
+	/// because it was built against a different plugin interface
+	PluginSymbolMissing {
+		/// The path of the plugin
+		path: String,
+		/// The name of the missing function
+		symbol: String,
+	},
+
+	/// The plugin was built against an incompatible major version of the
+	/// plugin interface. Plugins that don't report a version are version 0.
+	PluginApiMismatch {
+		/// The major version this crate implements
+		expected: u32,
+		/// The plugin's major version
+		found: u32,
+	},
+
 	/// Occurs when attempting to load a plugin that doesn't exist
 	PluginNotFoundError(String),
 
+	/// The plugin file exists, but can't be read
+	PluginPermissionError(String),
+
+	/// The plugin's file isn't one of the builds pinned in its config
+	PluginHashMismatch {
+		/// The path of the plugin
+		path: String,
+		/// The hash of the file found there
+		hash: String,
+	},
+
 	/// Occurs when trying to load a plugin directory that doesn't
 	/// contain any plugins
 	NoPluginsFoundError(String),
@@ -45,14 +74,114 @@ pub enum CuckooMinerError {
 	/// Error setting a parameter
 	ParameterError(String),
 
+	/// The plugin doesn't recognise the given parameter name
+	ParameterNotFoundError(String),
+
+	/// The given parameter value is outside the range allowed by the plugin
+	ParameterRangeError(String),
+
+	/// The given parameter value is outside the range the plugin declares
+	/// for it, detected before the value is passed to the plugin
+	ParameterOutOfRange {
+		/// The parameter name
+		name: String,
+		/// The rejected value
+		value: u64,
+		/// The plugin's declared minimum
+		min: u64,
+		/// The plugin's declared maximum
+		max: u64,
+	},
+
+	/// A parameter given in a configuration file isn't one the plugin
+	/// declares
+	UnknownParameter {
+		/// The plugin's configured name or path
+		plugin: String,
+		/// The parameter name
+		parameter: String,
+	},
+
+	/// The given parameter name is longer than the plugin accepts
+	ParameterNameTooLongError(String),
+
+	/// The plugin doesn't have a device with the given id
+	DeviceNotFoundError(String),
+
+	/// A buffer provided to the plugin is too small for its output
+	BufferTooSmallError(String),
+
+	/// The plugin's input queue can't accept any more data
+	QueueFull {
+		/// The plugin
+		plugin: String,
+		/// The number of entries in the queue, if the plugin reports its
+		/// capacity
+		depth: Option<u32>,
+	},
+
+	/// Data pushed to the plugin is longer than the plugin allows
+	DataTooLongError(String),
+
 	/// IO Error
 	PluginIOError(String),
 
 	/// Plugin processing can't start
 	PluginProcessingError(String),
 
+	/// Processing has stopped and the output queue is empty, so no more
+	/// solutions will arrive
+	ProcessingStopped(String),
+
 	/// Error getting stats or stats not implemented
 	StatsError(String),
+
+	/// A plugin call isn't allowed in the plugin's current lifecycle state
+	PluginStateError(String),
+
+	/// A solution is malformed
+	SolutionError(String),
+
+	/// A plugin's processing didn't stop within the time allowed
+	PluginShutdownTimeout(String),
+
+	/// The plugin doesn't export an optional function needed for the call
+	NotSupported(String),
+
+	/// A [CuckooMinerBuilder](struct.CuckooMinerBuilder.html) couldn't
+	/// build a miner. Holds every problem found, so they can all be fixed
+	/// at once.
+	MinerBuildError(Vec<CuckooMinerError>),
+
+	/// A configuration file can't be read, or its contents are malformed
+	ConfigError(String),
+
+	/// A hex string, such as a header, is malformed
+	InvalidHex {
+		/// The byte offset into the string of the offending character
+		offset: usize,
+		/// What's wrong with it
+		reason: String,
+	},
+
+	/// A plugin couldn't be reloaded from its file, even after retrying
+	PluginReloadFailed {
+		/// The plugin's configured path
+		plugin: String,
+		/// Why the last attempt failed
+		reason: String,
+	},
+
+	/// The plugins expect more memory than the system has available
+	InsufficientMemory {
+		/// The plugin whose expected memory took the total over
+		plugin: String,
+		/// The memory expected by that plugin and those before it, in
+		/// bytes
+		required: u64,
+		/// The memory the system has available, in bytes
+		available: u64,
+	},
 }
 
 impl From<io::Error> for CuckooMinerError {
@@ -44,9 +44,11 @@
 //! The miner can be run in either synchronous or asynchronous mode.
 //!
 //! Syncronous mode uses the [`mine`](struct.CuckooMiner.html#method.mine) function,
-//! which takes a complete hash, processes it within the calling thread via the plugin's 
-//! [`call_cuckoo`](struct.PluginLibrary.html#method.call_cuckoo) function, 
-//! and returns the result.
+//! which takes the pre-nonce and post-nonce parts of a block header, and tries
+//! nonces in turn within the calling thread via the plugin's 
+//! [`call_cuckoo`](struct.PluginLibrary.html#method.call_cuckoo) function
+//! until it finds a solution. A single complete hash can be processed
+//! with [`mine_hash`](struct.CuckooMiner.html#method.mine_hash).
 //!
 //! Asynchronous mode uses the [`notify`](struct.CuckoMiner.html#method.notify) 
 //! function, which takes the pre-nonce and
@@ -54,6 +56,12 @@
 //! inserts the resulting hash into the plugin's internal queue for processing.
 //! Solutions are placed into an output queue, which the calling thread can 
 //! read ascynronously via a [job handle](struct.CuckooMinerJobHandle.html).
+//! [`notify_header`](struct.CuckooMiner.html#method.notify_header) does the
+//! same with a header of any length given as bytes, which is reduced to the
+//! 32 bytes plugins accept by [`header_hash`](fn.header_hash.html).
+//! Alternatively, solutions and other [events](enum.MinerEvent.html) can be
+//! delivered to a channel set with
+//! [`set_event_channel`](struct.CuckooMiner.html#method.set_event_channel).
 //!
 //! Examples of using either mode follow:
 //!
@@ -98,11 +106,12 @@
 //! 	let mut header:[u8; 32] = [0;32]; 
 //! 	let mut iterations=0;
 //! 	let mut solution = cuckoo::CuckooMinerSolution::new();
+//! 	let mut cuckoo_size = 0;
 //! 	loop {
 //! 		header[0]=i;
 //! 		//Mine on plugin loaded at index 0 (which should be only one loaded in
 //! 		//Sync mode
-//! 		let result = miner.mine(&header, &mut solution, 0).unwrap();
+//! 		let result = miner.mine_hash(&header, &mut cuckoo_size, &mut solution, 0).unwrap();
 //! 		iterations+=1;
 //! 		if result == true {
 //! 			println!("Solution found after {} iterations: {}", i, solution);
@@ -230,9 +239,12 @@
 extern crate log;
 extern crate env_logger;
 
+#[cfg(feature = "serde")]
 extern crate serde;
+#[cfg(feature = "serde")]
 #[macro_use]
 extern crate serde_derive;
+#[cfg(feature = "serde")]
 extern crate serde_json;
 
 extern crate regex;
@@ -245,17 +257,109 @@ extern crate libloading as libloading;
 extern crate libc;
 
 extern crate glob;
+extern crate toml;
+
+#[cfg(feature = "async")]
+extern crate futures_channel;
+#[cfg(feature = "async")]
+extern crate futures_core;
 
 mod error;
 mod miner;
 mod manager;
 mod cuckoo_sys;
+mod util;
+mod verify;
 
 pub use error::error::CuckooMinerError;
 
-pub use miner::miner::{CuckooMinerConfig, CuckooMiner, CuckooMinerSolution, CuckooMinerJobHandle,
-                CuckooMinerDeviceStats};
+pub use miner::miner::{CuckooMinerConfig, CuckooMiner, CuckooMinerBuilder, CuckooMinerSolution, CuckooMinerJobHandle,
+                CuckooMinerDeviceStats, SolutionMetadata, header_hash, encoded_header_hash, shutdown_plugins};
+
+pub use miner::events::MinerEvent;
+
+pub use miner::solutions::SolutionIter;
+#[cfg(feature = "async")]
+pub use miner::solutions::SolutionStream;
+
+pub use miner::bench::{BenchmarkMode, BenchmarkReport, BenchmarkPhases, benchmark_header, benchmark_queue};
+#[cfg(feature = "serde")]
+pub use miner::bench::benchmark_json;
+
+pub use miner::tune::{TuningOptions, TuningPoint, TuningResult, values_in_range, combinations,
+                DEFAULT_TUNING_GRAPHS};
+
+pub use miner::tuning_file::{save_tuning, load_tuning};
+
+pub use miner::dedup::{DuplicateFilter, DEFAULT_DEDUP_WINDOW};
+
+pub use miner::watchdog::{Watchdog, StallMonitor, DEFAULT_STOP_TIMEOUT, DEFAULT_SHUTDOWN_DEADLINE, DEFAULT_STALL_WARNING};
+
+pub use miner::blacklist::{FailureTracker, DEFAULT_FAILURE_THRESHOLD, DEFAULT_BLACKLIST_COOLDOWN};
+
+pub use miner::thermal::{ThermalMonitor, ThermalChange, DEFAULT_TEMPERATURE_HYSTERESIS};
+
+pub use miner::nonce::{NonceRange, NonceGenerator, NonceEncoding};
+
+pub use miner::job::{HeaderHasher, Blake2bHasher, MiningJob};
+
+pub use miner::affinity::{Priority, affinity_mask, online_cores, pin_current_thread, set_current_thread_priority,
+	CPU_AFFINITY_MASK_PARAM, THREAD_PRIORITY_PARAM, MAX_AFFINITY_CORES};
+
+pub use miner::self_test::SelfTestReport;
+
+pub use miner::devices::{DeviceConfig, NUM_BLOCKS_PARAM, THREADS_PER_BLOCK_PARAM};
+
+pub use miner::config::{MinerConfigFile, MiningMode};
+
+pub use miner::stats::{StatsCollector, DeviceSnapshot, Hashrate, DEFAULT_STATS_WINDOW, DEFAULT_SAMPLE_INTERVAL,
+                ONE_MINUTE, FIVE_MINUTES};
+
+#[cfg(any(feature = "metrics", feature = "control"))]
+pub use miner::http::HttpServer;
+
+#[cfg(feature = "metrics")]
+pub use miner::metrics::{MetricsServer, render_metrics, serve_metrics, METRICS_CONTENT_TYPE, METRICS_PATH};
+
+#[cfg(feature = "stratum")]
+pub use miner::stratum::{StratumWorker, StratumOptions, StratumStats, DEFAULT_MIN_BACKOFF, DEFAULT_MAX_BACKOFF,
+                DEFAULT_SOLUTION_BUFFER_TIME};
+
+#[cfg(feature = "control")]
+pub use miner::control::{ControlServer, serve_control, CONTROL_PATH, PARSE_ERROR, INVALID_REQUEST, METHOD_NOT_FOUND,
+                INVALID_PARAMS, MINER_ERROR};
+
+#[cfg(feature = "fault-injection")]
+pub use miner::PANIC_NONCE;
 
 pub use manager::manager::{CuckooPluginManager, CuckooPluginCapabilities};
 
-pub use cuckoo_sys::manager::PluginLibrary;
+pub use manager::install::{PluginReport, verify_plugin, install_plugin, plugin_hash, check_pinned_hash,
+                VERIFY_SELF_TEST_TIMEOUT, QUICK_SELF_TEST_CUCKOO_SIZE};
+
+pub use cuckoo_sys::manager::{PluginLibrary, SolverContext, PluginCall, PluginDescription,
+                CuckooPluginParameter, PluginDeviceStats, DeviceInfo, PluginCapabilities, GraphType, CALL_REFUSED,
+                CALL_CANCELLED, CANCEL_BOUND, ALL_DEVICES, PLUGIN_API_VERSION, QUEUE_SIZE_PARAM,
+                OUTPUT_QUEUE_SIZE_PARAM, USE_DEVICE_PARAM, EDGE_BITS_PARAM, EXPECTED_MEMORY_PARAM, JOB_HEADER_SIZE,
+                JOB_RECORD_SIZE, pack_jobs};
+
+pub use cuckoo_sys::path::{resolve_plugin_path, plugin_search_dirs, PLUGIN_PATH_VAR,
+                PLUGIN_SUFFIXES, BUILD_PLUGIN_DIR};
+
+pub use cuckoo_sys::state::{StateModel, PluginState, PluginOp, ALL_PLUGIN_OPS};
+
+pub use verify::verify::{verify, verify_nonce, verify_solution, verify_with_keys, is_verifiable, check_bounds,
+                VerifyError, PROOF_SIZE, MAX_32_BIT_EDGE_BITS};
+
+pub use verify::siphash::SipHashKeys;
+
+pub use verify::test_vectors::{TestVector, TEST_VECTORS, test_vector, KNOWN_30_HASH_1, KNOWN_16_HASH_1,
+                KNOWN_16_SOLUTION_1, KNOWN_16_HASH_12, KNOWN_16_SOLUTION_12};
+
+pub use util::hex::{from_hex, to_hex, Header};
+pub use util::memory::available_memory;
+
+// Only public so the tests can reach the reader for plugins' JSON output
+#[doc(hidden)]
+pub use util::json;
+pub use verify::keys::siphash_keys;
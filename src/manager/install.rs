@@ -0,0 +1,290 @@
+// Copyright 2017 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Checks plugin files before they're deployed or loaded, so a truncated
+//! copy, a plugin built against another interface or a mixed up build is
+//! caught with an error rather than a crash when mining starts. Plugins
+//! are identified by a hash of their contents, which deployments can pin
+//! to refuse any other build.
+
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::process;
+use std::time::Duration;
+
+use blake2::blake2b::Blake2b;
+
+use cuckoo_sys::manager::{PluginLibrary, PluginDescription, CuckooPluginParameter, PluginCapabilities};
+use error::error::CuckooMinerError;
+use miner::self_test::SelfTestReport;
+use util::hex::to_hex;
+use verify::test_vectors::test_vector;
+
+/// The longest a plugin is given to pass its self-test during
+/// [verify_plugin](fn.verify_plugin.html)
+pub const VERIFY_SELF_TEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The largest cuckoo size a plugin is self-tested at by
+/// [verify_plugin](fn.verify_plugin.html). Bigger graphs take too long to
+/// search for a quick check.
+pub const QUICK_SELF_TEST_CUCKOO_SIZE: u32 = 16;
+
+/// What [verify_plugin](fn.verify_plugin.html) found out about a plugin
+
+#[derive(Debug, Clone)]
+pub struct PluginReport {
+	/// The plugin's path
+	pub path: String,
+
+	/// The blake2b-256 hash of the plugin's file, in hex, as pinned in
+	/// [CuckooMinerConfig](struct.CuckooMinerConfig.html)::pinned_hashes
+	pub hash: String,
+
+	/// The plugin's interface version as `(major << 16) | minor`, or 0
+	/// for a legacy plugin
+	pub api_version: u32,
+
+	/// The plugin's name and description, if it exports
+	/// `cuckoo_description`
+	pub description: Option<PluginDescription>,
+
+	/// The parameters the plugin accepts
+	pub parameters: Vec<CuckooPluginParameter>,
+
+	/// What the plugin can do, as it declares
+	pub capabilities: PluginCapabilities,
+
+	/// The plugin's self-test, if it searches graphs small enough to be
+	/// tested quickly
+	pub self_test: Option<SelfTestReport>,
+}
+
+/// #Description
+///
+/// Hashes a plugin's file, as it's identified in pinned hash lists.
+///
+/// #Arguments
+///
+/// * `path` (IN) The plugin's file
+///
+/// #Returns
+///
+/// * The file's blake2b-256 hash, in lower case hex
+/// * a PluginNotFoundError or PluginIOError if it can't be read
+///
+
+pub fn plugin_hash<P: AsRef<Path>>(path: P) -> Result<String, CuckooMinerError> {
+	let path = path.as_ref();
+	let mut file = File::open(path).map_err(|e| file_error(path, e))?;
+	let mut blake2b = Blake2b::new(32);
+	let mut buffer = vec![0u8; 64 * 1024];
+	loop {
+		let read = file.read(&mut buffer).map_err(|e| file_error(path, e))?;
+		if read == 0 {
+			break;
+		}
+		blake2b.update(&buffer[..read]);
+	}
+	Ok(to_hex(blake2b.finalize().as_bytes()))
+}
+
+/// #Description
+///
+/// Checks a plugin's file is one of a pinned set of builds. An empty set
+/// pins nothing, so accepts any file. Hashes are compared ignoring case.
+///
+/// #Arguments
+///
+/// * `path` (IN) The plugin's file
+/// * `pinned_hashes` (IN) The hashes, as returned by
+/// [plugin_hash](fn.plugin_hash.html), of the builds allowed
+///
+/// #Returns
+///
+/// * `Ok(())` if the file's allowed
+/// * a PluginHashMismatch error if its hash isn't pinned
+/// * an error as for [plugin_hash](fn.plugin_hash.html) if it can't be
+/// read
+///
+
+pub fn check_pinned_hash<P: AsRef<Path>>(path: P, pinned_hashes: &[String]) -> Result<(), CuckooMinerError> {
+	if pinned_hashes.is_empty() {
+		return Ok(());
+	}
+	let hash = plugin_hash(&path)?;
+	if pinned_hashes.iter().any(|h| h.eq_ignore_ascii_case(&hash)) {
+		return Ok(());
+	}
+	Err(CuckooMinerError::PluginHashMismatch {
+		path: path.as_ref().to_string_lossy().into_owned(),
+		hash: hash,
+	})
+}
+
+/// #Description
+///
+/// Checks a file is a working plugin without mining with it. The plugin's
+/// loaded, which fails if any function the interface requires is missing,
+/// then its description, parameters and capabilities are read, and if it
+/// searches graphs no bigger than
+/// [QUICK_SELF_TEST_CUCKOO_SIZE](constant.QUICK_SELF_TEST_CUCKOO_SIZE.html)
+/// it's given its [self_test](struct.PluginLibrary.html#method.self_test).
+/// The plugin is unloaded again before this returns. Legacy plugins are
+/// accepted, and reported with an api_version of 0, as they still load
+/// where the miner's configured to allow them.
+///
+/// #Arguments
+///
+/// * `path` (IN) The plugin's file
+///
+/// #Returns
+///
+/// * `Ok(PluginReport)` if the plugin's usable
+/// * a PluginNotFoundError, PluginIOError or PluginSymbolMissing error if
+/// the file isn't a loadable plugin, e.g. it's truncated or built against
+/// another interface
+/// * a PluginProcessingError if the plugin failed its self-test
+/// * another [CuckooMinerError](enum.CuckooMinerError.html) if one of
+/// its calls failed
+///
+/// #Example
+///
+/// ```
+///  # use cuckoo_miner::verify_plugin;
+///  # use std::env;
+///  # use std::path::PathBuf;
+///  # let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+///  # d.push("./target/debug/plugins/lean_cpu_16.cuckooplugin");
+///  let report = verify_plugin(&d).unwrap();
+///  println!("{} {:?}", report.hash, report.capabilities);
+/// ```
+///
+
+pub fn verify_plugin<P: AsRef<Path>>(path: P) -> Result<PluginReport, CuckooMinerError> {
+	let path = path.as_ref();
+	let hash = plugin_hash(path)?;
+	let library = PluginLibrary::load(&path.to_string_lossy(), true)?;
+	let report = plugin_report(&library, hash);
+	library.unload();
+	let report = report?;
+	if let Some(SelfTestReport { passed: false, ref failure, .. }) = report.self_test {
+		return Err(CuckooMinerError::PluginProcessingError(format!(
+			"{} failed its self-test: {}",
+			report.path,
+			failure.as_ref().map_or("", |f| f.as_str())
+		)));
+	}
+	Ok(report)
+}
+
+/// #Description
+///
+/// Installs a plugin into a directory, once it's passed
+/// [verify_plugin](fn.verify_plugin.html). The plugin's copied to a
+/// temporary file in the directory, checked against the original's hash,
+/// then renamed into place, so a plugin of the same name is only ever
+/// replaced by a complete copy. The directory's created if need be.
+///
+/// #Arguments
+///
+/// * `src` (IN) The plugin's file
+/// * `dest_dir` (IN) The directory to install it to, under the same file
+/// name
+///
+/// #Returns
+///
+/// * `Ok(PluginReport)` for the installed copy
+/// * an error as for [verify_plugin](fn.verify_plugin.html) if the plugin
+/// doesn't verify, in which case nothing is written
+/// * a PluginIOError if it couldn't be copied intact
+///
+
+pub fn install_plugin<P: AsRef<Path>, Q: AsRef<Path>>(src: P, dest_dir: Q) -> Result<PluginReport, CuckooMinerError> {
+	let (src, dest_dir) = (src.as_ref(), dest_dir.as_ref());
+	let mut report = verify_plugin(src)?;
+	let file_name = match src.file_name() {
+		Some(n) => n,
+		None => {
+			return Err(CuckooMinerError::PluginIOError(
+				format!("{} isn't a plugin file name", src.display()),
+			))
+		}
+	};
+	fs::create_dir_all(dest_dir).map_err(|e| file_error(dest_dir, e))?;
+	let dest = dest_dir.join(file_name);
+	let temp = dest_dir.join(format!(".{}.{}.tmp", file_name.to_string_lossy(), process::id()));
+	let copied = copy_synced(src, &temp).and_then(|_| {
+		let copied_hash = plugin_hash(&temp)?;
+		if copied_hash != report.hash {
+			return Err(CuckooMinerError::PluginIOError(format!(
+				"{} changed while being copied to {}",
+				src.display(),
+				dest.display()
+			)));
+		}
+		fs::rename(&temp, &dest).map_err(|e| file_error(&dest, e))
+	});
+	if let Err(e) = copied {
+		let _ = fs::remove_file(&temp);
+		return Err(e);
+	}
+	info!("Installed plugin {} to {}", src.display(), dest.display());
+	report.path = dest.to_string_lossy().into_owned();
+	Ok(report)
+}
+
+// Reads what a loaded plugin reports about itself, and self-tests it if
+// its graphs are small enough
+fn plugin_report(library: &PluginLibrary, hash: String) -> Result<PluginReport, CuckooMinerError> {
+	let description = match library.description() {
+		Ok(d) => Some(d),
+		Err(CuckooMinerError::PluginSymbolMissing { .. }) => None,
+		Err(e) => return Err(e),
+	};
+	let parameters = library.parameters()?;
+	let capabilities = library.capabilities()?;
+	let quick = capabilities.edge_bits.map_or(false, |e| {
+		e < QUICK_SELF_TEST_CUCKOO_SIZE && test_vector(e, capabilities.proof_size as usize).is_some()
+	});
+	let self_test = if quick { Some(library.self_test(VERIFY_SELF_TEST_TIMEOUT)?) } else { None };
+	Ok(PluginReport {
+		path: library.lib_full_path.clone(),
+		hash: hash,
+		api_version: library.api_version(),
+		description: description,
+		parameters: parameters,
+		capabilities: capabilities,
+		self_test: self_test,
+	})
+}
+
+// Copies a file, only returning once the copy's on disk
+fn copy_synced(src: &Path, dest: &Path) -> Result<(), CuckooMinerError> {
+	let mut input = File::open(src).map_err(|e| file_error(src, e))?;
+	let mut output = File::create(dest).map_err(|e| file_error(dest, e))?;
+	io::copy(&mut input, &mut output).map_err(|e| file_error(dest, e))?;
+	output.flush().and_then(|_| output.sync_all()).map_err(|e| file_error(dest, e))
+}
+
+// The error for a plugin file which can't be read or written
+fn file_error(path: &Path, e: io::Error) -> CuckooMinerError {
+	match e.kind() {
+		io::ErrorKind::NotFound => CuckooMinerError::PluginNotFoundError(path.to_string_lossy().into_owned()),
+		io::ErrorKind::PermissionDenied => {
+			CuckooMinerError::PluginPermissionError(format!("{} - {}", path.display(), e))
+		}
+		_ => CuckooMinerError::PluginIOError(format!("{} - {}", path.display(), e)),
+	}
+}
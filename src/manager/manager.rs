@@ -38,19 +38,20 @@
 
 use std::fmt;
 use std::env;
+use std::collections::HashSet;
+use std::ffi::OsString;
 use std::path::Path;
+use std::time::Duration;
 
 use regex::Regex;
-use glob::glob;
+use glob::{glob, Pattern};
 
-use serde_json;
 
-use cuckoo_sys::manager::PluginLibrary;
+use cuckoo_sys::manager::{PluginLibrary, CuckooPluginParameter, DeviceInfo, PluginCapabilities};
+use cuckoo_sys::path::{plugin_search_dirs, PLUGIN_SUFFIXES, BUILD_PLUGIN_DIR};
 use error::error::CuckooMinerError;
-
-// OS-specific library extensions
-
-static DLL_SUFFIX: &str = "cuckooplugin";
+use miner::self_test::SelfTestReport;
+use super::install::check_pinned_hash;
 
 // Helper function to get the absolute path from a relative path
 
@@ -70,8 +71,22 @@ pub struct CuckooPluginCapabilities {
 	/// The plugin's file name
 	pub file_name: String,
 
+	/// The plugin's reported name, or its file name without the extension
+	/// if it doesn't export cuckoo_description
+	pub name: String,
+
+	/// The plugin's reported description, empty if it doesn't export
+	/// cuckoo_description
+	pub description: String,
+
 	/// The plugin's reported parameters
 	pub parameters: Vec<CuckooPluginParameter>,
+
+	/// The devices the plugin can mine on, empty if it found none
+	pub devices: Vec<DeviceInfo>,
+
+	/// What the plugin can do, such as the size of graph it searches
+	pub capabilities: PluginCapabilities,
 }
 
 impl Default for CuckooPluginCapabilities {
@@ -79,61 +94,65 @@ impl Default for CuckooPluginCapabilities {
 		CuckooPluginCapabilities {
 			full_path: String::from(""),
 			file_name: String::from(""),
+			name: String::from(""),
+			description: String::from(""),
 			parameters: Vec::new(),
+			devices: Vec::new(),
+			capabilities: PluginCapabilities::default(),
 		}
 	}
 }
 
+impl CuckooPluginCapabilities {
+	/// The cuckoo size of the graphs the plugin searches, i.e. its edge
+	/// bits plus one, if it reports them
+
+	pub fn cuckoo_size(&self) -> Option<u32> {
+		self.capabilities.edge_bits.map(|e| e + 1)
+	}
+}
+
 impl fmt::Display for CuckooPluginCapabilities {
 	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
 		write!(
 			f,
-			"Path:{}\nParameters:{}\n",
+			"Name:{}\nDescription:{}\nPath:{}\nParameters:{:?}\nDevices:{:?}\nCapabilities:{:?}\n",
+			self.name,
+			self.description,
 			self.full_path,
-			serde_json::to_string(&self.parameters).unwrap()
+			self.parameters,
+			self.devices,
+			self.capabilities
 		)
 	}
 }
 
-/// Holds a set of plugin parameter descriptions returned from a plugin
-/// as deserialised from json
-
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct CuckooPluginParameter {
-	/// The name of the parameter
-	pub name: String,
-
-	/// Description of the parameter
-	pub description: String,
-
-	/// The default value of the parameter, used if none is provided
-	pub default_value: u32,
-
-	/// The minimum allowed value for the parameter
-	pub min_value: u32,
-
-	/// The maximum allowed value for the parameter
-	pub max_value: u32,
-}
-
 /// A structure that loads and queries all of the plugins in a particular
 /// directory via their [`cuckoo_description`]
 /// (struct.PluginLibrary.html#method.call_cuckoo_description) method
 
 pub struct CuckooPluginManager {
-	// The current directory
-	plugin_dir: String,
+	// The directories most recently scanned
+	plugin_dirs: Vec<String>,
 
 	// Holds the current set of plugin capabilities, as returned
 	// from all of the plugins in the plugin directory
 	current_plugin_caps: Option<Vec<CuckooPluginCapabilities>>,
+
+	// Whether plugins without an interface version are loaded
+	allow_legacy_plugins: bool,
+
+	// The hashes of the plugin builds allowed, or empty for any
+	pinned_hashes: Vec<String>,
 }
 
 impl Default for CuckooPluginManager {
 	fn default() -> CuckooPluginManager {
 		CuckooPluginManager {
-			plugin_dir: String::from("target/debug"),
+			plugin_dirs: vec![String::from("target/debug")],
 			current_plugin_caps: None,
+			allow_legacy_plugins: true,
+			pinned_hashes: Vec::new(),
 		}
 	}
 }
@@ -160,13 +179,35 @@ impl CuckooPluginManager {
 		Ok(CuckooPluginManager::default())
 	}
 
+	/// #Description
+	///
+	/// Sets whether plugins that don't report a plugin interface version
+	/// are scanned and loaded. On by default. When off, they're skipped
+	/// with a warning.
+
+	pub fn set_allow_legacy_plugins(&mut self, allow: bool) {
+		self.allow_legacy_plugins = allow;
+	}
+
+	/// #Description
+	///
+	/// Restricts the plugins scanned and loaded to the builds with the
+	/// given hashes, as reported by [plugin_hash](fn.plugin_hash.html).
+	/// Other plugins are skipped with a warning when scanning, and refused
+	/// by [load](#method.load). Empty by default, allowing any build.
+
+	pub fn set_pinned_hashes(&mut self, hashes: Vec<String>) {
+		self.pinned_hashes = hashes;
+	}
+
 	/// #Description
 	///
 	/// Loads all available plugins in the specified directory one by one,
 	/// calls their cuckoo_description functions, and stores an internal vector
 	/// of [CuckooPluginCapabilities](struct.CuckooPluginCapabilities.html)
 	/// representing the plugins in the directory. This will parse any file
-	/// with the extension `.cuckooplugin`.
+	/// with the extension `.cuckooplugin`, or the platform's library
+	/// extension.
 	///
 	/// #Arguments
 	///
@@ -182,12 +223,69 @@ impl CuckooPluginManager {
 	///
 
 	pub fn load_plugin_dir(&mut self, plugin_dir: String) -> Result<(), CuckooMinerError> {
-		self.plugin_dir = plugin_dir.clone();
-		let caps = self.load_all_plugin_caps(&plugin_dir)?;
+		self.load_plugin_dirs(&[plugin_dir.as_str()])
+	}
+
+	/// #Description
+	///
+	/// As [load_plugin_dir](#method.load_plugin_dir), but scans each of
+	/// the given directories in turn, replacing any previously loaded
+	/// capabilities. Files that can't be loaded as plugins are skipped
+	/// with a warning rather than aborting the scan. Where the same file
+	/// name is in more than one directory, only the first is kept, as
+	/// that's the one a plugin's name resolves to.
+	///
+	/// #Arguments
+	///
+	/// * `plugin_dirs` (IN) The directories to scan, relative to the current
+	/// directory or full paths
+	///
+	/// #Returns
+	///
+	/// * `Ok` if at least one plugin was found across all directories
+	/// * a NoPluginsFoundError otherwise
+	///
+
+	pub fn load_plugin_dirs(&mut self, plugin_dirs: &[&str]) -> Result<(), CuckooMinerError> {
+		let mut caps = Vec::new();
+		let mut seen = HashSet::new();
+		for dir in plugin_dirs {
+			caps.append(&mut self.load_all_plugin_caps(dir, &mut seen));
+		}
+		if caps.len() == 0 {
+			return Err(CuckooMinerError::NoPluginsFoundError(format!(
+				"No plugins found in plugin directories {:?}",
+				plugin_dirs
+			)));
+		}
+		self.plugin_dirs = plugin_dirs.iter().map(|d| String::from(*d)).collect();
 		self.current_plugin_caps = Some(caps);
 		Ok(())
 	}
 
+	/// #Description
+	///
+	/// As [load_plugin_dirs](#method.load_plugin_dirs), scanning the
+	/// plugin search directories: those in `CUCKOO_PLUGIN_PATH`, next to
+	/// the running executable, and the default install location, followed
+	/// by [BUILD_PLUGIN_DIR](constant.BUILD_PLUGIN_DIR.html).
+	///
+	/// #Returns
+	///
+	/// * `Ok` if at least one plugin was found
+	/// * a NoPluginsFoundError listing the directories searched otherwise
+	///
+
+	pub fn load_search_dirs(&mut self) -> Result<(), CuckooMinerError> {
+		let mut dirs: Vec<String> = plugin_search_dirs()
+			.iter()
+			.map(|d| d.to_string_lossy().into_owned())
+			.collect();
+		dirs.push(String::from(BUILD_PLUGIN_DIR));
+		let dirs: Vec<&str> = dirs.iter().map(|d| d.as_str()).collect();
+		self.load_plugin_dirs(&dirs)
+	}
+
 	/// #Description
 	///
 	/// Returns an list of
@@ -240,30 +338,145 @@ impl CuckooPluginManager {
 		&mut self,
 		filter: &str,
 	) -> Result<Vec<CuckooPluginCapabilities>, CuckooMinerError> {
+		let caps = match self.current_plugin_caps {
+			Some(ref c) => c,
+			None => {
+				return Err(CuckooMinerError::NoPluginsFoundError(
+					String::from("No plugin directory has been loaded"),
+				))
+			}
+		};
 		if filter.len() == 0 {
-			return Ok(self.current_plugin_caps.as_mut().unwrap().clone());
-		} else {
-			let result = self.current_plugin_caps
-				.as_mut()
-				.unwrap()
-				.clone()
-				.into_iter()
-				.filter(|ref i| {
-					let re = Regex::new(&format!(r"{}", filter)).unwrap();
-					let caps = re.captures(&i.full_path);
-					match caps {
-						Some(_) => return true,
-						None => return false,
-					}
-				})
-				.collect::<Vec<_>>();
-			if result.len() == 0 {
+			return Ok(caps.clone());
+		}
+		let re = match Regex::new(filter) {
+			Ok(r) => r,
+			Err(e) => {
 				return Err(CuckooMinerError::NoPluginsFoundError(
-					format!("For given filter: {}", filter),
-				));
+					format!("Invalid filter {}: {}", filter, e),
+				))
 			}
-			return Ok(result);
+		};
+		let result = caps.iter()
+			.filter(|i| re.is_match(&i.file_name))
+			.cloned()
+			.collect::<Vec<_>>();
+		if result.len() == 0 {
+			return Err(CuckooMinerError::NoPluginsFoundError(
+				format!("For given filter: {}", filter),
+			));
 		}
+		Ok(result)
+	}
+
+	/// #Description
+	///
+	/// Returns the plugins in the currently loaded directories which
+	/// search graphs of the given size, so a plugin can be picked by what
+	/// it mines rather than by its file name.
+	///
+	/// #Arguments
+	///
+	/// * `cuckoo_size` (IN) The cuckoo size, e.g. 30
+	///
+	/// #Returns
+	///
+	/// * `Ok(Vec<CuckooPluginCapabilities>)` with every plugin which
+	/// reports that size, in scan order
+	/// * a NoPluginsFoundError if there are none, or no plugin directory
+	/// has been loaded
+	///
+
+	pub fn get_plugins_with_cuckoo_size(
+		&mut self,
+		cuckoo_size: u32,
+	) -> Result<Vec<CuckooPluginCapabilities>, CuckooMinerError> {
+		let result = self.get_available_plugins("")?
+			.into_iter()
+			.filter(|c| c.cuckoo_size() == Some(cuckoo_size))
+			.collect::<Vec<_>>();
+		if result.len() == 0 {
+			return Err(CuckooMinerError::NoPluginsFoundError(
+				format!("For cuckoo size: {}", cuckoo_size),
+			));
+		}
+		Ok(result)
+	}
+
+	/// #Description
+	///
+	/// Loads every plugin matching the given filter, ready for mining.
+	///
+	/// #Arguments
+	///
+	/// * `filter` As in [get_available_plugins](#method.get_available_plugins)
+	///
+	/// #Returns
+	///
+	/// * `Ok(Vec<PluginLibrary>)` with each matching plugin loaded
+	/// * a PluginHashMismatch error if a matching plugin's hash isn't
+	/// pinned, when hashes are
+	/// * a [CuckooMinerError](enum.CuckooMinerError.html) if no plugins
+	/// match or a matching plugin can't be loaded
+	///
+	/// #Example
+	///
+	/// ```
+	/// extern crate cuckoo_miner as cuckoo;
+	/// use std::path::PathBuf;
+	/// let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+	/// d.push("target/debug/plugins/");
+	///
+	/// let mut plugin_manager = cuckoo::CuckooPluginManager::new().unwrap();
+	/// plugin_manager
+	/// 	.load_plugin_dir(String::from(d.to_str().unwrap()))
+	/// 	.expect("");
+	/// let libraries = plugin_manager.load("lean_cpu_16").unwrap();
+	/// ```
+	///
+
+	pub fn load(&mut self, filter: &str) -> Result<Vec<PluginLibrary>, CuckooMinerError> {
+		let mut libraries = Vec::new();
+		for c in self.get_available_plugins(filter)? {
+			check_pinned_hash(&c.full_path, &self.pinned_hashes)?;
+			libraries.push(PluginLibrary::load(&c.full_path, self.allow_legacy_plugins)?);
+		}
+		Ok(libraries)
+	}
+
+	/// #Description
+	///
+	/// Runs the [self_test](struct.PluginLibrary.html#method.self_test) of
+	/// every available plugin, loading each in turn and unloading it once
+	/// tested.
+	///
+	/// #Arguments
+	///
+	/// * `timeout` (IN) The longest time each plugin is given to find its
+	/// test header's solution
+	///
+	/// #Returns
+	///
+	/// * `Ok(Vec<Result<SelfTestReport, CuckooMinerError>>)` with an entry
+	/// per plugin, in the order of [get_available_plugins](#method.get_available_plugins).
+	/// An entry is an error if the plugin couldn't be loaded or tested,
+	/// e.g. there's no test vector for its graph size.
+	/// * a NoPluginsFoundError if no plugin directory has been loaded
+	///
+
+	pub fn self_test_all(
+		&mut self,
+		timeout: Duration,
+	) -> Result<Vec<Result<SelfTestReport, CuckooMinerError>>, CuckooMinerError> {
+		let mut reports = Vec::new();
+		for c in self.get_available_plugins("")? {
+			reports.push(PluginLibrary::load(&c.full_path, self.allow_legacy_plugins).and_then(|library| {
+				let report = library.self_test(timeout);
+				library.unload();
+				report
+			}));
+		}
+		Ok(reports)
 	}
 
 	/// Fills out and Returns a CuckooPluginCapabilities structure parsed from a
@@ -271,61 +484,76 @@ impl CuckooPluginManager {
 
 	fn load_plugin_caps(
 		&mut self,
-		full_path: String,
+		full_path: &Path,
 	) -> Result<CuckooPluginCapabilities, CuckooMinerError> {
-		debug!("Querying plugin at {}", full_path);
-		let library = PluginLibrary::new(&full_path).unwrap();
+		let full_path_str = String::from(full_path.to_str().unwrap());
+		debug!("Querying plugin at {}", full_path_str);
+		check_pinned_hash(full_path, &self.pinned_hashes)?;
+		let library = PluginLibrary::load(&full_path_str, self.allow_legacy_plugins)?;
 		let mut caps = CuckooPluginCapabilities::default();
 
-		caps.full_path = full_path.clone();
-		caps.file_name = String::from("");
-
-		let mut param_list_bytes: [u8; 4096] = [0; 4096];
-		let mut param_list_len = param_list_bytes.len() as u32;
-		// get a list of parameters
-		library.call_cuckoo_parameter_list(&mut param_list_bytes, &mut param_list_len);
-		let mut param_list_vec: Vec<u8> = Vec::new();
-		// result contains null zero
-		for i in 0..param_list_len {
-			param_list_vec.push(param_list_bytes[i as usize].clone());
+		caps.full_path = full_path_str;
+		caps.file_name = full_path.file_name().unwrap().to_string_lossy().into_owned();
+		match library.description() {
+			Ok(d) => {
+				caps.name = d.name;
+				caps.description = d.description;
+			}
+			Err(_) => {
+				caps.name = full_path.file_stem().unwrap().to_string_lossy().into_owned();
+			}
 		}
-		let param_list_json = String::from_utf8(param_list_vec)?;
-		caps.parameters = serde_json::from_str(&param_list_json).unwrap();
 
+		let parameters = library.parameters();
+		let devices = library.list_devices();
+		let capabilities = library.capabilities();
 		library.unload();
+		caps.parameters = parameters?;
+		caps.devices = devices?;
+		caps.capabilities = capabilities?;
 
 		return Ok(caps);
 	}
 
-	/// Loads and fills out the internal plugin capabilites vector from the
-	/// given directory.
+	/// Returns the capabilities of every plugin that can be loaded from the
+	/// given directory, skipping any that can't with a warning, and any
+	/// whose file name has already been seen.
 
 	fn load_all_plugin_caps(
 		&mut self,
 		plugin_dir: &str,
-	) -> Result<Vec<CuckooPluginCapabilities>, CuckooMinerError> {
+		seen: &mut HashSet<OsString>,
+	) -> Vec<CuckooPluginCapabilities> {
 		let lib_full_path = abspath(Path::new(&plugin_dir));
-		let glob_search_path = format!("{}/*.{}", lib_full_path, DLL_SUFFIX);
-
 		let mut result_vec: Vec<CuckooPluginCapabilities> = Vec::new();
 
-		for entry in glob(&glob_search_path).expect("Failed to read glob pattern") {
-			match entry {
-				Ok(path) => {
-					let caps = self.load_plugin_caps(String::from(path.to_str().unwrap()))?;
-					result_vec.push(caps);
+		for suffix in PLUGIN_SUFFIXES {
+			let glob_search_path = Path::new(&Pattern::escape(&lib_full_path))
+				.join(format!("*.{}", suffix));
+			let entries = match glob(&glob_search_path.to_string_lossy()) {
+				Ok(e) => e,
+				Err(e) => {
+					warn!("Unable to search plugin directory {}: {}", lib_full_path, e);
+					return result_vec;
+				}
+			};
+			for entry in entries {
+				match entry {
+					Ok(path) => {
+						if !seen.insert(path.file_name().unwrap().to_os_string()) {
+							debug!("Skipping {}, shadowed by a plugin earlier in the search", path.display());
+							continue;
+						}
+						match self.load_plugin_caps(&path) {
+							Ok(caps) => result_vec.push(caps),
+							Err(e) => warn!("Skipping plugin {}: {:?}", path.display(), e),
+						}
+					}
+					Err(e) => error!("{:?}", e),
 				}
-				Err(e) => error!("{:?}", e),
 			}
 		}
 
-		if result_vec.len() == 0 {
-			return Err(CuckooMinerError::NoPluginsFoundError(format!(
-				"No plugins found in plugin directory {}",
-				lib_full_path.clone()
-			)));
-		}
-
-		Ok(result_vec)
+		result_vec
 	}
 }
@@ -33,3 +33,4 @@ extern crate regex;
 extern crate glob;
 
 pub mod manager;
+pub mod install;
@@ -0,0 +1,272 @@
+// Copyright 2017 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Keeps a miner off cores and out of the way of other work on the same
+//! machine, such as a node. Plugins are asked to pin and deprioritise
+//! their own threads through standard parameters, and the miner does the
+//! same for the threads it runs for each plugin. Where the platform can't
+//! do either, the setting is logged and ignored.
+
+use std::str::FromStr;
+
+use error::error::CuckooMinerError;
+
+/// The standard 64-bit parameter holding the cores a plugin's threads may
+/// run on, as a mask with bit `n` set for core `n`, or 0 for any core. Set
+/// through the plugin's 64-bit parameter call, or its 32-bit one if it has
+/// no 64-bit calls and the mask fits. Plugins which don't declare it run
+/// wherever the OS puts them, and only the miner's own threads are pinned.
+pub const CPU_AFFINITY_MASK_PARAM: &str = "CPU_AFFINITY_MASK";
+
+/// The standard parameter holding the scheduling priority of a plugin's
+/// threads, as the value of a [Priority](enum.Priority.html)
+pub const THREAD_PRIORITY_PARAM: &str = "THREAD_PRIORITY";
+
+/// The most cores an affinity mask can name
+pub const MAX_AFFINITY_CORES: usize = 64;
+
+/// The scheduling priority of mining threads
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum Priority {
+	/// Only runs when nothing else wants the core
+	Idle,
+
+	/// Yields to most other work
+	Low,
+
+	/// The OS's default
+	Normal,
+
+	/// Ahead of other work. Usually needs privileges, without which the
+	/// thread is left at its priority.
+	High,
+}
+
+impl Priority {
+	/// The value of [THREAD_PRIORITY_PARAM](constant.THREAD_PRIORITY_PARAM.html)
+	/// for this priority, from 0 for Idle to 3 for High
+
+	pub fn value(&self) -> u32 {
+		match *self {
+			Priority::Idle => 0,
+			Priority::Low => 1,
+			Priority::Normal => 2,
+			Priority::High => 3,
+		}
+	}
+
+	// The nice value the miner's own threads are given on Linux
+	#[cfg(target_os = "linux")]
+	fn nice(&self) -> i32 {
+		match *self {
+			Priority::Idle => 19,
+			Priority::Low => 10,
+			Priority::Normal => 0,
+			Priority::High => -10,
+		}
+	}
+}
+
+impl FromStr for Priority {
+	type Err = CuckooMinerError;
+
+	fn from_str(s: &str) -> Result<Priority, CuckooMinerError> {
+		match s {
+			"idle" => Ok(Priority::Idle),
+			"low" => Ok(Priority::Low),
+			"normal" => Ok(Priority::Normal),
+			"high" => Ok(Priority::High),
+			_ => Err(CuckooMinerError::ParameterError(format!(
+				"Unknown thread priority {}, expected idle, low, normal or high",
+				s
+			))),
+		}
+	}
+}
+
+/// #Description
+///
+/// Checks a set of core indices, and returns them as the mask passed in
+/// [CPU_AFFINITY_MASK_PARAM](constant.CPU_AFFINITY_MASK_PARAM.html).
+///
+/// #Arguments
+///
+/// * `cores` (IN) The indices of the cores to run on, from 0
+///
+/// #Returns
+///
+/// * The mask, with bit `n` set for core `n`
+/// * A ParameterError if no cores are given, or one is beyond
+/// [MAX_AFFINITY_CORES](constant.MAX_AFFINITY_CORES.html) or the number
+/// of cores online, where the platform reports it
+///
+
+pub fn affinity_mask(cores: &[usize]) -> Result<u64, CuckooMinerError> {
+	if cores.is_empty() {
+		return Err(CuckooMinerError::ParameterError(
+			String::from("A CPU affinity must name at least one core"),
+		));
+	}
+	let limit = match online_cores() {
+		Some(n) if n < MAX_AFFINITY_CORES => n,
+		_ => MAX_AFFINITY_CORES,
+	};
+	let mut mask = 0;
+	for &c in cores {
+		if c >= limit {
+			return Err(CuckooMinerError::ParameterError(format!(
+				"No core {} to run on, cores are numbered from 0 to {}",
+				c,
+				limit - 1
+			)));
+		}
+		mask |= 1 << c;
+	}
+	Ok(mask)
+}
+
+/// The number of cores online, if the platform reports it
+
+#[cfg(unix)]
+pub fn online_cores() -> Option<usize> {
+	match unsafe { ::libc::sysconf(::libc::_SC_NPROCESSORS_ONLN) } {
+		n if n > 0 => Some(n as usize),
+		_ => None,
+	}
+}
+
+/// The number of cores online, if the platform reports it
+
+#[cfg(not(unix))]
+pub fn online_cores() -> Option<usize> {
+	None
+}
+
+/// #Description
+///
+/// Restricts the calling thread to the given cores.
+///
+/// #Arguments
+///
+/// * `cores` (IN) The indices of the cores to run on, as for
+/// [affinity_mask](fn.affinity_mask.html)
+///
+/// #Returns
+///
+/// * `Ok(true)` if the thread was pinned
+/// * `Ok(false)` if the platform can't pin threads, or refused, which is
+/// logged
+/// * A ParameterError if the cores are invalid
+///
+
+#[cfg(target_os = "linux")]
+pub fn pin_current_thread(cores: &[usize]) -> Result<bool, CuckooMinerError> {
+	use std::mem;
+	affinity_mask(cores)?;
+	unsafe {
+		let mut set: ::libc::cpu_set_t = mem::zeroed();
+		for &c in cores {
+			::libc::CPU_SET(c, &mut set);
+		}
+		if ::libc::sched_setaffinity(0, mem::size_of::<::libc::cpu_set_t>(), &set) != 0 {
+			warn!(
+				"Unable to pin a mining thread to cores {:?}: {}",
+				cores,
+				::std::io::Error::last_os_error()
+			);
+			return Ok(false);
+		}
+	}
+	Ok(true)
+}
+
+/// #Description
+///
+/// Restricts the calling thread to the given cores.
+///
+/// #Arguments
+///
+/// * `cores` (IN) The indices of the cores to run on, as for
+/// [affinity_mask](fn.affinity_mask.html)
+///
+/// #Returns
+///
+/// * `Ok(true)` if the thread was pinned
+/// * `Ok(false)` if the platform can't pin threads, or refused, which is
+/// logged
+/// * A ParameterError if the cores are invalid
+///
+
+#[cfg(not(target_os = "linux"))]
+pub fn pin_current_thread(cores: &[usize]) -> Result<bool, CuckooMinerError> {
+	affinity_mask(cores)?;
+	info!("Pinning threads to cores isn't supported on this platform, ignoring cores {:?}", cores);
+	Ok(false)
+}
+
+/// #Description
+///
+/// Sets the scheduling priority of the calling thread.
+///
+/// #Arguments
+///
+/// * `priority` (IN) The priority
+///
+/// #Returns
+///
+/// Whether the priority was set. It isn't if the platform can't set a
+/// thread's priority, or refused, e.g. raising it without privileges,
+/// which is logged.
+///
+
+#[cfg(target_os = "linux")]
+pub fn set_current_thread_priority(priority: Priority) -> bool {
+	unsafe {
+		// on Linux each thread has its own nice value, set through its id
+		let tid = ::libc::syscall(::libc::SYS_gettid);
+		if ::libc::setpriority(::libc::PRIO_PROCESS, tid as ::libc::id_t, priority.nice()) != 0 {
+			warn!(
+				"Unable to set a mining thread's priority to {:?}: {}",
+				priority,
+				::std::io::Error::last_os_error()
+			);
+			return false;
+		}
+	}
+	true
+}
+
+/// #Description
+///
+/// Sets the scheduling priority of the calling thread.
+///
+/// #Arguments
+///
+/// * `priority` (IN) The priority
+///
+/// #Returns
+///
+/// Whether the priority was set. It isn't if the platform can't set a
+/// thread's priority, or refused, e.g. raising it without privileges,
+/// which is logged.
+///
+
+#[cfg(not(target_os = "linux"))]
+pub fn set_current_thread_priority(priority: Priority) -> bool {
+	info!("Setting thread priorities isn't supported on this platform, ignoring {:?}", priority);
+	false
+}
@@ -0,0 +1,342 @@
+// Copyright 2017 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Benchmarks a plugin over a fixed run of graphs, so the effect of its
+//! parameters, or of the queue against synchronous calls, can be
+//! measured consistently, and plugins compared. With the `serde` feature,
+//! reports can be written out as JSON.
+
+use std::collections::HashMap;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use byteorder::{ByteOrder, BigEndian};
+
+use super::watchdog::{Watchdog, DEFAULT_STOP_TIMEOUT};
+use cuckoo_sys::manager::PluginLibrary;
+use cuckoo_sys::state::PluginState;
+use error::error::CuckooMinerError;
+#[cfg(feature = "serde")]
+use serde_json;
+
+/// How often the plugin's stats and output queue are read while
+/// benchmarking through the queue
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// How a benchmark calls the plugin
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum BenchmarkMode {
+	/// One graph at a time through the plugin's synchronous call, timed
+	/// around each call
+	Sync,
+
+	/// Through the plugin's input queue, with the graph times the plugin
+	/// reports in its stats
+	Async,
+}
+
+/// Where the wall time of a benchmark run went
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct BenchmarkPhases {
+	/// Getting the plugin ready, i.e. creating its solver context for a
+	/// synchronous run, or starting processing for a queued one
+	#[cfg_attr(feature = "serde", serde(with = "super::serialize::duration_ms"))]
+	pub setup: Duration,
+
+	/// Attempting the graphs
+	#[cfg_attr(feature = "serde", serde(with = "super::serialize::duration_ms"))]
+	pub search: Duration,
+
+	/// Putting the plugin back as it was, i.e. destroying the solver
+	/// context, or stopping processing and clearing the queues
+	#[cfg_attr(feature = "serde", serde(with = "super::serialize::duration_ms"))]
+	pub teardown: Duration,
+}
+
+/// The results of benchmarking a plugin. With the `serde` feature, times
+/// are serialised in milliseconds.
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct BenchmarkReport {
+	/// The plugin's path
+	pub plugin: String,
+
+	/// How the plugin was called
+	pub mode: BenchmarkMode,
+
+	/// The number of graphs timed
+	pub graphs: usize,
+
+	/// The fastest graph
+	#[cfg_attr(feature = "serde", serde(with = "super::serialize::duration_ms"))]
+	pub min_graph_time: Duration,
+
+	/// The median graph time
+	#[cfg_attr(feature = "serde", serde(with = "super::serialize::duration_ms"))]
+	pub median_graph_time: Duration,
+
+	/// The mean graph time
+	#[cfg_attr(feature = "serde", serde(with = "super::serialize::duration_ms"))]
+	pub mean_graph_time: Duration,
+
+	/// The slowest graph
+	#[cfg_attr(feature = "serde", serde(with = "super::serialize::duration_ms"))]
+	pub max_graph_time: Duration,
+
+	/// Graphs completed per second over the whole run, across all of the
+	/// plugin's devices
+	pub graphs_per_second: f64,
+
+	/// The number of graphs in which a solution was found
+	pub solutions_found: u32,
+
+	/// The wall time of the whole run
+	#[cfg_attr(feature = "serde", serde(with = "super::serialize::duration_ms"))]
+	pub elapsed: Duration,
+
+	/// Whether a synchronous run reused the plugin's solver context
+	/// between graphs, rather than the plugin setting it up for each
+	pub persistent_context: bool,
+
+	/// The time spent in each phase of the run, which adds up to about
+	/// `elapsed`
+	pub phases: BenchmarkPhases,
+}
+
+impl BenchmarkReport {
+	/// #Description
+	///
+	/// Summarises a benchmark run.
+	///
+	/// #Arguments
+	///
+	/// * `plugin` (IN) The plugin's path
+	/// * `mode` (IN) How the plugin was called
+	/// * `graph_times` (IN) The time each graph took, in any order
+	/// * `solutions_found` (IN) The number of solutions found
+	/// * `elapsed` (IN) The wall time of the run
+	///
+	/// #Returns
+	///
+	/// The report. The graph times are all zero if no graphs were timed.
+	///
+
+	pub fn new(
+		plugin: &str,
+		mode: BenchmarkMode,
+		graph_times: &[Duration],
+		solutions_found: u32,
+		elapsed: Duration,
+	) -> BenchmarkReport {
+		let mut times = graph_times.to_vec();
+		times.sort();
+		let zero = Duration::new(0, 0);
+		let n = times.len();
+		let median = match n {
+			0 => zero,
+			n if n % 2 == 1 => times[n / 2],
+			n => (times[n / 2 - 1] + times[n / 2]) / 2,
+		};
+		let mean = match n {
+			0 => zero,
+			n => times.iter().fold(zero, |sum, t| sum + *t) / n as u32,
+		};
+		let secs = elapsed.as_secs() as f64 + elapsed.subsec_nanos() as f64 / 1_000_000_000.0;
+		BenchmarkReport {
+			plugin: String::from(plugin),
+			mode: mode,
+			graphs: n,
+			min_graph_time: times.first().cloned().unwrap_or(zero),
+			median_graph_time: median,
+			mean_graph_time: mean,
+			max_graph_time: times.last().cloned().unwrap_or(zero),
+			graphs_per_second: if secs > 0.0 { n as f64 / secs } else { 0.0 },
+			solutions_found: solutions_found,
+			elapsed: elapsed,
+			persistent_context: false,
+			phases: BenchmarkPhases::default(),
+		}
+	}
+
+	/// The report as a JSON object
+
+	#[cfg(feature = "serde")]
+	pub fn to_json(&self) -> String {
+		serde_json::to_string(self).unwrap()
+	}
+}
+
+/// #Description
+///
+/// Writes benchmark reports out as a JSON array, one object per report,
+/// with times in milliseconds, e.g. to keep with the plugin builds they
+/// came from or to compare plugins. Only built with the `serde` feature.
+///
+/// #Example
+///
+/// ```no_run
+///  # use cuckoo_miner::{CuckooMiner, CuckooMinerConfig, BenchmarkMode, benchmark_json};
+///  let mut lean = CuckooMinerConfig::new();
+///  lean.plugin_full_path = String::from("lean_cpu_30");
+///  let mut mean = CuckooMinerConfig::new();
+///  mean.plugin_full_path = String::from("mean_cpu_30");
+///  let miner = CuckooMiner::new(vec![lean, mean]).unwrap();
+///  let reports = miner.benchmark_all(10, 0, BenchmarkMode::Sync).unwrap();
+///  println!("{}", benchmark_json(&reports));
+/// ```
+///
+
+#[cfg(feature = "serde")]
+pub fn benchmark_json(reports: &[BenchmarkReport]) -> String {
+	serde_json::to_string_pretty(reports).unwrap()
+}
+
+/// The header for the `index`th graph of a benchmark, which is all zeroes
+/// but for `header_seed + index` as a Big-Endian u64 in its first 8
+/// bytes, so runs with the same seed attempt the same graphs
+
+pub fn benchmark_header(header_seed: u64, index: u64) -> [u8; 32] {
+	let mut header = [0; 32];
+	BigEndian::write_u64(&mut header[..8], header_seed.wrapping_add(index));
+	header
+}
+
+/// #Description
+///
+/// Benchmarks a plugin through its input queue, pushing `iterations`
+/// headers from [benchmark_header](fn.benchmark_header.html) and timing
+/// each graph from the plugin's stats. The plugin must be idle, and is
+/// stopped, reset and has its queues cleared afterwards, so it's idle
+/// again however the run ends.
+///
+/// #Returns
+///
+/// * `Ok(BenchmarkReport)` once `iterations` graphs have completed
+/// * A PluginStateError if the plugin isn't idle
+/// * A PluginProcessingError if a device fails, or the plugin makes no
+/// progress for [DEFAULT_STOP_TIMEOUT](constant.DEFAULT_STOP_TIMEOUT.html)
+/// * Another [CuckooMinerError](enum.CuckooMinerError.html) if a plugin
+/// call fails
+///
+
+pub fn benchmark_queue(
+	library: &PluginLibrary,
+	iterations: u32,
+	header_seed: u64,
+) -> Result<BenchmarkReport, CuckooMinerError> {
+	if library.state() != PluginState::Idle {
+		return Err(CuckooMinerError::PluginStateError(format!(
+			"{} must be idle to benchmark, but is {:?}",
+			library.lib_full_path,
+			library.state()
+		)));
+	}
+	// graphs are counted from the devices' totals so far
+	let mut completed: HashMap<String, u32> = library
+		.stats()?
+		.into_iter()
+		.map(|s| (s.device_id, s.iterations_completed))
+		.collect();
+	let setup_start = Instant::now();
+	library.start_processing()?;
+	let setup = setup_start.elapsed();
+	let result = run_queue(library, iterations, header_seed, &mut completed);
+	// put the plugin back as it was, however the run went
+	let teardown_start = Instant::now();
+	let stopped = library.stop_and_wait(DEFAULT_STOP_TIMEOUT);
+	library.clear_input_queue()?;
+	library.clear_output_queue()?;
+	let teardown = teardown_start.elapsed();
+	let mut report = result?;
+	stopped?;
+	report.phases = BenchmarkPhases {
+		setup: setup,
+		search: report.elapsed,
+		teardown: teardown,
+	};
+	report.elapsed += setup + teardown;
+	Ok(report)
+}
+
+fn run_queue(
+	library: &PluginLibrary,
+	iterations: u32,
+	header_seed: u64,
+	completed: &mut HashMap<String, u32>,
+) -> Result<BenchmarkReport, CuckooMinerError> {
+	let start = Instant::now();
+	let mut watchdog = Watchdog::new(DEFAULT_STOP_TIMEOUT);
+	let mut pushed = 0;
+	let mut times = Vec::new();
+	let mut solutions = 0;
+	while times.len() < iterations as usize {
+		while pushed < iterations {
+			let mut nonce = [0; 8];
+			BigEndian::write_u64(&mut nonce, header_seed.wrapping_add(pushed as u64));
+			let header = benchmark_header(header_seed, pushed as u64);
+			match library.push_to_input_queue(0, &header, &nonce) {
+				Ok(()) => pushed += 1,
+				Err(CuckooMinerError::QueueFull { .. }) => break,
+				Err(e) => return Err(e),
+			}
+		}
+		solutions += library.drain_solutions(usize::max_value()).len() as u32;
+		let stats = library.stats()?;
+		for s in &stats {
+			if s.has_errored {
+				return Err(CuckooMinerError::PluginProcessingError(format!(
+					"{} device {} failed while benchmarking: {}",
+					library.lib_full_path,
+					s.device_id,
+					s.error_reason
+				)));
+			}
+			let last = completed.entry(s.device_id.clone()).or_insert(0);
+			// a count going backwards has been reset
+			let new_graphs = if s.iterations_completed >= *last {
+				s.iterations_completed - *last
+			} else {
+				s.iterations_completed
+			};
+			// only the latest graph's time is reported, so graphs
+			// completed between reads are taken to have taken as long
+			for _ in 0..new_graphs {
+				times.push(Duration::from_millis(s.last_solution_time));
+			}
+			*last = s.iterations_completed;
+		}
+		if watchdog.check(0, &stats) {
+			return Err(CuckooMinerError::PluginProcessingError(format!(
+				"{} made no progress for {:?} while benchmarking",
+				library.lib_full_path,
+				watchdog.timeout()
+			)));
+		}
+		thread::sleep(POLL_INTERVAL);
+	}
+	times.truncate(iterations as usize);
+	Ok(BenchmarkReport::new(
+		&library.lib_full_path,
+		BenchmarkMode::Async,
+		&times,
+		solutions,
+		start.elapsed(),
+	))
+}
@@ -0,0 +1,157 @@
+// Copyright 2017 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Counts the failures of each plugin in a job, so one which keeps
+//! failing is benched for a while rather than restarted forever.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Default number of failures after which a plugin is blacklisted
+pub const DEFAULT_FAILURE_THRESHOLD: u32 = 3;
+
+/// Default time a blacklisted plugin is benched before it's retried
+pub const DEFAULT_BLACKLIST_COOLDOWN: Duration = Duration::from_secs(300);
+
+/// Failure history of a single plugin
+#[derive(Default)]
+struct PluginRecord {
+	/// Failures since the plugin was last blacklisted or retried
+	failures: u32,
+
+	/// When the plugin may be retried, while it's blacklisted
+	blacklisted_until: Option<Instant>,
+
+	/// When the plugin was last retried after being blacklisted
+	retried_at: Option<Instant>,
+}
+
+/// #Description
+///
+/// Tracks plugin failures, such as failing to start processing, a
+/// device crashing, hanging or timing out on shutdown. A plugin which
+/// fails the threshold number of times is blacklisted for the cool-down
+/// period, after which it's retried once. If it fails again within a
+/// cool-down period of the retry, it's blacklisted again straight away.
+
+pub struct FailureTracker {
+	/// Failures after which a plugin is blacklisted
+	threshold: u32,
+
+	/// How long a blacklisted plugin is benched
+	cooldown: Duration,
+
+	/// History per plugin index
+	plugins: HashMap<usize, PluginRecord>,
+}
+
+impl Default for FailureTracker {
+	fn default() -> FailureTracker {
+		FailureTracker::new(DEFAULT_FAILURE_THRESHOLD, DEFAULT_BLACKLIST_COOLDOWN)
+	}
+}
+
+impl FailureTracker {
+	/// Returns a new tracker, blacklisting plugins for `cooldown` once
+	/// they've failed `threshold` times. A threshold of 0 is taken as 1.
+
+	pub fn new(threshold: u32, cooldown: Duration) -> FailureTracker {
+		FailureTracker {
+			threshold: threshold,
+			cooldown: cooldown,
+			plugins: HashMap::new(),
+		}
+	}
+
+	/// How long a blacklisted plugin is benched
+	pub fn cooldown(&self) -> Duration {
+		self.cooldown
+	}
+
+	/// Records a failure of the plugin at `plugin_index`, as of now.
+	/// See [record_failure_at](#method.record_failure_at).
+
+	pub fn record_failure(&mut self, plugin_index: usize) -> bool {
+		self.record_failure_at(plugin_index, Instant::now())
+	}
+
+	/// #Description
+	///
+	/// Records a failure of the plugin at `plugin_index`.
+	///
+	/// #Arguments
+	///
+	/// * `plugin_index` (IN) The index of the plugin which failed
+	/// * `now` (IN) The time of the failure. Failures are expected in time
+	/// order.
+	///
+	/// #Returns
+	///
+	/// true if this failure blacklists the plugin, false if it doesn't or
+	/// the plugin is already blacklisted
+	///
+
+	pub fn record_failure_at(&mut self, plugin_index: usize, now: Instant) -> bool {
+		let threshold = self.threshold;
+		let cooldown = self.cooldown;
+		let r = self.plugins.entry(plugin_index).or_insert_with(PluginRecord::default);
+		if r.blacklisted_until.is_some() {
+			return false;
+		}
+		r.failures += 1;
+		let on_probation = r.retried_at.map_or(false, |t| now.duration_since(t) < cooldown);
+		if !on_probation && r.failures < threshold {
+			return false;
+		}
+		r.failures = 0;
+		r.retried_at = None;
+		r.blacklisted_until = Some(now + cooldown);
+		true
+	}
+
+	/// Whether the plugin at `plugin_index` is blacklisted
+
+	pub fn is_blacklisted(&self, plugin_index: usize) -> bool {
+		self.plugins
+			.get(&plugin_index)
+			.map_or(false, |r| r.blacklisted_until.is_some())
+	}
+
+	/// Whether the plugin at `plugin_index` is blacklisted and its
+	/// cool-down has passed as of `now`
+
+	pub fn retry_due_at(&self, plugin_index: usize, now: Instant) -> bool {
+		self.plugins
+			.get(&plugin_index)
+			.and_then(|r| r.blacklisted_until)
+			.map_or(false, |t| now >= t)
+	}
+
+	/// Takes the plugin at `plugin_index` off the blacklist to be retried
+	/// at `now`, on probation for a cool-down period
+
+	pub fn begin_retry_at(&mut self, plugin_index: usize, now: Instant) {
+		if let Some(r) = self.plugins.get_mut(&plugin_index) {
+			r.blacklisted_until = None;
+			r.retried_at = Some(now);
+		}
+	}
+
+	/// The number of failures counted towards blacklisting the plugin at
+	/// `plugin_index`
+
+	pub fn failures(&self, plugin_index: usize) -> u32 {
+		self.plugins.get(&plugin_index).map_or(0, |r| r.failures)
+	}
+}
@@ -0,0 +1,582 @@
+// Copyright 2017 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Loads miner configs from a TOML file, so a miner's plugins, their
+//! parameters and how it mines can be set up without code. See `doc/cuckoo-miner.toml`
+//! for a documented example.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use std::str::FromStr;
+
+use toml::Value;
+use toml::value::Table;
+
+use super::miner::CuckooMinerConfig;
+use super::devices::DeviceConfig;
+use super::nonce::{NonceRange, NonceEncoding};
+use super::affinity::Priority;
+use cuckoo_sys::manager::{PluginLibrary, CuckooPluginParameter, ALL_DEVICES};
+use error::error::CuckooMinerError;
+
+/// The keys recognised at the top level of a config file
+const ROOT_KEYS: &[&str] = &["mode", "plugin"];
+
+/// The keys recognised in each `[[plugin]]` table
+const PLUGIN_KEYS: &[&str] = &[
+	"name",
+	"path",
+	"allow_legacy",
+	"devices",
+	"instance_per_device",
+	"queue_size",
+	"nonce_range",
+	"parameters",
+	"device_parameters",
+	"cpu_affinity",
+	"thread_priority",
+	"nonce_encoding",
+	"skip_memory_check",
+	"pinned_hashes",
+];
+
+/// The keys recognised in a plugin's `nonce_range` table
+const NONCE_RANGE_KEYS: &[&str] = &["start", "stride"];
+
+/// The key in a plugin's `device_parameters` table which applies to every
+/// device
+const ALL_DEVICES_KEY: &str = "all";
+
+impl CuckooMinerConfig {
+	/// #Description
+	///
+	/// Reads the configs for a miner from a TOML file, with a `[[plugin]]`
+	/// table for each plugin to load, and checks each plugin's parameters
+	/// against those the plugin declares. Each plugin is loaded briefly
+	/// to read its parameters, and unloaded again before returning.
+	///
+	/// A plugin given by `path` is found relative to the file's directory
+	/// if the path is relative, while one given by `name` is looked for
+	/// in the plugin search directories when the miner loads it. Unknown
+	/// keys are logged as warnings and otherwise ignored.
+	///
+	/// #Arguments
+	///
+	/// * `path` (IN) The path of the TOML file
+	///
+	/// #Returns
+	///
+	/// * `Ok(Vec<CuckooMinerConfig>)` with a config per plugin, in the
+	/// order they appear in the file, ready to pass to
+	/// [CuckooMiner::new](struct.CuckooMiner.html#method.new)
+	/// * a ConfigError if the file can't be read, isn't valid TOML, or a
+	/// value has the wrong type or doesn't fit
+	/// * an UnknownParameter error naming the plugin and parameter if a
+	/// plugin doesn't declare one of the parameters set for it
+	/// * a ParameterOutOfRange error if a value is outside the range the
+	/// plugin declares for it
+	/// * another [CuckooMinerError](enum.CuckooMinerError.html) if a
+	/// plugin can't be loaded
+	///
+	/// #Example
+	///
+	/// ```no_run
+	///  # use cuckoo_miner::{CuckooMiner, CuckooMinerConfig};
+	///  let configs = CuckooMinerConfig::from_file("cuckoo-miner.toml").unwrap();
+	///  let miner = CuckooMiner::new(configs).unwrap();
+	/// ```
+	///
+
+	pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Vec<CuckooMinerConfig>, CuckooMinerError> {
+		let configs = read_file(path.as_ref())?.plugins;
+		for c in &configs {
+			let parameters = PluginLibrary::load(&c.plugin_full_path, c.allow_legacy_plugins)?
+				.parameters()?;
+			c.validate_parameters(&parameters)?;
+		}
+		Ok(configs)
+	}
+
+	/// #Description
+	///
+	/// Parses configs from TOML, as [from_file](#method.from_file), but
+	/// without loading the plugins, so parameters aren't checked against
+	/// those the plugins declare. Relative plugin paths are left as they
+	/// are.
+	///
+	/// #Returns
+	///
+	/// * `Ok(Vec<CuckooMinerConfig>)` with a config per plugin
+	/// * a ConfigError if the TOML is malformed, or a value has the wrong
+	/// type or doesn't fit
+	///
+
+	pub fn from_toml_str(toml: &str) -> Result<Vec<CuckooMinerConfig>, CuckooMinerError> {
+		parse_file(toml, None).map(|f| f.plugins)
+	}
+
+	/// #Description
+	///
+	/// Checks the names and values of the parameters set in the config,
+	/// for every device, against the parameters a plugin declares.
+	///
+	/// #Arguments
+	///
+	/// * `parameters` (IN) The plugin's parameters, as returned by
+	/// [PluginLibrary::parameters](struct.PluginLibrary.html#method.parameters)
+	///
+	/// #Returns
+	///
+	/// * `Ok(())` if every parameter is known and in range
+	/// * an UnknownParameter error for the first parameter the plugin
+	/// doesn't declare
+	/// * a ParameterOutOfRange error for the first value outside the
+	/// plugin's declared range
+	///
+
+	pub fn validate_parameters(&self, parameters: &[CuckooPluginParameter]) -> Result<(), CuckooMinerError> {
+		match self.parameter_errors(parameters).into_iter().next() {
+			Some(e) => Err(e),
+			None => Ok(()),
+		}
+	}
+
+	/// As [validate_parameters](#method.validate_parameters), but returns
+	/// an error for every bad parameter, in the order they're set
+
+	pub fn parameter_errors(&self, parameters: &[CuckooPluginParameter]) -> Vec<CuckooMinerError> {
+		let listed = self.parameter_list.iter().map(|&(ref name, _, value)| (name, value));
+		let per_device = self.device_parameters
+			.values()
+			.flat_map(|params| params.iter().map(|(name, value)| (name, *value)));
+		let mut errors = Vec::new();
+		for (name, value) in listed.chain(per_device) {
+			let p = match parameters.iter().find(|p| p.name == *name) {
+				Some(p) => p,
+				None => {
+					errors.push(CuckooMinerError::UnknownParameter {
+						plugin: self.plugin_full_path.clone(),
+						parameter: name.clone(),
+					});
+					continue;
+				}
+			};
+			let value = value as u64;
+			if value < p.min_value || value > p.max_value {
+				errors.push(CuckooMinerError::ParameterOutOfRange {
+					name: name.clone(),
+					value: value,
+					min: p.min_value,
+					max: p.max_value,
+				});
+			}
+		}
+		errors
+	}
+}
+
+/// How the miner described by a config file mines
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MiningMode {
+	/// One header at a time, waiting for each graph, with
+	/// [CuckooMiner::mine](struct.CuckooMiner.html#method.mine). Only the
+	/// first plugin is used, so only one can be configured.
+	Sync,
+
+	/// Headers are queued to every plugin, which mine them in the
+	/// background, with [CuckooMiner::notify](struct.CuckooMiner.html#method.notify)
+	Async,
+}
+
+impl Default for MiningMode {
+	fn default() -> MiningMode {
+		MiningMode::Async
+	}
+}
+
+impl FromStr for MiningMode {
+	type Err = CuckooMinerError;
+
+	fn from_str(s: &str) -> Result<MiningMode, CuckooMinerError> {
+		match s {
+			"sync" => Ok(MiningMode::Sync),
+			"async" => Ok(MiningMode::Async),
+			_ => Err(CuckooMinerError::ConfigError(format!(
+				"Unknown mining mode {}, expected sync or async",
+				s
+			))),
+		}
+	}
+}
+
+/// #Description
+///
+/// The whole of a miner config file: how the miner mines, as given by its
+/// top level `mode` key, and the configs of its plugins, as read by
+/// [CuckooMinerConfig::from_file](struct.CuckooMinerConfig.html#method.from_file).
+///
+/// #Example
+///
+/// ```no_run
+///  # use cuckoo_miner::{CuckooMiner, MinerConfigFile, MiningMode};
+///  let file = MinerConfigFile::load("cuckoo-miner.toml").unwrap();
+///  let miner = CuckooMiner::new(file.plugins).unwrap();
+///  match file.mode {
+///  	MiningMode::Sync => { /* miner.mine(...) */ }
+///  	MiningMode::Async => { /* miner.notify(...) */ }
+///  }
+/// ```
+///
+
+#[derive(Debug, Clone)]
+pub struct MinerConfigFile {
+	/// How the miner mines. Defaults to async.
+	pub mode: MiningMode,
+
+	/// A config per plugin, in the order they appear in the file
+	pub plugins: Vec<CuckooMinerConfig>,
+}
+
+impl MinerConfigFile {
+	/// #Description
+	///
+	/// Reads a miner config file, checking the plugins' parameters as
+	/// [CuckooMinerConfig::from_file](struct.CuckooMinerConfig.html#method.from_file)
+	/// does, and that every plugin can mine in the file's mode.
+	///
+	/// #Arguments
+	///
+	/// * `path` (IN) The path of the TOML file
+	///
+	/// #Returns
+	///
+	/// * `Ok(MinerConfigFile)` with the file's mode and plugin configs
+	/// * a ConfigError if the file can't be read or is malformed, as for
+	/// `from_file`, if it configures more than one plugin to mine
+	/// synchronously, or if a plugin can't mine asynchronously in async
+	/// mode
+	/// * another [CuckooMinerError](enum.CuckooMinerError.html) as for
+	/// `from_file`
+	///
+
+	pub fn load<P: AsRef<Path>>(path: P) -> Result<MinerConfigFile, CuckooMinerError> {
+		let file = read_file(path.as_ref())?;
+		for c in &file.plugins {
+			let library = PluginLibrary::load(&c.plugin_full_path, c.allow_legacy_plugins)?;
+			c.validate_parameters(&library.parameters()?)?;
+			if file.mode == MiningMode::Async && !library.capabilities()?.supports_async {
+				return Err(CuckooMinerError::ConfigError(format!(
+					"{} can't mine asynchronously, set mode = \"sync\"",
+					c.plugin_full_path
+				)));
+			}
+		}
+		Ok(file)
+	}
+
+	/// Parses a miner config file's TOML, as [load](#method.load), but
+	/// without loading the plugins, as
+	/// [CuckooMinerConfig::from_toml_str](struct.CuckooMinerConfig.html#method.from_toml_str)
+
+	pub fn from_toml_str(toml: &str) -> Result<MinerConfigFile, CuckooMinerError> {
+		parse_file(toml, None)
+	}
+}
+
+/// Reads and parses a config file, naming the file in any ConfigError
+
+fn read_file(path: &Path) -> Result<MinerConfigFile, CuckooMinerError> {
+	let mut contents = String::new();
+	File::open(path)
+		.and_then(|mut f| f.read_to_string(&mut contents))
+		.map_err(|e| {
+			CuckooMinerError::ConfigError(format!("Unable to read {}: {}", path.display(), e))
+		})?;
+	parse_file(&contents, path.parent()).map_err(|e| match e {
+		CuckooMinerError::ConfigError(m) => {
+			CuckooMinerError::ConfigError(format!("{}: {}", path.display(), m))
+		}
+		e => e,
+	})
+}
+
+/// Parses the mining mode and the `[[plugin]]` tables, resolving relative
+/// plugin paths against `base_dir` if given
+
+fn parse_file(toml: &str, base_dir: Option<&Path>) -> Result<MinerConfigFile, CuckooMinerError> {
+	let root = toml.parse::<Value>().map_err(|e| {
+		CuckooMinerError::ConfigError(format!("Invalid TOML: {}", e))
+	})?;
+	let root = match root {
+		Value::Table(t) => t,
+		_ => return Err(CuckooMinerError::ConfigError(String::from("Expected a table"))),
+	};
+	for key in root.keys().filter(|k| !ROOT_KEYS.contains(&k.as_str())) {
+		warn!("Ignoring unknown key in miner config: {}", key);
+	}
+	let mode = match root.get("mode") {
+		Some(&Value::String(ref m)) => m.parse()?,
+		Some(_) => {
+			return Err(CuckooMinerError::ConfigError(
+				String::from("mode must be one of sync or async"),
+			))
+		}
+		None => MiningMode::default(),
+	};
+	let plugins = match root.get("plugin") {
+		Some(&Value::Array(ref a)) => a,
+		Some(_) => {
+			return Err(CuckooMinerError::ConfigError(
+				String::from("plugin must be an array of tables, i.e. [[plugin]]"),
+			))
+		}
+		None => {
+			return Err(CuckooMinerError::ConfigError(
+				String::from("No [[plugin]] tables found"),
+			))
+		}
+	};
+	let configs = plugins
+		.iter()
+		.enumerate()
+		.map(|(i, p)| match *p {
+			Value::Table(ref t) => parse_plugin(t, base_dir).and_then(|c| fan_out(t, c)),
+			_ => Err(CuckooMinerError::ConfigError(
+				format!("plugin {} must be a table", i + 1),
+			)),
+		})
+		.collect::<Result<Vec<Vec<CuckooMinerConfig>>, CuckooMinerError>>()?;
+	let plugins: Vec<CuckooMinerConfig> = configs.into_iter().flat_map(|c| c).collect();
+	if mode == MiningMode::Sync && plugins.len() > 1 {
+		return Err(CuckooMinerError::ConfigError(format!(
+			"{} plugins configured, but only one can mine in sync mode",
+			plugins.len()
+		)));
+	}
+	Ok(MinerConfigFile {
+		mode: mode,
+		plugins: plugins,
+	})
+}
+
+/// Fans a plugin's config out into one per listed device, if its table
+/// asks for an instance of the plugin per device
+
+fn fan_out(t: &Table, c: CuckooMinerConfig) -> Result<Vec<CuckooMinerConfig>, CuckooMinerError> {
+	let per_device = match t.get("instance_per_device") {
+		Some(&Value::Boolean(b)) => b,
+		Some(_) => return Err(type_error(&c.plugin_full_path, "instance_per_device", "a boolean")),
+		None => false,
+	};
+	if !per_device {
+		return Ok(vec![c]);
+	}
+	let devices: Vec<DeviceConfig> = match c.enabled_devices {
+		Some(ref ids) => ids.iter().map(|id| DeviceConfig::new(*id)).collect(),
+		None => {
+			return Err(CuckooMinerError::ConfigError(format!(
+				"{}: instance_per_device needs the devices listed",
+				c.plugin_full_path
+			)))
+		}
+	};
+	Ok(c.per_device(&devices))
+}
+
+/// Parses a single `[[plugin]]` table into a config
+
+fn parse_plugin(t: &Table, base_dir: Option<&Path>) -> Result<CuckooMinerConfig, CuckooMinerError> {
+	let plugin = match (t.get("name"), t.get("path")) {
+		(Some(&Value::String(ref name)), None) => name.clone(),
+		(None, Some(&Value::String(ref path))) => match base_dir {
+			Some(dir) if Path::new(path).is_relative() => dir.join(path).to_string_lossy().into_owned(),
+			_ => path.clone(),
+		},
+		(Some(_), Some(_)) => {
+			return Err(CuckooMinerError::ConfigError(
+				String::from("A plugin has both a name and a path, give only one"),
+			))
+		}
+		(None, None) => {
+			return Err(CuckooMinerError::ConfigError(
+				String::from("A plugin has neither a name nor a path"),
+			))
+		}
+		_ => {
+			return Err(CuckooMinerError::ConfigError(
+				String::from("A plugin's name or path must be a string"),
+			))
+		}
+	};
+	warn_unknown_keys(t, PLUGIN_KEYS, &plugin);
+
+	let mut c = CuckooMinerConfig::new();
+	if let Some(v) = t.get("allow_legacy") {
+		c.allow_legacy_plugins = match *v {
+			Value::Boolean(b) => b,
+			_ => return Err(type_error(&plugin, "allow_legacy", "a boolean")),
+		};
+	}
+	if let Some(v) = t.get("skip_memory_check") {
+		c.skip_memory_check = match *v {
+			Value::Boolean(b) => b,
+			_ => return Err(type_error(&plugin, "skip_memory_check", "a boolean")),
+		};
+	}
+	if let Some(v) = t.get("devices") {
+		let ids = match *v {
+			Value::Array(ref a) => a,
+			_ => return Err(type_error(&plugin, "devices", "an array of device ids")),
+		};
+		c.enabled_devices = Some(
+			ids.iter()
+				.map(|id| to_u32(&plugin, "devices", id))
+				.collect::<Result<Vec<u32>, CuckooMinerError>>()?,
+		);
+	}
+	if let Some(v) = t.get("queue_size") {
+		c.queue_size = Some(to_u32(&plugin, "queue_size", v)?);
+	}
+	if let Some(v) = t.get("nonce_range") {
+		let r = as_table(&plugin, "nonce_range", v)?;
+		warn_unknown_keys(r, NONCE_RANGE_KEYS, &plugin);
+		let mut range = NonceRange::default();
+		if let Some(v) = r.get("start") {
+			range.start = to_u64(&plugin, "nonce_range.start", v)?;
+		}
+		if let Some(v) = r.get("stride") {
+			range.stride = to_u64(&plugin, "nonce_range.stride", v)?;
+		}
+		if range.stride == 0 {
+			return Err(CuckooMinerError::ConfigError(
+				format!("{}: nonce_range.stride must be at least 1", plugin),
+			));
+		}
+		c.nonce_range = Some(range);
+	}
+	if let Some(v) = t.get("parameters") {
+		for (name, value) in as_table(&plugin, "parameters", v)? {
+			let key = format!("parameters.{}", name);
+			c.parameter_list.push((name.clone(), 0, to_u32(&plugin, &key, value)?));
+		}
+	}
+	if let Some(v) = t.get("device_parameters") {
+		for (device, params) in as_table(&plugin, "device_parameters", v)? {
+			let device_id = if device == ALL_DEVICES_KEY {
+				ALL_DEVICES
+			} else {
+				device.parse::<u32>().map_err(|_| {
+					CuckooMinerError::ConfigError(format!(
+						"{}: device_parameters key {} must be a device id or \"{}\"",
+						plugin,
+						device,
+						ALL_DEVICES_KEY
+					))
+				})?
+			};
+			let key = format!("device_parameters.{}", device);
+			let mut values = BTreeMap::new();
+			for (name, value) in as_table(&plugin, &key, params)? {
+				let key = format!("{}.{}", key, name);
+				values.insert(name.clone(), to_u32(&plugin, &key, value)?);
+			}
+			c.device_parameters.insert(device_id, values);
+		}
+	}
+	if let Some(v) = t.get("cpu_affinity") {
+		let cores = match *v {
+			Value::Array(ref a) => a,
+			_ => return Err(type_error(&plugin, "cpu_affinity", "an array of core indices")),
+		};
+		c.cpu_affinity = Some(
+			cores.iter()
+				.map(|core| to_u32(&plugin, "cpu_affinity", core).map(|n| n as usize))
+				.collect::<Result<Vec<usize>, CuckooMinerError>>()?,
+		);
+	}
+	if let Some(v) = t.get("thread_priority") {
+		c.thread_priority = match *v {
+			Value::String(ref p) => Some(p.parse::<Priority>().map_err(|_| {
+				type_error(&plugin, "thread_priority", "one of idle, low, normal or high")
+			})?),
+			_ => return Err(type_error(&plugin, "thread_priority", "a string")),
+		};
+	}
+	if let Some(v) = t.get("pinned_hashes") {
+		let hashes = match *v {
+			Value::Array(ref a) => a,
+			_ => return Err(type_error(&plugin, "pinned_hashes", "an array of hashes")),
+		};
+		c.pinned_hashes = hashes.iter()
+			.map(|h| match *h {
+				Value::String(ref h) => Ok(h.clone()),
+				_ => Err(type_error(&plugin, "pinned_hashes", "an array of hashes")),
+			})
+			.collect::<Result<Vec<String>, CuckooMinerError>>()?;
+	}
+	if let Some(v) = t.get("nonce_encoding") {
+		c.nonce_encoding = match *v {
+			Value::String(ref e) => e.parse::<NonceEncoding>().map_err(|_| {
+				type_error(&plugin, "nonce_encoding", "one of big_endian_u64, little_endian_u64 or raw_bytes")
+			})?,
+			_ => return Err(type_error(&plugin, "nonce_encoding", "a string")),
+		};
+	}
+	c.plugin_full_path = plugin;
+	Ok(c)
+}
+
+fn warn_unknown_keys(t: &Table, known: &[&str], plugin: &str) {
+	for key in t.keys().filter(|k| !known.contains(&k.as_str())) {
+		warn!("Ignoring unknown key {} in miner config for {}", key, plugin);
+	}
+}
+
+fn type_error(plugin: &str, key: &str, expected: &str) -> CuckooMinerError {
+	CuckooMinerError::ConfigError(format!("{}: {} must be {}", plugin, key, expected))
+}
+
+fn as_table<'a>(plugin: &str, key: &str, v: &'a Value) -> Result<&'a Table, CuckooMinerError> {
+	match *v {
+		Value::Table(ref t) => Ok(t),
+		_ => Err(type_error(plugin, key, "a table")),
+	}
+}
+
+fn to_u64(plugin: &str, key: &str, v: &Value) -> Result<u64, CuckooMinerError> {
+	match *v {
+		Value::Integer(i) if i >= 0 => Ok(i as u64),
+		Value::Integer(i) => Err(CuckooMinerError::ConfigError(
+			format!("{}: {} is {}, which is negative", plugin, key, i),
+		)),
+		_ => Err(type_error(plugin, key, "an integer")),
+	}
+}
+
+fn to_u32(plugin: &str, key: &str, v: &Value) -> Result<u32, CuckooMinerError> {
+	let i = to_u64(plugin, key, v)?;
+	if i > u32::max_value() as u64 {
+		return Err(CuckooMinerError::ConfigError(format!(
+			"{}: {} is {}, more than the maximum of {}",
+			plugin,
+			key,
+			i,
+			u32::max_value()
+		)));
+	}
+	Ok(i as u32)
+}
@@ -0,0 +1,385 @@
+// Copyright 2017 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A JSON-RPC 2.0 server over HTTP for watching and controlling a running
+//! job, e.g. from a dashboard or a script, without restarting the miner.
+
+use std::net::ToSocketAddrs;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::{self, Value};
+
+use super::http::{self, HttpRequest, HttpResponse, HttpServer};
+use super::miner::CuckooMinerJobHandle;
+use cuckoo_sys::manager::ALL_DEVICES;
+use error::error::CuckooMinerError;
+
+/// The path requests are posted to
+pub const CONTROL_PATH: &str = "/control";
+
+/// The error code for a request body which isn't JSON
+pub const PARSE_ERROR: i64 = -32700;
+
+/// The error code for JSON which isn't a JSON-RPC request
+pub const INVALID_REQUEST: i64 = -32600;
+
+/// The error code for an unknown method
+pub const METHOD_NOT_FOUND: i64 = -32601;
+
+/// The error code for missing or malformed parameters
+pub const INVALID_PARAMS: i64 = -32602;
+
+/// The error code for a call the miner refused or failed, whose message
+/// holds the [CuckooMinerError](enum.CuckooMinerError.html)
+pub const MINER_ERROR: i64 = -32000;
+
+/// A running control server, started by
+/// [serve_control](fn.serve_control.html). The server stops when this is
+/// dropped.
+pub type ControlServer = HttpServer;
+
+#[derive(Deserialize)]
+struct Request {
+	jsonrpc: String,
+	#[serde(default)]
+	id: Value,
+	method: String,
+	#[serde(default)]
+	params: Value,
+}
+
+#[derive(Serialize)]
+struct Response {
+	jsonrpc: &'static str,
+	id: Value,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	result: Option<Value>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	error: Option<RpcError>,
+}
+
+#[derive(Serialize)]
+struct RpcError {
+	code: i64,
+	message: String,
+}
+
+impl RpcError {
+	fn new(code: i64, message: String) -> RpcError {
+		RpcError {
+			code: code,
+			message: message,
+		}
+	}
+}
+
+impl From<CuckooMinerError> for RpcError {
+	fn from(e: CuckooMinerError) -> RpcError {
+		RpcError::new(MINER_ERROR, format!("{:?}", e))
+	}
+}
+
+#[derive(Serialize)]
+struct PluginStatus {
+	index: usize,
+	name: String,
+	path: String,
+	state: String,
+}
+
+#[derive(Serialize)]
+struct Status {
+	job_id: u32,
+	stopped: bool,
+	graphs_per_second: f64,
+	solutions_found: u64,
+	cycles_found: u64,
+	plugins: Vec<PluginStatus>,
+}
+
+#[derive(Deserialize)]
+struct StatsParams {
+	plugin_index: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct PluginStats {
+	plugin_index: usize,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	devices: Option<Value>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	error: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ParameterParams {
+	plugin_index: usize,
+	name: String,
+	value: u32,
+	device_id: Option<u32>,
+}
+
+#[derive(Deserialize)]
+struct DeviceParams {
+	plugin_index: usize,
+	device_id: u32,
+	enabled: bool,
+}
+
+#[derive(Deserialize)]
+struct ReloadParams {
+	name: String,
+}
+
+#[derive(Deserialize)]
+struct ShutdownParams {
+	deadline_ms: Option<u64>,
+}
+
+// Reads a method's parameters, treating none as an empty object so
+// methods whose parameters are all optional can be called without any
+
+fn params<T: DeserializeOwned>(params: Value) -> Result<T, RpcError> {
+	let params = match params {
+		Value::Null => Value::Object(Default::default()),
+		p => p,
+	};
+	serde_json::from_value(params).map_err(|e| RpcError::new(INVALID_PARAMS, e.to_string()))
+}
+
+fn to_value<T: Serialize>(value: T) -> Result<Value, RpcError> {
+	serde_json::to_value(value).map_err(|e| RpcError::new(MINER_ERROR, e.to_string()))
+}
+
+// Checks a plugin index, so a bad one is an error rather than a panic
+
+fn check_plugin(handle: &CuckooMinerJobHandle, index: usize) -> Result<(), RpcError> {
+	match index < handle.library.read().unwrap().len() {
+		true => Ok(()),
+		false => Err(RpcError::new(INVALID_PARAMS, format!("No plugin at index {}", index))),
+	}
+}
+
+fn get_status(handle: &CuckooMinerJobHandle) -> Result<Value, RpcError> {
+	let names = handle.plugin_names();
+	let plugins = handle
+		.library
+		.read()
+		.unwrap()
+		.iter()
+		.enumerate()
+		.map(|(i, l)| PluginStatus {
+			index: i,
+			name: names.get(i).cloned().unwrap_or_default(),
+			path: l.lib_full_path.clone(),
+			state: format!("{:?}", l.state()),
+		})
+		.collect();
+	let job_id = handle.shared_data.read().unwrap().job_id;
+	let stopped = {
+		let c = handle.control_data.read().unwrap();
+		c.stop_flag || c.has_stopped || handle.shutdown_flag.load(Ordering::SeqCst)
+	};
+	to_value(Status {
+		job_id: job_id,
+		stopped: stopped,
+		graphs_per_second: handle.graphs_per_second(),
+		solutions_found: handle.solutions_found(),
+		cycles_found: handle.cycles_found(),
+		plugins: plugins,
+	})
+}
+
+fn get_stats(handle: &CuckooMinerJobHandle, p: StatsParams) -> Result<Value, RpcError> {
+	if let Some(i) = p.plugin_index {
+		check_plugin(handle, i)?;
+		return to_value(handle.get_stats(i)?);
+	}
+	let stats = handle
+		.get_all_stats()
+		.into_iter()
+		.enumerate()
+		.map(|(i, s)| match s {
+			Ok(devices) => Ok(PluginStats {
+				plugin_index: i,
+				devices: Some(to_value(devices)?),
+				error: None,
+			}),
+			Err(e) => Ok(PluginStats {
+				plugin_index: i,
+				devices: None,
+				error: Some(format!("{:?}", e)),
+			}),
+		})
+		.collect::<Result<Vec<_>, RpcError>>()?;
+	to_value(stats)
+}
+
+// Starts shutting the job down, as the shutdown flag does, without
+// waiting, so the job's owner still collects its last solutions
+
+fn shutdown(handle: &CuckooMinerJobHandle, p: ShutdownParams) -> Result<Value, RpcError> {
+	if let Some(ms) = p.deadline_ms {
+		handle.control_data.write().unwrap().shutdown_deadline = Some(Duration::from_millis(ms));
+	}
+	handle.shutdown_flag.store(true, Ordering::SeqCst);
+	Ok(Value::Bool(true))
+}
+
+fn call(handle: &CuckooMinerJobHandle, method: &str, p: Value) -> Result<Value, RpcError> {
+	match method {
+		"get_status" => get_status(handle),
+		"get_stats" => get_stats(handle, params(p)?),
+		"set_parameter" => {
+			let p: ParameterParams = params(p)?;
+			check_plugin(handle, p.plugin_index)?;
+			let device_id = p.device_id.unwrap_or(ALL_DEVICES);
+			handle.set_parameter(p.plugin_index, &p.name, device_id, p.value)?;
+			Ok(Value::Bool(true))
+		}
+		"set_device_enabled" => {
+			let p: DeviceParams = params(p)?;
+			check_plugin(handle, p.plugin_index)?;
+			handle.set_device_enabled(p.plugin_index, p.device_id, p.enabled)?;
+			Ok(Value::Bool(true))
+		}
+		"reload_plugin" => {
+			let p: ReloadParams = params(p)?;
+			handle.reload_plugin(&p.name)?;
+			Ok(Value::Bool(true))
+		}
+		"shutdown" => shutdown(handle, params(p)?),
+		m => Err(RpcError::new(METHOD_NOT_FOUND, format!("Unknown method {}", m))),
+	}
+}
+
+// Answers the body of a request, which has already been authorized
+
+fn answer_rpc(handle: &CuckooMinerJobHandle, body: &[u8]) -> Response {
+	let respond = |id: Value, result: Result<Value, RpcError>| match result {
+		Ok(r) => Response {
+			jsonrpc: "2.0",
+			id: id,
+			result: Some(r),
+			error: None,
+		},
+		Err(e) => Response {
+			jsonrpc: "2.0",
+			id: id,
+			result: None,
+			error: Some(e),
+		},
+	};
+	let request: Value = match serde_json::from_slice(body) {
+		Ok(r) => r,
+		Err(e) => return respond(Value::Null, Err(RpcError::new(PARSE_ERROR, e.to_string()))),
+	};
+	let id = request.get("id").cloned().unwrap_or(Value::Null);
+	let request = match serde_json::from_value::<Request>(request) {
+		Ok(ref r) if r.jsonrpc != "2.0" => {
+			return respond(id, Err(RpcError::new(INVALID_REQUEST, String::from("Not a JSON-RPC 2.0 request"))))
+		}
+		Ok(r) => r,
+		Err(e) => return respond(id, Err(RpcError::new(INVALID_REQUEST, e.to_string()))),
+	};
+	debug!("Control request {}", request.method);
+	let result = call(handle, &request.method, request.params);
+	if let Err(ref e) = result {
+		debug!("Control request {} failed: {}", request.method, e.message);
+	}
+	respond(request.id, result)
+}
+
+// Compares the token given with the server's, taking as long whichever
+// byte differs
+
+fn authorized(request: &HttpRequest, token: &str) -> bool {
+	let given = match request.header("authorization") {
+		Some(h) if h.starts_with("Bearer ") => h["Bearer ".len()..].trim().as_bytes(),
+		_ => return false,
+	};
+	let token = token.as_bytes();
+	given.len() == token.len() && given.iter().zip(token).fold(0, |d, (a, b)| d | (a ^ b)) == 0
+}
+
+/// #Description
+///
+/// Starts a JSON-RPC 2.0 server on its own thread, for controlling a
+/// running job. Requests are POSTed to [CONTROL_PATH](constant.CONTROL_PATH.html),
+/// one per request, with an `Authorization: Bearer <token>` header;
+/// requests without the token are refused with a 401. The methods are:
+///
+/// * `get_status` The job id, whether the job has stopped, its graphs per
+/// second, solutions and cycles found, and each plugin's index, name,
+/// path and state
+/// * `get_stats` Each plugin's device stats, or `{"plugin_index": n}`
+/// for just one plugin's
+/// * `set_parameter` `{"plugin_index", "name", "value", "device_id"}`
+/// sets a plugin parameter, on every device if `device_id` is left out
+/// * `set_device_enabled` `{"plugin_index", "device_id", "enabled"}`
+/// * `reload_plugin` `{"name"}` reloads a plugin, answering once it's
+/// mining again
+/// * `shutdown` `{"deadline_ms"}` starts shutting the job down, as the
+/// [shutdown flag](struct.CuckooMinerJobHandle.html#method.shutdown_flag)
+/// does, without waiting for it
+///
+/// Changes are made through the handle, so they're serialized against the
+/// job loop's own job switches and plugin reloads. The server keeps
+/// answering after the job stops, until it's dropped.
+///
+/// #Arguments
+///
+/// * `handle` (IN) The job to control
+/// * `addr` (IN) The address to listen on, e.g. "127.0.0.1:9091"
+/// * `token` (IN) The token clients must send, which mustn't be empty
+///
+/// #Returns
+///
+/// * `Ok(ControlServer)` once listening
+/// * a ConfigError if the token is empty
+/// * a PluginIOError if the address can't be bound
+///
+
+pub fn serve_control<A: ToSocketAddrs>(
+	handle: CuckooMinerJobHandle,
+	addr: A,
+	token: &str,
+) -> Result<ControlServer, CuckooMinerError> {
+	if token.is_empty() {
+		return Err(CuckooMinerError::ConfigError(String::from(
+			"The control server needs a token",
+		)));
+	}
+	let token = String::from(token);
+	http::serve(addr, "control", move |request| {
+		if request.path != CONTROL_PATH {
+			return HttpResponse::text("404 Not Found", "Not found\n");
+		}
+		if request.method != "POST" {
+			return HttpResponse::text("405 Method Not Allowed", "Requests must be POSTed\n");
+		}
+		if !authorized(request, &token) {
+			return HttpResponse::text("401 Unauthorized", "Unauthorized\n");
+		}
+		let response = answer_rpc(&handle, &request.body);
+		HttpResponse {
+			status: "200 OK",
+			content_type: "application/json",
+			body: serde_json::to_string(&response).unwrap_or_default(),
+		}
+	})
+}
@@ -0,0 +1,105 @@
+// Copyright 2017 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Filters out solutions a plugin reports more than once, e.g. when the
+//! same graph is attempted again after a queue clear, so callers don't
+//! submit the same share twice.
+
+use std::collections::{HashSet, VecDeque};
+
+use CuckooMinerSolution;
+
+/// Default number of recent solutions remembered by a
+/// [DuplicateFilter](struct.DuplicateFilter.html)
+pub const DEFAULT_DEDUP_WINDOW: usize = 256;
+
+/// Remembers the most recent solutions, by nonce and proof, and picks out
+/// exact repeats of them. Once the window is full, the oldest solution is
+/// forgotten to make room for each new one.
+
+pub struct DuplicateFilter {
+	/// How many solutions are remembered
+	window: usize,
+
+	/// The remembered solutions, oldest first
+	order: VecDeque<(u64, Vec<u64>)>,
+
+	/// The remembered solutions, for lookup
+	seen: HashSet<(u64, Vec<u64>)>,
+}
+
+impl Default for DuplicateFilter {
+	fn default() -> DuplicateFilter {
+		DuplicateFilter::new(DEFAULT_DEDUP_WINDOW)
+	}
+}
+
+impl DuplicateFilter {
+	/// Returns a new filter remembering up to `window` solutions. A window
+	/// of 0 lets every solution through.
+
+	pub fn new(window: usize) -> DuplicateFilter {
+		DuplicateFilter {
+			window: window,
+			order: VecDeque::with_capacity(window),
+			seen: HashSet::with_capacity(window),
+		}
+	}
+
+	/// #Description
+	///
+	/// Checks whether a solution with the same nonce and proof is among
+	/// those remembered, remembering it if not.
+	///
+	/// #Returns
+	///
+	/// true if the solution is new, false if it's a repeat
+
+	pub fn insert(&mut self, solution: &CuckooMinerSolution) -> bool {
+		if self.window == 0 {
+			return true;
+		}
+		let key = (solution.nonce, solution.solution_nonces.clone());
+		if self.seen.contains(&key) {
+			return false;
+		}
+		if self.order.len() == self.window {
+			if let Some(oldest) = self.order.pop_front() {
+				self.seen.remove(&oldest);
+			}
+		}
+		self.seen.insert(key.clone());
+		self.order.push_back(key);
+		true
+	}
+
+	/// Forgets every solution, e.g. when switching to a new job
+
+	pub fn clear(&mut self) {
+		self.order.clear();
+		self.seen.clear();
+	}
+
+	/// The number of solutions currently remembered
+
+	pub fn len(&self) -> usize {
+		self.order.len()
+	}
+
+	/// Whether no solutions are remembered
+
+	pub fn is_empty(&self) -> bool {
+		self.order.is_empty()
+	}
+}
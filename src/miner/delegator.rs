@@ -17,68 +17,130 @@
 //!
 //!
 
-use std::sync::{Arc, RwLock};
-use std::{thread, time};
-use std::mem::transmute;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex, RwLock};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::path::Path;
+use std::{cmp, thread, time};
+use std::mem;
 
 use rand::{self, Rng};
-use byteorder::{ByteOrder, BigEndian};
-use blake2::blake2b::Blake2b;
 use env_logger;
 
-use cuckoo_sys::manager::PluginLibrary;
+use cuckoo_sys::manager::{PluginLibrary, PluginCall, PluginDeviceStats};
+use cuckoo_sys::state::PluginState;
+use super::stats::{StatsCollector, DEFAULT_STATS_WINDOW, DEFAULT_SAMPLE_INTERVAL};
+use super::events::MinerEvent;
+use super::solutions::SolutionSender;
+use super::nonce::NonceGenerator;
+use super::dedup::{DuplicateFilter, DEFAULT_DEDUP_WINDOW};
+use super::watchdog::{Watchdog, StallMonitor, DEFAULT_STOP_TIMEOUT, DEFAULT_SHUTDOWN_DEADLINE, DEFAULT_STALL_WARNING};
+use super::thermal::{ThermalMonitor, ThermalChange};
+use super::blacklist::{FailureTracker, DEFAULT_FAILURE_THRESHOLD, DEFAULT_BLACKLIST_COOLDOWN};
+use super::ledger::{PushLedger, LEDGER_CAPACITY};
+use super::worker::{spawn_worker, WorkerJob, WorkerSettings, WorkerCommand, WorkerReport, WORKER_POLL_INTERVAL};
 use error::error::CuckooMinerError;
 use CuckooMinerJobHandle;
 use CuckooMinerSolution;
-
-/// From grin
-/// The target is the 8-bytes hash block hashes must be lower than.
-const MAX_TARGET: [u8; 8] = [0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff];
+use super::miner::{load_plugin, reload_plugin_library, CuckooMinerConfig};
+use super::job::{HeaderHasher, Blake2bHasher, MiningJob};
+use verify::verify::{verify_solution, is_verifiable, check_bounds};
 
 type JobSharedDataType = Arc<RwLock<JobSharedData>>;
 type JobControlDataType = Arc<RwLock<JobControlData>>;
 type PluginLibrariesDataType = Arc<RwLock<Vec<PluginLibrary>>>;
 
+/// The most headers pushed to a plugin in one pass of the job loop, given
+/// its input queue capacity. Leaving some headroom means a pass doesn't
+/// normally run into a full queue.
+fn high_water_mark(capacity: u32) -> u32 {
+	if capacity < 4 {
+		capacity
+	} else {
+		capacity - capacity / 4
+	}
+}
+
+/// Records the position of a solution's header among its job's attempts,
+/// once it's known which header the solution is for
+fn set_attempt(solution: &mut CuckooMinerSolution, attempt: Option<u64>) {
+	if let Some(ref mut m) = solution.metadata {
+		m.attempt_index = attempt;
+	}
+}
+
 /// Data intended to be shared across threads
 pub struct JobSharedData {
-	/// ID of the current running job (not currently used)
+	/// ID of the current running job. Solutions are tagged with the id
+	/// of the job they were found for, and those for earlier jobs are
+	/// discarded.
 	pub job_id: u32,
 
 	/// The part of the header before the nonce, which this
 	/// module will mutate in search of a solution
-	pub pre_nonce: String,
+	pub pre_nonce: Vec<u8>,
 
 	/// The part of the header after the nonce
-	pub post_nonce: String,
+	pub post_nonce: Vec<u8>,
+
+	/// Whether the header is hashed down to 32 bytes before it's pushed
+	/// to the plugins, as [header_hash](fn.header_hash.html) does
+	pub hash_header: bool,
 
 	/// The target difficulty. Only solutions >= this
 	/// target will be put into the output queue
 	pub difficulty: u64,
 
+	/// The job switched to, if it changes which nonces the plugins mine,
+	/// until the job loop picks it up
+	pub nonce_job: Option<MiningJob>,
+
 	/// Output solutions
 	pub solutions: Vec<CuckooMinerSolution>,
+
+	/// Rolling stats, sampled from the plugins by the job loop
+	pub stats: StatsCollector,
+
+	/// The iterators and streams over the job's solutions, each sent a
+	/// copy of every solution until it's dropped
+	pub subscribers: Vec<SolutionSender>,
+
+	/// Set once the job loop has ended, after which no more solutions
+	/// are found
+	pub finished: bool,
 }
 
 impl Default for JobSharedData {
 	fn default() -> JobSharedData {
 		JobSharedData {
 			job_id: 0,
-			pre_nonce: String::from(""),
-			post_nonce: String::from(""),
+			pre_nonce: Vec::new(),
+			post_nonce: Vec::new(),
+			hash_header: true,
 			difficulty: 0,
+			nonce_job: None,
 			solutions: Vec::new(),
+			stats: StatsCollector::default(),
+			subscribers: Vec::new(),
+			finished: false,
 		}
 	}
 }
 
 impl JobSharedData {
-	pub fn new(job_id: u32, pre_nonce: &str, post_nonce: &str, difficulty: u64) -> JobSharedData {
+	pub fn new(job_id: u32, pre_nonce: &[u8], post_nonce: &[u8], difficulty: u64, hash_header: bool) -> JobSharedData {
 		JobSharedData {
 			job_id: job_id,
-			pre_nonce: String::from(pre_nonce),
-			post_nonce: String::from(post_nonce),
+			pre_nonce: pre_nonce.to_vec(),
+			post_nonce: post_nonce.to_vec(),
+			hash_header: hash_header,
 			difficulty: difficulty,
+			nonce_job: None,
 			solutions: Vec::new(),
+			stats: StatsCollector::default(),
+			subscribers: Vec::new(),
+			finished: false,
 		}
 	}
 }
@@ -91,6 +153,21 @@ pub struct JobControlData {
 
 	/// Whether all plugins have stopped
 	pub has_stopped: bool,
+
+	/// Indexes of the plugins waiting to be reloaded
+	pub reload_requests: Vec<usize>,
+
+	/// The outcome of each finished reload, by plugin index, until it's
+	/// collected
+	pub reload_results: HashMap<usize, Result<(), CuckooMinerError>>,
+
+	/// The config and instance to load in place of each plugin whose
+	/// reload swaps it for another, by plugin index
+	pub swaps: HashMap<usize, (CuckooMinerConfig, usize)>,
+
+	/// How long plugins are given to stop when the job is shut down, if
+	/// not the job's default
+	pub shutdown_deadline: Option<time::Duration>,
 }
 
 impl Default for JobControlData {
@@ -98,6 +175,10 @@ impl Default for JobControlData {
 		JobControlData {
 			stop_flag: false,
 			has_stopped: false,
+			reload_requests: Vec::new(),
+			reload_results: HashMap::new(),
+			swaps: HashMap::new(),
+			shutdown_deadline: None,
 		}
 	}
 }
@@ -115,27 +196,254 @@ pub struct Delegator {
 
 	/// Loaded Plugin Library
 	libraries: PluginLibrariesDataType,
+
+	/// Whether to verify solutions before reporting them
+	verify_solutions: bool,
+
+	/// Where to send events, if anywhere
+	events: Option<Sender<MinerEvent>>,
+
+	/// The nonces to mine, one generator for each plugin, until they're
+	/// handed to the plugins' workers
+	nonces: Vec<NonceGenerator>,
+
+	/// The nonces each plugin was first given, which jobs setting their
+	/// own nonces start over from
+	first_nonces: Vec<NonceGenerator>,
+
+	/// The job the plugins are mining, as given to their workers
+	job: Arc<WorkerJob>,
+
+	/// How each job's headers are hashed
+	hasher: Arc<dyn HeaderHasher>,
+
+	/// The difficulty solutions must meet to be reported
+	difficulty: u64,
+
+	/// The headers pushed with each nonce for the current and previous
+	/// jobs, recorded by the workers
+	ledger: Arc<Mutex<PushLedger>>,
+
+	/// Where each plugin's worker is told what to do
+	workers: Vec<Sender<WorkerCommand>>,
+
+	/// The minimum time between samples of each plugin's stats
+	stats_interval: time::Duration,
+
+	/// Plugins whose stats show they've hung, to be given up on
+	hung: Vec<usize>,
+
+	/// Devices whose temperature has crossed the limit, to be paused or
+	/// resumed
+	thermal_changes: Vec<(usize, ThermalChange)>,
+
+	/// Plugins with too many failed devices, to be given up on
+	benched: Vec<usize>,
+
+	/// Drops solutions reported more than once
+	duplicates: DuplicateFilter,
+
+	/// Spots hung plugins, if enabled
+	watchdog: Option<Watchdog>,
+
+	/// Spots plugins which have gone quiet, to warn about, if enabled
+	stall: Option<StallMonitor>,
+
+	/// Pauses overheating devices, if enabled
+	thermal: Option<ThermalMonitor>,
+
+	/// The plugin index and device id of each device reporting an error,
+	/// so each failure is only reported once
+	failed_devices: HashSet<(usize, String)>,
+
+	/// Counts plugin failures, blacklisting plugins which fail too often
+	failures: FailureTracker,
+
+	/// The config and instance each plugin was loaded from, so a failed
+	/// plugin can be reloaded
+	sources: Vec<(CuckooMinerConfig, usize)>,
+
+	/// How long to wait for a plugin to stop processing
+	stop_timeout: time::Duration,
+
+	/// Set to shut the job down, unloading every plugin. It's atomic
+	/// rather than in the control data so it can be set without locking.
+	shutdown_flag: Arc<AtomicBool>,
+
+	/// How long plugins are given to stop when the job is shut down
+	shutdown_deadline: time::Duration,
+
+	/// Held while the job is switched, plugins are reloaded or devices
+	/// paused, and by the handle while it changes plugins' settings, so
+	/// those changes don't interleave
+	control_lock: Arc<Mutex<()>>,
+
+	/// The share of the time each plugin spends on graphs, as a
+	/// percentage, which the handle can change while the job runs
+	duty_cycle: Arc<AtomicUsize>,
+}
+
+/// How a job should be run, as set up on the CuckooMiner
+
+pub struct JobOptions {
+	/// Whether to verify solutions before reporting them
+	pub verify_solutions: bool,
+
+	/// Where to send events, if anywhere
+	pub events: Option<Sender<MinerEvent>>,
+
+	/// The nonces to mine, one generator for each plugin
+	pub nonces: Vec<NonceGenerator>,
+
+	/// How many recent solutions are checked for repeats
+	pub dedup_window: usize,
+
+	/// How long a plugin may go without progress before it's given up
+	/// on, or None to never give up
+	pub watchdog_timeout: Option<time::Duration>,
+
+	/// How long a plugin may go without finishing a graph or finding a
+	/// solution before a warning is logged, or None to never warn
+	pub stall_warning: Option<time::Duration>,
+
+	/// The temperature in degrees Celsius above which a device is paused,
+	/// or None to never pause devices
+	pub max_temperature: Option<f32>,
+
+	/// The failures after which a plugin is blacklisted
+	pub failure_threshold: u32,
+
+	/// How long a blacklisted plugin is benched before it's retried
+	pub blacklist_cooldown: time::Duration,
+
+	/// The config and instance each plugin was loaded from, for reloading
+	/// failed plugins. Plugins without one are given up on when they fail.
+	pub sources: Vec<(CuckooMinerConfig, usize)>,
+
+	/// How long to wait for a plugin to stop processing
+	pub stop_timeout: time::Duration,
+
+	/// How long plugins are given to stop when the job is shut down
+	pub shutdown_deadline: time::Duration,
+
+	/// The minimum time between samples of the plugins' stats
+	pub stats_interval: time::Duration,
+
+	/// The share of the time each plugin spends on graphs, as a
+	/// percentage from 1 to 100, shared with the job so it can be changed
+	/// as the job runs. At 100, plugins are kept busy.
+	pub duty_cycle: Arc<AtomicUsize>,
+
+	/// How headers are hashed before they're pushed
+	pub hasher: Arc<dyn HeaderHasher>,
+}
+
+impl Default for JobOptions {
+	fn default() -> JobOptions {
+		JobOptions {
+			verify_solutions: true,
+			events: None,
+			nonces: Vec::new(),
+			dedup_window: DEFAULT_DEDUP_WINDOW,
+			watchdog_timeout: None,
+			stall_warning: Some(DEFAULT_STALL_WARNING),
+			max_temperature: None,
+			failure_threshold: DEFAULT_FAILURE_THRESHOLD,
+			blacklist_cooldown: DEFAULT_BLACKLIST_COOLDOWN,
+			sources: Vec::new(),
+			stop_timeout: DEFAULT_STOP_TIMEOUT,
+			shutdown_deadline: DEFAULT_SHUTDOWN_DEADLINE,
+			stats_interval: DEFAULT_SAMPLE_INTERVAL,
+			duty_cycle: Arc::new(AtomicUsize::new(100)),
+			hasher: Arc::new(Blake2bHasher),
+		}
+	}
+}
+
+/// Where each plugin is up to within the job
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PluginStatus {
+	/// Mining
+	Running,
+	/// Waiting until the deadline for processing to stop
+	Retiring(time::Instant),
+	/// Processing has stopped at the end of the job
+	Stopped,
+	/// Failed, and waiting to be reloaded and restarted
+	Restarting,
+	/// Failed too often, and unloaded until its cool-down has passed
+	Blacklisted,
+	/// Waiting until the deadline for processing to stop, to be reloaded
+	Reloading(time::Instant),
+	/// Failed, and no longer used
+	Failed,
 }
 
 impl Delegator {
 	/// Create a new job delegator
 
-	pub fn new(job_id: u32, pre_nonce: &str, post_nonce: &str, difficulty: u64, libraries:Vec<PluginLibrary>) -> Delegator {
+	pub fn new(
+		job_id: u32,
+		pre_nonce: &[u8],
+		post_nonce: &[u8],
+		difficulty: u64,
+		hash_header: bool,
+		libraries: Vec<PluginLibrary>,
+		options: JobOptions,
+	) -> Delegator {
 		Delegator {
-			shared_data: Arc::new(RwLock::new(JobSharedData::new(
-				job_id,
-				pre_nonce,
-				post_nonce,
-				difficulty,
-			))),
+			shared_data: Arc::new(RwLock::new(JobSharedData {
+				stats: StatsCollector::new(DEFAULT_STATS_WINDOW, options.stats_interval),
+				..JobSharedData::new(job_id, pre_nonce, post_nonce, difficulty, hash_header)
+			})),
 			control_data: Arc::new(RwLock::new(JobControlData::default())),
 			libraries: Arc::new(RwLock::new(libraries)),
+			verify_solutions: options.verify_solutions,
+			events: options.events,
+			first_nonces: options.nonces.clone(),
+			nonces: options.nonces,
+			job: Arc::new(WorkerJob {
+				job_id: job_id,
+				queue_id: 0,
+				pre_nonce: pre_nonce.to_vec(),
+				post_nonce: post_nonce.to_vec(),
+				hash_header: hash_header,
+				hasher: options.hasher.clone(),
+				difficulty: difficulty,
+			}),
+			hasher: options.hasher,
+			difficulty: difficulty,
+			ledger: Arc::new(Mutex::new(PushLedger::new(LEDGER_CAPACITY))),
+			workers: Vec::new(),
+			stats_interval: options.stats_interval,
+			hung: Vec::new(),
+			thermal_changes: Vec::new(),
+			benched: Vec::new(),
+			duplicates: DuplicateFilter::new(options.dedup_window),
+			watchdog: options.watchdog_timeout.map(Watchdog::new),
+			stall: options.stall_warning.map(StallMonitor::new),
+			thermal: options.max_temperature.map(ThermalMonitor::new),
+			failed_devices: HashSet::new(),
+			failures: FailureTracker::new(options.failure_threshold, options.blacklist_cooldown),
+			sources: options.sources,
+			stop_timeout: options.stop_timeout,
+			shutdown_flag: Arc::new(AtomicBool::new(false)),
+			shutdown_deadline: options.shutdown_deadline,
+			control_lock: Arc::new(Mutex::new(())),
+			duty_cycle: options.duty_cycle,
 		}
 	}
 
+	/// Sends an iterator or stream a copy of each solution the job finds,
+	/// from before the job loop is started
+
+	pub fn subscribe(&self, sender: SolutionSender) {
+		self.shared_data.write().unwrap().subscribers.push(sender);
+	}
+
 	/// Starts the job loop, and initialises the internal plugin
 
-	pub fn start_job_loop(self, hash_header: bool) -> Result<CuckooMinerJobHandle, CuckooMinerError> {
+	pub fn start_job_loop(self) -> Result<CuckooMinerJobHandle, CuckooMinerError> {
 		let _=env_logger::init();
 		// this will block, waiting until previous job is cleared
 		// call_cuckoo_stop_processing();
@@ -143,9 +451,13 @@ impl Delegator {
 		let shared_data = self.shared_data.clone();
 		let control_data = self.control_data.clone();
 		let jh_library = self.libraries.clone();
+		let configs = self.sources.iter().map(|s| s.0.clone()).collect();
+		let shutdown_flag = self.shutdown_flag.clone();
+		let control_lock = self.control_lock.clone();
+		let duty_cycle = self.duty_cycle.clone();
 
 		thread::spawn(move || {
-			let result = self.job_loop(hash_header);
+			let result = self.job_loop();
 			if let Err(e) = result {
 				error!("Error in job loop: {:?}", e);
 			}
@@ -154,163 +466,940 @@ impl Delegator {
 			shared_data: shared_data,
 			control_data: control_data,
 			library: jh_library,
+			configs: configs,
+			shutdown_flag: shutdown_flag,
+			control_lock: control_lock,
+			duty_cycle: duty_cycle,
 		})
 	}
 
-	/// Helper to convert a hex string
+	/// Checks a solution against the header it was found for. Solutions
+	/// from plugins whose graphs can't be verified are passed.
+
+	fn is_valid(&self, l: &PluginLibrary, header: &[u8], sol: &CuckooMinerSolution) -> bool {
+		if !is_verifiable(l.graph_type()) {
+			debug!("Cuckoo-miner: Can't verify solutions from {:?}", sol.plugin_name);
+			return true;
+		}
+		match verify_solution(header, sol) {
+			Ok(()) => true,
+			Err(e) => {
+				warn!(
+					"Cuckoo-miner: Dropping invalid solution from {:?} for Nonce:({}): {}",
+					sol.plugin_name,
+					sol.nonce,
+					e
+				);
+				false
+			}
+		}
+	}
+
+	/// Works out which outstanding job a solution is for. Where its nonce
+	/// was pushed with more than one header, or solutions are verified,
+	/// that's the job whose header the proof is a cycle in, whatever the
+	/// plugin says, and the solution's id is corrected to match. Solutions
+	/// whose push has been forgotten are checked against the job they
+	/// claim, rebuilding its header from the nonce. Returns None if the
+	/// solution should be dropped.
 
-	fn from_hex_string(&self, in_str: &str) -> Vec<u8> {
-		let mut bytes = Vec::new();
-		for i in 0..(in_str.len() / 2) {
-			let res = u8::from_str_radix(&in_str[2 * i..2 * i + 2], 16);
-			match res {
-				Ok(v) => bytes.push(v),
-				Err(e) => println!("Problem with hex: {}", e),
+	fn attribute(&self, l: &PluginLibrary, solution: &mut CuckooMinerSolution) -> Option<Arc<WorkerJob>> {
+		let (claimed, mut pushes) = {
+			let ledger = self.ledger.lock().unwrap();
+			(ledger.job(solution.id)?, ledger.pushes(solution.nonce))
+		};
+		if pushes.is_empty() || !is_verifiable(l.graph_type()) || (!self.verify_solutions && pushes.len() == 1) {
+			if self.verify_solutions {
+				let header = claimed.header(solution.nonce, l.nonce_encoding());
+				if !self.is_valid(l, &header, solution) {
+					return None;
+				}
+			}
+			let attempt = pushes.iter().find(|p| p.job.job_id == claimed.job_id).map(|p| p.attempt);
+			set_attempt(solution, attempt);
+			return Some(claimed);
+		}
+		// the header the plugin claims is tried first
+		pushes.sort_by_key(|p| p.job.job_id != claimed.job_id);
+		for p in pushes {
+			if verify_solution(&p.header, solution).is_ok() {
+				if p.job.job_id != solution.id {
+					warn!(
+						"Cuckoo-miner: Solution from {:?} for Nonce:({}) was reported for job {}, but is for job {}",
+						solution.plugin_name,
+						solution.nonce,
+						solution.id,
+						p.job.job_id
+					);
+					solution.id = p.job.job_id;
+				}
+				set_attempt(solution, Some(p.attempt));
+				return Some(p.job);
 			}
 		}
-		bytes
+		warn!(
+			"Cuckoo-miner: Dropping solution from {:?} for Nonce:({}), which isn't for any header pushed with the nonce",
+			solution.plugin_name,
+			solution.nonce
+		);
+		let _ = self.send_event(MinerEvent::UnmatchedSolution {
+			plugin: self.plugin_name(l),
+			nonce: solution.nonce,
+			job_id: claimed.job_id,
+		});
+		None
+	}
+
+	/// Sends an event to the event channel, handing it back if there's
+	/// no channel or nothing is listening
+
+	fn send_event(&self, event: MinerEvent) -> Result<(), MinerEvent> {
+		match self.events {
+			Some(ref e) => e.send(event).map_err(|e| e.0),
+			None => Err(event),
+		}
 	}
 
-	/// As above, except doesn't hash the result
-	fn header_data(&self, pre_nonce: &str, post_nonce: &str, nonce: u64) -> Vec<u8> {
-		// Turn input strings into vectors
-		let mut pre_vec = self.from_hex_string(pre_nonce);
-		let mut post_vec = self.from_hex_string(post_nonce);
+	/// The name of a plugin, as used in events
 
-		let mut nonce_bytes = [0; 8];
-		BigEndian::write_u64(&mut nonce_bytes, nonce);
-		let mut nonce_vec = nonce_bytes.to_vec();
+	fn plugin_name(&self, library: &PluginLibrary) -> String {
+		Path::new(&library.lib_full_path)
+			.file_stem()
+			.map(|s| s.to_string_lossy().into_owned())
+			.unwrap_or_default()
+	}
 
-		// Generate new header
-		pre_vec.append(&mut nonce_vec);
-		pre_vec.append(&mut post_vec);
+	/// Reports a failed plugin call, to the log and the event channel,
+	/// and counts it against the plugin at `index`
 
-		pre_vec
+	fn plugin_error(&self, index: usize, library: &PluginLibrary, call: PluginCall, code: u32) {
+		let message = match (call, call.check(code, &library.lib_full_path)) {
+			(_, Err(e)) => format!("{:?}", e),
+			// a push which returned 0 but took none of the headers
+			(PluginCall::PushToInputQueue, Ok(())) => {
+				format!("{} accepted none of the headers pushed", library.lib_full_path)
+			}
+			(_, Ok(())) => return,
+		};
+		self.shared_data.write().unwrap().stats.record_plugin_error(index);
+		error!("Cuckoo-miner: {:?} failed: {}", call, message);
+		let _ = self.send_event(MinerEvent::PluginError {
+			plugin: self.plugin_name(library),
+			code: code,
+			message: message,
+		});
 	}
-	/// helper that generates a nonce and returns a header
 
-	fn get_next_header_data_hashed(&self, pre_nonce: &str, post_nonce: &str) -> (u64, Vec<u8>) {
-		// Generate new nonce
-		let nonce: u64 = rand::OsRng::new().unwrap().gen();
-		let mut blake2b = Blake2b::new(32);
-		blake2b.update(&self.header_data(pre_nonce, post_nonce, nonce));
+	/// Reports a plugin failing without an error code, e.g. being given
+	/// up on or returning a malformed solution, to the log and the event
+	/// channel, and counts it against the plugin at `index`
 
-		let mut ret = [0; 32];
-		ret.copy_from_slice(blake2b.finalize().as_bytes());
-		(nonce, ret.to_vec())
+	fn plugin_failed(&self, index: usize, library: &PluginLibrary, message: String) {
+		self.shared_data.write().unwrap().stats.record_plugin_error(index);
+		error!("Cuckoo-miner: {}: {}", library.lib_full_path, message);
+		let _ = self.send_event(MinerEvent::PluginError {
+			plugin: self.plugin_name(library),
+			code: 0,
+			message: message,
+		});
 	}
 
-	/// as above, except doesn't hash the result
-	fn get_next_header_data(&self, pre_nonce: &str, post_nonce: &str) -> (u64, Vec<u8>) {
-		let nonce: u64 = rand:: OsRng::new().unwrap().gen();
-		(nonce, self.header_data(pre_nonce, post_nonce, nonce))
+	/// Tells the worker of the plugin at `index` what to do. A worker which
+	/// has gone can only be waiting to be joined, so is left alone.
+
+	fn tell_worker(&self, index: usize, command: WorkerCommand) {
+		if let Some(w) = self.workers.get(index) {
+			let _ = w.send(command);
+		}
 	}
 
-	/// Helper to determing whether a solution meets a target difficulty
-	/// based on same algorithm from grin
+	/// Gives up on a plugin which has failed while processing, e.g.
+	/// stopped making progress, telling it to stop. It's unloaded once
+	/// it has stopped, or force unloaded if it hasn't by the deadline.
+
+	fn retire_plugin(&mut self, status: &mut [PluginStatus], index: usize, message: String) {
+		let libraries = self.libraries.clone();
+		let libraries = libraries.read().unwrap();
+		let l = &libraries[index];
+		self.plugin_failed(index, l, message);
+		self.tell_worker(index, WorkerCommand::Idle);
+		l.call_cuckoo_stop_processing();
+		status[index] = PluginStatus::Retiring(time::Instant::now() + self.stop_timeout);
+		if let Some(ref mut w) = self.watchdog {
+			w.forget(index);
+		}
+		if let Some(ref mut s) = self.stall {
+			s.forget(index);
+		}
+	}
 
-	fn meets_difficulty(&self, in_difficulty: u64, sol: CuckooMinerSolution) -> bool {
-		let max_target = BigEndian::read_u64(&MAX_TARGET);
-		let num = BigEndian::read_u64(&sol.hash()[0..8]);
-		max_target / num >= in_difficulty
+	/// Pauses a device which has overheated, reporting it to the log and
+	/// the event channel, or resumes one which has cooled down
+
+	fn apply_thermal_change(&self, index: usize, change: ThermalChange) {
+		let libraries = self.libraries.read().unwrap();
+		let l = &libraries[index];
+		let max = self.thermal.as_ref().map(|t| t.max_temperature()).unwrap_or_default();
+		let (device_id, enabled) = match change {
+			ThermalChange::Paused { device_id, temperature_c } => {
+				let message = format!(
+					"Device {} at {:.1}C is above the maximum of {:.1}C, pausing it",
+					device_id,
+					temperature_c,
+					max
+				);
+				warn!("Cuckoo-miner: {}: {}", l.lib_full_path, message);
+				let _ = self.send_event(MinerEvent::PluginError {
+					plugin: self.plugin_name(l),
+					code: 0,
+					message: message,
+				});
+				(device_id, false)
+			}
+			ThermalChange::Resumed { device_id, temperature_c } => {
+				info!(
+					"Cuckoo-miner: {}: Device {} has cooled to {:.1}C, resuming it",
+					l.lib_full_path,
+					device_id,
+					temperature_c
+				);
+				(device_id, true)
+			}
+		};
+		if let Err(e) = l.set_device_enabled(device_id, enabled) {
+			warn!("Unable to pause or resume device {} of {}: {:?}", device_id, l.lib_full_path, e);
+		}
+	}
+
+	/// Unloads retiring plugins once they've stopped, or force unloads
+	/// them once their deadline has passed
+
+	fn check_retiring(&mut self, status: &mut [PluginStatus]) {
+		let libraries = self.libraries.clone();
+		for (i, l) in libraries.read().unwrap().iter().enumerate() {
+			if let PluginStatus::Retiring(deadline) = status[i] {
+				if l.call_cuckoo_has_processing_stopped() == 1 {
+					l.call_cuckoo_reset_processing();
+					l.unload();
+					self.plugin_down(status, i, "Failed while processing");
+				} else if time::Instant::now() >= deadline {
+					let message = String::from("Processing didn't stop in time");
+					self.plugin_failed(i, l, message.clone());
+					l.force_unload();
+					self.failures.record_failure(i);
+					self.plugin_down(status, i, &message);
+				}
+			}
+		}
+	}
+
+	/// Decides what happens to a plugin which has failed and is no longer
+	/// processing. It's restarted, unless it has failed too often, in
+	/// which case it's unloaded, so a replaced plugin file is picked up
+	/// when it's retried, and reported as blacklisted.
+
+	fn plugin_down(&mut self, status: &mut [PluginStatus], index: usize, reason: &str) {
+		if index >= self.sources.len() {
+			status[index] = PluginStatus::Failed;
+			return;
+		}
+		if !self.failures.is_blacklisted(index) {
+			status[index] = PluginStatus::Restarting;
+			return;
+		}
+		let libraries = self.libraries.read().unwrap();
+		let l = &libraries[index];
+		l.unload();
+		let cooldown = self.failures.cooldown();
+		error!(
+			"Cuckoo-miner: {}: Blacklisted for {:?} after repeated failures, last: {}",
+			l.lib_full_path,
+			cooldown,
+			reason
+		);
+		let _ = self.send_event(MinerEvent::PluginBlacklisted {
+			plugin: self.plugin_name(l),
+			reason: String::from(reason),
+			retry_in: cooldown,
+		});
+		status[index] = PluginStatus::Blacklisted;
+	}
+
+	/// Starts the reloads the job handle has asked for. A running plugin is
+	/// told to stop, and reloaded once it has, while one which isn't
+	/// running is reloaded straight away.
+
+	fn begin_reloads(&mut self, status: &mut [PluginStatus]) {
+		let requests = mem::replace(&mut self.control_data.write().unwrap().reload_requests, Vec::new());
+		for i in requests {
+			match status[i] {
+				PluginStatus::Running => {
+					self.tell_worker(i, WorkerCommand::Idle);
+					self.libraries.read().unwrap()[i].call_cuckoo_stop_processing();
+					status[i] = PluginStatus::Reloading(time::Instant::now() + self.stop_timeout);
+				}
+				PluginStatus::Reloading(_) => {}
+				PluginStatus::Restarting | PluginStatus::Blacklisted | PluginStatus::Failed => {
+					self.finish_reload(status, i, true);
+				}
+				s => {
+					let e = CuckooMinerError::PluginStateError(format!(
+						"Plugin {} can't be reloaded while {:?}",
+						i,
+						s
+					));
+					let mut c = self.control_data.write().unwrap();
+					c.swaps.remove(&i);
+					c.reload_results.insert(i, Err(e));
+				}
+			}
+		}
+	}
+
+	/// The reloading plugins which are ready to be reloaded, with whether
+	/// each stopped processing in time
+
+	fn reloads_ready(&self, status: &[PluginStatus]) -> Vec<(usize, bool)> {
+		let now = time::Instant::now();
+		let mut ready = Vec::new();
+		for (i, l) in self.libraries.read().unwrap().iter().enumerate() {
+			if let PluginStatus::Reloading(deadline) = status[i] {
+				if l.call_cuckoo_has_processing_stopped() == 1 {
+					ready.push((i, true));
+				} else if now >= deadline {
+					ready.push((i, false));
+				}
+			}
+		}
+		ready
 	}
 
-	/// The main job loop. Pushes hashes to the plugin and reads solutions
-	/// from the queue, putting them into the job's output queue. Continues
+	/// Swaps a plugin for a fresh copy loaded from its file, or for the
+	/// replacement the job handle has given, with its config's parameters
+	/// and the same devices disabled, and starts it on the current job.
+	/// The outcome is left for the job handle. If the plugin can't be
+	/// reloaded, it's treated as failed, and a replacement is forgotten,
+	/// so the old plugin is the one reloaded if it's retried.
+
+	fn finish_reload(&mut self, status: &mut [PluginStatus], index: usize, stopped: bool) {
+		let swap = self.control_data.write().unwrap().swaps.remove(&index);
+		let disabled = {
+			let libraries = self.libraries.clone();
+			let libraries = libraries.read().unwrap();
+			let l = &libraries[index];
+			let disabled = l.disabled_devices();
+			if stopped {
+				// anything found while stopping is kept, as the worker
+				// may not have read it yet
+				if l.state() != PluginState::Unloaded {
+					let solutions = l.drain_solutions(usize::max_value());
+					let edge_bits = l.edge_bits();
+					self.accept_solutions(index, l, solutions, edge_bits);
+				}
+				l.call_cuckoo_reset_processing();
+				l.unload();
+			} else {
+				self.plugin_failed(index, l, String::from("Processing didn't stop in time for reload"));
+				l.force_unload();
+			}
+			disabled
+		};
+		let source = swap.clone().or_else(|| self.sources.get(index).cloned());
+		let result = match source {
+			Some((config, instance)) => reload_plugin_library(&config, instance).and_then(|l| {
+				for d in &disabled {
+					l.set_device_enabled(*d, false)?;
+				}
+				let code = l.call_cuckoo_start_processing();
+				PluginCall::StartProcessing.check(code, &l.lib_full_path)?;
+				Ok(l)
+			}),
+			None => Err(CuckooMinerError::NotSupported(format!(
+				"Plugin {} has no config to reload it from",
+				index
+			))),
+		};
+		let result = match result {
+			Ok(l) => {
+				info!("Cuckoo-miner: Reloaded {}", l.lib_full_path);
+				let high_water = l.input_queue_capacity().ok().map(high_water_mark);
+				self.libraries.write().unwrap()[index] = l;
+				if let Some(source) = swap {
+					self.sources[index] = source;
+				}
+				status[index] = PluginStatus::Running;
+				self.tell_worker(index, WorkerCommand::Start(high_water));
+				if let Some(ref mut w) = self.watchdog {
+					w.forget(index);
+				}
+				if let Some(ref mut s) = self.stall {
+					s.forget(index);
+				}
+				Ok(())
+			}
+			Err(e) => {
+				let message = format!("Reload failed: {:?}", e);
+				error!("Cuckoo-miner: Plugin {}: {}", index, message);
+				self.failures.record_failure(index);
+				self.plugin_down(status, index, &message);
+				Err(e)
+			}
+		};
+		self.control_data.write().unwrap().reload_results.insert(index, result);
+	}
+
+	/// Reloads and restarts failed plugins, and blacklisted ones whose
+	/// cool-down has passed, starting their workers feeding them again
+
+	fn restart_plugins(&mut self, status: &mut [PluginStatus]) {
+		let now = time::Instant::now();
+		for i in 0..status.len() {
+			match status[i] {
+				PluginStatus::Restarting => {}
+				PluginStatus::Blacklisted if self.failures.retry_due_at(i, now) => {
+					self.failures.begin_retry_at(i, now);
+				}
+				_ => continue,
+			}
+			// the old instance has to go first, as the new one shares
+			// its file
+			self.libraries.read().unwrap()[i].unload();
+			let (config, instance) = self.sources[i].clone();
+			let started = load_plugin(&config, instance).and_then(|l| {
+				let code = l.call_cuckoo_start_processing();
+				PluginCall::StartProcessing.check(code, &l.lib_full_path).map(|_| l)
+			});
+			match started {
+				Ok(l) => {
+					info!("Cuckoo-miner: Restarted {}", l.lib_full_path);
+					let high_water = l.input_queue_capacity().ok().map(high_water_mark);
+					self.libraries.write().unwrap()[i] = l;
+					status[i] = PluginStatus::Running;
+					self.tell_worker(i, WorkerCommand::Start(high_water));
+				}
+				Err(e) => {
+					let message = format!("Unable to restart {}: {:?}", config.plugin_full_path, e);
+					error!("Cuckoo-miner: {}", message);
+					self.failures.record_failure(i);
+					self.plugin_down(status, i, &message);
+				}
+			}
+		}
+	}
+
+	/// Puts the solutions read from the plugin at `index` which are for the
+	/// current job, and verify and meet the difficulty, into the job's
+	/// output queue. Those for the previous job are sent as stale.
+
+	fn accept_solutions(
+		&mut self,
+		index: usize,
+		l: &PluginLibrary,
+		solutions: Vec<CuckooMinerSolution>,
+		edge_bits: Option<u32>,
+	) {
+		let job_id = self.job.job_id;
+		for mut solution in solutions {
+			// solutions for retired jobs may still be queued, as may
+			// another miner's sharing the plugin
+			solution.id ^= self.job.queue_id;
+			if self.ledger.lock().unwrap().job(solution.id).is_none() {
+				continue;
+			}
+			// a buggy plugin's garbage never reaches the caller
+			if let Err(e) = check_bounds(&solution.solution_nonces, edge_bits) {
+				self.plugin_failed(
+					index,
+					l,
+					format!("Dropping malformed solution for Nonce:({}): {}", solution.nonce, e),
+				);
+				continue;
+			}
+			if !self.duplicates.insert(&solution) {
+				debug!(
+					"Cuckoo-miner plugin[{}]: Dropping repeated solution for Nonce:({})",
+					index,
+					solution.nonce
+				);
+				self.shared_data.write().unwrap().stats.record_duplicate();
+				continue;
+			}
+			let job = match self.attribute(l, &mut solution) {
+				Some(j) => j,
+				None => continue,
+			};
+			solution.header_hash = Some(job.hash(solution.nonce, l.nonce_encoding()));
+			// found in a graph already under way when the job switched
+			if job.job_id != job_id {
+				if solution.meets_difficulty(job.difficulty) {
+					debug!(
+						"Cuckoo-miner plugin[{}]: Stale solution found for job {}, Nonce:({})",
+						index,
+						job.job_id,
+						solution.nonce
+					);
+					let _ = self.send_event(MinerEvent::StaleSolution(solution));
+				}
+				continue;
+			}
+			{
+				// every verified solution is a graph with a cycle,
+				// whether or not it meets the difficulty
+				let mut s = self.shared_data.write().unwrap();
+				if s.job_id != job_id {
+					continue;
+				}
+				s.stats.record_cycle();
+			}
+			if solution.meets_difficulty(self.difficulty) {
+				debug!(
+					"Cuckoo-miner plugin[{}]: Solution Found for Nonce:({}), {:?}",
+					index,
+					solution.nonce,
+					solution
+				);
+				let mut s = self.shared_data.write().unwrap();
+				if s.job_id != job_id {
+					continue;
+				}
+				s.stats.record_solution();
+				s.subscribers.retain(|t| t.send(&solution));
+				if let Err(MinerEvent::SolutionFound(solution)) =
+					self.send_event(MinerEvent::SolutionFound(solution))
+				{
+					s.solutions.push(solution);
+				}
+			}
+		}
+	}
+
+	/// Records a sample of the stats of the plugin at `index`, checking
+	/// them for hung plugins, hot devices and failed devices, and sending
+	/// them on to the event channel
+
+	fn sample_stats(&mut self, index: usize, l: &PluginLibrary, mut stats: Vec<PluginDeviceStats>) {
+		self.shared_data.write().unwrap().stats.sample(index, &stats);
+		if let Some(ref mut w) = self.watchdog {
+			if w.check(index, &stats) {
+				self.hung.push(index);
+			}
+		}
+		if self.stall.as_mut().map_or(false, |s| s.check(index, &stats)) {
+			self.warn_stalled(index, l);
+		}
+		if let Some(ref mut t) = self.thermal {
+			let changes = t.check(index, &stats);
+			self.thermal_changes.extend(changes.into_iter().map(|c| (index, c)));
+		}
+		let name = self.plugin_name(l);
+		for d in &mut stats {
+			d.plugin_name = Some(name.clone());
+		}
+		for e in newly_failed_devices(&mut self.failed_devices, index, &stats) {
+			if self.failures.record_failure(index) {
+				self.benched.push(index);
+			}
+			let _ = self.send_event(e);
+		}
+		let _ = self.send_event(MinerEvent::StatsUpdated(stats));
+	}
+
+	/// Logs that a plugin has gone quiet, with the depths of its queues,
+	/// which show whether it's short of headers or its solutions aren't
+	/// being read
+
+	fn warn_stalled(&self, index: usize, l: &PluginLibrary) {
+		let interval = self.stall.as_ref().map(|s| s.interval()).unwrap_or_default();
+		let (input, output) = self.shared_data.read().unwrap().stats.queue_depths(index).unwrap_or((0, 0));
+		let hint = match (input, output) {
+			(0, _) => "its input queue is empty, so it may not be fed fast enough",
+			(_, 0) => "its queues look healthy, so it may be stuck on a graph",
+			_ => "its output queue isn't empty, so its solutions may not be read fast enough",
+		};
+		warn!(
+			"Cuckoo-miner: {} hasn't finished a graph or found a solution in {:?}; \
+			 input queue depth {}, output queue depth {}: {}",
+			l.lib_full_path,
+			interval,
+			input,
+			output,
+			hint
+		);
+	}
+
+	/// Acts on a report from a plugin's worker. Anything the worker reads
+	/// after its plugin has stopped running is only kept if it's a
+	/// solution.
+
+	fn handle_report(&mut self, status: &mut [PluginStatus], report: WorkerReport) {
+		let libraries = self.libraries.clone();
+		let libraries = libraries.read().unwrap();
+		match report {
+			WorkerReport::Solutions { index, solutions, edge_bits, last_nonce } => {
+				if let Some(nonce) = last_nonce {
+					self.shared_data.write().unwrap().stats.record_nonce_position(index, nonce);
+				}
+				if !solutions.is_empty() {
+					if let Some(ref mut s) = self.stall {
+						s.record_solution(index, time::Instant::now());
+					}
+				}
+				self.accept_solutions(index, &libraries[index], solutions, edge_bits);
+			}
+			WorkerReport::Stats { index, stats, duty_cycle, queue_depths } => {
+				if status[index] != PluginStatus::Running {
+					return;
+				}
+				{
+					let mut s = self.shared_data.write().unwrap();
+					s.stats.record_duty_cycle(index, duty_cycle);
+					s.stats.record_queue_depths(index, queue_depths);
+				}
+				match stats {
+					Ok(stats) => self.sample_stats(index, &libraries[index], stats),
+					Err(e) => warn!("Unable to sample stats from {}: {:?}", libraries[index].lib_full_path, e),
+				}
+			}
+			WorkerReport::CallFailed { index, call, code } => {
+				if status[index] == PluginStatus::Running {
+					self.plugin_error(index, &libraries[index], call, code);
+				}
+			}
+			WorkerReport::Panicked { index, message } => {
+				let message = format!("Panicked while handling the plugin: {}", message);
+				self.failures.record_failure(index);
+				match status[index] {
+					PluginStatus::Running => {
+						drop(libraries);
+						self.retire_plugin(status, index, message);
+					}
+					_ => self.plugin_failed(index, &libraries[index], message),
+				}
+			}
+			WorkerReport::Exited(index) => {
+				warn!("Cuckoo-miner: Worker for plugin {} exited early", index);
+			}
+		}
+	}
+
+	/// Waits out a pass of the job loop, acting on the workers' reports as
+	/// they come in
+
+	fn handle_reports(&mut self, status: &mut [PluginStatus], reports: &Receiver<WorkerReport>) {
+		let end = time::Instant::now() + WORKER_POLL_INTERVAL;
+		loop {
+			let now = time::Instant::now();
+			if now >= end {
+				return;
+			}
+			match reports.recv_timeout(end - now) {
+				Ok(r) => self.handle_report(status, r),
+				Err(RecvTimeoutError::Timeout) => return,
+				Err(RecvTimeoutError::Disconnected) => {
+					thread::sleep(end - now);
+					return;
+				}
+			}
+		}
+	}
+
+	/// Tells every worker to read what's left in its plugin's queue and
+	/// exit, and joins them, acting on their last reports. Workers which
+	/// haven't exited by the deadline are left to finish on their own.
+
+	fn finish_workers(
+		&mut self,
+		status: &mut [PluginStatus],
+		reports: &Receiver<WorkerReport>,
+		workers: Vec<thread::JoinHandle<()>>,
+		deadline: time::Instant,
+	) {
+		for i in 0..workers.len() {
+			self.tell_worker(i, WorkerCommand::Finish);
+		}
+		let mut workers: Vec<_> = workers.into_iter().map(Some).collect();
+		let mut running = workers.len();
+		while running > 0 {
+			let now = time::Instant::now();
+			if now >= deadline {
+				break;
+			}
+			match reports.recv_timeout(deadline - now) {
+				Ok(WorkerReport::Exited(i)) => {
+					if let Some(w) = workers[i].take() {
+						let _ = w.join();
+						running -= 1;
+					}
+				}
+				Ok(r) => self.handle_report(status, r),
+				Err(_) => break,
+			}
+		}
+		for (i, w) in workers.iter().enumerate() {
+			if w.is_some() {
+				warn!("Cuckoo-miner: Worker for plugin {} didn't exit in time, leaving it", i);
+			}
+		}
+		self.workers.clear();
+	}
+
+	/// The main job loop. Starts a worker for each plugin, which pushes
+	/// hashes to the plugin and reads solutions from its queue, and puts
+	/// the solutions they report into the job's output queue. Continues
 	/// until another thread sets the is_running flag to false
 
-	fn job_loop(self, hash_header: bool) -> Result<(), CuckooMinerError> {
-		// keep some unchanging data here, can move this out of shared
-		// object later if it's not needed anywhere else
-		let pre_nonce: String;
-		let post_nonce: String;
+	fn job_loop(mut self) -> Result<(), CuckooMinerError> {
 		// generate an identifier to ensure we're only reading our
-		// jobs from the queue
+		// jobs from the queue. Job ids are pushed to the plugin xored
+		// with this, so they can be told apart from any left over from
+		// another miner sharing the plugin
 		let queue_id: u32 = rand::OsRng::new().unwrap().gen();
-		let difficulty;
 		{
 			let s = self.shared_data.read().unwrap();
-			pre_nonce = s.pre_nonce.clone();
-			post_nonce = s.post_nonce.clone();
-			difficulty = s.difficulty;
+			self.difficulty = s.difficulty;
+			self.job = Arc::new(WorkerJob {
+				job_id: s.job_id,
+				queue_id: queue_id,
+				pre_nonce: s.pre_nonce.clone(),
+				post_nonce: s.post_nonce.clone(),
+				hash_header: s.hash_header,
+				hasher: self.hasher.clone(),
+				difficulty: s.difficulty,
+			});
 		}
+		self.ledger.lock().unwrap().start_job(self.job.clone());
 		debug!(
 			"Cuckoo-miner: Searching for solution >= difficulty {}",
-			difficulty
+			self.difficulty
 		);
-	
-		for l in self.libraries.read().unwrap().iter() {
-			l.call_cuckoo_start_processing();
+
+		// each plugin mines its own slice of the nonce space, so plugins
+		// never duplicate each other's work
+		let nonces = mem::replace(&mut self.nonces, Vec::new());
+		let (report_sender, reports) = mpsc::channel();
+		let mut workers = Vec::new();
+		for (i, n) in nonces.into_iter().enumerate() {
+			let (command_sender, commands) = mpsc::channel();
+			let (cpu_affinity, priority) = match self.sources.get(i) {
+				Some(&(ref c, _)) => (c.cpu_affinity.clone(), c.thread_priority),
+				None => (None, None),
+			};
+			workers.push(spawn_worker(
+				i,
+				self.libraries.clone(),
+				commands,
+				report_sender.clone(),
+				n,
+				self.job.clone(),
+				WorkerSettings {
+					stats_interval: self.stats_interval,
+					cpu_affinity: cpu_affinity,
+					priority: priority,
+					duty_cycle: self.duty_cycle.clone(),
+					ledger: self.ledger.clone(),
+				},
+			));
+			self.workers.push(command_sender);
 		}
+		drop(report_sender);
 
-		debug!("Cuckoo Miner Job loop processing");
-		let mut solution = CuckooMinerSolution::new();
+		// a plugin which fails to start is left out, and the others
+		// carry on without it. Plugins which don't report their queue
+		// capacity are fed until they say they're over their limit.
+		let mut status = Vec::new();
+		let mut failed_to_start = Vec::new();
+		for (i, l) in self.libraries.read().unwrap().iter().enumerate() {
+			let code = l.call_cuckoo_start_processing();
+			self.plugin_error(i, l, PluginCall::StartProcessing, code);
+			status.push(PluginStatus::Running);
+			if code != 0 {
+				failed_to_start.push(i);
+			} else {
+				let high_water = l.input_queue_capacity().ok().map(high_water_mark);
+				self.tell_worker(i, WorkerCommand::Start(high_water));
+			}
+		}
+		for i in failed_to_start {
+			self.failures.record_failure(i);
+			self.plugin_down(&mut status, i, "Processing failed to start");
+		}
 
+		debug!("Cuckoo Miner Job loop processing");
 		loop {
 			// Check if it's time to stop
 			{
 				let s = self.control_data.read().unwrap();
-				if s.stop_flag {
+				if s.stop_flag || self.shutdown_flag.load(Ordering::SeqCst) {
 					break;
 				}
 			}
-			for l in self.libraries.read().unwrap().iter() {
-				while l.call_cuckoo_is_queue_under_limit() == 1 {
-					let (nonce, data) = match hash_header {
-						true => self.get_next_header_data_hashed(&pre_nonce, &post_nonce),
-						false => self.get_next_header_data(&pre_nonce, &post_nonce),
+			// Pick up a new job if there is one. Each worker discards
+			// anything queued for the old one.
+			{
+				let _control = self.control_lock.lock().unwrap();
+				let mut s = self.shared_data.write().unwrap();
+				if s.job_id != self.job.job_id {
+					debug!("Cuckoo-miner: Switching from job {} to job {}", self.job.job_id, s.job_id);
+					self.difficulty = s.difficulty;
+					self.job = Arc::new(WorkerJob {
+						job_id: s.job_id,
+						queue_id: queue_id,
+						pre_nonce: s.pre_nonce.clone(),
+						post_nonce: s.post_nonce.clone(),
+						hash_header: s.hash_header,
+						hasher: self.hasher.clone(),
+						difficulty: s.difficulty,
+					});
+					// the last job's graphs under way may still finish
+					self.ledger.lock().unwrap().start_job(self.job.clone());
+					let mut nonces = match s.nonce_job.take() {
+						Some(j) => j.nonce_generators(&self.first_nonces).into_iter().map(Some).collect(),
+						None => Vec::new(),
 					};
-					// TODO: make this a serialise operation instead
-					let nonce_bytes: [u8; 8] = unsafe { transmute(nonce.to_be()) };
-					l.call_cuckoo_push_to_input_queue(queue_id, &data, &nonce_bytes);
+					nonces.resize(self.workers.len(), None);
+					for (i, n) in nonces.into_iter().enumerate() {
+						self.tell_worker(i, WorkerCommand::Job(self.job.clone(), n));
+					}
+					self.duplicates.clear();
 				}
 			}
 
-			let mut plugin_index=0;
-			for l in self.libraries.read().unwrap().iter() {
-				let mut qid:u32 = 0;
-				while l.call_cuckoo_read_from_output_queue(
-					&mut qid,
-					&mut solution.solution_nonces,
-					&mut solution.cuckoo_size,
-					&mut solution.nonce,
-				) != 0
-				{
-					// TODO: make this a serialise operation instead
-					let nonce = unsafe { transmute::<[u8; 8], u64>(solution.nonce) }.to_be();
-
-					if self.meets_difficulty(difficulty, solution) && qid == queue_id {
-						debug!(
-							"Cuckoo-miner plugin[{}]: Solution Found for Nonce:({}), {:?}",
-							plugin_index,
-							nonce,
-							solution
-						);
-						let mut s = self.shared_data.write().unwrap();
-						s.solutions.push(solution.clone());
-						plugin_index+=1;
-					}
+			self.handle_reports(&mut status, &reports);
 
+			let control_lock = self.control_lock.clone();
+			let control = control_lock.lock().unwrap();
+			for (i, stopped) in self.reloads_ready(&status) {
+				self.finish_reload(&mut status, i, stopped);
+			}
+			drop(control);
+			let timeout = self.watchdog.as_ref().map(|w| w.timeout()).unwrap_or_default();
+			for i in mem::replace(&mut self.hung, Vec::new()) {
+				if status[i] == PluginStatus::Running {
+					self.failures.record_failure(i);
+					let message = format!("No graphs attempted for {:?}, giving up on plugin", timeout);
+					self.retire_plugin(&mut status, i, message);
 				}
 			}
-			//avoid busy wait 
-			let sleep_dur = time::Duration::from_millis(100);
-			thread::sleep(sleep_dur);
+			for i in mem::replace(&mut self.benched, Vec::new()) {
+				if status[i] == PluginStatus::Running {
+					let message = String::from("Too many device failures, giving up on plugin");
+					self.retire_plugin(&mut status, i, message);
+				}
+			}
+			let control = control_lock.lock().unwrap();
+			for (i, change) in mem::replace(&mut self.thermal_changes, Vec::new()) {
+				self.apply_thermal_change(i, change);
+			}
+			self.check_retiring(&mut status);
+			self.restart_plugins(&mut status);
+			self.begin_reloads(&mut status);
+			drop(control);
 		}
 
-		// Do any cleanup
-		for l in self.libraries.read().unwrap().iter() {
-			l.call_cuckoo_stop_processing();
+		// Do any cleanup, giving plugins until the stop timeout to finish
+		// internal processing, or the shutdown deadline if shutting down
+		let shutdown = self.shutdown_flag.load(Ordering::SeqCst);
+		let timeout = match shutdown {
+			true => self.control_data
+				.read()
+				.unwrap()
+				.shutdown_deadline
+				.unwrap_or(self.shutdown_deadline),
+			false => self.stop_timeout,
+		};
+		let deadline = time::Instant::now() + timeout;
+		for i in 0..self.workers.len() {
+			self.tell_worker(i, WorkerCommand::Idle);
 		}
-		for l in self.libraries.read().unwrap().iter() {
-			//wait for internal processing to finish
-			while l.call_cuckoo_has_processing_stopped()==0{
-				thread::sleep(time::Duration::from_millis(1));
-			};
-			l.call_cuckoo_reset_processing();
+		for (i, l) in self.libraries.read().unwrap().iter().enumerate() {
+			match status[i] {
+				PluginStatus::Running => {
+					l.call_cuckoo_stop_processing();
+					status[i] = PluginStatus::Retiring(deadline);
+				}
+				// already stopping, and won't be reloaded now
+				PluginStatus::Reloading(d) => status[i] = PluginStatus::Retiring(d),
+				_ => {}
+			}
+		}
+		// all plugins are stopping at once, so each only gets what's left
+		// of its deadline
+		let libraries = self.libraries.clone();
+		for (i, l) in libraries.read().unwrap().iter().enumerate() {
+			if let PluginStatus::Retiring(d) = status[i] {
+				let now = time::Instant::now();
+				let remaining = if d > now { d - now } else { time::Duration::from_secs(0) };
+				match l.wait_for_processing_stopped(remaining) {
+					Ok(()) => status[i] = PluginStatus::Stopped,
+					Err(e) => {
+						self.plugin_failed(i, l, format!("{:?}", e));
+						l.force_unload();
+						status[i] = PluginStatus::Failed;
+					}
+				}
+			}
+		}
+		// keep anything found while stopping, before reset clears the
+		// queues. Workers are given a moment even if the deadline has
+		// passed, as reading a queue doesn't wait on the plugin.
+		let deadline = cmp::max(deadline, time::Instant::now() + WORKER_POLL_INTERVAL * 5);
+		self.finish_workers(&mut status, &reports, workers, deadline);
+		for (i, l) in self.libraries.read().unwrap().iter().enumerate() {
+			if status[i] == PluginStatus::Stopped {
+				l.call_cuckoo_reset_processing();
+			}
+			// plugins given up on were force unloaded above
+			if shutdown {
+				l.unload();
+			}
+		}
+		{
+			// ends the iterators and streams over the job's solutions
+			let mut s = self.shared_data.write().unwrap();
+			s.finished = true;
+			s.subscribers.clear();
 		}
 		let mut s = self.control_data.write().unwrap();
 		s.has_stopped=true;
+		let _ = self.send_event(MinerEvent::JobStopped(self.job.job_id));
 		Ok(())
 	}
 }
+
+/// Picks out the devices of the plugin at `index` which have started
+/// reporting an error since the last stats sample, logging each and
+/// returning an event for it. `failed` holds the devices already known to
+/// have failed, so a device which recovers is reported again if it fails
+/// again.
+
+fn newly_failed_devices(
+	failed: &mut HashSet<(usize, String)>,
+	index: usize,
+	stats: &[PluginDeviceStats],
+) -> Vec<MinerEvent> {
+	let mut events = Vec::new();
+	for d in stats {
+		let key = (index, d.device_id.clone());
+		if !d.has_errored {
+			failed.remove(&key);
+			continue;
+		}
+		if !failed.insert(key) {
+			continue;
+		}
+		let plugin = d.plugin_name.clone().unwrap_or_default();
+		error!(
+			"Cuckoo-miner: {} device {} ({}) failed: {}",
+			plugin,
+			d.device_id,
+			d.device_name,
+			d.error_reason
+		);
+		events.push(MinerEvent::DeviceFailed {
+			plugin: plugin,
+			device_id: d.device_id.clone(),
+			reason: d.error_reason.clone(),
+		});
+	}
+	events
+}
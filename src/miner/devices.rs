@@ -0,0 +1,203 @@
+// Copyright 2017 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Fans a plugin config out into an instance of the plugin per device,
+//! e.g. one lean_cuda_30 per GPU, each set up for its own device.
+
+use std::collections::BTreeMap;
+
+use super::miner::{CuckooMinerConfig, load_plugin};
+use cuckoo_sys::manager::ALL_DEVICES;
+use error::error::CuckooMinerError;
+
+/// The parameter holding the number of blocks a CUDA plugin launches on
+/// a device
+pub const NUM_BLOCKS_PARAM: &str = "NUM_BLOCKS";
+
+/// The parameter holding the number of threads in each block a CUDA
+/// plugin launches on a device
+pub const THREADS_PER_BLOCK_PARAM: &str = "THREADS_PER_BLOCK";
+
+/// #Description
+///
+/// How a single device is set up when a plugin's config is fanned out
+/// with [CuckooMinerConfig::per_device](struct.CuckooMinerConfig.html#method.per_device).
+/// The threads and blocks are set through the plugin's
+/// [THREADS_PER_BLOCK](constant.THREADS_PER_BLOCK_PARAM.html) and
+/// [NUM_BLOCKS](constant.NUM_BLOCKS_PARAM.html) parameters, as the CUDA
+/// plugins take them, and any other parameters as given.
+///
+/// #Example
+///
+/// ```
+///  # use cuckoo_miner::DeviceConfig;
+///  let gpu = DeviceConfig::new(1).threads(64).blocks(128).parameter("N_TRIMS", 176);
+///  assert_eq!(gpu.parameters().len(), 3);
+/// ```
+///
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct DeviceConfig {
+	/// The device's id, as listed by
+	/// [list_devices](struct.PluginLibrary.html#method.list_devices)
+	pub device_id: u32,
+
+	/// The threads per block, if set
+	pub threads: Option<u32>,
+
+	/// The number of blocks, if set
+	pub blocks: Option<u32>,
+
+	/// Other parameters for the device, by name
+	pub parameters: BTreeMap<String, u32>,
+}
+
+impl DeviceConfig {
+	/// Returns a config for a device, leaving its parameters as the
+	/// plugin's config has them
+
+	pub fn new(device_id: u32) -> DeviceConfig {
+		DeviceConfig {
+			device_id: device_id,
+			..DeviceConfig::default()
+		}
+	}
+
+	/// Sets the threads per block on the device
+
+	pub fn threads(mut self, threads: u32) -> DeviceConfig {
+		self.threads = Some(threads);
+		self
+	}
+
+	/// Sets the number of blocks on the device
+
+	pub fn blocks(mut self, blocks: u32) -> DeviceConfig {
+		self.blocks = Some(blocks);
+		self
+	}
+
+	/// Sets another parameter on the device
+
+	pub fn parameter(mut self, name: &str, value: u32) -> DeviceConfig {
+		self.parameters.insert(String::from(name), value);
+		self
+	}
+
+	/// Every parameter set on the device, including the threads and
+	/// blocks, by name
+
+	pub fn parameters(&self) -> BTreeMap<String, u32> {
+		let mut parameters = self.parameters.clone();
+		if let Some(t) = self.threads {
+			parameters.insert(String::from(THREADS_PER_BLOCK_PARAM), t);
+		}
+		if let Some(b) = self.blocks {
+			parameters.insert(String::from(NUM_BLOCKS_PARAM), b);
+		}
+		parameters
+	}
+}
+
+impl CuckooMinerConfig {
+	/// #Description
+	///
+	/// Fans the config out into one per device, each loading its own
+	/// instance of the plugin with only that device enabled, so the
+	/// devices are mined and monitored separately. Each config keeps the
+	/// plugin's parameters, its parameters for every device, and those
+	/// for its device, with the device's own config set over them.
+	///
+	/// A configured nonce range is split between the configs, so they
+	/// don't mine the same nonces. Otherwise, each gets its share of the
+	/// nonces as any other plugin does.
+	///
+	/// #Arguments
+	///
+	/// * `devices` (IN) The devices, and how each is set up
+	///
+	/// #Returns
+	///
+	/// A config per device, in the order given, ready to pass to
+	/// [CuckooMiner::new](struct.CuckooMiner.html#method.new)
+	///
+	/// #Example
+	///
+	/// ```no_run
+	///  # use cuckoo_miner::{CuckooMiner, CuckooMinerConfig, DeviceConfig};
+	///  let mut config = CuckooMinerConfig::new();
+	///  config.plugin_full_path = String::from("lean_cuda_30");
+	///  let configs = config.per_device(&[
+	///  	DeviceConfig::new(0).threads(64).blocks(128),
+	///  	DeviceConfig::new(1).threads(32).blocks(64),
+	///  ]);
+	///  let miner = CuckooMiner::new(configs).unwrap();
+	/// ```
+	///
+
+	pub fn per_device(&self, devices: &[DeviceConfig]) -> Vec<CuckooMinerConfig> {
+		let count = devices.len() as u64;
+		devices
+			.iter()
+			.enumerate()
+			.map(|(i, d)| {
+				let mut c = self.clone();
+				c.enabled_devices = Some(vec![d.device_id]);
+				c.device_parameters.retain(|id, _| *id == ALL_DEVICES || *id == d.device_id);
+				c.device_parameters
+					.entry(d.device_id)
+					.or_insert_with(BTreeMap::new)
+					.extend(d.parameters());
+				c.nonce_range = self.nonce_range.map(|r| r.split(i as u64, count));
+				c
+			})
+			.collect()
+	}
+
+	/// #Description
+	///
+	/// Fans the config out into one per device the plugin reports as
+	/// usable, as [per_device](#method.per_device) does, leaving each
+	/// device's parameters as the config has them. The plugin's loaded
+	/// with the config applied to list its devices, and unloaded again
+	/// before returning. If the config enables only some devices, only
+	/// those are used.
+	///
+	/// #Returns
+	///
+	/// * `Ok(Vec<CuckooMinerConfig>)` with a config per usable device,
+	/// in the order the plugin lists them
+	/// * a DeviceNotFoundError if the plugin has no usable devices
+	/// * another [CuckooMinerError](enum.CuckooMinerError.html) if the
+	/// plugin can't be loaded or set up, or its devices can't be listed
+	///
+
+	pub fn per_usable_device(&self) -> Result<Vec<CuckooMinerConfig>, CuckooMinerError> {
+		let library = load_plugin(self, 0)?;
+		let devices = library.usable_devices();
+		library.unload();
+		let devices: Vec<DeviceConfig> = devices?
+			.into_iter()
+			.filter(|d| self.enabled_devices.as_ref().map_or(true, |e| e.contains(&d.device_id)))
+			.map(|d| DeviceConfig::new(d.device_id))
+			.collect();
+		if devices.is_empty() {
+			return Err(CuckooMinerError::DeviceNotFoundError(format!(
+				"{} has no usable devices",
+				self.plugin_full_path
+			)));
+		}
+		Ok(self.per_device(&devices))
+	}
+}
@@ -0,0 +1,91 @@
+// Copyright 2017 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Events sent by a running job to the channel set with
+//! [CuckooMiner::set_event_channel](struct.CuckooMiner.html#method.set_event_channel),
+//! as an alternative to polling the job handle.
+
+use std::time::Duration;
+
+use cuckoo_sys::manager::PluginDeviceStats;
+use CuckooMinerSolution;
+
+/// Something that happened while mining
+
+#[derive(Debug, Clone)]
+pub enum MinerEvent {
+	/// A solution for the current job which meets the target difficulty,
+	/// with its [metadata](struct.SolutionMetadata.html) saying where and
+	/// when it was found
+	SolutionFound(CuckooMinerSolution),
+
+	/// A solution for the job before the current one, which meets that
+	/// job's difficulty, found in a graph already under way when the job
+	/// switched. Its `id` is the earlier job's. Pools may accept these for
+	/// a short while after a new block, and otherwise they can be ignored.
+	StaleSolution(CuckooMinerSolution),
+
+	/// A plugin reported a solution which isn't a cycle in any header
+	/// pushed to it with the solution's nonce, so it was dropped. A plugin
+	/// which mixes up jobs sharing a nonce is the usual cause.
+	UnmatchedSolution {
+		/// The name of the plugin
+		plugin: String,
+		/// The solution's nonce
+		nonce: u64,
+		/// The job the plugin reported the solution for
+		job_id: u32,
+	},
+
+	/// A plugin call returned an error code, or the plugin was given up
+	/// on, e.g. by the watchdog
+	PluginError {
+		/// The name of the plugin
+		plugin: String,
+		/// The code the plugin returned, or 0 if the plugin was given up
+		/// on without returning one
+		code: u32,
+		/// A description of the failed call
+		message: String,
+	},
+
+	/// A device of a plugin has reported an error, while the plugin may
+	/// carry on with its other devices. Sent once when the device starts
+	/// reporting the error, rather than on each stats sample.
+	DeviceFailed {
+		/// The name of the plugin
+		plugin: String,
+		/// The device id, as reported in its stats
+		device_id: String,
+		/// Why the device failed, if the plugin says
+		reason: String,
+	},
+
+	/// A plugin has failed too many times, and has been unloaded and
+	/// benched while the other plugins carry on
+	PluginBlacklisted {
+		/// The name of the plugin
+		plugin: String,
+		/// A description of the failure which got it blacklisted
+		reason: String,
+		/// How long until it's retried
+		retry_in: Duration,
+	},
+
+	/// Freshly sampled stats for each device of a plugin
+	StatsUpdated(Vec<PluginDeviceStats>),
+
+	/// The job with this id has stopped, and no more events will be sent
+	JobStopped(u32),
+}
@@ -0,0 +1,239 @@
+// Copyright 2017 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A minimal HTTP/1.1 listener for the metrics and control servers, which
+//! answers one request per connection, one connection at a time.
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use error::error::CuckooMinerError;
+
+/// How often the listener checks whether it's been stopped
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(50);
+
+/// How long a client has to send its request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The longest request line or header accepted
+const MAX_LINE_LENGTH: u64 = 8192;
+
+/// The most headers accepted in a request
+const MAX_HEADERS: usize = 64;
+
+/// The largest request body accepted
+const MAX_BODY_LENGTH: usize = 1 << 20;
+
+/// A request, as read by the listener
+
+pub struct HttpRequest {
+	/// The method, e.g. GET
+	pub method: String,
+
+	/// The path, including any query string
+	pub path: String,
+
+	/// The headers, with their names in lower case
+	pub headers: Vec<(String, String)>,
+
+	/// The body, if a Content-Length was given
+	pub body: Vec<u8>,
+}
+
+impl HttpRequest {
+	/// The value of a header, looked up without regard to case
+	pub fn header(&self, name: &str) -> Option<&str> {
+		let name = name.to_lowercase();
+		self.headers.iter().find(|h| h.0 == name).map(|h| h.1.as_str())
+	}
+}
+
+/// A response to a request
+
+pub struct HttpResponse {
+	/// The status line's code and reason, e.g. "200 OK"
+	pub status: &'static str,
+
+	/// The body's content type
+	pub content_type: &'static str,
+
+	/// The body
+	pub body: String,
+}
+
+impl HttpResponse {
+	/// A plain text response
+	pub fn text(status: &'static str, body: &str) -> HttpResponse {
+		HttpResponse {
+			status: status,
+			content_type: "text/plain",
+			body: String::from(body),
+		}
+	}
+}
+
+/// A running listener, started by the metrics or control server. The
+/// listener stops when this is dropped.
+
+pub struct HttpServer {
+	/// The address the listener is bound to
+	addr: SocketAddr,
+
+	/// Set to stop the listener
+	stop: Arc<AtomicBool>,
+
+	/// The listener thread
+	thread: Option<JoinHandle<()>>,
+}
+
+impl HttpServer {
+	/// The address the listener is bound to, e.g. to find the port picked
+	/// when binding to port 0
+	pub fn local_addr(&self) -> SocketAddr {
+		self.addr
+	}
+
+	/// Stops the listener, waiting for any request in progress to finish
+	pub fn stop(mut self) {
+		self.shut_down();
+	}
+
+	fn shut_down(&mut self) {
+		self.stop.store(true, Ordering::SeqCst);
+		if let Some(t) = self.thread.take() {
+			let _ = t.join();
+		}
+	}
+}
+
+impl Drop for HttpServer {
+	fn drop(&mut self) {
+		self.shut_down();
+	}
+}
+
+/// Starts a listener on its own thread, answering each request with what
+/// `handler` returns. `name` says what the listener is for in errors and
+/// the log.
+
+pub fn serve<A, F>(addr: A, name: &'static str, handler: F) -> Result<HttpServer, CuckooMinerError>
+where
+	A: ToSocketAddrs,
+	F: Fn(&HttpRequest) -> HttpResponse + Send + 'static,
+{
+	let io_error = |e: io::Error| CuckooMinerError::PluginIOError(format!("Unable to serve {}: {}", name, e));
+	let listener = TcpListener::bind(addr).map_err(&io_error)?;
+	let local_addr = listener.local_addr().map_err(&io_error)?;
+	listener.set_nonblocking(true).map_err(&io_error)?;
+	let stop = Arc::new(AtomicBool::new(false));
+	let thread_stop = stop.clone();
+	let thread = thread::spawn(move || {
+		while !thread_stop.load(Ordering::SeqCst) {
+			match listener.accept() {
+				Ok((stream, _)) => {
+					if let Err(e) = answer(stream, &handler) {
+						debug!("Unable to answer {} request: {}", name, e);
+					}
+				}
+				Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => thread::sleep(ACCEPT_INTERVAL),
+				Err(e) => {
+					warn!("The {} listener on {} failed: {}", name, local_addr, e);
+					thread::sleep(ACCEPT_INTERVAL);
+				}
+			}
+		}
+	});
+	Ok(HttpServer {
+		addr: local_addr,
+		stop: stop,
+		thread: Some(thread),
+	})
+}
+
+// Reads a line of the request head, without its line ending
+
+fn read_line<R: BufRead>(reader: &mut R) -> io::Result<String> {
+	let mut line = String::new();
+	reader.by_ref().take(MAX_LINE_LENGTH).read_line(&mut line)?;
+	if !line.ends_with('\n') {
+		return Err(io::Error::new(io::ErrorKind::InvalidData, "Request line too long or cut short"));
+	}
+	Ok(String::from(line.trim_end()))
+}
+
+// Reads a request
+
+fn read_request<R: BufRead>(reader: &mut R) -> io::Result<HttpRequest> {
+	let invalid = |m: &str| io::Error::new(io::ErrorKind::InvalidData, String::from(m));
+	let request_line = read_line(reader)?;
+	let mut words = request_line.split_whitespace();
+	let (method, path) = match (words.next(), words.next()) {
+		(Some(m), Some(p)) => (String::from(m), String::from(p)),
+		_ => return Err(invalid("Malformed request line")),
+	};
+	let mut headers = Vec::new();
+	loop {
+		let line = read_line(reader)?;
+		if line.is_empty() {
+			break;
+		}
+		if headers.len() == MAX_HEADERS {
+			return Err(invalid("Too many headers"));
+		}
+		let colon = line.find(':').ok_or_else(|| invalid("Malformed header"))?;
+		headers.push((line[..colon].trim().to_lowercase(), String::from(line[colon + 1..].trim())));
+	}
+	let mut request = HttpRequest {
+		method: method,
+		path: path,
+		headers: headers,
+		body: Vec::new(),
+	};
+	if let Some(length) = request.header("content-length") {
+		let length = length.parse::<usize>().map_err(|_| invalid("Malformed Content-Length"))?;
+		if length > MAX_BODY_LENGTH {
+			return Err(invalid("Body too long"));
+		}
+		request.body = vec![0; length];
+		reader.read_exact(&mut request.body)?;
+	}
+	Ok(request)
+}
+
+// Reads a request and answers it
+
+fn answer<F: Fn(&HttpRequest) -> HttpResponse>(stream: TcpStream, handler: &F) -> io::Result<()> {
+	stream.set_nonblocking(false)?;
+	stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+	stream.set_write_timeout(Some(REQUEST_TIMEOUT))?;
+	let mut writer = stream.try_clone()?;
+	let response = match read_request(&mut BufReader::new(stream)) {
+		Ok(request) => handler(&request),
+		Err(ref e) if e.kind() == io::ErrorKind::InvalidData => HttpResponse::text("400 Bad Request", "Bad request\n"),
+		Err(e) => return Err(e),
+	};
+	write!(
+		writer,
+		"HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+		response.status,
+		response.content_type,
+		response.body.len(),
+		response.body
+	)?;
+	writer.flush()
+}
@@ -0,0 +1,232 @@
+// Copyright 2017 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Jobs given to the miner as raw block headers, and how each header is
+//! hashed, with its nonce, into the 32 bytes the plugins mine on.
+
+use blake2::blake2b::Blake2b;
+
+use super::nonce::{NonceRange, NonceGenerator};
+
+/// #Description
+///
+/// Reduces a block header, with a nonce spliced in, to the 32 bytes the
+/// plugins mine on and solutions verify against. The miner hashes with
+/// [Blake2bHasher](struct.Blake2bHasher.html) unless another is set with
+/// [CuckooMiner::set_header_hasher](struct.CuckooMiner.html#method.set_header_hasher),
+/// e.g. for a chain which hashes its headers differently.
+///
+/// Hashers are shared between the miner's threads, and called for every
+/// header pushed, so should be quick and mustn't block.
+///
+
+pub trait HeaderHasher: Send + Sync {
+	/// #Description
+	///
+	/// Hashes a header with its nonce.
+	///
+	/// #Arguments
+	///
+	/// * `header_pre` (IN) The part of the header before the nonce
+	/// * `nonce` (IN) The nonce, as laid out in the header
+	/// * `header_post` (IN) The part of the header after the nonce, which
+	/// may be empty, in which case the nonce is appended to the header
+	///
+	/// #Returns
+	///
+	/// The hash
+	///
+
+	fn hash(&self, header_pre: &[u8], nonce: &[u8; 8], header_post: &[u8]) -> [u8; 32];
+}
+
+/// #Description
+///
+/// Hashes headers as grin does, i.e. `header_pre || nonce || header_post`
+/// with unkeyed blake2b-256, as [header_hash](fn.header_hash.html)
+/// documents. The miner's default [HeaderHasher](trait.HeaderHasher.html).
+///
+/// #Example
+///
+/// ```
+///  # use cuckoo_miner::{Blake2bHasher, HeaderHasher, header_hash};
+///  let hash = Blake2bHasher.hash(&[0u8; 72], &[0, 0, 0, 0, 0, 0, 0, 56], &[0u8; 5]);
+///  assert_eq!(hash, header_hash(&[0u8; 72], &[0u8; 5], 56));
+/// ```
+///
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Blake2bHasher;
+
+impl HeaderHasher for Blake2bHasher {
+	fn hash(&self, header_pre: &[u8], nonce: &[u8; 8], header_post: &[u8]) -> [u8; 32] {
+		let mut blake2b = Blake2b::new(32);
+		blake2b.update(header_pre);
+		blake2b.update(nonce);
+		blake2b.update(header_post);
+		let mut ret = [0; 32];
+		ret.copy_from_slice(blake2b.finalize().as_bytes());
+		ret
+	}
+}
+
+/// #Description
+///
+/// A job for the miner, given as the raw block header either side of the
+/// nonce, rather than as hex or hashes. The miner works through nonces
+/// itself, from the job's start nonce if it has one, and hashes each
+/// header with the miner's [HeaderHasher](trait.HeaderHasher.html) before
+/// pushing it to the plugins. Started with
+/// [CuckooMiner::start](struct.CuckooMiner.html#method.start), or
+/// switched to with
+/// [CuckooMinerJobHandle::notify_job](struct.CuckooMinerJobHandle.html#method.notify_job).
+///
+/// A job may also be given its own slice of the nonce space, e.g. one
+/// assigned by a pool, which is shared out between the plugins, and a
+/// limit on the nonces tried, after which the plugins are left idle.
+/// Each solution carries the nonce it was found with.
+///
+/// #Example
+///
+/// ```
+///  # use cuckoo_miner::{MiningJob, NonceRange};
+///  let job = MiningJob::new(1, &[0u8; 72], &[0u8; 5])
+///  	.difficulty(10)
+///  	.start_nonce(56)
+///  	.nonce_range(NonceRange::interleaved(1, 4))
+///  	.max_nonces(1000);
+///  assert_eq!(job.start_nonce, Some(56));
+/// ```
+///
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MiningJob {
+	/// The job's id, which solutions found for it carry in their `id`
+	pub job_id: u32,
+
+	/// The part of the header before the nonce
+	pub header_pre: Vec<u8>,
+
+	/// The part of the header after the nonce, which may be empty
+	pub header_post: Vec<u8>,
+
+	/// Only solutions of at least this difficulty are returned
+	pub difficulty: u64,
+
+	/// The nonce the plugins' nonce ranges start from, or None to keep
+	/// the miner's, as set by
+	/// [set_start_nonce](struct.CuckooMiner.html#method.set_start_nonce)
+	/// or picked at random
+	pub start_nonce: Option<u64>,
+
+	/// The job's nonces, relative to the start nonce, split between the
+	/// plugins with [NonceRange::split](struct.NonceRange.html#method.split),
+	/// or None to mine the plugins' own ranges
+	pub nonce_range: Option<NonceRange>,
+
+	/// The most nonces to try, across every plugin, or None to keep
+	/// trying until the job's stopped or switched
+	pub max_nonces: Option<u64>,
+}
+
+impl MiningJob {
+	/// Returns a job for a header, taking solutions of any difficulty,
+	/// from the miner's start nonce
+
+	pub fn new(job_id: u32, header_pre: &[u8], header_post: &[u8]) -> MiningJob {
+		MiningJob {
+			job_id: job_id,
+			header_pre: header_pre.to_vec(),
+			header_post: header_post.to_vec(),
+			difficulty: 0,
+			start_nonce: None,
+			nonce_range: None,
+			max_nonces: None,
+		}
+	}
+
+	/// Sets the difficulty solutions must meet
+
+	pub fn difficulty(mut self, difficulty: u64) -> MiningJob {
+		self.difficulty = difficulty;
+		self
+	}
+
+	/// Sets the nonce to start mining from
+
+	pub fn start_nonce(mut self, start_nonce: u64) -> MiningJob {
+		self.start_nonce = Some(start_nonce);
+		self
+	}
+
+	/// Sets the job's slice of the nonce space
+
+	pub fn nonce_range(mut self, range: NonceRange) -> MiningJob {
+		self.nonce_range = Some(range);
+		self
+	}
+
+	/// Sets the most nonces to try
+
+	pub fn max_nonces(mut self, max_nonces: u64) -> MiningJob {
+		self.max_nonces = Some(max_nonces);
+		self
+	}
+
+	/// Whether the job changes which nonces the plugins mine, rather than
+	/// leaving them to carry on through their ranges
+
+	pub fn sets_nonces(&self) -> bool {
+		self.start_nonce.is_some() || self.nonce_range.is_some() || self.max_nonces.is_some()
+	}
+
+	/// #Description
+	///
+	/// The nonces each plugin mines for the job, given those it would
+	/// otherwise mine. Each plugin starts over from the job's start
+	/// nonce, or its own, on its share of the job's range, or its own
+	/// range, keeping any extranonce prefix. With a limit, the plugins
+	/// share it out as they share the range, so `max_nonces` are tried
+	/// between them.
+	///
+	/// #Arguments
+	///
+	/// * `generators` (IN) The plugins' nonces, one generator each
+	///
+	/// #Returns
+	///
+	/// A generator for each plugin, in the same order
+	///
+
+	pub fn nonce_generators(&self, generators: &[NonceGenerator]) -> Vec<NonceGenerator> {
+		let count = generators.len() as u64;
+		generators
+			.iter()
+			.enumerate()
+			.map(|(i, g)| {
+				let i = i as u64;
+				let start = self.start_nonce.unwrap_or(g.start());
+				let range = self.nonce_range.map_or(g.range(), |r| r.split(i, count));
+				let g = g.restart(start, range);
+				match self.max_nonces {
+					// of the first max positions, those which fall to
+					// plugin i
+					Some(max) if max > i => g.with_limit((max - i - 1) / count + 1),
+					Some(_) => g.with_limit(0),
+					None => g,
+				}
+			})
+			.collect()
+	}
+}
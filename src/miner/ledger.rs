@@ -0,0 +1,168 @@
+// Copyright 2017 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A record of the headers pushed to plugins, by nonce, so a solution can
+//! be matched to the header it's actually a cycle in rather than taken on
+//! the plugin's word. A nonce may be pushed with more than one header when
+//! the caller reuses nonces across jobs, and a plugin which keeps track of
+//! its work by nonce can then report a solution under the wrong job. Used
+//! internally
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+
+use super::worker::WorkerJob;
+
+/// The most pushes remembered. Once full, the oldest push is forgotten
+/// for each new one, and solutions for forgotten pushes are checked
+/// against the job they claim to be for.
+pub const LEDGER_CAPACITY: usize = 4096;
+
+/// A header pushed to a plugin, exactly as pushed, and the job it was for
+
+#[derive(Clone)]
+pub struct Push {
+	/// The job
+	pub job: Arc<WorkerJob>,
+
+	/// The header
+	pub header: Vec<u8>,
+
+	/// How many headers had been pushed for the job before this one,
+	/// across every plugin
+	pub attempt: u64,
+}
+
+/// The headers pushed with each nonce for the jobs still outstanding,
+/// which are the current job and the one before it, as graphs already
+/// under way when the job switched may still finish.
+
+pub struct PushLedger {
+	/// The most pushes remembered
+	capacity: usize,
+
+	/// The outstanding jobs, oldest first
+	jobs: VecDeque<Arc<WorkerJob>>,
+
+	/// The headers pushed with each nonce, oldest first
+	pushes: HashMap<u64, Vec<Push>>,
+
+	/// The nonce and job id of each push, oldest first, for forgetting
+	/// the oldest when full
+	order: VecDeque<(u64, u32)>,
+
+	/// The headers pushed for each outstanding job
+	attempts: HashMap<u32, u64>,
+}
+
+impl PushLedger {
+	/// Returns a new ledger remembering up to `capacity` pushes
+
+	pub fn new(capacity: usize) -> PushLedger {
+		PushLedger {
+			capacity: capacity,
+			jobs: VecDeque::new(),
+			pushes: HashMap::new(),
+			order: VecDeque::new(),
+			attempts: HashMap::new(),
+		}
+	}
+
+	/// Starts recording pushes for a new job, retiring every job but the
+	/// last, along with its pushes
+
+	pub fn start_job(&mut self, job: Arc<WorkerJob>) {
+		while self.jobs.len() > 1 {
+			if let Some(retired) = self.jobs.pop_front() {
+				self.retire(retired.job_id);
+			}
+		}
+		self.jobs.push_back(job);
+	}
+
+	/// The outstanding job with the given id, if there is one
+
+	pub fn job(&self, job_id: u32) -> Option<Arc<WorkerJob>> {
+		self.jobs.iter().find(|j| j.job_id == job_id).cloned()
+	}
+
+	/// #Description
+	///
+	/// Records a header pushed to a plugin. Pushes for jobs which have
+	/// already retired are ignored, as are repeats of a nonce within a
+	/// job, which always give the same header.
+	///
+	/// #Arguments
+	///
+	/// * `job` (IN) The job the header was pushed for
+	/// * `nonce` (IN) The nonce it was pushed with
+	/// * `header` (IN) The header, exactly as pushed
+	///
+
+	pub fn record(&mut self, job: &Arc<WorkerJob>, nonce: u64, header: &[u8]) {
+		if self.capacity == 0 || self.job(job.job_id).is_none() {
+			return;
+		}
+		if self.pushes
+			.get(&nonce)
+			.map_or(false, |p| p.iter().any(|p| p.job.job_id == job.job_id))
+		{
+			return;
+		}
+		if self.order.len() >= self.capacity {
+			if let Some((nonce, job_id)) = self.order.pop_front() {
+				self.forget(nonce, job_id);
+			}
+		}
+		let attempts = self.attempts.entry(job.job_id).or_insert(0);
+		self.pushes.entry(nonce).or_insert_with(Vec::new).push(Push {
+			job: job.clone(),
+			header: header.to_vec(),
+			attempt: *attempts,
+		});
+		*attempts += 1;
+		self.order.push_back((nonce, job.job_id));
+	}
+
+	/// The headers pushed with a nonce for the outstanding jobs, oldest
+	/// first
+
+	pub fn pushes(&self, nonce: u64) -> Vec<Push> {
+		self.pushes.get(&nonce).cloned().unwrap_or_default()
+	}
+
+	// Forgets every push for a job
+	fn retire(&mut self, job_id: u32) {
+		self.pushes.retain(|_, p| {
+			p.retain(|p| p.job.job_id != job_id);
+			!p.is_empty()
+		});
+		self.order.retain(|&(_, j)| j != job_id);
+		self.attempts.remove(&job_id);
+	}
+
+	// Forgets one push
+	fn forget(&mut self, nonce: u64, job_id: u32) {
+		let empty = match self.pushes.get_mut(&nonce) {
+			Some(p) => {
+				p.retain(|p| p.job.job_id != job_id);
+				p.is_empty()
+			}
+			None => false,
+		};
+		if empty {
+			self.pushes.remove(&nonce);
+		}
+	}
+}
@@ -0,0 +1,200 @@
+// Copyright 2017 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Renders a job's stats in the Prometheus text exposition format, and
+//! serves them over HTTP for Prometheus to scrape.
+
+use std::fmt::Write;
+use std::net::ToSocketAddrs;
+
+use super::http::{self, HttpResponse, HttpServer};
+use super::stats::StatsCollector;
+use error::error::CuckooMinerError;
+
+/// The content type of the exposition format
+pub const METRICS_CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+/// The path metrics are served on
+pub const METRICS_PATH: &str = "/metrics";
+
+// Escapes a label value, as the exposition format requires
+
+fn escape(value: &str) -> String {
+	value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+// Writes a metric family's HELP and TYPE lines
+
+fn family(out: &mut String, name: &str, kind: &str, help: &str) {
+	let _ = writeln!(out, "# HELP {} {}", name, help);
+	let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+/// #Description
+///
+/// Renders stats in the Prometheus text exposition format. Per device
+/// figures are labelled with `plugin`, the plugin's file name, `instance`,
+/// which tells apart several instances of the same plugin, and `device`,
+/// the device id. Only what the collector already holds is read, so this
+/// never waits on the plugins.
+///
+/// The metric families are:
+///
+/// * `cuckoo_miner_graphs_per_second` Graphs per second, per device
+/// * `cuckoo_miner_device_errored` 1 if a device reported an error
+/// * `cuckoo_miner_device_temperature_celsius` Device temperature, for
+/// devices which report it
+/// * `cuckoo_miner_plugin_errors_total` Errors per plugin
+/// * `cuckoo_miner_duty_cycle_percent` The share of the stats window a
+/// plugin spent on graphs, for plugins throttled by a duty cycle
+/// * `cuckoo_miner_input_queue_depth` Headers waiting in a plugin's
+/// input queue, as last sampled
+/// * `cuckoo_miner_output_queue_depth` Solutions waiting in a plugin's
+/// output queue, as last sampled
+/// * `cuckoo_miner_solutions_found_total` Solutions meeting the difficulty
+/// * `cuckoo_miner_cycles_found_total` Graphs found with a cycle
+/// * `cuckoo_miner_duplicates_suppressed_total` Repeated solutions dropped
+///
+/// #Arguments
+///
+/// * `stats` (IN) The job's stats
+/// * `plugins` (IN) The name of each plugin, by plugin index
+///
+/// #Returns
+///
+/// The metrics, one family after another
+///
+
+pub fn render_metrics(stats: &StatsCollector, plugins: &[String]) -> String {
+	let labels = |index: usize| {
+		let name = plugins.get(index).map(|n| n.as_str()).unwrap_or("");
+		let instance = plugins[..index.min(plugins.len())].iter().filter(|n| *n == name).count();
+		format!("plugin=\"{}\",instance=\"{}\"", escape(name), instance)
+	};
+	let devices = stats.device_snapshots();
+	let mut out = String::new();
+
+	family(&mut out, "cuckoo_miner_graphs_per_second", "gauge", "Graphs searched per second, averaged over the stats window");
+	for d in &devices {
+		let _ = writeln!(
+			out,
+			"cuckoo_miner_graphs_per_second{{{},device=\"{}\"}} {}",
+			labels(d.plugin_index),
+			escape(&d.device_id),
+			d.graphs_per_second
+		);
+	}
+
+	family(&mut out, "cuckoo_miner_device_errored", "gauge", "Whether the device reported an error");
+	for d in &devices {
+		let _ = writeln!(
+			out,
+			"cuckoo_miner_device_errored{{{},device=\"{}\"}} {}",
+			labels(d.plugin_index),
+			escape(&d.device_id),
+			d.has_errored as u32
+		);
+	}
+
+	family(&mut out, "cuckoo_miner_device_temperature_celsius", "gauge", "Device temperature in degrees Celsius");
+	for d in &devices {
+		if let Some(t) = d.temperature_c {
+			let _ = writeln!(
+				out,
+				"cuckoo_miner_device_temperature_celsius{{{},device=\"{}\"}} {}",
+				labels(d.plugin_index),
+				escape(&d.device_id),
+				t
+			);
+		}
+	}
+
+	family(&mut out, "cuckoo_miner_plugin_errors_total", "counter", "Failed calls and other errors from the plugin");
+	for i in 0..plugins.len() {
+		let _ = writeln!(out, "cuckoo_miner_plugin_errors_total{{{}}} {}", labels(i), stats.plugin_errors(i));
+	}
+
+	family(&mut out, "cuckoo_miner_duty_cycle_percent", "gauge", "Share of the stats window the plugin spent on graphs");
+	for i in 0..plugins.len() {
+		if let Some(d) = stats.duty_cycle(i) {
+			let _ = writeln!(out, "cuckoo_miner_duty_cycle_percent{{{}}} {}", labels(i), d);
+		}
+	}
+
+	family(&mut out, "cuckoo_miner_input_queue_depth", "gauge", "Headers waiting in the plugin's input queue");
+	for i in 0..plugins.len() {
+		if let Some((input, _)) = stats.queue_depths(i) {
+			let _ = writeln!(out, "cuckoo_miner_input_queue_depth{{{}}} {}", labels(i), input);
+		}
+	}
+
+	family(&mut out, "cuckoo_miner_output_queue_depth", "gauge", "Solutions waiting in the plugin's output queue");
+	for i in 0..plugins.len() {
+		if let Some((_, output)) = stats.queue_depths(i) {
+			let _ = writeln!(out, "cuckoo_miner_output_queue_depth{{{}}} {}", labels(i), output);
+		}
+	}
+
+	let totals = [
+		("cuckoo_miner_solutions_found_total", "Solutions found meeting the target difficulty", stats.solutions_found()),
+		("cuckoo_miner_cycles_found_total", "Graphs found with a cycle, whatever their difficulty", stats.cycles_found()),
+		("cuckoo_miner_duplicates_suppressed_total", "Repeated solutions dropped", stats.duplicates_suppressed()),
+	];
+	for &(name, help, value) in &totals {
+		family(&mut out, name, "counter", help);
+		let _ = writeln!(out, "{} {}", name, value);
+	}
+	out
+}
+
+/// A running metrics listener, started by
+/// [serve_metrics](fn.serve_metrics.html). The listener stops when this
+/// is dropped.
+pub type MetricsServer = HttpServer;
+
+/// #Description
+///
+/// Starts a minimal HTTP listener on its own thread, answering GETs of
+/// [METRICS_PATH](constant.METRICS_PATH.html) with whatever `render`
+/// returns, and anything else with a 404. Scrapes are answered one at a
+/// time, which is plenty for a Prometheus server or two.
+///
+/// #Arguments
+///
+/// * `addr` (IN) The address to listen on, e.g. "0.0.0.0:9090"
+/// * `render` (IN) Renders the metrics for each scrape
+///
+/// #Returns
+///
+/// * `Ok(MetricsServer)` once listening
+/// * a PluginIOError if the address can't be bound
+///
+
+pub fn serve_metrics<A, F>(addr: A, render: F) -> Result<MetricsServer, CuckooMinerError>
+where
+	A: ToSocketAddrs,
+	F: Fn() -> String + Send + 'static,
+{
+	http::serve(addr, "metrics", move |request| {
+		let scrape = request.path == METRICS_PATH || request.path.starts_with(&format!("{}?", METRICS_PATH));
+		match (request.method.as_str(), scrape) {
+			("GET", true) => HttpResponse {
+				status: "200 OK",
+				content_type: METRICS_CONTENT_TYPE,
+				body: render(),
+			},
+			_ => HttpResponse::text("404 Not Found", "Not found\n"),
+		}
+	})
+}
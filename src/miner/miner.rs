@@ -16,102 +16,402 @@
 //! to load a mining plugin, send it a Cuckoo Cycle POW problem, and
 //! return any resulting solutions.
 
-use std::sync::{Arc, RwLock};
+use std::sync::{Arc, Mutex, RwLock};
+use std::sync::mpsc::{self, Sender};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::{thread, time};
-use std::{fmt, cmp};
-use std::path::Path;
+use std::{fmt, cmp, hash};
+use std::path::{Path, PathBuf};
+use std::collections::{BTreeMap, HashMap};
 
 use byteorder::{ByteOrder, BigEndian};
+use rand::{self, Rng};
 use blake2::blake2b::Blake2b;
 
-use serde_json;
-
-use super::delegator:: {JobSharedData, JobControlData, Delegator};
-use cuckoo_sys::manager::PluginLibrary;
+use super::delegator:: {JobSharedData, JobControlData, JobOptions, Delegator};
+use super::bench::{BenchmarkMode, BenchmarkReport, BenchmarkPhases, benchmark_header, benchmark_queue};
+use super::events::MinerEvent;
+use super::stats::{Hashrate, DeviceSnapshot};
+use super::solutions::{SolutionIter, SolutionSender};
+#[cfg(feature = "async")]
+use super::solutions::SolutionStream;
+#[cfg(feature = "async")]
+use futures_channel::mpsc::unbounded;
+use super::tune::{TuningOptions, TuningPoint, TuningResult, values_in_range, combinations};
+use super::tuning_file::{save_tuning, load_tuning};
+use super::nonce::{NonceRange, NonceGenerator, NonceEncoding};
+use super::job::{HeaderHasher, Blake2bHasher, MiningJob};
+use super::affinity::{self, Priority, CPU_AFFINITY_MASK_PARAM, THREAD_PRIORITY_PARAM};
+#[cfg(feature = "metrics")]
+use super::metrics::{MetricsServer, render_metrics, serve_metrics};
+#[cfg(feature = "control")]
+use super::control::{ControlServer, serve_control};
+#[cfg(any(feature = "metrics", feature = "control"))]
+use std::net::ToSocketAddrs;
+use cuckoo_sys::manager::{PluginLibrary, SolverContext, PluginCall, PluginDeviceStats, ALL_DEVICES, CALL_CANCELLED};
+use cuckoo_sys::state::PluginState;
+use cuckoo_sys::path::resolve_plugin_path;
 use error::error::CuckooMinerError;
+use verify::verify::{verify_solution, is_verifiable, PROOF_SIZE, MAX_32_BIT_EDGE_BITS};
+use verify::keys::siphash_keys;
+use util::hex::from_hex;
+use util::memory::available_memory;
+use manager::install::check_pinned_hash;
+
+/// From grin
+/// The target is the 8-bytes hash block hashes must be lower than.
+const MAX_TARGET: [u8; 8] = [0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff];
+
+/// A simple struct to hold a cuckoo miner solution. The `solution_nonces`
+/// are allocated here, as many as the plugin's declared proof size, and
+/// filled in by the plugin upon finding a solution.
+///
+/// Solutions compare and hash by their proof nonces only, so the same
+/// proof read twice (or from two plugins) is treated as a duplicate.
 
-// Hardcoded assumption for now that the solution size will be 42 will be
-// maintained, to avoid having to allocate memory within the called C functions
-
-const CUCKOO_SOLUTION_SIZE: usize = 42;
-
-/// A simple struct to hold a cuckoo miner solution. Currently,
-/// it's assumed that a solution will be 42 bytes. The `solution_nonces`
-/// member is statically allocated here, and will be filled in
-/// by a plugin upon finding a solution.
-
-#[derive(Copy)]
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct CuckooMinerSolution {
 	/// Cuckoo size
 	pub cuckoo_size: u32,
-	/// An array allocated in rust that will be filled
-	/// by the called plugin upon successfully finding
-	/// a solution
-	pub solution_nonces: [u32; CUCKOO_SOLUTION_SIZE],
+	/// The proof's edge nonces, allocated in rust and filled by the
+	/// called plugin upon successfully finding a solution. 42 of them
+	/// unless the plugin declares a different proof size. Held as u64s,
+	/// as graphs with more than 32 edge bits have edge indices which
+	/// don't fit in a u32.
+	#[cfg_attr(feature = "serde", serde(with = "super::serialize::proof"))]
+	pub solution_nonces: Vec<u64>,
 
 	/// The nonce that was used to generate the
-	/// hash for which a solution was found
-	pub nonce: [u8; 8],
+	/// hash for which a solution was found. Plugins pass nonces as
+	/// 8 bytes, which are read in the plugin's
+	/// [NonceEncoding](enum.NonceEncoding.html), Big-Endian by default.
+	/// Serialised as a decimal string.
+	#[cfg_attr(feature = "serde", serde(with = "super::serialize::u64_string"))]
+	pub nonce: u64,
+
+	/// The id of the job the solution was found for, as given to
+	/// [notify](struct.CuckooMiner.html#method.notify). Solutions read
+	/// straight from a plugin have the id they were pushed with.
+	#[cfg_attr(feature = "serde", serde(default))]
+	pub id: u32,
+
+	/// The file name of the plugin which found the solution, if known
+	#[cfg_attr(feature = "serde", serde(default))]
+	pub plugin_name: Option<String>,
+
+	/// The blake2b hash of the header the solution was found for, with
+	/// the nonce spliced in, as [header_hash](fn.header_hash.html) gives
+	/// it, for solutions mined or matched to a job. Serialised as hex.
+	#[cfg_attr(
+		feature = "serde",
+		serde(default, skip_serializing_if = "Option::is_none", with = "super::serialize::hash_hex")
+	)]
+	pub header_hash: Option<[u8; 32]>,
+
+	/// Where and when the solution was found, for solutions read from a
+	/// plugin or mined
+	#[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Option::is_none"))]
+	pub metadata: Option<SolutionMetadata>,
+}
+
+/// Where and when a solution was found, for tuning. Anything that can't
+/// be worked out for certain, e.g. because several devices finished
+/// graphs at once, is None rather than guessed.
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SolutionMetadata {
+	/// The file name of the plugin which found the solution
+	#[cfg_attr(feature = "serde", serde(default))]
+	pub plugin_name: Option<String>,
+
+	/// The id of the device which found the solution, as the plugin
+	/// reports it, worked out from which device's stats show it finished
+	/// a graph as the solution was read
+	#[cfg_attr(feature = "serde", serde(default))]
+	pub device_id: Option<String>,
+
+	/// How long the graph took to search, in milliseconds
+	#[cfg_attr(feature = "serde", serde(default))]
+	pub graph_time_ms: Option<u64>,
+
+	/// The position of the solution's header among the headers tried for
+	/// the job, across every plugin, counting from 0, i.e. how many nonces
+	/// had been attempted before it
+	#[cfg_attr(feature = "serde", serde(default))]
+	pub attempt_index: Option<u64>,
+
+	/// When the solution was read from the plugin
+	pub found_at: time::SystemTime,
+}
+
+impl SolutionMetadata {
+	/// Returns metadata for a solution from the named plugin, found now,
+	/// with nothing else known
+
+	pub fn new(plugin_name: Option<String>) -> SolutionMetadata {
+		SolutionMetadata {
+			plugin_name: plugin_name,
+			device_id: None,
+			graph_time_ms: None,
+			attempt_index: None,
+			found_at: time::SystemTime::now(),
+		}
+	}
 }
 
 impl Default for CuckooMinerSolution {
 	fn default() -> CuckooMinerSolution {
 		CuckooMinerSolution {
 			cuckoo_size: 30,
-			solution_nonces: [0; CUCKOO_SOLUTION_SIZE],
-			nonce: [0; 8],
+			solution_nonces: vec![0; PROOF_SIZE],
+			nonce: 0,
+			id: 0,
+			plugin_name: None,
+			header_hash: None,
+			metadata: None,
 		}
 	}
 }
 
-impl Clone for CuckooMinerSolution {
-	fn clone(&self) -> CuckooMinerSolution {
-		*self
-	}
-}
-
 impl CuckooMinerSolution {
 	/// Creates a new cuckoo miner solution
-	/// with nonces set to a u32 array of size
-	/// 42 filled with zeroes.
+	/// with nonces set to 42 zeroes.
 
 	pub fn new() -> CuckooMinerSolution {
 		CuckooMinerSolution::default()
 	}
 
-	/// Sets the solution, mostly for testing
-	pub fn set_solution(&mut self, nonces: [u32; CUCKOO_SOLUTION_SIZE]) {
-		self.solution_nonces = nonces;
+	/// Sets the solution from u32s or u64s, mostly for testing
+	pub fn set_solution<T: Copy + Into<u64>>(&mut self, nonces: &[T]) {
+		self.solution_nonces = nonces.iter().map(|n| (*n).into()).collect();
 	}
 
 	/// return the nonce as a u64, for convenience
 	pub fn get_nonce_as_u64(&self) -> u64 {
-		BigEndian::read_u64(&self.nonce)
+		self.nonce
+	}
+
+	/// The nonce as the Big-Endian bytes passed to and from plugins
+	pub fn nonce_bytes(&self) -> [u8; 8] {
+		let mut bytes = [0; 8];
+		BigEndian::write_u64(&mut bytes, self.nonce);
+		bytes
+	}
+
+	/// Sets the nonce from the Big-Endian bytes passed to and from plugins
+	pub fn set_nonce_bytes(&mut self, bytes: &[u8; 8]) {
+		self.nonce = BigEndian::read_u64(bytes);
+	}
+
+	/// The nonce as bytes in the given encoding
+	pub fn nonce_bytes_as(&self, encoding: NonceEncoding) -> [u8; 8] {
+		encoding.encode(self.nonce)
+	}
+
+	/// Sets the nonce from bytes in the given encoding
+	pub fn set_nonce_bytes_as(&mut self, bytes: &[u8; 8], encoding: NonceEncoding) {
+		self.nonce = encoding.decode(bytes);
 	}
 
 	/// Converts the proof to a vector of u64s
 	pub fn to_u64s(&self) -> Vec<u64> {
-		let mut nonces = Vec::with_capacity(CUCKOO_SOLUTION_SIZE);
-		for n in self.solution_nonces.iter() {
-			nonces.push(*n as u64);
+		self.solution_nonces.clone()
+	}
+
+	/// Whether the solution is for a graph whose edge indices don't fit
+	/// in a u32, so it's read through the plugins' 64-bit calls and its
+	/// nonces are hashed as u64s
+	pub fn is_64_bit(&self) -> bool {
+		self.cuckoo_size.saturating_sub(1) > MAX_32_BIT_EDGE_BITS
+	}
+
+	/// #Description
+	///
+	/// Creates a solution from a proof given as u64s, as returned by
+	/// [to_u64s](#method.to_u64s). Other fields are left at their
+	/// defaults.
+	///
+	/// #Arguments
+	///
+	/// * `nonces` (IN) The proof nonces, usually 42
+	///
+	/// #Returns
+	///
+	/// * `Ok(CuckooMinerSolution)` if successful
+	/// * a SolutionError if there isn't an even, non-zero number of
+	/// nonces
+	///
+
+	pub fn from_u64s(nonces: &[u64]) -> Result<CuckooMinerSolution, CuckooMinerError> {
+		if nonces.is_empty() || nonces.len() % 2 != 0 {
+			return Err(CuckooMinerError::SolutionError(format!(
+				"Expected an even number of proof nonces, got {}",
+				nonces.len()
+			)));
+		}
+		let mut solution = CuckooMinerSolution::new();
+		solution.solution_nonces = nonces.to_vec();
+		Ok(solution)
+	}
+
+	/// How long the graph the solution was found in took to search, if
+	/// known
+
+	pub fn solve_time(&self) -> Option<time::Duration> {
+		self.metadata
+			.as_ref()
+			.and_then(|m| m.graph_time_ms)
+			.map(time::Duration::from_millis)
+	}
+
+	/// #Description
+	///
+	/// Encodes the proof as grin nodes read it: a byte holding the edge
+	/// bits, followed by the nonces packed into that many bits each,
+	/// least significant bit first, and padded with zeros to a whole
+	/// number of bytes.
+	///
+	/// #Returns
+	///
+	/// The encoded proof, 1 + (edge bits * proof size + 7) / 8 bytes long
+	///
+	/// #Example
+	///
+	/// ```
+	///  # use cuckoo_miner::CuckooMinerSolution;
+	///  let mut s = CuckooMinerSolution::new();
+	///  s.cuckoo_size = 30;
+	///  assert_eq!(s.to_bytes().len(), 1 + (29 * 42 + 7) / 8);
+	/// ```
+	///
+
+	pub fn to_bytes(&self) -> Vec<u8> {
+		let edge_bits = self.edge_bits();
+		let mut bytes = vec![0; 1 + packed_len(edge_bits, self.solution_nonces.len())];
+		bytes[0] = edge_bits as u8;
+		for (i, n) in self.solution_nonces.iter().enumerate() {
+			for b in 0..edge_bits {
+				if (n >> b) & 1 == 1 {
+					let pos = i * edge_bits as usize + b as usize;
+					bytes[1 + pos / 8] |= 1 << (pos % 8);
+				}
+			}
+		}
+		bytes
+	}
+
+	/// #Description
+	///
+	/// Decodes a proof encoded by [to_bytes](#method.to_bytes). Only the
+	/// cuckoo size and proof nonces are set, other fields are left at
+	/// their defaults.
+	///
+	/// #Arguments
+	///
+	/// * `bytes` (IN) The encoded proof
+	/// * `proof_size` (IN) The number of nonces in the proof, usually 42
+	///
+	/// #Returns
+	///
+	/// * `Ok(CuckooMinerSolution)` if successful
+	/// * a SolutionError if the proof size isn't even and non-zero, the
+	/// edge bits are 0 or over 64, the length doesn't match, or padding
+	/// bits are set
+	///
+
+	pub fn from_bytes(bytes: &[u8], proof_size: usize) -> Result<CuckooMinerSolution, CuckooMinerError> {
+		if bytes.is_empty() {
+			return Err(CuckooMinerError::SolutionError(String::from("Empty proof")));
+		}
+		let edge_bits = bytes[0] as u32;
+		if edge_bits == 0 || edge_bits > 64 {
+			return Err(CuckooMinerError::SolutionError(format!(
+				"Proof has {} edge bits",
+				edge_bits
+			)));
+		}
+		let expected = packed_len(edge_bits, proof_size);
+		if bytes.len() != 1 + expected {
+			return Err(CuckooMinerError::SolutionError(format!(
+				"Expected {} bytes for a proof of {} {}-bit nonces, got {}",
+				1 + expected,
+				proof_size,
+				edge_bits,
+				bytes.len()
+			)));
 		}
-		nonces
+		let packed = &bytes[1..];
+		let bit = |pos: usize| (packed[pos / 8] >> (pos % 8)) & 1;
+		let used = edge_bits as usize * proof_size;
+		if (used..expected * 8).any(|pos| bit(pos) == 1) {
+			return Err(CuckooMinerError::SolutionError(String::from("Proof padding isn't zero")));
+		}
+		let nonces: Vec<u64> = (0..proof_size)
+			.map(|i| {
+				(0..edge_bits as usize).fold(0u64, |n, b| n | (bit(i * edge_bits as usize + b) as u64) << b)
+			})
+			.collect();
+		let mut solution = CuckooMinerSolution::from_u64s(&nonces)?;
+		solution.cuckoo_size = edge_bits + 1;
+		Ok(solution)
+	}
+
+	// The number of bits in each edge index, one fewer than the cuckoo
+	// size
+	fn edge_bits(&self) -> u32 {
+		self.cuckoo_size.saturating_sub(1)
 	}
 
 	/// Returns the has of the solution, as performed in
-	/// grin
+	/// grin. Nonces are hashed as Big-Endian u32s, or u64s for
+	/// [64-bit](#method.is_64_bit) solutions.
 	pub fn hash(&self) -> [u8; 32] {
 		// Hash
 		let mut blake2b = Blake2b::new(32);
 		for n in 0..self.solution_nonces.len() {
-			let mut bytes = [0; 4];
-			BigEndian::write_u32(&mut bytes, self.solution_nonces[n]);
-			blake2b.update(&bytes);
+			if self.is_64_bit() {
+				let mut bytes = [0; 8];
+				BigEndian::write_u64(&mut bytes, self.solution_nonces[n]);
+				blake2b.update(&bytes);
+			} else {
+				let mut bytes = [0; 4];
+				BigEndian::write_u32(&mut bytes, self.solution_nonces[n] as u32);
+				blake2b.update(&bytes);
+			}
 		}
 		let mut ret = [0; 32];
 		ret.copy_from_slice(blake2b.finalize().as_bytes());
 		ret
 	}
+
+	/// #Description
+	///
+	/// Returns the difficulty of the solution, as calculated in grin: the
+	/// maximum target divided by the first 8 bytes of the solution's
+	/// [hash](#method.hash), read as a Big-Endian u64.
+	///
+	/// #Returns
+	///
+	/// The difficulty, which is u64::MAX for a hash starting with 8 zero
+	/// bytes
+
+	pub fn difficulty(&self) -> u64 {
+		let max_target = BigEndian::read_u64(&MAX_TARGET);
+		let num = BigEndian::read_u64(&self.hash()[0..8]);
+		if num == 0 {
+			return max_target;
+		}
+		max_target / num
+	}
+
+	/// Whether the solution's [difficulty](#method.difficulty) is at
+	/// least `target`
+
+	pub fn meets_difficulty(&self, target: u64) -> bool {
+		self.difficulty() >= target
+	}
 }
 
 impl fmt::Display for CuckooMinerSolution {
@@ -137,18 +437,376 @@ impl fmt::Debug for CuckooMinerSolution {
 
 impl cmp::PartialEq for CuckooMinerSolution {
 	fn eq(&self, other: &CuckooMinerSolution) -> bool {
-		for i in 0..CUCKOO_SOLUTION_SIZE {
-			if self.solution_nonces[i] != other.solution_nonces[i] {
-				return false;
+		self.solution_nonces == other.solution_nonces
+	}
+}
+
+impl cmp::Eq for CuckooMinerSolution {}
+
+impl hash::Hash for CuckooMinerSolution {
+	fn hash<H: hash::Hasher>(&self, state: &mut H) {
+		self.solution_nonces.hash(state);
+	}
+}
+
+// The bytes taken by a proof's nonces packed into edge_bits bits each
+fn packed_len(edge_bits: u32, proof_size: usize) -> usize {
+	(edge_bits as usize * proof_size + 7) / 8
+}
+
+/// #Description
+///
+/// Reduces a header of any length to the 32 bytes plugins accept. The
+/// nonce is spliced in between the two parts of the header as 8
+/// Big-Endian bytes, i.e. `header_pre || nonce || header_post`, and the
+/// result is hashed with unkeyed blake2b-256. Either part may be empty,
+/// e.g. an empty `header_post` appends the nonce to the header.
+///
+/// #Arguments
+///
+/// * `header_pre` (IN) The part of the header before the nonce
+/// * `header_post` (IN) The part of the header after the nonce
+/// * `nonce` (IN) The nonce
+///
+/// #Returns
+///
+/// The hash, which is what the plugin mines on and what solutions verify
+/// against
+///
+/// #Example
+/// ```
+///  # use cuckoo_miner::header_hash;
+///  let header_pre = [0u8; 72];
+///  let header_post = [0u8; 5];
+///  let hash = header_hash(&header_pre, &header_post, 56);
+///  assert_eq!(hash.len(), 32);
+/// ```
+///
+
+pub fn header_hash(header_pre: &[u8], header_post: &[u8], nonce: u64) -> [u8; 32] {
+	encoded_header_hash(header_pre, header_post, nonce, NonceEncoding::BigEndianU64)
+}
+
+/// #Description
+///
+/// As [header_hash](fn.header_hash.html), with the nonce spliced in using
+/// the given encoding rather than always as Big-Endian bytes.
+///
+/// #Arguments
+///
+/// * `header_pre` (IN) The part of the header before the nonce
+/// * `header_post` (IN) The part of the header after the nonce
+/// * `nonce` (IN) The nonce
+/// * `encoding` (IN) How the nonce is laid out in the header
+///
+/// #Returns
+///
+/// The hash
+///
+/// #Example
+/// ```
+///  # use cuckoo_miner::{header_hash, encoded_header_hash, NonceEncoding};
+///  let header_pre = [0u8; 72];
+///  let header_post = [0u8; 5];
+///  let hash = encoded_header_hash(&header_pre, &header_post, 56, NonceEncoding::LittleEndianU64);
+///  assert!(hash != header_hash(&header_pre, &header_post, 56));
+/// ```
+///
+
+pub fn encoded_header_hash(header_pre: &[u8], header_post: &[u8], nonce: u64, encoding: NonceEncoding) -> [u8; 32] {
+	Blake2bHasher.hash(header_pre, &encoding.encode(nonce), header_post)
+}
+
+/// The instance of its plugin each config loads. A plugin listed more
+/// than once gets an independent instance each time.
+
+pub fn plugin_instances(configs: &[CuckooMinerConfig]) -> Vec<usize> {
+	let mut instances: HashMap<PathBuf, usize> = HashMap::new();
+	configs
+		.iter()
+		.map(|c| match resolve_plugin_path(&c.plugin_full_path) {
+			Ok(p) => {
+				let n = instances.entry(p).or_insert(0);
+				*n += 1;
+				*n - 1
+			}
+			Err(_) => 0,
+		})
+		.collect()
+}
+
+// The nonces each config's plugin mines: its own range if it's given
+// one, and otherwise its share of the nonce space interleaved between
+// all the plugins
+fn nonce_ranges(configs: &[CuckooMinerConfig]) -> Vec<NonceRange> {
+	let count = configs.len() as u64;
+	configs
+		.iter()
+		.enumerate()
+		.map(|(i, c)| c.nonce_range.unwrap_or(NonceRange::interleaved(i as u64, count)))
+		.collect()
+}
+
+// Checks no two plugins would mine the same nonces, which happens when
+// ranges are given to some of the plugins but not to others, or the same
+// range is given twice, and would waste one plugin's work
+fn check_nonce_ranges(configs: &[CuckooMinerConfig]) -> Result<(), CuckooMinerError> {
+	let ranges = nonce_ranges(configs);
+	for i in 0..ranges.len() {
+		for j in i + 1..ranges.len() {
+			if ranges[i].collides_with(&ranges[j]) {
+				return Err(CuckooMinerError::ConfigError(format!(
+					"{} (plugin {}) and {} (plugin {}) would mine the same nonces, with ranges {:?} and {:?}",
+					configs[i].plugin_full_path,
+					i,
+					configs[j].plugin_full_path,
+					j,
+					ranges[i],
+					ranges[j]
+				)));
+			}
+		}
+	}
+	Ok(())
+}
+
+/// Loads the given instance of a config's plugin, and sets it up as the
+/// config asks. Also used to reload a plugin which has failed.
+
+pub fn load_plugin(c: &CuckooMinerConfig, instance: usize) -> Result<PluginLibrary, CuckooMinerError> {
+	if !c.pinned_hashes.is_empty() {
+		check_pinned_hash(resolve_plugin_path(&c.plugin_full_path)?, &c.pinned_hashes)?;
+	}
+	let lib=PluginLibrary::load_instance(&c.plugin_full_path, instance, c.allow_legacy_plugins)?;
+	lib.set_nonce_encoding(c.nonce_encoding);
+	configure_plugin(&lib, c)?;
+	Ok(lib)
+}
+
+/// Sets up a loaded plugin's parameters, devices and queue as the config
+/// asks
+
+fn configure_plugin(lib: &PluginLibrary, c: &CuckooMinerConfig) -> Result<(), CuckooMinerError> {
+	for elem in c.parameter_list.clone() {
+		CuckooMiner::set_parameter(elem.0.clone(), elem.1.clone(), elem.2.clone(), &lib)?;
+	}
+	// values for all devices go first, so single devices can
+	// override them
+	let all = c.device_parameters.get(&ALL_DEVICES).into_iter().map(|p| (ALL_DEVICES, p));
+	let single = c.device_parameters.iter()
+		.filter(|&(id, _)| *id != ALL_DEVICES)
+		.map(|(id, p)| (*id, p));
+	for (device_id, params) in all.chain(single) {
+		for (name, value) in params {
+			lib.set_parameter_for_device(name, device_id, *value)?;
+		}
+	}
+	if let Some(ref enabled) = c.enabled_devices {
+		CuckooMiner::select_devices(lib, enabled)?;
+	}
+	if let Some(n) = c.queue_size {
+		lib.set_input_queue_capacity(n)?;
+	}
+	if let Some(ref cores) = c.cpu_affinity {
+		let mask = affinity::affinity_mask(cores)?;
+		let result = match lib.set_parameter_u64(CPU_AFFINITY_MASK_PARAM, mask) {
+			Err(CuckooMinerError::NotSupported(_)) if mask <= u32::max_value() as u64 => {
+				lib.set_parameter(CPU_AFFINITY_MASK_PARAM, mask as u32)
+			}
+			r => r,
+		};
+		optional_parameter(lib, CPU_AFFINITY_MASK_PARAM, result)?;
+	}
+	if let Some(priority) = c.thread_priority {
+		let result = lib.set_parameter(THREAD_PRIORITY_PARAM, priority.value());
+		optional_parameter(lib, THREAD_PRIORITY_PARAM, result)?;
+	}
+	Ok(())
+}
+
+// Passes on the result of setting a standard parameter the plugin may
+// not have, in which case only the miner's own threads are affected
+fn optional_parameter(
+	lib: &PluginLibrary,
+	name: &str,
+	result: Result<(), CuckooMinerError>,
+) -> Result<(), CuckooMinerError> {
+	match result {
+		Err(CuckooMinerError::ParameterNotFoundError(_)) | Err(CuckooMinerError::NotSupported(_)) => {
+			debug!("{} has no usable {} parameter, leaving it to the OS", lib.lib_full_path, name);
+			Ok(())
+		}
+		r => r,
+	}
+}
+
+// Adds up the memory reported in each plugin's stats, counting the host
+// memory every device of a plugin reports only once
+
+fn total_memory_bytes<I>(plugins: I) -> Option<u64>
+where
+	I: Iterator<Item = Vec<PluginDeviceStats>>,
+{
+	let mut total = None;
+	for stats in plugins {
+		let host = stats.iter().filter_map(|s| s.host_memory_bytes).max();
+		let devices = stats.iter().filter_map(|s| s.memory_used_bytes);
+		for bytes in host.into_iter().chain(devices) {
+			total = Some(total.unwrap_or(0) + bytes);
+		}
+	}
+	total
+}
+
+/// Checks the system has the memory the plugins expect to allocate once
+/// they start processing, between them. Plugins which can't tell, or
+/// whose config skips the check, aren't counted, and nothing is checked
+/// if the system doesn't say how much memory it has available.
+
+fn check_memory(libraries: &[PluginLibrary], configs: &[CuckooMinerConfig]) -> Result<(), CuckooMinerError> {
+	let available = match available_memory() {
+		Some(m) => m,
+		None => {
+			debug!("The memory available isn't known, so plugins' memory isn't checked");
+			return Ok(());
+		}
+	};
+	let mut required = 0;
+	for (l, c) in libraries.iter().zip(configs) {
+		if c.skip_memory_check {
+			continue;
+		}
+		if let Some(expected) = l.expected_memory()? {
+			required += expected;
+			if required > available {
+				return Err(CuckooMinerError::InsufficientMemory {
+					plugin: l.lib_full_path.clone(),
+					required: required,
+					available: available,
+				});
+			}
+		}
+	}
+	Ok(())
+}
+
+// Checks a duty cycle is a percentage the plugins can be held to
+
+fn check_duty_cycle(percent: u8) -> Result<(), CuckooMinerError> {
+	if percent == 0 || percent > 100 {
+		return Err(CuckooMinerError::ParameterError(format!(
+			"A duty cycle of {}% is outside 1 to 100%",
+			percent
+		)));
+	}
+	Ok(())
+}
+
+/// How many times a plugin is loaded when reloading it, in case its file
+/// is being replaced at the time
+const RELOAD_ATTEMPTS: usize = 5;
+
+/// How long to wait between attempts to reload a plugin
+const RELOAD_RETRY_INTERVAL: time::Duration = time::Duration::from_millis(200);
+
+/// Reloads a plugin from its file, as [load_plugin](fn.load_plugin.html),
+/// retrying briefly if it can't be loaded, e.g. because the file is
+/// being replaced. Returns a PluginReloadFailed error if it keeps failing.
+
+pub fn reload_plugin_library(c: &CuckooMinerConfig, instance: usize) -> Result<PluginLibrary, CuckooMinerError> {
+	let mut attempt = 1;
+	loop {
+		match load_plugin(c, instance) {
+			Ok(l) => return Ok(l),
+			Err(e) => {
+				if attempt == RELOAD_ATTEMPTS {
+					return Err(CuckooMinerError::PluginReloadFailed {
+						plugin: c.plugin_full_path.clone(),
+						reason: format!("{:?}", e),
+					});
+				}
+				warn!("Unable to reload {}, retrying: {:?}", c.plugin_full_path, e);
+			}
+		}
+		attempt += 1;
+		thread::sleep(RELOAD_RETRY_INTERVAL);
+	}
+}
+
+/// #Description
+///
+/// Shuts down a set of plugins. Stop is broadcast to every plugin that's
+/// processing at once, then each is waited for until the shared deadline,
+/// and any that haven't stopped by then are force unloaded. Solutions
+/// left in the output queues of the rest are drained, then they're
+/// unloaded. Calling this again does nothing.
+///
+/// Force unloaded plugins refuse all calls, but their libraries stay
+/// mapped, as their threads may still be running in them. Every other
+/// plugin's threads have stopped, and its library is closed, when this
+/// returns.
+///
+/// #Arguments
+///
+/// * `libraries` (IN) The plugins to shut down
+/// * `deadline` (IN) How long plugins are given to stop processing
+///
+/// #Returns
+///
+/// The solutions drained from the plugins' output queues
+///
+
+pub fn shutdown_plugins(libraries: &[PluginLibrary], deadline: time::Duration) -> Vec<CuckooMinerSolution> {
+	let end = time::Instant::now() + deadline;
+	let mut stopping = Vec::new();
+	for l in libraries {
+		match l.state() {
+			PluginState::Processing => {
+				l.call_cuckoo_stop_processing();
+				stopping.push(l);
 			}
+			PluginState::Stopping => stopping.push(l),
+			_ => {}
+		}
+	}
+	for l in stopping {
+		let now = time::Instant::now();
+		let remaining = if end > now { end - now } else { time::Duration::from_secs(0) };
+		if let Err(e) = l.wait_for_processing_stopped(remaining) {
+			error!("Shutting down: {:?}", e);
+			l.force_unload();
+		}
+	}
+	let mut solutions = Vec::new();
+	for l in libraries {
+		if l.state() != PluginState::Unloaded {
+			solutions.extend(l.drain_solutions(usize::max_value()));
 		}
-		return true;
+		l.unload();
 	}
+	solutions
+}
+
+/// The indexes of the configs for the named plugin, which is either its
+/// configured path or its file name without the extension
+
+pub fn plugin_indices(configs: &[CuckooMinerConfig], name: &str) -> Vec<usize> {
+	configs
+		.iter()
+		.enumerate()
+		.filter(|&(_, c)| {
+			c.plugin_full_path == name ||
+				Path::new(&c.plugin_full_path).file_stem().map_or(false, |s| s == name)
+		})
+		.map(|(i, _)| i)
+		.collect()
 }
 
 /// Structure containing the configuration values to pass into an
 /// instance of a miner
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct CuckooMinerConfig {
 	/// The full path to the plugin to load and use to find a solution
 	/// to a POW problem. Defaults to empty string, so must be filled
@@ -158,6 +816,68 @@ pub struct CuckooMinerConfig {
 	/// A parameter list, which differs depending on which
 	/// plugin is being called
 	pub parameter_list: Vec<(String, u32, u32)>,
+
+	/// Whether to load the plugin, with a warning, even if it doesn't
+	/// report a plugin interface version. Defaults to true.
+	pub allow_legacy_plugins: bool,
+
+	/// The slice of the nonce space this plugin mines in asynchronous
+	/// mode. Defaults to None, in which case each plugin gets an
+	/// interleaved share of the nonces. Set either all or none of the
+	/// plugins' ranges, as automatic ranges don't avoid configured ones.
+	/// Plugins whose ranges would mine the same nonces aren't loaded.
+	#[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+	pub nonce_range: Option<NonceRange>,
+
+	/// The number of headers the plugin's input queue accepts, set through
+	/// its QUEUE_SIZE parameter when the plugin is loaded. Defaults to
+	/// None, leaving the plugin's own default.
+	#[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+	pub queue_size: Option<u32>,
+
+	/// Parameter values for individual devices, keyed by device id and
+	/// then parameter name, set when the plugin is loaded after those in
+	/// `parameter_list`. The device id
+	/// [ALL_DEVICES](constant.ALL_DEVICES.html) sets a value on every
+	/// device, before the other ids are applied.
+	#[cfg_attr(feature = "serde", serde(skip_serializing_if = "BTreeMap::is_empty"))]
+	pub device_parameters: BTreeMap<u32, BTreeMap<String, u32>>,
+
+	/// The ids of the devices to mine on, set when the plugin is loaded.
+	/// Defaults to None, in which case every device the plugin reports
+	/// is used.
+	#[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+	pub enabled_devices: Option<Vec<u32>>,
+
+	/// The indices of the cores the plugin's threads may run on, set
+	/// through its CPU_AFFINITY_MASK parameter if it has one. The threads
+	/// the miner runs for the plugin are pinned to them as well. Defaults
+	/// to None, leaving scheduling to the OS.
+	#[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+	pub cpu_affinity: Option<Vec<usize>>,
+
+	/// The scheduling priority of the plugin's threads, set through its
+	/// THREAD_PRIORITY parameter if it has one, and of the threads the
+	/// miner runs for the plugin. Defaults to None, leaving the OS's
+	/// default.
+	#[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+	pub thread_priority: Option<Priority>,
+
+	/// How nonces are laid out in the headers pushed to the plugin and
+	/// read back from its solutions. Defaults to Big-Endian, as grin
+	/// headers hold them.
+	pub nonce_encoding: NonceEncoding,
+
+	/// Whether to start the plugin even if it expects more memory than
+	/// the system has available. Defaults to false.
+	pub skip_memory_check: bool,
+
+	/// The hashes of the plugin builds allowed, as reported by
+	/// [plugin_hash](fn.plugin_hash.html). The plugin isn't loaded unless
+	/// its file's hash is one of them. Defaults to empty, allowing any
+	/// build.
+	#[cfg_attr(feature = "serde", serde(skip_serializing_if = "Vec::is_empty"))]
+	pub pinned_hashes: Vec<String>,
 }
 
 impl Default for CuckooMinerConfig {
@@ -165,6 +885,16 @@ impl Default for CuckooMinerConfig {
 		CuckooMinerConfig {
 			plugin_full_path: String::from(""),
 			parameter_list: Vec::new(),
+			allow_legacy_plugins: true,
+			nonce_range: None,
+			queue_size: None,
+			device_parameters: BTreeMap::new(),
+			enabled_devices: None,
+			cpu_affinity: None,
+			thread_priority: None,
+			nonce_encoding: NonceEncoding::BigEndianU64,
+			skip_memory_check: false,
+			pinned_hashes: Vec::new(),
 		}
 	}
 }
@@ -177,50 +907,18 @@ impl CuckooMinerConfig {
 	}
 }
 
-/// Holds deserialised performance metrics returned from the
-/// plugin
-///
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct CuckooMinerDeviceStats {
-	/// The plugin file name (optional so the plugins don't have to deal with it on de/ser)
-	pub plugin_name: Option<String>,
-
-	/// The internal device id
-	pub device_id: String,
-
-	/// Cuckoo size currently being used by the device
-	pub cuckoo_size: String,
-
-	/// The device name
-	pub device_name: String,
-
-	/// Whether the device is marked for use
-	pub in_use: u32,
- 
-	/// Whether the device has thrown an error (and has stopped)
-	pub has_errored: u32,
+/// Per-device stats as returned from the plugin. Kept under its original
+/// name for existing callers.
 
-	/// The time at which the device last began to search a hash (epoch in
-	/// mills)
-	pub last_start_time: u64,
-
-	/// The time at which the device last completed a solution search (epoch in
-	/// mills)
-	pub last_end_time: u64,
-
-	/// The amount of time the last solution search took (epoch in mills)
-	pub last_solution_time: u64,
-
-	/// The total number of searched performed since init
-	pub iterations_completed: u32,
-}
+pub type CuckooMinerDeviceStats = PluginDeviceStats;
 
 /// Handle to the miner's running job, used to read solutions
 /// or to control the job. Internal members are not exposed
 /// and all interactions should be via public functions
 /// This will basically hold an arc reference clone of
-/// the Delegator's internal shared data
+/// the Delegator's internal shared data. Clones share the same job.
 
+#[derive(Clone)]
 pub struct CuckooMinerJobHandle {
 	/// Data shared across threads
 	pub shared_data: Arc<RwLock<JobSharedData>>,
@@ -230,6 +928,19 @@ pub struct CuckooMinerJobHandle {
 
 	/// The loaded plugin
 	pub library: Arc<RwLock<Vec<PluginLibrary>>>,
+
+	/// The config each plugin was loaded from
+	pub configs: Vec<CuckooMinerConfig>,
+
+	/// Shuts the job down when set
+	pub shutdown_flag: Arc<AtomicBool>,
+
+	/// Held while plugins' settings are changed, which the job loop
+	/// also holds while it switches jobs or reloads plugins
+	pub control_lock: Arc<Mutex<()>>,
+
+	/// The share of the time the plugins spend on graphs, as a percentage
+	pub duty_cycle: Arc<AtomicUsize>,
 }
 
 impl CuckooMinerJobHandle {
@@ -266,278 +977,1878 @@ impl CuckooMinerJobHandle {
 
 	/// #Description
 	///
-	/// Stops the current job, and signals for the loaded plugin to stop
-	/// processing and perform any cleanup it needs to do. Blocks until
-	/// the jobs have completed
+	/// Returns all solutions currently waiting, in the order they were
+	/// found. Solutions found before the job was stopped are still
+	/// returned after [stop_jobs](#method.stop_jobs).
 	///
 	/// #Returns
 	///
-	/// Nothing
+	/// * Some(Vec<[CuckooMinerSolution](struct.CuckooMinerSolution.html)>)
+	/// if any solutions are waiting
+	/// * None if no solution is waiting
 
-	pub fn stop_jobs(&self) {
-		debug!("Stop jobs called");
-		{ 
-			let mut r = self.control_data.write().unwrap();
-			r.stop_flag = true;
-		}
-		debug!("Stop jobs flag set");
-		loop {
-			{
-				let r = self.control_data.read().unwrap();
-				if r.has_stopped {
-					break;
-				}
-			}
-			thread::sleep(time::Duration::from_millis(5));
+	pub fn get_solutions(&self) -> Option<Vec<CuckooMinerSolution>> {
+		let mut s = self.shared_data.write().unwrap();
+		if s.solutions.is_empty() {
+			return None;
 		}
-		debug!("All jobs have stopped");
+		Some(s.solutions.drain(..).collect())
 	}
 
 	/// #Description
 	///
-	/// Returns an vector of [CuckooMinerDeviceStats](struct.CuckooMinerDeviceStats.html)
-	/// which will contain information about every device currently mining within the plugin.
-	/// In CPU based plugins, this will generally only contain the CPU device, but in plugins
-	/// that access multiple devices (such as cuda) the vector will contain information for
-	/// each currently engaged device. 
+	/// Returns a blocking iterator over the solutions found from now on,
+	/// as they're found, which ends once the job has stopped. The
+	/// iterator is sent its own copy of each solution, so solutions are
+	/// still queued for [get_solution](#method.get_solution), or sent to
+	/// the event channel, as before, and dropping the iterator doesn't
+	/// affect mining.
 	///
 	/// #Returns
 	///
-	/// * Ok([CuckooMinerDeviceStats](struct.CuckooMinerDeviceStats.html)) if successful
-	/// * A [CuckooMinerError](enum.CuckooMinerError.html) with specific detail if an
-	/// error occurred
+	/// The iterator. If the job has already stopped, it's empty.
+	///
+	/// #Example
+	///
+	/// ```no_run
+	///  # use cuckoo_miner::{CuckooMiner, CuckooMinerConfig};
+	///  # let miner = CuckooMiner::new(vec![CuckooMinerConfig::new()]).unwrap();
+	///  # let handle = miner.notify(1, "00", "00", 0, true).unwrap();
+	///  for solution in handle.solutions() {
+	///      println!("Found a solution for nonce {}", solution.nonce);
+	///  }
+	/// ```
+	///
 
-	pub fn get_stats(&self, plugin_index:usize) -> Result<Vec<CuckooMinerDeviceStats>, CuckooMinerError> {
-		let mut stats_bytes: [u8; 4096] = [0; 4096];
-		let mut stats_bytes_len = stats_bytes.len() as u32;
-		// get a list of parameters
-		self.library.read().unwrap()[plugin_index].call_cuckoo_get_stats(
-			&mut stats_bytes,
-			&mut stats_bytes_len,
-		);
-		let mut stats_vec: Vec<u8> = Vec::new();
-		// result contains null zero
-		for i in 0..stats_bytes_len {
-			stats_vec.push(stats_bytes[i as usize].clone());
-		}
-		let stats_json = String::from_utf8(stats_vec)?;
-		//println!("Stats_json: {}", stats_json);
-
-		let result = serde_json::from_str(&stats_json);
-		if let Err(e) = result {
-			return Err(CuckooMinerError::StatsError(
-				String::from(format!("Error retrieving stats from plugin: {:?}", e)),
-			));
-		}
+	pub fn solutions(&self) -> SolutionIter {
+		let (tx, rx) = mpsc::channel();
+		self.subscribe(SolutionSender::Blocking(tx));
+		SolutionIter::new(rx)
+	}
 
-		let mut result:Vec<CuckooMinerDeviceStats> = result.unwrap();
-		let lib_full_path = &self.library.read().unwrap()[plugin_index].lib_full_path;
-		let path_str = Path::new(lib_full_path).file_name().unwrap();
-		let path = Path::new(path_str).file_stem().unwrap();
-		
-		for r in &mut result {
-			r.plugin_name = Some(path.to_str().unwrap().to_owned());
-		}
+	/// #Description
+	///
+	/// As [solutions](#method.solutions), but a stream for async code,
+	/// which works with any executor. Only available with the `async`
+	/// feature.
+	///
+	/// #Returns
+	///
+	/// The [stream](struct.SolutionStream.html), which ends once the job
+	/// has stopped, or straight away if it already has. Dropping it
+	/// leaves the job mining.
+	///
 
-		Ok(result)
+	#[cfg(feature = "async")]
+	pub fn solution_stream(&self) -> SolutionStream {
+		let (tx, rx) = unbounded();
+		self.subscribe(SolutionSender::Async(tx));
+		SolutionStream::new(rx, None)
 	}
-}
 
-/// An instance of a miner, which loads a cuckoo-miner plugin
-/// and calls its mine function according to the provided configuration
+	// Adds an iterator or stream over the job's solutions, or if the job
+	// has ended, drops its sender, so it ends straight away
 
-pub struct CuckooMiner {
-	/// The internal Configuration objects, one for each loaded plugin
-	pub configs: Vec<CuckooMinerConfig>,
+	fn subscribe(&self, sender: SolutionSender) {
+		let mut s = self.shared_data.write().unwrap();
+		if !s.finished {
+			s.subscribers.push(sender);
+		}
+	}
+
+	/// #Description
+	///
+	/// Switches the running job to a new header, e.g. when a new block
+	/// arrives. Queued work for the previous job is discarded, and any of
+	/// its solutions found from now on, or not yet read, are dropped, so
+	/// only solutions for the new job are returned by
+	/// [get_solution](#method.get_solution).
+	///
+	/// #Arguments
+	///
+	/// * `job_id` (IN) An id for the new job, which must differ from the
+	/// current job's. Returned solutions carry it in their `id`.
+	/// * `pre_nonce` (IN) The part of the header which comes before the
+	/// nonce, as a hex string slice
+	/// * `post_nonce` (IN) The part of the header which comes after the
+	/// nonce, as a hex string slice
+	/// * `difficulty` (IN) Only solutions of at least this difficulty are
+	/// returned
+	///
+	/// #Returns
+	///
+	/// * Ok(()) if the job was switched
+	/// * An InvalidHex error if either part of the header isn't valid
+	/// hex, as for [from_hex](fn.from_hex.html)
+	/// * A [CuckooMinerError](enum.CuckooMinerError.html)::ProcessingStopped
+	/// if [stop_jobs](#method.stop_jobs) has been called, even if the
+	/// stop hasn't completed yet. A stopped job can't be restarted, so
+	/// start a new one with [CuckooMiner::notify](struct.CuckooMiner.html#method.notify)
+	/// once the stop has completed.
+
+	pub fn notify(&self, job_id: u32, pre_nonce: &str, post_nonce: &str, difficulty: u64) -> Result<(), CuckooMinerError> {
+		let hash_header = self.shared_data.read().unwrap().hash_header;
+		self.switch_job(
+			job_id,
+			&from_hex(pre_nonce)?,
+			&from_hex(post_nonce)?,
+			difficulty,
+			hash_header,
+			None,
+		)
+	}
+
+	/// #Description
+	///
+	/// As [notify](#method.notify), but takes the parts of the header as
+	/// bytes, of any length. From now on each header is reduced to the 32
+	/// bytes the plugins accept by [header_hash](fn.header_hash.html),
+	/// which documents how the nonce is spliced in.
+	///
+
+	pub fn notify_header(
+		&self,
+		job_id: u32,
+		header_pre: &[u8],
+		header_post: &[u8],
+		difficulty: u64,
+	) -> Result<(), CuckooMinerError> {
+		self.switch_job(job_id, header_pre, header_post, difficulty, true, None)
+	}
+
+	/// #Description
+	///
+	/// Switches the running job to a [MiningJob](struct.MiningJob.html),
+	/// as [notify_header](#method.notify_header) does. If the job sets a
+	/// start nonce, nonce range or limit, the plugins start over on the
+	/// nonces it gives them, as
+	/// [MiningJob::nonce_generators](struct.MiningJob.html#method.nonce_generators)
+	/// shares them out. Otherwise they carry on through their ranges.
+	///
+	/// #Arguments
+	///
+	/// * `job` (IN) The new job, whose id must differ from the current
+	/// job's
+	///
+	/// #Returns
+	///
+	/// As [notify](#method.notify)
+	///
+
+	pub fn notify_job(&self, job: MiningJob) -> Result<(), CuckooMinerError> {
+		let nonce_job = match job.sets_nonces() {
+			true => Some(job.clone()),
+			false => None,
+		};
+		self.switch_job(job.job_id, &job.header_pre, &job.header_post, job.difficulty, true, nonce_job)
+	}
+
+	// Switches to a new job, which, if given, sets the nonces the plugins
+	// mine for it
+	fn switch_job(
+		&self,
+		job_id: u32,
+		pre_nonce: &[u8],
+		post_nonce: &[u8],
+		difficulty: u64,
+		hash_header: bool,
+		nonce_job: Option<MiningJob>,
+	) -> Result<(), CuckooMinerError> {
+		// held across the update, so a stop can't slip in between
+		let c = self.control_data.read().unwrap();
+		if c.stop_flag {
+			return Err(CuckooMinerError::ProcessingStopped(
+				String::from("Job has been stopped"),
+			));
+		}
+		let mut s = self.shared_data.write().unwrap();
+		s.job_id = job_id;
+		s.pre_nonce = pre_nonce.to_vec();
+		s.post_nonce = post_nonce.to_vec();
+		s.hash_header = hash_header;
+		s.difficulty = difficulty;
+		s.nonce_job = nonce_job;
+		s.solutions.retain(|sol| sol.id == job_id);
+		Ok(())
+	}
+
+	/// #Description
+	///
+	/// Stops the current job, and signals for the loaded plugin to stop
+	/// processing and perform any cleanup it needs to do. Blocks until
+	/// the jobs have completed, or for at most the miner's
+	/// [stop timeout](struct.CuckooMiner.html#method.set_stop_timeout),
+	/// after which plugins that haven't stopped are force unloaded
+	///
+	/// #Returns
+	///
+	/// Nothing
+
+	pub fn stop_jobs(&self) {
+		debug!("Stop jobs called");
+		{ 
+			let mut r = self.control_data.write().unwrap();
+			r.stop_flag = true;
+		}
+		debug!("Stop jobs flag set");
+		self.wait_for_stop();
+		debug!("All jobs have stopped");
+	}
+
+	// Waits for the job loop to finish stopping
+	fn wait_for_stop(&self) {
+		loop {
+			{
+				let r = self.control_data.read().unwrap();
+				if r.has_stopped {
+					break;
+				}
+			}
+			thread::sleep(time::Duration::from_millis(5));
+		}
+	}
+
+	/// #Description
+	///
+	/// Shuts the job down for good, as [shutdown_plugins](fn.shutdown_plugins.html)
+	/// does: stop is sent to every plugin at once, plugins that haven't
+	/// stopped by the deadline are force unloaded, and then every plugin
+	/// is unloaded. Safe to call more than once, or after
+	/// [stop_jobs](#method.stop_jobs).
+	///
+	/// To shut down from a signal handler, which mustn't lock or block,
+	/// set the [shutdown flag](#method.shutdown_flag) instead.
+	///
+	/// #Arguments
+	///
+	/// * `deadline` (IN) How long plugins are given to stop processing
+	///
+	/// #Returns
+	///
+	/// The job's solutions which haven't been collected yet, including
+	/// any found while stopping. Solutions sent to an event channel
+	/// aren't kept for collection.
+	///
+
+	pub fn shutdown(&self, deadline: time::Duration) -> Vec<CuckooMinerSolution> {
+		debug!("Shutting down, giving plugins {:?} to stop", deadline);
+		self.control_data.write().unwrap().shutdown_deadline = Some(deadline);
+		self.shutdown_flag.store(true, Ordering::SeqCst);
+		self.wait_for_stop();
+		// the job loop may have stopped earlier without unloading
+		for l in self.library.read().unwrap().iter() {
+			l.unload();
+		}
+		self.get_solutions().unwrap_or_default()
+	}
+
+	/// #Description
+	///
+	/// Returns the job's shutdown flag. Setting it only stores to an
+	/// atomic, so is safe from a signal handler. The job's own thread then
+	/// shuts down as [shutdown](#method.shutdown) would, with the miner's
+	/// [shutdown deadline](struct.CuckooMiner.html#method.set_shutdown_deadline).
+	/// [stop_jobs](#method.stop_jobs) or [shutdown](#method.shutdown)
+	/// can be used to wait until it's done.
+
+	pub fn shutdown_flag(&self) -> Arc<AtomicBool> {
+		self.shutdown_flag.clone()
+	}
+
+	/// #Description
+	///
+	/// Returns the graphs per second across all plugins and devices,
+	/// averaged over the stats window (60 seconds by default)
+	///
+	/// #Returns
+	///
+	/// The average graphs per second, or 0.0 if no stats have been
+	/// sampled yet
+
+	pub fn graphs_per_second(&self) -> f64 {
+		self.shared_data.read().unwrap().stats.graphs_per_second()
+	}
+
+	/// #Description
+	///
+	/// Returns the graphs per second across all plugins and devices as
+	/// 1-minute, 5-minute and session moving averages, as kept by the
+	/// job's [StatsCollector](struct.StatsCollector.html#method.hashrate)
+	///
+	/// #Returns
+	///
+	/// The averages, all 0.0 if no stats have been sampled yet
+	///
+
+	pub fn hashrate(&self) -> Hashrate {
+		self.shared_data.read().unwrap().stats.hashrate()
+	}
+
+	/// Returns the state of each device, with its moving averages, as of
+	/// the last stats sample, ordered by plugin index then device id
+
+	pub fn device_snapshots(&self) -> Vec<DeviceSnapshot> {
+		self.shared_data.read().unwrap().stats.device_snapshots()
+	}
+
+	/// #Description
+	///
+	/// Returns the total number of solutions found by this job that met
+	/// the target difficulty
+
+	pub fn solutions_found(&self) -> u64 {
+		self.shared_data.read().unwrap().stats.solutions_found()
+	}
+
+	/// #Description
+	///
+	/// Renders the job's stats in the Prometheus text exposition format,
+	/// as described by [render_metrics](fn.render_metrics.html). The
+	/// figures are those of the job loop's most recent stats sample, so
+	/// the plugins aren't called, and mining isn't held up.
+
+	#[cfg(feature = "metrics")]
+	pub fn metrics_text(&self) -> String {
+		let names = self.plugin_names();
+		render_metrics(&self.shared_data.read().unwrap().stats, &names)
+	}
+
+	/// #Description
+	///
+	/// Serves [metrics_text](#method.metrics_text) over HTTP on its own
+	/// thread, for Prometheus to scrape from
+	/// [METRICS_PATH](constant.METRICS_PATH.html). The listener keeps
+	/// serving after the job stops, until the returned server is dropped.
+	///
+	/// #Arguments
+	///
+	/// * `addr` (IN) The address to listen on, e.g. "0.0.0.0:9090"
+	///
+	/// #Returns
+	///
+	/// * `Ok(MetricsServer)` once listening
+	/// * a PluginIOError if the address can't be bound
+	///
+	/// #Example
+	///
+	/// ```no_run
+	///  # use cuckoo_miner::{CuckooMiner, CuckooMinerConfig};
+	///  let mut config = CuckooMinerConfig::new();
+	///  config.plugin_full_path = String::from("mean_cpu_30");
+	///  let miner = CuckooMiner::new(vec![config]).unwrap();
+	///  let handle = miner.notify(1, "00", "00", 0, true).unwrap();
+	///  let _server = handle.serve_metrics("0.0.0.0:9090").unwrap();
+	/// ```
+	///
+
+	#[cfg(feature = "metrics")]
+	pub fn serve_metrics<A: ToSocketAddrs>(&self, addr: A) -> Result<MetricsServer, CuckooMinerError> {
+		let shared_data = self.shared_data.clone();
+		let names = self.plugin_names();
+		serve_metrics(addr, move || render_metrics(&shared_data.read().unwrap().stats, &names))
+	}
+
+	/// #Description
+	///
+	/// Starts a JSON-RPC server on its own thread for controlling this
+	/// job, as described by [serve_control](fn.serve_control.html)
+	///
+	/// #Arguments
+	///
+	/// * `addr` (IN) The address to listen on, e.g. "127.0.0.1:9091"
+	/// * `token` (IN) The token clients must send as
+	/// `Authorization: Bearer <token>`
+	///
+	/// #Returns
+	///
+	/// * `Ok(ControlServer)` once listening
+	/// * a ConfigError if the token is empty
+	/// * a PluginIOError if the address can't be bound
+	///
+
+	#[cfg(feature = "control")]
+	pub fn serve_control<A: ToSocketAddrs>(&self, addr: A, token: &str) -> Result<ControlServer, CuckooMinerError> {
+		serve_control(self.clone(), addr, token)
+	}
+
+	/// #Description
+	///
+	/// Returns the name of each plugin, by plugin index, which is its
+	/// configured file name without the extension
+
+	pub fn plugin_names(&self) -> Vec<String> {
+		self.configs
+			.iter()
+			.map(|c| {
+				Path::new(&c.plugin_full_path)
+					.file_stem()
+					.map(|s| s.to_string_lossy().into_owned())
+					.unwrap_or_default()
+			})
+			.collect()
+	}
+
+	/// #Description
+	///
+	/// Returns the last nonce queued for the plugin at `plugin_index`,
+	/// showing how far through its nonce range it has got
+	///
+	/// #Returns
+	///
+	/// The last nonce queued, or None if nothing has been queued for the
+	/// plugin yet
+
+	pub fn nonce_position(&self, plugin_index: usize) -> Option<u64> {
+		self.shared_data.read().unwrap().stats.nonce_position(plugin_index)
+	}
+
+	/// #Description
+	///
+	/// Changes the share of the time the job's plugins spend on graphs,
+	/// as [CuckooMiner::set_duty_cycle](struct.CuckooMiner.html#method.set_duty_cycle),
+	/// while the job runs. The change applies from the next graph each
+	/// plugin starts.
+	///
+	/// #Arguments
+	///
+	/// * `percent` (IN) The duty cycle, from 1 to 100
+	///
+	/// #Returns
+	///
+	/// * `Ok(())` if the duty cycle was changed
+	/// * A ParameterError if it's outside 1 to 100
+	///
+
+	pub fn set_duty_cycle(&self, percent: u8) -> Result<(), CuckooMinerError> {
+		check_duty_cycle(percent)?;
+		self.duty_cycle.store(percent as usize, Ordering::Relaxed);
+		Ok(())
+	}
+
+	/// #Description
+	///
+	/// Returns the share of the stats window the plugin at
+	/// `plugin_index` spent on graphs, as of its last stats sample, so
+	/// the effect of the duty cycle can be seen. Throttling works a graph
+	/// at a time, so this only settles near the duty cycle once a few
+	/// graphs have been searched.
+	///
+	/// #Returns
+	///
+	/// The duty cycle achieved, as a percentage, or None if the plugin
+	/// isn't throttled, i.e. the duty cycle is 100
+
+	pub fn duty_cycle(&self, plugin_index: usize) -> Option<f64> {
+		self.shared_data.read().unwrap().stats.duty_cycle(plugin_index)
+	}
+
+	/// #Description
+	///
+	/// Returns how full the queues of the plugin at `plugin_index` were
+	/// as of its last stats sample, as read with
+	/// [PluginLibrary::queue_depths](struct.PluginLibrary.html#method.queue_depths).
+	/// When solutions stop coming, an empty input queue points to the
+	/// plugin not being fed fast enough, and a full output queue to its
+	/// solutions not being read.
+	///
+	/// #Returns
+	///
+	/// The headers waiting in the input queue and the solutions waiting
+	/// in the output queue, or None if the plugin hasn't been sampled
+	///
+
+	pub fn queue_depths(&self, plugin_index: usize) -> Option<(u32, u32)> {
+		self.shared_data.read().unwrap().stats.queue_depths(plugin_index)
+	}
+
+	/// #Description
+	///
+	/// Returns the total number of graphs this job has found with a
+	/// cycle, including solutions which didn't meet the target difficulty
+	/// and so weren't returned
+
+	pub fn cycles_found(&self) -> u64 {
+		self.shared_data.read().unwrap().stats.cycles_found()
+	}
+
+	/// #Description
+	///
+	/// Returns the total number of repeated solutions this job has
+	/// dropped
+
+	pub fn duplicates_suppressed(&self) -> u64 {
+		self.shared_data.read().unwrap().stats.duplicates_suppressed()
+	}
+
+	/// #Description
+	///
+	/// Sets the window over which graphs per second are averaged
+	///
+	/// #Arguments
+	///
+	/// * `window` (IN) The new averaging window
+
+	pub fn set_stats_window(&self, window: time::Duration) {
+		self.shared_data.write().unwrap().stats.set_window(window);
+	}
+
+	/// #Description
+	///
+	/// Returns an vector of [CuckooMinerDeviceStats](struct.CuckooMinerDeviceStats.html)
+	/// which will contain information about every device currently mining within the plugin.
+	/// In CPU based plugins, this will generally only contain the CPU device, but in plugins
+	/// that access multiple devices (such as cuda) the vector will contain information for
+	/// each currently engaged device. 
+	///
+	/// #Returns
+	///
+	/// * Ok([CuckooMinerDeviceStats](struct.CuckooMinerDeviceStats.html)) if successful
+	/// * A [CuckooMinerError](enum.CuckooMinerError.html) with specific detail if an
+	/// error occurred
+
+	pub fn get_stats(&self, plugin_index:usize) -> Result<Vec<CuckooMinerDeviceStats>, CuckooMinerError> {
+		let library = &self.library.read().unwrap()[plugin_index];
+		let mut result = library.stats()?;
+		let path_str = Path::new(&library.lib_full_path).file_name().unwrap();
+		let path = Path::new(path_str).file_stem().unwrap();
+		
+		for r in &mut result {
+			r.plugin_name = Some(path.to_str().unwrap().to_owned());
+		}
+
+		Ok(result)
+	}
+
+	/// #Description
+	///
+	/// Returns the stats of every loaded plugin, grouped by plugin, as
+	/// returned by [get_stats](#method.get_stats) for each. A plugin
+	/// whose stats can't be read doesn't prevent the others' being
+	/// returned.
+	///
+	/// #Returns
+	///
+	/// A vector with an entry for each plugin, in the order they were
+	/// configured, holding either its
+	/// [CuckooMinerDeviceStats](struct.CuckooMinerDeviceStats.html) or the
+	/// [CuckooMinerError](enum.CuckooMinerError.html) reading them
+
+	pub fn get_all_stats(&self) -> Vec<Result<Vec<CuckooMinerDeviceStats>, CuckooMinerError>> {
+		let count = self.library.read().unwrap().len();
+		(0..count).map(|i| self.get_stats(i)).collect()
+	}
+
+	/// #Description
+	///
+	/// Returns the memory the job's plugins report using, host and device
+	/// together, so the plugin responsible for running a machine out of
+	/// memory can be found. Each plugin's host memory is counted once,
+	/// however many devices report it.
+	///
+	/// #Returns
+	///
+	/// The total in bytes, or None if no plugin reports its memory.
+	/// Plugins whose stats can't be read aren't counted.
+
+	pub fn total_memory_bytes(&self) -> Option<u64> {
+		total_memory_bytes(self.get_all_stats().into_iter().filter_map(|s| s.ok()))
+	}
+
+	/// #Description
+	///
+	/// Enables or disables a single device of a plugin while the job
+	/// runs, without stopping the plugin. The change takes effect from
+	/// the device's next graph attempt. See
+	/// [PluginLibrary::set_device_enabled](struct.PluginLibrary.html#method.set_device_enabled).
+	///
+	/// #Arguments
+	///
+	/// * `plugin_index` (IN) The index of the plugin, in the order they
+	/// were configured
+	/// * `device_id` (IN) The device, as reported by
+	/// [list_devices](struct.PluginLibrary.html#method.list_devices)
+	/// * `enabled` (IN) Whether the device should mine
+	///
+
+	pub fn set_device_enabled(&self, plugin_index: usize, device_id: u32, enabled: bool) -> Result<(), CuckooMinerError> {
+		let _control = self.control_lock.lock().unwrap();
+		self.library.read().unwrap()[plugin_index].set_device_enabled(device_id, enabled)
+	}
+
+	/// #Description
+	///
+	/// Sets a parameter of a plugin while the job runs, e.g. to retune
+	/// it, as [PluginLibrary::set_parameter_for_device](struct.PluginLibrary.html#method.set_parameter_for_device)
+	/// does. The change waits for any job switch or plugin reload in
+	/// progress, so it's never lost to one half done. A reload restores
+	/// the plugin's configured parameters.
+	///
+	/// #Arguments
+	///
+	/// * `plugin_index` (IN) The index of the plugin, in the order they
+	/// were configured
+	/// * `name` (IN) The name of the parameter
+	/// * `device_id` (IN) The device to set it on, or
+	/// [ALL_DEVICES](constant.ALL_DEVICES.html)
+	/// * `value` (IN) The value to set
+	///
+	/// #Returns
+	///
+	/// * `Ok(())` if the parameter was set
+	/// * PluginNotFoundError if there's no plugin at `plugin_index`
+	/// * another [CuckooMinerError](enum.CuckooMinerError.html) if the
+	/// plugin rejected the value
+	///
+
+	pub fn set_parameter(&self, plugin_index: usize, name: &str, device_id: u32, value: u32) -> Result<(), CuckooMinerError> {
+		let _control = self.control_lock.lock().unwrap();
+		match self.library.read().unwrap().get(plugin_index) {
+			Some(l) => l.set_parameter_for_device(name, device_id, value),
+			None => Err(CuckooMinerError::PluginNotFoundError(format!("No plugin at index {}", plugin_index))),
+		}
+	}
+
+	/// #Description
+	///
+	/// Reloads a plugin from its file, e.g. after it's been rebuilt,
+	/// without stopping the job. The plugin is told to stop, and once it
+	/// has, its remaining solutions are read and it's unloaded. The file
+	/// is then loaded again, retrying briefly if it's being replaced at
+	/// the time, its config's parameters applied and the same devices
+	/// disabled, and it's restarted on the current job. The other plugins
+	/// carry on mining throughout. Blocks until the reload has finished.
+	///
+	/// #Arguments
+	///
+	/// * `name` (IN) The plugin's configured path, or its file name
+	/// without the extension. Every instance of the plugin is reloaded.
+	///
+	/// #Returns
+	///
+	/// * `Ok(())` once the plugin is mining again
+	/// * PluginNotFoundError if no plugin has the name
+	/// * PluginReloadFailed if the file couldn't be loaded, in which case
+	/// it's treated as a plugin failure, as set by
+	/// [set_failure_policy](struct.CuckooMiner.html#method.set_failure_policy)
+	/// * ProcessingStopped if the job stops before the reload finishes
+	/// * another [CuckooMinerError](enum.CuckooMinerError.html) if the
+	/// reloaded plugin couldn't be set up or started
+	///
+
+	pub fn reload_plugin(&self, name: &str) -> Result<(), CuckooMinerError> {
+		let indices = plugin_indices(&self.configs, name);
+		if indices.is_empty() {
+			return Err(CuckooMinerError::PluginNotFoundError(String::from(name)));
+		}
+		self.reload_indices(&indices, name, Vec::new())?.into_iter().collect()
+	}
+
+	/// #Description
+	///
+	/// Swaps a plugin for another without stopping the job, e.g. to move
+	/// from a CPU plugin to a GPU plugin once a GPU frees up. The old
+	/// plugin is told to stop, and once it has, its remaining solutions
+	/// are read and it's unloaded, as
+	/// [reload_plugin](#method.reload_plugin) does. The replacement is
+	/// then loaded from its config, its parameters applied and the same
+	/// devices disabled, and it's started on the current job, carrying on
+	/// through the nonces the old plugin was mining. The other plugins
+	/// carry on mining throughout. Blocks until the swap has finished.
+	///
+	/// #Arguments
+	///
+	/// * `old` (IN) The configured path of the plugin to swap out, or its
+	/// file name without the extension. Every instance of the plugin is
+	/// swapped.
+	/// * `new` (IN) The config to load the replacement from. Its nonce
+	/// range is ignored, as the replacement takes over the old plugin's.
+	///
+	/// #Returns
+	///
+	/// * `Ok(())` once the replacement is mining, after which it's in
+	/// `configs` in place of the old plugin
+	/// * PluginNotFoundError if no plugin has the name
+	/// * PluginReloadFailed if the replacement couldn't be loaded, in
+	/// which case it's treated as a failure of the old plugin, which is
+	/// what's reloaded if it's retried
+	/// * ProcessingStopped if the job stops before the swap finishes
+	/// * another [CuckooMinerError](enum.CuckooMinerError.html) if the
+	/// replacement couldn't be set up or started
+	///
+
+	pub fn swap_plugin(&mut self, old: &str, new: CuckooMinerConfig) -> Result<(), CuckooMinerError> {
+		let indices = plugin_indices(&self.configs, old);
+		if indices.is_empty() {
+			return Err(CuckooMinerError::PluginNotFoundError(String::from(old)));
+		}
+		let mut configs = self.configs.clone();
+		for i in &indices {
+			configs[*i] = new.clone();
+		}
+		let instances = plugin_instances(&configs);
+		let swaps = indices.iter().map(|i| (*i, (new.clone(), instances[*i]))).collect();
+		let results = self.reload_indices(&indices, old, swaps)?;
+		for (i, r) in indices.iter().zip(&results) {
+			if r.is_ok() {
+				self.configs[*i] = new.clone();
+			}
+		}
+		results.into_iter().collect()
+	}
+
+	// Asks the job loop to reload plugins, swapping in any replacements
+	// given, and waits for the outcome of each
+	fn reload_indices(
+		&self,
+		indices: &[usize],
+		name: &str,
+		swaps: Vec<(usize, (CuckooMinerConfig, usize))>,
+	) -> Result<Vec<Result<(), CuckooMinerError>>, CuckooMinerError> {
+		{
+			let mut c = self.control_data.write().unwrap();
+			c.swaps.extend(swaps);
+			for i in indices {
+				c.reload_results.remove(i);
+				c.reload_requests.push(*i);
+			}
+		}
+		loop {
+			thread::sleep(time::Duration::from_millis(10));
+			let mut c = self.control_data.write().unwrap();
+			if indices.iter().all(|i| c.reload_results.contains_key(i)) {
+				return Ok(indices.iter().filter_map(|i| c.reload_results.remove(i)).collect());
+			}
+			if c.has_stopped {
+				for i in indices {
+					c.swaps.remove(i);
+				}
+				return Err(CuckooMinerError::ProcessingStopped(format!(
+					"The job stopped before {} was reloaded",
+					name
+				)));
+			}
+		}
+	}
+}
+
+/// An instance of a miner, which loads a cuckoo-miner plugin
+/// and calls its mine function according to the provided configuration
+
+pub struct CuckooMiner {
+	/// The internal Configuration objects, one for each loaded plugin
+	pub configs: Vec<CuckooMinerConfig>,
+
+	/// Delegator object, used when spawning a processing thread
+	delegator: Option<Delegator>,
+
+	/// Loaded plugin
+	libraries: Vec<PluginLibrary>,
+
+	/// How the asynchronous job is run
+	options: JobOptions,
+
+	/// Checked between attempts by mine
+	stop_flag: Arc<AtomicBool>,
+
+	/// The nonce the job's nonce ranges start from, random if None
+	start_nonce: Option<u64>,
+
+	/// The extranonce prefix and its size in bits, if any
+	extranonce: Option<(u64, u32)>,
+}
+
+impl CuckooMiner {
+	/// #Description
+	///
+	/// Creates a new instance of a CuckooMiner with the given configuration.
+	///
+	/// #Arguments
+	///
+	/// * `configs` an vector of
+	/// [CuckooMinerConfigs](struct.CuckooMinerConfig.html), one for each plugin
+	/// that is to be loaded and run, and each of which contains
+	/// the full path name of a valid mining plugin. Each config struct may
+	/// also contain values in its `parameter_list` field, which will be automatically set
+	/// in the specified plugin. 
+	///
+	/// #Returns
+	///
+	/// * `Ok()` if successful, and the specified plugin has been loaded internally.
+	/// * Otherwise a [CuckooMinerError](enum.CuckooMinerError.html)
+	/// with specific detail
+
+	pub fn new(configs: Vec<CuckooMinerConfig>) -> Result<CuckooMiner, CuckooMinerError> {
+		CuckooMiner::init(configs)
+	}
+
+	/// Internal function to perform tha actual library loading
+
+	fn init(configs: Vec<CuckooMinerConfig>) -> Result<CuckooMiner, CuckooMinerError> {
+		check_nonce_ranges(&configs)?;
+		let mut lib_vec=Vec::new();
+		for (c, instance) in configs.iter().zip(plugin_instances(&configs)) {
+			lib_vec.push(load_plugin(c, instance)?);
+		}
+
+		let ret_val=CuckooMiner {
+			configs : configs.clone(),
+			delegator : None,
+			libraries : lib_vec,
+			options : JobOptions::default(),
+			stop_flag : Arc::new(AtomicBool::new(false)),
+			start_nonce : None,
+			extranonce : None,
+		};
+
+		Ok(ret_val)
+	}
+
+	/// #Description
+	///
+	/// Sets whether solutions found in asynchronous mode are verified
+	/// against their header before they're reported, which they are by
+	/// default. Solutions failing verification are logged and dropped.
+	/// Verification only needs 84 siphashes per solution, but can be
+	/// turned off when benchmarking.
+	///
+	/// #Arguments
+	///
+	/// * `verify_solutions` Whether to verify solutions
+	///
+
+	pub fn set_verify_solutions(&mut self, verify_solutions: bool) {
+		self.options.verify_solutions = verify_solutions;
+	}
+
+	/// #Description
+	///
+	/// Sets a channel to which the asynchronous job started by
+	/// [notify](#method.notify) sends [MinerEvents](enum.MinerEvent.html)
+	/// as they happen, so the caller doesn't need to poll the job handle.
+	///
+	/// Solutions sent to the channel aren't put into the job handle's
+	/// queue. If the receiver has been dropped, solutions go back to the
+	/// job handle's queue, so none are lost, while other events are
+	/// discarded. Sending never blocks the job.
+	///
+	/// #Arguments
+	///
+	/// * `sender` (IN) The sending end of the channel
+	///
+
+	pub fn set_event_channel(&mut self, sender: Sender<MinerEvent>) {
+		self.options.events = Some(sender);
+	}
+
+	/// #Description
+	///
+	/// Sets how headers are hashed, with their nonces, before they're
+	/// given to the plugins, by [mine](#method.mine) and by jobs whose
+	/// headers are hashed. [Blake2bHasher](struct.Blake2bHasher.html) by
+	/// default. Solutions are verified against, and carry, the hashes it
+	/// gives.
+	///
+	/// #Arguments
+	///
+	/// * `hasher` (IN) The hasher
+	///
+
+	pub fn set_header_hasher<H: HeaderHasher + 'static>(&mut self, hasher: H) {
+		self.options.hasher = Arc::new(hasher);
+	}
+
+	/// #Description
+	///
+	/// Sets the nonce which each plugin's
+	/// [NonceRange](struct.NonceRange.html) is offset from in asynchronous
+	/// mode. By default a random one is picked, so restarted jobs don't
+	/// repeat earlier work. Nonces wrap around at u64::MAX.
+	///
+	/// #Arguments
+	///
+	/// * `start_nonce` (IN) The starting nonce
+	///
+
+	pub fn set_start_nonce(&mut self, start_nonce: u64) {
+		self.start_nonce = Some(start_nonce);
+	}
+
+	/// #Description
+	///
+	/// Fixes the top bits of every nonce mined in asynchronous mode, e.g.
+	/// to an extranonce assigned to this worker by a pool, so that rigs
+	/// mining for the same worker never share a nonce. The nonce ranges
+	/// then wrap around within the remaining bits.
+	///
+	/// #Arguments
+	///
+	/// * `prefix` (IN) The value of the fixed bits
+	/// * `prefix_bits` (IN) How many of the top bits are fixed, which must
+	/// be less than 64
+	///
+	/// #Returns
+	///
+	/// * Ok(()) if the prefix was set
+	/// * A [CuckooMinerError](enum.CuckooMinerError.html)::ParameterError
+	/// if `prefix` doesn't fit in `prefix_bits` bits
+	///
+
+	pub fn set_extranonce(&mut self, prefix: u64, prefix_bits: u32) -> Result<(), CuckooMinerError> {
+		NonceGenerator::new(0, NonceRange::default()).with_prefix(prefix, prefix_bits)?;
+		self.extranonce = Some((prefix, prefix_bits));
+		Ok(())
+	}
+
+	/// #Description
+	///
+	/// Sets how many recent solutions the asynchronous job remembers in
+	/// order to drop exact repeats, i.e. the same proof for the same
+	/// nonce, which some plugins occasionally report. Defaults to
+	/// [DEFAULT_DEDUP_WINDOW](constant.DEFAULT_DEDUP_WINDOW.html). The
+	/// remembered solutions are forgotten when the job is switched.
+	///
+	/// #Arguments
+	///
+	/// * `window` (IN) How many solutions to remember, or 0 to turn
+	/// repeat checking off
+	///
+
+	pub fn set_dedup_window(&mut self, window: usize) {
+		self.options.dedup_window = window;
+	}
+
+	/// #Description
+	///
+	/// Enables a watchdog on the asynchronous job, which gives up on any
+	/// plugin whose stats show no new graph attempts for `timeout`, e.g.
+	/// because one of its internal threads has hung. The plugin is
+	/// reported with a [MinerEvent::PluginError](enum.MinerEvent.html),
+	/// told to stop and unloaded, while the other plugins carry on. If it
+	/// doesn't stop within the [stop timeout](#method.set_stop_timeout),
+	/// it's force unloaded. It's then restarted or blacklisted, as set by
+	/// [set_failure_policy](#method.set_failure_policy). The watchdog is
+	/// off by default.
+	///
+	/// #Arguments
+	///
+	/// * `timeout` (IN) How long a plugin may go without progress, which
+	/// should comfortably exceed the time its slowest device takes to
+	/// attempt a single graph, or None to turn the watchdog off
+	///
+
+	pub fn set_watchdog_timeout(&mut self, timeout: Option<time::Duration>) {
+		self.options.watchdog_timeout = timeout;
+	}
+
+	/// #Description
+	///
+	/// Sets how long a plugin in the asynchronous job may go without
+	/// finishing a graph or finding a solution before a warning is logged,
+	/// giving the depths of its input and output queues, so a plugin
+	/// starved of headers can be told from one whose solutions aren't
+	/// being read. The warning is repeated after each further interval
+	/// without progress. Nothing else is done about it; see
+	/// [set_watchdog_timeout](#method.set_watchdog_timeout) for giving up
+	/// on hung plugins. Defaults to
+	/// [DEFAULT_STALL_WARNING](constant.DEFAULT_STALL_WARNING.html).
+	///
+	/// #Arguments
+	///
+	/// * `interval` (IN) How long a plugin may go without progress, or
+	/// None to never warn
+	///
+
+	pub fn set_stall_warning(&mut self, interval: Option<time::Duration>) {
+		self.options.stall_warning = interval;
+	}
+
+	/// #Description
+	///
+	/// Sets the temperature above which the asynchronous job pauses a
+	/// device, as reported in its stats. The device is reported with a
+	/// [MinerEvent::PluginError](enum.MinerEvent.html) and stops mining
+	/// until it cools
+	/// [DEFAULT_TEMPERATURE_HYSTERESIS](constant.DEFAULT_TEMPERATURE_HYSTERESIS.html)
+	/// degrees below the maximum. Devices which don't report their
+	/// temperature are never paused. Off by default.
+	///
+	/// #Arguments
+	///
+	/// * `max_temperature` (IN) The maximum temperature in degrees
+	/// Celsius, or None to never pause devices
+	///
+
+	pub fn set_max_temperature(&mut self, max_temperature: Option<f32>) {
+		self.options.max_temperature = max_temperature;
+	}
+
+	/// #Description
+	///
+	/// Sets how the asynchronous job deals with plugins which keep
+	/// failing, e.g. failing to start processing, crashing a device,
+	/// hanging or timing out on shutdown. A failed plugin is reloaded and
+	/// restarted, until it has failed `threshold` times, when it's
+	/// blacklisted and unloaded, and reported with a
+	/// [MinerEvent::PluginBlacklisted](enum.MinerEvent.html). Once
+	/// `cooldown` has passed it's reloaded from disk, picking up a
+	/// replaced plugin file, and retried once. If it fails again within
+	/// `cooldown` it's blacklisted again straight away. The other plugins
+	/// carry on mining throughout. Defaults to
+	/// [DEFAULT_FAILURE_THRESHOLD](constant.DEFAULT_FAILURE_THRESHOLD.html)
+	/// and [DEFAULT_BLACKLIST_COOLDOWN](constant.DEFAULT_BLACKLIST_COOLDOWN.html).
+	///
+	/// #Arguments
+	///
+	/// * `threshold` (IN) The failures after which a plugin is blacklisted
+	/// * `cooldown` (IN) How long a blacklisted plugin is benched
+	///
+
+	pub fn set_failure_policy(&mut self, threshold: u32, cooldown: time::Duration) {
+		self.options.failure_threshold = threshold;
+		self.options.blacklist_cooldown = cooldown;
+	}
+
+	/// #Description
+	///
+	/// Sets how long the asynchronous job waits for a plugin to stop
+	/// processing, when the job is stopped or the plugin is given up on,
+	/// before force unloading it. Defaults to
+	/// [DEFAULT_STOP_TIMEOUT](constant.DEFAULT_STOP_TIMEOUT.html).
+	///
+	/// #Arguments
+	///
+	/// * `timeout` (IN) How long to wait
+	///
+
+	pub fn set_stop_timeout(&mut self, timeout: time::Duration) {
+		self.options.stop_timeout = timeout;
+	}
+
+	/// #Description
+	///
+	/// Sets how long plugins are given to stop when a job is shut down
+	/// through its [shutdown flag](struct.CuckooMinerJobHandle.html#method.shutdown_flag),
+	/// before they're force unloaded. Defaults to
+	/// [DEFAULT_SHUTDOWN_DEADLINE](constant.DEFAULT_SHUTDOWN_DEADLINE.html),
+	/// which CUDA plugins may need more than.
+	///
+	/// #Arguments
+	///
+	/// * `deadline` (IN) How long to wait
+	///
 
-	/// Delegator object, used when spawning a processing thread
-	delegator: Option<Delegator>,
+	pub fn set_shutdown_deadline(&mut self, deadline: time::Duration) {
+		self.options.shutdown_deadline = deadline;
+	}
 
-	/// Loaded plugin
-	libraries: Vec<PluginLibrary>,
-}
+	/// #Description
+	///
+	/// Shuts the miner down, as [shutdown_plugins](fn.shutdown_plugins.html)
+	/// does, after setting the [stop flag](#method.stop_flag) so a
+	/// [mine](#method.mine) running on another thread returns. Safe to
+	/// call more than once. From a signal handler, set the stop flag, and
+	/// shut down once mine has returned.
+	///
+	/// #Arguments
+	///
+	/// * `deadline` (IN) How long plugins are given to stop processing
+	///
+	/// #Returns
+	///
+	/// The solutions left in the plugins' output queues
+	///
+
+	pub fn shutdown(&self, deadline: time::Duration) -> Vec<CuckooMinerSolution> {
+		self.stop_flag.store(true, Ordering::SeqCst);
+		shutdown_plugins(&self.libraries, deadline)
+	}
 
-impl CuckooMiner {
 	/// #Description
 	///
-	/// Creates a new instance of a CuckooMiner with the given configuration.
+	/// Sets how often the asynchronous job samples the plugins' stats,
+	/// for the job handle's rolling figures and the checks made on them,
+	/// such as the watchdog and device temperatures. Defaults to
+	/// [DEFAULT_SAMPLE_INTERVAL](constant.DEFAULT_SAMPLE_INTERVAL.html).
 	///
 	/// #Arguments
 	///
-	/// * `configs` an vector of
-	/// [CuckooMinerConfigs](struct.CuckooMinerConfig.html), one for each plugin
-	/// that is to be loaded and run, and each of which contains
-	/// the full path name of a valid mining plugin. Each config struct may
-	/// also contain values in its `parameter_list` field, which will be automatically set
-	/// in the specified plugin. 
+	/// * `interval` (IN) The minimum time between samples
+	///
+
+	pub fn set_stats_interval(&mut self, interval: time::Duration) {
+		self.options.stats_interval = interval;
+	}
+
+	/// #Description
+	/// Sets the share of the time each plugin spends on graphs in
+	/// asynchronous mode, so a miner can run with only the spare cycles
+	/// of a machine without changing its thread counts, and so its
+	/// memory use. Below 100, each plugin is given one graph per device
+	/// at a time, and left idle after each for long enough to keep to
+	/// the duty cycle. Graphs are only held back from starting, never
+	/// interrupted. Defaults to 100, keeping plugins busy. The job
+	/// handle's [set_duty_cycle](struct.CuckooMinerJobHandle.html#method.set_duty_cycle)
+	/// changes it while the job runs.
+	///
+	/// #Arguments
+	///
+	/// * `percent` (IN) The duty cycle, from 1 to 100
+	///
+	/// #Returns
+	///
+	/// * `Ok(())` if the duty cycle was set
+	/// * A ParameterError if it's outside 1 to 100
+	///
+
+	pub fn set_duty_cycle(&mut self, percent: u8) -> Result<(), CuckooMinerError> {
+		check_duty_cycle(percent)?;
+		self.options.duty_cycle.store(percent as usize, Ordering::Relaxed);
+		Ok(())
+	}
+
+	/// Builds the nonce generator for each plugin, from the configured
+	/// ranges, starting nonce and extranonce
+
+	fn nonce_generators(&self) -> Result<Vec<NonceGenerator>, CuckooMinerError> {
+		let start = match self.start_nonce {
+			Some(n) => n,
+			None => rand::OsRng::new().unwrap().gen(),
+		};
+		let mut generators = Vec::new();
+		for range in nonce_ranges(&self.configs) {
+			let mut g = NonceGenerator::new(start, range);
+			if let Some((prefix, bits)) = self.extranonce {
+				g = g.with_prefix(prefix, bits)?;
+			}
+			generators.push(g);
+		}
+		Ok(generators)
+	}
+
+	/// #Description
+	///
+	/// Sets a parameter in the loaded plugin
+	///
+	/// #Arguments
+	///
+	/// * `name` The name of the parameter to set
+	///
+	/// * `value` The value to set the parameter to
+	///
+	/// #Returns
+	///
+	/// *`Ok()` if successful and the parameter has been set.
+	/// * Otherwise a
+	/// [CuckooMinerError](enum.CuckooMinerError.html)
+	/// with specific detail is returned.
+	///
+
+	pub fn set_parameter(name: String, device_id: u32, value: u32, library:&PluginLibrary) -> Result<(), CuckooMinerError> {
+		let return_code = library.call_cuckoo_set_parameter(
+			name.as_bytes(),
+			device_id,
+			value,
+		);
+		PluginCall::SetParameter.check(
+			return_code,
+			&format!("{} to {} on device {}", name, value, device_id),
+		)
+	}
+
+	/// #Description
+	///
+	/// Returns the miner's stop flag. Setting it makes a running
+	/// [mine](#method.mine) return `Ok(None)`, e.g. when a new block
+	/// arrives. Plugins exporting `cuckoo_call_cancellable` abandon the
+	/// graph they're searching, so mine returns within
+	/// [CANCEL_BOUND](constant.CANCEL_BOUND.html). Other plugins can't be
+	/// interrupted, so mine returns once the current graph is done. The
+	/// flag stays set, so clear it before mining again.
+
+	pub fn stop_flag(&self) -> Arc<AtomicBool> {
+		self.stop_flag.clone()
+	}
+
+	/// #Description
+	///
+	/// Synchronously mines on the first loaded plugin, trying nonces in
+	/// turn from `start_nonce` until a solution is found. For each nonce,
+	/// the header is assembled as `header_pre`, the nonce as 8 bytes in the
+	/// plugin's [NonceEncoding](enum.NonceEncoding.html), then
+	/// `header_post`, and its blake2b-256 hash is passed to
+	/// the plugin. Plugins exporting the context calls keep one
+	/// [SolverContext](struct.SolverContext.html) for the whole run, so
+	/// their solver is only set up once. Unless turned off with
+	/// [set_verify_solutions](#method.set_verify_solutions), solutions are
+	/// verified before they're returned. Setting the
+	/// [stop flag](#method.stop_flag) cancels the attempt in progress,
+	/// where the plugin allows it, and mine returns `Ok(None)`.
+	///
+	/// #Arguments
+	///
+	/// * `header_pre` (IN) The part of the header before the nonce
+	/// * `header_post` (IN) The part of the header after the nonce
+	/// * `start_nonce` (IN) The first nonce to try. Nonces wrap around
+	/// at u64::MAX.
+	/// * `max_nonces` (IN) The most nonces to try, or None to keep trying
+	/// until a solution is found or the stop flag is set
+	///
+	/// #Returns
+	///
+	/// * `Ok(Some(`[CuckooMinerSolution](struct.CuckooMinerSolution.html)`))`
+	/// with the solution and the nonce it was found with
+	/// * `Ok(None)` if no solution was found in the range, or the stop flag
+	/// was set
+	/// * A [CuckooMinerError](enum.CuckooMinerError.html)
+	/// if there is an error calling the plugin
+	///
+	/// #Example
+	///
+	/// ```
+	///  # use cuckoo_miner::{CuckooMiner, CuckooMinerConfig};
+	///  # use std::env;
+	///  # use std::path::PathBuf;
+	///  # let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+	///  # d.push("target/debug/plugins/lean_cpu_16.cuckooplugin");
+	///  let mut config = CuckooMinerConfig::new();
+	///  config.plugin_full_path = String::from(d.to_str().unwrap());
+	///  let miner = CuckooMiner::new(vec![config]).unwrap();
+	///  let header_pre = [0u8; 72];
+	///  let header_post = [0u8; 5];
+	///  if let Some(s) = miner.mine(&header_pre, &header_post, 0, Some(100)).unwrap() {
+	///    println!("Solution found with nonce {}: {}", s.nonce, s);
+	///  }
+	/// ```
+	///
+
+	pub fn mine(
+		&self,
+		header_pre: &[u8],
+		header_post: &[u8],
+		start_nonce: u64,
+		max_nonces: Option<u64>,
+	) -> Result<Option<CuckooMinerSolution>, CuckooMinerError> {
+		let library = match self.libraries.first() {
+			Some(l) => l,
+			None => {
+				return Err(CuckooMinerError::PluginNotLoadedError(
+					String::from("No plugins loaded"),
+				))
+			}
+		};
+		let plugin_name = Path::new(&library.lib_full_path)
+			.file_stem()
+			.map(|s| s.to_string_lossy().into_owned());
+		// one solver for every nonce, where the plugin allows it
+		let context = library.solver_context();
+		let mut attempts = 0;
+		loop {
+			if self.stop_flag.load(Ordering::SeqCst) {
+				debug!("Stop flag set, mining stopped after {} attempts", attempts);
+				return Ok(None);
+			}
+			if let Some(m) = max_nonces {
+				if attempts >= m {
+					return Ok(None);
+				}
+			}
+			let nonce = start_nonce.wrapping_add(attempts);
+			attempts += 1;
+
+			let hash = self.options.hasher.hash(header_pre, &library.nonce_encoding().encode(nonce), header_post);
+
+			let mut solution = CuckooMinerSolution::new();
+			let mut cuckoo_size = 0;
+			let started = time::Instant::now();
+			if !self.mine_hash_in(&hash, &mut cuckoo_size, &mut solution, 0, Some(&context))? {
+				continue;
+			}
+			let elapsed = started.elapsed();
+			solution.cuckoo_size = cuckoo_size;
+			solution.nonce = nonce;
+			solution.plugin_name = plugin_name.clone();
+			solution.header_hash = Some(hash);
+			solution.metadata = Some(SolutionMetadata {
+				graph_time_ms: Some(elapsed.as_secs() * 1000 + (elapsed.subsec_nanos() / 1_000_000) as u64),
+				attempt_index: Some(attempts - 1),
+				..SolutionMetadata::new(plugin_name.clone())
+			});
+			if self.options.verify_solutions && is_verifiable(library.graph_type()) {
+				if let Err(e) = verify_solution(&hash, &solution) {
+					warn!("Dropping invalid solution for nonce {}: {}", nonce, e);
+					continue;
+				}
+			}
+			return Ok(Some(solution));
+		}
+	}
+
+	/// #Description
+	///
+	/// Makes a single synchronous call to the cuckoo_call function of the
+	/// plugin at `plugin_index`, which will perform
+	/// a Cuckoo Cycle on the given seed, filling the first solution (a cycle
+	/// as long as the plugin's proofs, usually 42) that is found in the provided
+	/// [CuckooMinerSolution](struct.CuckooMinerSolution.html) structure.
+	/// The implementation details are dependent on the particular loaded plugin.
+	/// Values provided
+	/// to the loaded plugin are contained in the internal
+	/// [CuckooMinerConfig](struct.CuckooMinerConfig.html). Plugins which
+	/// can be cancelled are called through `cuckoo_call_cancellable`, and
+	/// setting the [stop flag](#method.stop_flag) ends the call early, as
+	/// though no solution was found.
+	///
+	/// #Arguments
+	///
+	/// * `header` (IN) A reference to a block of [u8] bytes to use for the
+	/// seed to the
+	/// internal SIPHASH function which generates edge locations in the
+	/// graph. In practice,
+	/// this is a SHA3 hash of a Grin blockheader, but from the plugin's
+	/// perspective this can be anything. Plugins which accept SipHash keys
+	/// directly are passed the keys derived from it by
+	/// [siphash_keys](fn.siphash_keys.html) instead.
+	///
+	/// * `solution` (OUT) An empty
+	/// [CuckooMinerSolution](struct.CuckooMinerSolution.html).
+	/// If a solution is found, this structure will contain a list of
+	/// solution nonces, otherwise, it will remain untouched but for its
+	/// nonces being resized to the plugin's proof size.
+	///
+	/// #Returns
+	///
+	/// * Ok(true) if a solution is found, with the solution nonces
+	/// contained within
+	/// the provided [CuckooMinerSolution](struct.CuckooMinerSolution.html).
+	/// * Ok(false) if no solution is found and `solution` remains untouched.
+	/// * A [CuckooMinerError](enum.CuckooMinerError.html)
+	/// if there is an error calling the function.
+
+	pub fn mine_hash(
+		&self,
+		header: &[u8],
+		cuckoo_size: &mut u32,
+		solution: &mut CuckooMinerSolution,
+		plugin_index: usize
+	) -> Result<bool, CuckooMinerError> {
+		self.mine_hash_in(header, cuckoo_size, solution, plugin_index, None)
+	}
+
+	// As mine_hash, reusing the plugin's solver context if it has a
+	// persistent one, so a run of calls doesn't set the solver up each time
+
+	fn mine_hash_in(
+		&self,
+		header: &[u8],
+		cuckoo_size: &mut u32,
+		solution: &mut CuckooMinerSolution,
+		plugin_index: usize,
+		context: Option<&SolverContext>,
+	) -> Result<bool, CuckooMinerError> {
+		let library = &self.libraries[plugin_index];
+		solution.solution_nonces.resize(library.proof_size(), 0);
+		if library.has_64_bit_calls() && library.has_wide_edges() {
+			let result = library.call_cuckoo_64(header, cuckoo_size, &mut solution.solution_nonces);
+			return match result {
+				1 => {
+					debug!("Solution found.");
+					Ok(true)
+				}
+				0 => Ok(false),
+				_ => Err(CuckooMinerError::UnexpectedResultError(result)),
+			};
+		}
+		// 32-bit plugins fill a scratch buffer, so a miss leaves
+		// the solution untouched
+		let mut nonces: Vec<u32> = solution.solution_nonces.iter().map(|n| *n as u32).collect();
+		let persistent = context.and_then(|c| if c.is_persistent() { Some(c) } else { None });
+		let result = if let Some(c) = persistent {
+			match c.call_cuckoo(header, cuckoo_size, &mut nonces, Some(&self.stop_flag)) {
+				CALL_CANCELLED => {
+					debug!("Attempt cancelled by the stop flag");
+					0
+				}
+				r => r,
+			}
+		} else if library.has_cancellable_call() {
+			match library.call_cuckoo_cancellable(header, cuckoo_size, &mut nonces, &self.stop_flag) {
+				CALL_CANCELLED => {
+					debug!("Attempt cancelled by the stop flag");
+					0
+				}
+				r => r,
+			}
+		} else if library.has_keyed_call() {
+			let keys = siphash_keys(header);
+			trace!("Mining with keys {:x} {:x} {:x} {:x}", keys[0], keys[1], keys[2], keys[3]);
+			library.call_cuckoo_with_keys(&keys, cuckoo_size, &mut nonces)
+		} else {
+			library.call_cuckoo(header, cuckoo_size, &mut nonces)
+		};
+		match result {
+			1 => {
+				debug!("Solution found.");
+				solution.set_solution(&nonces);
+				Ok(true)
+			}
+			0 => Ok(false),
+			_ => Err(CuckooMinerError::UnexpectedResultError(result)),
+		}
+	}
+
+	/// #Description
+	///
+	/// Benchmarks a loaded plugin over a fixed number of graphs, built
+	/// from headers which count up from a seed, so runs with the same seed
+	/// are comparable. Running the same benchmark in both modes shows the
+	/// overhead of the queue. Synchronous runs reuse one solver context
+	/// across the graphs where the plugin exports the context calls, as
+	/// [mine](#method.mine) does. The plugin is left idle afterwards, as
+	/// it was before.
+	///
+	/// #Arguments
+	///
+	/// * `plugin_index` (IN) The index of the plugin to benchmark
+	/// * `iterations` (IN) The number of graphs to attempt
+	/// * `header_seed` (IN) The seed for the headers, as for
+	/// [benchmark_header](fn.benchmark_header.html)
+	/// * `mode` (IN) Whether to call the plugin synchronously, timing each
+	/// call, or through its queue, with the graph times from its stats
+	///
+	/// #Returns
+	///
+	/// * Ok([BenchmarkReport](struct.BenchmarkReport.html)) with the graph
+	/// times, graphs per second and solutions found
+	/// * A PluginNotLoadedError if there's no plugin at `plugin_index`
+	/// * Another [CuckooMinerError](enum.CuckooMinerError.html) if a graph
+	/// attempt fails, as for [mine_hash](#method.mine_hash) and
+	/// [benchmark_queue](fn.benchmark_queue.html)
+	///
+	/// #Example
+	///
+	/// ```no_run
+	///  # use cuckoo_miner::{CuckooMiner, CuckooMinerConfig, BenchmarkMode};
+	///  let mut config = CuckooMinerConfig::new();
+	///  config.plugin_full_path = String::from("lean_cpu_30");
+	///  let miner = CuckooMiner::new(vec![config]).unwrap();
+	///  let report = miner.benchmark(0, 10, 0, BenchmarkMode::Sync).unwrap();
+	///  println!("Median graph time: {:?}", report.median_graph_time);
+	/// ```
+	///
+
+	pub fn benchmark(
+		&self,
+		plugin_index: usize,
+		iterations: u32,
+		header_seed: u64,
+		mode: BenchmarkMode,
+	) -> Result<BenchmarkReport, CuckooMinerError> {
+		let library = match self.libraries.get(plugin_index) {
+			Some(l) => l,
+			None => {
+				return Err(CuckooMinerError::PluginNotLoadedError(
+					format!("No plugin loaded at index {}", plugin_index),
+				))
+			}
+		};
+		if mode == BenchmarkMode::Async {
+			return benchmark_queue(library, iterations, header_seed);
+		}
+		let (report, _) = self.time_graphs(plugin_index, iterations, header_seed, None)?;
+		Ok(report)
+	}
+
+	/// #Description
+	///
+	/// Benchmarks every loaded plugin in turn, as
+	/// [benchmark](#method.benchmark), over the same headers, so the
+	/// plugins can be compared, e.g. lean against mean, or CPU against
+	/// CUDA.
+	///
+	/// #Returns
+	///
+	/// * Ok(Vec<[BenchmarkReport](struct.BenchmarkReport.html)>) with a
+	/// report per plugin, in the order they were loaded
+	/// * The first [CuckooMinerError](enum.CuckooMinerError.html) any
+	/// plugin's run fails with, as for [benchmark](#method.benchmark)
+	///
+
+	pub fn benchmark_all(
+		&self,
+		iterations: u32,
+		header_seed: u64,
+		mode: BenchmarkMode,
+	) -> Result<Vec<BenchmarkReport>, CuckooMinerError> {
+		(0..self.libraries.len())
+			.map(|i| self.benchmark(i, iterations, header_seed, mode))
+			.collect()
+	}
+
+	// Attempts graphs from benchmark headers one at a time through the
+	// synchronous call, timing each, and stopping early if one takes
+	// longer than `max_graph_time`. Also returns whether it stopped early.
+
+	fn time_graphs(
+		&self,
+		plugin_index: usize,
+		iterations: u32,
+		header_seed: u64,
+		max_graph_time: Option<time::Duration>,
+	) -> Result<(BenchmarkReport, bool), CuckooMinerError> {
+		let start = time::Instant::now();
+		let context = self.libraries[plugin_index].solver_context();
+		let setup = start.elapsed();
+		let mut times = Vec::new();
+		let mut solutions = 0;
+		let mut aborted = false;
+		for i in 0..iterations {
+			let header = benchmark_header(header_seed, i as u64);
+			let mut cuckoo_size = 0;
+			let mut solution = CuckooMinerSolution::new();
+			let graph_start = time::Instant::now();
+			if self.mine_hash_in(&header, &mut cuckoo_size, &mut solution, plugin_index, Some(&context))? {
+				solutions += 1;
+			}
+			let graph_time = graph_start.elapsed();
+			times.push(graph_time);
+			if max_graph_time.map_or(false, |max| graph_time > max) {
+				aborted = true;
+				break;
+			}
+		}
+		let search = start.elapsed() - setup;
+		let persistent_context = context.is_persistent();
+		let teardown_start = time::Instant::now();
+		drop(context);
+		let teardown = teardown_start.elapsed();
+		let mut report = BenchmarkReport::new(
+			&self.libraries[plugin_index].lib_full_path,
+			BenchmarkMode::Sync,
+			&times,
+			solutions,
+			start.elapsed(),
+		);
+		report.persistent_context = persistent_context;
+		report.phases = BenchmarkPhases {
+			setup: setup,
+			search: search,
+			teardown: teardown,
+		};
+		Ok((report, aborted))
+	}
+
+	/// #Description
+	///
+	/// Sweeps combinations of values for a plugin's parameters, such as
+	/// its thread or trim counts, benchmarking each combination through
+	/// the synchronous call, and leaves the plugin set to the combination
+	/// giving the most graphs per second. The plugin's config is updated
+	/// to match, so the values are kept if the plugin is reloaded.
+	///
+	/// Values outside a parameter's declared range are skipped with a
+	/// warning. A combination is abandoned as soon as one of its graphs
+	/// takes longer than the options' `max_graph_time`, so hopeless
+	/// settings don't hold up the sweep.
+	///
+	/// #Arguments
+	///
+	/// * `plugin_index` (IN) The index of the plugin to tune
+	/// * `sweep` (IN) Each parameter to tune with its candidate values
+	/// * `options` (IN) How many graphs to try per combination, and the
+	/// graph time ceiling
+	///
+	/// #Returns
+	///
+	/// * Ok([TuningResult](struct.TuningResult.html)) with the best values
+	/// and the graphs per second of every combination tried
+	/// * A PluginNotLoadedError if there's no plugin at `plugin_index`
+	/// * A ParameterNotFoundError or ParameterOutOfRange error if a
+	/// parameter isn't declared, or has no values in range
+	/// * A PluginProcessingError if every combination was abandoned
+	/// * Another [CuckooMinerError](enum.CuckooMinerError.html) if a
+	/// parameter can't be set or a graph attempt fails, in which case the
+	/// plugin is left with the values last tried
+	///
+	/// #Example
+	///
+	/// ```no_run
+	///  # use std::time::Duration;
+	///  # use cuckoo_miner::{CuckooMiner, CuckooMinerConfig, TuningOptions};
+	///  let mut config = CuckooMinerConfig::new();
+	///  config.plugin_full_path = String::from("mean_cpu_30");
+	///  let mut miner = CuckooMiner::new(vec![config]).unwrap();
+	///  let options = TuningOptions {
+	///  	max_graph_time: Some(Duration::from_secs(10)),
+	///  	..TuningOptions::default()
+	///  };
+	///  let result = miner.autotune(0, &[("NUM_THREADS", &[2, 4, 8, 16, 32])], &options).unwrap();
+	///  println!("Best: {:?} at {:.3} graphs/s", result.best, result.best_graphs_per_second);
+	/// ```
+	///
+
+	pub fn autotune(
+		&mut self,
+		plugin_index: usize,
+		sweep: &[(&str, &[u32])],
+		options: &TuningOptions,
+	) -> Result<TuningResult, CuckooMinerError> {
+		let parameters = match self.libraries.get(plugin_index) {
+			Some(l) => l.parameters()?,
+			None => {
+				return Err(CuckooMinerError::PluginNotLoadedError(
+					format!("No plugin loaded at index {}", plugin_index),
+				))
+			}
+		};
+		let sweep = values_in_range(sweep, &parameters)?;
+		let mut points = Vec::new();
+		for values in combinations(&sweep) {
+			for &(ref name, value) in &values {
+				self.libraries[plugin_index].set_parameter_checked(name, value as u64)?;
+			}
+			let (report, aborted) = self.time_graphs(
+				plugin_index,
+				options.graphs,
+				options.header_seed,
+				options.max_graph_time,
+			)?;
+			debug!(
+				"Tuning {}: {:?} gave {:.3} graphs/s{}",
+				report.plugin,
+				values,
+				report.graphs_per_second,
+				if aborted { ", abandoned" } else { "" }
+			);
+			points.push(TuningPoint {
+				values: values,
+				report: report,
+				aborted: aborted,
+			});
+		}
+		let result = TuningResult::from_points(points)?;
+		let config = &mut self.configs[plugin_index];
+		for &(ref name, value) in &result.best {
+			self.libraries[plugin_index].set_parameter_checked(name, value as u64)?;
+			config.parameter_list.retain(|p| p.0 != *name);
+			config.parameter_list.push((name.clone(), 0, value));
+		}
+		Ok(result)
+	}
+
+	/// #Description
+	///
+	/// Saves the values every plugin's parameters currently have, e.g.
+	/// after [autotune](#method.autotune), to a TOML file which
+	/// [load_tuning](#method.load_tuning) applies at the next start. See
+	/// [save_tuning](fn.save_tuning.html) for the file's layout.
+	///
+	/// #Arguments
+	///
+	/// * `path` (IN) The file to write, which is replaced if it exists
+	///
+	/// #Returns
+	///
+	/// * `Ok(())` if the file was written
+	/// * a ConfigError if the file can't be written
+	/// * another [CuckooMinerError](enum.CuckooMinerError.html) if a
+	/// plugin's parameters can't be read
+	///
+	/// #Example
+	///
+	/// ```no_run
+	///  # use cuckoo_miner::{CuckooMiner, CuckooMinerConfig, TuningOptions};
+	///  let mut config = CuckooMinerConfig::new();
+	///  config.plugin_full_path = String::from("mean_cpu_30");
+	///  let mut miner = CuckooMiner::new(vec![config]).unwrap();
+	///  if miner.load_tuning("tuning.toml").is_err() {
+	///  	miner.autotune(0, &[("NUM_THREADS", &[2, 4, 8, 16])], &TuningOptions::default()).unwrap();
+	///  	miner.save_tuning("tuning.toml").unwrap();
+	///  }
+	/// ```
+	///
+
+	pub fn save_tuning<P: AsRef<Path>>(&self, path: P) -> Result<(), CuckooMinerError> {
+		save_tuning(&self.libraries, path)
+	}
+
+	/// #Description
+	///
+	/// Applies parameter values saved by [save_tuning](#method.save_tuning)
+	/// to the loaded plugins, and keeps them in their configs so they're
+	/// set again if a plugin is reloaded. Parameters a plugin no longer
+	/// declares are skipped, and values outside a parameter's current range
+	/// are clamped to it, with a warning logged for each.
+	///
+	/// #Arguments
+	///
+	/// * `path` (IN) The file to read
+	///
+	/// #Returns
+	///
+	/// * `Ok(())` if the values were applied
+	/// * a ConfigError if the file can't be read or is malformed
+	/// * another [CuckooMinerError](enum.CuckooMinerError.html) if a
+	/// plugin refuses a value
+	///
+
+	pub fn load_tuning<P: AsRef<Path>>(&mut self, path: P) -> Result<(), CuckooMinerError> {
+		load_tuning(&self.libraries, &mut self.configs, path)
+	}
+
+	/// #Description
+	///
+	/// Returns an vector of [CuckooMinerDeviceStats](struct.CuckooMinerDeviceStats.html)
+	/// which will contain information about every device currently mining within the plugin.
+	/// When called in syncronous mode, this wil only ever return a vector with a single value
+	/// containing stats on the currently running device. 
 	///
 	/// #Returns
 	///
-	/// * `Ok()` if successful, and the specified plugin has been loaded internally.
-	/// * Otherwise a [CuckooMinerError](enum.CuckooMinerError.html)
-	/// with specific detail
+	/// * Ok([CuckooMinerDeviceStats](struct.CuckooMinerDeviceStats.html)) if successful
+	/// * A [CuckooMinerError](enum.CuckooMinerError.html) with specific detail if an
+	/// error occurred
 
-	pub fn new(configs: Vec<CuckooMinerConfig>) -> Result<CuckooMiner, CuckooMinerError> {
-		CuckooMiner::init(configs)
+	pub fn get_stats(&self, plugin_index:usize) -> Result<Vec<CuckooMinerDeviceStats>, CuckooMinerError> {
+		self.libraries[plugin_index].stats()
 	}
 
-	/// Internal function to perform tha actual library loading
+	/// #Description
+	///
+	/// Returns the memory the plugins report using, as
+	/// [CuckooMinerJobHandle::total_memory_bytes](struct.CuckooMinerJobHandle.html#method.total_memory_bytes)
+	///
 
-	fn init(configs: Vec<CuckooMinerConfig>) -> Result<CuckooMiner, CuckooMinerError> {
-		let mut lib_vec=Vec::new();
-		for c in &configs {
-			let lib=PluginLibrary::new(&c.plugin_full_path)?;
-			for elem in c.parameter_list.clone() {
-				CuckooMiner::set_parameter(elem.0.clone(), elem.1.clone(), elem.2.clone(), &lib)?;
-			}
-			lib_vec.push(lib);
-		}
+	pub fn total_memory_bytes(&self) -> Option<u64> {
+		total_memory_bytes(self.libraries.iter().filter_map(|l| l.stats().ok()))
+	}
 
-		let ret_val=CuckooMiner {
-			configs : configs.clone(),
-			delegator : None,
-			libraries : lib_vec,
-		};
+	// Enables only the listed devices of a plugin
 
-		Ok(ret_val)
+	fn select_devices(library: &PluginLibrary, enabled: &[u32]) -> Result<(), CuckooMinerError> {
+		let devices = library.list_devices()?;
+		if let Some(id) = enabled.iter().find(|id| !devices.iter().any(|d| d.device_id == **id)) {
+			return Err(CuckooMinerError::DeviceNotFoundError(format!(
+				"{} has no device {}",
+				library.lib_full_path,
+				id
+			)));
+		}
+		for d in devices {
+			library.set_device_enabled(d.device_id, enabled.contains(&d.device_id))?;
+		}
+		Ok(())
 	}
 
 	/// #Description
 	///
-	/// Sets a parameter in the loaded plugin
+	/// Reloads a plugin from its file, e.g. after it's been rebuilt. Its
+	/// config's parameters are applied again, and the same devices
+	/// disabled. Once mining has started, use the job handle's
+	/// [reload_plugin](struct.CuckooMinerJobHandle.html#method.reload_plugin),
+	/// which reloads it without stopping the job.
 	///
 	/// #Arguments
 	///
-	/// * `name` The name of the parameter to set
-	///
-	/// * `value` The value to set the parameter to
+	/// * `name` (IN) The plugin's configured path, or its file name
+	/// without the extension. Every instance of the plugin is reloaded.
 	///
 	/// #Returns
 	///
-	/// *`Ok()` if successful and the parameter has been set.
-	/// * Otherwise a
-	/// [CuckooMinerError](enum.CuckooMinerError.html)
-	/// with specific detail is returned.
+	/// * `Ok(())` if the plugin was reloaded
+	/// * PluginNotFoundError if no plugin has the name
+	/// * PluginReloadFailed if the file couldn't be loaded after retrying,
+	/// in which case the plugin stays unloaded
+	/// * another [CuckooMinerError](enum.CuckooMinerError.html) if the
+	/// reloaded plugin couldn't be set up
 	///
 
-	pub fn set_parameter(name: String, device_id: u32, value: u32, library:&PluginLibrary) -> Result<(), CuckooMinerError> {
-		let return_code = library.call_cuckoo_set_parameter(
-			name.as_bytes(),
-			device_id,
-			value,
-		);
-		if return_code != 0 {
-
-			let reason = match return_code {
-				1 => "Property doesn't exist for this plugin",
-				2 => "Property outside allowed range",
-				5 => "Device doesn't exist",
-				_ => "Unknown Error",
-			};
-
-			return Err(CuckooMinerError::ParameterError(String::from(format!(
-				"Error setting parameter: {} to {} - {}",
-				name,
-				value,
-				reason
-			))));
+	pub fn reload_plugin(&mut self, name: &str) -> Result<(), CuckooMinerError> {
+		let indices = plugin_indices(&self.configs, name);
+		if indices.is_empty() {
+			return Err(CuckooMinerError::PluginNotFoundError(String::from(name)));
+		}
+		let instances = plugin_instances(&self.configs);
+		for i in indices {
+			let disabled = self.libraries[i].disabled_devices();
+			// the old library has to go first, as the new one shares its
+			// file
+			self.libraries[i].unload();
+			let lib = reload_plugin_library(&self.configs[i], instances[i])?;
+			for d in disabled {
+				lib.set_device_enabled(d, false)?;
+			}
+			self.libraries[i] = lib;
 		}
 		Ok(())
 	}
 
 	/// #Description
 	///
-	/// Synchronous call to the cuckoo_call function of the currently loaded
-	/// plugin, which will perform
-	/// a Cuckoo Cycle on the given seed, filling the first solution (a length
-	/// 42 cycle) that is found in the provided
-	/// [CuckooMinerSolution](struct.CuckooMinerSolution.html) structure.
-	/// The implementation details are dependent on the particular loaded plugin.
-	/// Values provided
-	/// to the loaded plugin are contained in the internal
-	/// [CuckooMinerConfig](struct.CuckooMinerConfig.html)
+	/// Enables or disables a single device of a loaded plugin, as
+	/// [PluginLibrary::set_device_enabled](struct.PluginLibrary.html#method.set_device_enabled).
+	/// Once mining has started, use the job handle's
+	/// [set_device_enabled](struct.CuckooMinerJobHandle.html#method.set_device_enabled).
 	///
 	/// #Arguments
 	///
-	/// * `header` (IN) A reference to a block of [u8] bytes to use for the
-	/// seed to the
-	/// internal SIPHASH function which generates edge locations in the
-	/// graph. In practice,
-	/// this is a SHA3 hash of a Grin blockheader, but from the plugin's
-	/// perspective this can be anything.
-	///
-	/// * `solution` (OUT) An empty
-	/// [CuckooMinerSolution](struct.CuckooMinerSolution.html).
-	/// If a solution is found, this structure will contain a list of
-	/// solution nonces, otherwise, it will remain untouched.
-	///
-	/// #Returns
+	/// * `plugin_index` (IN) The index of the plugin, in the order they
+	/// were configured
+	/// * `device_id` (IN) The device, as reported by
+	/// [list_devices](struct.PluginLibrary.html#method.list_devices)
+	/// * `enabled` (IN) Whether the device should mine
 	///
-	/// * Ok(true) if a solution is found, with the 42 solution nonces
-	/// contained within
-	/// the provided [CuckooMinerSolution](struct.CuckooMinerSolution.html).
-	/// * Ok(false) if no solution is found and `solution` remains untouched.
-	/// * A [CuckooMinerError](enum.CuckooMinerError.html)
-	/// if there is an error calling the function.
 
-	pub fn mine(
-		&self,
-		header: &[u8],
-		cuckoo_size: &mut u32,
-		solution: &mut CuckooMinerSolution,
-		plugin_index: usize
-	) -> Result<bool, CuckooMinerError> {
-		let result = self.libraries[plugin_index].call_cuckoo(
-			header,
-			cuckoo_size,
-			&mut solution.solution_nonces,
-		);
-		match result {
-			1 => {
-				debug!("Solution found.");
-				Ok(true)
-			}
-			0 => Ok(false),
-			_ => Err(CuckooMinerError::UnexpectedResultError(result)),
-		}
+	pub fn set_device_enabled(&self, plugin_index: usize, device_id: u32, enabled: bool) -> Result<(), CuckooMinerError> {
+		self.libraries[plugin_index].set_device_enabled(device_id, enabled)
 	}
 
 	/// #Description
 	///
-	/// Returns an vector of [CuckooMinerDeviceStats](struct.CuckooMinerDeviceStats.html)
-	/// which will contain information about every device currently mining within the plugin.
-	/// When called in syncronous mode, this wil only ever return a vector with a single value
-	/// containing stats on the currently running device. 
+	/// Retires a job on every loaded plugin, e.g. when a new block arrives.
+	/// Headers waiting in the plugins' input queues are cleared first, so
+	/// no more work is started on them, then the solutions already found
+	/// are optionally read, as they may still be worth submitting, and the
+	/// output queues cleared. Safe to call while the plugins are processing.
+	///
+	/// Plugins which can only clear both queues at once have their
+	/// solutions read before both queues are cleared, in which case a
+	/// solution found in between is lost.
+	///
+	/// #Arguments
+	///
+	/// * `job_id` (IN) The id the job's headers were pushed with
+	///
+	/// * `drain_remaining` (IN) Whether to read and return the solutions
+	///   found for the job, rather than discarding them
 	///
 	/// #Returns
 	///
-	/// * Ok([CuckooMinerDeviceStats](struct.CuckooMinerDeviceStats.html)) if successful
-	/// * A [CuckooMinerError](enum.CuckooMinerError.html) with specific detail if an
-	/// error occurred
+	/// * Ok(Vec<[CuckooMinerSolution](struct.CuckooMinerSolution.html)>)
+	/// holding the job's remaining solutions, which is empty unless
+	/// `drain_remaining` is set. Solutions for other jobs are discarded.
+	/// * A [CuckooMinerError](enum.CuckooMinerError.html) if a plugin's
+	/// queues couldn't be cleared
+	///
 
-	pub fn get_stats(&self, plugin_index:usize) -> Result<Vec<CuckooMinerDeviceStats>, CuckooMinerError> {
-		let mut stats_bytes: [u8; 2048] = [0; 2048];
-		let mut stats_bytes_len = stats_bytes.len() as u32;
-		// get a list of parameters
-		self.libraries[plugin_index].call_cuckoo_get_stats(
-			&mut stats_bytes,
-			&mut stats_bytes_len,
-		);
-		let mut stats_vec: Vec<u8> = Vec::new();
-		// result contains null zero
-		for i in 0..stats_bytes_len {
-			stats_vec.push(stats_bytes[i as usize].clone());
-		}
-		let stats_json = String::from_utf8(stats_vec)?;
-		//println!("Stats_json: {}", stats_json);
-
-		let result = serde_json::from_str(&stats_json);
-		if let Err(e) = result {
-			return Err(CuckooMinerError::StatsError(
-				String::from(format!("Error retrieving stats from plugin: {:?}", e)),
-			));
+	pub fn retire_job(&self, job_id: u32, drain_remaining: bool) -> Result<Vec<CuckooMinerSolution>, CuckooMinerError> {
+		let mut solutions = Vec::new();
+		for l in &self.libraries {
+			let cleared_both = match l.clear_input_queue() {
+				Ok(()) => false,
+				Err(CuckooMinerError::PluginSymbolMissing { .. }) => true,
+				Err(e) => return Err(e),
+			};
+			if drain_remaining {
+				let drained = l.drain_solutions(usize::max_value());
+				solutions.extend(drained.into_iter().filter(|s| s.id == job_id));
+			}
+			if cleared_both {
+				l.call_cuckoo_clear_queues();
+			} else {
+				l.clear_output_queue()?;
+			}
 		}
-		Ok(result.unwrap())
+		Ok(solutions)
 	}
 
 	/// #Description
@@ -561,7 +2872,9 @@ impl CuckooMiner {
 	///
 	/// #Arguments
 	///
-	/// * `job_id` (IN) A job ID, for later reference (not currently used).
+	/// * `job_id` (IN) A job ID, which solutions found for the job carry
+	///   in their `id`. The job can be switched to a new header with
+	///   [CuckooMinerJobHandle::notify](struct.CuckooMinerJobHandle.html#method.notify).
 	///
 	/// * `pre_nonce` (IN) The part of the header which comes before the nonce,
 	///   as a hex string slice.
@@ -579,12 +2892,16 @@ impl CuckooMiner {
 	/// * Ok([CuckooMinerJobHandle](struct.CuckooMinerJobHandle.html)) if the
 	/// job
 	/// is successfully started.
+	/// * An InvalidHex error if either part of the header isn't valid
+	/// hex, as for [from_hex](fn.from_hex.html)
+	/// * An InsufficientMemory error if the plugins expect more memory
+	/// than the system has available, unless their configs skip the check
 	/// * A [CuckooMinerError](enum.CuckooMinerError.html)
 	/// if there is no plugin loaded, or if there is an error calling the
 	/// function.
 
 	pub fn notify(
-		mut self,
+		self,
 		job_id: u32, // Job id
 		pre_nonce: &str, // Pre-nonce portion of header
 		post_nonce: &str, // Post-nonce portion of header
@@ -593,8 +2910,488 @@ impl CuckooMiner {
 		hash_header: bool, // (Temporary) Whether to hash the header before sending (true for testnet2 and earlier)
 	) -> Result<CuckooMinerJobHandle, CuckooMinerError> {
 
+		self.start_job(
+			job_id,
+			&from_hex(pre_nonce)?,
+			&from_hex(post_nonce)?,
+			difficulty,
+			hash_header,
+		)
+	}
+
+	/// #Description
+	///
+	/// As [notify](#method.notify), but takes the parts of the header as
+	/// bytes, of any length, rather than hex strings. Each header is
+	/// reduced to the 32 bytes the plugins accept by
+	/// [header_hash](fn.header_hash.html), which documents how the nonce
+	/// is spliced in, so solutions verify against that hash.
+	///
+	/// #Arguments
+	///
+	/// * `job_id` (IN) A job ID, which solutions found for the job carry
+	///   in their `id`
+	///
+	/// * `header_pre` (IN) The part of the header before the nonce
+	///
+	/// * `header_post` (IN) The part of the header after the nonce
+	///
+	/// * `difficulty` (IN) The miner will only put solutions greater than or
+	///   equal to this difficulty in its output queue.
+	///
+	/// #Returns
+	///
+	/// As [notify](#method.notify)
+	///
+
+	pub fn notify_header(
+		self,
+		job_id: u32,
+		header_pre: &[u8],
+		header_post: &[u8],
+		difficulty: u64,
+	) -> Result<CuckooMinerJobHandle, CuckooMinerError> {
+		self.start_job(job_id, header_pre, header_post, difficulty, true)
+	}
+
+	/// #Description
+	///
+	/// Starts mining a [MiningJob](struct.MiningJob.html), given as the
+	/// raw block header. The miner works through the nonces, from the
+	/// job's start nonce if it has one, hashes each header with the nonce
+	/// spliced in using its [HeaderHasher](trait.HeaderHasher.html), and
+	/// pushes the hashes to the plugins. A job's nonce range and limit
+	/// are shared out between the plugins as
+	/// [MiningJob::nonce_generators](struct.MiningJob.html#method.nonce_generators)
+	/// describes.
+	///
+	/// #Arguments
+	///
+	/// * `job` (IN) The job
+	///
+	/// #Returns
+	///
+	/// As [notify](#method.notify)
+	///
+	/// #Example
+	///
+	/// ```no_run
+	///  # use cuckoo_miner::{CuckooMiner, CuckooMinerConfig, MiningJob};
+	///  let miner = CuckooMiner::new(vec![CuckooMinerConfig::new()]).unwrap();
+	///  let header = [0u8; 80];
+	///  let job = MiningJob::new(1, &header[..72], &header[72..]).difficulty(10);
+	///  let handle = miner.start(job).unwrap();
+	/// ```
+	///
+
+	pub fn start(mut self, job: MiningJob) -> Result<CuckooMinerJobHandle, CuckooMinerError> {
+		self.options.nonces = job.nonce_generators(&self.nonce_generators()?);
+		self.start_job(job.job_id, &job.header_pre, &job.header_post, job.difficulty, true)
+	}
+
+	/// #Description
+	///
+	/// As [notify](#method.notify), but returns a
+	/// [SolutionStream](struct.SolutionStream.html) of the job's verified
+	/// solutions, as they're found, for async code, rather than the job
+	/// handle, which callers would otherwise poll for solutions. The
+	/// stream owns the job, which it shuts down when dropped, and its
+	/// handle is reached through [SolutionStream::job](struct.SolutionStream.html#method.job).
+	/// It's subscribed before the job starts, so it gets every solution
+	/// the job finds. Only available with the `async` feature.
+	///
+	/// #Arguments
+	///
+	/// As [notify](#method.notify)
+	///
+	/// #Returns
+	///
+	/// * Ok([SolutionStream](struct.SolutionStream.html)) if the job is
+	/// successfully started
+	/// * A [CuckooMinerError](enum.CuckooMinerError.html) as for
+	/// [notify](#method.notify) otherwise
+	///
+	/// #Example
+	///
+	/// ```no_run
+	///  # extern crate cuckoo_miner;
+	///  # extern crate futures_executor;
+	///  # use cuckoo_miner::{CuckooMiner, CuckooMinerConfig};
+	///  # use futures_executor::block_on_stream;
+	///  # fn main() {
+	///  let miner = CuckooMiner::new(vec![CuckooMinerConfig::new()]).unwrap();
+	///  let stream = miner.notify_stream(1, "00", "00", 0, true).unwrap();
+	///  for solution in block_on_stream(stream).take(3) {
+	///      println!("Found a solution for nonce {}", solution.nonce);
+	///  }
+	///  # }
+	/// ```
+	///
+
+	#[cfg(feature = "async")]
+	pub fn notify_stream(
+		self,
+		job_id: u32,
+		pre_nonce: &str,
+		post_nonce: &str,
+		difficulty: u64,
+		hash_header: bool,
+	) -> Result<SolutionStream, CuckooMinerError> {
+		let (tx, rx) = unbounded();
+		let job = self.start_job_with(
+			job_id,
+			&from_hex(pre_nonce)?,
+			&from_hex(post_nonce)?,
+			difficulty,
+			hash_header,
+			Some(SolutionSender::Async(tx)),
+		)?;
+		Ok(SolutionStream::new(rx, Some(job)))
+	}
+
+	fn start_job(
+		self,
+		job_id: u32,
+		pre_nonce: &[u8],
+		post_nonce: &[u8],
+		difficulty: u64,
+		hash_header: bool,
+	) -> Result<CuckooMinerJobHandle, CuckooMinerError> {
+		self.start_job_with(job_id, pre_nonce, post_nonce, difficulty, hash_header, None)
+	}
+
+	// Starts a job, first subscribing `subscriber` to its solutions, if
+	// given, so it can't miss any found as the job starts
+
+	fn start_job_with(
+		mut self,
+		job_id: u32,
+		pre_nonce: &[u8],
+		post_nonce: &[u8],
+		difficulty: u64,
+		hash_header: bool,
+		subscriber: Option<SolutionSender>,
+	) -> Result<CuckooMinerJobHandle, CuckooMinerError> {
+		check_memory(&self.libraries, &self.configs)?;
 		//Note this gives up the plugin to the job thread
-		self.delegator = Some(Delegator::new(job_id, pre_nonce, post_nonce, difficulty, self.libraries));
-		Ok(self.delegator.unwrap().start_job_loop(hash_header).unwrap())
+		if self.options.nonces.is_empty() {
+			self.options.nonces = self.nonce_generators()?;
+		}
+		self.options.sources = self.configs
+			.iter()
+			.cloned()
+			.zip(plugin_instances(&self.configs))
+			.collect();
+		self.delegator = Some(Delegator::new(
+			job_id,
+			pre_nonce,
+			post_nonce,
+			difficulty,
+			hash_header,
+			self.libraries,
+			self.options,
+		));
+		let delegator = self.delegator.unwrap();
+		if let Some(s) = subscriber {
+			delegator.subscribe(s);
+		}
+		Ok(delegator.start_job_loop().unwrap())
+	}
+}
+
+/// #Description
+///
+/// Puts together a [CuckooMiner](struct.CuckooMiner.html) in code, as an
+/// alternative to filling in
+/// [CuckooMinerConfigs](struct.CuckooMinerConfig.html). Each call to
+/// [plugin](#method.plugin) adds a plugin, which the plugin settings
+/// after it apply to, up to the next plugin.
+///
+/// Plugins are only loaded by [build](#method.build). Rather than stop at
+/// the first problem, as [CuckooMiner::new](struct.CuckooMiner.html#method.new)
+/// does, it returns every problem found at once, so a setup can be fixed
+/// in a single pass. If anything fails, the plugins already loaded are
+/// unloaded again, so there's never a half set up miner.
+///
+/// #Example
+///
+/// ```no_run
+///  # use std::time::Duration;
+///  # use cuckoo_miner::CuckooMinerBuilder;
+///  let miner = CuckooMinerBuilder::new()
+///  	.plugin("mean_cpu_30")
+///  	.parameter("NUM_THREADS", 8)
+///  	.plugin("lean_cuda_30")
+///  	.device(0)
+///  	.parameter("N_TRIMS", 96)
+///  	.stats_interval(Duration::from_secs(2))
+///  	.build()
+///  	.unwrap();
+/// ```
+
+pub struct CuckooMinerBuilder {
+	/// A config for each plugin, in the order they were added
+	configs: Vec<CuckooMinerConfig>,
+
+	/// The device the current plugin's parameters are set on, if any
+	device: Option<u32>,
+
+	/// How the miner's asynchronous jobs are run
+	options: JobOptions,
+
+	/// Problems found while putting the miner together, such as a
+	/// parameter given before any plugin
+	errors: Vec<CuckooMinerError>,
+}
+
+impl Default for CuckooMinerBuilder {
+	fn default() -> CuckooMinerBuilder {
+		CuckooMinerBuilder {
+			configs: Vec::new(),
+			device: None,
+			options: JobOptions::default(),
+			errors: Vec::new(),
+		}
+	}
+}
+
+impl CuckooMinerBuilder {
+	/// Returns a new builder, with no plugins
+
+	pub fn new() -> CuckooMinerBuilder {
+		CuckooMinerBuilder::default()
+	}
+
+	/// Applies a setting to the current plugin, or notes an error if no
+	/// plugin has been added yet
+
+	fn with_plugin<F>(mut self, setting: &str, f: F) -> CuckooMinerBuilder
+	where
+		F: FnOnce(&mut CuckooMinerConfig, Option<u32>),
+	{
+		let device = self.device;
+		match self.configs.last_mut() {
+			Some(c) => f(c, device),
+			None => self.errors.push(CuckooMinerError::ParameterError(
+				format!("{} given before any plugin", setting),
+			)),
+		}
+		self
+	}
+
+	/// Adds a plugin, by name or path, as for
+	/// [resolve_plugin_path](fn.resolve_plugin_path.html). Adding the same
+	/// plugin again loads another instance of it.
+
+	pub fn plugin(mut self, plugin: &str) -> CuckooMinerBuilder {
+		let mut c = CuckooMinerConfig::new();
+		c.plugin_full_path = String::from(plugin);
+		self.configs.push(c);
+		self.device = None;
+		self
+	}
+
+	/// Adds a device for the current plugin to mine on, and sets the
+	/// parameters which follow on that device alone. A plugin without
+	/// any devices mines on all of them.
+
+	pub fn device(self, device_id: u32) -> CuckooMinerBuilder {
+		let mut b = self.with_plugin(&format!("Device {}", device_id), |c, _| {
+			let devices = c.enabled_devices.get_or_insert_with(Vec::new);
+			if !devices.contains(&device_id) {
+				devices.push(device_id);
+			}
+		});
+		b.device = Some(device_id);
+		b
+	}
+
+	/// Sets a parameter of the current plugin, on the device given by the
+	/// last call to [device](#method.device) if any, otherwise on the
+	/// plugin as a whole
+
+	pub fn parameter(self, name: &str, value: u32) -> CuckooMinerBuilder {
+		self.with_plugin(&format!("Parameter {}", name), |c, device| match device {
+			Some(id) => {
+				c.device_parameters
+					.entry(id)
+					.or_insert_with(BTreeMap::new)
+					.insert(String::from(name), value);
+			}
+			None => c.parameter_list.push((String::from(name), 0, value)),
+		})
+	}
+
+	/// Sets whether the current plugin is loaded even if it doesn't report
+	/// a plugin interface version, which it is by default
+
+	pub fn allow_legacy_plugin(self, allow: bool) -> CuckooMinerBuilder {
+		self.with_plugin("allow_legacy_plugin", |c, _| c.allow_legacy_plugins = allow)
+	}
+
+	/// Sets the number of headers the current plugin's input queue accepts
+
+	pub fn queue_size(self, queue_size: u32) -> CuckooMinerBuilder {
+		self.with_plugin("queue_size", |c, _| c.queue_size = Some(queue_size))
+	}
+
+	/// Sets the slice of the nonce space the current plugin mines in
+	/// asynchronous mode
+
+	pub fn nonce_range(self, range: NonceRange) -> CuckooMinerBuilder {
+		self.with_plugin("nonce_range", |c, _| c.nonce_range = Some(range))
+	}
+
+	/// Sets the cores the current plugin's threads may run on
+
+	pub fn cpu_affinity(self, cores: &[usize]) -> CuckooMinerBuilder {
+		self.with_plugin("cpu_affinity", |c, _| c.cpu_affinity = Some(cores.to_vec()))
+	}
+
+	/// Sets the scheduling priority of the current plugin's threads
+
+	pub fn thread_priority(self, priority: Priority) -> CuckooMinerBuilder {
+		self.with_plugin("thread_priority", |c, _| c.thread_priority = Some(priority))
+	}
+
+	/// Sets how nonces are laid out in the headers pushed to the current
+	/// plugin and read back from its solutions
+
+	pub fn nonce_encoding(self, encoding: NonceEncoding) -> CuckooMinerBuilder {
+		self.with_plugin("nonce_encoding", |c, _| c.nonce_encoding = encoding)
+	}
+
+	/// Sets the hashes of the builds of the current plugin allowed to load
+
+	pub fn pinned_hashes(self, hashes: &[&str]) -> CuckooMinerBuilder {
+		self.with_plugin("pinned_hashes", |c, _| c.pinned_hashes = hashes.iter().map(|h| String::from(*h)).collect())
+	}
+
+	/// Sets whether the current plugin is started even if it expects more
+	/// memory than the system has available
+
+	pub fn skip_memory_check(self, skip: bool) -> CuckooMinerBuilder {
+		self.with_plugin("skip_memory_check", |c, _| c.skip_memory_check = skip)
+	}
+
+	/// As [CuckooMiner::set_stats_interval](struct.CuckooMiner.html#method.set_stats_interval)
+
+	pub fn stats_interval(mut self, interval: time::Duration) -> CuckooMinerBuilder {
+		self.options.stats_interval = interval;
+		self
+	}
+
+	/// As [CuckooMiner::set_duty_cycle](struct.CuckooMiner.html#method.set_duty_cycle)
+
+	pub fn duty_cycle(mut self, percent: u8) -> CuckooMinerBuilder {
+		match check_duty_cycle(percent) {
+			Ok(()) => self.options.duty_cycle.store(percent as usize, Ordering::Relaxed),
+			Err(e) => self.errors.push(e),
+		}
+		self
+	}
+
+	/// As [CuckooMiner::set_verify_solutions](struct.CuckooMiner.html#method.set_verify_solutions)
+
+	pub fn verify_solutions(mut self, verify_solutions: bool) -> CuckooMinerBuilder {
+		self.options.verify_solutions = verify_solutions;
+		self
+	}
+
+	/// As [CuckooMiner::set_watchdog_timeout](struct.CuckooMiner.html#method.set_watchdog_timeout)
+
+	pub fn watchdog_timeout(mut self, timeout: Option<time::Duration>) -> CuckooMinerBuilder {
+		self.options.watchdog_timeout = timeout;
+		self
+	}
+
+	/// As [CuckooMiner::set_stall_warning](struct.CuckooMiner.html#method.set_stall_warning)
+
+	pub fn stall_warning(mut self, interval: Option<time::Duration>) -> CuckooMinerBuilder {
+		self.options.stall_warning = interval;
+		self
+	}
+
+	/// As [CuckooMiner::set_stop_timeout](struct.CuckooMiner.html#method.set_stop_timeout)
+
+	pub fn stop_timeout(mut self, timeout: time::Duration) -> CuckooMinerBuilder {
+		self.options.stop_timeout = timeout;
+		self
+	}
+
+	/// As [CuckooMiner::set_shutdown_deadline](struct.CuckooMiner.html#method.set_shutdown_deadline)
+
+	pub fn shutdown_deadline(mut self, deadline: time::Duration) -> CuckooMinerBuilder {
+		self.options.shutdown_deadline = deadline;
+		self
+	}
+
+	/// The config of each plugin added so far
+
+	pub fn configs(&self) -> &[CuckooMinerConfig] {
+		&self.configs
+	}
+
+	/// #Description
+	///
+	/// Loads every plugin, checks its parameters against those it
+	/// declares and applies its settings.
+	///
+	/// #Returns
+	///
+	/// * `Ok(CuckooMiner)` with every plugin loaded and set up
+	/// * A [CuckooMinerError](enum.CuckooMinerError.html)::MinerBuildError
+	/// holding every problem found otherwise, such as plugins which
+	/// couldn't be loaded, or parameters they don't declare or which are
+	/// out of range. No plugins are left loaded.
+	///
+
+	pub fn build(self) -> Result<CuckooMiner, CuckooMinerError> {
+		let mut errors = self.errors;
+		if self.configs.is_empty() {
+			errors.push(CuckooMinerError::ParameterError(String::from("No plugins given")));
+		}
+		if let Err(e) = check_nonce_ranges(&self.configs) {
+			errors.push(e);
+		}
+		let mut libraries = Vec::new();
+		for (c, instance) in self.configs.iter().zip(plugin_instances(&self.configs)) {
+			let lib = match PluginLibrary::load_instance(&c.plugin_full_path, instance, c.allow_legacy_plugins) {
+				Ok(l) => l,
+				Err(e) => {
+					errors.push(e);
+					continue;
+				}
+			};
+			// a plugin which can't list its parameters is left to reject
+			// them itself
+			let bad_parameters = match lib.parameters() {
+				Ok(p) => c.parameter_errors(&p),
+				Err(_) => Vec::new(),
+			};
+			if bad_parameters.is_empty() {
+				if let Err(e) = configure_plugin(&lib, c) {
+					errors.push(e);
+				}
+			} else {
+				errors.extend(bad_parameters);
+			}
+			libraries.push(lib);
+		}
+		if !errors.is_empty() {
+			for l in &libraries {
+				l.unload();
+			}
+			return Err(CuckooMinerError::MinerBuildError(errors));
+		}
+		Ok(CuckooMiner {
+			configs: self.configs,
+			delegator: None,
+			libraries: libraries,
+			options: self.options,
+			stop_flag: Arc::new(AtomicBool::new(false)),
+			start_nonce: None,
+			extranonce: None,
+		})
 	}
 }
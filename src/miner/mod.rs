@@ -22,4 +22,35 @@
 #![warn(missing_docs)]
 
 mod delegator;
+mod ledger;
+mod worker;
+pub mod affinity;
+pub mod bench;
+pub mod blacklist;
+pub mod config;
+pub mod dedup;
+pub mod devices;
+pub mod events;
+pub mod job;
 pub mod miner;
+pub mod nonce;
+pub mod self_test;
+pub mod solutions;
+pub mod stats;
+pub mod thermal;
+pub mod tune;
+pub mod tuning_file;
+pub mod watchdog;
+#[cfg(feature = "serde")]
+mod serialize;
+#[cfg(any(feature = "metrics", feature = "control"))]
+pub mod http;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+#[cfg(feature = "stratum")]
+pub mod stratum;
+#[cfg(feature = "control")]
+pub mod control;
+
+#[cfg(feature = "fault-injection")]
+pub use self::worker::PANIC_NONCE;
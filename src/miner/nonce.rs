@@ -0,0 +1,439 @@
+// Copyright 2017 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Partitioning of the nonce space, so plugins mining the same header,
+//! or rigs mining for the same pool worker, never duplicate each other's
+//! work, and the layout of the nonce as passed to and from plugins.
+
+use std::str::FromStr;
+
+use byteorder::{ByteOrder, BigEndian, LittleEndian, NativeEndian};
+
+use error::error::CuckooMinerError;
+
+/// How a nonce is laid out in the 8 bytes spliced into the header and
+/// passed to and from plugins with each header and solution. Hashing,
+/// pushing and reading solutions all use the same encoding, so it need
+/// only match what the plugin and the chain being mined expect.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum NonceEncoding {
+	/// A Big-Endian u64, as grin headers hold it. The default.
+	BigEndianU64,
+
+	/// A Little-Endian u64
+	LittleEndianU64,
+
+	/// The u64's bytes as they are in memory, i.e. in the host's byte
+	/// order, for plugins which copy the nonce straight into a u64. Only
+	/// portable between hosts of the same byte order.
+	RawBytes,
+}
+
+impl Default for NonceEncoding {
+	fn default() -> NonceEncoding {
+		NonceEncoding::BigEndianU64
+	}
+}
+
+impl NonceEncoding {
+	/// The bytes of a nonce in this encoding
+	pub fn encode(&self, nonce: u64) -> [u8; 8] {
+		let mut bytes = [0; 8];
+		match *self {
+			NonceEncoding::BigEndianU64 => BigEndian::write_u64(&mut bytes, nonce),
+			NonceEncoding::LittleEndianU64 => LittleEndian::write_u64(&mut bytes, nonce),
+			NonceEncoding::RawBytes => NativeEndian::write_u64(&mut bytes, nonce),
+		}
+		bytes
+	}
+
+	/// The nonce held in bytes of this encoding
+	pub fn decode(&self, bytes: &[u8; 8]) -> u64 {
+		match *self {
+			NonceEncoding::BigEndianU64 => BigEndian::read_u64(bytes),
+			NonceEncoding::LittleEndianU64 => LittleEndian::read_u64(bytes),
+			NonceEncoding::RawBytes => NativeEndian::read_u64(bytes),
+		}
+	}
+}
+
+impl FromStr for NonceEncoding {
+	type Err = CuckooMinerError;
+
+	fn from_str(s: &str) -> Result<NonceEncoding, CuckooMinerError> {
+		match s {
+			"big_endian_u64" => Ok(NonceEncoding::BigEndianU64),
+			"little_endian_u64" => Ok(NonceEncoding::LittleEndianU64),
+			"raw_bytes" => Ok(NonceEncoding::RawBytes),
+			_ => Err(CuckooMinerError::ParameterError(format!(
+				"Unknown nonce encoding {}, expected big_endian_u64, little_endian_u64 or raw_bytes",
+				s
+			))),
+		}
+	}
+}
+
+/// A slice of the nonce space, made up of the nonces `start`,
+/// `start + stride`, `start + 2 * stride`, and so on, offset from the
+/// job's starting nonce. Plugins given the same stride and different
+/// starts below it never share a nonce.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct NonceRange {
+	/// The first nonce in the range, relative to the job's starting nonce
+	pub start: u64,
+
+	/// The gap between successive nonces in the range
+	pub stride: u64,
+}
+
+impl Default for NonceRange {
+	fn default() -> NonceRange {
+		NonceRange {
+			start: 0,
+			stride: 1,
+		}
+	}
+}
+
+impl NonceRange {
+	/// Returns a new NonceRange
+
+	pub fn new(start: u64, stride: u64) -> NonceRange {
+		NonceRange {
+			start: start,
+			stride: stride,
+		}
+	}
+
+	/// #Description
+	///
+	/// Returns the `index`th of `count` interleaved ranges, which between
+	/// them cover every nonce, i.e. nonces `index`, `index + count`, ...
+	///
+	/// #Arguments
+	///
+	/// * `index` (IN) Which range to return, counting from 0
+	/// * `count` (IN) The number of ranges the nonce space is split into
+	///
+
+	pub fn interleaved(index: u64, count: u64) -> NonceRange {
+		NonceRange::new(index, count)
+	}
+
+	/// #Description
+	///
+	/// Returns the `index`th of `count` equal contiguous blocks of the
+	/// nonce space. Each block is only disjoint from the others until
+	/// its nonces run into the next block, after 2^64 / `count` nonces.
+	///
+	/// #Arguments
+	///
+	/// * `index` (IN) Which block to return, counting from 0
+	/// * `count` (IN) The number of blocks the nonce space is split into
+	///
+
+	pub fn block(index: u64, count: u64) -> NonceRange {
+		let size = match count {
+			0 | 1 => 0,
+			c => u64::max_value() / c + 1,
+		};
+		NonceRange::new(index.wrapping_mul(size), 1)
+	}
+
+	/// The `n`th nonce in the range, relative to the job's starting
+	/// nonce. Wraps around at u64::MAX.
+
+	pub fn nth(&self, n: u64) -> u64 {
+		self.start.wrapping_add(n.wrapping_mul(self.stride))
+	}
+
+	/// #Description
+	///
+	/// Returns the `index`th of `count` interleaved ranges the range can
+	/// be split into, which between them hand out the range's nonces,
+	/// i.e. the range's nonces `index`, `index + count`, ...
+	///
+	/// #Arguments
+	///
+	/// * `index` (IN) Which range to return, counting from 0
+	/// * `count` (IN) The number of ranges the range is split into
+	///
+
+	pub fn split(&self, index: u64, count: u64) -> NonceRange {
+		NonceRange::new(self.nth(index), self.stride.wrapping_mul(count))
+	}
+
+	/// #Description
+	///
+	/// Whether two ranges hand out the same nonce soon enough to matter,
+	/// i.e. within the first 2^40 nonces of each, which at a million
+	/// graphs a second is about 12 days of mining. Ranges which only meet
+	/// further on, such as [blocks](#method.block) of a nonce space split
+	/// fewer than 2^24 ways, or only by wrapping around at u64::MAX, don't
+	/// collide.
+	///
+	/// #Arguments
+	///
+	/// * `other` (IN) The other range
+	///
+	/// #Returns
+	///
+	/// True if the ranges share a nonce within the first 2^40 of each
+	///
+
+	pub fn collides_with(&self, other: &NonceRange) -> bool {
+		match first_shared(self, other) {
+			Some((i, j)) => i < COLLISION_HORIZON && j < COLLISION_HORIZON,
+			None => false,
+		}
+	}
+}
+
+/// How many nonces into two ranges a shared nonce must be to be ignored
+const COLLISION_HORIZON: u128 = 1 << 40;
+
+// The positions in each range of the smallest nonce both ranges hand
+// out, ignoring wrapping at u64::MAX, if there is one. Solved as a pair
+// of congruences, x = a mod s and x = b mod t, over the u128s.
+fn first_shared(r: &NonceRange, q: &NonceRange) -> Option<(u128, u128)> {
+	let (a, s, b, t) = (r.start as u128, r.stride as u128, q.start as u128, q.stride as u128);
+	let position = |x: u128, start: u128, stride: u128| match stride {
+		0 => 0,
+		_ => (x - start) / stride,
+	};
+	let is_in = |x: u128, start: u128, stride: u128| match stride {
+		0 => x == start,
+		_ => x >= start && (x - start) % stride == 0,
+	};
+	// a single nonce repeated
+	if s == 0 || t == 0 {
+		let (x, start, stride) = if s == 0 { (a, b, t) } else { (b, a, s) };
+		return match is_in(x, start, stride) {
+			true => Some((position(x, a, s), position(x, b, t))),
+			false => None,
+		};
+	}
+	let g = gcd(s, t);
+	let d = if b >= a { b - a } else { a - b };
+	if d % g != 0 {
+		return None;
+	}
+	// x = a + s * k, where s / g * k = (b - a) / g mod t / g
+	let m = t / g;
+	let rhs = match b >= a {
+		true => (d / g) % m,
+		false => (m - (d / g) % m) % m,
+	};
+	let k = (rhs * mod_inverse((s / g) % m, m)) % m;
+	let mut x = a.checked_add(s.checked_mul(k)?)?;
+	// move up to the first shared nonce in both ranges
+	let lcm = (s / g).checked_mul(t)?;
+	let floor = if a > b { a } else { b };
+	if x < floor {
+		x = x.checked_add((floor - x + lcm - 1) / lcm * lcm)?;
+	}
+	Some((position(x, a, s), position(x, b, t)))
+}
+
+// The greatest common divisor of two non-zero numbers
+fn gcd(mut a: u128, mut b: u128) -> u128 {
+	while b != 0 {
+		let r = a % b;
+		a = b;
+		b = r;
+	}
+	a
+}
+
+// The inverse of a modulo m, where a and m are coprime, or 0 when m is 1
+fn mod_inverse(a: u128, m: u128) -> u128 {
+	if m == 1 {
+		return 0;
+	}
+	// extended Euclid, tracking the coefficients of a mod m
+	let (mut old_r, mut r) = (a as i128, m as i128);
+	let (mut old_c, mut c) = (1i128, 0i128);
+	while r != 0 {
+		let q = old_r / r;
+		let next_r = old_r - q * r;
+		old_r = r;
+		r = next_r;
+		let next_c = old_c - q * c;
+		old_c = c;
+		c = next_c;
+	}
+	old_c.rem_euclid(m as i128) as u128
+}
+
+/// Hands out the nonces of a [NonceRange](struct.NonceRange.html) in turn,
+/// offset from a starting nonce and optionally under a fixed extranonce
+/// prefix. When a prefix is set, only the bits below it change, wrapping
+/// around within the prefix's share of the nonce space. A generator with
+/// a limit stops once it's handed out that many nonces.
+
+#[derive(Debug, Clone)]
+pub struct NonceGenerator {
+	/// The nonce which the range is offset from
+	start: u64,
+
+	/// The nonces to hand out
+	range: NonceRange,
+
+	/// The prefix, already shifted into the top bits
+	prefix: u64,
+
+	/// How many top bits the prefix fixes
+	prefix_bits: u32,
+
+	/// How many nonces have been handed out
+	position: u64,
+
+	/// The last nonce handed out
+	last: Option<u64>,
+
+	/// The most nonces to hand out, if limited
+	limit: Option<u64>,
+}
+
+impl NonceGenerator {
+	/// Returns a generator for `range`, offset from `start`
+
+	pub fn new(start: u64, range: NonceRange) -> NonceGenerator {
+		NonceGenerator {
+			start: start,
+			range: range,
+			prefix: 0,
+			prefix_bits: 0,
+			position: 0,
+			last: None,
+			limit: None,
+		}
+	}
+
+	/// #Description
+	///
+	/// Fixes the top `prefix_bits` bits of every nonce to `prefix`, e.g.
+	/// an extranonce assigned by a pool.
+	///
+	/// #Arguments
+	///
+	/// * `prefix` (IN) The value of the fixed bits
+	/// * `prefix_bits` (IN) How many of the top bits are fixed. Must be
+	/// less than 64, and `prefix` must fit in this many bits.
+	///
+	/// #Returns
+	///
+	/// * The generator, if the prefix is valid
+	/// * A [CuckooMinerError](enum.CuckooMinerError.html)::ParameterError
+	/// otherwise
+	///
+
+	pub fn with_prefix(mut self, prefix: u64, prefix_bits: u32) -> Result<NonceGenerator, CuckooMinerError> {
+		if prefix_bits >= 64 || prefix >> prefix_bits != 0 {
+			return Err(CuckooMinerError::ParameterError(format!(
+				"Extranonce {:#x} doesn't fit in {} bits, or leaves no nonce bits",
+				prefix,
+				prefix_bits
+			)));
+		}
+		self.prefix = match prefix_bits {
+			0 => 0,
+			b => prefix << (64 - b),
+		};
+		self.prefix_bits = prefix_bits;
+		Ok(self)
+	}
+
+	/// Stops the generator once it's handed out `limit` nonces in all
+
+	pub fn with_limit(mut self, limit: u64) -> NonceGenerator {
+		self.limit = Some(limit);
+		self
+	}
+
+	/// #Description
+	///
+	/// Returns a generator for another range, offset from another start,
+	/// under the same prefix, e.g. for a new job. It starts from the
+	/// range's first nonce, with no limit.
+	///
+	/// #Arguments
+	///
+	/// * `start` (IN) The nonce the range is offset from
+	/// * `range` (IN) The nonces to hand out
+	///
+
+	pub fn restart(&self, start: u64, range: NonceRange) -> NonceGenerator {
+		NonceGenerator {
+			start: start,
+			range: range,
+			position: 0,
+			last: None,
+			limit: None,
+			..self.clone()
+		}
+	}
+
+	/// The nonce the range is offset from
+
+	pub fn start(&self) -> u64 {
+		self.start
+	}
+
+	/// The nonces handed out
+
+	pub fn range(&self) -> NonceRange {
+		self.range
+	}
+
+	/// How many more nonces will be handed out, or None if unlimited
+
+	pub fn remaining(&self) -> Option<u64> {
+		self.limit.map(|l| l.saturating_sub(self.position))
+	}
+
+	/// How many nonces have been handed out
+
+	pub fn position(&self) -> u64 {
+		self.position
+	}
+
+	/// The last nonce handed out, if any
+
+	pub fn last_nonce(&self) -> Option<u64> {
+		self.last
+	}
+}
+
+impl Iterator for NonceGenerator {
+	type Item = u64;
+
+	fn next(&mut self) -> Option<u64> {
+		if self.remaining() == Some(0) {
+			return None;
+		}
+		let mut nonce = self.start.wrapping_add(self.range.nth(self.position));
+		if self.prefix_bits > 0 {
+			nonce = self.prefix | (nonce & (u64::max_value() >> self.prefix_bits));
+		}
+		self.position = self.position.wrapping_add(1);
+		self.last = Some(nonce);
+		Some(nonce)
+	}
+}
@@ -0,0 +1,248 @@
+// Copyright 2017 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Checks a plugin works before it's trusted with mining, by giving it a
+//! header known to have a solution at its graph size and verifying the
+//! proof it returns.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::bench::BenchmarkMode;
+use super::watchdog::DEFAULT_STOP_TIMEOUT;
+use cuckoo_sys::manager::{PluginLibrary, CALL_CANCELLED};
+use cuckoo_sys::state::PluginState;
+use error::error::CuckooMinerError;
+use util::hex::from_hex;
+use verify::test_vectors::test_vector;
+use verify::verify::verify;
+
+/// How often the output queue and stats are read during an async self-test
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// The outcome of a plugin's [self_test](struct.PluginLibrary.html#method.self_test)
+
+#[derive(Debug, Clone)]
+pub struct SelfTestReport {
+	/// The plugin's path
+	pub plugin: String,
+
+	/// How the plugin was called
+	pub mode: BenchmarkMode,
+
+	/// The cuckoo size of the graph searched
+	pub cuckoo_size: u32,
+
+	/// The test header, in hex
+	pub header: String,
+
+	/// Whether the plugin returned a proof which verified
+	pub passed: bool,
+
+	/// How long the plugin took, from being given the header to its proof
+	/// being read, or until it gave up
+	pub graph_time: Duration,
+
+	/// Why the test failed, if it did
+	pub failure: Option<String>,
+}
+
+// How an attempt at the test header ended
+enum Attempt {
+	Solved(u32, Vec<u64>),
+	NotFound,
+	TimedOut,
+	Failed(String),
+}
+
+impl PluginLibrary {
+	/// #Description
+	///
+	/// Gives the plugin a header known to have a solution at its edge bits
+	/// and proof size, from the [test vectors](constant.TEST_VECTORS.html),
+	/// and verifies the proof it returns. The header goes through the
+	/// input queue if the plugin supports it, as it will when mining, and
+	/// otherwise through the synchronous call, cancelled at the timeout if
+	/// the plugin exports `cuckoo_call_cancellable`. Older plugins without
+	/// either can't be bounded, so are given as long as they take.
+	///
+	/// The plugin must be idle, and is left idle, with its queues cleared.
+	///
+	/// #Arguments
+	///
+	/// * `timeout` (IN) The longest time the plugin is given to find the
+	/// solution
+	///
+	/// #Returns
+	///
+	/// * `Ok(SelfTestReport)` once the attempt is over, which passed only if
+	/// the plugin returned a proof which verifies. Finding nothing, timing
+	/// out, a failing device or an invalid proof all fail the test.
+	/// * a NotSupported error if there's no test vector for the plugin's
+	/// graph size, or the plugin doesn't report one
+	/// * a PluginStateError if the plugin isn't idle
+	/// * another [CuckooMinerError](enum.CuckooMinerError.html) if the
+	/// plugin couldn't be set up for the test
+	///
+	/// #Example
+	///
+	/// ```
+	///  # use cuckoo_miner::PluginLibrary;
+	///  # use std::env;
+	///  # use std::path::PathBuf;
+	///  # use std::time::Duration;
+	///  # static DLL_SUFFIX: &str = ".cuckooplugin";
+	///  # let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+	///  # d.push(format!("./target/debug/plugins/lean_cpu_16{}", DLL_SUFFIX).as_str());
+	///  # let plugin_path = d.to_str().unwrap();
+	///  let pl = PluginLibrary::new(plugin_path).unwrap();
+	///  let report = pl.self_test(Duration::from_secs(30)).unwrap();
+	///  assert!(report.passed, "{:?}", report.failure);
+	/// ```
+	///
+
+	pub fn self_test(&self, timeout: Duration) -> Result<SelfTestReport, CuckooMinerError> {
+		let caps = self.capabilities()?;
+		let edge_bits = match caps.edge_bits {
+			Some(e) => e,
+			None => {
+				return Err(CuckooMinerError::NotSupported(format!(
+					"{} doesn't report its edge bits, so has no test vector",
+					self.lib_full_path
+				)))
+			}
+		};
+		let vector = match test_vector(edge_bits, caps.proof_size as usize) {
+			Some(v) => v,
+			None => {
+				return Err(CuckooMinerError::NotSupported(format!(
+					"No test vector for {} at {} edge bits and proof size {}",
+					self.lib_full_path,
+					edge_bits,
+					caps.proof_size
+				)))
+			}
+		};
+		if self.state() != PluginState::Idle {
+			return Err(CuckooMinerError::PluginStateError(format!(
+				"{} must be idle to self-test, but is {:?}",
+				self.lib_full_path,
+				self.state()
+			)));
+		}
+		let header = from_hex(vector.header)?;
+		let mode = if caps.supports_async { BenchmarkMode::Async } else { BenchmarkMode::Sync };
+
+		let start = Instant::now();
+		let attempt = match mode {
+			BenchmarkMode::Async => self.attempt_async(&header, timeout)?,
+			BenchmarkMode::Sync => self.attempt_sync(&header, timeout),
+		};
+		let graph_time = start.elapsed();
+
+		let failure = match attempt {
+			Attempt::Solved(cuckoo_size, proof) => match verify(&header, cuckoo_size, &proof) {
+				Ok(()) => None,
+				Err(e) => Some(format!("The proof returned is invalid: {:?}", e)),
+			},
+			Attempt::NotFound => Some(String::from("The graph was searched without finding its solution")),
+			Attempt::TimedOut => Some(format!("No solution was found within {:?}", timeout)),
+			Attempt::Failed(reason) => Some(reason),
+		};
+		if let Some(ref f) = failure {
+			warn!("{} failed its self-test: {}", self.lib_full_path, f);
+		}
+		Ok(SelfTestReport {
+			plugin: self.lib_full_path.clone(),
+			mode: mode,
+			cuckoo_size: vector.cuckoo_size,
+			header: String::from(vector.header),
+			passed: failure.is_none(),
+			graph_time: graph_time,
+			failure: failure,
+		})
+	}
+
+	// Pushes the header to the input queue and waits for its solution,
+	// putting the plugin back as it was afterwards
+
+	fn attempt_async(&self, header: &[u8], timeout: Duration) -> Result<Attempt, CuckooMinerError> {
+		let completed: u32 = self.stats()?.iter().map(|s| s.iterations_completed).sum();
+		self.start_processing()?;
+		let attempt = self.push_and_wait(header, timeout, completed);
+		let stopped = self.stop_and_wait(DEFAULT_STOP_TIMEOUT);
+		self.clear_input_queue()?;
+		self.clear_output_queue()?;
+		let attempt = attempt?;
+		stopped?;
+		Ok(attempt)
+	}
+
+	fn push_and_wait(&self, header: &[u8], timeout: Duration, completed: u32) -> Result<Attempt, CuckooMinerError> {
+		self.push_to_input_queue(0, header, &[0; 8])?;
+		let start = Instant::now();
+		loop {
+			// the stats are read first, so a graph counted as searched
+			// has had its solution queued before the queue is read
+			let stats = self.stats()?;
+			if let Some(s) = stats.iter().find(|s| s.has_errored) {
+				return Ok(Attempt::Failed(format!("Device {} failed: {}", s.device_id, s.error_reason)));
+			}
+			let searched = stats.iter().map(|s| s.iterations_completed).sum::<u32>() > completed;
+			if let Some(s) = self.drain_solutions(1).pop() {
+				return Ok(Attempt::Solved(s.cuckoo_size, s.solution_nonces));
+			}
+			if searched {
+				return Ok(Attempt::NotFound);
+			}
+			if start.elapsed() >= timeout {
+				return Ok(Attempt::TimedOut);
+			}
+			thread::sleep(POLL_INTERVAL);
+		}
+	}
+
+	// Calls the plugin synchronously, cancelling the call at the timeout
+	// if the plugin allows it
+
+	fn attempt_sync(&self, header: &[u8], timeout: Duration) -> Attempt {
+		let mut cuckoo_size = 0;
+		let mut proof = vec![0u32; self.proof_size()];
+		let code = if self.has_cancellable_call() {
+			let cancel = Arc::new(AtomicBool::new(false));
+			let (done, finished) = mpsc::channel::<()>();
+			let timer = {
+				let cancel = cancel.clone();
+				thread::spawn(move || if let Err(mpsc::RecvTimeoutError::Timeout) = finished.recv_timeout(timeout) {
+					cancel.store(true, Ordering::SeqCst);
+				})
+			};
+			let code = self.call_cuckoo_cancellable(header, &mut cuckoo_size, &mut proof, &cancel);
+			drop(done);
+			let _ = timer.join();
+			code
+		} else {
+			self.call_cuckoo(header, &mut cuckoo_size, &mut proof)
+		};
+		match code {
+			1 => Attempt::Solved(cuckoo_size, proof.into_iter().map(|n| n as u64).collect()),
+			0 => Attempt::NotFound,
+			CALL_CANCELLED => Attempt::TimedOut,
+			c => Attempt::Failed(format!("The plugin's call failed with code {}", c)),
+		}
+	}
+}
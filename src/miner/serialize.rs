@@ -0,0 +1,135 @@
+// Copyright 2017 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Serde helpers fixing the wire format of types that don't serialise
+//! the way callers need by default. Only built with the `serde` feature.
+
+/// Serialises a proof as an array of numbers
+pub mod proof {
+	use serde::{Serializer, Deserializer, Deserialize};
+	use serde::de::Error;
+
+	/// Serialises the proof nonces as a sequence
+	pub fn serialize<S>(nonces: &Vec<u64>, serializer: S) -> Result<S::Ok, S::Error>
+	where
+		S: Serializer,
+	{
+		serializer.collect_seq(nonces.iter())
+	}
+
+	/// Deserialises a sequence of proof nonces, which must be an even,
+	/// non-zero number to be a cycle
+	pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<u64>, D::Error>
+	where
+		D: Deserializer<'de>,
+	{
+		let v = Vec::<u64>::deserialize(deserializer)?;
+		if v.is_empty() || v.len() % 2 != 0 {
+			return Err(D::Error::invalid_length(v.len(), &"an even number of proof nonces"));
+		}
+		Ok(v)
+	}
+}
+
+/// Serialises a u64 as a decimal string, so it survives consumers which
+/// read numbers as doubles (e.g. JavaScript)
+pub mod u64_string {
+	use serde::{Serializer, Deserializer, Deserialize};
+	use serde::de::Error;
+
+	/// Serialises the value as a decimal string
+	pub fn serialize<S>(value: &u64, serializer: S) -> Result<S::Ok, S::Error>
+	where
+		S: Serializer,
+	{
+		serializer.serialize_str(&value.to_string())
+	}
+
+	/// Deserialises a decimal string
+	pub fn deserialize<'de, D>(deserializer: D) -> Result<u64, D::Error>
+	where
+		D: Deserializer<'de>,
+	{
+		let s = String::deserialize(deserializer)?;
+		s.parse().map_err(D::Error::custom)
+	}
+}
+
+/// Serialises a Duration as a number of milliseconds, with a fractional
+/// part, so timings read naturally in JSON
+pub mod duration_ms {
+	use std::time::Duration;
+
+	use serde::{Serializer, Deserializer, Deserialize};
+	use serde::de::Error;
+
+	/// Serialises the duration in milliseconds
+	pub fn serialize<S>(value: &Duration, serializer: S) -> Result<S::Ok, S::Error>
+	where
+		S: Serializer,
+	{
+		let ms = value.as_secs() as f64 * 1000.0 + value.subsec_nanos() as f64 / 1_000_000.0;
+		serializer.serialize_f64(ms)
+	}
+
+	/// Deserialises a non-negative number of milliseconds
+	pub fn deserialize<'de, D>(deserializer: D) -> Result<Duration, D::Error>
+	where
+		D: Deserializer<'de>,
+	{
+		let ms = f64::deserialize(deserializer)?;
+		if !(ms >= 0.0) || !ms.is_finite() {
+			return Err(D::Error::custom(format!("{} isn't a duration in milliseconds", ms)));
+		}
+		let nanos = (ms * 1_000_000.0).round() as u64;
+		Ok(Duration::new(nanos / 1_000_000_000, (nanos % 1_000_000_000) as u32))
+	}
+}
+
+/// Serialises an optional 32-byte hash as a hex string
+pub mod hash_hex {
+	use serde::{Serializer, Deserializer, Deserialize};
+	use serde::de::Error;
+
+	use util::hex::{to_hex, from_hex};
+
+	/// Serialises the hash as lower case hex, or none
+	pub fn serialize<S>(value: &Option<[u8; 32]>, serializer: S) -> Result<S::Ok, S::Error>
+	where
+		S: Serializer,
+	{
+		match *value {
+			Some(ref h) => serializer.serialize_some(&to_hex(h)),
+			None => serializer.serialize_none(),
+		}
+	}
+
+	/// Deserialises a hex string of exactly 32 bytes
+	pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<[u8; 32]>, D::Error>
+	where
+		D: Deserializer<'de>,
+	{
+		let s = match Option::<String>::deserialize(deserializer)? {
+			Some(s) => s,
+			None => return Ok(None),
+		};
+		let bytes = from_hex(&s).map_err(|e| D::Error::custom(format!("{:?}", e)))?;
+		if bytes.len() != 32 {
+			return Err(D::Error::invalid_length(bytes.len(), &"a 32 byte hash"));
+		}
+		let mut hash = [0; 32];
+		hash.copy_from_slice(&bytes);
+		Ok(Some(hash))
+	}
+}
@@ -0,0 +1,166 @@
+// Copyright 2017 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Iterators over the solutions a running job finds, from
+//! [CuckooMinerJobHandle::solutions](struct.CuckooMinerJobHandle.html#method.solutions),
+//! and with the `async` feature, streams from
+//! [solution_stream](struct.CuckooMinerJobHandle.html#method.solution_stream),
+//! as alternatives to polling the job handle. Each is sent its own copy
+//! of every solution found while it's alive, so any number can be used
+//! alongside each other and the handle's queue or event channel, and
+//! dropping one leaves mining and the others as they were, unless it's
+//! a stream which owns the job, from
+//! [CuckooMiner::notify_stream](struct.CuckooMiner.html#method.notify_stream).
+
+use std::sync::mpsc::{Receiver, Sender};
+#[cfg(feature = "async")]
+use std::pin::Pin;
+#[cfg(feature = "async")]
+use std::sync::atomic::Ordering;
+#[cfg(feature = "async")]
+use std::task::{Context, Poll};
+
+#[cfg(feature = "async")]
+use futures_channel::mpsc::{UnboundedSender, UnboundedReceiver};
+#[cfg(feature = "async")]
+use futures_core::Stream;
+
+use CuckooMinerSolution;
+#[cfg(feature = "async")]
+use CuckooMinerJobHandle;
+
+/// Where an iterator or stream over a job's solutions is sent them
+
+pub enum SolutionSender {
+	/// A [SolutionIter](struct.SolutionIter.html)
+	Blocking(Sender<CuckooMinerSolution>),
+
+	/// A stream
+	#[cfg(feature = "async")]
+	Async(UnboundedSender<CuckooMinerSolution>),
+}
+
+impl SolutionSender {
+	/// Sends a copy of a solution, returning false if the iterator or
+	/// stream has been dropped
+
+	pub fn send(&self, solution: &CuckooMinerSolution) -> bool {
+		match *self {
+			SolutionSender::Blocking(ref s) => s.send(solution.clone()).is_ok(),
+			#[cfg(feature = "async")]
+			SolutionSender::Async(ref s) => s.unbounded_send(solution.clone()).is_ok(),
+		}
+	}
+}
+
+/// A blocking iterator over the solutions a job finds from when the
+/// iterator was created, as they're found. They're those the job would
+/// otherwise report, i.e. verified and meeting the difficulty, for the
+/// job running at the time. The iterator ends once the job has stopped.
+
+pub struct SolutionIter {
+	/// Where the job sends the solutions
+	solutions: Receiver<CuckooMinerSolution>,
+}
+
+impl SolutionIter {
+	/// Returns an iterator over the solutions sent to `solutions`
+
+	pub fn new(solutions: Receiver<CuckooMinerSolution>) -> SolutionIter {
+		SolutionIter { solutions: solutions }
+	}
+}
+
+impl Iterator for SolutionIter {
+	type Item = CuckooMinerSolution;
+
+	/// Waits for the next solution, returning None once the job's stopped
+
+	fn next(&mut self) -> Option<CuckooMinerSolution> {
+		self.solutions.recv().ok()
+	}
+}
+
+/// #Description
+///
+/// A stream of the solutions a job finds, as they're found, for async
+/// code, which works with any executor. Like a
+/// [SolutionIter](struct.SolutionIter.html), it's sent the solutions the
+/// job would otherwise report, i.e. verified and meeting the difficulty,
+/// and ends once the job has stopped. Only available with the `async`
+/// feature.
+///
+/// A stream from
+/// [CuckooMiner::notify_stream](struct.CuckooMiner.html#method.notify_stream)
+/// owns its job, which is reached through [job](#method.job), e.g. to
+/// switch headers or read stats. Dropping it shuts the job down, as
+/// setting the job's [shutdown flag](struct.CuckooMinerJobHandle.html#method.shutdown_flag)
+/// does, without waiting for the plugins to stop.
+/// Streams from [CuckooMinerJobHandle::solution_stream](struct.CuckooMinerJobHandle.html#method.solution_stream)
+/// don't own the job, and leave it mining when dropped.
+///
+
+#[cfg(feature = "async")]
+pub struct SolutionStream {
+	/// Where the job sends the solutions
+	solutions: UnboundedReceiver<CuckooMinerSolution>,
+
+	/// The job, if the stream owns it
+	job: Option<CuckooMinerJobHandle>,
+}
+
+#[cfg(feature = "async")]
+impl SolutionStream {
+	/// Returns a stream over the solutions sent to `solutions`, owning
+	/// `job` if given
+
+	pub fn new(solutions: UnboundedReceiver<CuckooMinerSolution>, job: Option<CuckooMinerJobHandle>) -> SolutionStream {
+		SolutionStream {
+			solutions: solutions,
+			job: job,
+		}
+	}
+
+	/// The job, if the stream owns it
+
+	pub fn job(&self) -> Option<&CuckooMinerJobHandle> {
+		self.job.as_ref()
+	}
+
+	/// Takes the job out of the stream, so it's left mining when the
+	/// stream is dropped, and must be stopped through its handle instead
+
+	pub fn take_job(&mut self) -> Option<CuckooMinerJobHandle> {
+		self.job.take()
+	}
+}
+
+#[cfg(feature = "async")]
+impl Stream for SolutionStream {
+	type Item = CuckooMinerSolution;
+
+	fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<CuckooMinerSolution>> {
+		Pin::new(&mut self.solutions).poll_next(cx)
+	}
+}
+
+#[cfg(feature = "async")]
+impl Drop for SolutionStream {
+	fn drop(&mut self) {
+		if let Some(ref job) = self.job {
+			debug!("Solution stream dropped, shutting its job down");
+			job.shutdown_flag().store(true, Ordering::SeqCst);
+		}
+	}
+}
@@ -0,0 +1,492 @@
+// Copyright 2017 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Aggregates the per-device stats sampled from plugins into rolling
+//! graphs per second figures and moving averages, so callers don't each
+//! need to work them out from the raw timestamps and counters.
+
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+use cuckoo_sys::manager::{PluginLibrary, PluginDeviceStats};
+
+/// Default window over which graphs per second are averaged
+pub const DEFAULT_STATS_WINDOW: Duration = Duration::from_secs(60);
+
+/// Default minimum interval between stats samples
+pub const DEFAULT_SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The span of the shorter moving average in a
+/// [Hashrate](struct.Hashrate.html)
+pub const ONE_MINUTE: Duration = Duration::from_secs(60);
+
+/// The span of the longer moving average in a
+/// [Hashrate](struct.Hashrate.html), and the least time each sample is
+/// kept for
+pub const FIVE_MINUTES: Duration = Duration::from_secs(300);
+
+/// Graphs per second, averaged over the last minute, the last five
+/// minutes and the whole session. Until a device has been tracked for an
+/// average's full span, the average is over the time it has been tracked.
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Hashrate {
+	/// Graphs per second over the last minute
+	pub one_minute: f64,
+
+	/// Graphs per second over the last five minutes
+	pub five_minutes: f64,
+
+	/// Graphs per second since the device was first sampled
+	pub session: f64,
+}
+
+/// Recent history of a single device
+struct DeviceHistory {
+	/// When the device first appeared in a sample
+	first_seen: Instant,
+
+	/// When the device last appeared in a sample
+	last_seen: Instant,
+
+	/// iterations_completed as of the last sample
+	last_iterations: u32,
+
+	/// Graphs completed since the device first appeared
+	total: u64,
+
+	/// Whether the device reported an error in the last sample
+	errored: bool,
+
+	/// The device's temperature in the last sample, if it reports one
+	temperature_c: Option<f32>,
+
+	/// Graphs completed between samples, with the time of the sample,
+	/// over the window or five minutes, whichever is longer
+	graphs: VecDeque<(Instant, u32)>,
+}
+
+impl DeviceHistory {
+	fn new(now: Instant, stats: &PluginDeviceStats) -> DeviceHistory {
+		DeviceHistory {
+			first_seen: now,
+			last_seen: now,
+			last_iterations: stats.iterations_completed,
+			total: 0,
+			errored: stats.has_errored,
+			temperature_c: stats.temperature_c,
+			graphs: VecDeque::new(),
+		}
+	}
+
+	// Graphs per second over the last `window`, or over the time the
+	// device has been tracked if that's shorter
+	fn graphs_per_second(&self, now: Instant, window: Duration) -> f64 {
+		let span = match now > self.first_seen {
+			true => now.duration_since(self.first_seen),
+			false => return 0.0,
+		};
+		let span = if span < window { span } else { window };
+		let within = |t: Instant| now <= t || now.duration_since(t) < window;
+		let graphs: u64 = self.graphs
+			.iter()
+			.filter(|&&(t, _)| within(t))
+			.map(|&(_, g)| g as u64)
+			.sum();
+		graphs as f64 / as_secs(span)
+	}
+
+	fn hashrate(&self, now: Instant) -> Hashrate {
+		let session = match now > self.first_seen {
+			true => self.total as f64 / as_secs(now.duration_since(self.first_seen)),
+			false => 0.0,
+		};
+		Hashrate {
+			one_minute: self.graphs_per_second(now, ONE_MINUTE),
+			five_minutes: self.graphs_per_second(now, FIVE_MINUTES),
+			session: session,
+		}
+	}
+}
+
+// A duration in fractional seconds
+fn as_secs(d: Duration) -> f64 {
+	d.as_secs() as f64 + d.subsec_nanos() as f64 / 1_000_000_000.0
+}
+
+/// #Description
+///
+/// Samples plugin device stats and keeps a rolling record of completed
+/// graphs per device, averaged over a configurable window so the
+/// reported figure doesn't jump each time a single graph completes, and
+/// as 1-minute, 5-minute and session moving averages. Also counts the solutions found, and the graphs found with a cycle
+/// whether or not they met the target difficulty.
+///
+/// Devices are tracked by plugin index and device id. New devices are
+/// picked up as they appear. Devices that haven't been seen for a full
+/// window are dropped. If a plugin's counters go backwards, e.g. after
+/// `call_cuckoo_reset_processing`, the new count is taken as the number
+/// of graphs completed since the reset.
+
+pub struct StatsCollector {
+	/// Window over which graphs per second are averaged
+	window: Duration,
+
+	/// Minimum time between samples
+	sample_interval: Duration,
+
+	/// Time of the most recent sample
+	last_sample: Option<Instant>,
+
+	/// History per (plugin index, device id)
+	devices: HashMap<(usize, String), DeviceHistory>,
+
+	/// Total solutions found
+	solutions_found: u64,
+
+	/// Total graphs found with a cycle, whatever their difficulty
+	cycles_found: u64,
+
+	/// Total repeated solutions dropped
+	duplicates_suppressed: u64,
+
+	/// The last nonce queued for each plugin
+	nonce_positions: HashMap<usize, u64>,
+
+	/// Failed plugin calls and other plugin errors, per plugin
+	plugin_errors: HashMap<usize, u64>,
+
+	/// The duty cycle each throttled plugin has achieved
+	duty_cycles: HashMap<usize, f64>,
+
+	/// The depths of each plugin's input and output queues, as last
+	/// sampled
+	queue_depths: HashMap<usize, (u32, u32)>,
+}
+
+/// The state of a single device as of the most recent sample, as given by
+/// [StatsCollector::device_snapshots](struct.StatsCollector.html#method.device_snapshots)
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeviceSnapshot {
+	/// The index of the plugin the device belongs to
+	pub plugin_index: usize,
+
+	/// The device id, as the plugin reports it
+	pub device_id: String,
+
+	/// Graphs per second, averaged over the collector's window
+	pub graphs_per_second: f64,
+
+	/// Whether the device reported an error
+	pub has_errored: bool,
+
+	/// The device temperature in degrees Celsius, if the plugin reports it
+	pub temperature_c: Option<f32>,
+
+	/// The device's moving averages
+	pub hashrate: Hashrate,
+}
+
+impl Default for StatsCollector {
+	fn default() -> StatsCollector {
+		StatsCollector::new(DEFAULT_STATS_WINDOW, DEFAULT_SAMPLE_INTERVAL)
+	}
+}
+
+impl StatsCollector {
+	/// Returns a new collector averaging over `window`, sampling no more
+	/// often than `sample_interval`
+
+	pub fn new(window: Duration, sample_interval: Duration) -> StatsCollector {
+		StatsCollector {
+			window: window,
+			sample_interval: sample_interval,
+			last_sample: None,
+			devices: HashMap::new(),
+			solutions_found: 0,
+			cycles_found: 0,
+			duplicates_suppressed: 0,
+			nonce_positions: HashMap::new(),
+			plugin_errors: HashMap::new(),
+			duty_cycles: HashMap::new(),
+			queue_depths: HashMap::new(),
+		}
+	}
+
+	/// The window over which graphs per second are averaged
+	pub fn window(&self) -> Duration {
+		self.window
+	}
+
+	/// Changes the averaging window. Graphs older than the previous
+	/// window, or five minutes if that's longer, have already been
+	/// discarded, so a longer window only fills up as new samples arrive.
+
+	pub fn set_window(&mut self, window: Duration) {
+		self.window = window;
+	}
+
+	/// Whether the sample interval has passed since the last sample
+	pub fn sample_due(&self) -> bool {
+		match self.last_sample {
+			Some(t) => t.elapsed() >= self.sample_interval,
+			None => true,
+		}
+	}
+
+	/// #Description
+	///
+	/// Samples the stats of each of the given plugins, where a plugin's
+	/// index in `libraries` identifies it. Plugins whose stats can't be
+	/// read are skipped with a warning.
+
+	pub fn sample_libraries(&mut self, libraries: &[PluginLibrary]) {
+		let now = Instant::now();
+		for (i, l) in libraries.iter().enumerate() {
+			match l.stats() {
+				Ok(s) => self.sample_at(i, &s, now),
+				Err(e) => warn!("Unable to sample stats from {}: {:?}", l.lib_full_path, e),
+			}
+		}
+	}
+
+	/// Records a sample of the stats for the plugin at `plugin_index`,
+	/// taken now
+
+	pub fn sample(&mut self, plugin_index: usize, stats: &[PluginDeviceStats]) {
+		self.sample_at(plugin_index, stats, Instant::now());
+	}
+
+	/// #Description
+	///
+	/// Records a sample of the stats for the plugin at `plugin_index`,
+	/// taken at `now`. Samples are expected in time order.
+	///
+	/// #Arguments
+	///
+	/// * `plugin_index` (IN) The index of the plugin the stats came from
+	/// * `stats` (IN) The plugin's per-device stats
+	/// * `now` (IN) The time the stats were read
+	///
+
+	pub fn sample_at(&mut self, plugin_index: usize, stats: &[PluginDeviceStats], now: Instant) {
+		for s in stats {
+			let key = (plugin_index, s.device_id.clone());
+			if let Some(h) = self.devices.get_mut(&key) {
+				let completed = if s.iterations_completed >= h.last_iterations {
+					s.iterations_completed - h.last_iterations
+				} else {
+					// counters have been reset
+					s.iterations_completed
+				};
+				if completed > 0 {
+					h.graphs.push_back((now, completed));
+					h.total += completed as u64;
+				}
+				h.last_iterations = s.iterations_completed;
+				h.last_seen = now;
+				h.errored = s.has_errored;
+				h.temperature_c = s.temperature_c;
+				continue;
+			}
+			// graphs completed before the device was first seen aren't
+			// counted, as we don't know when they happened
+			self.devices.insert(key, DeviceHistory::new(now, s));
+		}
+
+		let window = self.window;
+		let kept = if window > FIVE_MINUTES { window } else { FIVE_MINUTES };
+		let older_than = |t: Instant, span: Duration| now > t && now.duration_since(t) >= span;
+		for h in self.devices.values_mut() {
+			while h.graphs.front().map_or(false, |&(t, _)| older_than(t, kept)) {
+				h.graphs.pop_front();
+			}
+		}
+		self.devices.retain(|_, h| !older_than(h.last_seen, window));
+		self.last_sample = Some(now);
+	}
+
+	/// Records that a solution has been found
+	pub fn record_solution(&mut self) {
+		self.solutions_found += 1;
+	}
+
+	/// Total number of solutions found
+	pub fn solutions_found(&self) -> u64 {
+		self.solutions_found
+	}
+
+	/// Records that a graph with a cycle has been found, whether or not
+	/// it met the target difficulty
+	pub fn record_cycle(&mut self) {
+		self.cycles_found += 1;
+	}
+
+	/// Total number of graphs found with a cycle, including those below
+	/// the target difficulty
+	pub fn cycles_found(&self) -> u64 {
+		self.cycles_found
+	}
+
+	/// Records that a repeated solution has been dropped
+	pub fn record_duplicate(&mut self) {
+		self.duplicates_suppressed += 1;
+	}
+
+	/// Total number of repeated solutions dropped
+	pub fn duplicates_suppressed(&self) -> u64 {
+		self.duplicates_suppressed
+	}
+
+	/// Records the last nonce queued for the plugin at `plugin_index`
+	pub fn record_nonce_position(&mut self, plugin_index: usize, nonce: u64) {
+		self.nonce_positions.insert(plugin_index, nonce);
+	}
+
+	/// The last nonce queued for the plugin at `plugin_index`, showing
+	/// how far through its nonce range it has got. None if nothing has
+	/// been queued for it yet.
+
+	pub fn nonce_position(&self, plugin_index: usize) -> Option<u64> {
+		self.nonce_positions.get(&plugin_index).cloned()
+	}
+
+	/// Records an error from the plugin at `plugin_index`, e.g. a failed
+	/// call or a malformed solution
+	pub fn record_plugin_error(&mut self, plugin_index: usize) {
+		*self.plugin_errors.entry(plugin_index).or_insert(0) += 1;
+	}
+
+	/// Total number of errors from the plugin at `plugin_index`
+	pub fn plugin_errors(&self, plugin_index: usize) -> u64 {
+		self.plugin_errors.get(&plugin_index).cloned().unwrap_or(0)
+	}
+
+	/// Records the share of the last window the plugin at `plugin_index`
+	/// spent on graphs, as a percentage, or None if it isn't throttled
+	pub fn record_duty_cycle(&mut self, plugin_index: usize, duty_cycle: Option<f64>) {
+		match duty_cycle {
+			Some(d) => self.duty_cycles.insert(plugin_index, d),
+			None => self.duty_cycles.remove(&plugin_index),
+		};
+	}
+
+	/// The share of the last window the plugin at `plugin_index` spent on
+	/// graphs, as a percentage, showing the effect of throttling it. None
+	/// if it isn't throttled.
+
+	pub fn duty_cycle(&self, plugin_index: usize) -> Option<f64> {
+		self.duty_cycles.get(&plugin_index).cloned()
+	}
+
+	/// Records the depths of the input and output queues of the plugin at
+	/// `plugin_index`, as read with
+	/// [PluginLibrary::queue_depths](struct.PluginLibrary.html#method.queue_depths)
+	pub fn record_queue_depths(&mut self, plugin_index: usize, depths: (u32, u32)) {
+		self.queue_depths.insert(plugin_index, depths);
+	}
+
+	/// The headers waiting in the input queue of the plugin at
+	/// `plugin_index`, and the solutions waiting in its output queue, as
+	/// last sampled. An empty input queue points to a plugin that isn't
+	/// being fed fast enough, a full output queue to solutions that aren't
+	/// being read. None if the plugin hasn't been sampled.
+
+	pub fn queue_depths(&self, plugin_index: usize) -> Option<(u32, u32)> {
+		self.queue_depths.get(&plugin_index).cloned()
+	}
+
+	/// Graphs per second across all devices, averaged over the window as
+	/// of the most recent sample. Devices which reported an error in the
+	/// most recent sample aren't counted.
+
+	pub fn graphs_per_second(&self) -> f64 {
+		let now = match self.last_sample {
+			Some(t) => t,
+			None => return 0.0,
+		};
+		self.devices
+			.values()
+			.filter(|h| !h.errored)
+			.map(|h| h.graphs_per_second(now, self.window))
+			.sum()
+	}
+
+	/// Graphs per second for a single device, averaged over the window as
+	/// of the most recent sample. None if the device isn't being tracked.
+
+	pub fn device_graphs_per_second(&self, plugin_index: usize, device_id: &str) -> Option<f64> {
+		let now = match self.last_sample {
+			Some(t) => t,
+			None => return None,
+		};
+		self.devices
+			.get(&(plugin_index, String::from(device_id)))
+			.map(|h| h.graphs_per_second(now, self.window))
+	}
+
+	/// Graphs per second across all devices as 1-minute, 5-minute and
+	/// session moving averages, as of the most recent sample, the sum of
+	/// each device's averages
+
+	pub fn hashrate(&self) -> Hashrate {
+		let now = match self.last_sample {
+			Some(t) => t,
+			None => return Hashrate::default(),
+		};
+		self.devices.values().map(|h| h.hashrate(now)).fold(Hashrate::default(), |t, h| Hashrate {
+			one_minute: t.one_minute + h.one_minute,
+			five_minutes: t.five_minutes + h.five_minutes,
+			session: t.session + h.session,
+		})
+	}
+
+	/// A single device's moving averages as of the most recent sample.
+	/// None if the device isn't being tracked.
+
+	pub fn device_hashrate(&self, plugin_index: usize, device_id: &str) -> Option<Hashrate> {
+		let now = match self.last_sample {
+			Some(t) => t,
+			None => return None,
+		};
+		self.devices
+			.get(&(plugin_index, String::from(device_id)))
+			.map(|h| h.hashrate(now))
+	}
+
+	/// Every device being tracked, as of the most recent sample, ordered
+	/// by plugin index then device id. Nothing is read from the plugins,
+	/// so this is cheap enough to call while they're mining.
+
+	pub fn device_snapshots(&self) -> Vec<DeviceSnapshot> {
+		let now = match self.last_sample {
+			Some(t) => t,
+			None => return Vec::new(),
+		};
+		let mut snapshots: Vec<DeviceSnapshot> = self.devices
+			.iter()
+			.map(|(&(plugin_index, ref device_id), h)| DeviceSnapshot {
+				plugin_index: plugin_index,
+				device_id: device_id.clone(),
+				graphs_per_second: h.graphs_per_second(now, self.window),
+				has_errored: h.errored,
+				temperature_c: h.temperature_c,
+				hashrate: h.hashrate(now),
+			})
+			.collect();
+		snapshots.sort_by(|a, b| (a.plugin_index, &a.device_id).cmp(&(b.plugin_index, &b.device_id)));
+		snapshots
+	}
+}
@@ -0,0 +1,583 @@
+// Copyright 2017 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A client for grin-style stratum pools, which takes jobs from the pool,
+//! mines them and submits the solutions back as shares.
+//!
+//! Messages are JSON-RPC 2.0 objects, one per line. The worker sends
+//! `login`, then `getjobtemplate`, and the pool pushes a `job` whenever
+//! there's new work, each with a `job_id`, `height`, `difficulty` and
+//! `pre_pow`, the header up to the nonce, in hex. A job with `clean_jobs`
+//! set invalidates every earlier one, as does a job for a greater height.
+//! Solutions are sent with `submit`.
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Write};
+use std::mem;
+use std::net::TcpStream;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use serde_json::{self, Value};
+
+use super::miner::{CuckooMiner, CuckooMinerJobHandle, CuckooMinerSolution};
+use error::error::CuckooMinerError;
+
+/// Default shortest wait before reconnecting to the pool
+pub const DEFAULT_MIN_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Default longest wait before reconnecting to the pool
+pub const DEFAULT_MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Default time solutions found while disconnected are kept for
+pub const DEFAULT_SOLUTION_BUFFER_TIME: Duration = Duration::from_secs(30);
+
+/// How long each read from the pool waits, so solutions and the stop
+/// flag are checked in between
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// How a [StratumWorker](struct.StratumWorker.html) connects to its pool
+
+#[derive(Debug, Clone)]
+pub struct StratumOptions {
+	/// The pool's address, as host:port
+	pub server: String,
+
+	/// The login, usually an account or wallet name
+	pub login: String,
+
+	/// The password, if the pool wants one
+	pub password: String,
+
+	/// The agent reported to the pool
+	pub agent: String,
+
+	/// Whether headers are hashed before they're mined, as for
+	/// [CuckooMiner::notify](struct.CuckooMiner.html#method.notify)
+	pub hash_header: bool,
+
+	/// The first wait before reconnecting, which doubles after each failed
+	/// attempt
+	pub min_backoff: Duration,
+
+	/// The longest wait before reconnecting
+	pub max_backoff: Duration,
+
+	/// How long solutions found while disconnected are kept, to be
+	/// submitted on reconnecting if their job is still current
+	pub solution_buffer_time: Duration,
+}
+
+impl Default for StratumOptions {
+	fn default() -> StratumOptions {
+		StratumOptions {
+			server: String::new(),
+			login: String::new(),
+			password: String::new(),
+			agent: format!("cuckoo-miner {}", env!("CARGO_PKG_VERSION")),
+			hash_header: true,
+			min_backoff: DEFAULT_MIN_BACKOFF,
+			max_backoff: DEFAULT_MAX_BACKOFF,
+			solution_buffer_time: DEFAULT_SOLUTION_BUFFER_TIME,
+		}
+	}
+}
+
+/// How a [StratumWorker](struct.StratumWorker.html) is getting on
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StratumStats {
+	/// Whether the worker is connected to the pool
+	pub connected: bool,
+
+	/// The number of times the worker has reconnected after losing the
+	/// pool
+	pub reconnects: u64,
+
+	/// The pool's id for the job being mined, if there is one
+	pub job_id: Option<u32>,
+
+	/// Shares sent to the pool
+	pub shares_submitted: u64,
+
+	/// Shares the pool has accepted
+	pub shares_accepted: u64,
+
+	/// Shares the pool has rejected
+	pub shares_rejected: u64,
+}
+
+/// A job from the pool
+
+struct StratumJob {
+	/// The job id, as the pool sent it
+	job_id: Value,
+
+	/// The job id the job is mined with
+	id: u32,
+
+	/// The block height the job is for
+	height: u64,
+
+	/// The header up to the nonce, in hex
+	pre_pow: String,
+}
+
+/// What each request awaiting a response was
+
+enum Pending {
+	Login,
+	GetJobTemplate,
+	Submit,
+}
+
+#[derive(Serialize)]
+struct Request<'a, T> {
+	id: String,
+	jsonrpc: &'a str,
+	method: &'a str,
+	params: T,
+}
+
+#[derive(Serialize)]
+struct LoginParams<'a> {
+	login: &'a str,
+	pass: &'a str,
+	agent: &'a str,
+}
+
+#[derive(Serialize)]
+struct SubmitParams<'a> {
+	edge_bits: u32,
+	height: u64,
+	job_id: &'a Value,
+	nonce: u64,
+	pow: &'a [u64],
+}
+
+#[derive(Deserialize)]
+struct JobParams {
+	job_id: Value,
+	height: u64,
+	difficulty: u64,
+	pre_pow: String,
+	#[serde(default)]
+	clean_jobs: bool,
+}
+
+/// A connection to the pool
+
+struct Connection {
+	reader: BufReader<TcpStream>,
+	writer: TcpStream,
+	/// A line read in part before a read timed out
+	line: Vec<u8>,
+}
+
+/// The worker thread's state
+
+struct Session {
+	options: StratumOptions,
+	stats: Arc<Mutex<StratumStats>>,
+	miner: Option<CuckooMiner>,
+	handle: Option<CuckooMinerJobHandle>,
+	/// Jobs whose solutions can still be submitted, oldest first
+	jobs: Vec<StratumJob>,
+	/// Solutions found while disconnected, with when they were found
+	buffered: Vec<(Instant, CuckooMinerSolution)>,
+	pending: HashMap<String, Pending>,
+	next_id: u64,
+}
+
+/// #Description
+///
+/// Mines for a grin-style stratum pool on its own thread. The worker logs
+/// in, starts the miner on the first job the pool sends, switches it to
+/// each new job with the pool's job id and share difficulty, and submits
+/// the solutions the miner finds. If the connection is lost, the miner
+/// carries on with the current job while the worker reconnects, waiting
+/// longer after each failed attempt, and solutions found meanwhile are
+/// submitted once it's back if their job is still current.
+///
+/// Job ids are passed to the miner as they are, so must be numbers which
+/// fit in a u32. Jobs with other ids are skipped with a warning.
+///
+/// #Example
+///
+/// ```no_run
+///  # use cuckoo_miner::{CuckooMiner, CuckooMinerConfig, StratumOptions, StratumWorker};
+///  let mut config = CuckooMinerConfig::new();
+///  config.plugin_full_path = String::from("mean_cpu_30");
+///  let miner = CuckooMiner::new(vec![config]).unwrap();
+///  let worker = StratumWorker::start(miner, StratumOptions {
+///  	server: String::from("pool.example.com:3416"),
+///  	login: String::from("my_wallet"),
+///  	..StratumOptions::default()
+///  });
+///  // ...
+///  println!("{:?}", worker.stats());
+///  worker.stop().unwrap();
+/// ```
+
+pub struct StratumWorker {
+	/// Set to stop the worker
+	stop: Arc<AtomicBool>,
+
+	/// Shared with the worker thread
+	stats: Arc<Mutex<StratumStats>>,
+
+	/// The worker thread, which returns why it stopped
+	thread: Option<JoinHandle<Result<(), CuckooMinerError>>>,
+}
+
+impl StratumWorker {
+	/// #Description
+	///
+	/// Starts a worker which connects to the pool and mines its jobs with
+	/// the given miner. Mining starts once the pool has sent a job.
+	///
+	/// #Arguments
+	///
+	/// * `miner` (IN) The miner, which the worker takes over
+	/// * `options` (IN) The pool to mine for, and how to connect to it
+	///
+	/// #Returns
+	///
+	/// The running worker
+	///
+
+	pub fn start(miner: CuckooMiner, options: StratumOptions) -> StratumWorker {
+		let stop = Arc::new(AtomicBool::new(false));
+		let stats = Arc::new(Mutex::new(StratumStats::default()));
+		let mut session = Session {
+			options: options,
+			stats: stats.clone(),
+			miner: Some(miner),
+			handle: None,
+			jobs: Vec::new(),
+			buffered: Vec::new(),
+			pending: HashMap::new(),
+			next_id: 0,
+		};
+		let thread_stop = stop.clone();
+		let thread = thread::spawn(move || {
+			let result = session.run(&thread_stop);
+			if let Some(ref h) = session.handle {
+				h.stop_jobs();
+			}
+			result
+		});
+		StratumWorker {
+			stop: stop,
+			stats: stats,
+			thread: Some(thread),
+		}
+	}
+
+	/// How the worker is getting on
+	pub fn stats(&self) -> StratumStats {
+		self.stats.lock().unwrap().clone()
+	}
+
+	/// #Description
+	///
+	/// Disconnects from the pool and stops mining, waiting for the miner's
+	/// plugins to stop.
+	///
+	/// #Returns
+	///
+	/// * `Ok(())` once stopped
+	/// * a [CuckooMinerError](enum.CuckooMinerError.html) if the worker
+	/// had already given up, e.g. because the miner couldn't be started
+	///
+
+	pub fn stop(mut self) -> Result<(), CuckooMinerError> {
+		self.shut_down()
+	}
+
+	fn shut_down(&mut self) -> Result<(), CuckooMinerError> {
+		self.stop.store(true, Ordering::SeqCst);
+		match self.thread.take().map(|t| t.join()) {
+			Some(Ok(result)) => result,
+			Some(Err(_)) => Err(CuckooMinerError::PluginProcessingError(
+				String::from("Stratum worker thread panicked"),
+			)),
+			None => Ok(()),
+		}
+	}
+}
+
+impl Drop for StratumWorker {
+	fn drop(&mut self) {
+		let _ = self.shut_down();
+	}
+}
+
+// Whether a read failed only because nothing arrived in time
+
+fn timed_out(e: &io::Error) -> bool {
+	e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::TimedOut
+}
+
+impl Session {
+	/// Connects and mines until stopped, reconnecting as needed
+	fn run(&mut self, stop: &AtomicBool) -> Result<(), CuckooMinerError> {
+		let mut backoff = self.options.min_backoff;
+		let mut connected_before = false;
+		while !stop.load(Ordering::SeqCst) {
+			match self.connect() {
+				Ok(mut c) => {
+					info!("Stratum: Connected to {}", self.options.server);
+					if connected_before {
+						self.stats.lock().unwrap().reconnects += 1;
+					}
+					connected_before = true;
+					backoff = self.options.min_backoff;
+					self.stats.lock().unwrap().connected = true;
+					let result = self.serve(&mut c, stop);
+					self.stats.lock().unwrap().connected = false;
+					self.pending.clear();
+					match result {
+						Ok(()) => return Ok(()),
+						Err(SessionError::Disconnected(e)) => {
+							warn!("Stratum: Lost {}: {}", self.options.server, e)
+						}
+						Err(SessionError::Miner(e)) => return Err(e),
+					}
+				}
+				Err(e) => warn!("Stratum: Unable to connect to {}: {}", self.options.server, e),
+			}
+			// keep mining the current job, and hold on to what's found
+			let retry_at = Instant::now() + backoff;
+			while !stop.load(Ordering::SeqCst) && Instant::now() < retry_at {
+				self.buffer_solutions();
+				thread::sleep(POLL_INTERVAL);
+			}
+			backoff = ::std::cmp::min(backoff * 2, self.options.max_backoff);
+		}
+		Ok(())
+	}
+
+	/// Opens a connection and logs in, then asks for a job and submits
+	/// anything found while disconnected
+	fn connect(&mut self) -> io::Result<Connection> {
+		let stream = TcpStream::connect(self.options.server.as_str())?;
+		stream.set_read_timeout(Some(POLL_INTERVAL))?;
+		let mut c = Connection {
+			reader: BufReader::new(stream.try_clone()?),
+			writer: stream,
+			line: Vec::new(),
+		};
+		let options = self.options.clone();
+		self.send(
+			&mut c,
+			"login",
+			Pending::Login,
+			LoginParams {
+				login: &options.login,
+				pass: &options.password,
+				agent: &options.agent,
+			},
+		)?;
+		self.send(&mut c, "getjobtemplate", Pending::GetJobTemplate, ())?;
+		Ok(c)
+	}
+
+	/// Handles messages from the pool and submits solutions until stopped
+	/// or disconnected
+	fn serve(&mut self, c: &mut Connection, stop: &AtomicBool) -> Result<(), SessionError> {
+		let buffered = mem::replace(&mut self.buffered, Vec::new());
+		let expiry = self.options.solution_buffer_time;
+		for (found, solution) in buffered {
+			if found.elapsed() <= expiry {
+				self.submit(c, &solution)?;
+			}
+		}
+		while !stop.load(Ordering::SeqCst) {
+			match c.reader.read_until(b'\n', &mut c.line) {
+				Ok(0) => return Err(SessionError::Disconnected(String::from("Connection closed"))),
+				Ok(_) => {
+					let line = mem::replace(&mut c.line, Vec::new());
+					self.handle_message(&line)?;
+				}
+				Err(ref e) if timed_out(e) => {}
+				Err(e) => return Err(SessionError::Disconnected(e.to_string())),
+			}
+			for solution in self.take_solutions() {
+				self.submit(c, &solution)?;
+			}
+		}
+		Ok(())
+	}
+
+	/// Sends a request, remembering what it was for its response
+	fn send<T: ::serde::Serialize>(
+		&mut self,
+		c: &mut Connection,
+		method: &str,
+		pending: Pending,
+		params: T,
+	) -> io::Result<()> {
+		let id = self.next_id.to_string();
+		self.next_id += 1;
+		let request = Request {
+			id: id.clone(),
+			jsonrpc: "2.0",
+			method: method,
+			params: params,
+		};
+		let mut line = serde_json::to_vec(&request).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+		line.push(b'\n');
+		c.writer.write_all(&line)?;
+		self.pending.insert(id, pending);
+		Ok(())
+	}
+
+	/// Handles a notification or response from the pool
+	fn handle_message(&mut self, line: &[u8]) -> Result<(), SessionError> {
+		let message: Value = match serde_json::from_slice(line) {
+			Ok(m) => m,
+			Err(e) => {
+				warn!("Stratum: Ignoring malformed message {}: {}", String::from_utf8_lossy(line), e);
+				return Ok(());
+			}
+		};
+		if let Some(method) = message.get("method").and_then(|m| m.as_str()) {
+			match method {
+				"job" => return self.new_job(message.get("params")),
+				m => debug!("Stratum: Ignoring {} from the pool", m),
+			}
+			return Ok(());
+		}
+		let id = match message.get("id") {
+			Some(&Value::String(ref s)) => s.clone(),
+			Some(v) => v.to_string(),
+			None => return Ok(()),
+		};
+		let error = message.get("error").filter(|e| !e.is_null());
+		match (self.pending.remove(&id), error) {
+			(Some(Pending::Login), Some(e)) => warn!("Stratum: Login refused: {}", e),
+			(Some(Pending::Login), None) => debug!("Stratum: Logged in as {}", self.options.login),
+			(Some(Pending::GetJobTemplate), Some(e)) => warn!("Stratum: No job template: {}", e),
+			(Some(Pending::GetJobTemplate), None) => return self.new_job(message.get("result")),
+			(Some(Pending::Submit), Some(e)) => {
+				warn!("Stratum: Share rejected: {}", e);
+				self.stats.lock().unwrap().shares_rejected += 1;
+			}
+			(Some(Pending::Submit), None) => self.stats.lock().unwrap().shares_accepted += 1,
+			(None, _) => debug!("Stratum: Ignoring response to unknown request {}", id),
+		}
+		Ok(())
+	}
+
+	/// Starts mining a job from the pool
+	fn new_job(&mut self, params: Option<&Value>) -> Result<(), SessionError> {
+		let job: JobParams = match params.map(|p| serde_json::from_value(p.clone())) {
+			Some(Ok(j)) => j,
+			Some(Err(e)) => {
+				warn!("Stratum: Ignoring malformed job: {}", e);
+				return Ok(());
+			}
+			None => {
+				warn!("Stratum: Ignoring job without params");
+				return Ok(());
+			}
+		};
+		let id = match job.job_id.as_u64() {
+			Some(id) if id <= u32::max_value() as u64 => id as u32,
+			_ => {
+				warn!("Stratum: Ignoring job with unusable id {}", job.job_id);
+				return Ok(());
+			}
+		};
+		if let Some(current) = self.jobs.last() {
+			// e.g. resent on reconnecting
+			if current.id == id && current.pre_pow == job.pre_pow {
+				return Ok(());
+			}
+		}
+		if job.clean_jobs {
+			self.jobs.clear();
+		}
+		self.jobs.retain(|j| j.height >= job.height && j.id != id);
+		debug!("Stratum: New job {} at height {}, difficulty {}", id, job.height, job.difficulty);
+		match self.handle {
+			Some(ref h) => h.notify(id, &job.pre_pow, "", job.difficulty).map_err(SessionError::Miner)?,
+			None => {
+				let miner = self.miner.take().unwrap();
+				let handle = miner
+					.notify(id, &job.pre_pow, "", job.difficulty, self.options.hash_header)
+					.map_err(SessionError::Miner)?;
+				self.handle = Some(handle);
+			}
+		}
+		self.jobs.push(StratumJob {
+			job_id: job.job_id,
+			id: id,
+			height: job.height,
+			pre_pow: job.pre_pow,
+		});
+		self.stats.lock().unwrap().job_id = Some(id);
+		Ok(())
+	}
+
+	/// Takes the solutions the miner has found
+	fn take_solutions(&self) -> Vec<CuckooMinerSolution> {
+		self.handle.as_ref().and_then(|h| h.get_solutions()).unwrap_or_default()
+	}
+
+	/// Holds on to the miner's solutions while disconnected, dropping
+	/// those held too long
+	fn buffer_solutions(&mut self) {
+		let solutions = self.take_solutions();
+		let now = Instant::now();
+		let expiry = self.options.solution_buffer_time;
+		self.buffered.retain(|&(found, _)| found.elapsed() <= expiry);
+		self.buffered.extend(solutions.into_iter().map(|s| (now, s)));
+	}
+
+	/// Submits a solution, if its job is still current
+	fn submit(&mut self, c: &mut Connection, solution: &CuckooMinerSolution) -> Result<(), SessionError> {
+		let (job_id, height) = match self.jobs.iter().find(|j| j.id == solution.id) {
+			Some(j) => (j.job_id.clone(), j.height),
+			None => {
+				debug!("Stratum: Dropping solution for stale job {}", solution.id);
+				return Ok(());
+			}
+		};
+		let params = SubmitParams {
+			edge_bits: solution.cuckoo_size.saturating_sub(1),
+			height: height,
+			job_id: &job_id,
+			nonce: solution.nonce,
+			pow: &solution.solution_nonces,
+		};
+		self.send(c, "submit", Pending::Submit, params)
+			.map_err(|e| SessionError::Disconnected(e.to_string()))?;
+		self.stats.lock().unwrap().shares_submitted += 1;
+		Ok(())
+	}
+}
+
+/// Why a session with the pool ended
+
+enum SessionError {
+	/// The connection was lost, so the worker reconnects
+	Disconnected(String),
+
+	/// The miner failed, so the worker gives up
+	Miner(CuckooMinerError),
+}
@@ -0,0 +1,142 @@
+// Copyright 2017 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Spots devices running too hot, from the temperatures they report in
+//! their stats, so a job can pause them until they've cooled down.
+
+use std::collections::HashSet;
+
+use cuckoo_sys::manager::PluginDeviceStats;
+
+/// Default number of degrees a paused device must cool below the maximum
+/// temperature before it's resumed
+pub const DEFAULT_TEMPERATURE_HYSTERESIS: f32 = 5.0;
+
+/// A device crossing one of a
+/// [ThermalMonitor](struct.ThermalMonitor.html)'s thresholds
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ThermalChange {
+	/// The device has exceeded the maximum temperature, and should be
+	/// paused
+	Paused {
+		/// The device id, as reported in its stats
+		device_id: u32,
+		/// The temperature reported, in degrees Celsius
+		temperature_c: f32,
+	},
+
+	/// The device has cooled below the resume threshold, and can mine
+	/// again
+	Resumed {
+		/// The device id, as reported in its stats
+		device_id: u32,
+		/// The temperature reported, in degrees Celsius
+		temperature_c: f32,
+	},
+}
+
+/// #Description
+///
+/// Watches the temperature each device reports in its stats. A device is
+/// reported as paused once it exceeds the maximum, and as resumed once
+/// it drops below the maximum less the hysteresis, so a device hovering
+/// around the maximum isn't toggled on every reading. Devices which
+/// don't report a temperature, such as CPUs, are never paused.
+
+pub struct ThermalMonitor {
+	/// The temperature above which a device is paused
+	max_temperature: f32,
+
+	/// How far below the maximum a paused device must cool to resume
+	hysteresis: f32,
+
+	/// The plugin index and device id of each paused device
+	paused: HashSet<(usize, u32)>,
+}
+
+impl ThermalMonitor {
+	/// Returns a new monitor, pausing devices above `max_temperature`
+	/// degrees Celsius, with the default hysteresis
+
+	pub fn new(max_temperature: f32) -> ThermalMonitor {
+		ThermalMonitor {
+			max_temperature: max_temperature,
+			hysteresis: DEFAULT_TEMPERATURE_HYSTERESIS,
+			paused: HashSet::new(),
+		}
+	}
+
+	/// Sets how many degrees below the maximum a paused device must cool
+	/// before it's resumed
+
+	pub fn with_hysteresis(mut self, hysteresis: f32) -> ThermalMonitor {
+		self.hysteresis = hysteresis;
+		self
+	}
+
+	/// The temperature above which a device is paused
+	pub fn max_temperature(&self) -> f32 {
+		self.max_temperature
+	}
+
+	/// #Description
+	///
+	/// Checks the latest stats of the plugin at `plugin_index` against the
+	/// thresholds.
+	///
+	/// #Arguments
+	///
+	/// * `plugin_index` (IN) The index of the plugin the stats came from
+	/// * `stats` (IN) The plugin's per-device stats
+	///
+	/// #Returns
+	///
+	/// The devices which have crossed a threshold since the last check,
+	/// in the order they appear in the stats
+	///
+
+	pub fn check(&mut self, plugin_index: usize, stats: &[PluginDeviceStats]) -> Vec<ThermalChange> {
+		let mut changes = Vec::new();
+		for s in stats {
+			let (device_id, temperature_c) = match (s.device_id.parse::<u32>(), s.temperature_c) {
+				(Ok(id), Some(t)) => (id, t),
+				_ => continue,
+			};
+			let key = (plugin_index, device_id);
+			if self.paused.contains(&key) {
+				if temperature_c < self.max_temperature - self.hysteresis {
+					self.paused.remove(&key);
+					changes.push(ThermalChange::Resumed {
+						device_id: device_id,
+						temperature_c: temperature_c,
+					});
+				}
+			} else if temperature_c > self.max_temperature {
+				self.paused.insert(key);
+				changes.push(ThermalChange::Paused {
+					device_id: device_id,
+					temperature_c: temperature_c,
+				});
+			}
+		}
+		changes
+	}
+
+	/// Whether a device is currently paused
+
+	pub fn is_paused(&self, plugin_index: usize, device_id: u32) -> bool {
+		self.paused.contains(&(plugin_index, device_id))
+	}
+}
@@ -0,0 +1,184 @@
+// Copyright 2017 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Finds the best values for a plugin's parameters, such as its thread
+//! or trim counts, by benchmarking every combination of the candidates.
+
+use std::time::Duration;
+
+use super::bench::BenchmarkReport;
+use cuckoo_sys::manager::CuckooPluginParameter;
+use error::error::CuckooMinerError;
+
+/// Default number of graphs attempted for each combination of values
+pub const DEFAULT_TUNING_GRAPHS: u32 = 5;
+
+/// How a [CuckooMiner::autotune](struct.CuckooMiner.html#method.autotune)
+/// sweep is run
+
+#[derive(Debug, Clone)]
+pub struct TuningOptions {
+	/// The number of graphs attempted for each combination of values
+	pub graphs: u32,
+
+	/// A combination is abandoned as soon as a graph takes longer than
+	/// this, if set
+	pub max_graph_time: Option<Duration>,
+
+	/// The seed for the benchmark headers, so sweeps are repeatable
+	pub header_seed: u64,
+}
+
+impl Default for TuningOptions {
+	fn default() -> TuningOptions {
+		TuningOptions {
+			graphs: DEFAULT_TUNING_GRAPHS,
+			max_graph_time: None,
+			header_seed: 0,
+		}
+	}
+}
+
+/// The measurements for one combination of parameter values
+
+#[derive(Debug, Clone)]
+pub struct TuningPoint {
+	/// The value of each swept parameter, in the order they were given
+	pub values: Vec<(String, u32)>,
+
+	/// The benchmark of the graphs attempted with these values
+	pub report: BenchmarkReport,
+
+	/// Whether the combination was abandoned because a graph took longer
+	/// than the ceiling, in which case it can't be picked
+	pub aborted: bool,
+}
+
+/// The outcome of a sweep
+
+#[derive(Debug, Clone)]
+pub struct TuningResult {
+	/// The values which gave the most graphs per second
+	pub best: Vec<(String, u32)>,
+
+	/// The graphs per second with the best values
+	pub best_graphs_per_second: f64,
+
+	/// Every combination tried, in the order they were tried
+	pub points: Vec<TuningPoint>,
+}
+
+impl TuningResult {
+	/// #Description
+	///
+	/// Picks the combination with the most graphs per second from those
+	/// which weren't abandoned. Ties go to the first tried.
+	///
+	/// #Returns
+	///
+	/// * `Ok(TuningResult)` with the best combination and every point
+	/// * A PluginProcessingError if every combination was abandoned
+	///
+
+	pub fn from_points(points: Vec<TuningPoint>) -> Result<TuningResult, CuckooMinerError> {
+		let (best, best_graphs_per_second) = {
+			let best = points.iter().filter(|p| !p.aborted).fold(None, |best: Option<&TuningPoint>, p| {
+				match best {
+					Some(b) if b.report.graphs_per_second >= p.report.graphs_per_second => Some(b),
+					_ => Some(p),
+				}
+			});
+			match best {
+				Some(b) => (b.values.clone(), b.report.graphs_per_second),
+				None => {
+					return Err(CuckooMinerError::PluginProcessingError(String::from(
+						"Every combination of values took too long",
+					)))
+				}
+			}
+		};
+		Ok(TuningResult {
+			best: best,
+			best_graphs_per_second: best_graphs_per_second,
+			points: points,
+		})
+	}
+}
+
+/// #Description
+///
+/// Checks the values to sweep against the parameters a plugin declares,
+/// dropping those outside a parameter's declared range with a warning.
+///
+/// #Returns
+///
+/// * `Ok(Vec<(String, Vec<u32>)>)` with the values in range
+/// * A ParameterNotFoundError if the plugin doesn't declare a parameter
+/// * A ParameterOutOfRange error for the first value of a parameter none
+/// of whose values are in range
+///
+
+pub fn values_in_range(
+	sweep: &[(&str, &[u32])],
+	parameters: &[CuckooPluginParameter],
+) -> Result<Vec<(String, Vec<u32>)>, CuckooMinerError> {
+	let mut checked = Vec::new();
+	for &(name, values) in sweep {
+		let p = match parameters.iter().find(|p| p.name == name) {
+			Some(p) => p,
+			None => return Err(CuckooMinerError::ParameterNotFoundError(String::from(name))),
+		};
+		let in_range = |v: &u32| *v as u64 >= p.min_value && *v as u64 <= p.max_value;
+		for v in values.iter().filter(|v| !in_range(v)) {
+			warn!(
+				"Not trying {} = {}, outside its range of {} to {}",
+				name,
+				v,
+				p.min_value,
+				p.max_value
+			);
+		}
+		let kept: Vec<u32> = values.iter().cloned().filter(|v| in_range(v)).collect();
+		if kept.is_empty() {
+			return Err(CuckooMinerError::ParameterOutOfRange {
+				name: String::from(name),
+				value: values.first().cloned().unwrap_or(0) as u64,
+				min: p.min_value,
+				max: p.max_value,
+			});
+		}
+		checked.push((String::from(name), kept));
+	}
+	Ok(checked)
+}
+
+/// Every combination of the given values, varying the last parameter
+/// fastest. No parameters gives a single empty combination.
+
+pub fn combinations(sweep: &[(String, Vec<u32>)]) -> Vec<Vec<(String, u32)>> {
+	let mut combos = vec![Vec::new()];
+	for &(ref name, ref values) in sweep {
+		combos = combos
+			.iter()
+			.flat_map(|c| {
+				values.iter().map(move |v| {
+					let mut c = c.clone();
+					c.push((name.clone(), *v));
+					c
+				})
+			})
+			.collect();
+	}
+	combos
+}
@@ -0,0 +1,309 @@
+// Copyright 2017 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Saves the parameter values plugins are running with to a TOML file,
+//! and applies them again at the next start, so tuning only has to be
+//! done once.
+
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+
+use toml::{self, Value};
+use toml::value::Table;
+
+use super::miner::CuckooMinerConfig;
+use cuckoo_sys::manager::{PluginLibrary, CuckooPluginParameter};
+use error::error::CuckooMinerError;
+
+/// The key of a plugin's table holding the values for every device
+const PARAMETERS_KEY: &str = "parameters";
+
+/// The key of a plugin's table holding the values for single devices,
+/// keyed by device id
+const DEVICES_KEY: &str = "devices";
+
+// The name plugins are saved under, their file name without extension
+
+fn plugin_name(library: &PluginLibrary) -> String {
+	Path::new(&library.lib_full_path)
+		.file_stem()
+		.map(|s| s.to_string_lossy().into_owned())
+		.unwrap_or_default()
+}
+
+/// #Description
+///
+/// Writes the current value of every parameter each plugin declares to a
+/// TOML file, with a table per plugin keyed by its name. A device whose
+/// value differs from the plugin's is saved under the plugin's `devices`
+/// table, keyed by device id. Values are read back from the plugins, so
+/// the file holds what they're actually running with. Where a plugin is
+/// loaded more than once, the first instance's values are saved.
+///
+/// #Arguments
+///
+/// * `libraries` (IN) The loaded plugins
+/// * `path` (IN) The file to write, which is replaced if it exists
+///
+/// #Returns
+///
+/// * `Ok(())` if the file was written
+/// * a ConfigError if the file can't be written
+/// * another [CuckooMinerError](enum.CuckooMinerError.html) if a
+/// plugin's parameters can't be read
+///
+/// #Example
+///
+/// ```toml
+/// [lean_cuda_30.parameters]
+/// N_TRIMS = 176
+///
+/// [lean_cuda_30.devices.1]
+/// GENU_BLOCKS = 64
+/// ```
+///
+
+pub fn save_tuning<P: AsRef<Path>>(libraries: &[PluginLibrary], path: P) -> Result<(), CuckooMinerError> {
+	let path = path.as_ref();
+	let mut root = Table::new();
+	for library in libraries {
+		let name = plugin_name(library);
+		if root.contains_key(&name) {
+			continue;
+		}
+		root.insert(name, Value::Table(plugin_values(library)?));
+	}
+	let contents = toml::to_string(&Value::Table(root)).map_err(|e| {
+		CuckooMinerError::ConfigError(format!("Unable to write {}: {}", path.display(), e))
+	})?;
+	File::create(path)
+		.and_then(|mut f| f.write_all(contents.as_bytes()))
+		.map_err(|e| {
+			CuckooMinerError::ConfigError(format!("Unable to write {}: {}", path.display(), e))
+		})
+}
+
+// Reads the values of a plugin's parameters, for the plugin and for any
+// device which differs from it. Per-device values are read through the
+// 32-bit call, so 64-bit parameters are only saved for the plugin.
+
+fn plugin_values(library: &PluginLibrary) -> Result<Table, CuckooMinerError> {
+	let parameters = library.parameters()?;
+	let mut values = Table::new();
+	for p in &parameters {
+		let value = library.get_parameter_checked(&p.name)?;
+		values.insert(p.name.clone(), Value::Integer(value as i64));
+	}
+	let mut devices = Table::new();
+	for device in library.list_devices()? {
+		let mut device_values = Table::new();
+		for p in parameters.iter().filter(|p| !p.is_64_bit()) {
+			let value = Value::Integer(library.get_parameter_for_device(&p.name, device.device_id)? as i64);
+			if values.get(&p.name) != Some(&value) {
+				device_values.insert(p.name.clone(), value);
+			}
+		}
+		if !device_values.is_empty() {
+			devices.insert(device.device_id.to_string(), Value::Table(device_values));
+		}
+	}
+	let mut table = Table::new();
+	table.insert(String::from(PARAMETERS_KEY), Value::Table(values));
+	if !devices.is_empty() {
+		table.insert(String::from(DEVICES_KEY), Value::Table(devices));
+	}
+	Ok(table)
+}
+
+/// #Description
+///
+/// Applies the parameter values saved by [save_tuning](fn.save_tuning.html)
+/// to every loaded plugin with a table in the file, and records them in
+/// the plugins' configs so they survive a reload. Plugins change between
+/// versions, so a parameter a plugin no longer declares, or a device it no
+/// longer has, is skipped with a warning, and a value outside the
+/// parameter's current range is clamped to it with a warning. Tables for
+/// plugins which aren't loaded are ignored.
+///
+/// #Arguments
+///
+/// * `libraries` (IN) The loaded plugins
+/// * `configs` (IN/OUT) The plugins' configs, in the same order
+/// * `path` (IN) The file to read
+///
+/// #Returns
+///
+/// * `Ok(())` if the values were applied
+/// * a ConfigError if the file can't be read, isn't valid TOML, or a
+/// value isn't an integer
+/// * another [CuckooMinerError](enum.CuckooMinerError.html) if a plugin
+/// refuses a value, in which case the values before it stay set
+///
+
+pub fn load_tuning<P: AsRef<Path>>(
+	libraries: &[PluginLibrary],
+	configs: &mut [CuckooMinerConfig],
+	path: P,
+) -> Result<(), CuckooMinerError> {
+	let path = path.as_ref();
+	let mut contents = String::new();
+	File::open(path)
+		.and_then(|mut f| f.read_to_string(&mut contents))
+		.map_err(|e| {
+			CuckooMinerError::ConfigError(format!("Unable to read {}: {}", path.display(), e))
+		})?;
+	let root = match contents.parse::<Value>() {
+		Ok(Value::Table(t)) => t,
+		Ok(_) => return Err(CuckooMinerError::ConfigError(format!("{}: Expected a table", path.display()))),
+		Err(e) => return Err(CuckooMinerError::ConfigError(format!("{}: Invalid TOML: {}", path.display(), e))),
+	};
+	for (library, config) in libraries.iter().zip(configs.iter_mut()) {
+		let name = plugin_name(library);
+		match root.get(&name) {
+			Some(&Value::Table(ref t)) => {
+				apply_values(library, config, &name, t).map_err(|e| match e {
+					CuckooMinerError::ConfigError(m) => {
+						CuckooMinerError::ConfigError(format!("{}: {}", path.display(), m))
+					}
+					e => e,
+				})?
+			}
+			Some(_) => {
+				return Err(CuckooMinerError::ConfigError(
+					format!("{}: {} should be a table", path.display(), name),
+				))
+			}
+			None => debug!("No saved tuning for {} in {}", name, path.display()),
+		}
+	}
+	Ok(())
+}
+
+// Applies one plugin's saved values, the plugin's own first, then each
+// device's
+
+fn apply_values(
+	library: &PluginLibrary,
+	config: &mut CuckooMinerConfig,
+	plugin: &str,
+	table: &Table,
+) -> Result<(), CuckooMinerError> {
+	let parameters = library.parameters()?;
+	for (name, value) in sub_table(table, PARAMETERS_KEY, plugin)? {
+		let value = match declared_value(&parameters, plugin, None, name, value)? {
+			Some(v) => v,
+			None => continue,
+		};
+		library.set_parameter_checked(name, value)?;
+		// the config only holds 32-bit values, so larger ones are applied
+		// but not kept over a reload
+		if value <= u32::max_value() as u64 {
+			config.parameter_list.retain(|p| p.0 != *name);
+			config.parameter_list.push((name.clone(), 0, value as u32));
+		}
+	}
+	let devices = library.list_devices()?;
+	for (id, values) in sub_table(table, DEVICES_KEY, plugin)? {
+		let device_id = match id.parse::<u32>() {
+			Ok(d) if devices.iter().any(|info| info.device_id == d) => d,
+			_ => {
+				warn!("Skipping saved tuning for device {} of {}, which it doesn't have", id, plugin);
+				continue;
+			}
+		};
+		let values = match *values {
+			Value::Table(ref t) => t,
+			_ => return Err(CuckooMinerError::ConfigError(format!("{}.{}.{} should be a table", plugin, DEVICES_KEY, id))),
+		};
+		for (name, value) in values {
+			let value = match declared_value(&parameters, plugin, Some(device_id), name, value)? {
+				Some(v) if v <= u32::max_value() as u64 => v as u32,
+				Some(_) => {
+					warn!("Skipping saved {} for device {} of {}, which is too large", name, device_id, plugin);
+					continue;
+				}
+				None => continue,
+			};
+			library.set_parameter_for_device(name, device_id, value)?;
+			config.device_parameters
+				.entry(device_id)
+				.or_insert_with(Default::default)
+				.insert(name.clone(), value);
+		}
+	}
+	Ok(())
+}
+
+// A table in a plugin's saved values, which is empty if absent
+
+fn sub_table<'a>(table: &'a Table, key: &str, plugin: &str) -> Result<Vec<(&'a String, &'a Value)>, CuckooMinerError> {
+	match table.get(key) {
+		Some(&Value::Table(ref t)) => Ok(t.iter().collect()),
+		Some(_) => Err(CuckooMinerError::ConfigError(format!("{}.{} should be a table", plugin, key))),
+		None => Ok(Vec::new()),
+	}
+}
+
+// Checks a saved value against the parameter the plugin declares, giving
+// None if the plugin no longer declares it, and clamping it to the
+// declared range
+
+fn declared_value(
+	parameters: &[CuckooPluginParameter],
+	plugin: &str,
+	device_id: Option<u32>,
+	name: &str,
+	value: &Value,
+) -> Result<Option<u64>, CuckooMinerError> {
+	let target = match device_id {
+		Some(id) => format!("{} for device {}", name, id),
+		None => String::from(name),
+	};
+	let value = match *value {
+		Value::Integer(v) => v,
+		_ => return Err(CuckooMinerError::ConfigError(format!("Saved {} for {} should be an integer", target, plugin))),
+	};
+	let p = match parameters.iter().find(|p| p.name == name) {
+		Some(p) => p,
+		None => {
+			warn!("Skipping saved {} for {}, which it no longer declares", target, plugin);
+			return Ok(None);
+		}
+	};
+	let clamped = if value < 0 {
+		p.min_value
+	} else {
+		let v = value as u64;
+		if v < p.min_value {
+			p.min_value
+		} else if v > p.max_value {
+			p.max_value
+		} else {
+			v
+		}
+	};
+	if value < 0 || clamped != value as u64 {
+		warn!(
+			"Saved {} = {} for {} is outside {}..{}, using {}",
+			target,
+			value,
+			plugin,
+			p.min_value,
+			p.max_value,
+			clamped
+		);
+	}
+	Ok(Some(clamped))
+}
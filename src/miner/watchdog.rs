@@ -0,0 +1,197 @@
+// Copyright 2017 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Detects plugins whose processing has hung, from the stats they
+//! report, so a job can give up on them and carry on with the rest, and
+//! plugins which have gone quiet for long enough to be worth a warning.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use cuckoo_sys::manager::PluginDeviceStats;
+
+/// Default time a job waits for a plugin to stop processing before
+/// giving up on it
+pub const DEFAULT_STOP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Default time plugins are given to stop when a miner is shut down,
+/// before they're force unloaded. CUDA plugins may need longer.
+pub const DEFAULT_SHUTDOWN_DEADLINE: Duration = Duration::from_millis(500);
+
+/// Default time a plugin may go without finishing a graph or finding a
+/// solution before a warning is logged
+pub const DEFAULT_STALL_WARNING: Duration = Duration::from_secs(60);
+
+/// #Description
+///
+/// Watches the graph attempts reported in each plugin's stats, and
+/// reports a plugin as hung once they haven't changed for the timeout.
+/// Any change counts as progress, including counters going backwards
+/// after a reset. The timeout should comfortably exceed the time the
+/// slowest device takes to attempt a single graph.
+
+pub struct Watchdog {
+	/// How long a plugin may go without progress
+	timeout: Duration,
+
+	/// Per plugin index, the graph attempts last reported and when they
+	/// last changed
+	progress: HashMap<usize, (u64, Instant)>,
+}
+
+impl Watchdog {
+	/// Returns a new watchdog, reporting plugins which make no progress
+	/// for `timeout`
+
+	pub fn new(timeout: Duration) -> Watchdog {
+		Watchdog {
+			timeout: timeout,
+			progress: HashMap::new(),
+		}
+	}
+
+	/// The time a plugin may go without progress
+	pub fn timeout(&self) -> Duration {
+		self.timeout
+	}
+
+	/// Checks the latest stats of the plugin at `plugin_index`, as of now.
+	/// See [check_at](#method.check_at).
+
+	pub fn check(&mut self, plugin_index: usize, stats: &[PluginDeviceStats]) -> bool {
+		self.check_at(plugin_index, stats, Instant::now())
+	}
+
+	/// #Description
+	///
+	/// Checks the stats of the plugin at `plugin_index`, read at `now`.
+	/// The first check of a plugin starts its clock.
+	///
+	/// #Arguments
+	///
+	/// * `plugin_index` (IN) The index of the plugin the stats came from
+	/// * `stats` (IN) The plugin's per-device stats
+	/// * `now` (IN) The time the stats were read. Checks are expected in
+	/// time order.
+	///
+	/// #Returns
+	///
+	/// true if the plugin has made no progress for the timeout
+
+	pub fn check_at(&mut self, plugin_index: usize, stats: &[PluginDeviceStats], now: Instant) -> bool {
+		let attempts: u64 = stats.iter().map(|s| s.iterations_completed as u64).sum();
+		let entry = self.progress.entry(plugin_index).or_insert((attempts, now));
+		if entry.0 != attempts {
+			*entry = (attempts, now);
+		}
+		now.duration_since(entry.1) >= self.timeout
+	}
+
+	/// Forgets a plugin, e.g. once it's been given up on or its
+	/// processing has been restarted
+
+	pub fn forget(&mut self, plugin_index: usize) {
+		self.progress.remove(&plugin_index);
+	}
+}
+
+/// #Description
+///
+/// Watches each plugin for graphs finished, as reported in its stats, and
+/// solutions found, and reports a plugin as stalled once it's gone the
+/// interval without either, then again after each further interval
+/// without. Unlike the [Watchdog](struct.Watchdog.html), a stalled plugin
+/// isn't given up on; it's only worth a warning, with enough detail to
+/// tell where the work has stopped.
+
+pub struct StallMonitor {
+	/// How long a plugin may go without progress
+	interval: Duration,
+
+	/// Per plugin index, the graph attempts last reported, when the
+	/// plugin last made progress, and when it was last reported as stalled
+	progress: HashMap<usize, (u64, Instant, Option<Instant>)>,
+}
+
+impl StallMonitor {
+	/// Returns a new monitor, reporting plugins which make no progress
+	/// for `interval`
+
+	pub fn new(interval: Duration) -> StallMonitor {
+		StallMonitor {
+			interval: interval,
+			progress: HashMap::new(),
+		}
+	}
+
+	/// The time a plugin may go without progress
+	pub fn interval(&self) -> Duration {
+		self.interval
+	}
+
+	/// Checks the latest stats of the plugin at `plugin_index`, as of now.
+	/// See [check_at](#method.check_at).
+
+	pub fn check(&mut self, plugin_index: usize, stats: &[PluginDeviceStats]) -> bool {
+		self.check_at(plugin_index, stats, Instant::now())
+	}
+
+	/// #Description
+	///
+	/// Checks the stats of the plugin at `plugin_index`, read at `now`.
+	/// The first check of a plugin starts its clock.
+	///
+	/// #Arguments
+	///
+	/// * `plugin_index` (IN) The index of the plugin the stats came from
+	/// * `stats` (IN) The plugin's per-device stats
+	/// * `now` (IN) The time the stats were read. Checks are expected in
+	/// time order.
+	///
+	/// #Returns
+	///
+	/// true if the plugin has made no progress for the interval, and
+	/// hasn't already been reported as stalled within the interval
+
+	pub fn check_at(&mut self, plugin_index: usize, stats: &[PluginDeviceStats], now: Instant) -> bool {
+		let attempts: u64 = stats.iter().map(|s| s.iterations_completed as u64).sum();
+		let entry = self.progress.entry(plugin_index).or_insert((attempts, now, None));
+		if entry.0 != attempts {
+			*entry = (attempts, now, None);
+		}
+		let since = entry.2.unwrap_or(entry.1);
+		if now.duration_since(since) < self.interval {
+			return false;
+		}
+		entry.2 = Some(now);
+		true
+	}
+
+	/// Records a solution from the plugin at `plugin_index`, found at
+	/// `now`, which counts as progress
+
+	pub fn record_solution(&mut self, plugin_index: usize, now: Instant) {
+		if let Some(entry) = self.progress.get_mut(&plugin_index) {
+			entry.1 = now;
+			entry.2 = None;
+		}
+	}
+
+	/// Forgets a plugin, e.g. once it's been given up on or its
+	/// processing has been restarted
+
+	pub fn forget(&mut self, plugin_index: usize) {
+		self.progress.remove(&plugin_index);
+	}
+}
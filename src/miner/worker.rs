@@ -0,0 +1,749 @@
+// Copyright 2017 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Per-plugin worker threads. Each plugin in a job has a thread of its own
+//! which feeds it headers, reads its solutions and samples its stats,
+//! reporting back to the job loop over a channel. The job loop keeps the
+//! decisions, such as when a plugin is stopped or reloaded, and tells the
+//! worker through its command channel. A worker which panics reports it
+//! and idles until it's restarted, so a panic while handling one plugin
+//! leaves the others mining. Used internally
+
+use std::any::Any;
+use std::cmp;
+use std::collections::{HashMap, VecDeque};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex, RwLock};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender, TryRecvError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use cuckoo_sys::manager::{PluginLibrary, PluginCall, PluginDeviceStats, CALL_REFUSED, pack_jobs};
+use cuckoo_sys::state::PluginState;
+use error::error::CuckooMinerError;
+use super::job::HeaderHasher;
+use super::affinity::{self, Priority};
+use super::ledger::PushLedger;
+use super::nonce::{NonceGenerator, NonceEncoding};
+use super::stats::DEFAULT_STATS_WINDOW;
+use CuckooMinerSolution;
+
+/// How often a worker feeds its plugin and reads its solutions
+pub const WORKER_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The most headers pushed in one batch to plugins which take batches,
+/// when the plugin's queue capacity isn't known
+pub const MAX_PUSH_BATCH: u32 = 64;
+
+/// A nonce which, with the `fault-injection` feature, makes a worker panic
+/// when its plugin returns a solution for it, so the tests can check a
+/// panicking worker doesn't take the rest of the job down with it
+
+#[cfg(feature = "fault-injection")]
+pub const PANIC_NONCE: u64 = 0xdead_beef;
+
+/// The job the workers are pushing headers for
+
+pub struct WorkerJob {
+	/// The job's id, xored with the queue id when it's pushed
+	pub job_id: u32,
+
+	/// Set apart this job loop's headers from any other miner's sharing
+	/// the plugin
+	pub queue_id: u32,
+
+	/// The part of the header before the nonce
+	pub pre_nonce: Vec<u8>,
+
+	/// The part of the header after the nonce
+	pub post_nonce: Vec<u8>,
+
+	/// Whether the header is hashed before it's pushed
+	pub hash_header: bool,
+
+	/// How the header is hashed, if it is
+	pub hasher: Arc<dyn HeaderHasher>,
+
+	/// The difficulty solutions must meet to be reported
+	pub difficulty: u64,
+}
+
+/// How a worker runs its plugin, which is set per plugin when the job
+/// starts
+
+pub struct WorkerSettings {
+	/// The minimum time between stats samples
+	pub stats_interval: Duration,
+
+	/// The cores the thread is pinned to, if any
+	pub cpu_affinity: Option<Vec<usize>>,
+
+	/// The thread's scheduling priority, if not the default
+	pub priority: Option<Priority>,
+
+	/// The share of the time the plugin should spend on graphs, as a
+	/// percentage, which may change as it runs
+	pub duty_cycle: Arc<AtomicUsize>,
+
+	/// Where the headers pushed are recorded, so solutions can be matched
+	/// to them
+	pub ledger: Arc<Mutex<PushLedger>>,
+}
+
+impl WorkerJob {
+	/// The header's hash with a nonce in the plugin's encoding
+
+	pub fn hash(&self, nonce: u64, encoding: NonceEncoding) -> [u8; 32] {
+		self.hasher.hash(&self.pre_nonce, &encoding.encode(nonce), &self.post_nonce)
+	}
+
+	/// The header for a nonce, hashed or not as the job requires, with
+	/// the nonce in the plugin's encoding
+
+	pub fn header(&self, nonce: u64, encoding: NonceEncoding) -> Vec<u8> {
+		if self.hash_header {
+			return self.hash(nonce, encoding).to_vec();
+		}
+		let nonce_bytes = encoding.encode(nonce);
+
+		let mut header = Vec::with_capacity(self.pre_nonce.len() + 8 + self.post_nonce.len());
+		header.extend_from_slice(&self.pre_nonce);
+		header.extend_from_slice(&nonce_bytes);
+		header.extend_from_slice(&self.post_nonce);
+		header
+	}
+}
+
+/// What the job loop tells a worker to do
+
+pub enum WorkerCommand {
+	/// Mine a new job, discarding anything queued for the last one, and
+	/// starting over on new nonces if given
+	Job(Arc<WorkerJob>, Option<NonceGenerator>),
+
+	/// Start feeding a freshly started plugin, pushing at most this many
+	/// headers a pass if its queue capacity is known
+	Start(Option<u32>),
+
+	/// Stop feeding the plugin and sampling its stats, e.g. while it's
+	/// stopping or being reloaded, though solutions are still read
+	Idle,
+
+	/// Read any solutions left in the plugin's queue, then exit
+	Finish,
+}
+
+/// What a worker tells the job loop
+
+pub enum WorkerReport {
+	/// Solutions read from the plugin, which may be none, with the
+	/// plugin's edge bits if there are some and the last nonce pushed
+	Solutions {
+		/// The plugin's index
+		index: usize,
+		/// The solutions, as read from the plugin
+		solutions: Vec<CuckooMinerSolution>,
+		/// The plugin's edge bits, if known and there are solutions
+		edge_bits: Option<u32>,
+		/// The last nonce pushed to the plugin, if any
+		last_nonce: Option<u64>,
+	},
+
+	/// A sample of the plugin's stats
+	Stats {
+		/// The plugin's index
+		index: usize,
+		/// The stats, or why they couldn't be read
+		stats: Result<Vec<PluginDeviceStats>, CuckooMinerError>,
+		/// The share of the last stats window the plugin spent on graphs,
+		/// as a percentage, if it's throttled
+		duty_cycle: Option<f64>,
+		/// The depths of the plugin's input and output queues
+		queue_depths: (u32, u32),
+	},
+
+	/// A plugin call failed
+	CallFailed {
+		/// The plugin's index
+		index: usize,
+		/// The call
+		call: PluginCall,
+		/// Its return code
+		code: u32,
+	},
+
+	/// The worker panicked, and is idle until it's started again
+	Panicked {
+		/// The plugin's index
+		index: usize,
+		/// The panic's message
+		message: String,
+	},
+
+	/// The worker has exited
+	Exited(usize),
+}
+
+// Tells the job loop a worker has exited, however it exits
+struct ExitNotice {
+	index: usize,
+	reports: Sender<WorkerReport>,
+}
+
+impl Drop for ExitNotice {
+	fn drop(&mut self) {
+		let _ = self.reports.send(WorkerReport::Exited(self.index));
+	}
+}
+
+struct Worker {
+	index: usize,
+	libraries: Arc<RwLock<Vec<PluginLibrary>>>,
+	commands: Receiver<WorkerCommand>,
+	reports: Sender<WorkerReport>,
+	nonces: NonceGenerator,
+	job: Arc<WorkerJob>,
+	stats_interval: Duration,
+	last_stats: Option<Instant>,
+	// whether the plugin is being fed, and the most headers a pass
+	running: bool,
+	high_water: Option<u32>,
+	// set once the worker panics, until the plugin is started again, as
+	// its library may have been left in a bad way
+	faulted: bool,
+	// the share of the time the plugin should spend on graphs, as a
+	// percentage, and how it's being held to it when under 100
+	duty_cycle: Arc<AtomicUsize>,
+	throttle: Option<Throttle>,
+	// where the headers pushed are recorded, shared with the job loop
+	ledger: Arc<Mutex<PushLedger>>,
+	// the graphs each device had finished as of the last read of the
+	// plugin's solutions, or None if some finished during the read
+	finished: Option<HashMap<String, u32>>,
+}
+
+// Holds a plugin to a duty cycle by giving it one graph per device at a
+// time, and leaving it idle after each round for long enough that the
+// time spent on graphs is the requested share of the whole. Graphs are
+// only ever held back from starting, never interrupted.
+struct Throttle {
+	// when the round of graphs in progress was pushed, if there is one,
+	// how many it had and the plugin's completed graphs before it
+	busy_since: Option<Instant>,
+	in_flight: u64,
+	completed_before: u64,
+	// when the next round may be pushed
+	resume_at: Instant,
+	// when throttling began, and the rounds finished within the window
+	started: Instant,
+	rounds: VecDeque<(Instant, Instant)>,
+}
+
+impl Throttle {
+	fn new(now: Instant) -> Throttle {
+		Throttle {
+			busy_since: None,
+			in_flight: 0,
+			completed_before: 0,
+			resume_at: now,
+			started: now,
+			rounds: VecDeque::new(),
+		}
+	}
+
+	// Ends the round in progress, if any, scheduling the next for once
+	// the plugin has been idle long enough
+
+	fn end_round(&mut self, now: Instant, duty_cycle: u32) {
+		if let Some(start) = self.busy_since.take() {
+			let busy = now.duration_since(start);
+			self.rounds.push_back((start, now));
+			self.resume_at = now + busy * (100 - duty_cycle) / duty_cycle;
+		}
+		self.in_flight = 0;
+	}
+
+	// The share of the last stats window, or of the time since throttling
+	// began if that's shorter, spent on graphs, as a percentage
+
+	fn effective_duty_cycle(&mut self, now: Instant) -> f64 {
+		let window_start = match now.checked_sub(DEFAULT_STATS_WINDOW) {
+			Some(t) if t > self.started => t,
+			_ => self.started,
+		};
+		while self.rounds.front().map_or(false, |&(_, end)| end <= window_start) {
+			self.rounds.pop_front();
+		}
+		let span = now.duration_since(window_start);
+		if span == Duration::from_secs(0) {
+			return 0.0;
+		}
+		let busy = self.rounds
+			.iter()
+			.cloned()
+			.chain(self.busy_since.map(|start| (start, now)))
+			.map(|(start, end)| end.duration_since(cmp::max(start, window_start)))
+			.fold(Duration::from_secs(0), |total, d| total + d);
+		100.0 * secs(busy) / secs(span)
+	}
+}
+
+fn secs(d: Duration) -> f64 {
+	d.as_secs() as f64 + d.subsec_nanos() as f64 / 1_000_000_000.0
+}
+
+/// #Description
+///
+/// Starts a worker thread for the plugin at `index`, which is idle until
+/// it's sent a Start command.
+///
+/// #Arguments
+///
+/// * `index` (IN) The plugin's index in `libraries`
+/// * `libraries` (IN) The job's plugins, which the worker only reads
+/// * `commands` (IN) Where the worker is told what to do
+/// * `reports` (IN) Where the worker reports to
+/// * `nonces` (IN) The nonces to push to the plugin
+/// * `job` (IN) The job to push headers for
+/// * `settings` (IN) How the worker runs the plugin
+///
+/// #Returns
+///
+/// The worker's thread
+///
+
+pub fn spawn_worker(
+	index: usize,
+	libraries: Arc<RwLock<Vec<PluginLibrary>>>,
+	commands: Receiver<WorkerCommand>,
+	reports: Sender<WorkerReport>,
+	nonces: NonceGenerator,
+	job: Arc<WorkerJob>,
+	settings: WorkerSettings,
+) -> JoinHandle<()> {
+	let WorkerSettings {
+		stats_interval,
+		cpu_affinity,
+		priority,
+		duty_cycle,
+		ledger,
+	} = settings;
+	let mut worker = Worker {
+		index: index,
+		libraries: libraries,
+		commands: commands,
+		reports: reports,
+		nonces: nonces,
+		job: job,
+		stats_interval: stats_interval,
+		last_stats: None,
+		running: false,
+		high_water: None,
+		faulted: false,
+		duty_cycle: duty_cycle,
+		throttle: None,
+		ledger: ledger,
+		finished: None,
+	};
+	thread::spawn(move || {
+		// the cores were checked when the plugin was loaded, and a
+		// placement the OS refuses is only logged
+		if let Some(cores) = cpu_affinity {
+			if let Err(e) = affinity::pin_current_thread(&cores) {
+				warn!("Cuckoo-miner: Worker for plugin {} not pinned: {:?}", index, e);
+			}
+		}
+		if let Some(p) = priority {
+			affinity::set_current_thread_priority(p);
+		}
+		let _exit = ExitNotice {
+			index: index,
+			reports: worker.reports.clone(),
+		};
+		// the worker's state is only its own, so a panic leaves nothing
+		// shared half-changed, and it carries on once restarted
+		while let Err(e) = panic::catch_unwind(AssertUnwindSafe(|| worker.run())) {
+			let message = panic_message(&e);
+			error!("Cuckoo-miner: Worker for plugin {} panicked: {}", index, message);
+			worker.running = false;
+			worker.faulted = true;
+			let _ = worker.reports.send(WorkerReport::Panicked {
+				index: index,
+				message: message,
+			});
+		}
+	})
+}
+
+// The message a panic was raised with, if it has one
+
+fn panic_message(e: &Box<dyn Any + Send>) -> String {
+	match e.downcast_ref::<&str>() {
+		Some(s) => String::from(*s),
+		None => match e.downcast_ref::<String>() {
+			Some(s) => s.clone(),
+			None => String::from("Unknown panic"),
+		},
+	}
+}
+
+impl Worker {
+	// Makes a pass over the plugin each poll interval, acting on commands
+	// as they come, until told to finish or the job loop has gone
+
+	fn run(&mut self) {
+		loop {
+			loop {
+				match self.commands.try_recv() {
+					Ok(c) => if !self.command(c) {
+						return;
+					},
+					Err(TryRecvError::Empty) => break,
+					Err(TryRecvError::Disconnected) => return,
+				}
+			}
+			self.pass();
+			match self.commands.recv_timeout(WORKER_POLL_INTERVAL) {
+				Ok(c) => if !self.command(c) {
+					return;
+				},
+				Err(RecvTimeoutError::Timeout) => {}
+				Err(RecvTimeoutError::Disconnected) => return,
+			}
+		}
+	}
+
+	// Acts on a command, returning false once the worker should exit
+
+	fn command(&mut self, command: WorkerCommand) -> bool {
+		match command {
+			WorkerCommand::Job(job, nonces) => {
+				self.job = job;
+				if let Some(n) = nonces {
+					self.nonces = n;
+				}
+				if !self.faulted {
+					self.libraries.read().unwrap()[self.index].call_cuckoo_clear_queues();
+				}
+				// a round's graphs may have been cleared before they began,
+				// so they're not waited for
+				let duty_cycle = self.duty_cycle.load(Ordering::Relaxed) as u32;
+				if let Some(ref mut t) = self.throttle {
+					t.end_round(Instant::now(), cmp::max(duty_cycle, 1));
+				}
+			}
+			WorkerCommand::Start(high_water) => {
+				self.running = true;
+				self.faulted = false;
+				self.high_water = high_water;
+				self.last_stats = None;
+				self.throttle = None;
+				self.finished = None;
+			}
+			WorkerCommand::Idle => self.running = false,
+			WorkerCommand::Finish => {
+				self.running = false;
+				self.pass();
+				return false;
+			}
+		}
+		true
+	}
+
+	// Feeds the plugin if it's running, then reads its solutions and, if
+	// due, its stats
+
+	fn pass(&mut self) {
+		if self.faulted {
+			return;
+		}
+		let libraries = self.libraries.clone();
+		let libraries = libraries.read().unwrap();
+		let l = &libraries[self.index];
+		if l.state() == PluginState::Unloaded {
+			return;
+		}
+		if self.running && !l.all_devices_disabled() {
+			match self.duty_cycle.load(Ordering::Relaxed) as u32 {
+				d if d >= 100 => {
+					self.throttle = None;
+					self.push_headers(l, None);
+				}
+				d => self.push_throttled(l, cmp::max(d, 1)),
+			}
+		}
+		// the stats either side of the read show which devices found the
+		// solutions
+		let before = match self.running {
+			true => l.stats().ok(),
+			false => None,
+		};
+		let mut solutions = l.drain_solutions(usize::max_value());
+		let after = match self.running {
+			true => Some(l.stats()),
+			false => None,
+		};
+		match (before, after.as_ref()) {
+			(Some(ref before), Some(&Ok(ref after))) => self.credit_devices(&mut solutions, before, after),
+			_ => self.finished = None,
+		}
+		#[cfg(feature = "fault-injection")]
+		{
+			if solutions.iter().any(|s| s.nonce == PANIC_NONCE) {
+				panic!("{} returned a solution for the panic nonce", l.lib_full_path);
+			}
+		}
+		let edge_bits = match solutions.is_empty() {
+			true => None,
+			false => l.edge_bits(),
+		};
+		let _ = self.reports.send(WorkerReport::Solutions {
+			index: self.index,
+			solutions: solutions,
+			edge_bits: edge_bits,
+			last_nonce: self.nonces.last_nonce(),
+		});
+		if self.running && self.last_stats.map_or(true, |t| t.elapsed() >= self.stats_interval) {
+			self.last_stats = Some(Instant::now());
+			let now = Instant::now();
+			let _ = self.reports.send(WorkerReport::Stats {
+				index: self.index,
+				stats: after.unwrap_or_else(|| l.stats()),
+				duty_cycle: self.throttle.as_mut().map(|t| t.effective_duty_cycle(now)),
+				queue_depths: l.queue_depths(),
+			});
+		}
+	}
+
+	// Records which device found each solution, and how long its graph
+	// took, where the stats read either side of the solutions show it for
+	// certain: nothing finished during the read, and since the last read,
+	// either only one device finished graphs, or each device which did
+	// finished one, with a solution each, in which case the solutions are
+	// in the order the devices finished
+
+	fn credit_devices(
+		&mut self,
+		solutions: &mut [CuckooMinerSolution],
+		before: &[PluginDeviceStats],
+		after: &[PluginDeviceStats],
+	) {
+		let finished: HashMap<String, u32> = after.iter().map(|s| (s.device_id.clone(), s.iterations_completed)).collect();
+		let unchanged = before.len() == after.len()
+			&& before.iter().all(|s| finished.get(&s.device_id) == Some(&s.iterations_completed));
+		let last = match unchanged {
+			true => self.finished.replace(finished),
+			false => {
+				self.finished = None;
+				return;
+			}
+		};
+		let last = match last {
+			Some(l) => l,
+			None => return,
+		};
+		// devices which finished graphs since the last read, and how many
+		let mut devices = Vec::new();
+		for s in after {
+			match last.get(&s.device_id) {
+				Some(&n) if n == s.iterations_completed => {}
+				Some(&n) if n < s.iterations_completed => devices.push((s, s.iterations_completed - n)),
+				// new, or reset
+				_ => return,
+			}
+		}
+		let graph_time = |s: &PluginDeviceStats, graphs: u32| match graphs == 1 && s.last_solution_time > 0 {
+			true => Some(s.last_solution_time),
+			false => None,
+		};
+		let credit: Vec<(&PluginDeviceStats, Option<u64>)> = if devices.len() == 1 {
+			let (s, graphs) = devices[0];
+			solutions.iter().map(|_| (s, graph_time(s, graphs))).collect()
+		} else if devices.len() == solutions.len() && devices.iter().all(|&(_, graphs)| graphs == 1) {
+			devices.sort_by_key(|&(s, _)| s.last_end_time);
+			devices.iter().map(|&(s, graphs)| (s, graph_time(s, graphs))).collect()
+		} else {
+			return;
+		};
+		for (solution, (s, graph_time)) in solutions.iter_mut().zip(credit) {
+			if let Some(ref mut m) = solution.metadata {
+				m.device_id = Some(s.device_id.clone());
+				m.graph_time_ms = graph_time;
+			}
+		}
+	}
+
+	// Pushes a round of graphs, one for each device, once the last round
+	// has finished and the plugin has been idle for long enough
+
+	fn push_throttled(&mut self, l: &PluginLibrary, duty_cycle: u32) {
+		let now = Instant::now();
+		let stats = match l.stats() {
+			Ok(s) => s,
+			Err(e) => {
+				warn!("Cuckoo-miner: Unable to throttle {}, as its stats can't be read: {:?}", l.lib_full_path, e);
+				return;
+			}
+		};
+		let completed: u64 = stats.iter().map(|s| s.iterations_completed as u64).sum();
+		let devices = stats.iter().filter(|s| s.in_use != 0 && !s.has_errored).count();
+		let throttle = self.throttle.get_or_insert_with(|| Throttle::new(now));
+		if throttle.busy_since.is_some() {
+			// counts go back to 0 if the plugin's reset
+			if completed < throttle.completed_before {
+				throttle.completed_before = 0;
+			}
+			if completed - throttle.completed_before < throttle.in_flight {
+				return;
+			}
+			throttle.end_round(now, duty_cycle);
+		}
+		if now < throttle.resume_at {
+			return;
+		}
+		let pushed = self.push_headers(l, Some(cmp::max(devices, 1) as u32));
+		if pushed > 0 {
+			let throttle = self.throttle.as_mut().unwrap();
+			throttle.busy_since = Some(now);
+			throttle.in_flight = pushed as u64;
+			throttle.completed_before = completed;
+		}
+	}
+
+	// Pushes headers until the plugin's queue is full, or the high water
+	// mark or the given limit is reached, returning how many were pushed.
+	// Plugins which report their queue depth are only topped up to the
+	// high water mark, rather than given that many more each pass.
+
+	fn push_headers(&mut self, l: &PluginLibrary, limit: Option<u32>) -> u32 {
+		let high_water = match self.high_water {
+			Some(h) if l.has_queue_depths() => Some(h.saturating_sub(l.queue_depths().0)),
+			h => h,
+		};
+		let limit = match (high_water, limit) {
+			(Some(h), Some(n)) => Some(cmp::min(h, n)),
+			(h, n) => h.or(n),
+		};
+		// batches are of hashed headers
+		if self.job.hash_header && l.has_batch_push() {
+			return self.push_batches(l, limit);
+		}
+		let encoding = l.nonce_encoding();
+		let mut pushed = 0;
+		while l.call_cuckoo_is_queue_under_limit() == 1 {
+			if let Some(h) = limit {
+				if pushed >= h {
+					break;
+				}
+			}
+			// the nonce's only used up once the plugin's accepted it
+			let nonce = match self.nonces.clone().next() {
+				Some(n) => n,
+				None => break,
+			};
+			let data = self.job.header(nonce, encoding);
+			let nonce_bytes = encoding.encode(nonce);
+			let code = l.call_cuckoo_push_to_input_queue(self.job.job_id ^ self.job.queue_id, &data, &nonce_bytes);
+			match code {
+				0 => {
+					self.nonces.next();
+					self.ledger.lock().unwrap().record(&self.job, nonce, &data);
+					pushed += 1;
+				}
+				// full already, which is fine
+				1 => break,
+				// the plugin is being stopped, and the job loop will
+				// tell us to idle shortly
+				CALL_REFUSED => break,
+				_ => {
+					let _ = self.reports.send(WorkerReport::CallFailed {
+						index: self.index,
+						call: PluginCall::PushToInputQueue,
+						code: code,
+					});
+					break;
+				}
+			}
+		}
+		pushed
+	}
+
+	// As push_headers, but a batch at a time, each as big as the room left
+	// under the limit
+
+	fn push_batches(&mut self, l: &PluginLibrary, limit: Option<u32>) -> u32 {
+		let encoding = l.nonce_encoding();
+		let mut pushed = 0;
+		while l.call_cuckoo_is_queue_under_limit() == 1 {
+			let size = match limit {
+				Some(h) if pushed >= h => break,
+				Some(h) => h - pushed,
+				None => MAX_PUSH_BATCH,
+			};
+			// nonces are only used up once the plugin's accepted them, so
+			// none are skipped when the queue fills part way
+			let jobs: Vec<_> = self.nonces
+				.clone()
+				.take(size as usize)
+				.map(|nonce| {
+					(self.job.hash(nonce, encoding), encoding.encode(nonce))
+				})
+				.collect();
+			// the job's nonces have run out
+			if jobs.is_empty() {
+				break;
+			}
+			let mut accepted = 0;
+			let code = l.call_cuckoo_push_batch_to_input_queue(
+				self.job.job_id ^ self.job.queue_id,
+				&pack_jobs(&jobs),
+				&mut accepted,
+			);
+			let accepted = cmp::min(accepted as usize, jobs.len());
+			{
+				let mut ledger = self.ledger.lock().unwrap();
+				for &(ref header, nonce_bytes) in &jobs[..accepted] {
+					ledger.record(&self.job, encoding.decode(&nonce_bytes), header);
+				}
+			}
+			for _ in 0..accepted {
+				self.nonces.next();
+			}
+			pushed += accepted as u32;
+			match code {
+				// nothing taken, so trying again would only spin
+				0 if accepted == 0 => {
+					let _ = self.reports.send(WorkerReport::CallFailed {
+						index: self.index,
+						call: PluginCall::PushToInputQueue,
+						code: code,
+					});
+					break;
+				}
+				0 => {}
+				1 | CALL_REFUSED => break,
+				_ => {
+					let _ = self.reports.send(WorkerReport::CallFailed {
+						index: self.index,
+						call: PluginCall::PushToInputQueue,
+						code: code,
+					});
+					break;
+				}
+			}
+		}
+		pushed
+	}
+}
@@ -0,0 +1,156 @@
+// Copyright 2017 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Converts bytes, such as headers, to and from hex strings.
+
+use std::fmt::Write;
+
+use error::error::CuckooMinerError;
+use miner::miner::header_hash;
+
+/// #Description
+///
+/// Encodes bytes as a lower case hex string, two digits per byte.
+///
+/// #Example
+///
+/// ```
+///  assert_eq!(cuckoo_miner::to_hex(&[0x01, 0xab]), "01ab");
+/// ```
+///
+
+pub fn to_hex(bytes: &[u8]) -> String {
+	let mut s = String::with_capacity(bytes.len() * 2);
+	for b in bytes {
+		let _ = write!(s, "{:02x}", b);
+	}
+	s
+}
+
+/// #Description
+///
+/// Decodes a hex string, in upper or lower case, with or without a `0x`
+/// prefix. ASCII whitespace is ignored anywhere after the prefix, so a
+/// long header can be wrapped.
+///
+/// #Arguments
+///
+/// * `hex` (IN) The hex string
+///
+/// #Returns
+///
+/// * `Ok(Vec<u8>)` holding the bytes
+/// * an InvalidHex error if a character isn't a hex digit, or the
+/// digits don't pair up into whole bytes, with the byte offset into
+/// `hex` of the offending character or unpaired digit
+///
+/// #Example
+///
+/// ```
+///  assert_eq!(cuckoo_miner::from_hex("0x01 AB").unwrap(), vec![0x01, 0xab]);
+///  assert!(cuckoo_miner::from_hex("01a").is_err());
+/// ```
+///
+
+pub fn from_hex(hex: &str) -> Result<Vec<u8>, CuckooMinerError> {
+	let start = if hex.starts_with("0x") || hex.starts_with("0X") { 2 } else { 0 };
+	let mut bytes = Vec::with_capacity(hex.len() / 2);
+	// the offset and value of a high digit waiting for its low digit
+	let mut high: Option<(usize, u8)> = None;
+	for (offset, c) in hex[start..].char_indices() {
+		let offset = offset + start;
+		if c.is_ascii_whitespace() {
+			continue;
+		}
+		let digit = match c.to_digit(16) {
+			Some(d) => d as u8,
+			None => {
+				return Err(CuckooMinerError::InvalidHex {
+					offset: offset,
+					reason: format!("{:?} isn't a hex digit", c),
+				})
+			}
+		};
+		high = match high {
+			Some((_, h)) => {
+				bytes.push(h << 4 | digit);
+				None
+			}
+			None => Some((offset, digit)),
+		};
+	}
+	match high {
+		Some((offset, _)) => Err(CuckooMinerError::InvalidHex {
+			offset: offset,
+			reason: String::from("An odd number of hex digits, so the last is unpaired"),
+		}),
+		None => Ok(bytes),
+	}
+}
+
+/// A block header as the miner is given it: the bytes either side of the
+/// nonce, which the miner fills in for each graph it searches.
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Header {
+	/// The part of the header which comes before the nonce
+	pub pre_nonce: Vec<u8>,
+
+	/// The part of the header which comes after the nonce
+	pub post_nonce: Vec<u8>,
+}
+
+impl Header {
+	/// #Description
+	///
+	/// Decodes a header from the hex of its parts, as accepted by
+	/// [from_hex](fn.from_hex.html)
+	///
+	/// #Arguments
+	///
+	/// * `pre_nonce` (IN) The part of the header before the nonce, in hex
+	/// * `post_nonce` (IN) The part of the header after the nonce, in hex
+	///
+	/// #Returns
+	///
+	/// * `Ok(Header)` if both parts are valid hex
+	/// * an InvalidHex error otherwise, whose reason says which part it's
+	/// about, and whose offset is into that part
+	///
+
+	pub fn from_hex(pre_nonce: &str, post_nonce: &str) -> Result<Header, CuckooMinerError> {
+		let part = |name: &str, hex: &str| {
+			from_hex(hex).map_err(|e| match e {
+				CuckooMinerError::InvalidHex { offset, reason } => CuckooMinerError::InvalidHex {
+					offset: offset,
+					reason: format!("In the {} header: {}", name, reason),
+				},
+				e => e,
+			})
+		};
+		Ok(Header {
+			pre_nonce: part("pre-nonce", pre_nonce)?,
+			post_nonce: part("post-nonce", post_nonce)?,
+		})
+	}
+
+	/// #Description
+	///
+	/// The 32 bytes a plugin searches for a nonce, as described by
+	/// [header_hash](fn.header_hash.html)
+
+	pub fn hash(&self, nonce: u64) -> [u8; 32] {
+		header_hash(&self.pre_nonce, &self.post_nonce, nonce)
+	}
+}
@@ -0,0 +1,433 @@
+// Copyright 2017 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A small JSON reader for what plugins report, i.e. their parameter
+//! lists, stats, devices and capabilities, so reading them doesn't need
+//! serde, which is only pulled in with the `serde` feature.
+
+use std::char;
+
+/// The deepest arrays and objects may nest, so a malformed report can't
+/// exhaust the stack
+const MAX_DEPTH: usize = 64;
+
+/// A parsed JSON value. Numbers are kept as written, so 64-bit integers
+/// are read without going through a float.
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum JsonValue {
+	/// null
+	Null,
+
+	/// true or false
+	Bool(bool),
+
+	/// A number, as written
+	Number(String),
+
+	/// A string, with its escapes resolved
+	String(String),
+
+	/// An array
+	Array(Vec<JsonValue>),
+
+	/// An object's members, in the order written
+	Object(Vec<(String, JsonValue)>),
+}
+
+impl JsonValue {
+	/// The member of an object with the given name, if it is an object
+	/// and has one
+
+	pub fn get(&self, name: &str) -> Option<&JsonValue> {
+		match *self {
+			JsonValue::Object(ref members) => members.iter().find(|m| m.0 == name).map(|m| &m.1),
+			_ => None,
+		}
+	}
+
+	/// The value as an unsigned integer, if it's a number which is one
+
+	pub fn as_u64(&self) -> Option<u64> {
+		match *self {
+			JsonValue::Number(ref n) => n.parse().ok(),
+			_ => None,
+		}
+	}
+
+	/// The value as a float, if it's a number
+
+	pub fn as_f64(&self) -> Option<f64> {
+		match *self {
+			JsonValue::Number(ref n) => n.parse().ok(),
+			_ => None,
+		}
+	}
+
+	/// The value as a string, if it's a string
+
+	pub fn as_str(&self) -> Option<&str> {
+		match *self {
+			JsonValue::String(ref s) => Some(s),
+			_ => None,
+		}
+	}
+
+	/// The value as a bool, if it's true or false
+
+	pub fn as_bool(&self) -> Option<bool> {
+		match *self {
+			JsonValue::Bool(b) => Some(b),
+			_ => None,
+		}
+	}
+}
+
+/// A type which can be read from a JSON value
+
+pub trait FromJson: Sized {
+	/// Reads the value, or returns why it can't be
+	fn from_json(value: &JsonValue) -> Result<Self, String>;
+}
+
+impl<T: FromJson> FromJson for Vec<T> {
+	fn from_json(value: &JsonValue) -> Result<Vec<T>, String> {
+		match *value {
+			JsonValue::Array(ref items) => items.iter().map(T::from_json).collect(),
+			_ => Err(String::from("expected an array")),
+		}
+	}
+}
+
+/// Parses a JSON document into a value of type `T`
+
+pub fn from_str<T: FromJson>(json: &str) -> Result<T, String> {
+	T::from_json(&parse(json)?)
+}
+
+/// Parses a JSON document
+
+pub fn parse(json: &str) -> Result<JsonValue, String> {
+	let mut parser = Parser {
+		bytes: json.as_bytes(),
+		pos: 0,
+	};
+	let value = parser.value(0)?;
+	parser.skip_whitespace();
+	if parser.pos != parser.bytes.len() {
+		return Err(format!("trailing characters at {}", parser.pos));
+	}
+	Ok(value)
+}
+
+// The named member of an object, which must be there
+fn member<'a>(value: &'a JsonValue, name: &str) -> Result<&'a JsonValue, String> {
+	value.get(name).ok_or_else(|| format!("missing field `{}`", name))
+}
+
+// The named member of an object, or None if it's missing or null
+fn optional_member<'a>(value: &'a JsonValue, name: &str) -> Option<&'a JsonValue> {
+	match value.get(name) {
+		None | Some(&JsonValue::Null) => None,
+		Some(v) => Some(v),
+	}
+}
+
+/// Reads an object's string member
+
+pub fn string_field(value: &JsonValue, name: &str) -> Result<String, String> {
+	member(value, name)?
+		.as_str()
+		.map(String::from)
+		.ok_or_else(|| format!("`{}` should be a string", name))
+}
+
+/// Reads an object's unsigned integer member
+
+pub fn u64_field(value: &JsonValue, name: &str) -> Result<u64, String> {
+	member(value, name)?
+		.as_u64()
+		.ok_or_else(|| format!("`{}` should be an unsigned integer", name))
+}
+
+/// Reads an object's unsigned 32-bit integer member
+
+pub fn u32_field(value: &JsonValue, name: &str) -> Result<u32, String> {
+	match u64_field(value, name)? {
+		n if n > u32::max_value() as u64 => Err(format!("`{}` should fit in 32 bits", name)),
+		n => Ok(n as u32),
+	}
+}
+
+/// Reads an object's bool member
+
+pub fn bool_field(value: &JsonValue, name: &str) -> Result<bool, String> {
+	member(value, name)?
+		.as_bool()
+		.ok_or_else(|| format!("`{}` should be true or false", name))
+}
+
+/// Reads an object's optional string member
+
+pub fn optional_string_field(value: &JsonValue, name: &str) -> Result<Option<String>, String> {
+	match optional_member(value, name) {
+		None => Ok(None),
+		Some(v) => v.as_str().map(|s| Some(String::from(s))).ok_or_else(|| format!("`{}` should be a string", name)),
+	}
+}
+
+/// Reads an object's optional unsigned integer member
+
+pub fn optional_u64_field(value: &JsonValue, name: &str) -> Result<Option<u64>, String> {
+	match optional_member(value, name) {
+		None => Ok(None),
+		Some(v) => v.as_u64().map(Some).ok_or_else(|| format!("`{}` should be an unsigned integer", name)),
+	}
+}
+
+/// Reads an object's optional unsigned 32-bit integer member
+
+pub fn optional_u32_field(value: &JsonValue, name: &str) -> Result<Option<u32>, String> {
+	match optional_u64_field(value, name)? {
+		Some(n) if n > u32::max_value() as u64 => Err(format!("`{}` should fit in 32 bits", name)),
+		n => Ok(n.map(|n| n as u32)),
+	}
+}
+
+/// Reads an object's optional number member as a float
+
+pub fn optional_f64_field(value: &JsonValue, name: &str) -> Result<Option<f64>, String> {
+	match optional_member(value, name) {
+		None => Ok(None),
+		Some(v) => v.as_f64().map(Some).ok_or_else(|| format!("`{}` should be a number", name)),
+	}
+}
+
+/// Reads an object's optional bool member
+
+pub fn optional_bool_field(value: &JsonValue, name: &str) -> Result<Option<bool>, String> {
+	match optional_member(value, name) {
+		None => Ok(None),
+		Some(v) => v.as_bool().map(Some).ok_or_else(|| format!("`{}` should be true or false", name)),
+	}
+}
+
+// Reads JSON text into values
+struct Parser<'a> {
+	bytes: &'a [u8],
+	pos: usize,
+}
+
+impl<'a> Parser<'a> {
+	fn skip_whitespace(&mut self) {
+		while self.pos < self.bytes.len() {
+			match self.bytes[self.pos] {
+				b' ' | b'\t' | b'\n' | b'\r' => self.pos += 1,
+				_ => break,
+			}
+		}
+	}
+
+	fn peek(&self) -> Option<u8> {
+		self.bytes.get(self.pos).cloned()
+	}
+
+	fn expect(&mut self, literal: &str, value: JsonValue) -> Result<JsonValue, String> {
+		if self.bytes[self.pos..].starts_with(literal.as_bytes()) {
+			self.pos += literal.len();
+			Ok(value)
+		} else {
+			Err(format!("expected {} at {}", literal, self.pos))
+		}
+	}
+
+	fn value(&mut self, depth: usize) -> Result<JsonValue, String> {
+		if depth > MAX_DEPTH {
+			return Err(format!("nested too deeply at {}", self.pos));
+		}
+		self.skip_whitespace();
+		match self.peek() {
+			Some(b'{') => self.object(depth),
+			Some(b'[') => self.array(depth),
+			Some(b'"') => self.string().map(JsonValue::String),
+			Some(b't') => self.expect("true", JsonValue::Bool(true)),
+			Some(b'f') => self.expect("false", JsonValue::Bool(false)),
+			Some(b'n') => self.expect("null", JsonValue::Null),
+			Some(b'-') | Some(b'0'..=b'9') => self.number(),
+			Some(c) => Err(format!("unexpected {:?} at {}", c as char, self.pos)),
+			None => Err(String::from("unexpected end of input")),
+		}
+	}
+
+	fn object(&mut self, depth: usize) -> Result<JsonValue, String> {
+		self.pos += 1;
+		let mut members = Vec::new();
+		self.skip_whitespace();
+		if self.peek() == Some(b'}') {
+			self.pos += 1;
+			return Ok(JsonValue::Object(members));
+		}
+		loop {
+			self.skip_whitespace();
+			if self.peek() != Some(b'"') {
+				return Err(format!("expected a member name at {}", self.pos));
+			}
+			let name = self.string()?;
+			self.skip_whitespace();
+			if self.peek() != Some(b':') {
+				return Err(format!("expected ':' at {}", self.pos));
+			}
+			self.pos += 1;
+			let value = self.value(depth + 1)?;
+			members.push((name, value));
+			self.skip_whitespace();
+			match self.peek() {
+				Some(b',') => self.pos += 1,
+				Some(b'}') => {
+					self.pos += 1;
+					return Ok(JsonValue::Object(members));
+				}
+				_ => return Err(format!("expected ',' or '}}' at {}", self.pos)),
+			}
+		}
+	}
+
+	fn array(&mut self, depth: usize) -> Result<JsonValue, String> {
+		self.pos += 1;
+		let mut items = Vec::new();
+		self.skip_whitespace();
+		if self.peek() == Some(b']') {
+			self.pos += 1;
+			return Ok(JsonValue::Array(items));
+		}
+		loop {
+			items.push(self.value(depth + 1)?);
+			self.skip_whitespace();
+			match self.peek() {
+				Some(b',') => self.pos += 1,
+				Some(b']') => {
+					self.pos += 1;
+					return Ok(JsonValue::Array(items));
+				}
+				_ => return Err(format!("expected ',' or ']' at {}", self.pos)),
+			}
+		}
+	}
+
+	// Skips a run of digits, returning how many there were
+	fn digits(&mut self) -> usize {
+		let start = self.pos;
+		while let Some(b'0'..=b'9') = self.peek() {
+			self.pos += 1;
+		}
+		self.pos - start
+	}
+
+	// Reads a number as JSON writes them, i.e. with no leading zeros or
+	// plus sign, and digits either side of any decimal point
+	fn number(&mut self) -> Result<JsonValue, String> {
+		let start = self.pos;
+		if self.peek() == Some(b'-') {
+			self.pos += 1;
+		}
+		let valid = match self.peek() {
+			Some(b'0') => {
+				self.pos += 1;
+				true
+			}
+			_ => self.digits() > 0,
+		};
+		let valid = valid && match self.peek() {
+			Some(b'.') => {
+				self.pos += 1;
+				self.digits() > 0
+			}
+			_ => true,
+		};
+		let valid = valid && match self.peek() {
+			Some(b'e') | Some(b'E') => {
+				self.pos += 1;
+				if let Some(b'+') | Some(b'-') = self.peek() {
+					self.pos += 1;
+				}
+				self.digits() > 0
+			}
+			_ => true,
+		};
+		// e.g. the 1 in 01, which would otherwise be trailing
+		let valid = valid && match self.peek() {
+			Some(b'0'..=b'9') | Some(b'.') => false,
+			_ => true,
+		};
+		if !valid {
+			return Err(format!("invalid number at {}", start));
+		}
+		Ok(JsonValue::Number(String::from_utf8_lossy(&self.bytes[start..self.pos]).into_owned()))
+	}
+
+	fn hex4(&mut self) -> Result<u32, String> {
+		let digits = match self.bytes.get(self.pos..self.pos + 4) {
+			Some(d) if d.iter().all(|c| (*c as char).is_digit(16)) => d,
+			_ => return Err(format!("invalid \\u escape at {}", self.pos)),
+		};
+		self.pos += 4;
+		Ok(digits.iter().fold(0, |code, c| code << 4 | (*c as char).to_digit(16).unwrap()))
+	}
+
+	fn string(&mut self) -> Result<String, String> {
+		self.pos += 1;
+		let mut bytes = Vec::new();
+		loop {
+			let c = self.peek().ok_or_else(|| String::from("unterminated string"))?;
+			self.pos += 1;
+			match c {
+				b'"' => break,
+				b'\\' => {
+					let e = self.peek().ok_or_else(|| String::from("unterminated string"))?;
+					self.pos += 1;
+					let unescaped = match e {
+						b'"' => '"',
+						b'\\' => '\\',
+						b'/' => '/',
+						b'b' => '\u{8}',
+						b'f' => '\u{c}',
+						b'n' => '\n',
+						b'r' => '\r',
+						b't' => '\t',
+						b'u' => {
+							let start = self.pos - 2;
+							let mut code = self.hex4()?;
+							// a surrogate pair, written as two escapes
+							if code >= 0xd800 && code < 0xdc00 && self.bytes[self.pos..].starts_with(b"\\u") {
+								self.pos += 2;
+								let low = self.hex4()?;
+								if low < 0xdc00 || low > 0xdfff {
+									return Err(format!("unpaired surrogate at {}", start));
+								}
+								code = 0x10000 + ((code - 0xd800) << 10) + (low - 0xdc00);
+							}
+							char::from_u32(code).ok_or_else(|| format!("unpaired surrogate at {}", start))?
+						}
+						_ => return Err(format!("invalid escape at {}", self.pos - 1)),
+					};
+					let mut buf = [0; 4];
+					bytes.extend_from_slice(unescaped.encode_utf8(&mut buf).as_bytes());
+				}
+				c if c < 0x20 => return Err(format!("unescaped control character at {}", self.pos - 1)),
+				c => bytes.push(c),
+			}
+		}
+		String::from_utf8(bytes).map_err(|e| e.to_string())
+	}
+}
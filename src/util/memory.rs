@@ -0,0 +1,62 @@
+// Copyright 2017 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Finds how much memory the system has free for plugins to allocate.
+
+/// #Description
+///
+/// Returns the memory the system can give to new allocations without
+/// swapping, in bytes. On Linux this is `MemAvailable` from
+/// `/proc/meminfo`, which counts caches that can be dropped as well as
+/// free memory.
+///
+/// #Returns
+///
+/// The memory available, or None if the platform doesn't say
+///
+
+#[cfg(target_os = "linux")]
+pub fn available_memory() -> Option<u64> {
+	use std::fs::File;
+	use std::io::Read;
+	let mut meminfo = String::new();
+	File::open("/proc/meminfo").ok()?.read_to_string(&mut meminfo).ok()?;
+	mem_available(&meminfo)
+}
+
+/// #Description
+///
+/// Returns the memory the system can give to new allocations without
+/// swapping, in bytes. On Linux this is `MemAvailable` from
+/// `/proc/meminfo`, which counts caches that can be dropped as well as
+/// free memory.
+///
+/// #Returns
+///
+/// The memory available, or None if the platform doesn't say
+///
+
+#[cfg(not(target_os = "linux"))]
+pub fn available_memory() -> Option<u64> {
+	None
+}
+
+// Reads MemAvailable, given in kB, from the contents of /proc/meminfo
+
+#[cfg(target_os = "linux")]
+fn mem_available(meminfo: &str) -> Option<u64> {
+	let line = meminfo.lines().find(|l| l.starts_with("MemAvailable:"))?;
+	let kb = line.split_whitespace().nth(1)?.parse::<u64>().ok()?;
+	Some(kb * 1024)
+}
@@ -0,0 +1,54 @@
+// Copyright 2017 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Derivation of the SipHash keys a cuckoo graph is generated from. This
+//! is the one place the derivation lives, shared by the verifier and by
+//! the keyed plugin call, so the two can't disagree.
+
+use byteorder::{ByteOrder, LittleEndian};
+use blake2::blake2b::blake2b;
+
+/// #Description
+///
+/// Derives the four SipHash keys for a header, as the plugins do: the
+/// unkeyed blake2b-256 hash of the header, read as four Little-Endian
+/// u64s.
+///
+/// #Arguments
+///
+/// * `header` (IN) The header, of any length. In practice this is the
+/// 32 byte hash passed to the plugin.
+///
+/// #Returns
+///
+/// The keys, in order
+///
+/// #Example
+/// ```
+///  # use cuckoo_miner::siphash_keys;
+///  let keys = siphash_keys(&[0u8; 32]);
+///  println!("Keys: {:x?}", keys);
+/// ```
+///
+
+pub fn siphash_keys(header: &[u8]) -> [u64; 4] {
+	let hash = blake2b(32, &[], header);
+	let bytes = hash.as_bytes();
+	[
+		LittleEndian::read_u64(&bytes[0..8]),
+		LittleEndian::read_u64(&bytes[8..16]),
+		LittleEndian::read_u64(&bytes[16..24]),
+		LittleEndian::read_u64(&bytes[24..32]),
+	]
+}
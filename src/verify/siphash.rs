@@ -0,0 +1,68 @@
+// Copyright 2017 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! SipHash-2-4 keyed from a header, as used by the plugins to generate
+//! the edges of the cuckoo graph
+
+use super::keys::siphash_keys;
+
+/// The four SipHash keys a graph is generated from, which are the
+/// blake2b hash of the header read as four Little-Endian u64s
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SipHashKeys {
+	/// The keys, in order
+	pub k: [u64; 4],
+}
+
+impl SipHashKeys {
+	/// Derives the keys from a header, as the plugins do. See
+	/// [siphash_keys](fn.siphash_keys.html).
+	pub fn from_header(header: &[u8]) -> SipHashKeys {
+		SipHashKeys {
+			k: siphash_keys(header),
+		}
+	}
+
+	/// SipHash-2-4 of `nonce` under these keys
+	pub fn siphash24(&self, nonce: u64) -> u64 {
+		let mut v = self.k;
+		v[3] ^= nonce;
+		sip_round(&mut v);
+		sip_round(&mut v);
+		v[0] ^= nonce;
+		v[2] ^= 0xff;
+		for _ in 0..4 {
+			sip_round(&mut v);
+		}
+		v[0] ^ v[1] ^ v[2] ^ v[3]
+	}
+}
+
+fn sip_round(v: &mut [u64; 4]) {
+	v[0] = v[0].wrapping_add(v[1]);
+	v[2] = v[2].wrapping_add(v[3]);
+	v[1] = v[1].rotate_left(13);
+	v[3] = v[3].rotate_left(16);
+	v[1] ^= v[0];
+	v[3] ^= v[2];
+	v[0] = v[0].rotate_left(32);
+	v[2] = v[2].wrapping_add(v[1]);
+	v[0] = v[0].wrapping_add(v[3]);
+	v[1] = v[1].rotate_left(17);
+	v[3] = v[3].rotate_left(21);
+	v[1] ^= v[2];
+	v[3] ^= v[0];
+	v[2] = v[2].rotate_left(32);
+}
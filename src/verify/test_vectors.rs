@@ -0,0 +1,107 @@
+// Copyright 2017 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Headers known to have a solution at a given cuckoo size, and their
+//! solutions where known, for checking plugins find what they should.
+
+/// A 32 byte header, in hex, known to have a 42-cycle at cuckoo 30
+pub const KNOWN_30_HASH_1: &str = "11c5059b4d4053131323fdfab6a6509d73ef22\
+9aedc4073d5995c6edced5a3e6";
+
+/// A 32 byte header, in hex, known to have a 42-cycle at cuckoo 16
+pub const KNOWN_16_HASH_1: &str = "c008b9ff7292fdacef0efbdff73d1db66674ff\
+3b6dea6cca670c85b6a110f0b2";
+
+/// The solution to [KNOWN_16_HASH_1](constant.KNOWN_16_HASH_1.html) at
+/// cuckoo 16
+pub const KNOWN_16_SOLUTION_1: [u32; 42] = [
+	1614, 2703, 4264, 4807, 6003, 7120, 8031, 8332, 8369, 8572,
+	10617, 10777, 11836, 14187, 14468, 14944, 15488, 15855, 15862, 16398,
+	16488, 17873, 18201, 18731, 18756, 19711, 19984, 20071, 22332, 24056,
+	24090, 24754, 25484, 26009, 26887, 27085, 27627, 28594, 29488, 30213,
+	31641, 32738,
+];
+
+/// A header of zeroes but for 0x0e in its second byte and 0x12 in its
+/// last, which has a 12-cycle at cuckoo 16
+pub const KNOWN_16_HASH_12: &str = "000e00000000000000000000000000000000000000\
+0000000000000000000012";
+
+/// The 12-cycle in [KNOWN_16_HASH_12](constant.KNOWN_16_HASH_12.html)
+pub const KNOWN_16_SOLUTION_12: [u32; 12] = [
+	739, 2105, 3492, 6431, 9143, 9732, 15584, 19988, 21316, 28431,
+	28997, 29639,
+];
+
+/// A header with a cycle of a given length at a given cuckoo size
+
+#[derive(Debug, Clone, Copy)]
+pub struct TestVector {
+	/// The header, in hex
+	pub header: &'static str,
+
+	/// The cuckoo size, i.e. the edge bits plus one
+	pub cuckoo_size: u32,
+
+	/// The number of edges in the cycle
+	pub proof_size: usize,
+
+	/// The cycle's edge nonces, if known. A plugin may find another
+	/// cycle in the same graph, which is just as valid.
+	pub solution: Option<&'static [u32]>,
+}
+
+/// Every known test vector
+pub static TEST_VECTORS: [TestVector; 3] = [
+	TestVector {
+		header: KNOWN_16_HASH_1,
+		cuckoo_size: 16,
+		proof_size: 42,
+		solution: Some(&KNOWN_16_SOLUTION_1),
+	},
+	TestVector {
+		header: KNOWN_16_HASH_12,
+		cuckoo_size: 16,
+		proof_size: 12,
+		solution: Some(&KNOWN_16_SOLUTION_12),
+	},
+	TestVector {
+		header: KNOWN_30_HASH_1,
+		cuckoo_size: 30,
+		proof_size: 42,
+		solution: None,
+	},
+];
+
+/// #Description
+///
+/// Finds the test vector for plugins searching graphs of the given size
+/// for cycles of the given length.
+///
+/// #Arguments
+///
+/// * `edge_bits` (IN) The log2 of the number of edges in the graph,
+/// i.e. the cuckoo size less one
+/// * `proof_size` (IN) The number of edges in a cycle
+///
+/// #Returns
+///
+/// The test vector, or None if there isn't one for the size
+///
+
+pub fn test_vector(edge_bits: u32, proof_size: usize) -> Option<&'static TestVector> {
+	TEST_VECTORS
+		.iter()
+		.find(|v| v.cuckoo_size == edge_bits + 1 && v.proof_size == proof_size)
+}
@@ -0,0 +1,255 @@
+// Copyright 2017 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Checks that a proof is a single cycle in the bipartite cuckoo graph
+//! generated from a header, as long as the proof. Grin's proofs are 42
+//! edges, but plugins may declare other even lengths. A graph of cuckoo size N has 2^(N-1) edges,
+//! each joining a node on the U side to one on the V side. Cuckatoo
+//! graphs, which join nodes differently, aren't supported yet.
+
+use std::fmt;
+
+use super::siphash::SipHashKeys;
+use CuckooMinerSolution;
+use GraphType;
+use header_hash;
+
+/// The number of edges in a proof, unless a plugin declares otherwise
+pub const PROOF_SIZE: usize = 42;
+
+/// The most edge bits whose edge indices fit in a u32. Larger graphs'
+/// proofs are read through the plugins' 64-bit calls.
+pub const MAX_32_BIT_EDGE_BITS: u32 = 32;
+
+/// The ways a proof can fail verification
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VerifyError {
+	/// An edge nonce is outside the graph
+	EdgeTooBig,
+
+	/// The edge nonces aren't in strictly ascending order
+	EdgesNotAscending,
+
+	/// The edges' endpoints don't pair up, so they can't form a cycle
+	NonMatchingCycle,
+
+	/// A node in the cycle has more than two of the proof's edges
+	BranchInCycle,
+
+	/// The cycle can't be followed back to its start
+	DeadEnd,
+
+	/// The edges form a cycle shorter than the proof
+	TooShort,
+
+	/// The proof has no edges, or an odd number, so can't be a cycle
+	WrongProofSize,
+}
+
+impl fmt::Display for VerifyError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let msg = match *self {
+			VerifyError::EdgeTooBig => "edge nonce too big for the graph",
+			VerifyError::EdgesNotAscending => "edge nonces not ascending",
+			VerifyError::NonMatchingCycle => "endpoints don't match up",
+			VerifyError::BranchInCycle => "branch in cycle",
+			VerifyError::DeadEnd => "cycle dead ends",
+			VerifyError::TooShort => "cycle too short",
+			VerifyError::WrongProofSize => "proof can't be a cycle of its length",
+		};
+		write!(f, "{}", msg)
+	}
+}
+
+// Endpoint of an edge, with the side of the graph in the lowest bit
+fn sipnode(keys: &SipHashKeys, edge_mask: u64, edge: u64, uorv: u64) -> u64 {
+	((keys.siphash24(2 * edge + uorv) & edge_mask) << 1) | uorv
+}
+
+/// #Description
+///
+/// Verifies a proof against the graph generated from a header.
+///
+/// #Arguments
+///
+/// * `header` (IN) The header, exactly as pushed to the plugin
+/// * `cuckoo_size` (IN) The cuckoo size the proof was found at
+/// * `proof` (IN) The proof's edge nonces, as u32s or u64s
+///
+/// #Returns
+///
+/// * `Ok(())` if the proof is a cycle of its length in the graph
+/// * a [VerifyError](enum.VerifyError.html) describing the failure
+/// otherwise
+///
+
+pub fn verify<T: Copy + Into<u64>>(header: &[u8], cuckoo_size: u32, proof: &[T]) -> Result<(), VerifyError> {
+	verify_with_keys(&SipHashKeys::from_header(header), cuckoo_size, proof)
+}
+
+/// #Description
+///
+/// Verifies a proof as a node would before accepting it, independently of
+/// the plugin which found it: the header is put back together around the
+/// nonce and hashed, as [header_hash](fn.header_hash.html) does, and the
+/// proof must be a cycle of exactly [PROOF_SIZE](constant.PROOF_SIZE.html)
+/// edges in the graph generated from the hash. Run on a solution before
+/// submitting it, this catches bogus proofs from buggy or malicious
+/// plugins.
+///
+/// #Arguments
+///
+/// * `header_pre` (IN) The part of the header before the nonce
+/// * `header_post` (IN) The part of the header after the nonce
+/// * `nonce` (IN) The nonce the proof was found for
+/// * `cuckoo_size` (IN) The cuckoo size the proof was found at
+/// * `proof` (IN) The proof's edge nonces, as u32s or u64s
+///
+/// #Returns
+///
+/// * `Ok(())` if the proof is a 42-cycle in the header's graph
+/// * a WrongProofSize [VerifyError](enum.VerifyError.html) if the proof
+/// isn't 42 edges long, or another describing why it isn't a cycle
+///
+
+pub fn verify_nonce<T: Copy + Into<u64>>(
+	header_pre: &[u8],
+	header_post: &[u8],
+	nonce: u64,
+	cuckoo_size: u32,
+	proof: &[T],
+) -> Result<(), VerifyError> {
+	if proof.len() != PROOF_SIZE {
+		return Err(VerifyError::WrongProofSize);
+	}
+	verify(&header_hash(header_pre, header_post, nonce), cuckoo_size, proof)
+}
+
+/// Whether proofs found in graphs of the given type can be verified,
+/// i.e. they're cuckoo proofs. Plugins report the type in their
+/// [capabilities](struct.PluginCapabilities.html), and it's kept by
+/// [PluginLibrary::graph_type](struct.PluginLibrary.html#method.graph_type).
+
+pub fn is_verifiable(graph_type: GraphType) -> bool {
+	graph_type == GraphType::Cuckoo
+}
+
+/// Verifies a solution against the header it was found for, at the
+/// solution's cuckoo size
+
+pub fn verify_solution(header: &[u8], solution: &CuckooMinerSolution) -> Result<(), VerifyError> {
+	verify(header, solution.cuckoo_size, &solution.solution_nonces)
+}
+
+/// #Description
+///
+/// Checks a proof is well formed, without checking it's a cycle: each
+/// edge nonce must be within the graph, and larger than the one before,
+/// so none is repeated. This is cheap enough to run on every solution,
+/// and catches plugins returning garbage.
+///
+/// #Arguments
+///
+/// * `proof` (IN) The proof's edge nonces, as u32s or u64s
+/// * `edge_bits` (IN) The log2 of the number of edges in the graph, i.e.
+/// the cuckoo size less one. Only the order is checked if this isn't
+/// known.
+///
+/// #Returns
+///
+/// * `Ok(())` if the proof is well formed
+/// * an EdgeTooBig or EdgesNotAscending
+/// [VerifyError](enum.VerifyError.html) otherwise
+///
+
+pub fn check_bounds<T: Copy + Into<u64>>(proof: &[T], edge_bits: Option<u32>) -> Result<(), VerifyError> {
+	for n in 0..proof.len() {
+		if let Some(b) = edge_bits {
+			if b < 64 && proof[n].into() >= 1u64 << b {
+				return Err(VerifyError::EdgeTooBig);
+			}
+		}
+		if n > 0 && proof[n].into() <= proof[n - 1].into() {
+			return Err(VerifyError::EdgesNotAscending);
+		}
+	}
+	Ok(())
+}
+
+/// As [verify](fn.verify.html), given the SipHash keys rather than the
+/// header they're derived from
+
+pub fn verify_with_keys<T: Copy + Into<u64>>(
+	keys: &SipHashKeys,
+	cuckoo_size: u32,
+	proof: &[T],
+) -> Result<(), VerifyError> {
+	if cuckoo_size < 2 || cuckoo_size > 64 {
+		return Err(VerifyError::EdgeTooBig);
+	}
+	let proof_size = proof.len();
+	if proof_size == 0 || proof_size % 2 != 0 {
+		return Err(VerifyError::WrongProofSize);
+	}
+	check_bounds(proof, Some(cuckoo_size - 1))?;
+	// edges and nodes are u64s throughout, for graphs too large for
+	// their edge indices to fit in a u32
+	let edge_mask = (1u64 << (cuckoo_size - 1)) - 1;
+	let mut uvs = vec![0u64; 2 * proof_size];
+	let mut xor0 = 0;
+	let mut xor1 = 0;
+	for n in 0..proof_size {
+		let edge = proof[n].into();
+		uvs[2 * n] = sipnode(keys, edge_mask, edge, 0);
+		uvs[2 * n + 1] = sipnode(keys, edge_mask, edge, 1);
+		xor0 ^= uvs[2 * n];
+		xor1 ^= uvs[2 * n + 1];
+	}
+	if xor0 | xor1 != 0 {
+		return Err(VerifyError::NonMatchingCycle);
+	}
+	// follow the cycle from the first edge, moving to the one other edge
+	// sharing each endpoint
+	let mut n = 0;
+	let mut i = 0;
+	loop {
+		let mut j = i;
+		let mut k = i;
+		loop {
+			k = (k + 2) % (2 * proof_size);
+			if k == i {
+				break;
+			}
+			if uvs[k] == uvs[i] {
+				if j != i {
+					return Err(VerifyError::BranchInCycle);
+				}
+				j = k;
+			}
+		}
+		if j == i {
+			return Err(VerifyError::DeadEnd);
+		}
+		i = j ^ 1;
+		n += 1;
+		if i == 0 {
+			break;
+		}
+	}
+	match n == proof_size {
+		true => Ok(()),
+		false => Err(VerifyError::TooShort),
+	}
+}
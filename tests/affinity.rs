@@ -0,0 +1,88 @@
+// Copyright 2017 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tests of CPU affinity and thread priority settings, using the mock
+//! plugin to see the parameters they're passed to plugins as
+
+extern crate cuckoo_miner as cuckoo;
+
+use std::thread;
+
+use cuckoo::{CuckooMiner, CuckooMinerConfig, CuckooMinerError, PluginLibrary, Priority, affinity_mask,
+	online_cores, pin_current_thread, CPU_AFFINITY_MASK_PARAM, THREAD_PRIORITY_PARAM};
+
+pub mod common;
+
+// A config for a fresh copy of the mock plugin with the given placement
+fn mock_config(cores: Option<Vec<usize>>, priority: Option<Priority>) -> CuckooMinerConfig {
+	let mut config = CuckooMinerConfig::new();
+	config.plugin_full_path = common::mock_plugin_copy();
+	config.cpu_affinity = cores;
+	config.thread_priority = priority;
+	config
+}
+
+#[test]
+fn masks_are_built_from_cores() {
+	assert_eq!(affinity_mask(&[0]).unwrap(), 1);
+	if online_cores().unwrap_or(64) > 3 {
+		assert_eq!(affinity_mask(&[3, 1, 1]).unwrap(), 0b1010);
+	}
+	for cores in &[vec![], vec![64], vec![0, 1000]] {
+		match affinity_mask(cores) {
+			Err(CuckooMinerError::ParameterError(_)) => {}
+			r => panic!("Expected a ParameterError for {:?}, got {:?}", cores, r),
+		}
+	}
+	if let Some(n) = online_cores() {
+		assert!(affinity_mask(&[n]).is_err());
+	}
+}
+
+#[test]
+fn placement_is_passed_to_the_plugin() {
+	let config = mock_config(Some(vec![0]), Some(Priority::Low));
+	// shares the mock's state, to see the values the miner sets
+	let observer = PluginLibrary::new(&config.plugin_full_path).unwrap();
+	let _miner = CuckooMiner::new(vec![config]).unwrap();
+	assert_eq!(observer.get_parameter_u64(CPU_AFFINITY_MASK_PARAM).unwrap(), 1);
+	assert_eq!(observer.get_parameter(THREAD_PRIORITY_PARAM).unwrap(), Priority::Low.value());
+
+	// and left alone when not configured
+	let config = mock_config(None, None);
+	let observer = PluginLibrary::new(&config.plugin_full_path).unwrap();
+	let _miner = CuckooMiner::new(vec![config]).unwrap();
+	assert_eq!(observer.get_parameter_u64(CPU_AFFINITY_MASK_PARAM).unwrap(), 0);
+	assert_eq!(observer.get_parameter(THREAD_PRIORITY_PARAM).unwrap(), Priority::Normal.value());
+}
+
+#[test]
+fn invalid_cores_are_rejected() {
+	for cores in vec![vec![], vec![64], vec![0, 4096]] {
+		match CuckooMiner::new(vec![mock_config(Some(cores.clone()), None)]) {
+			Err(CuckooMinerError::ParameterError(_)) => {}
+			Err(e) => panic!("Expected a ParameterError for {:?}, got {:?}", cores, e),
+			Ok(_) => panic!("Cores {:?} were accepted", cores),
+		}
+	}
+}
+
+#[test]
+fn threads_can_be_pinned() {
+	let pinned = thread::spawn(|| pin_current_thread(&[0])).join().unwrap();
+	// refusing is logged rather than an error, so only the cores matter
+	assert!(pinned.is_ok());
+	let invalid = thread::spawn(|| pin_current_thread(&[64])).join().unwrap();
+	assert!(invalid.is_err());
+}
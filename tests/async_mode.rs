@@ -15,6 +15,13 @@
 
 //! Tests for async mode.. should be run with RUST_TEST_THREADS=1
 
+extern crate cuckoo_miner as cuckoo;
+extern crate time;
+
+use std::path::Path;
+use std::sync::mpsc;
+use std::time::Duration;
+
 pub mod common;
 
 //mines for a bit on each available plugin, one after the other
@@ -81,3 +88,266 @@ fn on_commit_mine_plugins_async() {
 	}
 	common::mine_async_for_duration(plugin_path_vec, 15, None);
 }
+
+//switches to a new job mid-run, after which only solutions for the new
+//job should be returned
+#[test]
+fn on_commit_switch_job_async() {
+	let caps = common::get_plugin_vec("lean_cpu_16");
+	let mut config = cuckoo::CuckooMinerConfig::new();
+	config.plugin_full_path = caps[0].full_path.clone();
+	let miner = cuckoo::CuckooMiner::new(vec![config]).unwrap();
+	let job_handle = miner
+		.notify(1, common::SAMPLE_GRIN_PRE_HEADER_1, common::SAMPLE_GRIN_POST_HEADER_1, 0, false)
+		.unwrap();
+
+	//let job 1 find a few solutions, leaving some unread
+	let deadline = time::get_time().sec + 5;
+	while time::get_time().sec < deadline {
+		if let Some(s) = job_handle.get_solution() {
+			assert!(s.id == 1);
+		}
+	}
+
+	//same header, so any job 1 solutions would be just as valid
+	job_handle.notify(2, common::SAMPLE_GRIN_PRE_HEADER_1, common::SAMPLE_GRIN_POST_HEADER_1, 0).unwrap();
+	let mut found = 0;
+	let deadline = time::get_time().sec + 10;
+	while time::get_time().sec < deadline {
+		if let Some(s) = job_handle.get_solution() {
+			assert!(s.id == 2, "Solution for job {} after switching to job 2", s.id);
+			found += 1;
+		}
+	}
+	println!("Found {} solutions for job 2", found);
+	job_handle.stop_jobs();
+
+	//solutions found before the stop can still be read, but the
+	//stopped job can't be switched
+	if let Some(sols) = job_handle.get_solutions() {
+		assert!(sols.iter().all(|s| s.id == 2));
+	}
+	assert!(job_handle.get_solutions().is_none());
+	match job_handle.notify(3, common::SAMPLE_GRIN_PRE_HEADER_1, common::SAMPLE_GRIN_POST_HEADER_1, 0) {
+		Err(cuckoo::CuckooMinerError::ProcessingStopped(_)) => {},
+		r => panic!("Expected ProcessingStopped, got {:?}", r),
+	}
+}
+
+//solutions should arrive on the event channel without any polling,
+//followed by a notice that the job has stopped
+#[test]
+fn on_commit_solution_event_async() {
+	let caps = common::get_plugin_vec("lean_cpu_16");
+	let mut config = cuckoo::CuckooMinerConfig::new();
+	config.plugin_full_path = caps[0].full_path.clone();
+	let mut miner = cuckoo::CuckooMiner::new(vec![config]).unwrap();
+	let (tx, rx) = mpsc::channel();
+	miner.set_event_channel(tx);
+	let job_handle = miner
+		.notify(1, common::SAMPLE_GRIN_PRE_HEADER_1, common::SAMPLE_GRIN_POST_HEADER_1, 0, true)
+		.unwrap();
+
+	let deadline = time::get_time().sec + 30;
+	let mut found = None;
+	while found.is_none() && time::get_time().sec < deadline {
+		match rx.recv_timeout(Duration::from_millis(500)) {
+			Ok(cuckoo::MinerEvent::SolutionFound(s)) => found = Some(s),
+			Ok(cuckoo::MinerEvent::PluginError { plugin, code, message }) => {
+				panic!("Plugin {} failed with code {}: {}", plugin, code, message)
+			}
+			_ => {}
+		}
+	}
+	let s = found.expect("No solution event within 30 seconds");
+	assert!(s.id == 1);
+	let stem = Path::new(&caps[0].full_path).file_stem().unwrap().to_string_lossy().into_owned();
+	assert!(s.plugin_name == Some(stem));
+
+	job_handle.stop_jobs();
+	let stopped = rx.iter().any(|e| match e {
+		cuckoo::MinerEvent::JobStopped(id) => id == 1,
+		_ => false,
+	});
+	assert!(stopped);
+}
+
+//the same plugin loaded twice with different settings should run as two
+//independent plugins, both doing work on the same job
+#[test]
+fn on_commit_same_plugin_twice_async() {
+	let caps = common::get_plugin_vec("lean_cpu_16");
+	let mut configs = Vec::new();
+	for threads in 1..3 {
+		let mut config = cuckoo::CuckooMinerConfig::new();
+		config.plugin_full_path = caps[0].full_path.clone();
+		config.parameter_list.push((String::from("NUM_THREADS"), 0, threads));
+		configs.push(config);
+	}
+	let miner = cuckoo::CuckooMiner::new(configs).unwrap();
+	let job_handle = miner
+		.notify(1, common::SAMPLE_GRIN_PRE_HEADER_1, common::SAMPLE_GRIN_POST_HEADER_1, 0, true)
+		.unwrap();
+
+	let mut solution = None;
+	let mut attempts = [0, 0];
+	let deadline = time::get_time().sec + 60;
+	while time::get_time().sec < deadline {
+		if solution.is_none() {
+			solution = job_handle.get_solution();
+		}
+		let stats = job_handle.get_all_stats();
+		assert!(stats.len() == 2);
+		for (i, s) in stats.into_iter().enumerate() {
+			let s = s.unwrap();
+			attempts[i] = s.iter().map(|d| d.iterations_completed).sum();
+		}
+		if solution.is_some() && attempts[0] > 0 && attempts[1] > 0 {
+			break;
+		}
+	}
+	job_handle.stop_jobs();
+
+	println!("Attempts per plugin: {:?}", attempts);
+	assert!(attempts[0] > 0 && attempts[1] > 0);
+	let solution = solution.expect("No solution found within 60 seconds");
+	let stem = Path::new(&caps[0].full_path).file_stem().unwrap().to_string_lossy().into_owned();
+	let name = solution.plugin_name.unwrap();
+	assert!(name == stem || name == format!("{}.1", stem), "Unexpected plugin {}", name);
+}
+
+//mining from a fixed starting nonce should reach the sample header's
+//known solution, and report how far through the nonces it has got
+#[test]
+fn on_commit_start_nonce_async() {
+	let caps = common::get_plugin_vec("lean_cpu_16");
+	let mut config = cuckoo::CuckooMinerConfig::new();
+	config.plugin_full_path = caps[0].full_path.clone();
+	let mut miner = cuckoo::CuckooMiner::new(vec![config]).unwrap();
+	miner.set_start_nonce(0);
+	let job_handle = miner
+		.notify(1, common::SAMPLE_GRIN_PRE_HEADER_1, common::SAMPLE_GRIN_POST_HEADER_1, 0, true)
+		.unwrap();
+
+	let mut found = None;
+	let deadline = time::get_time().sec + 60;
+	while found.is_none() && time::get_time().sec < deadline {
+		if let Some(s) = job_handle.get_solution() {
+			if s.nonce == common::SAMPLE_GRIN_16_NONCE_1 {
+				found = Some(s);
+			}
+		}
+	}
+	let position = job_handle.nonce_position(0);
+	job_handle.stop_jobs();
+
+	let s = found.expect("Known solution not found within 60 seconds");
+	assert!(s.solution_nonces == common::widen(&common::SAMPLE_GRIN_16_SOLUTION_1));
+	assert!(position.unwrap() >= common::SAMPLE_GRIN_16_NONCE_1);
+}
+
+//Starts a job from the header as bytes, which the miner hashes itself,
+//and checks the known solution for it comes back
+#[test]
+fn on_commit_notify_header_async() {
+	let caps = common::get_plugin_vec("lean_cpu_16");
+	let mut config = cuckoo::CuckooMinerConfig::new();
+	config.plugin_full_path = caps[0].full_path.clone();
+	let mut miner = cuckoo::CuckooMiner::new(vec![config]).unwrap();
+	miner.set_start_nonce(0);
+	let header_pre = cuckoo::from_hex(common::SAMPLE_GRIN_PRE_HEADER_1).unwrap();
+	let header_post = cuckoo::from_hex(common::SAMPLE_GRIN_POST_HEADER_1).unwrap();
+	let job_handle = miner.notify_header(1, &header_pre, &header_post, 0).unwrap();
+
+	let mut found = None;
+	let deadline = time::get_time().sec + 60;
+	while found.is_none() && time::get_time().sec < deadline {
+		if let Some(s) = job_handle.get_solution() {
+			if s.nonce == common::SAMPLE_GRIN_16_NONCE_1 {
+				found = Some(s);
+			}
+		}
+	}
+	job_handle.stop_jobs();
+
+	let s = found.expect("Known solution not found within 60 seconds");
+	assert!(s.solution_nonces == common::widen(&common::SAMPLE_GRIN_16_SOLUTION_1));
+}
+
+//Disables the only device of a CPU plugin, which should then sit idle,
+//still reported in the stats but marked as not in use
+#[test]
+fn on_commit_disabled_device_async() {
+	let caps = common::get_plugin_vec("lean_cpu_16");
+	let mut config = cuckoo::CuckooMinerConfig::new();
+	config.plugin_full_path = caps[0].full_path.clone();
+	config.enabled_devices = Some(vec![]);
+	let miner = cuckoo::CuckooMiner::new(vec![config]).unwrap();
+	let header_pre = cuckoo::from_hex(common::SAMPLE_GRIN_PRE_HEADER_1).unwrap();
+	let header_post = cuckoo::from_hex(common::SAMPLE_GRIN_POST_HEADER_1).unwrap();
+	let job_handle = miner.notify_header(1, &header_pre, &header_post, 0).unwrap();
+
+	std::thread::sleep(Duration::from_secs(5));
+	let stats = job_handle.get_stats(0).unwrap();
+	assert!(stats.len() == 1);
+	assert!(stats[0].in_use == 0);
+	assert!(stats[0].iterations_completed == 0);
+	assert!(job_handle.get_solution().is_none());
+
+	//re-enabling it at runtime starts it mining again
+	job_handle.set_device_enabled(0, 0, true).unwrap();
+	let deadline = time::get_time().sec + 60;
+	let mut iterations = 0;
+	while iterations == 0 && time::get_time().sec < deadline {
+		iterations = job_handle.get_stats(0).unwrap().iter().map(|s| s.iterations_completed).sum();
+		std::thread::sleep(Duration::from_millis(100));
+	}
+	job_handle.stop_jobs();
+	assert!(iterations > 0);
+}
+
+//Replaces a plugin's file while it's mining, by renaming a fresh copy over
+//it, reloads it and checks it carries on attempting graphs
+#[test]
+fn on_commit_reload_plugin_async() {
+	let caps = common::get_plugin_vec("lean_cpu_16");
+	let mut dir = std::env::temp_dir();
+	dir.push(format!("cuckoo-miner-reload-test-{}", std::process::id()));
+	std::fs::create_dir_all(&dir).unwrap();
+	let plugin = dir.join("lean_cpu_16.cuckooplugin");
+	let replacement = dir.join("lean_cpu_16.new");
+	std::fs::copy(&caps[0].full_path, &plugin).unwrap();
+
+	let mut config = cuckoo::CuckooMinerConfig::new();
+	config.plugin_full_path = String::from(plugin.to_str().unwrap());
+	let miner = cuckoo::CuckooMiner::new(vec![config]).unwrap();
+	let header_pre = cuckoo::from_hex(common::SAMPLE_GRIN_PRE_HEADER_1).unwrap();
+	let header_post = cuckoo::from_hex(common::SAMPLE_GRIN_POST_HEADER_1).unwrap();
+	let job_handle = miner.notify_header(1, &header_pre, &header_post, 0).unwrap();
+
+	let wait_for_graphs = || {
+		let deadline = time::get_time().sec + 60;
+		while time::get_time().sec < deadline {
+			let iterations: u32 = job_handle.get_stats(0).unwrap().iter().map(|s| s.iterations_completed).sum();
+			if iterations > 0 {
+				return iterations;
+			}
+			std::thread::sleep(Duration::from_millis(100));
+		}
+		panic!("No graphs attempted within 60 seconds");
+	};
+	wait_for_graphs();
+
+	std::fs::copy(&caps[0].full_path, &replacement).unwrap();
+	std::fs::rename(&replacement, &plugin).unwrap();
+	job_handle.reload_plugin("lean_cpu_16").unwrap();
+
+	//a fresh plugin starts counting from scratch
+	assert!(wait_for_graphs() > 0);
+	match job_handle.reload_plugin("no_such_plugin") {
+		Err(cuckoo::CuckooMinerError::PluginNotFoundError(_)) => {},
+		r => panic!("Expected PluginNotFoundError, got {:?}", r),
+	}
+	job_handle.stop_jobs();
+	let _ = std::fs::remove_dir_all(&dir);
+}
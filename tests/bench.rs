@@ -0,0 +1,59 @@
+// Copyright 2017 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tests for summarising benchmark runs
+
+extern crate cuckoo_miner as cuckoo;
+
+use std::time::Duration;
+
+use cuckoo::{BenchmarkMode, BenchmarkReport, benchmark_header};
+
+fn ms(n: u64) -> Duration {
+	Duration::from_millis(n)
+}
+
+#[test]
+fn report_summarises_graph_times() {
+	let times = [ms(40), ms(10), ms(30), ms(20)];
+	let r = BenchmarkReport::new("lean_cpu_16", BenchmarkMode::Sync, &times, 1, ms(200));
+	assert_eq!(r.graphs, 4);
+	assert_eq!(r.min_graph_time, ms(10));
+	assert_eq!(r.median_graph_time, ms(25));
+	assert_eq!(r.mean_graph_time, ms(25));
+	assert_eq!(r.max_graph_time, ms(40));
+	assert!((r.graphs_per_second - 20.0).abs() < 1e-9);
+	assert_eq!(r.solutions_found, 1);
+
+	let r = BenchmarkReport::new("lean_cpu_16", BenchmarkMode::Async, &times[..3], 0, ms(60));
+	assert_eq!(r.median_graph_time, ms(30));
+	assert!((r.graphs_per_second - 50.0).abs() < 1e-9);
+}
+
+#[test]
+fn empty_report_is_zero() {
+	let r = BenchmarkReport::new("lean_cpu_16", BenchmarkMode::Sync, &[], 0, Duration::new(0, 0));
+	assert_eq!(r.graphs, 0);
+	assert_eq!(r.median_graph_time, Duration::new(0, 0));
+	assert_eq!(r.graphs_per_second, 0.0);
+}
+
+#[test]
+fn benchmark_headers_count_up_from_the_seed() {
+	let h = benchmark_header(0xff, 1);
+	assert_eq!(&h[..8], &[0, 0, 0, 0, 0, 0, 1, 0]);
+	assert!(h[8..].iter().all(|b| *b == 0));
+	assert_eq!(benchmark_header(5, 3), benchmark_header(3, 5));
+	assert_eq!(&benchmark_header(u64::max_value(), 1)[..8], &[0; 8]);
+}
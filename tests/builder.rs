@@ -0,0 +1,98 @@
+// Copyright 2017 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tests for putting a miner together with the builder
+
+extern crate cuckoo_miner as cuckoo;
+
+use cuckoo::{CuckooMinerBuilder, CuckooMinerError, NonceRange};
+
+// the errors from building, failing if the build doesn't fail
+fn build_errors(builder: CuckooMinerBuilder) -> Vec<CuckooMinerError> {
+	match builder.build() {
+		Err(CuckooMinerError::MinerBuildError(errors)) => errors,
+		Err(e) => panic!("Expected a MinerBuildError, got {:?}", e),
+		Ok(_) => panic!("Expected a MinerBuildError, but the miner was built"),
+	}
+}
+
+#[test]
+fn settings_apply_to_the_current_plugin() {
+	let b = CuckooMinerBuilder::new()
+		.plugin("mean_cpu_30")
+		.parameter("NUM_THREADS", 8)
+		.queue_size(16)
+		.plugin("lean_cuda_30")
+		.device(0)
+		.parameter("N_TRIMS", 96)
+		.device(2)
+		.parameter("N_TRIMS", 64)
+		.nonce_range(NonceRange::new(1, 2));
+	let configs = b.configs();
+	assert_eq!(configs.len(), 2);
+	assert_eq!(configs[0].plugin_full_path, "mean_cpu_30");
+	assert_eq!(configs[0].parameter_list, vec![(String::from("NUM_THREADS"), 0, 8)]);
+	assert_eq!(configs[0].queue_size, Some(16));
+	assert!(configs[0].enabled_devices.is_none());
+	assert!(configs[0].nonce_range.is_none());
+	assert!(configs[1].parameter_list.is_empty());
+	assert_eq!(configs[1].enabled_devices, Some(vec![0, 2]));
+	assert_eq!(configs[1].device_parameters[&0]["N_TRIMS"], 96);
+	assert_eq!(configs[1].device_parameters[&2]["N_TRIMS"], 64);
+	assert_eq!(configs[1].nonce_range, Some(NonceRange::new(1, 2)));
+}
+
+#[test]
+fn no_plugins_is_an_error() {
+	let errors = build_errors(CuckooMinerBuilder::new());
+	assert_eq!(errors.len(), 1);
+}
+
+#[test]
+fn every_error_is_reported() {
+	let errors = build_errors(
+		CuckooMinerBuilder::new()
+			.parameter("NUM_THREADS", 4)
+			.plugin("no_such_plugin_a")
+			.parameter("NUM_THREADS", 4)
+			.plugin("no_such_plugin_b"),
+	);
+	assert_eq!(errors.len(), 3);
+	match errors[0] {
+		CuckooMinerError::ParameterError(ref m) => assert!(m.contains("NUM_THREADS")),
+		ref e => panic!("Expected a ParameterError, got {:?}", e),
+	}
+	for e in &errors[1..] {
+		match *e {
+			CuckooMinerError::PluginNotFoundError(_) => {}
+			ref e => panic!("Expected a PluginNotFoundError, got {:?}", e),
+		}
+	}
+}
+
+#[test]
+fn colliding_nonce_ranges_are_an_error() {
+	// the first plugin's automatic range is the one given to the second
+	let errors = build_errors(
+		CuckooMinerBuilder::new()
+			.plugin("no_such_plugin_a")
+			.plugin("no_such_plugin_b")
+			.nonce_range(NonceRange::interleaved(0, 2)),
+	);
+	assert_eq!(errors.len(), 3);
+	match errors[0] {
+		CuckooMinerError::ConfigError(ref m) => assert!(m.contains("no_such_plugin_a") && m.contains("no_such_plugin_b")),
+		ref e => panic!("Expected a ConfigError, got {:?}", e),
+	}
+}
@@ -21,39 +21,19 @@ extern crate time;
 extern crate rand;
 
 use std::path::PathBuf;
-use std::fmt::Write;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::{env, fs, process};
 use std;
 
 use common::rand::Rng;
 
 use self::cuckoo::{CuckooPluginManager,
+	to_hex,
 	CuckooPluginCapabilities,
 	CuckooMinerSolution,
 	CuckooMinerConfig,
-	CuckooMiner};
-
-// Encode the provided bytes into a hex string
-pub fn to_hex(bytes: Vec<u8>) -> String {
-	let mut s = String::new();
-	for byte in bytes {
-		write!(&mut s, "{:02x}", byte).expect("Unable to write");
-	}
-	s
-}
-
-//Helper to convert from hex string
-//avoids a lot of awkward byte array initialisation below
-pub fn _from_hex_string(in_str: &str) -> Vec<u8> {
-	let mut bytes = Vec::new();
-	for i in 0..(in_str.len() / 2) {
-		let res = u8::from_str_radix(&in_str[2 * i..2 * i + 2], 16);
-		match res {
-			Ok(v) => bytes.push(v),
-			Err(e) => println!("Problem with hex: {}", e),
-		}
-	}
-	bytes
-}
+	CuckooMiner,
+	PluginLibrary};
 
 pub const _DLL_SUFFIX: &str = ".cuckooplugin";
 
@@ -75,12 +55,78 @@ pub const SAMPLE_GRIN_PRE_HEADER_1:&str = "00000000000000118e0fe6bcfaa76c6795592
 
 pub const SAMPLE_GRIN_POST_HEADER_1:&str = "010a020364";
 
-//hashes known to return a solution at cuckoo 30 and 16
-pub const KNOWN_30_HASH_1:&str = "11c5059b4d4053131323fdfab6a6509d73ef22\
-9aedc4073d5995c6edced5a3e6";
+//hashes known to return a solution at cuckoo 30 and 16, with their
+//solutions where known
+pub use self::cuckoo::{KNOWN_30_HASH_1, KNOWN_16_HASH_1, KNOWN_16_SOLUTION_1,
+	KNOWN_16_HASH_12, KNOWN_16_SOLUTION_12};
+
+//the first nonce which, inserted between SAMPLE_GRIN_PRE_HEADER_1 and
+//SAMPLE_GRIN_POST_HEADER_1, gives a header with a solution at cuckoo 16
+pub const SAMPLE_GRIN_16_NONCE_1:u64 = 56;
+
+//the solution for SAMPLE_GRIN_16_NONCE_1
+pub const SAMPLE_GRIN_16_SOLUTION_1:[u32;42] = [
+	219, 521, 1028, 1059, 2358, 2674, 2789, 2896, 3854, 4796,
+	5455, 7940, 8068, 8109, 8151, 9872, 10165, 10353, 10660, 11280,
+	11594, 12008, 12103, 14227, 15011, 15404, 15446, 15449, 18315, 18356,
+	20541, 22292, 22652, 23965, 24148, 24526, 25081, 25467, 26310, 26502,
+	26612, 32263,
+];
+
+//a header of zeroes but for 0x28 in its second byte and 0x29 in its
+//last, which the mock plugin solves at 40 edge bits. The solution isn't
+//a real cycle
+pub const MOCK_40_HASH:&str = "002800000000000000000000000000000000000000\
+0000000000000000000029";
+
+//the mock's solution to MOCK_40_HASH, with nonces too big for a u32
+pub const MOCK_40_SOLUTION:[u64;42] = [
+	59602490257, 64261236989, 69277281641, 180341550461, 189255149691, 226092516247,
+	230140327644, 230486493922, 270719719242, 279660798720, 306438998373, 325671254997,
+	344258043877, 350489710499, 364924463807, 372616235987, 451390609670, 542069801734,
+	573092164861, 576223994975, 579101676094, 583590998906, 584238525883, 641919827914,
+	652379084937, 708505498145, 735652418662, 753822818061, 770125198288, 784345123092,
+	808810046096, 812724089554, 815597889918, 824117248069, 842364945140, 863910366229,
+	865271933087, 872636648149, 903836191478, 909857257787, 938281416271, 1077535352614,
+];
 
-pub const KNOWN_16_HASH_1:&str = "c008b9ff7292fdacef0efbdff73d1db66674ff\
-3b6dea6cca670c85b6a110f0b2";
+// Widens 32-bit solution nonces, to compare against a solution's
+pub fn widen(nonces: &[u32]) -> Vec<u64> {
+	nonces.iter().map(|n| *n as u64).collect()
+}
+
+// Path to the mock plugin, which is built along with the tests. Test
+// executables are in target/<profile>/deps, and the mock is built to that
+// directory and its parent.
+pub fn mock_plugin_path() -> String {
+	let file = format!("{}cuckoo_mock_plugin{}", env::consts::DLL_PREFIX, env::consts::DLL_SUFFIX);
+	let exe = env::current_exe().unwrap();
+	let deps = exe.parent().unwrap();
+	for dir in vec![deps, deps.parent().unwrap()] {
+		if dir.join(&file).is_file() {
+			return dir.join(&file).to_string_lossy().into_owned();
+		}
+	}
+	panic!("The mock plugin {} hasn't been built next to {}", file, deps.display());
+}
+
+// Copies the mock plugin to a new file, returning its path. The mock's
+// state is global to each loaded library, so each test mines with its own
+// copy rather than sharing one with the tests running alongside it.
+pub fn mock_plugin_copy() -> String {
+	static COPIES: AtomicUsize = AtomicUsize::new(0);
+	let mut path = env::temp_dir();
+	path.push(format!("cuckoo-mock-{}", process::id()));
+	fs::create_dir_all(&path).unwrap();
+	path.push(format!("mock.{}.cuckooplugin", COPIES.fetch_add(1, Ordering::SeqCst)));
+	fs::copy(mock_plugin_path(), &path).unwrap();
+	path.to_string_lossy().into_owned()
+}
+
+// Helper to load a fresh copy of the mock plugin
+pub fn load_mock_plugin() -> PluginLibrary {
+	PluginLibrary::new(&mock_plugin_copy()).unwrap()
+}
 
 pub fn get_random_hash() -> [u8;32] {
 	let mut ret_val:[u8;32] = [0;32];
@@ -125,7 +171,7 @@ pub fn mine_once(full_path:&str, params:Option<Vec<(String, u32, u32)>>) {
 	let header:[u8; 32] = get_random_hash();
 	let mut cuckoo_size = 0;
 	let mut solution = CuckooMinerSolution::new();
-	let result = miner.mine(&header, &mut cuckoo_size, &mut solution, 0).unwrap();
+	miner.mine_hash(&header, &mut cuckoo_size, &mut solution, 0).unwrap();
 }
 
 // Helper function, tests a particular miner implementation against a known set
@@ -157,11 +203,11 @@ pub fn mine_sync_for_duration(full_path:&str, duration_in_seconds: i64, params:O
 			let header:[u8; 32] = get_random_hash();
 			let mut cuckoo_size = 0;
 			//Mine on plugin loaded at index 0
-			let result = miner.mine(&header, &mut cuckoo_size, &mut solution, 0).unwrap();
+			let result = miner.mine_hash(&header, &mut cuckoo_size, &mut solution, 0).unwrap();
 			iterations+=1;
 			if result == true {
 				println!("Solution found after {} iterations: {}", i, solution);
-				println!("For hash: {:?}", to_hex(header.to_vec()));
+				println!("For hash: {:?}", to_hex(&header));
 				i=0;
 				break;
 			}
@@ -176,8 +222,8 @@ pub fn mine_sync_for_duration(full_path:&str, duration_in_seconds: i64, params:O
 					let last_solution_time_secs = s.last_solution_time as f64 / 1000000000.0;
 					let last_hashes_per_sec = 1.0 / last_solution_time_secs;
 					let status = match s.has_errored {
-						0 => "OK",
-						_ => "ERRORED", 
+						false => "OK",
+						true => "ERRORED",
 					};
 					println!("Plugin 0 - Device {} ({}) Status: {}, - Last Graph time: {}; Graphs per second: {:.*} \
 					- Total Attempts {}", 
@@ -242,8 +288,8 @@ pub fn mine_async_for_duration(full_paths: Vec<&str>, duration_in_seconds: i64,
 					for s in stats_vec.unwrap().into_iter() {
 						if s.in_use == 0 {continue;}
 						let status = match s.has_errored {
-							0 => "OK",
-							_ => "ERRORED", 
+							false => "OK",
+							true => "ERRORED",
 						};
 						let last_solution_time_secs = s.last_solution_time as f64 / 1000000000.0;
 						let last_hashes_per_sec = 1.0 / last_solution_time_secs;
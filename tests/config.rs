@@ -0,0 +1,292 @@
+// Copyright 2017 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tests for loading miner configs from TOML
+
+extern crate cuckoo_miner as cuckoo;
+
+use std::{env, fs, process};
+use std::fs::File;
+use std::io::Read;
+use std::path::PathBuf;
+
+use cuckoo::{CuckooMinerConfig, CuckooMinerError, CuckooPluginParameter, NonceEncoding, NonceRange, Priority, ALL_DEVICES,
+	MinerConfigFile, MiningMode};
+
+pub mod common;
+
+fn parameter(name: &str, min_value: u64, max_value: u64) -> CuckooPluginParameter {
+	CuckooPluginParameter {
+		name: String::from(name),
+		description: String::new(),
+		default_value: min_value,
+		min_value: min_value,
+		max_value: max_value,
+	}
+}
+
+// the parse error's message, failing if there's no ConfigError
+fn config_error(toml: &str) -> String {
+	match CuckooMinerConfig::from_toml_str(toml) {
+		Err(CuckooMinerError::ConfigError(m)) => m,
+		r => panic!("Expected a ConfigError, got {:?}", r),
+	}
+}
+
+#[test]
+fn example_config_parses() {
+	let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+	path.push("doc/cuckoo-miner.toml");
+	let mut toml = String::new();
+	File::open(path).unwrap().read_to_string(&mut toml).unwrap();
+	assert_eq!(MinerConfigFile::from_toml_str(&toml).unwrap().mode, MiningMode::Async);
+	let configs = CuckooMinerConfig::from_toml_str(&toml).unwrap();
+	assert_eq!(configs.len(), 2);
+	let c = &configs[0];
+	assert_eq!(c.plugin_full_path, "lean_cpu_16");
+	assert!(!c.allow_legacy_plugins);
+	assert!(!c.skip_memory_check);
+	assert_eq!(c.enabled_devices, Some(vec![0]));
+	assert_eq!(c.queue_size, Some(64));
+	assert_eq!(c.nonce_range, Some(NonceRange::new(0, 2)));
+	assert_eq!(c.parameter_list, vec![(String::from("NUM_THREADS"), 0, 4)]);
+	assert_eq!(c.cpu_affinity, Some(vec![0, 1, 2, 3]));
+	assert_eq!(c.thread_priority, Some(Priority::Low));
+	assert_eq!(c.nonce_encoding, NonceEncoding::BigEndianU64);
+	assert_eq!(configs[1].nonce_range, Some(NonceRange::new(1, 2)));
+	assert!(configs[1].cpu_affinity.is_none());
+	assert!(configs[1].thread_priority.is_none());
+}
+
+#[test]
+fn device_parameters_parse() {
+	let configs = CuckooMinerConfig::from_toml_str(
+		r#"
+		[[plugin]]
+		path = "/opt/plugins/cuda_30.cuckooplugin"
+		[plugin.device_parameters.all]
+		N_TRIMS = 96
+		[plugin.device_parameters.1]
+		N_TRIMS = 64
+		"#,
+	).unwrap();
+	let c = &configs[0];
+	assert_eq!(c.plugin_full_path, "/opt/plugins/cuda_30.cuckooplugin");
+	assert_eq!(c.device_parameters[&ALL_DEVICES]["N_TRIMS"], 96);
+	assert_eq!(c.device_parameters[&1]["N_TRIMS"], 64);
+	assert!(c.nonce_range.is_none());
+	assert!(c.enabled_devices.is_none());
+}
+
+#[test]
+fn instance_per_device_fans_out() {
+	let configs = CuckooMinerConfig::from_toml_str(
+		r#"
+		[[plugin]]
+		name = "lean_cuda_30"
+		devices = [0, 2]
+		instance_per_device = true
+		[plugin.nonce_range]
+		start = 1
+		stride = 2
+		[plugin.device_parameters.all]
+		N_TRIMS = 96
+		[plugin.device_parameters.1]
+		N_TRIMS = 32
+		[plugin.device_parameters.2]
+		N_TRIMS = 64
+		"#,
+	).unwrap();
+	assert_eq!(configs.len(), 2);
+	assert_eq!(configs[0].enabled_devices, Some(vec![0]));
+	assert_eq!(configs[1].enabled_devices, Some(vec![2]));
+	assert_eq!(configs[0].nonce_range, Some(NonceRange::new(1, 4)));
+	assert_eq!(configs[1].nonce_range, Some(NonceRange::new(3, 4)));
+	// each keeps the parameters for every device and for its own
+	assert_eq!(configs[0].device_parameters.keys().collect::<Vec<_>>(), vec![&0, &ALL_DEVICES]);
+	assert!(configs[0].device_parameters[&0].is_empty());
+	assert_eq!(configs[1].device_parameters.keys().collect::<Vec<_>>(), vec![&2, &ALL_DEVICES]);
+	assert_eq!(configs[1].device_parameters[&2]["N_TRIMS"], 64);
+	assert!(configs.iter().all(|c| c.device_parameters[&ALL_DEVICES]["N_TRIMS"] == 96));
+
+	assert!(config_error("[[plugin]]\nname = \"lean_cuda_30\"\ninstance_per_device = true").contains("devices"));
+	let m = config_error("[[plugin]]\nname = \"lean_cuda_30\"\ndevices = [0]\ninstance_per_device = 1");
+	assert!(m.contains("instance_per_device"));
+}
+
+#[test]
+fn mining_mode_parses() {
+	let file = MinerConfigFile::from_toml_str("mode = \"sync\"\n[[plugin]]\nname = \"lean_cpu_16\"").unwrap();
+	assert_eq!(file.mode, MiningMode::Sync);
+	assert_eq!(file.plugins.len(), 1);
+	let file = MinerConfigFile::from_toml_str("[[plugin]]\nname = \"lean_cpu_16\"").unwrap();
+	assert_eq!(file.mode, MiningMode::Async);
+
+	assert!(config_error("mode = \"turbo\"\n[[plugin]]\nname = \"lean_cpu_16\"").contains("turbo"));
+	assert!(config_error("mode = 1\n[[plugin]]\nname = \"lean_cpu_16\"").contains("mode"));
+	let m = config_error("mode = \"sync\"\n[[plugin]]\nname = \"lean_cpu_16\"\n[[plugin]]\nname = \"lean_cpu_30\"");
+	assert!(m.contains("sync"));
+}
+
+#[test]
+fn config_file_is_loaded_against_the_plugins() {
+	let mut path = env::temp_dir();
+	path.push(format!("cuckoo-miner-{}.toml", process::id()));
+	let plugin = common::mock_plugin_copy();
+	let write = |toml: &str| fs::write(&path, format!("[[plugin]]\npath = \"{}\"\n{}", plugin, toml)).unwrap();
+
+	write("[plugin.parameters]\nNUM_THREADS = 4");
+	let file = MinerConfigFile::load(&path).unwrap();
+	assert_eq!(file.mode, MiningMode::Async);
+	assert_eq!(file.plugins[0].plugin_full_path, plugin);
+	assert_eq!(file.plugins[0].parameter_list, vec![(String::from("NUM_THREADS"), 0, 4)]);
+
+	write("[plugin.parameters]\nNUM_THREADS = 64");
+	match MinerConfigFile::load(&path) {
+		Err(CuckooMinerError::ParameterOutOfRange { .. }) => {}
+		r => panic!("Expected a ParameterOutOfRange error, got {:?}", r),
+	}
+	fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn unknown_keys_are_ignored() {
+	let configs = CuckooMinerConfig::from_toml_str(
+		r#"
+		log_level = "debug"
+		[[plugin]]
+		name = "lean_cpu_16"
+		colour = "blue"
+		"#,
+	).unwrap();
+	assert_eq!(configs.len(), 1);
+}
+
+#[test]
+fn missing_file_is_an_error() {
+	match CuckooMinerConfig::from_file("/no/such/cuckoo-miner.toml") {
+		Err(CuckooMinerError::ConfigError(m)) => assert!(m.contains("/no/such/cuckoo-miner.toml")),
+		r => panic!("Expected a ConfigError, got {:?}", r),
+	}
+}
+
+#[test]
+fn malformed_configs_are_errors() {
+	config_error("[[plugin]\nname = \"lean_cpu_16\"");
+	config_error("");
+	config_error("plugin = \"lean_cpu_16\"");
+	assert!(config_error("[[plugin]]\ndevices = [0]").contains("neither"));
+	assert!(config_error("[[plugin]]\nname = \"a\"\npath = \"b\"").contains("both"));
+}
+
+#[test]
+fn bad_types_are_errors() {
+	let m = config_error("[[plugin]]\nname = \"lean_cpu_16\"\n[plugin.parameters]\nNUM_THREADS = \"4\"");
+	assert!(m.contains("lean_cpu_16") && m.contains("NUM_THREADS"));
+	let m = config_error("[[plugin]]\nname = \"lean_cpu_16\"\ndevices = 0");
+	assert!(m.contains("devices"));
+	let m = config_error("[[plugin]]\nname = \"lean_cpu_16\"\nallow_legacy = 1");
+	assert!(m.contains("allow_legacy"));
+	let m = config_error("[[plugin]]\nname = \"lean_cpu_16\"\nskip_memory_check = \"yes\"");
+	assert!(m.contains("skip_memory_check"));
+	let m = config_error("[[plugin]]\nname = \"lean_cpu_16\"\n[plugin.device_parameters.gpu]\nN_TRIMS = 1");
+	assert!(m.contains("gpu"));
+	let m = config_error("[[plugin]]\nname = \"lean_cpu_16\"\ncpu_affinity = 0");
+	assert!(m.contains("cpu_affinity"));
+	let m = config_error("[[plugin]]\nname = \"lean_cpu_16\"\ncpu_affinity = [-1]");
+	assert!(m.contains("cpu_affinity"));
+	let m = config_error("[[plugin]]\nname = \"lean_cpu_16\"\nthread_priority = \"realtime\"");
+	assert!(m.contains("thread_priority"));
+	let m = config_error("[[plugin]]\nname = \"lean_cpu_16\"\nthread_priority = 1");
+	assert!(m.contains("thread_priority"));
+	let m = config_error("[[plugin]]\nname = \"lean_cpu_16\"\nnonce_encoding = \"middle_endian\"");
+	assert!(m.contains("nonce_encoding"));
+	let m = config_error("[[plugin]]\nname = \"lean_cpu_16\"\npinned_hashes = \"00\"");
+	assert!(m.contains("pinned_hashes"));
+	let m = config_error("[[plugin]]\nname = \"lean_cpu_16\"\npinned_hashes = [0]");
+	assert!(m.contains("pinned_hashes"));
+}
+
+#[test]
+fn nonce_encoding_parses() {
+	let configs = CuckooMinerConfig::from_toml_str(
+		r#"
+		[[plugin]]
+		name = "lean_cpu_16"
+		nonce_encoding = "little_endian_u64"
+
+		[[plugin]]
+		name = "lean_cpu_30"
+		nonce_encoding = "raw_bytes"
+
+		[[plugin]]
+		name = "mean_cpu_30"
+		"#,
+	).unwrap();
+	assert_eq!(configs[0].nonce_encoding, NonceEncoding::LittleEndianU64);
+	assert_eq!(configs[1].nonce_encoding, NonceEncoding::RawBytes);
+	assert_eq!(configs[2].nonce_encoding, NonceEncoding::BigEndianU64);
+}
+
+#[test]
+fn pinned_hashes_parse() {
+	let configs = CuckooMinerConfig::from_toml_str(
+		r#"
+		[[plugin]]
+		name = "lean_cpu_16"
+		pinned_hashes = ["aa", "bb"]
+
+		[[plugin]]
+		name = "lean_cpu_30"
+		"#,
+	).unwrap();
+	assert_eq!(configs[0].pinned_hashes, vec![String::from("aa"), String::from("bb")]);
+	assert!(configs[1].pinned_hashes.is_empty());
+}
+
+#[test]
+fn out_of_range_values_are_errors() {
+	let m = config_error("[[plugin]]\nname = \"lean_cpu_16\"\n[plugin.parameters]\nNUM_THREADS = -1");
+	assert!(m.contains("NUM_THREADS"));
+	let m = config_error("[[plugin]]\nname = \"lean_cpu_16\"\nqueue_size = 4294967296");
+	assert!(m.contains("queue_size"));
+	let m = config_error("[[plugin]]\nname = \"lean_cpu_16\"\n[plugin.nonce_range]\nstride = 0");
+	assert!(m.contains("stride"));
+}
+
+#[test]
+fn parameters_are_validated_against_the_plugin() {
+	let plugin_parameters = vec![parameter("NUM_THREADS", 1, 32), parameter("N_TRIMS", 8, 256)];
+	let parse = |toml: &str| CuckooMinerConfig::from_toml_str(toml).unwrap().remove(0);
+
+	let c = parse("[[plugin]]\nname = \"lean_cpu_16\"\n[plugin.parameters]\nNUM_THREADS = 4");
+	assert!(c.validate_parameters(&plugin_parameters).is_ok());
+
+	let c = parse("[[plugin]]\nname = \"lean_cpu_16\"\n[plugin.parameters]\nNUM_THREDS = 4");
+	match c.validate_parameters(&plugin_parameters) {
+		Err(CuckooMinerError::UnknownParameter { plugin, parameter }) => {
+			assert_eq!(plugin, "lean_cpu_16");
+			assert_eq!(parameter, "NUM_THREDS");
+		}
+		r => panic!("Expected an UnknownParameter error, got {:?}", r),
+	}
+
+	let c = parse("[[plugin]]\nname = \"lean_cpu_16\"\n[plugin.device_parameters.0]\nN_TRIMS = 512");
+	match c.validate_parameters(&plugin_parameters) {
+		Err(CuckooMinerError::ParameterOutOfRange { name, value, min, max }) => {
+			assert_eq!((name.as_str(), value, min, max), ("N_TRIMS", 512, 8, 256));
+		}
+		r => panic!("Expected a ParameterOutOfRange error, got {:?}", r),
+	}
+}
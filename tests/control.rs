@@ -0,0 +1,146 @@
+// Copyright 2017 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tests of the JSON-RPC control server, driven over the loopback
+//! interface against a job running the mock plugin
+
+extern crate cuckoo_miner as cuckoo;
+extern crate serde_json;
+
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde_json::Value;
+
+use cuckoo::{CuckooMiner, CuckooMinerConfig, CuckooMinerJobHandle, ControlServer, CONTROL_PATH,
+	PARSE_ERROR, METHOD_NOT_FOUND, INVALID_PARAMS, MINER_ERROR};
+
+pub mod common;
+
+const TOKEN: &str = "let-me-in";
+
+// Posts a body to the control server, returning the status line and body
+fn post(addr: SocketAddr, path: &str, token: Option<&str>, body: &str) -> (String, String) {
+	let mut stream = TcpStream::connect(addr).unwrap();
+	let auth = token.map(|t| format!("Authorization: Bearer {}\r\n", t)).unwrap_or_default();
+	write!(
+		stream,
+		"POST {} HTTP/1.1\r\nHost: localhost\r\n{}Content-Length: {}\r\n\r\n{}",
+		path,
+		auth,
+		body.len(),
+		body
+	).unwrap();
+	let mut response = String::new();
+	stream.read_to_string(&mut response).unwrap();
+	let head_end = response.find("\r\n\r\n").unwrap();
+	let status = String::from(response.lines().next().unwrap());
+	(status, String::from(&response[head_end + 4..]))
+}
+
+// Calls a method, returning the response
+fn call(server: &ControlServer, method: &str, params: &str) -> Value {
+	let body = format!("{{\"jsonrpc\":\"2.0\",\"id\":7,\"method\":\"{}\",\"params\":{}}}", method, params);
+	let (status, body) = post(server.local_addr(), CONTROL_PATH, Some(TOKEN), &body);
+	assert_eq!(status, "HTTP/1.1 200 OK", "{}", body);
+	let response: Value = serde_json::from_str(&body).unwrap();
+	assert_eq!(response["jsonrpc"], "2.0");
+	assert_eq!(response["id"], 7);
+	response
+}
+
+fn error_code(response: &Value) -> i64 {
+	response["error"]["code"].as_i64().expect(&response.to_string())
+}
+
+// Starts a job of two mock devices
+fn start_job() -> CuckooMinerJobHandle {
+	let mut config = CuckooMinerConfig::new();
+	config.plugin_full_path = common::mock_plugin_copy();
+	config.parameter_list.push((String::from("MOCK_DEVICES"), 0, 2));
+	config.parameter_list.push((String::from("MOCK_DELAY_MS"), 0, 10));
+	let mut miner = CuckooMiner::new(vec![config]).unwrap();
+	miner.set_stats_interval(Duration::from_millis(100));
+	miner.notify(3, common::SAMPLE_GRIN_PRE_HEADER_1, common::SAMPLE_GRIN_POST_HEADER_1, 0, true)
+		.unwrap()
+}
+
+#[test]
+fn requests_must_carry_the_token() {
+	let handle = start_job();
+	assert!(handle.serve_control("127.0.0.1:0", "").is_err());
+	let server = handle.serve_control("127.0.0.1:0", TOKEN).unwrap();
+	let body = "{\"jsonrpc\":\"2.0\",\"id\":1,\"method\":\"shutdown\"}";
+	let addr = server.local_addr();
+	assert_eq!(post(addr, CONTROL_PATH, None, body).0, "HTTP/1.1 401 Unauthorized");
+	assert_eq!(post(addr, CONTROL_PATH, Some("let-me-ix"), body).0, "HTTP/1.1 401 Unauthorized");
+	assert_eq!(post(addr, "/", Some(TOKEN), body).0, "HTTP/1.1 404 Not Found");
+	// refused requests leave the job running
+	assert_eq!(call(&server, "get_status", "null")["result"]["stopped"], false);
+
+	assert_eq!(error_code(&call(&server, "no_such_method", "{}")), METHOD_NOT_FOUND);
+	assert_eq!(error_code(&call(&server, "set_parameter", "{\"plugin_index\":0}")), INVALID_PARAMS);
+	let (_, body) = post(addr, CONTROL_PATH, Some(TOKEN), "{\"jsonrpc\":");
+	assert_eq!(error_code(&serde_json::from_str(&body).unwrap()), PARSE_ERROR);
+	handle.stop_jobs();
+}
+
+#[test]
+fn a_mock_job_is_controlled() {
+	let handle = start_job();
+	let server = handle.serve_control("127.0.0.1:0", TOKEN).unwrap();
+	thread::sleep(Duration::from_millis(500));
+
+	let status = &call(&server, "get_status", "{}")["result"];
+	assert_eq!(status["job_id"], 3);
+	assert_eq!(status["stopped"], false);
+	assert_eq!(status["plugins"][0]["index"], 0);
+	assert_eq!(status["plugins"][0]["state"], "Processing");
+	let stats = &call(&server, "get_stats", "{}")["result"];
+	assert_eq!(stats[0]["plugin_index"], 0);
+	assert_eq!(stats[0]["devices"].as_array().unwrap().len(), 2);
+	let stats = &call(&server, "get_stats", "{\"plugin_index\":0}")["result"];
+	assert_eq!(stats.as_array().unwrap().len(), 2);
+	assert_eq!(error_code(&call(&server, "get_stats", "{\"plugin_index\":1}")), INVALID_PARAMS);
+
+	// parameters and devices are changed on the running plugin
+	let response = call(&server, "set_parameter", "{\"plugin_index\":0,\"name\":\"MOCK_DELAY_MS\",\"value\":20}");
+	assert_eq!(response["result"], true, "{}", response);
+	assert_eq!(handle.library.read().unwrap()[0].get_parameter("MOCK_DELAY_MS").unwrap(), 20);
+	let response = call(&server, "set_parameter", "{\"plugin_index\":0,\"name\":\"QUEUE_SIZE\",\"value\":0}");
+	assert_eq!(error_code(&response), MINER_ERROR);
+	let response = call(&server, "set_device_enabled", "{\"plugin_index\":0,\"device_id\":1,\"enabled\":false}");
+	assert_eq!(response["result"], true, "{}", response);
+	assert_eq!(handle.library.read().unwrap()[0].disabled_devices(), vec![1]);
+
+	// a reload restores the configured parameters
+	let name = status["plugins"][0]["name"].as_str().unwrap();
+	let response = call(&server, "reload_plugin", &format!("{{\"name\":\"{}\"}}", name));
+	assert_eq!(response["result"], true, "{}", response);
+	assert_eq!(handle.library.read().unwrap()[0].get_parameter("MOCK_DELAY_MS").unwrap(), 10);
+	let response = call(&server, "reload_plugin", "{\"name\":\"no_such_plugin\"}");
+	assert_eq!(error_code(&response), MINER_ERROR);
+
+	assert_eq!(call(&server, "shutdown", "{\"deadline_ms\":1000}")["result"], true);
+	let start = Instant::now();
+	while call(&server, "get_status", "{}")["result"]["plugins"][0]["state"] != "Unloaded" {
+		assert!(start.elapsed() < Duration::from_secs(10), "The job didn't shut down");
+		thread::sleep(Duration::from_millis(50));
+	}
+	assert_eq!(call(&server, "get_status", "{}")["result"]["stopped"], true);
+	handle.stop_jobs();
+	server.stop();
+}
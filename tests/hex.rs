@@ -0,0 +1,89 @@
+// Copyright 2017 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tests of the hex helpers
+
+extern crate cuckoo_miner as cuckoo;
+
+use cuckoo::{from_hex, to_hex, header_hash, CuckooMinerError, Header};
+
+pub mod common;
+
+// The offset of the error from decoding some malformed hex
+fn error_offset(hex: &str) -> usize {
+	match from_hex(hex) {
+		Err(CuckooMinerError::InvalidHex { offset, .. }) => offset,
+		r => panic!("Expected an InvalidHex error for {:?}, got {:?}", hex, r),
+	}
+}
+
+#[test]
+fn hex_round_trips() {
+	let bytes: Vec<u8> = (0..=255).collect();
+	let hex = to_hex(&bytes);
+	assert_eq!(hex.len(), 512);
+	assert_eq!(&hex[..8], "00010203");
+	assert_eq!(from_hex(&hex).unwrap(), bytes);
+	assert_eq!(from_hex(&hex.to_uppercase()).unwrap(), bytes);
+	assert_eq!(to_hex(&[]), "");
+	assert_eq!(from_hex("").unwrap(), Vec::<u8>::new());
+	for known in &[common::KNOWN_16_HASH_1, common::SAMPLE_GRIN_PRE_HEADER_1, common::SAMPLE_GRIN_POST_HEADER_1] {
+		assert_eq!(to_hex(&from_hex(known).unwrap()), *known);
+	}
+}
+
+#[test]
+fn prefixes_and_whitespace_are_accepted() {
+	assert_eq!(from_hex("0x01ab").unwrap(), vec![0x01, 0xab]);
+	assert_eq!(from_hex("0XFF").unwrap(), vec![0xff]);
+	assert_eq!(from_hex(" 01 ab\n\tcd\r\n").unwrap(), vec![0x01, 0xab, 0xcd]);
+	// whitespace may even split a byte's digits
+	assert_eq!(from_hex("0x0 1").unwrap(), vec![0x01]);
+	assert_eq!(from_hex("0x").unwrap(), Vec::<u8>::new());
+}
+
+#[test]
+fn malformed_hex_is_rejected() {
+	// the unpaired digit
+	assert_eq!(error_offset("01a"), 2);
+	assert_eq!(error_offset("0x01 a "), 5);
+	assert_eq!(error_offset("f"), 0);
+	// the offending character, counting the prefix and whitespace
+	assert_eq!(error_offset("01zz"), 2);
+	assert_eq!(error_offset("0x 01g0"), 5);
+	assert_eq!(error_offset("-1"), 0);
+	// a prefix is only one at the start
+	assert_eq!(error_offset("010x"), 3);
+	assert_eq!(error_offset("0x0x01"), 3);
+	// offsets are in bytes, whatever the characters before
+	assert_eq!(error_offset("01\u{a0}00"), 2);
+	assert_eq!(error_offset("é0"), 0);
+	assert_eq!(error_offset("00é0"), 2);
+}
+
+#[test]
+fn headers_are_decoded() {
+	let header = Header::from_hex(common::SAMPLE_GRIN_PRE_HEADER_1, common::SAMPLE_GRIN_POST_HEADER_1).unwrap();
+	assert_eq!(header.pre_nonce, from_hex(common::SAMPLE_GRIN_PRE_HEADER_1).unwrap());
+	assert_eq!(header.post_nonce, vec![0x01, 0x0a, 0x02, 0x03, 0x64]);
+	assert_eq!(header.hash(56), header_hash(&header.pre_nonce, &header.post_nonce, 56));
+
+	match Header::from_hex("00", "0x1") {
+		Err(CuckooMinerError::InvalidHex { offset, reason }) => {
+			assert_eq!(offset, 2);
+			assert!(reason.contains("post-nonce"), "{}", reason);
+		}
+		r => panic!("Expected an InvalidHex error, got {:?}", r),
+	}
+}
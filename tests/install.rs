@@ -0,0 +1,161 @@
+// Copyright 2017 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tests of verifying, installing and pinning plugins, using the mock
+//! plugin
+
+extern crate cuckoo_miner as cuckoo;
+
+use std::{env, fs, process};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use cuckoo::{CuckooMiner, CuckooMinerConfig, CuckooMinerError, CuckooPluginManager, verify_plugin, install_plugin,
+	plugin_hash, check_pinned_hash};
+
+pub mod common;
+
+//Shared libraries known to exist on common linux installs, which
+//aren't plugins
+const NON_PLUGIN_LIBS: [&str; 3] = [
+	"/lib/x86_64-linux-gnu/libm.so.6",
+	"/lib64/libm.so.6",
+	"/usr/lib/libm.so.6",
+];
+
+// A new empty directory for the test to install to
+fn temp_dir() -> PathBuf {
+	static DIRS: AtomicUsize = AtomicUsize::new(0);
+	let mut path = env::temp_dir();
+	path.push(format!("cuckoo-install-{}", process::id()));
+	path.push(DIRS.fetch_add(1, Ordering::SeqCst).to_string());
+	let _ = fs::remove_dir_all(&path);
+	fs::create_dir_all(&path).unwrap();
+	path
+}
+
+// The names of the files in a directory
+fn files(dir: &Path) -> Vec<String> {
+	let mut names: Vec<String> = fs::read_dir(dir)
+		.unwrap()
+		.map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+		.collect();
+	names.sort();
+	names
+}
+
+#[test]
+fn mock_plugin_verifies() {
+	let path = common::mock_plugin_copy();
+	let report = verify_plugin(&path).unwrap();
+	assert_eq!(report.path, path);
+	assert_eq!(report.hash, plugin_hash(&path).unwrap());
+	assert_eq!(report.hash.len(), 64);
+	assert!(report.api_version > 0);
+	assert!(report.description.is_some());
+	assert!(report.parameters.iter().any(|p| p.name == "EDGE_BITS"));
+	assert_eq!(report.capabilities.edge_bits, Some(15));
+	let self_test = report.self_test.unwrap();
+	assert!(self_test.passed, "{:?}", self_test.failure);
+	assert_eq!(self_test.cuckoo_size, 16);
+}
+
+#[test]
+fn broken_plugins_are_refused() {
+	let dir = temp_dir();
+	let dest = temp_dir();
+
+	// the start of a copy, as left by an interrupted transfer, which would
+	// crash the loader
+	let truncated = dir.join("truncated.cuckooplugin");
+	let contents = fs::read(common::mock_plugin_path()).unwrap();
+	for &len in &[contents.len() - 1, contents.len() / 2, 4096, 10] {
+		fs::write(&truncated, &contents[..len]).unwrap();
+		match verify_plugin(&truncated) {
+			Err(CuckooMinerError::PluginIOError(_)) => {}
+			r => panic!("Expected PluginIOError for {} bytes, got {:?}", len, r),
+		}
+	}
+	assert!(install_plugin(&truncated, &dest).is_err());
+
+	match verify_plugin(dir.join("missing.cuckooplugin")) {
+		Err(CuckooMinerError::PluginNotFoundError(_)) => {}
+		r => panic!("Expected PluginNotFoundError, got {:?}", r),
+	}
+
+	for l in NON_PLUGIN_LIBS.iter().filter(|l| Path::new(l).exists()) {
+		match verify_plugin(l) {
+			Err(CuckooMinerError::PluginSymbolMissing { symbol, .. }) => assert_eq!(symbol, "cuckoo_init"),
+			r => panic!("Expected PluginSymbolMissing, got {:?}", r),
+		}
+		assert!(install_plugin(l, &dest).is_err());
+	}
+	assert!(files(&dest).is_empty());
+}
+
+#[test]
+fn plugins_are_installed_whole() {
+	let src = common::mock_plugin_copy();
+	let dest = temp_dir();
+	let report = install_plugin(&src, dest.join("plugins")).unwrap();
+	let file_name = Path::new(&src).file_name().unwrap().to_string_lossy().into_owned();
+	let installed = dest.join("plugins").join(&file_name);
+	assert_eq!(report.path, installed.to_string_lossy());
+	assert_eq!(files(&dest.join("plugins")), vec![file_name.clone()]);
+	assert_eq!(plugin_hash(&installed).unwrap(), plugin_hash(&src).unwrap());
+	assert_eq!(report.hash, plugin_hash(&src).unwrap());
+
+	// installing again replaces the copy, leaving nothing else behind
+	install_plugin(&src, dest.join("plugins")).unwrap();
+	assert_eq!(files(&dest.join("plugins")), vec![file_name]);
+	assert!(verify_plugin(&installed).is_ok());
+}
+
+#[test]
+fn only_pinned_builds_are_loaded() {
+	// in a directory of its own, as the manager loads every plugin there
+	let dir = temp_dir();
+	let path = install_plugin(common::mock_plugin_copy(), &dir).unwrap().path;
+	let hash = plugin_hash(&path).unwrap();
+	let other = "00".repeat(32);
+	assert!(check_pinned_hash(&path, &[]).is_ok());
+	assert!(check_pinned_hash(&path, &[other.clone(), hash.to_uppercase()]).is_ok());
+
+	let mut config = CuckooMinerConfig::new();
+	config.plugin_full_path = path.clone();
+	config.pinned_hashes = vec![other.clone()];
+	match CuckooMiner::new(vec![config.clone()]) {
+		Err(CuckooMinerError::PluginHashMismatch { path: p, hash: h }) => {
+			assert_eq!(p, path);
+			assert_eq!(h, hash);
+		}
+		r => panic!("Expected PluginHashMismatch, got {:?}", r.err()),
+	}
+	config.pinned_hashes.push(hash.clone());
+	assert!(CuckooMiner::new(vec![config]).is_ok());
+
+	// the manager skips unpinned plugins when scanning
+	let dir = dir.to_string_lossy().into_owned();
+	let mut manager = CuckooPluginManager::new().unwrap();
+	manager.set_pinned_hashes(vec![other]);
+	match manager.load_plugin_dir(dir.clone()) {
+		Err(CuckooMinerError::NoPluginsFoundError(_)) => {}
+		r => panic!("Expected NoPluginsFoundError, got {:?}", r),
+	}
+	manager.set_pinned_hashes(vec![hash]);
+	manager.load_plugin_dir(dir).unwrap();
+	let plugins = manager.get_available_plugins("").unwrap();
+	assert_eq!(plugins.len(), 1);
+	assert_eq!(plugins[0].full_path, path);
+}
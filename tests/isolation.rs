@@ -0,0 +1,93 @@
+// Copyright 2017 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tests that a panic while handling one plugin is contained to its
+//! worker, using the mock plugin to return the nonce the `fault-injection`
+//! feature panics on
+
+extern crate cuckoo_miner as cuckoo;
+
+use std::path::Path;
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use cuckoo::{CuckooMiner, CuckooMinerConfig, MinerEvent, PANIC_NONCE};
+
+pub mod common;
+
+// A config for a fresh copy of the mock plugin, with the given parameters,
+// and the name it's reported under
+fn mock_config(params: &[(&str, u32)]) -> (CuckooMinerConfig, String) {
+	let mut config = CuckooMinerConfig::new();
+	config.plugin_full_path = common::mock_plugin_copy();
+	for &(name, value) in params {
+		config.parameter_list.push((String::from(name), 0, value));
+	}
+	let name = Path::new(&config.plugin_full_path)
+		.file_stem()
+		.unwrap()
+		.to_string_lossy()
+		.into_owned();
+	(config, name)
+}
+
+#[test]
+fn a_panicking_worker_leaves_the_others_mining() {
+	let (panicking, panicking_name) = mock_config(&[("MOCK_SOLVE_ALL", 1), ("MOCK_NONCE", PANIC_NONCE as u32)]);
+	let (healthy, healthy_name) = mock_config(&[]);
+	let mut miner = CuckooMiner::new(vec![panicking, healthy]).unwrap();
+	let (tx, rx) = mpsc::channel();
+	miner.set_event_channel(tx);
+	miner.set_stats_interval(Duration::from_millis(100));
+	let handle = miner
+		.notify(1, common::SAMPLE_GRIN_PRE_HEADER_1, common::SAMPLE_GRIN_POST_HEADER_1, 0, true)
+		.unwrap();
+
+	// the panic is reported as the plugin's error, and it's given up on
+	let start = Instant::now();
+	loop {
+		assert!(start.elapsed() < Duration::from_secs(30), "The panic wasn't reported");
+		match rx.recv_timeout(Duration::from_millis(100)) {
+			Ok(MinerEvent::PluginError { plugin, message, .. }) => {
+				assert_eq!(plugin, panicking_name);
+				assert!(message.contains("panic"), "{}", message);
+				break;
+			}
+			Ok(_) | Err(_) => {}
+		}
+	}
+
+	// while the other plugin keeps mining
+	let mut healthy_updates = 0;
+	let start = Instant::now();
+	while healthy_updates < 3 {
+		assert!(start.elapsed() < Duration::from_secs(30), "The other plugin stopped reporting");
+		if let Ok(MinerEvent::StatsUpdated(stats)) = rx.recv_timeout(Duration::from_millis(100)) {
+			if stats.iter().any(|s| s.plugin_name.as_ref() == Some(&healthy_name)) {
+				healthy_updates += 1;
+			}
+		}
+	}
+
+	// and the job still stops cleanly
+	handle.stop_jobs();
+	let start = Instant::now();
+	loop {
+		assert!(start.elapsed() < Duration::from_secs(30), "The job didn't stop");
+		if let Ok(MinerEvent::JobStopped(id)) = rx.recv_timeout(Duration::from_millis(100)) {
+			assert_eq!(id, 1);
+			break;
+		}
+	}
+}
@@ -0,0 +1,123 @@
+// Copyright 2017 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tests of the reader for the JSON plugins report
+
+extern crate cuckoo_miner as cuckoo;
+
+use cuckoo::json::{self, JsonValue};
+
+// The string a JSON document holds
+fn string(json: &str) -> String {
+	match json::parse(json) {
+		Ok(JsonValue::String(s)) => s,
+		r => panic!("Expected a string from {:?}, got {:?}", json, r),
+	}
+}
+
+#[test]
+fn json_reads_values() {
+	let v = json::parse(" {\"a\": [1, -2.5e3, true, false, null], \"b\": {}, \"c\": []} ").unwrap();
+	assert_eq!(
+		v.get("a"),
+		Some(&JsonValue::Array(vec![
+			JsonValue::Number(String::from("1")),
+			JsonValue::Number(String::from("-2.5e3")),
+			JsonValue::Bool(true),
+			JsonValue::Bool(false),
+			JsonValue::Null,
+		]))
+	);
+	assert_eq!(v.get("b"), Some(&JsonValue::Object(vec![])));
+	assert_eq!(v.get("c"), Some(&JsonValue::Array(vec![])));
+	assert_eq!(v.get("d"), None);
+	assert_eq!(json::parse("0").unwrap().as_u64(), Some(0));
+	assert_eq!(json::parse("-0.5E-2").unwrap().as_f64(), Some(-0.005));
+}
+
+#[test]
+fn json_resolves_escapes() {
+	assert_eq!(string(r#""\"\\\/\b\f\n\r\t""#), "\"\\/\u{8}\u{c}\n\r\t");
+	assert_eq!(string(r#""\u0041\u00e9\u4E2D""#), "A\u{e9}\u{4e2d}");
+	assert_eq!(string("\"caf\u{e9}\""), "caf\u{e9}");
+	// a surrogate pair makes up a single character
+	assert_eq!(string(r#""\ud83d\ude00""#), "\u{1f600}");
+	assert_eq!(string(r#""\uD83D\uDE00x""#), "\u{1f600}x");
+}
+
+#[test]
+fn json_refuses_bad_strings() {
+	for bad in &[
+		r#""\ud83d""#,
+		r#""\ud83dx""#,
+		r#""\ud83d\u0041""#,
+		r#""\ude00""#,
+		r#""\u+123""#,
+		r#""\u12""#,
+		r#""\u12g4""#,
+		r#""\x""#,
+		"\"tab\there\"",
+	] {
+		assert!(json::parse(bad).is_err(), "{:?} should be refused", bad);
+	}
+}
+
+#[test]
+fn json_refuses_loose_numbers() {
+	for bad in &["+1", "01", "-01", ".5", "1.", "1.e3", "1e", "1e+", "-", "--1", "1.5.2", "0x10"] {
+		assert!(json::parse(bad).is_err(), "{:?} should be refused", bad);
+	}
+	for good in &["0", "-0", "10", "0.5", "1e3", "1E+3", "1e-3", "18446744073709551616"] {
+		assert!(json::parse(good).is_ok(), "{:?} should be read", good);
+	}
+}
+
+#[test]
+fn json_integers_must_fit() {
+	let v = json::parse("{\"max\": 18446744073709551615, \"over\": 18446744073709551616, \"big\": 4294967296}").unwrap();
+	assert_eq!(json::u64_field(&v, "max"), Ok(u64::max_value()));
+	assert!(json::u64_field(&v, "over").is_err());
+	assert!(json::u32_field(&v, "big").is_err());
+	assert_eq!(json::optional_u32_field(&v, "missing"), Ok(None));
+	assert!(json::optional_u32_field(&v, "big").is_err());
+	// large integers aren't read through a float
+	assert_eq!(v.get("max").unwrap().as_u64(), Some(18446744073709551615));
+	assert_eq!(json::parse("-1").unwrap().as_u64(), None);
+	assert_eq!(json::parse("1.0").unwrap().as_u64(), None);
+}
+
+#[test]
+fn json_limits_nesting() {
+	let nested = |depth: usize| format!("{}{}", "[".repeat(depth), "]".repeat(depth));
+	assert!(json::parse(&nested(64)).is_ok());
+	assert!(json::parse(&nested(66)).is_err());
+	// deep enough to overflow the stack if it weren't limited
+	assert!(json::parse(&nested(100000)).is_err());
+	let objects = format!("{}1{}", "{\"a\":".repeat(100), "}".repeat(100));
+	assert!(json::parse(&objects).is_err());
+}
+
+#[test]
+fn json_refuses_truncated_input() {
+	let doc = r#"{"name": "NUM_THREADS", "values": [1, 2.5, true, null], "text": "é"}"#;
+	assert!(json::parse(doc).is_ok());
+	for end in 0..doc.len() {
+		if doc.is_char_boundary(end) {
+			assert!(json::parse(&doc[..end]).is_err(), "{:?} should be refused", &doc[..end]);
+		}
+	}
+	for bad in &["", " ", "tru", "nul", "[1,]", "{\"a\":1,}", "{\"a\"}", "{1:2}", "[1 2]", "1 2", "}"] {
+		assert!(json::parse(bad).is_err(), "{:?} should be refused", bad);
+	}
+}
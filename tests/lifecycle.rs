@@ -0,0 +1,300 @@
+// Copyright 2017 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+/// Model-based tests of the plugin lifecycle. Random call sequences are
+/// run against the mock plugin, and lean_cpu_16 with the
+/// `real-plugin-tests` feature, and every result is checked against the
+/// StateModel. Failing sequences are shrunk to a minimal reproduction.
+/// Only built with the `lifecycle-tests` feature.
+
+extern crate rand;
+extern crate cuckoo_miner as cuckoo;
+
+#[cfg(feature = "real-plugin-tests")]
+use std::path::PathBuf;
+use std::time::Duration;
+
+use rand::{Rng, SeedableRng, StdRng};
+
+use cuckoo::{PluginLibrary, StateModel, PluginState, PluginOp, ALL_PLUGIN_OPS, CALL_REFUSED};
+
+pub mod common;
+
+#[cfg(feature = "real-plugin-tests")]
+static DLL_SUFFIX: &str = ".cuckooplugin";
+
+// Keeps the harness within CI time
+const SEQUENCE_COUNT: usize = 50;
+const SEQUENCE_LENGTH: usize = 24;
+
+// How long a sequence may wait for processing to stop during cleanup
+const STOP_TIMEOUT_MS: u64 = 10000;
+
+//Helper to get the path of a built plugin
+#[cfg(feature = "real-plugin-tests")]
+fn plugin_path(plugin:&str) -> String {
+	let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+	d.push(format!("target/debug/plugins/{}{}", plugin, DLL_SUFFIX).as_str());
+	d.to_str().unwrap().to_owned()
+}
+
+//Performs a single op, checking the result against the model
+fn perform(pl: &PluginLibrary, model: &mut StateModel, op: PluginOp) -> Result<(), String> {
+	let legal = model.allows(op);
+	let header:[u8;32] = [0;32];
+	let nonce:[u8;8] = [0;8];
+	let mut ok = true;
+	match op {
+		PluginOp::Init => pl.call_cuckoo_init(),
+		PluginOp::Call => {
+			let mut solution:[u32; 42] = [0;42];
+			let mut size = 0;
+			let r = pl.call_cuckoo(&header, &mut size, &mut solution);
+			ok = (r == CALL_REFUSED) != legal;
+		},
+		PluginOp::ParameterList => {
+			let mut bytes:[u8;4096] = [0;4096];
+			let mut len = bytes.len() as u32;
+			let r = pl.call_cuckoo_parameter_list(&mut bytes, &mut len);
+			ok = (r == CALL_REFUSED) != legal;
+		},
+		PluginOp::GetParameter => {
+			let mut value = 0;
+			let r = pl.call_cuckoo_get_parameter("NUM_THREADS".as_bytes(), 0, &mut value);
+			ok = (r == CALL_REFUSED) != legal;
+		},
+		PluginOp::SetParameter => {
+			let r = pl.call_cuckoo_set_parameter("NUM_THREADS".as_bytes(), 0, 2);
+			ok = (r == CALL_REFUSED) != legal;
+		},
+		PluginOp::IsQueueUnderLimit => {
+			let r = pl.call_cuckoo_is_queue_under_limit();
+			ok = legal || r == 0;
+		},
+		PluginOp::PushToInputQueue => {
+			let r = pl.call_cuckoo_push_to_input_queue(0, &header, &nonce);
+			ok = (r == CALL_REFUSED) != legal;
+		},
+		PluginOp::ReadFromOutputQueue => {
+			let mut sols:[u32; 42] = [0; 42];
+			let mut nonce:[u8; 8] = [0;8];
+			let mut id = 0;
+			let mut size = 0;
+			let r = pl.call_cuckoo_read_from_output_queue(&mut id, &mut sols, &mut size, &mut nonce);
+			ok = legal || r == 0;
+		},
+		PluginOp::ClearQueues => pl.call_cuckoo_clear_queues(),
+		PluginOp::ClearInputQueue => {
+			let r = pl.clear_input_queue();
+			// not all plugins export it, only refusals are checked
+			if !legal {
+				ok = r.is_err();
+			}
+		},
+		PluginOp::ClearOutputQueue => {
+			let r = pl.clear_output_queue();
+			ok = r.is_ok() == legal;
+		},
+		PluginOp::StartProcessing => {
+			let r = pl.call_cuckoo_start_processing();
+			ok = if legal { r == 0 } else { r == CALL_REFUSED };
+		},
+		PluginOp::StopProcessing => {
+			let r = pl.call_cuckoo_stop_processing();
+			ok = legal || r == 0;
+		},
+		PluginOp::HasProcessingStopped => {
+			let r = pl.call_cuckoo_has_processing_stopped();
+			if legal {
+				model.apply(op).unwrap();
+				model.processing_stopped(r == 1);
+				return check_state(pl, model, op);
+			}
+			ok = r == 1;
+		},
+		PluginOp::ResetProcessing => {
+			let r = pl.call_cuckoo_reset_processing();
+			ok = legal || r == 0;
+		},
+		PluginOp::GetStats => {
+			let mut bytes:[u8;4096] = [0;4096];
+			let mut len = bytes.len() as u32;
+			let r = pl.call_cuckoo_get_stats(&mut bytes, &mut len);
+			ok = (r == CALL_REFUSED) != legal;
+		},
+		PluginOp::Description => {
+			let r = pl.description();
+			// not all plugins export it, only refusals are checked
+			if !legal {
+				ok = r.is_err();
+			}
+		},
+		PluginOp::ListDevices => {
+			let r = pl.list_devices();
+			ok = r.is_ok() == legal;
+		},
+		PluginOp::Capabilities => {
+			let r = pl.capabilities();
+			ok = r.is_ok() == legal;
+		},
+		PluginOp::Unload => {
+			pl.unload();
+			// unload stops and resets processing itself first
+			if model.state() == PluginState::Processing {
+				model.apply(PluginOp::StopProcessing).unwrap();
+			}
+			model.processing_stopped(true);
+			if model.state() == PluginState::Stopped {
+				model.apply(PluginOp::ResetProcessing).unwrap();
+			}
+			if model.state() != PluginState::Unloaded {
+				model.apply(PluginOp::Unload).unwrap();
+			}
+			return check_state(pl, model, op);
+		},
+	}
+	if !ok {
+		return Err(format!("Unexpected result for {:?} while {:?}", op, model.state()));
+	}
+	if legal {
+		model.apply(op).unwrap();
+	}
+	check_state(pl, model, op)
+}
+
+//The library's own state tracking must agree with the model
+fn check_state(pl: &PluginLibrary, model: &StateModel, op: PluginOp) -> Result<(), String> {
+	if pl.state() != model.state() {
+		return Err(format!(
+			"After {:?}, library is {:?} but model is {:?}",
+			op,
+			pl.state(),
+			model.state()
+		));
+	}
+	Ok(())
+}
+
+//Brings the plugin back to idle so the next sequence starts clean,
+//failing if processing doesn't stop in time
+fn cleanup(pl: &PluginLibrary) -> Result<(), String> {
+	let state = pl.state();
+	if state == PluginState::Processing {
+		pl.call_cuckoo_stop_processing();
+	}
+	if state == PluginState::Processing || state == PluginState::Stopping {
+		pl.wait_for_processing_stopped(Duration::from_millis(STOP_TIMEOUT_MS))
+			.map_err(|e| format!("Processing didn't stop: {:?}", e))?;
+	}
+	if pl.state() == PluginState::Stopped {
+		pl.call_cuckoo_reset_processing();
+	}
+	pl.call_cuckoo_clear_queues();
+	Ok(())
+}
+
+//Runs a whole sequence on a freshly loaded plugin
+fn run_sequence(plugin: &str, ops: &[PluginOp]) -> Result<(), String> {
+	let pl = PluginLibrary::new(plugin)
+		.unwrap_or_else(|e| panic!("Couldn't load {}: {:?}", plugin, e));
+	let mut model = StateModel::new();
+	let mut result = Ok(());
+	for op in ops {
+		result = perform(&pl, &mut model, *op);
+		if result.is_err() {
+			break;
+		}
+	}
+	let clean = cleanup(&pl);
+	result.and(clean)
+}
+
+//Removes ops one at a time while the sequence still fails, until no
+//single op can be removed
+fn shrink(plugin: &str, ops: Vec<PluginOp>) -> Vec<PluginOp> {
+	let mut current = ops;
+	loop {
+		let mut shrunk = false;
+		for i in 0..current.len() {
+			let mut candidate = current.clone();
+			candidate.remove(i);
+			if run_sequence(plugin, &candidate).is_err() {
+				current = candidate;
+				shrunk = true;
+				break;
+			}
+		}
+		if !shrunk {
+			return current;
+		}
+	}
+}
+
+fn random_sequence(rng: &mut StdRng) -> Vec<PluginOp> {
+	let mut ops = Vec::new();
+	for _ in 0..SEQUENCE_LENGTH {
+		let op = ALL_PLUGIN_OPS[rng.gen_range(0, ALL_PLUGIN_OPS.len())];
+		ops.push(op);
+	}
+	ops
+}
+
+fn lifecycle_model_test(plugin: &str) {
+	let seed: &[_] = &[16, 42];
+	let mut rng: StdRng = SeedableRng::from_seed(seed);
+	for i in 0..SEQUENCE_COUNT {
+		let ops = random_sequence(&mut rng);
+		if let Err(e) = run_sequence(plugin, &ops) {
+			let minimal = shrink(plugin, ops);
+			panic!("Sequence {} failed: {}\nMinimal reproduction: {:?}", i, e, minimal);
+		}
+	}
+}
+
+fn known_hazards_test(plugin: &str) {
+	let unload_while_processing = [PluginOp::StartProcessing, PluginOp::Unload];
+	run_sequence(plugin, &unload_while_processing).unwrap();
+
+	let reset_before_stop = [
+		PluginOp::StartProcessing,
+		PluginOp::ResetProcessing,
+		PluginOp::StopProcessing,
+	];
+	run_sequence(plugin, &reset_before_stop).unwrap();
+}
+
+//random lifecycle sequences against the mock plugin
+#[test]
+fn lifecycle_model_mock() {
+	lifecycle_model_test(&common::mock_plugin_copy());
+}
+
+//the known hazards, which the guards should now make safe
+#[test]
+fn lifecycle_known_hazards_mock() {
+	known_hazards_test(&common::mock_plugin_copy());
+}
+
+//random lifecycle sequences against lean_cpu_16
+#[cfg(feature = "real-plugin-tests")]
+#[test]
+fn on_commit_lifecycle_model_lean_cpu_16() {
+	lifecycle_model_test(&plugin_path("lean_cpu_16"));
+}
+
+#[cfg(feature = "real-plugin-tests")]
+#[test]
+fn on_commit_lifecycle_known_hazards() {
+	known_hazards_test(&plugin_path("lean_cpu_16"));
+}
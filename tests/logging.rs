@@ -0,0 +1,96 @@
+// Copyright 2017 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tests that plugins' log output is routed through the log crate. The
+//! logger is global, so this is its own test executable.
+
+extern crate cuckoo_miner as cuckoo;
+extern crate log;
+
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use log::{LogLevel, LogLevelFilter, LogMetadata, LogRecord};
+
+pub mod common;
+
+// Keeps every record logged, as (level, target, message)
+struct TestLogger {
+	records: Arc<Mutex<Vec<(LogLevel, String, String)>>>,
+}
+
+impl log::Log for TestLogger {
+	fn enabled(&self, _metadata: &LogMetadata) -> bool {
+		true
+	}
+
+	fn log(&self, record: &LogRecord) {
+		self.records.lock().unwrap().push((
+			record.level(),
+			String::from(record.target()),
+			format!("{}", record.args()),
+		));
+	}
+}
+
+// Waits for a record from the target containing the given text
+fn wait_for_record(
+	records: &Mutex<Vec<(LogLevel, String, String)>>,
+	target: &str,
+	text: &str,
+) -> (LogLevel, String, String) {
+	let start = Instant::now();
+	while start.elapsed() < Duration::from_secs(10) {
+		if let Some(r) = records.lock().unwrap().iter().find(|r| r.1 == target && r.2.contains(text)) {
+			return r.clone();
+		}
+		std::thread::sleep(Duration::from_millis(10));
+	}
+	panic!("No record from {} containing {}, got {:?}", target, text, records.lock().unwrap());
+}
+
+#[test]
+fn plugin_messages_are_logged() {
+	let records = Arc::new(Mutex::new(Vec::new()));
+	let logger_records = records.clone();
+	log::set_logger(|max_level| {
+		max_level.set(LogLevelFilter::Trace);
+		Box::new(TestLogger { records: logger_records })
+	}).unwrap();
+
+	let path = common::mock_plugin_copy();
+	let target = Path::new(&path).file_stem().unwrap().to_string_lossy().into_owned();
+	let pl = cuckoo::PluginLibrary::new(&path).unwrap();
+	pl.set_parameter("MOCK_ERROR_AFTER", 1).unwrap();
+	let header = cuckoo::from_hex(common::KNOWN_16_HASH_1).unwrap();
+	pl.push_to_input_queue(0, &header, &[0; 8]).unwrap();
+	pl.start_processing().unwrap();
+
+	// logged from the calling thread
+	let r = wait_for_record(&records, &target, "Processing on 1 devices");
+	assert_eq!(r.0, LogLevel::Info);
+	// and from the mock's own threads
+	let r = wait_for_record(&records, &target, "found a solution for job 0");
+	assert_eq!(r.0, LogLevel::Debug);
+	let r = wait_for_record(&records, &target, "Failed after 1 graphs");
+	assert_eq!(r.0, LogLevel::Error);
+
+	pl.stop_and_wait(Duration::from_secs(10)).unwrap();
+	pl.unload();
+	// nothing more is logged once the plugin's gone
+	let logged = records.lock().unwrap().iter().filter(|r| r.1 == target).count();
+	std::thread::sleep(Duration::from_millis(50));
+	assert_eq!(records.lock().unwrap().iter().filter(|r| r.1 == target).count(), logged);
+}
@@ -0,0 +1,189 @@
+// Copyright 2017 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+//! Tests for plugin discovery via the plugin manager
+
+extern crate cuckoo_miner as cuckoo;
+extern crate time;
+extern crate rand;
+
+pub mod common;
+
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use rand::Rng;
+
+use cuckoo::{CuckooPluginManager, CuckooMinerError, resolve_plugin_path, PLUGIN_PATH_VAR};
+
+// Creates an empty temporary directory for a test
+fn temp_plugin_dir() -> PathBuf {
+	let id: u32 = rand::OsRng::new().unwrap().gen();
+	let mut d = env::temp_dir();
+	d.push(format!("cuckoo_manager_test_{}", id));
+	fs::create_dir_all(&d).unwrap();
+	d
+}
+
+// Copies the mock plugin into a directory under the given file name
+fn install_mock(dir: &Path, file_name: &str) -> PathBuf {
+	let path = dir.join(file_name);
+	fs::copy(common::mock_plugin_path(), &path).unwrap();
+	path
+}
+
+// Held by tests setting CUCKOO_PLUGIN_PATH, so they don't see each
+// other's
+static PLUGIN_PATH_LOCK: Mutex<()> = Mutex::new(());
+
+//files that aren't loadable plugins are skipped rather than failing the scan
+#[test]
+fn manager_skips_unloadable_plugins() {
+	let d = temp_plugin_dir();
+	let mut f = fs::File::create(d.join("not_a_plugin.cuckooplugin")).unwrap();
+	f.write_all(b"not a shared library").unwrap();
+
+	let mut plugin_manager = CuckooPluginManager::new().unwrap();
+	match plugin_manager.load_plugin_dirs(&[d.to_str().unwrap(), "/nonexistent/plugin/dir"]) {
+		Err(CuckooMinerError::NoPluginsFoundError(_)) => {},
+		r => panic!("Expected NoPluginsFoundError, got {:?}", r),
+	}
+	match plugin_manager.get_available_plugins("") {
+		Err(CuckooMinerError::NoPluginsFoundError(_)) => {},
+		r => panic!("Expected NoPluginsFoundError, got {:?}", r),
+	}
+	fs::remove_dir_all(&d).unwrap();
+}
+
+//scans the build directory alongside a junk one, then filters and loads
+#[test]
+fn on_commit_manager_filter_and_load() {
+	let d = temp_plugin_dir();
+	let mut f = fs::File::create(d.join("not_a_plugin.cuckooplugin")).unwrap();
+	f.write_all(b"not a shared library").unwrap();
+
+	let mut plugin_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+	plugin_dir.push("target/debug/plugins/");
+
+	let mut plugin_manager = CuckooPluginManager::new().unwrap();
+	plugin_manager
+		.load_plugin_dirs(&[d.to_str().unwrap(), plugin_dir.to_str().unwrap()])
+		.unwrap();
+
+	let all = plugin_manager.get_available_plugins("").unwrap();
+	for c in &all {
+		println!("Found plugin: [{}]", c);
+		assert!(!c.file_name.contains("not_a_plugin"));
+		assert!(c.name.len() > 0);
+	}
+
+	let caps = plugin_manager.get_available_plugins("lean_cpu_16").unwrap();
+	for c in &caps {
+		assert!(c.file_name.contains("lean_cpu_16"));
+	}
+
+	let libraries = plugin_manager.load("lean_cpu_16").unwrap();
+	assert!(libraries.len() == caps.len());
+	for l in &libraries {
+		l.get_parameter("NUM_THREADS").unwrap();
+	}
+	fs::remove_dir_all(&d).unwrap();
+}
+
+//plugins are found by name via CUCKOO_PLUGIN_PATH, and a failed search
+//reports where it looked
+#[test]
+fn manager_resolve_plugin_path() {
+	let _lock = PLUGIN_PATH_LOCK.lock().unwrap();
+	let d = temp_plugin_dir();
+	let plugin = d.join("resolve_test.cuckooplugin");
+	fs::File::create(&plugin).unwrap();
+
+	// explicit paths are used as is
+	assert!(resolve_plugin_path(plugin.to_str().unwrap()).unwrap() == plugin);
+
+	let search_path = env::join_paths(vec![PathBuf::from("/nonexistent/plugin/dir"), d.clone()]).unwrap();
+	env::set_var(PLUGIN_PATH_VAR, &search_path);
+	assert!(resolve_plugin_path("resolve_test").unwrap() == plugin);
+	assert!(resolve_plugin_path("resolve_test.cuckooplugin").unwrap() == plugin);
+
+	match resolve_plugin_path("missing_plugin") {
+		Err(CuckooMinerError::PluginNotFoundError(e)) => {
+			assert!(e.contains(d.join("missing_plugin.cuckooplugin").to_str().unwrap()));
+			assert!(e.contains("nonexistent"));
+		},
+		r => panic!("Expected PluginNotFoundError, got {:?}", r),
+	}
+	env::remove_var(PLUGIN_PATH_VAR);
+	fs::remove_dir_all(&d).unwrap();
+}
+
+//plugins can be picked by the size of graph they search
+#[test]
+fn manager_finds_plugins_by_cuckoo_size() {
+	let d = temp_plugin_dir();
+	install_mock(&d, "mock_b.cuckooplugin");
+	install_mock(&d, "mock_a.cuckooplugin");
+
+	let mut plugin_manager = CuckooPluginManager::new().unwrap();
+	plugin_manager.load_plugin_dir(String::from(d.to_str().unwrap())).unwrap();
+	let caps = plugin_manager.get_plugins_with_cuckoo_size(16).unwrap();
+	assert_eq!(caps.iter().map(|c| c.file_name.as_str()).collect::<Vec<_>>(),
+		vec!["mock_a.cuckooplugin", "mock_b.cuckooplugin"]);
+	for c in &caps {
+		assert_eq!(c.name, "mock");
+		assert_eq!(c.cuckoo_size(), Some(16));
+	}
+	match plugin_manager.get_plugins_with_cuckoo_size(30) {
+		Err(CuckooMinerError::NoPluginsFoundError(_)) => {},
+		r => panic!("Expected NoPluginsFoundError, got {:?}", r),
+	}
+	fs::remove_dir_all(&d).unwrap();
+}
+
+//a plugin in an earlier directory hides one of the same name in a later one
+#[test]
+fn manager_earlier_dirs_shadow_later_ones() {
+	let (first, second) = (temp_plugin_dir(), temp_plugin_dir());
+	let shadowing = install_mock(&first, "mock.cuckooplugin");
+	install_mock(&second, "mock.cuckooplugin");
+	let other = install_mock(&second, "other.cuckooplugin");
+
+	let mut plugin_manager = CuckooPluginManager::new().unwrap();
+	plugin_manager.load_plugin_dirs(&[first.to_str().unwrap(), second.to_str().unwrap()]).unwrap();
+	let caps = plugin_manager.get_available_plugins("").unwrap();
+	assert_eq!(caps.iter().map(|c| PathBuf::from(&c.full_path)).collect::<Vec<_>>(), vec![shadowing, other]);
+	fs::remove_dir_all(&first).unwrap();
+	fs::remove_dir_all(&second).unwrap();
+}
+
+//the search directories include CUCKOO_PLUGIN_PATH and the build directory
+#[test]
+fn manager_scans_search_dirs() {
+	let _lock = PLUGIN_PATH_LOCK.lock().unwrap();
+	let d = temp_plugin_dir();
+	let plugin = install_mock(&d, "env_mock.cuckooplugin");
+	env::set_var(PLUGIN_PATH_VAR, &d);
+	let mut plugin_manager = CuckooPluginManager::new().unwrap();
+	let result = plugin_manager.load_search_dirs();
+	env::remove_var(PLUGIN_PATH_VAR);
+	result.unwrap();
+	let caps = plugin_manager.get_available_plugins("env_mock").unwrap();
+	assert_eq!(PathBuf::from(&caps[0].full_path), plugin);
+	fs::remove_dir_all(&d).unwrap();
+}
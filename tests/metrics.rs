@@ -0,0 +1,210 @@
+// Copyright 2017 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tests of the Prometheus metrics rendered from a job's stats
+
+extern crate cuckoo_miner as cuckoo;
+
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use cuckoo::{CuckooMiner, CuckooMinerConfig, PluginDeviceStats, StatsCollector, render_metrics};
+
+pub mod common;
+
+// A sample: the metric name, its labels and its value
+type Sample = (String, BTreeMap<String, String>, f64);
+
+// Parses the exposition format, checking every sample belongs to the
+// family declared before it, and returns the families' types and samples
+fn parse(text: &str) -> (BTreeMap<String, String>, Vec<Sample>) {
+	let mut types = BTreeMap::new();
+	let mut samples = Vec::new();
+	let mut family = String::new();
+	for line in text.lines() {
+		if line.starts_with("# HELP ") {
+			continue;
+		}
+		if line.starts_with("# TYPE ") {
+			let words: Vec<&str> = line.split_whitespace().collect();
+			assert_eq!(words.len(), 4, "{}", line);
+			assert!(words[3] == "gauge" || words[3] == "counter", "{}", line);
+			assert!(types.insert(String::from(words[2]), String::from(words[3])).is_none(), "{}", line);
+			family = String::from(words[2]);
+			continue;
+		}
+		let (name, rest) = line.split_at(line.find(|c| c == '{' || c == ' ').expect(line));
+		assert_eq!(name, family, "{}", line);
+		let mut labels = BTreeMap::new();
+		let mut rest = rest.chars().peekable();
+		if rest.peek() == Some(&'{') {
+			rest.next();
+			loop {
+				let key: String = rest.by_ref().take_while(|c| *c != '=').collect();
+				assert_eq!(rest.next(), Some('"'), "{}", line);
+				let mut value = String::new();
+				loop {
+					match rest.next().expect(line) {
+						'\\' => value.push(match rest.next().expect(line) {
+							'n' => '\n',
+							c => c,
+						}),
+						'"' => break,
+						c => value.push(c),
+					}
+				}
+				assert!(labels.insert(key, value).is_none(), "{}", line);
+				match rest.next() {
+					Some(',') => continue,
+					Some('}') => break,
+					_ => panic!("Malformed labels in {}", line),
+				}
+			}
+		}
+		let value: String = rest.collect();
+		assert!(value.starts_with(' '), "{}", line);
+		let value = value.trim().parse::<f64>().expect(line);
+		samples.push((String::from(name), labels, value));
+	}
+	(types, samples)
+}
+
+fn device(id: &str, iterations: u32, temperature_c: Option<f32>) -> PluginDeviceStats {
+	PluginDeviceStats {
+		plugin_name: None,
+		device_id: String::from(id),
+		cuckoo_size: String::from("30"),
+		device_name: format!("Device {}", id),
+		in_use: 1,
+		has_errored: false,
+		error_reason: String::new(),
+		last_start_time: 0,
+		last_end_time: 0,
+		last_solution_time: 0,
+		iterations_completed: iterations,
+		temperature_c: temperature_c,
+		fan_percent: None,
+		memory_used_bytes: None,
+		host_memory_bytes: None,
+	}
+}
+
+fn labels(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+	pairs.iter().map(|&(k, v)| (String::from(k), String::from(v))).collect()
+}
+
+#[test]
+fn stats_are_rendered() {
+	let mut stats = StatsCollector::new(Duration::from_secs(10), Duration::from_secs(1));
+	let start = Instant::now();
+	stats.sample_at(0, &[device("0", 0, Some(71.5)), device("1", 0, None)], start);
+	stats.sample_at(1, &[device("0", 0, None)], start);
+	stats.sample_at(0, &[device("0", 20, Some(72.0)), device("1", 10, None)], start + Duration::from_secs(10));
+	stats.record_solution();
+	stats.record_cycle();
+	stats.record_cycle();
+	stats.record_plugin_error(1);
+	stats.record_duty_cycle(1, Some(48.5));
+	stats.record_queue_depths(0, (12, 3));
+	// two instances of a plugin, whose name needs escaping
+	let names = vec![String::from("lean \"cpu\""), String::from("lean \"cpu\"")];
+	let (types, samples) = parse(&render_metrics(&stats, &names));
+
+	assert_eq!(types["cuckoo_miner_graphs_per_second"], "gauge");
+	assert_eq!(types["cuckoo_miner_solutions_found_total"], "counter");
+	let first = |device: &str| labels(&[("plugin", "lean \"cpu\""), ("instance", "0"), ("device", device)]);
+	let find = |name: &str, labels: &BTreeMap<String, String>| {
+		samples
+			.iter()
+			.find(|s| s.0 == name && s.1 == *labels)
+			.map(|s| s.2)
+	};
+	assert_eq!(find("cuckoo_miner_graphs_per_second", &first("0")), Some(2.0));
+	assert_eq!(find("cuckoo_miner_graphs_per_second", &first("1")), Some(1.0));
+	assert_eq!(find("cuckoo_miner_device_temperature_celsius", &first("0")), Some(72.0));
+	// devices which don't report a temperature are left out
+	assert_eq!(find("cuckoo_miner_device_temperature_celsius", &first("1")), None);
+	assert_eq!(find("cuckoo_miner_device_errored", &first("0")), Some(0.0));
+	let second = labels(&[("plugin", "lean \"cpu\""), ("instance", "1")]);
+	assert_eq!(find("cuckoo_miner_plugin_errors_total", &second), Some(1.0));
+	// only throttled plugins have a duty cycle
+	assert_eq!(find("cuckoo_miner_duty_cycle_percent", &second), Some(48.5));
+	let first_plugin = labels(&[("plugin", "lean \"cpu\""), ("instance", "0")]);
+	assert_eq!(find("cuckoo_miner_duty_cycle_percent", &first_plugin), None);
+	assert_eq!(types["cuckoo_miner_input_queue_depth"], "gauge");
+	assert_eq!(find("cuckoo_miner_input_queue_depth", &first_plugin), Some(12.0));
+	assert_eq!(find("cuckoo_miner_output_queue_depth", &first_plugin), Some(3.0));
+	// and only sampled plugins have queue depths
+	assert_eq!(find("cuckoo_miner_input_queue_depth", &second), None);
+	assert_eq!(find("cuckoo_miner_solutions_found_total", &BTreeMap::new()), Some(1.0));
+	assert_eq!(find("cuckoo_miner_cycles_found_total", &BTreeMap::new()), Some(2.0));
+}
+
+// Fetches a path from the metrics listener, returning the response
+fn get(port: u16, path: &str) -> String {
+	let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+	write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).unwrap();
+	let mut response = String::new();
+	stream.read_to_string(&mut response).unwrap();
+	response
+}
+
+#[test]
+fn mock_job_metrics_are_served() {
+	let mut config = CuckooMinerConfig::new();
+	config.plugin_full_path = common::mock_plugin_copy();
+	config.parameter_list.push((String::from("MOCK_DEVICES"), 0, 2));
+	config.parameter_list.push((String::from("MOCK_DELAY_MS"), 0, 10));
+	// every graph gives a malformed solution, which counts as an error
+	config.parameter_list.push((String::from("MOCK_SOLVE_ALL"), 0, 1));
+	config.parameter_list.push((String::from("MOCK_BAD_PROOF"), 0, 1));
+	let mut miner = CuckooMiner::new(vec![config]).unwrap();
+	miner.set_stats_interval(Duration::from_millis(100));
+	let handle = miner.notify(1, common::SAMPLE_GRIN_PRE_HEADER_1, common::SAMPLE_GRIN_POST_HEADER_1, 0, true)
+		.unwrap();
+	let server = handle.serve_metrics("127.0.0.1:0").unwrap();
+	thread::sleep(Duration::from_millis(1000));
+
+	let response = get(server.local_addr().port(), "/metrics");
+	handle.stop_jobs();
+	assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+	assert!(response.contains("Content-Type: text/plain; version=0.0.4\r\n"), "{}", response);
+	let body = &response[response.find("\r\n\r\n").unwrap() + 4..];
+	let (types, samples) = parse(body);
+	for family in &[
+		"cuckoo_miner_graphs_per_second",
+		"cuckoo_miner_device_errored",
+		"cuckoo_miner_device_temperature_celsius",
+		"cuckoo_miner_plugin_errors_total",
+		"cuckoo_miner_solutions_found_total",
+		"cuckoo_miner_cycles_found_total",
+		"cuckoo_miner_duplicates_suppressed_total",
+	] {
+		assert!(types.contains_key(*family), "{} missing from {}", family, body);
+	}
+	let rates: Vec<&Sample> = samples.iter().filter(|s| s.0 == "cuckoo_miner_graphs_per_second").collect();
+	assert_eq!(rates.len(), 2, "{}", body);
+	assert!(rates.iter().all(|s| s.2 > 0.0), "{}", body);
+	assert_eq!(rates[1].1["device"], "1");
+	let errors = samples.iter().find(|s| s.0 == "cuckoo_miner_plugin_errors_total").unwrap();
+	assert!(errors.2 > 0.0, "{}", body);
+	// as served
+	assert_eq!(parse(&handle.metrics_text()).0, types);
+
+	assert!(get(server.local_addr().port(), "/").starts_with("HTTP/1.1 404"));
+	server.stop();
+}
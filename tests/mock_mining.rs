@@ -0,0 +1,735 @@
+// Copyright 2017 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tests of mining against the mock plugin, which can be made to
+//! misbehave in ways the real plugins can't be relied on to
+
+extern crate cuckoo_miner as cuckoo;
+
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::time::{Duration, Instant};
+
+use cuckoo::{BenchmarkMode, CuckooMiner, CuckooMinerConfig, CuckooMinerError, CuckooMinerJobHandle, MinerEvent,
+	NonceEncoding, NonceRange, PluginLibrary, PluginState, from_hex, header_hash, shutdown_plugins, available_memory, verify, DEFAULT_SHUTDOWN_DEADLINE,
+	CANCEL_BOUND, ALL_DEVICES, DeviceConfig, MiningJob, HeaderHasher, Blake2bHasher};
+
+pub mod common;
+
+// A config for a fresh copy of the mock plugin, with the given parameters
+fn mock_config(params: &[(&str, u32)]) -> CuckooMinerConfig {
+	let mut config = CuckooMinerConfig::new();
+	config.plugin_full_path = common::mock_plugin_copy();
+	for &(name, value) in params {
+		config.parameter_list.push((String::from(name), 0, value));
+	}
+	config
+}
+
+// Starts mining the sample header from the nonce with a known solution,
+// hashing the header as the mock only knows the hash
+fn mine_sample(config: CuckooMinerConfig, verify: bool) -> (CuckooMinerJobHandle, Receiver<MinerEvent>) {
+	let mut miner = CuckooMiner::new(vec![config]).unwrap();
+	let (tx, rx) = mpsc::channel();
+	miner.set_event_channel(tx);
+	miner.set_start_nonce(common::SAMPLE_GRIN_16_NONCE_1);
+	miner.set_verify_solutions(verify);
+	let handle = miner
+		.notify(1, common::SAMPLE_GRIN_PRE_HEADER_1, common::SAMPLE_GRIN_POST_HEADER_1, 0, true)
+		.unwrap();
+	(handle, rx)
+}
+
+// Whether every plugin in the job has been unloaded
+fn all_unloaded(handle: &CuckooMinerJobHandle) -> bool {
+	handle.library.read().unwrap().iter().all(|l| l.state() == PluginState::Unloaded)
+}
+
+// Collects events until one matches, or panics after 30 seconds
+fn events_until<F>(rx: &Receiver<MinerEvent>, matches: F) -> Vec<MinerEvent>
+where
+	F: Fn(&MinerEvent) -> bool,
+{
+	let start = Instant::now();
+	let mut events = Vec::new();
+	while start.elapsed() < Duration::from_secs(30) {
+		if let Ok(e) = rx.recv_timeout(Duration::from_millis(100)) {
+			let done = matches(&e);
+			events.push(e);
+			if done {
+				return events;
+			}
+		}
+	}
+	panic!("Expected event not sent within 30 seconds, got {:?}", events);
+}
+
+// Mines with the mock returning malformed proofs, which should each be
+// reported and dropped, returning the message reported
+fn malformed_proof_message(bad_proof: u32) -> String {
+	let config = mock_config(&[("MOCK_BAD_PROOF", bad_proof)]);
+	let name = Path::new(&config.plugin_full_path)
+		.file_stem()
+		.unwrap()
+		.to_string_lossy()
+		.into_owned();
+	// without verification, so only the bounds check can catch them
+	let (handle, rx) = mine_sample(config, false);
+	let events = events_until(&rx, |e| match *e {
+		MinerEvent::PluginError { .. } => true,
+		_ => false,
+	});
+	handle.stop_jobs();
+	for e in rx.iter() {
+		if let MinerEvent::JobStopped(_) = e {
+			break;
+		}
+		assert!(match e {
+			MinerEvent::SolutionFound(_) => false,
+			_ => true,
+		});
+	}
+	match events.into_iter().last() {
+		Some(MinerEvent::PluginError { plugin, code, message }) => {
+			assert_eq!(plugin, name);
+			assert_eq!(code, 0);
+			message
+		}
+		e => panic!("Expected a PluginError, got {:?}", e),
+	}
+}
+
+#[test]
+fn known_solution_is_found() {
+	let (handle, rx) = mine_sample(mock_config(&[]), true);
+	let events = events_until(&rx, |e| match *e {
+		MinerEvent::SolutionFound(_) => true,
+		_ => false,
+	});
+	handle.stop_jobs();
+	match events.into_iter().last() {
+		Some(MinerEvent::SolutionFound(s)) => {
+			assert_eq!(s.nonce, common::SAMPLE_GRIN_16_NONCE_1);
+			assert_eq!(s.solution_nonces, common::widen(&common::SAMPLE_GRIN_16_SOLUTION_1));
+			let hash = header_hash(
+				&from_hex(common::SAMPLE_GRIN_PRE_HEADER_1).unwrap(),
+				&from_hex(common::SAMPLE_GRIN_POST_HEADER_1).unwrap(),
+				s.nonce,
+			);
+			assert_eq!(s.header_hash, Some(hash));
+		}
+		e => panic!("Expected a solution, got {:?}", e),
+	}
+}
+
+// Hashes as the miner does by default, counting the headers hashed
+struct CountingHasher(Arc<AtomicUsize>);
+
+impl HeaderHasher for CountingHasher {
+	fn hash(&self, header_pre: &[u8], nonce: &[u8; 8], header_post: &[u8]) -> [u8; 32] {
+		self.0.fetch_add(1, Ordering::SeqCst);
+		Blake2bHasher.hash(header_pre, nonce, header_post)
+	}
+}
+
+#[test]
+fn raw_header_jobs_are_hashed_by_the_miner() {
+	let hashed = Arc::new(AtomicUsize::new(0));
+	let mut miner = CuckooMiner::new(vec![mock_config(&[])]).unwrap();
+	let (tx, rx) = mpsc::channel();
+	miner.set_event_channel(tx);
+	miner.set_header_hasher(CountingHasher(hashed.clone()));
+	let pre = from_hex(common::SAMPLE_GRIN_PRE_HEADER_1).unwrap();
+	let post = from_hex(common::SAMPLE_GRIN_POST_HEADER_1).unwrap();
+	let job = MiningJob::new(4, &pre, &post).start_nonce(common::SAMPLE_GRIN_16_NONCE_1 - 3);
+	let handle = miner.start(job).unwrap();
+	let events = events_until(&rx, |e| match *e {
+		MinerEvent::SolutionFound(_) => true,
+		_ => false,
+	});
+	handle.stop_jobs();
+	match events.into_iter().last() {
+		Some(MinerEvent::SolutionFound(s)) => {
+			assert_eq!(s.id, 4);
+			assert_eq!(s.nonce, common::SAMPLE_GRIN_16_NONCE_1);
+			assert_eq!(s.header_hash, Some(header_hash(&pre, &post, s.nonce)));
+		}
+		e => panic!("Expected a solution, got {:?}", e),
+	}
+	assert!(hashed.load(Ordering::SeqCst) >= 4);
+}
+
+#[test]
+fn jobs_set_their_own_nonces() {
+	let pre = from_hex(common::SAMPLE_GRIN_PRE_HEADER_1).unwrap();
+	let post = from_hex(common::SAMPLE_GRIN_POST_HEADER_1).unwrap();
+	let mut miner = CuckooMiner::new(vec![mock_config(&[])]).unwrap();
+	let (tx, rx) = mpsc::channel();
+	miner.set_event_channel(tx);
+	// four nonces well clear of the one with a solution
+	let start = common::SAMPLE_GRIN_16_NONCE_1 + 1000;
+	let handle = miner.start(MiningJob::new(1, &pre, &post).start_nonce(start).max_nonces(4)).unwrap();
+	let waiting = Instant::now();
+	while handle.nonce_position(0) != Some(start + 3) {
+		assert!(waiting.elapsed() < Duration::from_secs(30), "at {:?}", handle.nonce_position(0));
+		std::thread::sleep(Duration::from_millis(10));
+	}
+	std::thread::sleep(Duration::from_millis(500));
+	assert_eq!(handle.nonce_position(0), Some(start + 3));
+
+	// the next job starts over, just short of the solution
+	let job = MiningJob::new(2, &pre, &post).start_nonce(common::SAMPLE_GRIN_16_NONCE_1 - 2);
+	handle.notify_job(job).unwrap();
+	let events = events_until(&rx, |e| match *e {
+		MinerEvent::SolutionFound(_) => true,
+		_ => false,
+	});
+	handle.stop_jobs();
+	match events.into_iter().last() {
+		Some(MinerEvent::SolutionFound(s)) => {
+			assert_eq!(s.id, 2);
+			assert_eq!(s.nonce, common::SAMPLE_GRIN_16_NONCE_1);
+		}
+		e => panic!("Expected a solution, got {:?}", e),
+	}
+}
+
+#[test]
+fn out_of_range_proofs_are_dropped() {
+	let message = malformed_proof_message(1);
+	assert!(message.contains("too big"), "{}", message);
+}
+
+#[test]
+fn repeated_nonce_proofs_are_dropped() {
+	let message = malformed_proof_message(2);
+	assert!(message.contains("not ascending"), "{}", message);
+}
+
+#[test]
+fn other_proof_sizes_are_mined() {
+	let config = mock_config(&[("MOCK_PROOF_SIZE", 12)]);
+	let mut miner = CuckooMiner::new(vec![config]).unwrap();
+	let (tx, rx) = mpsc::channel();
+	miner.set_event_channel(tx);
+	miner.set_start_nonce(0);
+	miner.set_verify_solutions(true);
+	// the header with a 12-cycle, split either side of a zero nonce
+	let header = common::KNOWN_16_HASH_12;
+	let handle = miner.notify(1, &header[..16], &header[32..], 0, false).unwrap();
+	let events = events_until(&rx, |e| match *e {
+		MinerEvent::SolutionFound(_) => true,
+		_ => false,
+	});
+	handle.stop_jobs();
+	match events.into_iter().last() {
+		Some(MinerEvent::SolutionFound(s)) => {
+			assert_eq!(s.nonce, 0);
+			assert_eq!(s.solution_nonces, common::widen(&common::KNOWN_16_SOLUTION_12));
+		}
+		e => panic!("Expected a solution, got {:?}", e),
+	}
+}
+
+#[test]
+fn large_graphs_are_mined() {
+	// the mock's solution at 40 edge bits isn't a real cycle
+	let config = mock_config(&[("EDGE_BITS", 40)]);
+	let mut miner = CuckooMiner::new(vec![config]).unwrap();
+	let (tx, rx) = mpsc::channel();
+	miner.set_event_channel(tx);
+	miner.set_start_nonce(0);
+	miner.set_verify_solutions(false);
+	let header = common::MOCK_40_HASH;
+	let handle = miner.notify(1, &header[..16], &header[32..], 0, false).unwrap();
+	let events = events_until(&rx, |e| match *e {
+		MinerEvent::SolutionFound(_) => true,
+		_ => false,
+	});
+	handle.stop_jobs();
+	match events.into_iter().last() {
+		Some(MinerEvent::SolutionFound(s)) => {
+			assert_eq!(s.cuckoo_size, 41);
+			assert_eq!(s.solution_nonces[..], common::MOCK_40_SOLUTION[..]);
+		}
+		e => panic!("Expected a solution, got {:?}", e),
+	}
+}
+
+#[test]
+fn shutdown_returns_solutions_and_unloads() {
+	let mut miner = CuckooMiner::new(vec![mock_config(&[]), mock_config(&[])]).unwrap();
+	miner.set_start_nonce(common::SAMPLE_GRIN_16_NONCE_1);
+	let handle = miner
+		.notify(1, common::SAMPLE_GRIN_PRE_HEADER_1, common::SAMPLE_GRIN_POST_HEADER_1, 0, true)
+		.unwrap();
+	let start = Instant::now();
+	while handle.solutions_found() == 0 {
+		assert!(start.elapsed() < Duration::from_secs(30), "No solution found");
+		std::thread::sleep(Duration::from_millis(10));
+	}
+	let solutions = handle.shutdown(DEFAULT_SHUTDOWN_DEADLINE);
+	assert!(solutions.iter().any(|s| s.nonce == common::SAMPLE_GRIN_16_NONCE_1));
+	assert!(all_unloaded(&handle));
+	// and again, which does nothing
+	assert!(handle.shutdown(DEFAULT_SHUTDOWN_DEADLINE).is_empty());
+}
+
+#[test]
+fn shutdown_force_unloads_stragglers() {
+	let config = mock_config(&[("MOCK_IGNORE_STOP_MS", 5000)]);
+	let (handle, _rx) = mine_sample(config, false);
+	let start = Instant::now();
+	handle.shutdown(Duration::from_millis(100));
+	assert!(start.elapsed() < Duration::from_secs(5));
+	assert!(all_unloaded(&handle));
+}
+
+#[test]
+fn shutdown_flag_shuts_down() {
+	let (handle, _rx) = mine_sample(mock_config(&[]), false);
+	// as a signal handler would
+	handle.shutdown_flag().store(true, std::sync::atomic::Ordering::SeqCst);
+	handle.stop_jobs();
+	assert!(all_unloaded(&handle));
+}
+
+#[test]
+fn shutdown_plugins_drains_queues() {
+	let pl = PluginLibrary::new(&common::mock_plugin_copy()).unwrap();
+	let header = cuckoo::from_hex(common::KNOWN_16_HASH_1).unwrap();
+	pl.push_to_input_queue(3, &header, &[0; 8]).unwrap();
+	pl.start_processing().unwrap();
+	std::thread::sleep(Duration::from_millis(200));
+	let libraries = vec![pl];
+	let solutions = shutdown_plugins(&libraries, DEFAULT_SHUTDOWN_DEADLINE);
+	assert_eq!(solutions.len(), 1);
+	assert_eq!(solutions[0].id, 3);
+	assert_eq!(libraries[0].state(), PluginState::Unloaded);
+	assert!(shutdown_plugins(&libraries, DEFAULT_SHUTDOWN_DEADLINE).is_empty());
+}
+
+#[test]
+fn mine_reuses_one_solver_context() {
+	let config = mock_config(&[]);
+	// shares the mock's state, to see the contexts the miner leaves
+	let observer = PluginLibrary::new(&config.plugin_full_path).unwrap();
+	let miner = CuckooMiner::new(vec![config]).unwrap();
+	let solution = miner
+		.mine(
+			&from_hex(common::SAMPLE_GRIN_PRE_HEADER_1).unwrap(),
+			&from_hex(common::SAMPLE_GRIN_POST_HEADER_1).unwrap(),
+			common::SAMPLE_GRIN_16_NONCE_1 - 5,
+			Some(10),
+		)
+		.unwrap()
+		.unwrap();
+	assert_eq!(solution.nonce, common::SAMPLE_GRIN_16_NONCE_1);
+	assert!(solution.header_hash.is_some() && solution.solve_time().is_some());
+	assert_eq!(observer.get_parameter("MOCK_CONTEXTS").unwrap(), 0);
+
+	let report = miner.benchmark(0, 5, 0, BenchmarkMode::Sync).unwrap();
+	assert!(report.persistent_context);
+	assert_eq!(report.graphs, 5);
+	assert_eq!(observer.get_parameter("MOCK_CONTEXTS").unwrap(), 0);
+}
+
+#[test]
+fn every_plugin_is_benchmarked() {
+	let configs = vec![mock_config(&[("MOCK_DELAY_MS", 5)]), mock_config(&[("MOCK_DELAY_MS", 10)])];
+	let miner = CuckooMiner::new(configs).unwrap();
+	for mode in &[BenchmarkMode::Sync, BenchmarkMode::Async] {
+		let reports = miner.benchmark_all(4, 0, *mode).unwrap();
+		assert_eq!(reports.len(), 2);
+		for (r, delay) in reports.iter().zip(&[5, 10]) {
+			assert_eq!((r.mode, r.graphs), (*mode, 4));
+			assert!(r.phases.search >= Duration::from_millis(4 * delay), "{:?}", r);
+			assert!(r.phases.setup + r.phases.search + r.phases.teardown <= r.elapsed, "{:?}", r);
+		}
+		assert!(reports[0].median_graph_time < reports[1].median_graph_time);
+	}
+}
+
+#[test]
+fn mine_is_cancelled_by_stop_flag() {
+	// each attempt takes as long as a cuckoo 30 graph might
+	let miner = CuckooMiner::new(vec![mock_config(&[("MOCK_DELAY_MS", 10000)])]).unwrap();
+	let stop = miner.stop_flag();
+	std::thread::spawn(move || {
+		std::thread::sleep(Duration::from_millis(100));
+		stop.store(true, std::sync::atomic::Ordering::SeqCst);
+	});
+	let start = Instant::now();
+	let result = miner.mine(&[0; 72], &[0; 5], 0, None).unwrap();
+	assert!(result.is_none());
+	assert!(start.elapsed() < Duration::from_millis(100) + CANCEL_BOUND);
+}
+
+#[test]
+fn duty_cycle_throttles_graphs() {
+	let mut miner = CuckooMiner::new(vec![mock_config(&[("MOCK_DELAY_MS", 200)])]).unwrap();
+	assert!(miner.set_duty_cycle(0).is_err());
+	assert!(miner.set_duty_cycle(101).is_err());
+	miner.set_duty_cycle(50).unwrap();
+	miner.set_stats_interval(Duration::from_millis(100));
+	let handle = miner
+		.notify(1, common::SAMPLE_GRIN_PRE_HEADER_1, common::SAMPLE_GRIN_POST_HEADER_1, 0, true)
+		.unwrap();
+	std::thread::sleep(Duration::from_secs(3));
+	// a graph at a time with as long idle after it, give or take a poll
+	let graphs = handle.get_stats(0).unwrap()[0].iterations_completed;
+	assert!(graphs >= 3 && graphs <= 10, "{} graphs", graphs);
+	let achieved = handle.duty_cycle(0).unwrap();
+	assert!(achieved > 25.0 && achieved < 75.0, "{}%", achieved);
+
+	// at 100 the plugin's kept busy, as if it had never been throttled
+	assert!(handle.set_duty_cycle(0).is_err());
+	handle.set_duty_cycle(100).unwrap();
+	let start = Instant::now();
+	while handle.duty_cycle(0).is_some() {
+		assert!(start.elapsed() < Duration::from_secs(5), "Still throttled");
+		std::thread::sleep(Duration::from_millis(50));
+	}
+	let before = handle.get_stats(0).unwrap()[0].iterations_completed;
+	std::thread::sleep(Duration::from_secs(1));
+	let after = handle.get_stats(0).unwrap()[0].iterations_completed;
+	assert!(after - before >= 4, "{} graphs", after - before);
+	handle.stop_jobs();
+}
+
+#[test]
+fn queues_are_topped_up_to_the_high_water_mark() {
+	// a high water mark of 6 headers
+	let mut miner = CuckooMiner::new(vec![mock_config(&[("QUEUE_SIZE", 8), ("MOCK_DELAY_MS", 200)])]).unwrap();
+	miner.set_stats_interval(Duration::from_millis(50));
+	let handle = miner
+		.notify(1, common::SAMPLE_GRIN_PRE_HEADER_1, common::SAMPLE_GRIN_POST_HEADER_1, 0, true)
+		.unwrap();
+	let start = Instant::now();
+	let mut depths = Vec::new();
+	while start.elapsed() < Duration::from_secs(2) {
+		depths.extend(handle.queue_depths(0));
+		std::thread::sleep(Duration::from_millis(50));
+	}
+	handle.stop_jobs();
+	// kept fed, but never filled
+	assert!(!depths.is_empty());
+	assert!(depths.iter().all(|&(input, _)| input >= 4 && input <= 6), "{:?}", depths);
+}
+
+#[test]
+fn pushes_accepting_nothing_are_reported() {
+	let (handle, rx) = mine_sample(mock_config(&[("MOCK_PUSH_NOTHING", 1)]), true);
+	let events = events_until(&rx, |e| match *e {
+		MinerEvent::PluginError { .. } => true,
+		_ => false,
+	});
+	match events.into_iter().last() {
+		Some(MinerEvent::PluginError { code, message, .. }) => {
+			assert_eq!(code, 0);
+			assert!(message.contains("accepted none"), "{}", message);
+		}
+		e => panic!("Expected a PluginError, got {:?}", e),
+	}
+	// the worker isn't left spinning, so the job still stops
+	let start = Instant::now();
+	handle.stop_jobs();
+	events_until(&rx, |e| match *e {
+		MinerEvent::JobStopped(_) => true,
+		_ => false,
+	});
+	assert!(start.elapsed() < Duration::from_secs(10));
+}
+
+#[test]
+fn job_keeps_hashrate_averages() {
+	let mut miner = CuckooMiner::new(vec![mock_config(&[("MOCK_DELAY_MS", 50)])]).unwrap();
+	miner.set_stats_interval(Duration::from_millis(100));
+	let handle = miner
+		.notify(1, common::SAMPLE_GRIN_PRE_HEADER_1, common::SAMPLE_GRIN_POST_HEADER_1, 0, true)
+		.unwrap();
+	std::thread::sleep(Duration::from_millis(1500));
+	let total = handle.hashrate();
+	let devices = handle.device_snapshots();
+	handle.stop_jobs();
+	// around 20 graphs a second, less the time taken to start
+	assert!(total.one_minute > 5.0 && total.one_minute < 30.0, "{:?}", total);
+	assert!(total.five_minutes == total.one_minute);
+	assert_eq!(devices.len(), 1);
+	assert_eq!(devices[0].hashrate, total);
+}
+
+#[test]
+fn devices_get_an_instance_each() {
+	let config = mock_config(&[("MOCK_DEVICES", 3)]);
+	let configs = config.per_usable_device().unwrap();
+	assert_eq!(configs.iter().map(|c| c.enabled_devices.clone().unwrap()).collect::<Vec<_>>(), vec![vec![0], vec![1], vec![2]]);
+
+	let configs = config.per_device(&[
+		DeviceConfig::new(2).parameter("MOCK_DELAY_MS", 5),
+		DeviceConfig::new(0).threads(64).blocks(128),
+	]);
+	assert_eq!(configs[1].device_parameters[&0]["THREADS_PER_BLOCK"], 64);
+	assert_eq!(configs[1].device_parameters[&0]["NUM_BLOCKS"], 128);
+	// the mock has no blocks to set
+	assert!(CuckooMiner::new(configs.clone()).is_err());
+
+	let miner = CuckooMiner::new(config.per_device(&[DeviceConfig::new(2).parameter("MOCK_DELAY_MS", 5), DeviceConfig::new(0)])).unwrap();
+	let in_use = |i: usize| miner.get_stats(i).unwrap().iter().map(|d| d.in_use).collect::<Vec<_>>();
+	assert_eq!(in_use(0), vec![0, 0, 1]);
+	assert_eq!(in_use(1), vec![1, 0, 0]);
+
+	let mut limited = config.clone();
+	limited.enabled_devices = Some(vec![1]);
+	assert_eq!(limited.per_usable_device().unwrap().len(), 1);
+	limited.enabled_devices = Some(vec![7]);
+	assert!(limited.per_usable_device().is_err());
+}
+
+#[test]
+fn memory_is_totalled_across_plugins() {
+	let mut config = mock_config(&[("MOCK_DEVICES", 2), ("MOCK_HOST_MEMORY", 1 << 30)]);
+	config.device_parameters.insert(ALL_DEVICES, vec![(String::from("MOCK_MEMORY"), 1 << 20)].into_iter().collect());
+	// a plugin reporting nothing adds nothing, rather than 0
+	let silent = mock_config(&[]);
+	let miner = CuckooMiner::new(vec![silent.clone()]).unwrap();
+	assert_eq!(miner.total_memory_bytes(), None);
+
+	let miner = CuckooMiner::new(vec![config, silent]).unwrap();
+	let stats = miner.get_stats(0).unwrap();
+	assert!(stats.iter().all(|s| s.host_memory_bytes == Some(1 << 30) && s.memory_used_bytes == Some(1 << 20)));
+	// host memory is the plugin's, so is only counted once
+	assert_eq!(miner.total_memory_bytes(), Some((1 << 30) + (2 << 20)));
+	let handle = miner
+		.notify(1, common::SAMPLE_GRIN_PRE_HEADER_1, common::SAMPLE_GRIN_POST_HEADER_1, 0, true)
+		.unwrap();
+	assert_eq!(handle.total_memory_bytes(), Some((1 << 30) + (2 << 20)));
+	handle.stop_jobs();
+}
+
+#[test]
+fn plugins_expecting_too_much_memory_arent_started() {
+	if available_memory().is_none() {
+		return;
+	}
+	// 4 PiB
+	let greedy = mock_config(&[("EXPECTED_MEMORY_MB", 0xffff_ffff)]);
+	let path = greedy.plugin_full_path.clone();
+	let miner = CuckooMiner::new(vec![mock_config(&[("EXPECTED_MEMORY_MB", 1)]), greedy]).unwrap();
+	match miner.notify(1, common::SAMPLE_GRIN_PRE_HEADER_1, common::SAMPLE_GRIN_POST_HEADER_1, 0, true) {
+		Err(CuckooMinerError::InsufficientMemory { plugin, required, available }) => {
+			assert_eq!(plugin, path);
+			assert_eq!(required, (0xffff_ffff + 1) << 20);
+			assert!(available < required);
+		}
+		Err(e) => panic!("Expected InsufficientMemory, got {:?}", e),
+		Ok(_) => panic!("The plugin was started"),
+	}
+
+	// unless the check's skipped
+	let mut greedy = mock_config(&[("EXPECTED_MEMORY_MB", 0xffff_ffff)]);
+	greedy.skip_memory_check = true;
+	let miner = CuckooMiner::new(vec![greedy]).unwrap();
+	let handle = miner
+		.notify(1, common::SAMPLE_GRIN_PRE_HEADER_1, common::SAMPLE_GRIN_POST_HEADER_1, 0, true)
+		.unwrap();
+	handle.stop_jobs();
+}
+
+#[test]
+fn nonces_are_pushed_and_read_in_the_configured_encoding() {
+	// the Little-Endian bytes of this nonce are those of nonce 56 in
+	// Big-Endian, so the header pushed is one the mock knows a solution for
+	let (pre, post) = ("0000000000000045", "00000000000000000000000000000000");
+	let nonce = 0x3800_0000_0000_0000;
+	let mut config = mock_config(&[]);
+	config.nonce_range = Some(NonceRange::new(0, 0));
+	config.nonce_encoding = NonceEncoding::LittleEndianU64;
+	let mut miner = CuckooMiner::new(vec![config]).unwrap();
+	let (tx, rx) = mpsc::channel();
+	miner.set_event_channel(tx);
+	miner.set_start_nonce(nonce);
+	let handle = miner.notify(1, pre, post, 0, false).unwrap();
+	let events = events_until(&rx, |e| match *e {
+		MinerEvent::SolutionFound(_) => true,
+		_ => false,
+	});
+	handle.stop_jobs();
+	match events.last() {
+		Some(&MinerEvent::SolutionFound(ref s)) => {
+			assert_eq!(s.nonce, nonce);
+			let header = from_hex(&format!("{}{:016x}{}", pre, 56, post)).unwrap();
+			assert!(verify(&header, s.cuckoo_size, &s.solution_nonces).is_ok());
+		}
+		e => panic!("Expected a solution, got {:?}", e),
+	}
+}
+
+#[test]
+fn solutions_are_attributed_to_the_header_they_solve() {
+	// two headers the mock knows solutions for, both pushed with nonce 56
+	let (pre_1, pre_2, post) = ("0000000000000045", "00000000000000a0", "00000000000000000000000000000000");
+	let header = |pre: &str| from_hex(&format!("{}{:016x}{}", pre, 56, post)).unwrap();
+
+	// the mock reports solutions under the job which last pushed their
+	// nonce, so the first job's, found once the job has switched, is
+	// reported for the second
+	let mut config = mock_config(&[("MOCK_DELAY_MS", 1000), ("MOCK_ID_BY_NONCE", 1)]);
+	config.nonce_range = Some(NonceRange::new(0, 0));
+	let mut miner = CuckooMiner::new(vec![config]).unwrap();
+	let (tx, rx) = mpsc::channel();
+	miner.set_event_channel(tx);
+	miner.set_start_nonce(56);
+	let handle = miner.notify(1, pre_1, post, 0, false).unwrap();
+	let start = Instant::now();
+	while handle.get_stats(0).unwrap()[0].last_start_time == 0 {
+		assert!(start.elapsed() < Duration::from_secs(5), "No graph started");
+		std::thread::sleep(Duration::from_millis(10));
+	}
+	handle.notify(2, pre_2, post, 0).unwrap();
+	let events = events_until(&rx, |e| match *e {
+		MinerEvent::SolutionFound(_) => true,
+		_ => false,
+	});
+	handle.stop_jobs();
+
+	let stale: Vec<_> = events
+		.iter()
+		.filter_map(|e| match *e {
+			MinerEvent::StaleSolution(ref s) => Some(s),
+			_ => None,
+		})
+		.collect();
+	assert!(!stale.is_empty(), "No stale solution in {:?}", events);
+	for s in stale {
+		assert_eq!(s.id, 1);
+		assert!(verify(&header(pre_1), s.cuckoo_size, &s.solution_nonces).is_ok());
+	}
+	match events.last() {
+		Some(&MinerEvent::SolutionFound(ref s)) => {
+			assert_eq!(s.id, 2);
+			assert_eq!(s.nonce, 56);
+			assert!(verify(&header(pre_2), s.cuckoo_size, &s.solution_nonces).is_ok());
+		}
+		e => panic!("Expected a solution, got {:?}", e),
+	}
+	assert!(!events.iter().any(|e| match *e {
+		MinerEvent::UnmatchedSolution { .. } => true,
+		_ => false,
+	}));
+}
+
+#[test]
+fn solutions_are_credited_to_the_device_that_found_them() {
+	// every header gets a solution, which won't verify, and each device
+	// takes its own time over a graph
+	let mut config = mock_config(&[("MOCK_DEVICES", 2), ("MOCK_SOLVE_ALL", 1)]);
+	config.device_parameters.insert(0, vec![(String::from("MOCK_DELAY_MS"), 150)].into_iter().collect());
+	config.device_parameters.insert(1, vec![(String::from("MOCK_DELAY_MS"), 400)].into_iter().collect());
+	let mut miner = CuckooMiner::new(vec![config]).unwrap();
+	let (tx, rx) = mpsc::channel();
+	miner.set_event_channel(tx);
+	miner.set_start_nonce(1000);
+	miner.set_verify_solutions(false);
+	let handle = miner
+		.notify(1, common::SAMPLE_GRIN_PRE_HEADER_1, common::SAMPLE_GRIN_POST_HEADER_1, 0, true)
+		.unwrap();
+	std::thread::sleep(Duration::from_secs(3));
+	handle.stop_jobs();
+
+	let mut credited = [0, 0];
+	for e in rx.try_iter() {
+		let s = match e {
+			MinerEvent::SolutionFound(s) => s,
+			_ => continue,
+		};
+		let m = s.metadata.clone().unwrap();
+		assert_eq!(m.plugin_name, s.plugin_name);
+		// one plugin, so its nonces are the job's attempts in order
+		assert_eq!(m.attempt_index, Some(s.nonce - 1000));
+		let device = match m.device_id {
+			Some(ref d) => d.parse::<usize>().unwrap(),
+			None => continue,
+		};
+		credited[device] += 1;
+		// a graph takes as long as its device's delay
+		if let Some(t) = m.graph_time_ms {
+			let delay = [150, 400][device];
+			assert!(t >= delay && t < delay + 100, "device {} took {} ms", device, t);
+		}
+	}
+	assert!(credited[0] >= 5 && credited[1] >= 2, "{:?}", credited);
+}
+
+#[test]
+fn solution_iterators_get_every_solution() {
+	// every header gets a solution, which won't verify
+	let mut miner = CuckooMiner::new(vec![mock_config(&[("MOCK_SOLVE_ALL", 1), ("MOCK_DELAY_MS", 100)])]).unwrap();
+	miner.set_verify_solutions(false);
+	let handle = miner
+		.notify(1, common::SAMPLE_GRIN_PRE_HEADER_1, common::SAMPLE_GRIN_POST_HEADER_1, 0, true)
+		.unwrap();
+	let all = handle.solutions();
+	let some = handle.solutions();
+	// an iterator which is dropped, or stops early, leaves the others be
+	drop(handle.solutions());
+	let all = std::thread::spawn(move || all.map(|s| s.nonce).collect::<Vec<_>>());
+	let some: Vec<_> = some.take(3).map(|s| s.nonce).collect();
+	std::thread::sleep(Duration::from_millis(500));
+	handle.stop_jobs();
+	let all = all.join().unwrap();
+	assert_eq!(&all[..3], &some[..]);
+
+	// and the solutions are queued as well
+	let queued: Vec<_> = handle.get_solutions().unwrap().iter().map(|s| s.nonce).collect();
+	assert_eq!(queued, all);
+
+	// an iterator over a stopped job is empty
+	assert_eq!(handle.solutions().count(), 0);
+}
+
+#[test]
+fn plugins_are_swapped_while_mining() {
+	let old = mock_config(&[]);
+	let old_name = Path::new(&old.plugin_full_path).file_stem().unwrap().to_string_lossy().into_owned();
+	let (mut handle, rx) = mine_sample(old, false);
+	let found = |e: &MinerEvent| match *e {
+		MinerEvent::SolutionFound(_) => true,
+		_ => false,
+	};
+	match events_until(&rx, &found).into_iter().last() {
+		Some(MinerEvent::SolutionFound(s)) => assert_eq!(s.nonce, common::SAMPLE_GRIN_16_NONCE_1),
+		e => panic!("Expected a solution, got {:?}", e),
+	}
+
+	// the replacement solves every header, so finds solutions for the
+	// nonces after those the old plugin mined
+	let new = mock_config(&[("MOCK_SOLVE_ALL", 1), ("MOCK_DELAY_MS", 10)]);
+	handle.swap_plugin(&old_name, new.clone()).unwrap();
+	assert_eq!(handle.configs[0].plugin_full_path, new.plugin_full_path);
+	assert_eq!(handle.library.read().unwrap()[0].lib_full_path, new.plugin_full_path);
+	match events_until(&rx, &found).into_iter().last() {
+		Some(MinerEvent::SolutionFound(s)) => assert!(s.nonce > common::SAMPLE_GRIN_16_NONCE_1),
+		e => panic!("Expected a solution, got {:?}", e),
+	}
+
+	// the old plugin's gone, so can't be swapped again
+	match handle.swap_plugin(&old_name, mock_config(&[])) {
+		Err(CuckooMinerError::PluginNotFoundError(_)) => {}
+		r => panic!("Expected PluginNotFoundError, got {:?}", r),
+	}
+	handle.stop_jobs();
+}
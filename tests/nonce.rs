@@ -0,0 +1,251 @@
+// Copyright 2017 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tests for the partitioning of nonces between plugins, and their
+//! encoding
+
+extern crate cuckoo_miner as cuckoo;
+
+use std::collections::HashSet;
+
+use cuckoo::{NonceRange, NonceGenerator, NonceEncoding, CuckooMinerError, CuckooMinerSolution, MiningJob, from_hex,
+	to_hex, header_hash, encoded_header_hash};
+
+const PUSHES: usize = 5000;
+
+// every nonce the generators hand out over PUSHES pushes each, failing
+// if any nonce is handed out twice
+fn all_nonces(generators: Vec<NonceGenerator>) -> HashSet<u64> {
+	let mut seen = HashSet::new();
+	for g in generators {
+		for n in g.take(PUSHES) {
+			assert!(seen.insert(n), "Nonce {:#x} handed out twice", n);
+		}
+	}
+	seen
+}
+
+#[test]
+fn interleaved_ranges_are_disjoint() {
+	// starting just short of the top, so the ranges wrap around
+	let start = u64::max_value() - 1000;
+	let generators = (0..2)
+		.map(|i| NonceGenerator::new(start, NonceRange::interleaved(i, 2)))
+		.collect();
+	let seen = all_nonces(generators);
+	assert!(seen.len() == 2 * PUSHES);
+	// between them, the two ranges cover every nonce
+	for i in 0..(2 * PUSHES as u64) {
+		assert!(seen.contains(&start.wrapping_add(i)));
+	}
+}
+
+#[test]
+fn block_ranges_are_disjoint() {
+	let generators = (0..4)
+		.map(|i| NonceGenerator::new(12345, NonceRange::block(i, 4)))
+		.collect();
+	assert!(all_nonces(generators).len() == 4 * PUSHES);
+	assert!(NonceRange::block(1, 4).start == 1 << 62);
+	assert!(NonceRange::block(0, 1) == NonceRange::new(0, 1));
+}
+
+#[test]
+fn split_ranges_share_out_the_range() {
+	let range = NonceRange::new(1, 2);
+	let splits: Vec<NonceRange> = (0..3).map(|i| range.split(i, 3)).collect();
+	assert_eq!(splits, vec![NonceRange::new(1, 6), NonceRange::new(3, 6), NonceRange::new(5, 6)]);
+	assert!(!splits[0].collides_with(&splits[1]) && !splits[1].collides_with(&splits[2]));
+	let generators = splits.iter().map(|r| NonceGenerator::new(0, *r)).collect();
+	let seen = all_nonces(generators);
+	assert!(seen.len() == 3 * PUSHES);
+	// between them, the odd nonces the range holds
+	for i in 0..(3 * PUSHES as u64) {
+		assert!(seen.contains(&range.nth(i)));
+	}
+}
+
+#[test]
+fn colliding_ranges_are_spotted() {
+	let collide = |a: NonceRange, b: NonceRange| {
+		assert_eq!(a.collides_with(&b), b.collides_with(&a));
+		a.collides_with(&b)
+	};
+	// the ranges plugins are given by default, or a share of them each
+	for count in 1..8 {
+		for i in 0..count {
+			for j in 0..count {
+				assert_eq!(collide(NonceRange::interleaved(i, count), NonceRange::interleaved(j, count)), i == j);
+				assert_eq!(collide(NonceRange::block(i, count), NonceRange::block(j, count)), i == j);
+			}
+		}
+	}
+	assert!(!collide(NonceRange::new(0, 4), NonceRange::new(2, 4)));
+	assert!(!collide(NonceRange::new(1, 4), NonceRange::new(0, 2)));
+	assert!(!collide(NonceRange::new(0, 6), NonceRange::new(1, 4)));
+
+	// every nonce, against any share of them
+	assert!(collide(NonceRange::new(0, 1), NonceRange::interleaved(1, 2)));
+	// one range runs into the other
+	assert!(collide(NonceRange::new(0, 2), NonceRange::new(1000, 2)));
+	assert!(collide(NonceRange::new(0, 1), NonceRange::new(1 << 39, 1)));
+	assert!(!collide(NonceRange::new(0, 1), NonceRange::new(1 << 41, 1)));
+	// strides which aren't multiples of each other, meeting at 15
+	assert!(collide(NonceRange::new(3, 4), NonceRange::new(0, 5)));
+	assert!(collide(NonceRange::new(5, 6), NonceRange::new(1, 4)));
+	// a fixed nonce, against itself and others
+	assert!(collide(NonceRange::new(7, 0), NonceRange::new(7, 0)));
+	assert!(!collide(NonceRange::new(7, 0), NonceRange::new(8, 0)));
+	assert!(collide(NonceRange::new(7, 0), NonceRange::new(1, 3)));
+	assert!(!collide(NonceRange::new(7, 0), NonceRange::new(0, 2)));
+	// huge strides, whose shared nonces are far apart
+	let big = u64::max_value();
+	assert!(!collide(NonceRange::new(0, big), NonceRange::new(1, big - 2)));
+	assert!(collide(NonceRange::new(0, big), NonceRange::new(1, big - 1)));
+	assert!(collide(NonceRange::new(big, big), NonceRange::new(big, 1)));
+}
+
+#[test]
+fn nonces_wrap_around() {
+	let nonces: Vec<u64> = NonceGenerator::new(u64::max_value() - 1, NonceRange::default())
+		.take(4)
+		.collect();
+	assert!(nonces == vec![u64::max_value() - 1, u64::max_value(), 0, 1]);
+
+	let mut g = NonceGenerator::new(0, NonceRange::new(u64::max_value(), 2));
+	assert!(g.last_nonce().is_none());
+	assert!(g.next() == Some(u64::max_value()));
+	assert!(g.next() == Some(1));
+	assert!(g.position() == 2);
+	assert!(g.last_nonce() == Some(1));
+}
+
+#[test]
+fn extranonce_prefix() {
+	// two rigs for the same pool worker, each with its own extranonce
+	let generators = (0..2)
+		.map(|i| NonceGenerator::new(u64::max_value() - 10, NonceRange::default())
+			.with_prefix(0xa0 | i, 8)
+			.unwrap())
+		.collect();
+	let seen = all_nonces(generators);
+	assert!(seen.iter().all(|n| n >> 56 == 0xa0 || n >> 56 == 0xa1));
+
+	// the lower bits wrap around without touching the prefix
+	let nonces: Vec<u64> = NonceGenerator::new(u64::max_value(), NonceRange::default())
+		.with_prefix(0xa0, 8)
+		.unwrap()
+		.take(2)
+		.collect();
+	assert!(nonces == vec![0xa0ff_ffff_ffff_ffff, 0xa000_0000_0000_0000]);
+
+	for &(prefix, bits) in &[(0x100, 8), (1, 0), (0, 64)] {
+		match NonceGenerator::new(0, NonceRange::default()).with_prefix(prefix, bits) {
+			Err(CuckooMinerError::ParameterError(_)) => {},
+			r => panic!("Expected ParameterError for {:#x}/{}, got {:?}", prefix, bits, r.map(|_| ())),
+		}
+	}
+}
+
+#[test]
+fn limited_generators_stop() {
+	let mut g = NonceGenerator::new(10, NonceRange::new(0, 2)).with_limit(3);
+	assert!(g.remaining() == Some(3));
+	assert!(g.by_ref().collect::<Vec<u64>>() == vec![10, 12, 14]);
+	assert!(g.remaining() == Some(0) && g.position() == 3 && g.last_nonce() == Some(14));
+
+	// starting over keeps the prefix, but not the position or limit
+	let g = NonceGenerator::new(0, NonceRange::default()).with_prefix(0xa0, 8).unwrap().with_limit(1);
+	let mut r = g.restart(5, NonceRange::new(1, 3));
+	assert!(r.remaining().is_none() && r.start() == 5 && r.range() == NonceRange::new(1, 3));
+	assert!(r.next() == Some(0xa000_0000_0000_0006));
+}
+
+#[test]
+fn mining_jobs_share_out_nonces() {
+	let generators: Vec<NonceGenerator> = (0..3)
+		.map(|i| NonceGenerator::new(0, NonceRange::interleaved(i, 3)).with_prefix(0xa0, 8).unwrap())
+		.collect();
+	let job = MiningJob::new(1, &[], &[])
+		.start_nonce(100)
+		.nonce_range(NonceRange::new(1, 2))
+		.max_nonces(10);
+	let shares = job.nonce_generators(&generators);
+	assert!(shares.iter().map(|g| g.remaining().unwrap()).collect::<Vec<u64>>() == vec![4, 3, 3]);
+	let seen = all_nonces(shares);
+	let expected: HashSet<u64> = (0..10).map(|i| 0xa000_0000_0000_0000 | (100 + 1 + 2 * i)).collect();
+	assert!(seen == expected);
+
+	// a job which doesn't set the nonces leaves the plugins' ranges
+	let job = MiningJob::new(2, &[], &[]);
+	assert!(!job.sets_nonces());
+	let same = job.nonce_generators(&generators);
+	assert!(same.iter().zip(&generators).all(|(a, b)| a.start() == b.start() && a.range() == b.range()));
+
+	// fewer nonces than plugins leaves some with none
+	let job = MiningJob::new(3, &[], &[]).max_nonces(1);
+	assert!(job.nonce_generators(&generators)[2].remaining() == Some(0));
+}
+
+const ENCODINGS: &[NonceEncoding] = &[
+	NonceEncoding::BigEndianU64,
+	NonceEncoding::LittleEndianU64,
+	NonceEncoding::RawBytes,
+];
+
+#[test]
+fn encodings_round_trip() {
+	for &encoding in ENCODINGS {
+		for &nonce in &[0, 1, 56, 0x0102_0304_0506_0708, u64::max_value()] {
+			assert_eq!(encoding.decode(&encoding.encode(nonce)), nonce, "{:?}", encoding);
+			let mut s = CuckooMinerSolution::new();
+			s.set_nonce_bytes_as(&encoding.encode(nonce), encoding);
+			assert_eq!(s.nonce, nonce);
+			assert_eq!(s.nonce_bytes_as(encoding), encoding.encode(nonce));
+		}
+	}
+	let nonce = 0x0102_0304_0506_0708;
+	assert_eq!(NonceEncoding::BigEndianU64.encode(nonce), [1, 2, 3, 4, 5, 6, 7, 8]);
+	assert_eq!(NonceEncoding::LittleEndianU64.encode(nonce), [8, 7, 6, 5, 4, 3, 2, 1]);
+	let raw = nonce.to_ne_bytes();
+	assert_eq!(NonceEncoding::RawBytes.encode(nonce), raw);
+	assert_eq!(NonceEncoding::default(), NonceEncoding::BigEndianU64);
+}
+
+#[test]
+fn encodings_parse() {
+	assert_eq!("big_endian_u64".parse::<NonceEncoding>().unwrap(), NonceEncoding::BigEndianU64);
+	assert_eq!("little_endian_u64".parse::<NonceEncoding>().unwrap(), NonceEncoding::LittleEndianU64);
+	assert_eq!("raw_bytes".parse::<NonceEncoding>().unwrap(), NonceEncoding::RawBytes);
+	assert!("BigEndianU64".parse::<NonceEncoding>().is_err());
+}
+
+#[test]
+fn encoded_header_hashes() {
+	// fixed vectors, so the bytes hashed for each encoding can't drift
+	let pre = from_hex("0000000000000045").unwrap();
+	let post = from_hex("00000000000000000000000000000000").unwrap();
+	let nonce = 0x0102_0304_0506_0708;
+	let big = encoded_header_hash(&pre, &post, nonce, NonceEncoding::BigEndianU64);
+	let little = encoded_header_hash(&pre, &post, nonce, NonceEncoding::LittleEndianU64);
+	assert_eq!(to_hex(&big), "eeb9d95377c4aecb3d9991230b971f446d8e61f86e3dc8187ceb3e81d03edb62");
+	assert_eq!(to_hex(&little), "87734c7f0a66d3521fef2ce623a6eadcf99d971f5ea456a5f833cf05a68b0ed1");
+	assert_eq!(big, header_hash(&pre, &post, nonce));
+	let raw = encoded_header_hash(&pre, &post, nonce, NonceEncoding::RawBytes);
+	if cfg!(target_endian = "little") {
+		assert_eq!(raw, little);
+	} else {
+		assert_eq!(raw, big);
+	}
+}
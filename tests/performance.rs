@@ -15,6 +15,8 @@
 
 //! Performance-related tests go here
 
+extern crate cuckoo_miner as cuckoo;
+
 pub mod common;
 
 //Test for profiling
@@ -44,3 +46,41 @@ fn perf_mean_30_compare() {
 	common::mine_sync_for_duration(plugin_path_vec[0].clone(), 20, Some(params.clone()));
 	common::mine_async_for_duration(plugin_path_vec, 20, Some(params.clone()));
 }
+
+//Benchmarks a plugin in both modes, which should each time every graph
+//and leave the plugin idle for the next
+#[test]
+fn on_commit_benchmark() {
+	let caps = common::get_plugin_vec("lean_cpu_16");
+	let mut config = cuckoo::CuckooMinerConfig::new();
+	config.plugin_full_path = caps[0].full_path.clone();
+	let miner = cuckoo::CuckooMiner::new(vec![config]).unwrap();
+	for mode in &[cuckoo::BenchmarkMode::Sync, cuckoo::BenchmarkMode::Async] {
+		let report = miner.benchmark(0, 5, 0, *mode).unwrap();
+		println!("{:?}", report);
+		assert!(report.graphs == 5);
+		assert!(report.min_graph_time <= report.median_graph_time);
+		assert!(report.median_graph_time <= report.max_graph_time);
+	}
+}
+
+//Tunes the thread count of a plugin, which should pick one of the
+//candidates in range and leave the plugin set to it
+#[test]
+fn on_commit_autotune() {
+	let caps = common::get_plugin_vec("lean_cpu_16");
+	let mut config = cuckoo::CuckooMinerConfig::new();
+	config.plugin_full_path = caps[0].full_path.clone();
+	let mut miner = cuckoo::CuckooMiner::new(vec![config]).unwrap();
+	let options = cuckoo::TuningOptions {
+		graphs: 3,
+		..cuckoo::TuningOptions::default()
+	};
+	let result = miner.autotune(0, &[("NUM_THREADS", &[1, 2, 4])], &options).unwrap();
+	println!("{:?}", result);
+	assert!(result.points.len() == 3);
+	let (ref name, threads) = result.best[0];
+	assert!(name == "NUM_THREADS");
+	assert!([1, 2, 4].contains(&threads));
+	assert!(miner.configs[0].parameter_list.contains(&(String::from("NUM_THREADS"), 0, threads)));
+}
@@ -18,21 +18,39 @@
 extern crate rand;
 extern crate cuckoo_miner as cuckoo;
 
+#[cfg(feature = "real-plugin-tests")]
 use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::{thread, time};
 use std::time::Instant;
 
 use cuckoo::CuckooMinerError;
+use cuckoo::from_hex;
 use cuckoo::PluginLibrary;
+use cuckoo::PLUGIN_API_VERSION;
+use cuckoo::ALL_DEVICES;
+use cuckoo::CALL_REFUSED;
+use cuckoo::{JOB_HEADER_SIZE, JOB_RECORD_SIZE, pack_jobs};
+use cuckoo::{CALL_CANCELLED, CANCEL_BOUND};
+use cuckoo::PluginState;
+use cuckoo::GraphType;
 
 pub mod common;
 
 use common::{
+	load_mock_plugin,
 	KNOWN_30_HASH_1,
-	KNOWN_16_HASH_1};
+	KNOWN_16_HASH_1,
+	KNOWN_16_HASH_12,
+	KNOWN_16_SOLUTION_12,
+	MOCK_40_HASH,
+	MOCK_40_SOLUTION};
 
+#[cfg(feature = "real-plugin-tests")]
 static DLL_SUFFIX: &str = ".cuckooplugin";
 
+#[cfg(feature = "real-plugin-tests")]
 const TEST_PLUGIN_LIBS_CORE : [&str;6] = [
 	"lean_cpu_16",
 	"lean_cpu_30",
@@ -42,24 +60,13 @@ const TEST_PLUGIN_LIBS_CORE : [&str;6] = [
 	"mean_compat_cpu_30"
 ];
 
+#[cfg(feature = "real-plugin-tests")]
 const TEST_PLUGIN_LIBS_OPTIONAL : [&str;1] = [
 	"lean_cuda_30",
 ];
 
-//Helper to convert from hex string
-fn from_hex_string(in_str: &str) -> Vec<u8> {
-	let mut bytes = Vec::new();
-	for i in 0..(in_str.len() / 2) {
-		let res = u8::from_str_radix(&in_str[2 * i..2 * i + 2], 16);
-		match res {
-			Ok(v) => bytes.push(v),
-			Err(e) => println!("Problem with hex: {}", e),
-		}
-	}
-	bytes
-}
-
 //Helper to load a plugin library
+#[cfg(feature = "real-plugin-tests")]
 fn load_plugin_lib(plugin:&str) -> Result<PluginLibrary, CuckooMinerError> {
 	let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
 	d.push(format!("target/debug/plugins/{}{}", plugin, DLL_SUFFIX).as_str());
@@ -67,6 +74,7 @@ fn load_plugin_lib(plugin:&str) -> Result<PluginLibrary, CuckooMinerError> {
 }
 
 //Helper to load all plugin libraries specified above
+#[cfg(feature = "real-plugin-tests")]
 fn load_all_plugins() -> Vec<PluginLibrary>{
 	let mut plugin_libs:Vec<PluginLibrary> = Vec::new();
 	for p in TEST_PLUGIN_LIBS_CORE.into_iter(){
@@ -81,13 +89,102 @@ fn load_all_plugins() -> Vec<PluginLibrary>{
 	plugin_libs
 }
 
+//Helpers to pick tests by what a plugin reports it can do, rather than
+//by its name
+fn is_cuckoo_16(pl: &PluginLibrary) -> bool {
+	pl.capabilities().unwrap().edge_bits == Some(15)
+}
+
+fn is_cuda(pl: &PluginLibrary) -> bool {
+	pl.capabilities().unwrap().is_cuda
+}
+
+//Helper to get a hash known to have a solution at the plugin's size. The
+//mock plugin has the one at cuckoo 16.
+fn known_hash(pl: &PluginLibrary) -> Vec<u8> {
+	if pl.capabilities().unwrap().edge_bits == Some(29) {
+		from_hex(KNOWN_30_HASH_1).unwrap()
+	} else {
+		from_hex(KNOWN_16_HASH_1).unwrap()
+	}
+}
+
+//Shared libraries known to exist on common linux installs, which
+//aren't plugins
+const NON_PLUGIN_LIBS : [&str;3] = [
+	"/lib/x86_64-linux-gnu/libm.so.6",
+	"/lib64/libm.so.6",
+	"/usr/lib/libm.so.6",
+];
+
+//checks each kind of load failure is reported distinctly
+#[test]
+fn plugin_loading_errors(){
+	match PluginLibrary::new("/nonexistent/plugin/lean_cpu_16.cuckooplugin") {
+		Err(CuckooMinerError::PluginNotFoundError(_)) => {},
+		r => panic!("Expected PluginNotFoundError, got {:?}", r.err()),
+	}
+
+	let mut junk = std::env::temp_dir();
+	junk.push("cuckoo_junk_plugin.cuckooplugin");
+	std::fs::write(&junk, b"not a shared library").unwrap();
+	match PluginLibrary::new(junk.to_str().unwrap()) {
+		Err(CuckooMinerError::PluginIOError(_)) => {},
+		r => panic!("Expected PluginIOError, got {:?}", r.err()),
+	}
+	std::fs::remove_file(&junk).unwrap();
+
+	for l in NON_PLUGIN_LIBS.iter() {
+		if !std::path::Path::new(l).exists() {
+			continue;
+		}
+		match PluginLibrary::load(l, false) {
+			Err(CuckooMinerError::PluginApiMismatch{expected, found}) => {
+				assert!(expected == PLUGIN_API_VERSION >> 16);
+				assert!(found == 0);
+			},
+			r => panic!("Expected PluginApiMismatch, got {:?}", r.err()),
+		}
+		match PluginLibrary::new(l) {
+			Err(CuckooMinerError::PluginSymbolMissing{symbol, ..}) => {
+				assert!(symbol == "cuckoo_init");
+			},
+			r => panic!("Expected PluginSymbolMissing, got {:?}", r.err()),
+		}
+	}
+}
+
+//loads plugins and drops them mid-processing many times, without
+//stopping them first. Drop should stop, reset and unload cleanly.
+#[cfg(feature = "real-plugin-tests")]
+#[test]
+fn on_commit_plugin_drop_while_processing(){
+	for _ in 0..100 {
+		for p in TEST_PLUGIN_LIBS_CORE.into_iter() {
+			let pl = load_plugin_lib(p).unwrap();
+			let header = known_hash(&pl);
+			let nonce:[u8;8]=[0;8];
+			pl.push_to_input_queue(0, &header, &nonce).unwrap();
+			pl.start_processing().unwrap();
+			thread::sleep(time::Duration::from_millis(10));
+			//and a double unload is harmless
+			if p.contains("mean") {
+				pl.unload();
+				pl.unload();
+			}
+		}
+	}
+}
+
 //loads and unloads a plugin many times
+#[cfg(feature = "real-plugin-tests")]
 #[test]
 fn on_commit_plugin_loading(){
 	//core plugins should be built on all systems, fail if they don't exist
 	for _ in 0..100 {
 		for p in TEST_PLUGIN_LIBS_CORE.into_iter() {
 			let pl = load_plugin_lib(p).unwrap();
+			assert!(pl.api_version() >> 16 == PLUGIN_API_VERSION >> 16);
 			pl.unload();
 		}
 	}
@@ -104,6 +201,7 @@ fn on_commit_plugin_loading(){
 }
 
 //Loads all plugins at once
+#[cfg(feature = "real-plugin-tests")]
 #[test]
 fn on_commit_plugin_multiple_loading(){
 	let _p=load_all_plugins();
@@ -111,6 +209,7 @@ fn on_commit_plugin_multiple_loading(){
 
 //tests cuckoo_init() on all available plugins
 //multiple calls to cuckoo init should be fine
+#[cfg(feature = "real-plugin-tests")]
 #[test]
 fn on_commit_cuckoo_init(){
 	let iterations = 100;
@@ -122,6 +221,27 @@ fn on_commit_cuckoo_init(){
 	}
 }
 
+// Helper to test description(), buffer sizing and null
+// stripping should be handled internally
+
+fn description_tests(pl: &PluginLibrary){
+	let desc = pl.description().unwrap();
+	println!("Plugin: {} - {}: {}", pl.lib_full_path, desc.name, desc.description);
+	assert!(desc.name.len() > 0);
+	assert!(!desc.name.contains('\0'));
+	assert!(!desc.description.contains('\0'));
+}
+
+//tests description() on all available plugins
+#[cfg(feature = "real-plugin-tests")]
+#[test]
+fn on_commit_cuckoo_description(){
+	let plugins = load_all_plugins();
+	for p in plugins.into_iter() {
+		description_tests(&p);
+	}
+}
+
 // Helper to test call_cuckoo_parameter_list and return results
 // Ensures that all plugins *probably* don't overwrite
 // their buffers as they contain an null zero somewhere 
@@ -129,7 +249,7 @@ fn on_commit_cuckoo_init(){
 
 fn call_cuckoo_parameter_list_tests(pl: &PluginLibrary){
 	///Test normal rust-enforced value
-	const LENGTH:usize = 1024;
+	const LENGTH:usize = 4096;
 	let mut param_list_bytes:[u8;LENGTH]=[0;LENGTH];
 	let mut param_list_bytes_len=param_list_bytes.len() as u32;
 	let ret_val=pl.call_cuckoo_parameter_list(&mut param_list_bytes,
@@ -161,6 +281,7 @@ fn call_cuckoo_parameter_list_tests(pl: &PluginLibrary){
 }
 
 //tests call_cuckoo_parameter_list() on all available plugins
+#[cfg(feature = "real-plugin-tests")]
 #[test]
 fn on_commit_cuckoo_parameter_list(){
 	let iterations = 100;
@@ -172,6 +293,29 @@ fn on_commit_cuckoo_parameter_list(){
 	}
 }
 
+// Helper to test parameters(), which should parse the same
+// list into typed values
+
+fn parameters_tests(pl: &PluginLibrary){
+	let params = pl.parameters().unwrap();
+	println!("Plugin: {} - {:?}", pl.lib_full_path, params);
+	let num_threads = params.iter().find(|p| p.name == "NUM_THREADS");
+	assert!(num_threads.is_some());
+	let num_threads = num_threads.unwrap();
+	assert!(num_threads.min_value <= num_threads.default_value);
+	assert!(num_threads.default_value <= num_threads.max_value);
+}
+
+//tests parameters() on all available plugins
+#[cfg(feature = "real-plugin-tests")]
+#[test]
+fn on_commit_cuckoo_parameters(){
+	let plugins = load_all_plugins();
+	for p in plugins.into_iter() {
+		parameters_tests(&p);
+	}
+}
+
 // Helper to test call_cuckoo_get_parameter and return results
 // Ensures that all plugins *probably* don't overwrite
 // their buffers as they contain an null zero somewhere 
@@ -202,6 +346,7 @@ fn call_cuckoo_get_parameter_tests(pl: &PluginLibrary){
 }
 
 //tests call_cuckoo_get_parameter() on all available plugins
+#[cfg(feature = "real-plugin-tests")]
 #[test]
 fn on_commit_cuckoo_get_parameter(){
 	let iterations = 100;
@@ -250,6 +395,7 @@ fn call_cuckoo_set_parameter_tests(pl: &PluginLibrary){
 }
 
 //tests call_cuckoo_get_parameter() on all available plugins
+#[cfg(feature = "real-plugin-tests")]
 #[test]
 fn on_commit_cuckoo_set_parameter(){
 	let iterations = 100;
@@ -261,6 +407,120 @@ fn on_commit_cuckoo_set_parameter(){
 	}
 }
 
+// Helper to test the typed get_parameter/set_parameter wrappers,
+// which should turn the raw codes above into specific errors
+
+fn typed_parameter_tests(pl: &PluginLibrary){
+	println!("Plugin: {}", pl.lib_full_path);
+	pl.set_parameter("NUM_THREADS", 8).unwrap();
+	assert!(pl.get_parameter("NUM_THREADS").unwrap() == 8);
+
+	match pl.set_parameter("NUM_THREADS", 99999999) {
+		Err(CuckooMinerError::ParameterRangeError(_)) => {},
+		r => panic!("Expected ParameterRangeError, got {:?}", r),
+	}
+
+	match pl.set_parameter("SANDWICHES", 8) {
+		Err(CuckooMinerError::ParameterNotFoundError(_)) => {},
+		r => panic!("Expected ParameterNotFoundError, got {:?}", r),
+	}
+
+	let name = "SANDWICHESSANDWICHESSANDWICHESSANDWICHESSANDWICHESSANDWICHESANDWICHESSAES";
+	match pl.get_parameter(name) {
+		Err(CuckooMinerError::ParameterNameTooLongError(_)) => {},
+		r => panic!("Expected ParameterNameTooLongError, got {:?}", r),
+	}
+}
+
+//tests the typed parameter wrappers on all available plugins
+#[cfg(feature = "real-plugin-tests")]
+#[test]
+fn on_commit_typed_parameter_errors(){
+	let plugins = load_all_plugins();
+	for p in plugins.into_iter() {
+		typed_parameter_tests(&p);
+	}
+}
+
+//Helper to test parameter values are validated against the
+//plugin's declared range before reaching the plugin
+fn checked_parameter_tests(pl: &PluginLibrary){
+	println!("Plugin: {}", pl.lib_full_path);
+	pl.set_parameter("NUM_THREADS", 2).unwrap();
+	match pl.set_parameter_checked("NUM_THREADS", 99999999) {
+		Err(CuckooMinerError::ParameterOutOfRange{name, value, min, max}) => {
+			assert!(name == "NUM_THREADS");
+			assert!(value == 99999999);
+			assert!(min <= max && max < value);
+		},
+		r => panic!("Expected ParameterOutOfRange, got {:?}", r),
+	}
+	//the plugin never saw the bad value
+	assert!(pl.get_parameter("NUM_THREADS").unwrap() == 2);
+
+	pl.set_parameter_checked("NUM_THREADS", 1).unwrap();
+	assert!(pl.get_parameter("NUM_THREADS").unwrap() == 1);
+
+	match pl.set_parameter_checked("SANDWICHES", 8) {
+		Err(CuckooMinerError::ParameterNotFoundError(_)) => {},
+		r => panic!("Expected ParameterNotFoundError, got {:?}", r),
+	}
+}
+
+//tests set_parameter_checked on all available plugins
+#[cfg(feature = "real-plugin-tests")]
+#[test]
+fn on_commit_checked_parameters(){
+	let plugins = load_all_plugins();
+	for p in plugins.into_iter() {
+		checked_parameter_tests(&p);
+	}
+}
+
+//Helper to test 64-bit parameters, on plugins with and without the
+//64-bit calls
+fn u64_parameter_tests(pl: &PluginLibrary){
+	println!("Plugin: {}", pl.lib_full_path);
+	match pl.get_parameter_u64("NUM_THREADS") {
+		Err(CuckooMinerError::NotSupported(_)) => {
+			println!("No 64-bit parameter calls");
+			match pl.set_parameter_u64("NUM_THREADS", 1) {
+				Err(CuckooMinerError::NotSupported(_)) => {},
+				r => panic!("Expected NotSupported, got {:?}", r),
+			}
+			//the 32-bit calls are unaffected
+			pl.set_parameter("NUM_THREADS", 1).unwrap();
+			assert!(pl.get_parameter("NUM_THREADS").unwrap() == 1);
+			return;
+		},
+		r => {
+			r.unwrap();
+		},
+	}
+	let big = (4u64 << 30) + 1;
+	let params = pl.parameters().unwrap();
+	let param = match params.iter().find(|p| p.is_64_bit() && p.min_value <= big && big <= p.max_value) {
+		Some(p) => p,
+		None => {
+			println!("No parameter takes values above 4GiB");
+			return;
+		},
+	};
+	pl.set_parameter_checked(&param.name, big).unwrap();
+	assert!(pl.get_parameter_checked(&param.name).unwrap() == big);
+	assert!(pl.get_parameter_u64(&param.name).unwrap() == big);
+}
+
+//tests 64-bit parameters on all available plugins
+#[cfg(feature = "real-plugin-tests")]
+#[test]
+fn on_commit_u64_parameters(){
+	let plugins = load_all_plugins();
+	for p in plugins.into_iter() {
+		u64_parameter_tests(&p);
+	}
+}
+
 // Helper to test cuckoo_call
 // at this level, given the time involved we're just going to
 // do a sanity check that the same known hash will indeed give
@@ -270,11 +530,7 @@ fn cuckoo_call_tests(pl: &PluginLibrary){
 	println!("Plugin: {}", pl.lib_full_path);
 
 	//Known Hash
-	let mut header = from_hex_string(KNOWN_30_HASH_1);
-	//or 16, if needed
-	if pl.lib_full_path.contains("16") {
-		header = from_hex_string(KNOWN_16_HASH_1);
-	}
+	let header = known_hash(pl);
 
 	let mut solution:[u32; 42] = [0;42];
 	let mut size = 0;
@@ -289,6 +545,7 @@ fn cuckoo_call_tests(pl: &PluginLibrary){
 }
 
 //tests cuckoo_call() on all available plugins
+#[cfg(feature = "real-plugin-tests")]
 #[test]
 fn on_commit_cuckoo_call(){
 	let iterations = 1;
@@ -296,7 +553,7 @@ fn on_commit_cuckoo_call(){
 	for p in plugins.into_iter() {
 		for _ in 0..iterations {
 			//Only do 16
-			if p.lib_full_path.contains("16"){
+			if is_cuckoo_16(&p){
 				cuckoo_call_tests(&p);
 			}
 		}
@@ -322,11 +579,8 @@ fn call_cuckoo_start_processing_tests(pl: &PluginLibrary){
 	let wait_time = time::Duration::from_millis(25);
 
 	thread::sleep(wait_time);
-	pl.call_cuckoo_stop_processing();
-
-	//wait for internal processing to finish
-	while pl.call_cuckoo_has_processing_stopped()==0{};
-	pl.call_cuckoo_reset_processing();
+	//stop and wait for internal processing to finish
+	pl.stop_and_wait(time::Duration::from_secs(60)).unwrap();
 
 	println!("{}",ret_val);
 	assert!(ret_val==0);
@@ -334,6 +588,7 @@ fn call_cuckoo_start_processing_tests(pl: &PluginLibrary){
 
 //tests call_cuckoo_start_processing 
 //on all available plugins
+#[cfg(feature = "real-plugin-tests")]
 #[test]
 fn on_commit_call_cuckoo_start_processing(){
 	let iterations = 10;
@@ -351,7 +606,7 @@ fn call_cuckoo_push_to_input_queue_tests(pl: &PluginLibrary){
 	println!("Plugin: {}", pl.lib_full_path);
 
 	//hash too long
-	let hash:[u8;42]=[0;42];
+	let hash=vec![0u8;300];
 	let nonce:[u8;8]=[0;8];
 	println!("HASH LEN {}", hash.len());
 	let result=pl.call_cuckoo_push_to_input_queue(0, &hash, &nonce);
@@ -364,14 +619,13 @@ fn call_cuckoo_push_to_input_queue_tests(pl: &PluginLibrary){
 	let result=pl.call_cuckoo_push_to_input_queue(1, &hash, &nonce);
 	assert!(result==0);
 
-	//push until queue is full
-	for i in 0..10000 {
+	//push until queue is full, which it should be within its capacity
+	let capacity = pl.input_queue_capacity().unwrap_or(10000);
+	for i in 0..capacity {
 		let result=pl.call_cuckoo_push_to_input_queue(i+2, &hash, &nonce);
 		if result==1 {
 			break;
 		}
-		//Should have been full long before now
-		assert!(i!=10000);
 	}
 
 	//should be full
@@ -379,13 +633,13 @@ fn call_cuckoo_push_to_input_queue_tests(pl: &PluginLibrary){
 	assert!(result==1);
 
 	//only do this on smaller test cuckoo, or we'll be here forever
-	if pl.lib_full_path.contains("16"){
+	if is_cuckoo_16(pl){
 		pl.call_cuckoo_start_processing();
 		let wait_time = time::Duration::from_millis(100);
 		thread::sleep(wait_time);
 		pl.call_cuckoo_stop_processing();
 		//wait for internal processing to finish
-		while pl.call_cuckoo_has_processing_stopped()==0{};
+		pl.wait_for_processing_stopped(time::Duration::from_secs(60)).unwrap();
 	}
 
 	//Clear queues and reset internal 'should_quit' flag
@@ -395,6 +649,7 @@ fn call_cuckoo_push_to_input_queue_tests(pl: &PluginLibrary){
 
 //tests call_cuckoo_push_to_input_queue
 //on all available plugins
+#[cfg(feature = "real-plugin-tests")]
 #[test]
 fn on_commit_call_cuckoo_push_to_input_queue(){
 	let iterations = 10;
@@ -406,6 +661,40 @@ fn on_commit_call_cuckoo_push_to_input_queue(){
 	}
 }
 
+// Helper to test that the input queue accepts exactly as many headers as
+// its configured capacity
+fn queue_capacity_tests(pl: &PluginLibrary){
+	println!("Plugin: {}", pl.lib_full_path);
+	if pl.input_queue_capacity().is_err() {
+		println!("No queue capacity reported, skipping");
+		return;
+	}
+	let hash:[u8;32]=[0;32];
+	let nonce:[u8;8]=[0;8];
+	for capacity in vec![1, 7, 64] {
+		pl.set_input_queue_capacity(capacity).unwrap();
+		assert!(pl.input_queue_capacity().unwrap() == capacity);
+		for i in 0..capacity {
+			pl.push_to_input_queue(i, &hash, &nonce).unwrap();
+		}
+		match pl.push_to_input_queue(capacity, &hash, &nonce) {
+			Err(CuckooMinerError::QueueFull { depth, .. }) => assert!(depth == Some(capacity)),
+			r => panic!("Expected QueueFull, got {:?}", r),
+		}
+		pl.call_cuckoo_clear_queues();
+	}
+}
+
+//tests input queue capacities on all available plugins
+#[cfg(feature = "real-plugin-tests")]
+#[test]
+fn on_commit_queue_capacity(){
+	let plugins = load_all_plugins();
+	for p in plugins.into_iter() {
+		queue_capacity_tests(&p);
+	}
+}
+
 // Helper to test call_cuckoo_stop_processing
 // basically, when a plugin is told to shut down,
 // it should immediately stop its processing,
@@ -437,33 +726,31 @@ fn call_cuckoo_stop_processing_tests(pl: &PluginLibrary){
 
 	let start=Instant::now();
 
-	//Now stop
-	pl.call_cuckoo_stop_processing();
-
-	//wait for internal processing to finish
-	while pl.call_cuckoo_has_processing_stopped()==0{};
-	pl.call_cuckoo_reset_processing();
+	//Now stop, and wait for internal processing to finish
+	//will give each plugin a few seconds for now
+	//but give cuda libs a pass for now, as they're hard to stop
+	let result = if is_cuda(pl) {
+		pl.stop_and_wait(time::Duration::from_secs(60))
+	} else {
+		pl.stop_and_wait(time::Duration::from_millis(5000))
+	};
 
 	let elapsed=start.elapsed();
 	let elapsed_ms=(elapsed.as_secs() * 1_000) + (elapsed.subsec_nanos() / 1_000_000) as u64;
 	println!("Shutdown elapsed_ms: {}",elapsed_ms);
-
-	//will give each plugin a few seconds for now
-	//but give cuda libs a pass for now, as they're hard to stop
-	if !pl.lib_full_path.contains("cuda"){
-		//assert!(elapsed_ms<=5000);
-	}
+	result.unwrap();
 }
 
 //tests call_cuckoo_stop_processing
 //on all available plugins
+#[cfg(feature = "real-plugin-tests")]
 #[test]
 fn on_commit_call_cuckoo_stop_processing(){
 	let iterations = 1;
 	let plugins = load_all_plugins();
 	for p in plugins.into_iter() {
 		for _ in 0..iterations {
-			if p.lib_full_path.contains("16"){
+			if is_cuckoo_16(&p){
 				call_cuckoo_stop_processing_tests(&p);
 			}
 		}
@@ -481,11 +768,7 @@ fn call_cuckoo_read_from_output_queue_tests(pl: &PluginLibrary){
 	println!("Plugin: {}", pl.lib_full_path);
 
 	//Known Hash
-	let mut header = from_hex_string(KNOWN_30_HASH_1);
-	//or 16, if needed
-	if pl.lib_full_path.contains("16") {
-		header = from_hex_string(KNOWN_16_HASH_1);
-	}
+	let header = known_hash(pl);
 	//Just zero nonce here, for ID
 	let nonce:[u8;8]=[0;8];
 	let result=pl.call_cuckoo_push_to_input_queue(0, &header, &nonce);
@@ -515,34 +798,29 @@ fn call_cuckoo_read_from_output_queue_tests(pl: &PluginLibrary){
 		let elapsed_ms=(elapsed.as_secs() * 1_000) + (elapsed.subsec_nanos() / 1_000_000) as u64;
 		if elapsed_ms > max_time_ms{
 		//stop
-			pl.call_cuckoo_stop_processing();
-
-			while pl.call_cuckoo_has_processing_stopped()==0{};
-			pl.call_cuckoo_reset_processing();
+			pl.stop_and_wait(time::Duration::from_secs(60)).unwrap();
 			//cry about it
 			panic!("Known solution not found");
 		}
 	}
 	
 	//now stop
-	pl.call_cuckoo_stop_processing();
-
-	//wait for internal processing to finish
-	while pl.call_cuckoo_has_processing_stopped()==0{};
-	pl.call_cuckoo_reset_processing();
+	//stop and wait for internal processing to finish
+	pl.stop_and_wait(time::Duration::from_secs(60)).unwrap();
 	
 }
 
 //tests call_cuckoo_read_from_output_queue() on all available
 //plugins
 
+#[cfg(feature = "real-plugin-tests")]
 #[test]
 fn on_commit_call_cuckoo_read_from_output_queue(){
 	let iterations = 1;
 	let plugins = load_all_plugins();
 	for p in plugins.into_iter() {
 		for _ in 0..iterations {
-			if p.lib_full_path.contains("16"){
+			if is_cuckoo_16(&p){
 				call_cuckoo_read_from_output_queue_tests(&p);
 			}
 		}
@@ -551,6 +829,248 @@ fn on_commit_call_cuckoo_read_from_output_queue(){
 	call_cuckoo_read_from_output_queue_tests(&pl);*/
 }
 
+// Helper to test wait_for_solution, which should find the known
+// solution without spinning, then report that processing has stopped
+// once it has
+
+fn wait_for_solution_tests(pl: &PluginLibrary){
+	println!("Plugin: {}", pl.lib_full_path);
+
+	//nothing is processing yet, so there's nothing to wait for
+	match pl.wait_for_solution(time::Duration::from_secs(60)) {
+		Err(CuckooMinerError::ProcessingStopped(_)) => {},
+		r => panic!("Expected ProcessingStopped, got {:?}", r),
+	}
+
+	let header = from_hex(KNOWN_16_HASH_1).unwrap();
+	let nonce:[u8;8]=[0;8];
+	let result=pl.call_cuckoo_push_to_input_queue(7, &header, &nonce);
+	assert!(result==0);
+	let ret_val=pl.call_cuckoo_start_processing();
+	assert!(ret_val==0);
+
+	let solution = pl.wait_for_solution(time::Duration::from_secs(480)).unwrap();
+	let solution = solution.expect("Known solution not found");
+	assert!(solution.id == 7);
+
+	//an empty queue times out while processing
+	let start=Instant::now();
+	assert!(pl.wait_for_solution(time::Duration::from_millis(100)).unwrap().is_none());
+	assert!(start.elapsed() >= time::Duration::from_millis(100));
+
+	pl.call_cuckoo_stop_processing();
+	let start=Instant::now();
+	loop {
+		match pl.wait_for_solution(time::Duration::from_secs(60)) {
+			Ok(Some(_)) => continue,
+			Err(CuckooMinerError::ProcessingStopped(_)) => break,
+			r => panic!("Expected ProcessingStopped, got {:?}", r),
+		}
+	}
+	assert!(start.elapsed() < time::Duration::from_secs(60));
+	pl.call_cuckoo_reset_processing();
+}
+
+#[cfg(feature = "real-plugin-tests")]
+#[test]
+fn on_commit_wait_for_solution(){
+	let plugins = load_all_plugins();
+	for p in plugins.into_iter() {
+		if is_cuckoo_16(&p){
+			wait_for_solution_tests(&p);
+		}
+	}
+}
+
+// Helper to test drain_solutions. Only one header with a known solution
+// is available at this size, so it's pushed as several distinct jobs,
+// each of which should come back from a single drain once processing
+// has stopped
+
+fn drain_solutions_tests(pl: &PluginLibrary){
+	println!("Plugin: {}", pl.lib_full_path);
+	let jobs = 4;
+	let header = from_hex(KNOWN_16_HASH_1).unwrap();
+	for i in 0..jobs {
+		let mut nonce:[u8;8]=[0;8];
+		nonce[7]=i as u8;
+		let result=pl.call_cuckoo_push_to_input_queue(i, &header, &nonce);
+		assert!(result==0);
+	}
+	let ret_val=pl.call_cuckoo_start_processing();
+	assert!(ret_val==0);
+
+	//wait until every job has been searched
+	let start=Instant::now();
+	loop {
+		let completed: u32 = pl.stats().unwrap().iter().map(|s| s.iterations_completed).sum();
+		if completed >= jobs {
+			break;
+		}
+		if start.elapsed() > time::Duration::from_secs(480) {
+			panic!("Jobs not completed");
+		}
+		thread::sleep(time::Duration::from_millis(10));
+	}
+	pl.call_cuckoo_stop_processing();
+	pl.wait_for_processing_stopped(time::Duration::from_secs(60)).unwrap();
+
+	let solutions = pl.drain_solutions(100);
+	assert!(solutions.len() == jobs as usize);
+	for i in 0..jobs {
+		let s = solutions.iter().find(|s| s.id == i).expect("Missing solution");
+		assert!(s.nonce == i as u64);
+		assert!(s.solution_nonces == solutions[0].solution_nonces);
+	}
+	assert!(pl.drain_solutions(100).is_empty());
+
+	pl.call_cuckoo_clear_queues();
+	pl.call_cuckoo_reset_processing();
+}
+
+#[cfg(feature = "real-plugin-tests")]
+#[test]
+fn on_commit_drain_solutions(){
+	let plugins = load_all_plugins();
+	for p in plugins.into_iter() {
+		if is_cuckoo_16(&p){
+			drain_solutions_tests(&p);
+		}
+	}
+}
+
+// Helper to test clearing the input queue on its own. Ten jobs with known
+// solutions are pushed, and once some have been searched the rest are
+// cleared. The solutions already found should still be readable.
+
+fn clear_input_queue_tests(pl: &PluginLibrary){
+	println!("Plugin: {}", pl.lib_full_path);
+	let jobs = 10;
+	let header = from_hex(KNOWN_16_HASH_1).unwrap();
+	for i in 0..jobs {
+		let mut nonce:[u8;8]=[0;8];
+		nonce[7]=i as u8;
+		pl.push_to_input_queue(i, &header, &nonce).unwrap();
+	}
+	pl.start_processing().unwrap();
+
+	//wait until at least one job has been searched
+	let start=Instant::now();
+	let mut completed = 0;
+	while completed == 0 {
+		completed = pl.stats().unwrap().iter().map(|s| s.iterations_completed).sum();
+		if start.elapsed() > time::Duration::from_secs(480) {
+			panic!("Jobs not completed");
+		}
+		thread::sleep(time::Duration::from_millis(10));
+	}
+	match pl.clear_input_queue() {
+		Err(CuckooMinerError::PluginSymbolMissing { .. }) => {
+			println!("Input queue can't be cleared on its own, skipping");
+			pl.stop_and_wait(time::Duration::from_secs(60)).unwrap();
+			pl.call_cuckoo_clear_queues();
+			return;
+		}
+		r => r.unwrap(),
+	}
+	pl.call_cuckoo_stop_processing();
+	pl.wait_for_processing_stopped(time::Duration::from_secs(60)).unwrap();
+
+	//everything searched before the clear has a solution waiting
+	let solutions = pl.drain_solutions(100);
+	println!("{} searched before clearing, {} solutions", completed, solutions.len());
+	assert!(solutions.len() >= completed as usize);
+	assert!(solutions.len() < jobs as usize);
+	for s in &solutions {
+		assert!(s.nonce == s.id as u64);
+	}
+
+	//clearing an empty output queue is fine too
+	pl.clear_output_queue().unwrap();
+	assert!(pl.drain_solutions(100).is_empty());
+
+	pl.call_cuckoo_clear_queues();
+	pl.call_cuckoo_reset_processing();
+}
+
+#[cfg(feature = "real-plugin-tests")]
+#[test]
+fn on_commit_clear_input_queue(){
+	let plugins = load_all_plugins();
+	for p in plugins.into_iter() {
+		if is_cuckoo_16(&p){
+			clear_input_queue_tests(&p);
+		}
+	}
+}
+
+// Helper to test list_devices. CPU plugins report a single device, while
+// GPU plugins may report none on a machine without a GPU
+
+fn list_devices_tests(pl: &PluginLibrary){
+	println!("Plugin: {}", pl.lib_full_path);
+	let devices = pl.list_devices().unwrap();
+	println!("Devices: {:?}", devices);
+	if !is_cuda(pl) {
+		assert!(devices.len() == 1);
+		assert!(devices[0].usable);
+	}
+	//the list is the same each time
+	assert!(pl.list_devices().unwrap() == devices);
+}
+
+#[cfg(feature = "real-plugin-tests")]
+#[test]
+fn on_commit_list_devices(){
+	let plugins = load_all_plugins();
+	for p in plugins.into_iter() {
+		list_devices_tests(&p);
+	}
+}
+
+// Helper to test per-device parameters. CPU plugins have a single
+// device 0, so any other id should be rejected rather than applied to it
+
+fn device_parameter_tests(pl: &PluginLibrary){
+	println!("Plugin: {}", pl.lib_full_path);
+	let devices = pl.list_devices().unwrap();
+	if devices.is_empty() {
+		println!("No devices, skipping");
+		return;
+	}
+	let first = devices[0].device_id;
+	pl.set_parameter_for_device("NUM_THREADS", first, 2).unwrap();
+	assert!(pl.get_parameter_for_device("NUM_THREADS", first).unwrap() == 2);
+
+	pl.set_parameter_for_device("NUM_THREADS", ALL_DEVICES, 1).unwrap();
+	for d in devices.iter() {
+		assert!(pl.get_parameter_for_device("NUM_THREADS", d.device_id).unwrap() == 1);
+	}
+	match pl.get_parameter_for_device("NUM_THREADS", ALL_DEVICES) {
+		Err(CuckooMinerError::DeviceNotFoundError(_)) => {},
+		r => panic!("Expected DeviceNotFoundError, got {:?}", r),
+	}
+
+	let missing = devices.iter().map(|d| d.device_id).max().unwrap() + 1;
+	match pl.set_parameter_for_device("NUM_THREADS", missing, 4) {
+		Err(CuckooMinerError::DeviceNotFoundError(_)) => {},
+		r => panic!("Expected DeviceNotFoundError, got {:?}", r),
+	}
+	//and device 0 is untouched
+	assert!(pl.get_parameter_for_device("NUM_THREADS", first).unwrap() == 1);
+}
+
+#[cfg(feature = "real-plugin-tests")]
+#[test]
+fn on_commit_device_parameters(){
+	let plugins = load_all_plugins();
+	for p in plugins.into_iter() {
+		if !is_cuda(&p){
+			device_parameter_tests(&p);
+		}
+	}
+}
+
 // Helper to test call_cuckoo_get_stats and return results
 // Ensures that all plugins *probably* don't overwrite
 // their buffers as they contain an null zero somewhere 
@@ -590,11 +1110,7 @@ fn call_cuckoo_get_stats_test(pl: &PluginLibrary){
 
 	//Now start up processing and check values
 	//Known Hash
-	let mut header = from_hex_string(KNOWN_30_HASH_1);
-	//or 16, if needed
-	if pl.lib_full_path.contains("16") {
-		header = from_hex_string(KNOWN_16_HASH_1);
-	}
+	let header = known_hash(pl);
 	//Just zero nonce here, for ID
 	let nonce:[u8;8]=[0;8];
 	let result=pl.call_cuckoo_push_to_input_queue(0, &header, &nonce);
@@ -624,21 +1140,19 @@ fn call_cuckoo_get_stats_test(pl: &PluginLibrary){
 	println!("Stats after starting: {}", result_list);
 
 	//now stop
-	pl.call_cuckoo_stop_processing();
-
-	//wait for internal processing to finish
-	while pl.call_cuckoo_has_processing_stopped()==0{};
-	pl.call_cuckoo_reset_processing();
+	//stop and wait for internal processing to finish
+	pl.stop_and_wait(time::Duration::from_secs(60)).unwrap();
 }
 
 //tests call_cuckoo_parameter_list() on all available plugins
+#[cfg(feature = "real-plugin-tests")]
 #[test]
 fn on_commit_call_cuckoo_get_stats(){
 	let iterations = 2;
 	let plugins = load_all_plugins();
 	for p in plugins.into_iter() {
 		for _ in 0..iterations {
-			if p.lib_full_path.contains("16"){
+			if is_cuckoo_16(&p){
 				call_cuckoo_get_stats_test(&p);
 			}
 		}
@@ -647,9 +1161,48 @@ fn on_commit_call_cuckoo_get_stats(){
 	call_cuckoo_get_stats_test(&pl);*/
 }
 
+// Helper to test the typed stats wrapper, before and while processing
+fn typed_stats_test(pl: &PluginLibrary){
+	println!("Plugin: {}", pl.lib_full_path);
+	let stats = pl.stats().unwrap();
+	println!("Stats before starting: {:?}", stats);
+
+	let header = known_hash(pl);
+	let nonce:[u8;8]=[0;8];
+	pl.push_to_input_queue(0, &header, &nonce).unwrap();
+	pl.start_processing().unwrap();
+	thread::sleep(time::Duration::from_millis(2000));
+
+	let stats = pl.stats().unwrap();
+	println!("Stats after starting: {:?}", stats);
+	assert!(stats.len() > 0);
+	for s in stats {
+		assert!(s.device_name.len() > 0);
+		assert!(s.error().is_none());
+	}
+
+	pl.call_cuckoo_stop_processing();
+	pl.wait_for_processing_stopped(time::Duration::from_secs(60)).unwrap();
+	pl.call_cuckoo_clear_queues();
+	pl.call_cuckoo_reset_processing();
+}
+
+//tests stats() on all available 16 plugins
+#[cfg(feature = "real-plugin-tests")]
+#[test]
+fn on_commit_typed_stats(){
+	let plugins = load_all_plugins();
+	for p in plugins.into_iter() {
+		if is_cuckoo_16(&p){
+			typed_stats_test(&p);
+		}
+	}
+}
+
 // test specific issues in plugins,
 // for instance exercising parameters, etc 
 // Known to fail hard at moment due to thread cleanup issues in lean_16 
+#[cfg(feature = "real-plugin-tests")]
 #[test]
 fn specific_lean_cpu_16(){
 	let pl = load_plugin_lib("lean_cpu_16").unwrap();
@@ -662,7 +1215,7 @@ fn specific_lean_cpu_16(){
 	assert!(return_value==0);
 
 	//check specific header on 4 threads
-	let known_header = from_hex_string(KNOWN_16_HASH_1);
+	let known_header = from_hex(KNOWN_16_HASH_1).unwrap();
 	let mut size = 0;
 	let return_value=pl.call_cuckoo(&known_header, &mut size, &mut solution);
 	assert!(return_value==1);
@@ -683,6 +1236,7 @@ fn specific_lean_cpu_16(){
 
 // test specific issues in plugins,
 // for instance exercising parameters, etc 
+#[cfg(feature = "real-plugin-tests")]
 #[test]
 fn on_commit_specific_mean_cpu_16(){
 	let pl = load_plugin_lib("mean_cpu_16").unwrap();
@@ -704,7 +1258,669 @@ fn on_commit_specific_mean_cpu_16(){
 		}
 	}
 	//check specific header on 4 threads
-	let known_header = from_hex_string(KNOWN_16_HASH_1);
+	let known_header = from_hex(KNOWN_16_HASH_1).unwrap();
 	let return_value=pl.call_cuckoo(&known_header, &mut size, &mut solution);
 	assert!(return_value==1);
 }
+
+//fails to compile if PluginLibrary can't be shared between threads
+#[test]
+fn plugin_library_is_send_sync(){
+	fn assert_send_sync<T: Send + Sync>() {}
+	assert_send_sync::<PluginLibrary>();
+}
+
+// Helper which shares one plugin between two threads pushing jobs, a
+// third reading solutions and a fourth reading stats, for `run_time`
+
+fn shared_plugin_tests(pl: Arc<PluginLibrary>, run_time: time::Duration){
+	println!("Plugin: {}", pl.lib_full_path);
+	let ret_val=pl.call_cuckoo_start_processing();
+	assert!(ret_val==0);
+
+	let start = Instant::now();
+	let mut pushers = Vec::new();
+	for t in 0..2u8 {
+		let pl = pl.clone();
+		pushers.push(thread::spawn(move || {
+			let mut pushed = 0;
+			let mut nonce:[u8;8]=[0;8];
+			nonce[0]=t;
+			while start.elapsed() < run_time {
+				let mut header:[u8;32] = [0;32];
+				for j in 0..32 {
+					header[j]=rand::random::<u8>();
+				}
+				match pl.call_cuckoo_push_to_input_queue(pushed, &header, &nonce) {
+					0 => pushed += 1,
+					1 => thread::sleep(time::Duration::from_millis(1)),
+					r => panic!("Unexpected push result: {}", r),
+				}
+			}
+			pushed
+		}));
+	}
+	let reader = {
+		let pl = pl.clone();
+		thread::spawn(move || {
+			let mut found = 0;
+			let mut sols:[u32; 42] = [0; 42];
+			let mut nonce:[u8;8]=[0;8];
+			let mut id = 0;
+			let mut size = 0;
+			while start.elapsed() < run_time {
+				if pl.call_cuckoo_read_from_output_queue(&mut id, &mut sols, &mut size, &mut nonce) == 1 {
+					found += 1;
+				}
+			}
+			found
+		})
+	};
+	let stats = {
+		let pl = pl.clone();
+		thread::spawn(move || {
+			while start.elapsed() < run_time {
+				pl.stats().unwrap();
+				thread::sleep(time::Duration::from_millis(10));
+			}
+		})
+	};
+
+	let mut pushed = 0;
+	for p in pushers {
+		pushed += p.join().unwrap();
+	}
+	let found = reader.join().unwrap();
+	stats.join().unwrap();
+	println!("Pushed {} jobs, found {} solutions", pushed, found);
+	assert!(pushed > 0);
+
+	pl.stop_and_wait(time::Duration::from_secs(60)).unwrap();
+}
+
+#[cfg(feature = "real-plugin-tests")]
+#[test]
+fn on_commit_shared_lean_cpu_16(){
+	let pl = Arc::new(load_plugin_lib("lean_cpu_16").unwrap());
+	shared_plugin_tests(pl, time::Duration::from_secs(5));
+}
+
+// The tests above, against the mock plugin. These don't need the real
+// plugins so are always run.
+
+#[test]
+fn mock_plugin_loading(){
+	for _ in 0..10 {
+		let pl = load_mock_plugin();
+		assert!(pl.api_version() >> 16 == PLUGIN_API_VERSION >> 16);
+		for _ in 0..10 {
+			pl.call_cuckoo_init();
+		}
+		pl.unload();
+	}
+}
+
+#[test]
+fn mock_plugin_drop_while_processing(){
+	for i in 0..10 {
+		let pl = load_mock_plugin();
+		let nonce:[u8;8]=[0;8];
+		pl.set_parameter("MOCK_DELAY_MS", 5).unwrap();
+		pl.push_to_input_queue(0, &known_hash(&pl), &nonce).unwrap();
+		pl.start_processing().unwrap();
+		thread::sleep(time::Duration::from_millis(10));
+		if i % 2 == 0 {
+			pl.unload();
+			pl.unload();
+		}
+	}
+}
+
+#[test]
+fn mock_cuckoo_description(){
+	let pl = load_mock_plugin();
+	description_tests(&pl);
+	assert!(pl.description().unwrap().name == "mock");
+}
+
+#[test]
+fn mock_cuckoo_parameter_list(){
+	let pl = load_mock_plugin();
+	for _ in 0..10 {
+		call_cuckoo_parameter_list_tests(&pl);
+	}
+	parameters_tests(&pl);
+}
+
+#[test]
+fn mock_cuckoo_parameters(){
+	let pl = load_mock_plugin();
+	for _ in 0..10 {
+		call_cuckoo_get_parameter_tests(&pl);
+		call_cuckoo_set_parameter_tests(&pl);
+	}
+	typed_parameter_tests(&pl);
+	checked_parameter_tests(&pl);
+	u64_parameter_tests(&pl);
+	device_parameter_tests(&pl);
+	assert!(pl.edge_bits() == Some(15));
+}
+
+#[test]
+fn mock_checked_parameters(){
+	let pl = load_mock_plugin();
+	//flags are 0 or 1
+	pl.set_parameter_checked("USE_DEVICE", 0).unwrap();
+	assert!(pl.get_parameter_checked("USE_DEVICE").unwrap() == 0);
+	match pl.set_parameter_checked("USE_DEVICE", 2) {
+		Err(CuckooMinerError::ParameterOutOfRange{min: 0, max: 1, ..}) => {},
+		r => panic!("Expected ParameterOutOfRange, got {:?}", r),
+	}
+
+	//values over u32::MAX go through the 64-bit calls
+	let mask = 0x1_0000_0003u64;
+	pl.set_parameter_checked("CPU_AFFINITY_MASK", mask).unwrap();
+	assert!(pl.get_parameter_checked("CPU_AFFINITY_MASK").unwrap() == mask);
+	assert!(pl.get_parameter_u64("CPU_AFFINITY_MASK").unwrap() == mask);
+
+	//calls an unloaded plugin refuses are reported as such
+	pl.unload();
+	match pl.get_parameter_checked("NUM_THREADS") {
+		Err(CuckooMinerError::PluginStateError(_)) => {},
+		r => panic!("Expected a PluginStateError, got {:?}", r),
+	}
+}
+
+#[test]
+fn mock_cuckoo_call(){
+	let pl = load_mock_plugin();
+	cuckoo_call_tests(&pl);
+	//anything else has no solution
+	let mut solution:[u32; 42] = [0;42];
+	let mut size = 0;
+	assert!(pl.call_cuckoo(&[0;32], &mut size, &mut solution) == 0);
+}
+
+#[test]
+fn mock_call_cuckoo_start_processing(){
+	let pl = load_mock_plugin();
+	for _ in 0..10 {
+		call_cuckoo_start_processing_tests(&pl);
+	}
+}
+
+#[test]
+fn mock_call_cuckoo_push_to_input_queue(){
+	let pl = load_mock_plugin();
+	for _ in 0..10 {
+		call_cuckoo_push_to_input_queue_tests(&pl);
+	}
+	queue_capacity_tests(&pl);
+}
+
+#[test]
+fn mock_call_cuckoo_stop_processing(){
+	let pl = load_mock_plugin();
+	pl.set_parameter("MOCK_DELAY_MS", 10).unwrap();
+	call_cuckoo_stop_processing_tests(&pl);
+}
+
+#[test]
+fn mock_call_cuckoo_read_from_output_queue(){
+	let pl = load_mock_plugin();
+	call_cuckoo_read_from_output_queue_tests(&pl);
+}
+
+#[test]
+fn mock_wait_for_solution(){
+	let pl = load_mock_plugin();
+	wait_for_solution_tests(&pl);
+}
+
+#[test]
+fn mock_drain_solutions(){
+	let pl = load_mock_plugin();
+	drain_solutions_tests(&pl);
+}
+
+#[test]
+fn mock_clear_input_queue(){
+	let pl = load_mock_plugin();
+	//slow enough that the queue is cleared before it's searched
+	pl.set_parameter("MOCK_DELAY_MS", 50).unwrap();
+	clear_input_queue_tests(&pl);
+}
+
+#[test]
+fn mock_list_devices(){
+	let pl = load_mock_plugin();
+	list_devices_tests(&pl);
+	pl.set_parameter("MOCK_DEVICES", 3).unwrap();
+	assert!(pl.list_devices().unwrap().len() == 3);
+	device_parameter_tests(&pl);
+}
+
+#[test]
+fn mock_capabilities(){
+	let pl = load_mock_plugin();
+	let caps = pl.capabilities().unwrap();
+	assert!(caps.edge_bits == Some(15));
+	assert!(caps.proof_size == 42);
+	assert!(!caps.is_cuda);
+	assert!(caps.supports_async);
+	assert!(caps.max_queue_depth == Some(10000));
+	assert!(caps.graph_type == GraphType::Cuckoo && pl.graph_type() == GraphType::Cuckoo);
+	//the same while processing, and refused once unloaded
+	pl.start_processing().unwrap();
+	assert!(pl.capabilities().unwrap() == caps);
+	pl.unload();
+	assert!(pl.capabilities().is_err());
+	assert!(pl.edge_bits().is_none());
+}
+
+#[test]
+fn mock_capability_flags(){
+	let pl = load_mock_plugin();
+	//the mock implements 1.1, so has to report its flags to load,
+	//which agree with the capabilities it reports as JSON
+	assert!(pl.api_version() == PLUGIN_API_VERSION);
+	let caps = pl.capabilities().unwrap();
+	assert!(caps.supports_async && caps.supports_stats && !caps.is_cuda);
+	assert!(caps.edge_bits == Some(15));
+	pl.set_parameter("EDGE_BITS", 19).unwrap();
+	assert!(pl.capabilities().unwrap().edge_bits == Some(19));
+	pl.unload();
+	assert!(pl.capabilities().is_err());
+}
+
+#[test]
+fn mock_proof_size(){
+	let pl = load_mock_plugin();
+	assert!(pl.proof_size() == 42);
+	pl.set_parameter("MOCK_PROOF_SIZE", 12).unwrap();
+	assert!(pl.proof_size() == 12);
+	assert!(pl.capabilities().unwrap().proof_size == 12);
+
+	//only the proof size is written, into a buffer of any length that
+	//holds it
+	let header = from_hex(KNOWN_16_HASH_12).unwrap();
+	let mut cuckoo_size = 0;
+	let mut solution = [0u32; 42];
+	assert!(pl.call_cuckoo(&header, &mut cuckoo_size, &mut solution) == 1);
+	assert!(solution[..12] == KNOWN_16_SOLUTION_12);
+	assert!(solution[12..].iter().all(|n| *n == 0));
+	let mut short = [0u32; 11];
+	assert!(pl.call_cuckoo(&header, &mut cuckoo_size, &mut short) == CALL_REFUSED);
+	//the 42-cycles aren't given at this size
+	assert!(pl.call_cuckoo(&from_hex(KNOWN_16_HASH_1).unwrap(), &mut cuckoo_size, &mut solution) == 0);
+
+	//solutions read from the queue are the declared size
+	let nonce:[u8;8] = [0;8];
+	pl.push_to_input_queue(0, &header, &nonce).unwrap();
+	pl.start_processing().unwrap();
+	let s = pl.wait_for_solution(time::Duration::from_secs(10)).unwrap().unwrap();
+	assert!(s.solution_nonces == common::widen(&KNOWN_16_SOLUTION_12));
+	pl.stop_and_wait(time::Duration::from_secs(10)).unwrap();
+
+	//a size which can't be a cycle is ignored
+	pl.call_cuckoo_reset_processing();
+	pl.set_parameter("MOCK_PROOF_SIZE", 11).unwrap();
+	assert!(pl.capabilities().is_err());
+	assert!(pl.proof_size() == 12);
+}
+
+#[test]
+fn mock_cancellable_call(){
+	let pl = Arc::new(load_mock_plugin());
+	assert!(pl.has_cancellable_call());
+	let header = from_hex(KNOWN_16_HASH_1).unwrap();
+	let mut cuckoo_size = 0;
+	let mut solution = [0u32; 42];
+	let cancel = Arc::new(AtomicBool::new(false));
+	assert!(pl.call_cuckoo_cancellable(&header, &mut cuckoo_size, &mut solution, &cancel) == 1);
+	assert!(solution == common::KNOWN_16_SOLUTION_1);
+
+	//a slow graph is given up on once the flag's set
+	pl.set_parameter("MOCK_DELAY_MS", 10000).unwrap();
+	let c = cancel.clone();
+	thread::spawn(move || {
+		thread::sleep(time::Duration::from_millis(100));
+		c.store(true, Ordering::SeqCst);
+	});
+	let start = Instant::now();
+	let mut solution = [0u32; 42];
+	assert!(pl.call_cuckoo_cancellable(&header, &mut cuckoo_size, &mut solution, &cancel) == CALL_CANCELLED);
+	assert!(start.elapsed() < time::Duration::from_millis(100) + CANCEL_BOUND);
+	assert!(solution.iter().all(|n| *n == 0));
+}
+
+#[test]
+fn mock_solver_context(){
+	let pl = load_mock_plugin();
+	assert!(pl.has_persistent_context());
+	let header = from_hex(KNOWN_16_HASH_1).unwrap();
+	let mut plain_size = 0;
+	let mut plain = [0u32; 42];
+	assert!(pl.call_cuckoo(&header, &mut plain_size, &mut plain) == 1);
+
+	//the context gives the same solution, however many times it's used
+	{
+		let context = pl.solver_context();
+		assert!(context.is_persistent());
+		assert!(pl.get_parameter("MOCK_CONTEXTS").unwrap() == 1);
+		for _ in 0..3 {
+			let mut cuckoo_size = 0;
+			let mut solution = [0u32; 42];
+			assert!(context.call_cuckoo(&header, &mut cuckoo_size, &mut solution, None) == 1);
+			assert!(cuckoo_size == plain_size);
+			assert!(solution == plain);
+			assert!(solution == common::KNOWN_16_SOLUTION_1);
+		}
+		let mut short = [0u32; 41];
+		assert!(context.call_cuckoo(&header, &mut plain_size, &mut short, None) == CALL_REFUSED);
+	}
+	//and is released once dropped
+	assert!(pl.get_parameter("MOCK_CONTEXTS").unwrap() == 0);
+
+	//a context which can't be created falls back to plain calls
+	pl.set_parameter("MOCK_NO_CONTEXT", 1).unwrap();
+	let context = pl.solver_context();
+	assert!(!context.is_persistent());
+	let mut cuckoo_size = 0;
+	let mut solution = [0u32; 42];
+	assert!(context.call_cuckoo(&header, &mut cuckoo_size, &mut solution, None) == 1);
+	assert!(solution == plain);
+}
+
+#[test]
+fn mock_solver_context_released_on_unload(){
+	//two instances of one library share the mock's state, so the second
+	//sees what the first leaves behind
+	let path = common::mock_plugin_copy();
+	let pl = PluginLibrary::new(&path).unwrap();
+	let observer = PluginLibrary::new(&path).unwrap();
+	let context = pl.solver_context();
+	assert!(observer.get_parameter("MOCK_CONTEXTS").unwrap() == 1);
+	pl.unload();
+	assert!(observer.get_parameter("MOCK_CONTEXTS").unwrap() == 0);
+
+	//the context is dead with the plugin, and dropping it is harmless
+	let mut cuckoo_size = 0;
+	let mut solution = [0u32; 42];
+	let header = from_hex(KNOWN_16_HASH_1).unwrap();
+	assert!(context.call_cuckoo(&header, &mut cuckoo_size, &mut solution, None) == CALL_REFUSED);
+	drop(context);
+	assert!(observer.get_parameter("MOCK_CONTEXTS").unwrap() == 0);
+}
+
+#[test]
+fn mock_batch_push(){
+	let pl = load_mock_plugin();
+	assert!(pl.has_batch_push());
+	pl.set_parameter("QUEUE_SIZE", 3).unwrap();
+
+	//the known header at nonces 1 and 3, and one without a solution
+	//between, which must come out as they went in
+	let mut known = [0u8; JOB_HEADER_SIZE];
+	known.copy_from_slice(&from_hex(KNOWN_16_HASH_1).unwrap());
+	let jobs: Vec<([u8; JOB_HEADER_SIZE], [u8; 8])> = (1..6u8)
+		.map(|i| (if i % 2 == 1 { known } else { [i; JOB_HEADER_SIZE] }, [0, 0, 0, 0, 0, 0, 0, i]))
+		.collect();
+	let records = pack_jobs(&jobs);
+	assert!(records.len() == 5 * JOB_RECORD_SIZE);
+	assert!(records[JOB_RECORD_SIZE..JOB_RECORD_SIZE + JOB_HEADER_SIZE] == [2; JOB_HEADER_SIZE]);
+	assert!(records[2 * JOB_RECORD_SIZE - 1] == 2);
+
+	//the queue only has room for the first three
+	assert!(pl.push_jobs(7, &jobs) == 3);
+	assert!(pl.call_cuckoo_is_queue_under_limit() == 0);
+	assert!(pl.push_jobs(7, &jobs) == 0);
+	let mut accepted = 0;
+	assert!(pl.call_cuckoo_push_batch_to_input_queue(7, &records[..JOB_RECORD_SIZE - 1], &mut accepted) == CALL_REFUSED);
+
+	pl.start_processing().unwrap();
+	let mut nonces = Vec::new();
+	for _ in 0..2 {
+		let s = pl.wait_for_solution(time::Duration::from_secs(10)).unwrap().unwrap();
+		assert!(s.id == 7);
+		assert!(s.solution_nonces == common::widen(&common::KNOWN_16_SOLUTION_1));
+		nonces.push(s.nonce);
+	}
+	nonces.sort();
+	assert!(nonces == vec![1, 3]);
+	//the fifth, which would also have been solved, was never queued
+	thread::sleep(time::Duration::from_millis(200));
+	assert!(pl.drain_solutions(10).is_empty());
+	pl.stop_and_wait(time::Duration::from_secs(10)).unwrap();
+}
+
+// Waits up to ten seconds for the plugin's queue depths to satisfy a
+// condition, returning the last depths read
+fn wait_for_depths<F>(pl: &PluginLibrary, done: F) -> (u32, u32)
+where
+	F: Fn((u32, u32)) -> bool,
+{
+	let start = time::Instant::now();
+	loop {
+		let depths = pl.queue_depths();
+		if done(depths) || start.elapsed() > time::Duration::from_secs(10) {
+			return depths;
+		}
+		thread::sleep(time::Duration::from_millis(10));
+	}
+}
+
+// Checks the queue depths rise as headers are pushed and solutions found,
+// and fall as they're searched and read. Plugins whose depths are
+// estimated always have an empty output queue.
+fn queue_depths_tests(pl: &PluginLibrary, reports_output: bool) {
+	pl.set_parameter("MOCK_DELAY_MS", 100).unwrap();
+	let header = from_hex(KNOWN_16_HASH_1).unwrap();
+	for i in 0..4u8 {
+		assert!(pl.call_cuckoo_push_to_input_queue(0, &header, &[0, 0, 0, 0, 0, 0, 0, i]) == 0);
+	}
+	assert!(pl.queue_depths() == (4, 0));
+
+	//feeding paused, the plugin works through its queue
+	pl.start_processing().unwrap();
+	let solved = if reports_output { 4 } else { 0 };
+	let depths = wait_for_depths(pl, |d| d == (0, solved));
+	assert!(depths == (0, solved), "{:?}", depths);
+
+	//feeding resumed
+	for i in 4..7u8 {
+		assert!(pl.call_cuckoo_push_to_input_queue(0, &header, &[0, 0, 0, 0, 0, 0, 0, i]) == 0);
+	}
+	let (input, _) = pl.queue_depths();
+	assert!(input >= 2 && input <= 3, "{}", input);
+	pl.clear_input_queue().unwrap();
+	assert!(pl.queue_depths().0 == 0);
+
+	//reading the solutions empties the output queue, once the graph in
+	//progress is done
+	thread::sleep(time::Duration::from_millis(300));
+	assert!(pl.drain_solutions(10).len() >= 4);
+	assert!(pl.queue_depths() == (0, 0));
+	pl.stop_and_wait(time::Duration::from_secs(10)).unwrap();
+}
+
+#[test]
+fn mock_queue_depths(){
+	let pl = load_mock_plugin();
+	assert!(pl.has_queue_depths());
+	queue_depths_tests(&pl, true);
+
+	//estimated, when the plugin can't tell
+	let pl = load_mock_plugin();
+	pl.set_parameter("MOCK_NO_QUEUE_DEPTHS", 1).unwrap();
+	queue_depths_tests(&pl, false);
+}
+
+#[test]
+fn mock_64_bit_calls(){
+	let pl = load_mock_plugin();
+	assert!(pl.has_64_bit_calls());
+	assert!(!pl.has_wide_edges());
+	pl.set_parameter("EDGE_BITS", 40).unwrap();
+	assert!(pl.edge_bits() == Some(40));
+	assert!(pl.has_wide_edges());
+
+	//nonces too big for a u32 are only given through the 64-bit calls
+	let header = from_hex(MOCK_40_HASH).unwrap();
+	let mut cuckoo_size = 0;
+	let mut solution = [0u64; 42];
+	assert!(pl.call_cuckoo_64(&header, &mut cuckoo_size, &mut solution) == 1);
+	assert!(cuckoo_size == 41);
+	assert!(solution[..] == MOCK_40_SOLUTION[..]);
+	let mut narrow = [0u32; 42];
+	assert!(pl.call_cuckoo(&header, &mut cuckoo_size, &mut narrow) == 0);
+	let mut short = [0u64; 41];
+	assert!(pl.call_cuckoo_64(&header, &mut cuckoo_size, &mut short) == CALL_REFUSED);
+
+	//and solutions read from the queue keep them whole
+	let nonce:[u8;8] = [0;8];
+	pl.push_to_input_queue(0, &header, &nonce).unwrap();
+	pl.start_processing().unwrap();
+	let s = pl.wait_for_solution(time::Duration::from_secs(10)).unwrap().unwrap();
+	assert!(s.cuckoo_size == 41);
+	assert!(s.is_64_bit());
+	assert!(s.solution_nonces[..] == MOCK_40_SOLUTION[..]);
+	pl.stop_and_wait(time::Duration::from_secs(10)).unwrap();
+}
+
+#[test]
+fn mock_call_cuckoo_get_stats(){
+	let pl = load_mock_plugin();
+	call_cuckoo_get_stats_test(&pl);
+	typed_stats_test(&pl);
+}
+
+#[test]
+fn mock_shared(){
+	let pl = Arc::new(load_mock_plugin());
+	pl.set_parameter("MOCK_SOLVE_ALL", 1).unwrap();
+	shared_plugin_tests(pl, time::Duration::from_secs(1));
+}
+
+// Tests of the mock's misbehaviour, as used to test how it's handled
+// higher up
+
+//a plugin which takes too long to stop times out, but can still be
+//waited for
+#[test]
+fn mock_ignores_stop(){
+	let pl = load_mock_plugin();
+	pl.set_parameter("MOCK_IGNORE_STOP_MS", 500).unwrap();
+	pl.start_processing().unwrap();
+	let start=Instant::now();
+	match pl.stop_and_wait(time::Duration::from_millis(100)) {
+		Err(CuckooMinerError::PluginShutdownTimeout(_)) => {},
+		r => panic!("Expected PluginShutdownTimeout, got {:?}", r),
+	}
+	pl.wait_for_processing_stopped(time::Duration::from_secs(5)).unwrap();
+	assert!(start.elapsed() >= time::Duration::from_millis(500));
+	pl.call_cuckoo_reset_processing();
+}
+
+//processing stopped from another thread is waited for and reset, and
+//can be started again
+#[test]
+fn mock_stop_and_wait_after_stop(){
+	let pl = load_mock_plugin();
+	pl.set_parameter("MOCK_DELAY_MS", 20).unwrap();
+	pl.start_processing().unwrap();
+	thread::scope(|s| {
+		s.spawn(|| pl.call_cuckoo_stop_processing());
+	});
+	pl.stop_and_wait(time::Duration::from_secs(5)).unwrap();
+	assert_eq!(pl.state(), PluginState::Idle);
+	pl.start_processing().unwrap();
+	assert_eq!(pl.state(), PluginState::Processing);
+	pl.stop_and_wait(time::Duration::from_secs(5)).unwrap();
+}
+
+//a plugin which takes too long to stop times out, and isn't reset until
+//it's waited for again
+#[test]
+fn mock_stop_and_wait_again(){
+	let pl = load_mock_plugin();
+	pl.set_parameter("MOCK_IGNORE_STOP_MS", 500).unwrap();
+	pl.start_processing().unwrap();
+	match pl.stop_and_wait(time::Duration::from_millis(100)) {
+		Err(CuckooMinerError::PluginShutdownTimeout(_)) => {},
+		r => panic!("Expected PluginShutdownTimeout, got {:?}", r),
+	}
+	assert_eq!(pl.state(), PluginState::Stopping);
+	pl.stop_and_wait(time::Duration::from_secs(5)).unwrap();
+	assert_eq!(pl.state(), PluginState::Idle);
+}
+
+//one device fails after a few graphs while the other carries on
+#[test]
+fn mock_device_errors(){
+	let pl = load_mock_plugin();
+	pl.set_parameter("MOCK_DEVICES", 2).unwrap();
+	pl.set_parameter_for_device("MOCK_ERROR_AFTER", 1, 2).unwrap();
+	pl.set_parameter("MOCK_DELAY_MS", 1).unwrap();
+	let nonce:[u8;8]=[0;8];
+	for i in 0..20 {
+		pl.push_to_input_queue(i, &[0;32], &nonce).unwrap();
+	}
+	pl.start_processing().unwrap();
+	let start=Instant::now();
+	loop {
+		let completed: u32 = pl.stats().unwrap().iter().map(|s| s.iterations_completed).sum();
+		if completed == 20 {
+			break;
+		}
+		if start.elapsed() > time::Duration::from_secs(60) {
+			panic!("Jobs not completed");
+		}
+		thread::sleep(time::Duration::from_millis(10));
+	}
+	let stats = pl.stats().unwrap();
+	assert!(!stats[0].has_errored);
+	assert!(stats[1].has_errored);
+	assert!(stats[1].iterations_completed == 2);
+	assert!(stats[1].error_reason.contains("2 graphs"));
+	assert!(stats[0].error().is_none());
+	assert!(stats[0].graphs_per_second() > 0.0);
+	assert!(stats[0].last_solve_time().is_some());
+	assert!(stats[1].error().unwrap().contains("2 graphs"));
+	pl.stop_and_wait(time::Duration::from_secs(5)).unwrap();
+}
+
+#[test]
+fn mock_reports_errors(){
+	let pl = load_mock_plugin();
+	pl.set_parameter("MOCK_START_ERROR", 7).unwrap();
+	match pl.start_processing() {
+		Err(CuckooMinerError::PluginProcessingError(_)) => {},
+		r => panic!("Expected PluginProcessingError, got {:?}", r),
+	}
+	pl.set_parameter("MOCK_BAD_STATS", 1).unwrap();
+	match pl.stats() {
+		Err(CuckooMinerError::StatsError(_)) => {},
+		r => panic!("Expected StatsError, got {:?}", r),
+	}
+}
+
+//output too big for the buffer isn't written to it, and the typed calls
+//retry with a bigger one
+#[test]
+fn mock_truncates_output(){
+	let pl = load_mock_plugin();
+	pl.set_parameter("MOCK_DEVICES", 4).unwrap();
+	pl.set_parameter("MOCK_NAME_LENGTH", 1000).unwrap();
+	let mut stat_bytes:[u8;1024]=[0;1024];
+	let mut stat_bytes_len=stat_bytes.len() as u32;
+	assert!(pl.call_cuckoo_get_stats(&mut stat_bytes, &mut stat_bytes_len) == 3);
+	assert!(stat_bytes.iter().all(|b| *b == 0));
+
+	let stats = pl.stats().unwrap();
+	assert!(stats.len() == 4);
+	assert!(stats.iter().all(|s| s.device_name.len() == 1000));
+	let devices = pl.list_devices().unwrap();
+	assert!(devices.len() == 4);
+	assert!(devices.iter().all(|d| d.name.len() == 1000));
+}
@@ -0,0 +1,137 @@
+// Copyright 2017 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tests of plugin self-tests against the known headers, using the mock
+//! plugin
+
+extern crate cuckoo_miner as cuckoo;
+
+use std::{env, fs, process};
+use std::time::{Duration, Instant};
+
+use cuckoo::{BenchmarkMode, CuckooMinerError, CuckooPluginManager, PluginLibrary, PluginState, test_vector,
+	KNOWN_16_HASH_1, KNOWN_16_HASH_12, KNOWN_30_HASH_1};
+
+pub mod common;
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+// A fresh copy of the mock with the given parameters
+fn mock(params: &[(&str, u32)]) -> PluginLibrary {
+	let library = common::load_mock_plugin();
+	for &(name, value) in params {
+		library.set_parameter(name, value).unwrap();
+	}
+	library
+}
+
+#[test]
+fn test_vectors_are_found_by_size() {
+	assert_eq!(test_vector(15, 42).unwrap().header, KNOWN_16_HASH_1);
+	assert_eq!(test_vector(15, 12).unwrap().header, KNOWN_16_HASH_12);
+	assert_eq!(test_vector(29, 42).unwrap().header, KNOWN_30_HASH_1);
+	assert!(test_vector(29, 42).unwrap().solution.is_none());
+	assert!(test_vector(19, 42).is_none());
+}
+
+#[test]
+fn the_mock_passes() {
+	let library = mock(&[("MOCK_DEVICES", 2)]);
+	let report = library.self_test(TIMEOUT).unwrap();
+	assert!(report.passed, "{:?}", report.failure);
+	assert!(report.failure.is_none());
+	assert_eq!(report.mode, BenchmarkMode::Async);
+	assert_eq!(report.cuckoo_size, 16);
+	assert_eq!(report.header, KNOWN_16_HASH_1);
+	assert_eq!(report.plugin, library.lib_full_path);
+	// the plugin is left as it was
+	assert_eq!(library.state(), PluginState::Idle);
+	assert!(library.drain_solutions(10).is_empty());
+
+	// a plugin finding 12-cycles gets the 12-cycle header
+	let library = mock(&[("MOCK_PROOF_SIZE", 12)]);
+	let report = library.self_test(TIMEOUT).unwrap();
+	assert!(report.passed, "{:?}", report.failure);
+	assert_eq!(report.header, KNOWN_16_HASH_12);
+}
+
+#[test]
+fn an_invalid_proof_fails() {
+	for bad_proof in 1..3 {
+		let library = mock(&[("MOCK_BAD_PROOF", bad_proof)]);
+		let report = library.self_test(TIMEOUT).unwrap();
+		assert!(!report.passed);
+		let failure = report.failure.unwrap();
+		assert!(failure.contains("invalid"), "{}", failure);
+		assert_eq!(library.state(), PluginState::Idle);
+	}
+}
+
+#[test]
+fn finding_nothing_fails() {
+	// the mock has no solution at cuckoo 30, so searches the graph in vain
+	let library = mock(&[("EDGE_BITS", 29)]);
+	let report = library.self_test(TIMEOUT).unwrap();
+	assert!(!report.passed);
+	assert_eq!(report.cuckoo_size, 30);
+	assert!(report.failure.unwrap().contains("without finding"));
+
+	// a plugin too slow for the timeout fails once it's up
+	let library = mock(&[("MOCK_DELAY_MS", 5000)]);
+	let start = Instant::now();
+	let report = library.self_test(Duration::from_millis(200)).unwrap();
+	assert!(start.elapsed() < Duration::from_secs(3));
+	assert!(!report.passed);
+	assert!(report.failure.unwrap().contains("within"));
+	assert_eq!(library.state(), PluginState::Idle);
+
+	// as does one whose device fails
+	let library = mock(&[("MOCK_ERROR_AFTER", 1), ("EDGE_BITS", 29)]);
+	let report = library.self_test(TIMEOUT).unwrap();
+	assert!(!report.passed);
+}
+
+#[test]
+fn sizes_without_a_vector_cant_be_tested() {
+	let library = mock(&[("EDGE_BITS", 19)]);
+	match library.self_test(TIMEOUT) {
+		Err(CuckooMinerError::NotSupported(_)) => {}
+		r => panic!("Expected NotSupported, got {:?}", r),
+	}
+
+	let library = mock(&[]);
+	library.start_processing().unwrap();
+	match library.self_test(TIMEOUT) {
+		Err(CuckooMinerError::PluginStateError(_)) => {}
+		r => panic!("Expected PluginStateError, got {:?}", r),
+	}
+	library.stop_and_wait(TIMEOUT).unwrap();
+}
+
+#[test]
+fn the_manager_tests_every_plugin() {
+	let mut dir = env::temp_dir();
+	dir.push(format!("cuckoo-self-test-{}", process::id()));
+	fs::create_dir_all(&dir).unwrap();
+	fs::copy(common::mock_plugin_path(), dir.join("mock.cuckooplugin")).unwrap();
+
+	let mut manager = CuckooPluginManager::new().unwrap();
+	manager.load_plugin_dir(dir.to_string_lossy().into_owned()).unwrap();
+	let reports = manager.self_test_all(TIMEOUT).unwrap();
+	assert_eq!(reports.len(), 1);
+	let report = reports[0].as_ref().unwrap();
+	assert!(report.passed, "{:?}", report.failure);
+	assert!(report.plugin.ends_with("mock.cuckooplugin"));
+	fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,263 @@
+// Copyright 2017 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tests for the JSON wire format of solutions and configs, which is
+//! checked against fixtures so it doesn't change unnoticed
+
+#![cfg(feature = "serde")]
+
+extern crate cuckoo_miner as cuckoo;
+extern crate serde_json;
+
+use std::fs::File;
+use std::io::Read;
+use std::path::PathBuf;
+use std::time::{Duration, UNIX_EPOCH};
+
+use cuckoo::{CuckooMinerSolution, CuckooMinerConfig, CuckooPluginParameter, PluginDeviceStats,
+	DeviceInfo, NonceRange, SolutionMetadata, BenchmarkMode, BenchmarkReport, BenchmarkPhases, benchmark_json};
+
+fn fixture(name: &str) -> serde_json::Value {
+	let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+	path.push("tests/fixtures");
+	path.push(name);
+	let mut s = String::new();
+	File::open(path).unwrap().read_to_string(&mut s).unwrap();
+	serde_json::from_str(&s).unwrap()
+}
+
+fn fixture_solution() -> CuckooMinerSolution {
+	let mut s = CuckooMinerSolution::new();
+	s.cuckoo_size = 16;
+	s.set_solution::<u32>(&[
+		0x1bd, 0x2a6, 0x7d5, 0xa1f, 0xd7a, 0x10b2, 0x12c8, 0x1516, 0x1a49, 0x1bf6,
+		0x1e35, 0x2045, 0x23c0, 0x2618, 0x27b4, 0x2c1e, 0x2f02, 0x3156, 0x3340, 0x36f1,
+		0x3a0d, 0x3c97, 0x3e12, 0x42b4, 0x4433, 0x47e8, 0x4a9c, 0x4d01, 0x4f5e, 0x5204,
+		0x5563, 0x5862, 0x5a1b, 0x5d3f, 0x6060, 0x62d7, 0x6651, 0x68ad, 0x6b23, 0x6e8f,
+		0x7134, 0x7e6b,
+	]);
+	// larger than a double can represent exactly
+	s.nonce = 18446744073709551557;
+	s.id = 7;
+	s.plugin_name = Some(String::from("lean_cpu_16"));
+	s
+}
+
+#[test]
+fn solution_round_trip() {
+	let s = fixture_solution();
+	let json = serde_json::to_string(&s).unwrap();
+	let back: CuckooMinerSolution = serde_json::from_str(&json).unwrap();
+	assert!(back == s);
+	assert!(back.nonce == s.nonce);
+	assert!(back.id == s.id);
+	assert!(back.plugin_name == s.plugin_name);
+}
+
+#[test]
+fn solution_metadata_round_trip() {
+	let mut s = fixture_solution();
+	s.metadata = Some(SolutionMetadata {
+		device_id: Some(String::from("1")),
+		graph_time_ms: Some(250),
+		found_at: UNIX_EPOCH + Duration::from_millis(1_500_000_000_123),
+		..SolutionMetadata::new(s.plugin_name.clone())
+	});
+	let json = serde_json::to_value(&s).unwrap();
+	assert!(json["metadata"]["attempt_index"].is_null());
+	let back: CuckooMinerSolution = serde_json::from_value(json).unwrap();
+	assert!(back.metadata == s.metadata);
+}
+
+#[test]
+fn solution_header_hash_is_hex() {
+	let mut s = fixture_solution();
+	let mut hash = [0u8; 32];
+	hash[0] = 0xab;
+	hash[31] = 0x01;
+	s.header_hash = Some(hash);
+	let json = serde_json::to_value(&s).unwrap();
+	let hex = json["header_hash"].as_str().unwrap().to_owned();
+	assert!(hex.len() == 64 && hex.starts_with("ab") && hex.ends_with("01"));
+	let back: CuckooMinerSolution = serde_json::from_value(json.clone()).unwrap();
+	assert!(back.header_hash == Some(hash));
+
+	let mut short = json;
+	short["header_hash"] = serde_json::Value::from(&hex[..62]);
+	assert!(serde_json::from_value::<CuckooMinerSolution>(short).is_err());
+}
+
+#[test]
+fn solution_matches_fixture() {
+	let expected = fixture("solution.json");
+	assert!(serde_json::to_value(&fixture_solution()).unwrap() == expected);
+	assert!(expected["nonce"].is_string());
+	assert!(expected["solution_nonces"].as_array().unwrap().len() == 42);
+
+	let s: CuckooMinerSolution = serde_json::from_value(expected).unwrap();
+	assert!(s.nonce == 18446744073709551557);
+}
+
+#[test]
+fn solution_rejects_bad_proofs() {
+	let mut short = fixture("solution.json");
+	short["solution_nonces"].as_array_mut().unwrap().pop();
+	assert!(serde_json::from_value::<CuckooMinerSolution>(short).is_err());
+
+	let mut numeric_nonce = fixture("solution.json");
+	numeric_nonce["nonce"] = serde_json::Value::from(5);
+	assert!(serde_json::from_value::<CuckooMinerSolution>(numeric_nonce).is_err());
+}
+
+#[test]
+fn config_round_trip() {
+	let mut c = CuckooMinerConfig::new();
+	c.plugin_full_path = String::from("target/debug/plugins/lean_cpu_16.cuckooplugin");
+	c.parameter_list.push((String::from("NUM_THREADS"), 0, 4));
+	let expected = fixture("config.json");
+	assert!(serde_json::to_value(&c).unwrap() == expected);
+
+	let back: CuckooMinerConfig = serde_json::from_value(expected).unwrap();
+	assert!(back.plugin_full_path == c.plugin_full_path);
+	assert!(back.parameter_list == c.parameter_list);
+
+	// missing fields take their defaults
+	let partial: CuckooMinerConfig = serde_json::from_str(r#"{"plugin_full_path": "lean_cpu_16"}"#).unwrap();
+	assert!(partial.parameter_list.is_empty());
+	assert!(partial.allow_legacy_plugins);
+	assert!(partial.nonce_range.is_none());
+	assert!(partial.queue_size.is_none());
+
+	let ranged: CuckooMinerConfig = serde_json::from_str(
+		r#"{"plugin_full_path": "lean_cpu_16", "nonce_range": {"start": 1, "stride": 2}}"#,
+	).unwrap();
+	assert!(ranged.nonce_range == Some(NonceRange::interleaved(1, 2)));
+	assert!(ranged.device_parameters.is_empty());
+
+	// per-device parameters, keyed by device id
+	let devices: CuckooMinerConfig = serde_json::from_str(
+		r#"{"plugin_full_path": "lean_cuda_30", "device_parameters": {"0": {"N_TRIMS": 176}, "1": {"N_TRIMS": 256, "GENU_BLOCKS": 64}}}"#,
+	).unwrap();
+	assert!(devices.device_parameters[&1]["GENU_BLOCKS"] == 64);
+	let back: CuckooMinerConfig = serde_json::from_str(&serde_json::to_string(&devices).unwrap()).unwrap();
+	assert!(back.device_parameters == devices.device_parameters);
+	assert!(devices.enabled_devices.is_none());
+
+	let enabled: CuckooMinerConfig = serde_json::from_str(
+		r#"{"plugin_full_path": "lean_cuda_30", "enabled_devices": [0, 2]}"#,
+	).unwrap();
+	assert!(enabled.enabled_devices == Some(vec![0, 2]));
+}
+
+#[test]
+fn plugin_structs_round_trip() {
+	let p: CuckooPluginParameter = serde_json::from_str(
+		r#"{"name": "NUM_THREADS", "description": "Number of threads", "default_value": 1, "min_value": 1, "max_value": 32}"#,
+	).unwrap();
+	let back: CuckooPluginParameter = serde_json::from_str(&serde_json::to_string(&p).unwrap()).unwrap();
+	assert!(back.name == p.name && back.max_value == 32);
+	assert!(!p.is_64_bit());
+
+	// memory sizes in bytes can exceed 32 bits
+	let p: CuckooPluginParameter = serde_json::from_str(
+		r#"{"name": "MEMORY", "description": "Device memory", "default_value": 0, "min_value": 0, "max_value": 17179869184}"#,
+	).unwrap();
+	assert!(p.max_value == 16 << 30);
+	assert!(p.is_64_bit());
+
+	let s: PluginDeviceStats = serde_json::from_str(
+		r#"{"device_id": "0", "cuckoo_size": "16", "device_name": "cpu", "in_use": 1, "has_errored": 0,
+		"last_start_time": 1, "last_end_time": 2, "last_solution_time": 1, "iterations_completed": 10}"#,
+	).unwrap();
+	let back: PluginDeviceStats = serde_json::from_str(&serde_json::to_string(&s).unwrap()).unwrap();
+	assert!(back.device_id == s.device_id && back.iterations_completed == 10);
+	// older plugins don't report health metrics
+	assert!(s.temperature_c.is_none() && s.fan_percent.is_none() && s.memory_used_bytes.is_none());
+	assert!(!serde_json::to_string(&s).unwrap().contains("temperature_c"));
+
+	let s: PluginDeviceStats = serde_json::from_str(
+		r#"{"device_id": "1", "cuckoo_size": "30", "device_name": "GeForce GTX 1070", "in_use": 1, "has_errored": 0,
+		"last_start_time": 1, "last_end_time": 2, "last_solution_time": 1, "iterations_completed": 10,
+		"temperature_c": 71.5, "fan_percent": 60, "memory_used_bytes": 6442450944}"#,
+	).unwrap();
+	assert!(s.temperature_c == Some(71.5) && s.fan_percent == Some(60));
+	let back: PluginDeviceStats = serde_json::from_str(&serde_json::to_string(&s).unwrap()).unwrap();
+	assert!(back.memory_used_bytes == Some(6 << 30));
+	assert!(s.host_memory_bytes.is_none());
+
+	// device memory is reported as device_memory_bytes by newer plugins,
+	// alongside the plugin's host memory
+	let s: PluginDeviceStats = serde_json::from_str(
+		r#"{"device_id": "1", "cuckoo_size": "30", "device_name": "GeForce GTX 1070", "in_use": 1, "has_errored": 0,
+		"last_start_time": 1, "last_end_time": 2, "last_solution_time": 1, "iterations_completed": 10,
+		"device_memory_bytes": 6442450944, "host_memory_bytes": 1073741824}"#,
+	).unwrap();
+	assert!(s.memory_used_bytes == Some(6 << 30) && s.host_memory_bytes == Some(1 << 30));
+	let back: PluginDeviceStats = serde_json::from_str(&serde_json::to_string(&s).unwrap()).unwrap();
+	assert!(back.memory_used_bytes == Some(6 << 30) && back.host_memory_bytes == Some(1 << 30));
+
+	// errors are reported as a bool with a reason, or as 0/1 by older plugins
+	assert!(!s.has_errored && s.error_reason.is_empty());
+	let s: PluginDeviceStats = serde_json::from_str(
+		r#"{"device_id": "1", "cuckoo_size": "30", "device_name": "GeForce GTX 1070", "in_use": 1, "has_errored": true,
+		"error_reason": "out of memory", "last_start_time": 1, "last_end_time": 2, "last_solution_time": 1,
+		"iterations_completed": 10}"#,
+	).unwrap();
+	assert!(s.has_errored && s.error_reason == "out of memory");
+	let back: PluginDeviceStats = serde_json::from_str(&serde_json::to_string(&s).unwrap()).unwrap();
+	assert!(back.has_errored && back.error_reason == s.error_reason);
+	let s: PluginDeviceStats = serde_json::from_str(
+		r#"{"device_id": "1", "cuckoo_size": "30", "device_name": "GeForce GTX 1070", "in_use": 1, "has_errored": 1,
+		"last_start_time": 1, "last_end_time": 2, "last_solution_time": 1, "iterations_completed": 10}"#,
+	).unwrap();
+	assert!(s.has_errored && s.error_reason.is_empty());
+
+	// device lists, including an empty one from a plugin with no devices
+	let d: Vec<DeviceInfo> = serde_json::from_str(
+		r#"[{"device_id": 0, "name": "GeForce GTX 1080", "total_memory": 8589934592, "usable": true}]"#,
+	).unwrap();
+	assert!(d[0].total_memory == 8 << 30 && d[0].usable);
+	let back: Vec<DeviceInfo> = serde_json::from_str(&serde_json::to_string(&d).unwrap()).unwrap();
+	assert!(back == d);
+	let none: Vec<DeviceInfo> = serde_json::from_str("[]").unwrap();
+	assert!(none.is_empty());
+}
+
+#[test]
+fn benchmark_reports_are_json_in_milliseconds() {
+	let ms = Duration::from_millis;
+	let mut r = BenchmarkReport::new("lean_cpu_16", BenchmarkMode::Sync, &[ms(10), ms(30)], 1, ms(45));
+	r.phases = BenchmarkPhases {
+		setup: Duration::from_micros(2500),
+		search: ms(40),
+		teardown: ms(2),
+	};
+	let v: serde_json::Value = serde_json::from_str(&r.to_json()).unwrap();
+	assert_eq!(v["plugin"], "lean_cpu_16");
+	assert_eq!(v["mode"], "sync");
+	assert_eq!(v["graphs"], 2);
+	assert_eq!(v["median_graph_time"], 20.0);
+	assert_eq!(v["elapsed"], 45.0);
+	assert_eq!(v["phases"]["setup"], 2.5);
+	assert_eq!(v["phases"]["search"], 40.0);
+
+	let back: BenchmarkReport = serde_json::from_str(&r.to_json()).unwrap();
+	assert_eq!(back.median_graph_time, r.median_graph_time);
+	assert_eq!(back.phases, r.phases);
+	assert_eq!(back.mode, BenchmarkMode::Sync);
+
+	let all: serde_json::Value = serde_json::from_str(&benchmark_json(&[r.clone(), r])).unwrap();
+	assert_eq!(all.as_array().unwrap().len(), 2);
+	assert!(serde_json::from_str::<BenchmarkReport>(&v.to_string().replace("45.0", "-1.0")).is_err());
+}
@@ -0,0 +1,199 @@
+// Copyright 2017 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tests for the CuckooMinerSolution conversions and equality semantics
+
+extern crate cuckoo_miner as cuckoo;
+
+use std::collections::HashSet;
+
+use cuckoo::{CuckooMinerSolution, CuckooMinerError, DuplicateFilter};
+
+// A proof shaped like a cuckoo16 solution: 42 ascending edge nonces
+// within the 2^15 edges of a size 16 graph
+const CUCKOO_16_PROOF: [u64; 42] = [
+	0x1bd, 0x2a6, 0x7d5, 0xa1f, 0xd7a, 0x10b2, 0x12c8, 0x1516, 0x1a49, 0x1bf6,
+	0x1e35, 0x2045, 0x23c0, 0x2618, 0x27b4, 0x2c1e, 0x2f02, 0x3156, 0x3340, 0x36f1,
+	0x3a0d, 0x3c97, 0x3e12, 0x42b4, 0x4433, 0x47e8, 0x4a9c, 0x4d01, 0x4f5e, 0x5204,
+	0x5563, 0x5862, 0x5a1b, 0x5d3f, 0x6060, 0x62d7, 0x6651, 0x68ad, 0x6b23, 0x6e8f,
+	0x7134, 0x7e6b,
+];
+
+fn known_solution() -> CuckooMinerSolution {
+	let mut s = CuckooMinerSolution::new();
+	s.cuckoo_size = 16;
+	s.set_solution(&CUCKOO_16_PROOF);
+	s.nonce = 0x0102_0304_0506_0708;
+	s
+}
+
+#[test]
+fn solution_u64_round_trip() {
+	let s = known_solution();
+	let u64s = s.to_u64s();
+	assert!(u64s.len() == 42);
+	assert!(u64s[0] == 0x1bd && u64s[41] == 0x7e6b);
+	let back = CuckooMinerSolution::from_u64s(&u64s).unwrap();
+	assert!(back.solution_nonces[..] == CUCKOO_16_PROOF[..]);
+	assert!(back == s);
+
+	match CuckooMinerSolution::from_u64s(&u64s[..41]) {
+		Err(CuckooMinerError::SolutionError(_)) => {},
+		r => panic!("Expected SolutionError, got {:?}", r),
+	}
+	// nonces too big for a u32 are kept whole, for larger graphs
+	let mut big = u64s.clone();
+	big[3] = 1 << 32;
+	let s = CuckooMinerSolution::from_u64s(&big).unwrap();
+	assert!(s.solution_nonces[3] == 1 << 32);
+}
+
+#[test]
+fn solution_byte_encoding() {
+	let s = known_solution();
+	let bytes = s.to_bytes();
+	// 15 edge bits, then 42 15-bit nonces in 79 bytes
+	assert!(bytes.len() == 80);
+	assert!(bytes[0] == 15);
+	// 0x1bd, then the low bit of 0x2a6 (0) at bit 15
+	assert!(bytes[1] == 0xbd && bytes[2] == 0x01);
+	let back = CuckooMinerSolution::from_bytes(&bytes, 42).unwrap();
+	assert!(back == s);
+	assert!(back.cuckoo_size == 16);
+
+	let mut s = known_solution();
+	s.cuckoo_size = 41;
+	s.solution_nonces[41] = (1 << 40) - 1;
+	let back = CuckooMinerSolution::from_bytes(&s.to_bytes(), 42).unwrap();
+	assert!(back.solution_nonces[41] == (1 << 40) - 1);
+
+	let mut padded = known_solution().to_bytes();
+	padded[79] |= 0x80;
+	for bad in [&bytes[..79], &padded[..], &[0u8][..], &[65u8][..], &[][..]].iter() {
+		match CuckooMinerSolution::from_bytes(bad, 42) {
+			Err(CuckooMinerError::SolutionError(_)) => {},
+			r => panic!("Expected SolutionError, got {:?}", r),
+		}
+	}
+	assert!(CuckooMinerSolution::from_bytes(&bytes, 41).is_err());
+}
+
+#[test]
+fn solution_64_bit() {
+	let mut s = known_solution();
+	assert!(!s.is_64_bit());
+	let hash_32 = s.hash();
+	s.cuckoo_size = 41;
+	assert!(s.is_64_bit());
+	// each nonce is hashed as 8 bytes rather than 4
+	assert!(s.hash() != hash_32);
+	s.solution_nonces[0] = 1 << 32;
+	assert!(s.to_u64s()[0] == 1 << 32);
+}
+
+#[test]
+fn solution_nonce_bytes() {
+	let mut s = known_solution();
+	assert!(s.nonce_bytes() == [1, 2, 3, 4, 5, 6, 7, 8]);
+	assert!(s.get_nonce_as_u64() == 0x0102_0304_0506_0708);
+	s.set_nonce_bytes(&[0, 0, 0, 0, 0, 0, 1, 0]);
+	assert!(s.nonce == 256);
+}
+
+#[test]
+fn solution_equality_and_dedup() {
+	let a = known_solution();
+	// same proof from a different plugin and nonce is a duplicate
+	let mut b = known_solution();
+	b.nonce = 42;
+	b.plugin_name = Some(String::from("mean_cpu_16"));
+	assert!(a == b);
+
+	let mut c = known_solution();
+	c.solution_nonces[41] += 1;
+	assert!(a != c);
+
+	let mut seen = HashSet::new();
+	assert!(seen.insert(a.clone()));
+	assert!(!seen.insert(b));
+	assert!(seen.insert(c));
+	assert!(seen.len() == 2);
+}
+
+#[test]
+fn solution_display() {
+	let s = known_solution();
+	let display = format!("{}", s);
+	assert!(display.starts_with("[0x1BD, 0x2A6, "));
+	assert!(display.ends_with("0x7134, 0x7E6B]"));
+}
+
+#[test]
+fn solution_difficulty() {
+	let s = known_solution();
+	// blake2b of the proof's nonces as Big-Endian u32s, as in grin
+	assert!(s.hash()[..8] == [0x54, 0xdb, 0x17, 0xd8, 0x1f, 0xd5, 0x60, 0xee]);
+	assert!(s.difficulty() == 3);
+	assert!(s.meets_difficulty(0));
+	assert!(s.meets_difficulty(1));
+	assert!(s.meets_difficulty(3));
+	assert!(!s.meets_difficulty(4));
+	assert!(!s.meets_difficulty(u64::max_value()));
+}
+
+#[test]
+fn solution_other_proof_sizes() {
+	let u64s = CUCKOO_16_PROOF[..12].to_vec();
+	let s = CuckooMinerSolution::from_u64s(&u64s).unwrap();
+	assert!(s.solution_nonces.len() == 12);
+	assert!(s.to_u64s() == u64s);
+	assert!(s != known_solution());
+	// only the proof's own nonces are hashed
+	assert!(s.hash()[..8] == [0xdc, 0x49, 0x1c, 0x57, 0xd0, 0xdb, 0xe5, 0x20]);
+	assert!(s.difficulty() == 1);
+
+	// a cycle needs an even number of edges
+	assert!(CuckooMinerSolution::from_u64s(&u64s[..11]).is_err());
+	assert!(CuckooMinerSolution::from_u64s(&[]).is_err());
+}
+
+#[test]
+fn duplicate_filter() {
+	let mut filter = DuplicateFilter::new(2);
+	let a = known_solution();
+	assert!(filter.insert(&a));
+	assert!(!filter.insert(&a.clone()));
+
+	// the same proof for another nonce isn't a repeat
+	let mut b = known_solution();
+	b.nonce += 1;
+	assert!(filter.insert(&b));
+	assert!(filter.len() == 2);
+
+	// a third solution pushes the oldest out of the window
+	let mut c = known_solution();
+	c.solution_nonces[0] += 1;
+	assert!(filter.insert(&c));
+	assert!(filter.len() == 2);
+	assert!(filter.insert(&a));
+	assert!(!filter.insert(&c));
+
+	// e.g. on a job switch
+	filter.clear();
+	assert!(filter.is_empty());
+	assert!(filter.insert(&c));
+
+	let mut off = DuplicateFilter::new(0);
+	assert!(off.insert(&a) && off.insert(&a));
+}
@@ -0,0 +1,118 @@
+// Copyright 2017 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tests of the streams of solutions the `async` feature adds, using the
+//! mock plugin to give a solution for every header
+
+extern crate cuckoo_miner as cuckoo;
+extern crate futures_executor;
+
+use std::thread;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+
+use futures_executor::block_on_stream;
+
+use cuckoo::{CuckooMiner, CuckooMinerConfig, PluginState, verify};
+
+pub mod common;
+
+#[test]
+fn streams_get_the_same_solutions_as_iterators() {
+	let mut config = CuckooMinerConfig::new();
+	config.plugin_full_path = common::mock_plugin_copy();
+	config.parameter_list.push((String::from("MOCK_SOLVE_ALL"), 0, 1));
+	config.parameter_list.push((String::from("MOCK_DELAY_MS"), 0, 100));
+	let mut miner = CuckooMiner::new(vec![config]).unwrap();
+	// the mock's solutions for most headers don't verify
+	miner.set_verify_solutions(false);
+	let handle = miner
+		.notify(1, common::SAMPLE_GRIN_PRE_HEADER_1, common::SAMPLE_GRIN_POST_HEADER_1, 0, true)
+		.unwrap();
+
+	let stream = handle.solution_stream();
+	let iter = handle.solutions();
+	let streamed = thread::spawn(move || block_on_stream(stream).map(|s| s.nonce).collect::<Vec<_>>());
+	let iterated = thread::spawn(move || iter.map(|s| s.nonce).collect::<Vec<_>>());
+	// a dropped stream leaves the others be
+	drop(handle.solution_stream());
+	thread::sleep(Duration::from_millis(800));
+	handle.stop_jobs();
+
+	let streamed = streamed.join().unwrap();
+	assert!(!streamed.is_empty());
+	assert_eq!(streamed, iterated.join().unwrap());
+
+	// a stream over a stopped job ends straight away
+	assert_eq!(block_on_stream(handle.solution_stream()).count(), 0);
+}
+
+// Starts mining the sample header from the nonce with a known solution,
+// with the job owned by a stream
+fn stream_sample(delay_ms: u32) -> cuckoo::SolutionStream {
+	let mut config = CuckooMinerConfig::new();
+	config.plugin_full_path = common::mock_plugin_copy();
+	config.parameter_list.push((String::from("MOCK_DELAY_MS"), 0, delay_ms));
+	let mut miner = CuckooMiner::new(vec![config]).unwrap();
+	miner.set_start_nonce(common::SAMPLE_GRIN_16_NONCE_1);
+	miner
+		.notify_stream(1, common::SAMPLE_GRIN_PRE_HEADER_1, common::SAMPLE_GRIN_POST_HEADER_1, 0, true)
+		.unwrap()
+}
+
+#[test]
+fn notified_streams_get_verified_solutions() {
+	let mut stream = stream_sample(0);
+	let flag = stream.job().unwrap().shutdown_flag();
+	let solution = block_on_stream(&mut stream).next().unwrap();
+	assert_eq!(solution.nonce, common::SAMPLE_GRIN_16_NONCE_1);
+	assert_eq!(solution.id, 1);
+	let header = cuckoo::header_hash(
+		&cuckoo::from_hex(common::SAMPLE_GRIN_PRE_HEADER_1).unwrap(),
+		&cuckoo::from_hex(common::SAMPLE_GRIN_POST_HEADER_1).unwrap(),
+		solution.nonce,
+	);
+	assert!(verify(&header, solution.cuckoo_size, &solution.solution_nonces).is_ok());
+
+	// stopping the job through the stream ends it
+	stream.job().unwrap().stop_jobs();
+	assert!(block_on_stream(&mut stream).all(|s| s.id == 1));
+	assert!(!flag.load(Ordering::SeqCst));
+}
+
+#[test]
+fn dropping_a_notified_stream_shuts_its_job_down() {
+	let stream = stream_sample(100);
+	let flag = stream.job().unwrap().shutdown_flag();
+	let libraries = stream.job().unwrap().library.clone();
+	drop(stream);
+	assert!(flag.load(Ordering::SeqCst));
+	let start = Instant::now();
+	while libraries.read().unwrap().iter().any(|l| l.state() != PluginState::Unloaded) {
+		assert!(start.elapsed() < Duration::from_secs(10), "Job not shut down");
+		thread::sleep(Duration::from_millis(10));
+	}
+}
+
+#[test]
+fn a_job_taken_from_its_stream_keeps_mining() {
+	let mut stream = stream_sample(100);
+	let handle = stream.take_job().unwrap();
+	assert!(stream.job().is_none());
+	drop(stream);
+	assert!(!handle.shutdown_flag().load(Ordering::SeqCst));
+	thread::sleep(Duration::from_millis(300));
+	assert!(handle.solutions_found() > 0);
+	handle.stop_jobs();
+}
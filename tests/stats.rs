@@ -0,0 +1,357 @@
+// Copyright 2017 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+/// Tests for the rolling stats collector, using synthetic device stats
+
+extern crate cuckoo_miner as cuckoo;
+
+use std::time::{Duration, Instant};
+
+use cuckoo::{PluginDeviceStats, StatsCollector, Watchdog, StallMonitor, ThermalMonitor, ThermalChange,
+	FailureTracker, ONE_MINUTE};
+
+fn device(id: &str, iterations: u32) -> PluginDeviceStats {
+	PluginDeviceStats {
+		plugin_name: None,
+		device_id: String::from(id),
+		cuckoo_size: String::from("30"),
+		device_name: format!("Device {}", id),
+		in_use: 1,
+		has_errored: false,
+		error_reason: String::from(""),
+		last_start_time: 0,
+		last_end_time: 0,
+		last_solution_time: 0,
+		iterations_completed: iterations,
+		temperature_c: None,
+		fan_percent: None,
+		memory_used_bytes: None,
+		host_memory_bytes: None,
+	}
+}
+
+fn errored_device(id: &str, iterations: u32) -> PluginDeviceStats {
+	let mut d = device(id, iterations);
+	d.has_errored = true;
+	d.error_reason = String::from("out of memory");
+	d
+}
+
+fn hot_device(id: &str, temperature_c: Option<f32>) -> PluginDeviceStats {
+	let mut d = device(id, 0);
+	d.temperature_c = temperature_c;
+	d
+}
+
+fn near(a: f64, b: f64) -> bool {
+	(a - b).abs() < 0.0001
+}
+
+#[test]
+fn stats_graphs_per_second() {
+	let mut c = StatsCollector::new(Duration::from_secs(10), Duration::from_secs(1));
+	let start = Instant::now();
+	assert!(c.graphs_per_second() == 0.0);
+
+	// graphs completed before the first sample aren't counted
+	c.sample_at(0, &[device("0", 100)], start);
+	assert!(near(c.graphs_per_second(), 0.0));
+
+	for i in 1..6 {
+		c.sample_at(0, &[device("0", 100 + i * 2)], start + Duration::from_secs(i as u64));
+	}
+	assert!(near(c.graphs_per_second(), 2.0));
+	assert!(near(c.device_graphs_per_second(0, "0").unwrap(), 2.0));
+	assert!(c.device_graphs_per_second(0, "1").is_none());
+
+	// beyond the window, older graphs drop out
+	for i in 6..30 {
+		let iterations = if i < 20 { 100 + i * 2 } else { 138 + (i - 19) };
+		c.sample_at(0, &[device("0", iterations)], start + Duration::from_secs(i as u64));
+	}
+	assert!(near(c.graphs_per_second(), 1.0));
+}
+
+#[test]
+fn stats_counter_reset() {
+	let mut c = StatsCollector::new(Duration::from_secs(10), Duration::from_secs(1));
+	let start = Instant::now();
+	c.sample_at(0, &[device("0", 50)], start);
+	c.sample_at(0, &[device("0", 60)], start + Duration::from_secs(5));
+	// plugin reset, and completed 10 more since
+	c.sample_at(0, &[device("0", 10)], start + Duration::from_secs(10));
+	assert!(near(c.graphs_per_second(), 2.0));
+}
+
+#[test]
+fn stats_devices_come_and_go() {
+	let mut c = StatsCollector::new(Duration::from_secs(10), Duration::from_secs(1));
+	let start = Instant::now();
+	c.sample_at(0, &[device("0", 0)], start);
+	c.sample_at(1, &[device("0", 0)], start);
+	c.sample_at(0, &[device("0", 10), device("1", 0)], start + Duration::from_secs(5));
+	c.sample_at(1, &[device("0", 5)], start + Duration::from_secs(5));
+	assert!(near(c.graphs_per_second(), 3.0));
+	assert!(c.device_graphs_per_second(0, "1").is_some());
+
+	// plugin 1's device disappears, and is dropped once it hasn't been
+	// seen for a full window
+	c.sample_at(0, &[device("0", 20), device("1", 10)], start + Duration::from_secs(10));
+	assert!(c.device_graphs_per_second(1, "0").is_some());
+	c.sample_at(0, &[device("0", 30), device("1", 20)], start + Duration::from_secs(16));
+	assert!(c.device_graphs_per_second(1, "0").is_none());
+}
+
+#[test]
+fn stats_exclude_errored_devices() {
+	let mut c = StatsCollector::new(Duration::from_secs(10), Duration::from_secs(1));
+	let start = Instant::now();
+	c.sample_at(0, &[device("0", 0), device("1", 0)], start);
+	for i in 1..6 {
+		let at = start + Duration::from_secs(i as u64);
+		c.sample_at(0, &[device("0", i * 2), device("1", i)], at);
+	}
+	assert!(near(c.graphs_per_second(), 3.0));
+
+	// device 1 errors, and no longer counts towards the total
+	c.sample_at(0, &[device("0", 12), errored_device("1", 5)], start + Duration::from_secs(6));
+	assert!(near(c.graphs_per_second(), 2.0));
+	assert!(c.device_graphs_per_second(0, "1").is_some());
+
+	// until it recovers
+	c.sample_at(0, &[device("0", 14), device("1", 6)], start + Duration::from_secs(7));
+	assert!(c.graphs_per_second() > 2.0);
+}
+
+#[test]
+fn stats_solutions_found() {
+	let mut c = StatsCollector::default();
+	assert!(c.solutions_found() == 0);
+	c.record_solution();
+	c.record_solution();
+	assert!(c.solutions_found() == 2);
+}
+
+#[test]
+fn stats_device_snapshots() {
+	let mut c = StatsCollector::new(Duration::from_secs(10), Duration::from_secs(1));
+	assert!(c.device_snapshots().is_empty());
+	let start = Instant::now();
+	c.sample_at(1, &[hot_device("0", Some(65.0))], start);
+	c.sample_at(0, &[device("1", 0), device("0", 0)], start);
+	c.sample_at(0, &[device("1", 10), errored_device("0", 5)], start + Duration::from_secs(5));
+	let snapshots = c.device_snapshots();
+	let ids: Vec<(usize, &str)> = snapshots.iter().map(|s| (s.plugin_index, s.device_id.as_str())).collect();
+	assert_eq!(ids, vec![(0, "0"), (0, "1"), (1, "0")]);
+	assert!(snapshots[0].has_errored);
+	assert!(near(snapshots[1].graphs_per_second, 2.0));
+	assert_eq!(snapshots[2].temperature_c, Some(65.0));
+
+	c.record_plugin_error(1);
+	c.record_plugin_error(1);
+	assert_eq!(c.plugin_errors(0), 0);
+	assert_eq!(c.plugin_errors(1), 2);
+}
+
+#[test]
+fn hashrate_moving_averages() {
+	let mut c = StatsCollector::new(ONE_MINUTE, Duration::from_secs(10));
+	let start = Instant::now();
+	assert!(c.hashrate().session == 0.0);
+
+	// 2 graphs a second for four minutes, then 0.5 a second for two
+	let mut iterations = 1000;
+	c.sample_at(0, &[device("0", iterations)], start);
+	for i in 1..37 {
+		iterations += if i <= 24 { 20 } else { 5 };
+		let now = start + Duration::from_secs(i * 10);
+		c.sample_at(0, &[device("0", iterations)], now);
+		// a second plugin's device, joining for the last half minute
+		if i >= 33 {
+			c.sample_at(1, &[device("0", (i as u32 - 33) * 10)], now);
+		}
+	}
+	let first = c.device_hashrate(0, "0").unwrap();
+	assert!(near(first.one_minute, 0.5));
+	assert!(near(first.five_minutes, 1.4));
+	assert!(near(first.session, 1.5));
+	assert!(near(first.one_minute, c.device_graphs_per_second(0, "0").unwrap()));
+	let second = c.device_hashrate(1, "0").unwrap();
+	assert!(near(second.one_minute, 1.0));
+	assert!(near(second.five_minutes, 1.0));
+	assert!(near(second.session, 1.0));
+	assert!(c.device_hashrate(0, "1").is_none());
+
+	let total = c.hashrate();
+	assert!(near(total.one_minute, 1.5));
+	assert!(near(total.five_minutes, 2.4));
+	assert!(near(total.session, 2.5));
+	let snapshots = c.device_snapshots();
+	assert_eq!(snapshots.iter().map(|d| d.plugin_index).collect::<Vec<_>>(), vec![0, 1]);
+	assert_eq!(snapshots[0].hashrate, first);
+
+	// a device which stops reporting is dropped after a window, as usual
+	c.sample_at(1, &[device("0", 30)], start + Duration::from_secs(420));
+	assert!(c.device_hashrate(0, "0").is_none());
+}
+
+#[test]
+fn device_stats_are_typed() {
+	let mut d = device("3", 12);
+	d.last_solution_time = 250;
+	assert_eq!(d.error(), None);
+	assert_eq!(d.last_solve_time(), Some(Duration::from_millis(250)));
+	assert!(near(d.graphs_per_second(), 4.0));
+
+	// nothing finished yet
+	let d = device("0", 0);
+	assert_eq!(d.last_solve_time(), None);
+	assert!(near(d.graphs_per_second(), 0.0));
+
+	assert_eq!(errored_device("1", 5).error(), Some("out of memory"));
+	let mut d = errored_device("1", 5);
+	d.error_reason = String::new();
+	assert_eq!(d.error(), Some(""));
+}
+
+#[test]
+fn watchdog_spots_hung_plugins() {
+	let mut w = Watchdog::new(Duration::from_secs(10));
+	let start = Instant::now();
+	let at = |secs: u64| start + Duration::from_secs(secs);
+
+	// the first check starts the clock
+	assert!(!w.check_at(0, &[device("0", 5), device("1", 5)], at(0)));
+	assert!(!w.check_at(1, &[device("0", 0)], at(0)));
+
+	// plugin 0 keeps attempting graphs on one device
+	for i in 1..30 {
+		assert!(!w.check_at(0, &[device("0", 5), device("1", 5 + i as u32)], at(i)));
+	}
+	// plugin 1 never does
+	assert!(!w.check_at(1, &[device("0", 0)], at(9)));
+	assert!(w.check_at(1, &[device("0", 0)], at(10)));
+
+	// counters going backwards, e.g. after a reset, count as progress
+	assert!(!w.check_at(0, &[device("0", 0), device("1", 0)], at(45)));
+	assert!(w.check_at(0, &[device("0", 0), device("1", 0)], at(55)));
+
+	// forgotten plugins start again from scratch
+	w.forget(1);
+	assert!(!w.check_at(1, &[device("0", 0)], at(60)));
+	assert!(w.check_at(1, &[device("0", 0)], at(70)));
+}
+
+#[test]
+fn stall_monitor_warns_once_per_interval() {
+	let mut s = StallMonitor::new(Duration::from_secs(10));
+	let start = Instant::now();
+	let at = |secs: u64| start + Duration::from_secs(secs);
+
+	assert!(!s.check_at(0, &[device("0", 5)], at(0)));
+	assert!(!s.check_at(0, &[device("0", 5)], at(9)));
+	assert!(s.check_at(0, &[device("0", 5)], at(10)));
+
+	// not again until another interval has passed
+	assert!(!s.check_at(0, &[device("0", 5)], at(11)));
+	assert!(s.check_at(0, &[device("0", 5)], at(20)));
+
+	// a finished graph is progress
+	assert!(!s.check_at(0, &[device("0", 6)], at(21)));
+	assert!(!s.check_at(0, &[device("0", 6)], at(30)));
+
+	// and so is a solution
+	s.record_solution(0, at(30));
+	assert!(!s.check_at(0, &[device("0", 6)], at(39)));
+	assert!(s.check_at(0, &[device("0", 6)], at(40)));
+
+	s.forget(0);
+	assert!(!s.check_at(0, &[device("0", 6)], at(45)));
+}
+
+#[test]
+fn stats_collector_keeps_queue_depths() {
+	let mut c = StatsCollector::new(Duration::from_secs(60), Duration::from_secs(0));
+	assert_eq!(c.queue_depths(0), None);
+	c.record_queue_depths(0, (12, 0));
+	c.record_queue_depths(1, (0, 3));
+	c.record_queue_depths(0, (4, 1));
+	assert_eq!(c.queue_depths(0), Some((4, 1)));
+	assert_eq!(c.queue_depths(1), Some((0, 3)));
+}
+
+#[test]
+fn thermal_monitor_pauses_hot_devices() {
+	let mut t = ThermalMonitor::new(85.0);
+
+	// devices without a temperature, like CPUs, are left alone
+	assert!(t.check(0, &[hot_device("0", None)]).is_empty());
+	assert!(t.check(0, &[hot_device("0", Some(85.0)), hot_device("1", Some(70.0))]).is_empty());
+
+	let changes = t.check(0, &[hot_device("0", Some(86.5)), hot_device("1", Some(70.0))]);
+	assert!(changes == vec![ThermalChange::Paused { device_id: 0, temperature_c: 86.5 }]);
+	assert!(t.is_paused(0, 0));
+	assert!(!t.is_paused(0, 1));
+	assert!(!t.is_paused(1, 0));
+
+	// still hot, or only just below the maximum, stays paused quietly
+	assert!(t.check(0, &[hot_device("0", Some(90.0))]).is_empty());
+	assert!(t.check(0, &[hot_device("0", Some(82.0))]).is_empty());
+	assert!(t.is_paused(0, 0));
+
+	// below the hysteresis threshold it resumes
+	let changes = t.check(0, &[hot_device("0", Some(79.0))]);
+	assert!(changes == vec![ThermalChange::Resumed { device_id: 0, temperature_c: 79.0 }]);
+	assert!(!t.is_paused(0, 0));
+
+	let mut t = ThermalMonitor::new(85.0).with_hysteresis(0.0);
+	assert!(t.check(2, &[hot_device("3", Some(86.0))]).len() == 1);
+	assert!(t.check(2, &[hot_device("3", Some(84.9))]).len() == 1);
+}
+
+#[test]
+fn failure_tracker_blacklists_repeat_offenders() {
+	let mut t = FailureTracker::new(3, Duration::from_secs(60));
+	let start = Instant::now();
+	let at = |secs: u64| start + Duration::from_secs(secs);
+
+	assert!(!t.record_failure_at(0, at(0)));
+	assert!(!t.record_failure_at(0, at(1)));
+	assert!(t.failures(0) == 2);
+	assert!(!t.is_blacklisted(0));
+
+	// the third failure benches it, other plugins are unaffected
+	assert!(!t.record_failure_at(1, at(2)));
+	assert!(t.record_failure_at(0, at(2)));
+	assert!(t.is_blacklisted(0));
+	assert!(!t.is_blacklisted(1));
+
+	// failures while benched don't count again
+	assert!(!t.record_failure_at(0, at(3)));
+
+	// it's retried once the cool-down has passed
+	assert!(!t.retry_due_at(0, at(61)));
+	assert!(t.retry_due_at(0, at(62)));
+	t.begin_retry_at(0, at(62));
+	assert!(!t.is_blacklisted(0));
+
+	// failing again soon after the retry benches it straight away
+	assert!(t.record_failure_at(0, at(100)));
+	assert!(t.retry_due_at(0, at(160)));
+	t.begin_retry_at(0, at(160));
+
+	// while a failure long after the retry counts as usual
+	assert!(!t.record_failure_at(0, at(300)));
+	assert!(!t.is_blacklisted(0));
+}
@@ -0,0 +1,169 @@
+// Copyright 2017 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tests of mining for a stratum pool, against a pool on the loopback
+//! interface and the mock plugin
+
+extern crate cuckoo_miner as cuckoo;
+extern crate serde_json;
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde_json::Value;
+
+use cuckoo::{CuckooMiner, CuckooMinerConfig, StratumOptions, StratumWorker};
+
+pub mod common;
+
+// One connection from the worker to the pool
+struct PoolConnection {
+	reader: BufReader<TcpStream>,
+	writer: TcpStream,
+}
+
+impl PoolConnection {
+	fn accept(listener: &TcpListener) -> PoolConnection {
+		let (stream, _) = listener.accept().unwrap();
+		stream.set_read_timeout(Some(Duration::from_secs(10))).unwrap();
+		PoolConnection {
+			reader: BufReader::new(stream.try_clone().unwrap()),
+			writer: stream,
+		}
+	}
+
+	// Reads the next request, which is expected to call `method`
+	fn expect(&mut self, method: &str) -> Value {
+		let mut line = String::new();
+		self.reader.read_line(&mut line).unwrap();
+		let request: Value = serde_json::from_str(&line).unwrap();
+		assert_eq!(request["jsonrpc"], "2.0", "{}", line);
+		assert_eq!(request["method"], method, "{}", line);
+		request
+	}
+
+	fn send(&mut self, message: String) {
+		self.writer.write_all(message.as_bytes()).unwrap();
+		self.writer.write_all(b"\n").unwrap();
+	}
+
+	fn reply(&mut self, request: &Value, result: &str) {
+		self.send(format!("{{\"id\":{},\"jsonrpc\":\"2.0\",\"result\":{}}}", request["id"], result));
+	}
+
+	// Answers the worker's login and job template requests
+	fn log_in(&mut self, job_id: u32, height: u64) {
+		let login = self.expect("login");
+		assert_eq!(login["params"]["login"], "worker");
+		assert_eq!(login["params"]["pass"], "secret");
+		self.reply(&login, "\"ok\"");
+		let template = self.expect("getjobtemplate");
+		self.reply(&template, &job(job_id, height, false));
+	}
+
+	// Reads a share, accepting it, and returns its job id
+	fn share(&mut self) -> u64 {
+		let submit = self.expect("submit");
+		let params = &submit["params"];
+		assert_eq!(params["edge_bits"], 15);
+		assert_eq!(params["pow"].as_array().unwrap().len(), 42);
+		assert!(params["nonce"].is_u64());
+		self.reply(&submit, "\"ok\"");
+		params["job_id"].as_u64().unwrap()
+	}
+}
+
+fn job(job_id: u32, height: u64, clean_jobs: bool) -> String {
+	format!(
+		"{{\"job_id\":{},\"height\":{},\"difficulty\":0,\"pre_pow\":\"{}\",\"clean_jobs\":{}}}",
+		job_id,
+		height,
+		common::SAMPLE_GRIN_PRE_HEADER_1,
+		clean_jobs
+	)
+}
+
+// Starts a worker mining with the mock, which solves every header
+fn start_worker(listener: &TcpListener) -> StratumWorker {
+	let mut config = CuckooMinerConfig::new();
+	config.plugin_full_path = common::mock_plugin_copy();
+	config.parameter_list.push((String::from("MOCK_SOLVE_ALL"), 0, 1));
+	config.parameter_list.push((String::from("MOCK_DELAY_MS"), 0, 20));
+	let mut miner = CuckooMiner::new(vec![config]).unwrap();
+	// the mock's solutions for other headers don't verify
+	miner.set_verify_solutions(false);
+	StratumWorker::start(
+		miner,
+		StratumOptions {
+			server: listener.local_addr().unwrap().to_string(),
+			login: String::from("worker"),
+			password: String::from("secret"),
+			min_backoff: Duration::from_millis(100),
+			..StratumOptions::default()
+		},
+	)
+}
+
+#[test]
+fn jobs_are_mined_and_shares_submitted() {
+	let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+	let worker = start_worker(&listener);
+	let mut pool = PoolConnection::accept(&listener);
+	pool.log_in(7, 100);
+	assert_eq!(pool.share(), 7);
+
+	// a new block invalidates the old job, so once its shares arrive, no
+	// more for the old one do
+	pool.send(format!("{{\"jsonrpc\":\"2.0\",\"method\":\"job\",\"params\":{}}}", job(8, 101, true)));
+	while pool.share() != 8 {}
+	for _ in 0..5 {
+		assert_eq!(pool.share(), 8);
+	}
+	// the pool's replies are read between shares
+	let start = Instant::now();
+	while worker.stats().shares_accepted < 6 && start.elapsed() < Duration::from_secs(10) {
+		thread::sleep(Duration::from_millis(10));
+	}
+	let stats = worker.stats();
+	assert!(stats.connected);
+	assert_eq!(stats.job_id, Some(8));
+	assert!(stats.shares_accepted >= 6, "{:?}", stats);
+	assert!(stats.shares_submitted >= stats.shares_accepted);
+	worker.stop().unwrap();
+}
+
+#[test]
+fn shares_survive_a_reconnect() {
+	let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+	let worker = start_worker(&listener);
+	{
+		let mut pool = PoolConnection::accept(&listener);
+		pool.log_in(3, 50);
+		assert_eq!(pool.share(), 3);
+	}
+	// the pool has dropped the worker, which keeps mining the job,
+	// logs in again, and carries on submitting shares for it before the
+	// pool has sent a job
+	let mut pool = PoolConnection::accept(&listener);
+	let login = pool.expect("login");
+	pool.reply(&login, "\"ok\"");
+	let _template = pool.expect("getjobtemplate");
+	assert_eq!(pool.share(), 3);
+	let stats = worker.stats();
+	assert_eq!(stats.reconnects, 1);
+	assert!(stats.connected);
+	worker.stop().unwrap();
+}
@@ -18,6 +18,8 @@
 extern crate cuckoo_miner as cuckoo;
 extern crate time;
 
+use std::sync::atomic::Ordering;
+
 pub mod common;
 
 //mines plugin as requsted
@@ -72,6 +74,59 @@ fn on_cuda_meaner_commit_mine_sync() {
 	}
 }
 
+//Mines through a range of nonces on the sample header, which should
+//stop at the first one with a solution
+#[test]
+fn on_commit_mine_nonce_range() {
+	let caps = common::get_plugin_vec("lean_cpu_16");
+	let mut config = cuckoo::CuckooMinerConfig::new();
+	config.plugin_full_path = caps[0].full_path.clone();
+	let miner = cuckoo::CuckooMiner::new(vec![config]).unwrap();
+	let header_pre = cuckoo::from_hex(common::SAMPLE_GRIN_PRE_HEADER_1).unwrap();
+	let header_post = cuckoo::from_hex(common::SAMPLE_GRIN_POST_HEADER_1).unwrap();
+
+	let solution = miner.mine(&header_pre, &header_post, 0, Some(64)).unwrap()
+		.expect("No solution found in nonce range");
+	assert!(solution.nonce == common::SAMPLE_GRIN_16_NONCE_1);
+	assert!(solution.cuckoo_size == 16);
+	assert!(solution.solution_nonces == common::widen(&common::SAMPLE_GRIN_16_SOLUTION_1));
+
+	// a range which stops short of the solution finds nothing
+	let result = miner.mine(&header_pre, &header_post, 40, Some(10)).unwrap();
+	assert!(result.is_none());
+
+	// nothing is attempted once the stop flag is set
+	miner.stop_flag().store(true, Ordering::SeqCst);
+	let result = miner.mine(&header_pre, &header_post, 0, None).unwrap();
+	assert!(result.is_none());
+}
+
+//Builds a miner, which should report every bad parameter at once, and
+//mines with it once they're fixed
+#[test]
+fn on_commit_builder() {
+	let caps = common::get_plugin_vec("lean_cpu_16");
+	let result = cuckoo::CuckooMinerBuilder::new()
+		.plugin(&caps[0].full_path)
+		.parameter("NUM_THREDS", 1)
+		.parameter("NUM_THREADS", 0)
+		.build();
+	match result {
+		Err(cuckoo::CuckooMinerError::MinerBuildError(errors)) => assert!(errors.len() == 2),
+		_ => panic!("Expected a MinerBuildError"),
+	}
+
+	let miner = cuckoo::CuckooMinerBuilder::new()
+		.plugin(&caps[0].full_path)
+		.parameter("NUM_THREADS", 1)
+		.build()
+		.unwrap();
+	let header_pre = cuckoo::from_hex(common::SAMPLE_GRIN_PRE_HEADER_1).unwrap();
+	let header_post = cuckoo::from_hex(common::SAMPLE_GRIN_POST_HEADER_1).unwrap();
+	let solution = miner.mine(&header_pre, &header_post, 0, Some(64)).unwrap();
+	assert!(solution.is_some());
+}
+
 //test for mean_16 compat
 //(won't be efficient, but should stress-tes plugins nicely)
 #[test]
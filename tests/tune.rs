@@ -0,0 +1,192 @@
+// Copyright 2017 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tests for picking parameter values to sweep, and the best of them
+
+extern crate cuckoo_miner as cuckoo;
+extern crate toml;
+
+use std::{env, fs, process};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use cuckoo::{BenchmarkMode, BenchmarkReport, CuckooMiner, CuckooMinerConfig, CuckooMinerError,
+	CuckooPluginParameter, TuningPoint, TuningResult, combinations, values_in_range};
+
+pub mod common;
+
+fn parameter(name: &str, min_value: u64, max_value: u64) -> CuckooPluginParameter {
+	CuckooPluginParameter {
+		name: String::from(name),
+		description: String::new(),
+		default_value: min_value,
+		min_value: min_value,
+		max_value: max_value,
+	}
+}
+
+// a point which completed `graphs` graphs in a second
+fn point(threads: u32, graphs: usize, aborted: bool) -> TuningPoint {
+	let times = vec![Duration::from_millis(100); graphs];
+	TuningPoint {
+		values: vec![(String::from("NUM_THREADS"), threads)],
+		report: BenchmarkReport::new("lean_cpu_16", BenchmarkMode::Sync, &times, 0, Duration::from_secs(1)),
+		aborted: aborted,
+	}
+}
+
+#[test]
+fn sweeps_cover_every_combination() {
+	let sweep = vec![
+		(String::from("NUM_THREADS"), vec![1, 2]),
+		(String::from("NUM_TRIMS"), vec![8, 16, 32]),
+	];
+	let combos = combinations(&sweep);
+	assert_eq!(combos.len(), 6);
+	assert_eq!(combos[0], vec![(String::from("NUM_THREADS"), 1), (String::from("NUM_TRIMS"), 8)]);
+	assert_eq!(combos[5], vec![(String::from("NUM_THREADS"), 2), (String::from("NUM_TRIMS"), 32)]);
+	assert_eq!(combinations(&[]), vec![Vec::new()]);
+}
+
+#[test]
+fn values_outside_the_declared_range_are_skipped() {
+	let parameters = vec![parameter("NUM_THREADS", 1, 16), parameter("NUM_TRIMS", 8, 256)];
+	let sweep = values_in_range(&[("NUM_THREADS", &[0, 2, 4, 8, 16, 32])], &parameters).unwrap();
+	assert_eq!(sweep, vec![(String::from("NUM_THREADS"), vec![2, 4, 8, 16])]);
+
+	match values_in_range(&[("NUM_TRIMS", &[1, 2, 512])], &parameters) {
+		Err(CuckooMinerError::ParameterOutOfRange { name, .. }) => assert_eq!(name, "NUM_TRIMS"),
+		r => panic!("Expected a ParameterOutOfRange error, got {:?}", r),
+	}
+	match values_in_range(&[("NUM_THREDS", &[1])], &parameters) {
+		Err(CuckooMinerError::ParameterNotFoundError(name)) => assert_eq!(name, "NUM_THREDS"),
+		r => panic!("Expected a ParameterNotFoundError, got {:?}", r),
+	}
+}
+
+#[test]
+fn fastest_combination_wins() {
+	let result = TuningResult::from_points(vec![point(2, 4, false), point(4, 6, false), point(8, 9, true)])
+		.unwrap();
+	assert_eq!(result.best, vec![(String::from("NUM_THREADS"), 4)]);
+	assert!((result.best_graphs_per_second - 6.0).abs() < 1e-9);
+	assert_eq!(result.points.len(), 3);
+
+	// every combination abandoned
+	assert!(TuningResult::from_points(vec![point(2, 1, true)]).is_err());
+}
+
+// A directory of the test's own, for tuning files and plugin copies
+fn tuning_dir(test: &str) -> PathBuf {
+	let mut dir = env::temp_dir();
+	dir.push(format!("cuckoo-tuning-{}", process::id()));
+	dir.push(test);
+	fs::create_dir_all(&dir).unwrap();
+	dir
+}
+
+// A miner on a copy of the mock named `tuned`, so tunings saved from one
+// copy apply to another, with two devices and the given values
+fn tuned_miner(dir: &Path, params: &[(&str, u32)], device_1: &[(&str, u32)]) -> CuckooMiner {
+	fs::create_dir_all(dir).unwrap();
+	let path = dir.join("tuned.cuckooplugin");
+	fs::copy(common::mock_plugin_path(), &path).unwrap();
+	let mut config = CuckooMinerConfig::new();
+	config.plugin_full_path = path.to_string_lossy().into_owned();
+	config.parameter_list.push((String::from("MOCK_DEVICES"), 0, 2));
+	for &(name, value) in params {
+		config.parameter_list.push((String::from(name), 0, value));
+	}
+	for &(name, value) in device_1 {
+		config.device_parameters.entry(1).or_insert_with(Default::default).insert(String::from(name), value);
+	}
+	CuckooMiner::new(vec![config]).unwrap()
+}
+
+fn read_tuning(path: &Path) -> toml::Value {
+	let mut contents = String::new();
+	fs::File::open(path).unwrap().read_to_string(&mut contents).unwrap();
+	contents.parse().unwrap()
+}
+
+#[test]
+fn tuning_round_trips() {
+	let dir = tuning_dir("round_trip");
+	let tuned = tuned_miner(&dir.join("a"), &[("NUM_THREADS", 4), ("QUEUE_SIZE", 50)], &[("NUM_THREADS", 8)]);
+	let saved = dir.join("tuning.toml");
+	tuned.save_tuning(&saved).unwrap();
+	let values = read_tuning(&saved);
+	assert_eq!(values["tuned"]["parameters"]["NUM_THREADS"].as_integer(), Some(4));
+	assert_eq!(values["tuned"]["parameters"]["QUEUE_SIZE"].as_integer(), Some(50));
+	assert_eq!(values["tuned"]["devices"]["1"]["NUM_THREADS"].as_integer(), Some(8));
+	// only values differing from the plugin's are saved per device
+	assert!(values["tuned"]["devices"]["1"].get("QUEUE_SIZE").is_none());
+	assert!(values["tuned"]["devices"].get("0").is_none());
+
+	// a fresh copy of the plugin starts with the saved values
+	let mut fresh = tuned_miner(&dir.join("b"), &[], &[]);
+	fresh.load_tuning(&saved).unwrap();
+	let resaved = dir.join("resaved.toml");
+	fresh.save_tuning(&resaved).unwrap();
+	assert_eq!(read_tuning(&resaved), values);
+	assert!(fresh.configs[0].parameter_list.contains(&(String::from("NUM_THREADS"), 0, 4)));
+	assert_eq!(fresh.configs[0].device_parameters[&1]["NUM_THREADS"], 8);
+}
+
+#[test]
+fn changed_plugins_are_tuned_with_what_still_fits() {
+	let dir = tuning_dir("changed");
+	let saved = dir.join("tuning.toml");
+	// as saved by an older version of the plugin, which had a parameter
+	// since dropped, wider ranges and more devices
+	fs::File::create(&saved)
+		.unwrap()
+		.write_all(
+			b"[tuned.parameters]\n\
+			OLD_PARAMETER = 3\n\
+			NUM_THREADS = 64\n\
+			QUEUE_SIZE = 0\n\
+			[tuned.devices.1]\n\
+			NUM_THREADS = -1\n\
+			OLD_PARAMETER = 5\n\
+			[tuned.devices.7]\n\
+			NUM_THREADS = 2\n\
+			[other_plugin.parameters]\n\
+			NUM_THREADS = 2\n",
+		)
+		.unwrap();
+	let mut miner = tuned_miner(&dir.join("a"), &[], &[]);
+	miner.load_tuning(&saved).unwrap();
+
+	let resaved = dir.join("resaved.toml");
+	miner.save_tuning(&resaved).unwrap();
+	let values = read_tuning(&resaved);
+	assert!(values["tuned"]["parameters"].get("OLD_PARAMETER").is_none());
+	assert_eq!(values["tuned"]["parameters"]["NUM_THREADS"].as_integer(), Some(32));
+	assert_eq!(values["tuned"]["parameters"]["QUEUE_SIZE"].as_integer(), Some(1));
+	assert_eq!(values["tuned"]["devices"]["1"]["NUM_THREADS"].as_integer(), Some(1));
+	assert!(values.get("other_plugin").is_none());
+	assert!(!miner.configs[0].parameter_list.iter().any(|p| p.0 == "OLD_PARAMETER"));
+
+	fs::File::create(&saved).unwrap().write_all(b"[tuned.parameters]\nNUM_THREADS = \"four\"\n").unwrap();
+	match miner.load_tuning(&saved) {
+		Err(CuckooMinerError::ConfigError(m)) => assert!(m.contains("NUM_THREADS"), "{}", m),
+		r => panic!("Expected a ConfigError, got {:?}", r),
+	}
+	match miner.load_tuning(dir.join("missing.toml")) {
+		Err(CuckooMinerError::ConfigError(_)) => {}
+		r => panic!("Expected a ConfigError, got {:?}", r),
+	}
+}
@@ -0,0 +1,127 @@
+// Copyright 2017 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tests of plugins being unloaded when dropped, alone or shared between
+//! threads, using the mock plugin
+
+extern crate cuckoo_miner as cuckoo;
+
+use std::fs;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use cuckoo::{PluginLibrary, PluginState, CALL_REFUSED};
+
+pub mod common;
+
+// Whether a library is mapped into the process, where that can be told
+#[cfg(target_os = "linux")]
+fn is_mapped(path: &str) -> Option<bool> {
+	Some(fs::read_to_string("/proc/self/maps").unwrap().contains(path))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn is_mapped(_path: &str) -> Option<bool> {
+	None
+}
+
+#[test]
+fn shared_plugins_are_one_plugin() {
+	let path = common::mock_plugin_copy();
+	let handle = Arc::new(PluginLibrary::new(&path).unwrap());
+	let shared = handle.clone();
+	handle.set_parameter("NUM_THREADS", 3).unwrap();
+	assert_eq!(shared.get_parameter("NUM_THREADS").unwrap(), 3);
+
+	// an explicit unload is seen by every owner
+	shared.unload();
+	assert_eq!(handle.state(), PluginState::Unloaded);
+	assert_eq!(handle.call_cuckoo_is_queue_under_limit(), 0);
+	let mut params = [0u8; 1024];
+	let mut len = params.len() as u32;
+	assert_eq!(handle.call_cuckoo_parameter_list(&mut params, &mut len), CALL_REFUSED);
+}
+
+#[test]
+fn shared_plugin_unloads_with_its_last_owner() {
+	let path = common::mock_plugin_copy();
+	let handle = Arc::new(PluginLibrary::new(&path).unwrap());
+	let threads: Vec<_> = (0..4)
+		.map(|_| {
+			let handle = handle.clone();
+			thread::spawn(move || {
+				for _ in 0..10 {
+					handle.stats().unwrap();
+				}
+			})
+		})
+		.collect();
+	for t in threads {
+		t.join().unwrap();
+	}
+	assert_eq!(Arc::strong_count(&handle), 1);
+
+	let shared = handle.clone();
+	drop(handle);
+	assert_eq!(shared.state(), PluginState::Idle);
+	assert!(is_mapped(&path).unwrap_or(true));
+	drop(shared);
+	assert!(!is_mapped(&path).unwrap_or(false));
+}
+
+#[test]
+fn load_drop_cycles_leave_nothing_loaded() {
+	let path = common::mock_plugin_copy();
+	for i in 0..50 {
+		match i % 3 {
+			// dropped without being unloaded
+			0 => {
+				let library = PluginLibrary::new(&path).unwrap();
+				library.stats().unwrap();
+			}
+			// dropped while processing. Started on a thread of its own, as
+			// the mock's std registers thread local destructors on the
+			// thread spawning its workers, and glibc keeps a library mapped
+			// until they've run.
+			1 => {
+				let path = path.clone();
+				let library = thread::spawn(move || {
+					let library = PluginLibrary::new(&path).unwrap();
+					library.set_parameter("MOCK_DELAY_MS", 5).unwrap();
+					library.start_processing().unwrap();
+					library
+				}).join()
+					.unwrap();
+				thread::sleep(Duration::from_millis(1));
+				assert_eq!(library.state(), PluginState::Processing);
+			}
+			// shared, and dropped on other threads
+			_ => {
+				let handle = Arc::new(PluginLibrary::new(&path).unwrap());
+				let threads: Vec<_> = (0..2)
+					.map(|_| {
+						let handle = handle.clone();
+						thread::spawn(move || handle.stats().unwrap())
+					})
+					.collect();
+				drop(handle);
+				for t in threads {
+					t.join().unwrap();
+				}
+			}
+		}
+		assert!(!is_mapped(&path).unwrap_or(false), "{} left loaded after cycle {}", path, i);
+	}
+}
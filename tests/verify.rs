@@ -0,0 +1,195 @@
+// Copyright 2017 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tests for pure-Rust proof verification, against a known solution and
+//! mutations of it
+
+extern crate cuckoo_miner as cuckoo;
+extern crate time;
+extern crate rand;
+
+pub mod common;
+
+use cuckoo::{from_hex, verify, verify_nonce, verify_solution, check_bounds, VerifyError, CuckooMinerSolution, SipHashKeys, header_hash,
+	siphash_keys};
+
+use common::{KNOWN_16_HASH_1, KNOWN_16_SOLUTION_1, KNOWN_16_HASH_12, KNOWN_16_SOLUTION_12, KNOWN_30_HASH_1, SAMPLE_GRIN_PRE_HEADER_1, SAMPLE_GRIN_POST_HEADER_1,
+	SAMPLE_GRIN_16_NONCE_1, SAMPLE_GRIN_16_SOLUTION_1};
+
+#[test]
+fn verify_known_solution() {
+	let header = from_hex(KNOWN_16_HASH_1).unwrap();
+	assert!(verify(&header, 16, &KNOWN_16_SOLUTION_1) == Ok(()));
+
+	let mut s = CuckooMinerSolution::new();
+	s.cuckoo_size = 16;
+	s.set_solution(&KNOWN_16_SOLUTION_1);
+	assert!(verify_solution(&header, &s) == Ok(()));
+}
+
+// a full grin header is longer than plugins accept, and is hashed down to
+// 32 bytes with the nonce spliced in first
+#[test]
+fn verify_full_length_header() {
+	let header_pre = from_hex(SAMPLE_GRIN_PRE_HEADER_1).unwrap();
+	let header_post = from_hex(SAMPLE_GRIN_POST_HEADER_1).unwrap();
+	assert!(header_pre.len() + 8 + header_post.len() > 32);
+
+	let hash = header_hash(&header_pre, &header_post, SAMPLE_GRIN_16_NONCE_1);
+	assert!(verify(&hash, 16, &SAMPLE_GRIN_16_SOLUTION_1) == Ok(()));
+
+	// the wrong nonce doesn't verify
+	let hash = header_hash(&header_pre, &header_post, SAMPLE_GRIN_16_NONCE_1 + 1);
+	assert!(verify(&hash, 16, &SAMPLE_GRIN_16_SOLUTION_1) == Err(VerifyError::NonMatchingCycle));
+}
+
+// as a node checks a solution, from the header's parts and the nonce
+#[test]
+fn verify_nonce_against_header_parts() {
+	let header_pre = from_hex(SAMPLE_GRIN_PRE_HEADER_1).unwrap();
+	let header_post = from_hex(SAMPLE_GRIN_POST_HEADER_1).unwrap();
+	let nonce = SAMPLE_GRIN_16_NONCE_1;
+	assert!(verify_nonce(&header_pre, &header_post, nonce, 16, &SAMPLE_GRIN_16_SOLUTION_1) == Ok(()));
+	let wide: Vec<u64> = SAMPLE_GRIN_16_SOLUTION_1.iter().map(|&n| n as u64).collect();
+	assert!(verify_nonce(&header_pre, &header_post, nonce, 16, &wide) == Ok(()));
+
+	assert!(verify_nonce(&header_pre, &header_post, nonce + 1, 16, &SAMPLE_GRIN_16_SOLUTION_1) == Err(VerifyError::NonMatchingCycle));
+	assert!(verify_nonce(&header_post, &header_pre, nonce, 16, &SAMPLE_GRIN_16_SOLUTION_1).is_err());
+	assert!(verify_nonce(&header_pre, &header_post, nonce, 18, &SAMPLE_GRIN_16_SOLUTION_1).is_err());
+
+	let mut bogus = SAMPLE_GRIN_16_SOLUTION_1;
+	bogus[41] = 32264;
+	assert!(verify_nonce(&header_pre, &header_post, nonce, 16, &bogus).is_err());
+
+	// only whole 42-cycles are accepted
+	assert!(verify_nonce(&header_pre, &header_post, nonce, 16, &SAMPLE_GRIN_16_SOLUTION_1[..40]) == Err(VerifyError::WrongProofSize));
+	assert!(verify_nonce::<u32>(&header_pre, &header_post, nonce, 16, &[]) == Err(VerifyError::WrongProofSize));
+}
+
+#[test]
+fn verify_rejects_mutated_proofs() {
+	let header = from_hex(KNOWN_16_HASH_1).unwrap();
+
+	// the same proof for a different header
+	let mut other = header.clone();
+	other[0] ^= 1;
+	assert!(verify(&other, 16, &KNOWN_16_SOLUTION_1) == Err(VerifyError::NonMatchingCycle));
+
+	// an edge swapped for another
+	let mut proof = KNOWN_16_SOLUTION_1;
+	proof[10] += 1;
+	assert!(verify(&header, 16, &proof) == Err(VerifyError::NonMatchingCycle));
+
+	let mut proof = KNOWN_16_SOLUTION_1;
+	proof.swap(3, 4);
+	assert!(verify(&header, 16, &proof) == Err(VerifyError::EdgesNotAscending));
+
+	let mut proof = KNOWN_16_SOLUTION_1;
+	proof[41] = 1 << 15;
+	assert!(verify(&header, 16, &proof) == Err(VerifyError::EdgeTooBig));
+
+	// wrong size for the graph
+	assert!(verify(&header, 18, &KNOWN_16_SOLUTION_1).is_err());
+}
+
+#[test]
+fn check_bounds_of_proofs() {
+	assert!(check_bounds(&KNOWN_16_SOLUTION_1, Some(15)).is_ok());
+
+	let mut proof = KNOWN_16_SOLUTION_1;
+	proof[41] = 1 << 15;
+	assert!(check_bounds(&proof, Some(15)) == Err(VerifyError::EdgeTooBig));
+	// without the edge bits only the order can be checked
+	assert!(check_bounds(&proof, None).is_ok());
+	assert!(check_bounds(&proof, Some(16)).is_ok());
+
+	let mut proof = KNOWN_16_SOLUTION_1;
+	proof[1] = proof[0];
+	assert!(check_bounds(&proof, Some(15)) == Err(VerifyError::EdgesNotAscending));
+	assert!(check_bounds(&proof, None) == Err(VerifyError::EdgesNotAscending));
+}
+
+#[test]
+fn verify_64_bit_proofs() {
+	// nonces too big for a u32 are in bounds in larger graphs
+	let mut proof = common::widen(&KNOWN_16_SOLUTION_1);
+	proof[41] = 1 << 39;
+	assert!(check_bounds(&proof, Some(40)).is_ok());
+	assert!(check_bounds(&proof, Some(39)) == Err(VerifyError::EdgeTooBig));
+	// and are searched for a cycle, which this isn't
+	let header = from_hex(KNOWN_16_HASH_1).unwrap();
+	assert!(verify(&header, 41, &proof) == Err(VerifyError::NonMatchingCycle));
+	// a u64 proof verifies as the same u32 one
+	assert!(verify(&header, 16, &common::widen(&KNOWN_16_SOLUTION_1)).is_ok());
+}
+
+#[test]
+fn verify_other_proof_sizes() {
+	let header = from_hex(KNOWN_16_HASH_12).unwrap();
+	assert!(verify(&header, 16, &KNOWN_16_SOLUTION_12).is_ok());
+	let mut s = CuckooMinerSolution::new();
+	s.cuckoo_size = 16;
+	s.set_solution(&KNOWN_16_SOLUTION_12);
+	assert!(verify_solution(&header, &s).is_ok());
+
+	// a 12-cycle isn't a 42-cycle, or any other length
+	assert!(verify(&header, 16, &KNOWN_16_SOLUTION_12[..10]) == Err(VerifyError::NonMatchingCycle));
+	let header = from_hex(KNOWN_16_HASH_1).unwrap();
+	assert!(verify(&header, 16, &KNOWN_16_SOLUTION_1[..12]) == Err(VerifyError::NonMatchingCycle));
+
+	// proofs which can't be cycles at all
+	assert!(verify(&header, 16, &KNOWN_16_SOLUTION_1[..41]) == Err(VerifyError::WrongProofSize));
+	assert!(verify(&header, 16, &[0u32; 0]) == Err(VerifyError::WrongProofSize));
+}
+
+#[test]
+fn verify_rejects_short_cycles() {
+	// an 8-cycle and a 34-cycle from the same graph: all endpoints pair
+	// up, but following the cycle from the first edge only covers 8
+	let header = from_hex(KNOWN_16_HASH_1).unwrap();
+	let proof: [u32; 42] = [
+		165, 1085, 1675, 2255, 3231, 4314, 4716, 5527, 6664, 7328,
+		8037, 11374, 11590, 12131, 13283, 13377, 14971, 16120, 16532, 16665,
+		16850, 17437, 17840, 18046, 19495, 21104, 21562, 21648, 22604, 24342,
+		24458, 25718, 28570, 28805, 28826, 29257, 29383, 30145, 30783, 31299,
+		32171, 32227,
+	];
+	assert!(verify(&header, 16, &proof) == Err(VerifyError::TooShort));
+}
+
+#[test]
+fn siphash_keys_from_header() {
+	let keys = SipHashKeys::from_header(&from_hex(KNOWN_16_HASH_1).unwrap());
+	assert!(keys.siphash24(0) == 0xb6992576501c1480);
+	assert!(keys.siphash24(1) == 0xb65a5b9f40fdcae4);
+}
+
+#[test]
+fn verify_skips_cuckatoo_plugins() {
+	assert!(cuckoo::is_verifiable(cuckoo::GraphType::Cuckoo));
+	assert!(!cuckoo::is_verifiable(cuckoo::GraphType::Cuckatoo));
+	assert!(cuckoo::GraphType::default() == cuckoo::GraphType::Cuckoo);
+}
+
+// the derived keys for the known test hashes are pinned, so any drift in
+// the derivation shared by the verifier and keyed plugin calls shows up
+#[test]
+fn siphash_keys_pinned() {
+	let keys = siphash_keys(&from_hex(KNOWN_16_HASH_1).unwrap());
+	assert!(keys == [0xe38cb8e05ef9588d, 0x9a6e07fd05b1b307, 0xbe32e39fa1c86879, 0x6a8542a437c19722]);
+	assert!(SipHashKeys::from_header(&from_hex(KNOWN_16_HASH_1).unwrap()).k == keys);
+
+	let keys = siphash_keys(&from_hex(KNOWN_30_HASH_1).unwrap());
+	assert!(keys == [0xd9153083ff39f51f, 0x7b9ab66d48156e64, 0x571c8c166c66a13b, 0x9a51116e1161c7c5]);
+}